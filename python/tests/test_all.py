@@ -1,6 +1,1369 @@
+import array
+import asyncio
+import csv
+import gzip
+import io
+import json
+import math
+import os
+import sqlite3
+import subprocess
+import sys
+import threading
+import types
+
 import pytest
 import traderusty
 
 
-def test_sum_as_string():
-    assert traderusty.sum_as_string(1, 1) == "2"
+def test_count_file_lines(tmp_path):
+    path = tmp_path / "lines.txt"
+    path.write_bytes(b"one\ntwo\nthree\n")
+    assert traderusty.count_file_lines(str(path)) == 3
+    assert traderusty.count_file_lines(str(path), traderusty.ReadOptions(buffer_size=2)) == 3
+    options = traderusty.ReadOptions(buffer_size=1000, read_ahead=4096, hint_sequential=True, direct_io=True)
+    assert traderusty.count_file_lines(str(path), options) == 3
+    assert "direct_io=True" in repr(options) and "prefetch=False" in repr(options)
+    options.prefetch = True
+    assert traderusty.count_file_lines(str(path), options) == 3
+
+
+def test_path_types(tmp_path):
+    path = tmp_path / "lines.txt"
+    path.write_bytes(b"one\ntwo\n")
+    assert traderusty.count_file_lines(path) == 2
+    assert traderusty.count_file_lines(bytes(path)) == 2
+    assert traderusty.validate_utf8(path) is None
+    # a name that isn't valid UTF-8
+    raw = os.path.join(os.fsencode(tmp_path), b"caf\xe9.prices")
+    with open(raw, "wb") as f:
+        f.write(b"@ SOL/Abraham Lincoln\nGold 9500 9000\n")
+    assert traderusty.count_file_lines(raw) == 2
+    assert [record.item for record in traderusty.PricesReader(raw)] == ["Gold"]
+    assert traderusty.migrate_database(tmp_path / "cache.db") == 3
+    with pytest.raises(TypeError):
+        traderusty.count_file_lines(42)
+
+
+def test_count_file_lines_missing(tmp_path):
+    with pytest.raises(IOError):
+        traderusty.count_file_lines(str(tmp_path / "missing.txt"))
+
+
+def test_validate_utf8(tmp_path):
+    path = tmp_path / "utf8.txt"
+    path.write_bytes("Lavé\n".encode())
+    assert traderusty.validate_utf8(str(path)) is None
+    path.write_bytes(b"abc\xff")
+    assert traderusty.validate_utf8(str(path)) == 3
+
+
+def test_verify_files(tmp_path):
+    import hashlib
+    good, bad = tmp_path / "good.prices", tmp_path / "bad.prices"
+    good.write_bytes(b"@ SOL/Abraham Lincoln\n")
+    bad.write_bytes(b"truncated")
+    digest = hashlib.sha256(good.read_bytes()).hexdigest()
+    assert traderusty.hash_files([good, str(good)]) == [digest, digest]
+    assert traderusty.verify_files([good], [digest.upper()]) == []
+    mismatches = traderusty.verify_files([good, bad, tmp_path / "missing"], [digest] * 3)
+    assert [m.path for m in mismatches] == [str(bad), str(tmp_path / "missing")]
+    assert mismatches[0].actual == hashlib.sha256(b"truncated").hexdigest()
+    assert mismatches[1].actual is None and "missing" in mismatches[1].error
+    with pytest.raises(ValueError):
+        traderusty.verify_files([good], [])
+    with pytest.raises(IOError):
+        traderusty.hash_files([tmp_path / "missing"])
+
+
+def test_tail_lines(tmp_path):
+    path = tmp_path / "log.txt"
+    path.write_bytes(b"".join(b"line %d\n" % i for i in range(10000)))
+    assert traderusty.tail_lines(path, 2) == ["line 9998", "line 9999"]
+    assert traderusty.tail_lines(path, 0) == []
+    with pytest.raises(IOError):
+        traderusty.tail_lines(tmp_path / "missing.txt", 1)
+
+
+def test_rev_lines(tmp_path):
+    path = tmp_path / "market.prices"
+    path.write_bytes(b"@ SOL/Abraham Lincoln\r\nGold 9500 9000\r\n@ LAVE/Lave Station\r\nGold 9400 9100\r\n")
+    with traderusty.RevLines(path, block_size=8) as lines:
+        assert next(lines) == "Gold 9400 9100"
+        assert next(line for line in lines if line.startswith("@")) == "@ LAVE/Lave Station"
+    assert lines.closed
+    with pytest.raises(ValueError):
+        next(lines)
+    assert list(traderusty.RevLines(path))[-1] == "@ SOL/Abraham Lincoln"
+
+
+def test_read_line_at(tmp_path):
+    path = tmp_path / "log.txt"
+    path.write_bytes(b"".join(b"line %d\n" % i for i in range(10000)))
+    index = traderusty.LineIndex(path)
+    assert len(index) == 10000
+    assert index.offset(1) == 7
+    assert traderusty.read_line_at(path, index, 1234) == "line 1234"
+    assert traderusty.read_lines_at(path, index, 9998, 5) == ["line 9998", "line 9999"]
+    with pytest.raises(IndexError):
+        traderusty.read_line_at(path, index, 10000)
+    with open(path, "ab") as f:
+        f.write(b"line 10000\n")
+    with pytest.raises(IOError):
+        traderusty.read_line_at(path, index, 0)
+
+
+def test_line_index_sidecar(tmp_path):
+    path = tmp_path / "log.txt"
+    path.write_bytes(b"".join(b"line %d\n" % i for i in range(1000)))
+    assert traderusty.LineIndex.load(path) is None
+    assert not traderusty.LineIndex.open(path).mapped
+    assert (tmp_path / "log.txt.lineidx").exists()
+    index = traderusty.LineIndex.open(path)
+    assert index.mapped
+    assert traderusty.read_line_at(path, index, 999) == "line 999"
+    del index
+    with open(path, "ab") as f:
+        f.write(b"line 1000\n")
+    assert traderusty.LineIndex.load(path) is None
+    sidecar = tmp_path / "elsewhere.idx"
+    traderusty.LineIndex(path).save(path, sidecar)
+    assert len(traderusty.LineIndex.load(path, sidecar)) == 1001
+
+
+LENIENT = traderusty.ParseOptions(strictness="lenient")
+LENIENT_COMMA = traderusty.ParseOptions(strictness="lenient", decimal=",")
+
+
+def test_parse_supply_level():
+    assert traderusty.parse_supply_level("?") == (-1, -1)
+    assert traderusty.parse_supply_level("-") == (0, 0)
+    assert traderusty.parse_supply_level("1000L") == (1000, 1)
+    with pytest.raises(ValueError):
+        traderusty.parse_supply_level("x")
+    assert traderusty.parse_supply_level("300 M", LENIENT) == (300, 2)
+    assert traderusty.parse_supply_level("5000000000M") == (5000000000, 2)
+    assert traderusty.StationItem(1, 2, supply_units=5000000000).supply_units == 5000000000
+    with pytest.raises(traderusty.ParseError):
+        traderusty.parse_supply_level("300 M")
+    with pytest.raises(traderusty.ParseError, match="unit"):
+        traderusty.parse_supply_level("300é")
+
+
+def test_parse_number():
+    assert traderusty.parse_number("1234567") == 1234567
+    assert traderusty.parse_number("1,234,567", LENIENT) == 1234567
+    assert traderusty.parse_number("1_234_567", LENIENT) == 1234567
+    with pytest.raises(traderusty.ParseError):
+        traderusty.parse_number("1,234,567")
+    with pytest.raises(traderusty.ParseError, match="separator"):
+        traderusty.parse_number("12,34", LENIENT)
+    assert traderusty.parse_number("1.234.567", LENIENT_COMMA) == 1234567
+
+
+def test_parse_decimal():
+    assert traderusty.parse_decimal("12.5") == 12.5
+    assert traderusty.parse_decimal("12,5", traderusty.ParseOptions(decimal=",")) == 12.5
+    assert traderusty.parse_decimal("1.234,5", LENIENT_COMMA) == 1234.5
+    with pytest.raises(traderusty.ParseError):
+        traderusty.parse_decimal("12,5")
+    with pytest.raises(ValueError, match="decimal"):
+        traderusty.ParseOptions(decimal=";")
+
+
+def test_read_csv():
+    systems = "unq:name;pos_x;pos_y;pos_z\nLave;75,75;48,75;70,75\n"
+    comma = traderusty.ParseOptions(decimal=",")
+    assert traderusty.read_system_csv(systems, comma) == [traderusty.System("Lave", 75.75, 48.75, 70.75)]
+    with pytest.raises(traderusty.ParseError) as raised:
+        traderusty.read_system_csv(systems)
+    assert (raised.value.line, raised.value.column) == (2, 6)
+    stations = "unq:name@System.system_id,unq:name,ls_from_star\n'Lave','Lave Station','299,5'\n"
+    assert traderusty.read_station_csv(stations, comma) == [("Lave", "Lave Station", 299.5)]
+    with pytest.raises(traderusty.ParseError):
+        traderusty.read_station_csv(stations)
+
+
+def test_parse_options():
+    options = traderusty.ParseOptions()
+    assert (options.strictness, options.max_errors, options.comment_char, options.decimal) == ("strict", None, "#", ".")
+    options.strictness = "lenient"
+    assert options.strictness == "lenient"
+    assert "lenient" in repr(options)
+    with pytest.raises(ValueError, match="strictness"):
+        traderusty.ParseOptions(strictness="lax")
+
+
+def test_stellar_grid_key():
+    assert traderusty.stellar_grid_key(0.0, 0.0, 0.0) == 0
+    assert traderusty.stellar_grid_key(-1.0, -1.0, -1.0) == 0xFFFFFFFFFFFFFFFF
+
+
+def test_stellar_grid_keys():
+    positions = [(float(i) * 13.5 - 200.0, -float(i), float(i) * 70.25) for i in range(20)]
+    assert traderusty.stellar_grid_keys(positions) == [
+        traderusty.stellar_grid_key(*pos) for pos in positions
+    ]
+    assert traderusty.stellar_grid_keys([]) == []
+    # buffers, flat or shaped (n, 3)
+    flat = array.array("d", [c for pos in positions for c in pos])
+    assert traderusty.stellar_grid_keys(flat) == traderusty.stellar_grid_keys(positions)
+    shaped = memoryview(flat).cast("B").cast("d", (len(positions), 3))
+    assert traderusty.stellar_grid_keys(shaped) == traderusty.stellar_grid_keys(positions)
+    with pytest.raises(traderusty.SpatialError):
+        traderusty.stellar_grid_keys(array.array("d", [1.0, 2.0]))
+    with pytest.raises(traderusty.SpatialError):
+        traderusty.stellar_grid_keys(array.array("i", [1, 2, 3]))
+    # float32 buffers are keyed as float32, without a round trip through float64
+    single = array.array("f", flat)
+    assert traderusty.stellar_grid_keys(single) == [
+        traderusty.stellar_grid_key(*pos)
+        for pos in zip(single[0::3], single[1::3], single[2::3])
+    ]
+
+
+def test_level_grid_key():
+    fine = traderusty.level_grid_key(5.0, 40.0, -300.0, 0)
+    medium = traderusty.parent_grid_key(fine)
+    assert medium == traderusty.level_grid_key(5.0, 40.0, -300.0, 1)
+    coarse = traderusty.parent_grid_key(medium)
+    assert traderusty.parent_grid_key(coarse) is None
+    children = traderusty.child_grid_keys(coarse)
+    assert len(children) == 512 and medium in children
+    assert traderusty.child_grid_keys(fine) == []
+    with pytest.raises(traderusty.SpatialError):
+        traderusty.level_grid_key(0.0, 0.0, 0.0, 3)
+    # a stellar grid key below the plane isn't a level key
+    below = traderusty.stellar_grid_key(0.0, -100.0, 0.0)
+    with pytest.raises(traderusty.SpatialError):
+        traderusty.parent_grid_key(below)
+    with pytest.raises(traderusty.SpatialError):
+        traderusty.child_grid_keys(below)
+
+
+def test_sector_for():
+    sector_id, index, origin = traderusty.sector_for(0.0, 0.0, 0.0)
+    assert index == (39, 32, 18)
+    assert origin == (-65.0, -25.0, -1065.0)
+    assert traderusty.sector_for(100.0, 100.0, 100.0)[0] == sector_id
+    assert traderusty.sector_from_id(sector_id) == (index, origin)
+
+
+def test_id64():
+    sol = 10477373803
+    assert traderusty.id64_position(sol) == (-25.0, 15.0, 15.0)
+    assert traderusty.id64_for_position(0.0, 0.0, 0.0, "d") == sol
+    with pytest.raises(traderusty.SpatialError):
+        traderusty.id64_for_position(0.0, 0.0, 0.0, "z")
+    with pytest.raises(traderusty.SpatialError):
+        traderusty.id64_for_position(0.0, 90000.0, 0.0, "a")
+
+
+def test_procedural_name():
+    name = traderusty.procedural_name("Wregoe", 0.0, 0.0, 0.0, "c", 4)
+    assert name.startswith("Wregoe ")
+    assert name.split()[-1].startswith("c") and name.endswith("4")
+    origin = traderusty.procedural_boxel_origin(name[len("Wregoe "):], 0.0, 0.0, 0.0)
+    assert all(o <= 0.0 < o + 40.0 for o in origin)
+    with pytest.raises(ValueError):
+        traderusty.procedural_name("Wregoe", 0.0, 0.0, 0.0, "z")
+
+
+def test_diff_markets():
+    old = traderusty.MarketSnapshot(1, 100, [
+        traderusty.StationItem(1, 10, demand_price=100, supply_price=90),
+        traderusty.StationItem(1, 20, demand_price=200),
+    ])
+    new = traderusty.MarketSnapshot(1, 200, [
+        traderusty.StationItem(1, 10, demand_price=110, supply_price=90),
+        traderusty.StationItem(1, 30, supply_price=50),
+    ])
+    diff = traderusty.diff_markets(old, new)
+    assert [i.item_id for i in diff.added] == [30]
+    assert [i.item_id for i in diff.removed] == [20]
+    assert [(o.demand_price, n.demand_price) for o, n in diff.changed] == [(100, 110)]
+    assert not traderusty.diff_markets(new, new)
+
+
+def test_market_store():
+    store = traderusty.MarketStore()
+    store.add_station(1, "Sol", 0.0, 0.0, 0.0)
+    store.add_station(2, "Sol", 5.0, 5.0, 5.0)
+    store.insert(traderusty.StationItem(1, 10, supply_price=100))
+    store.insert(traderusty.StationItem(2, 10, demand_price=150, supply_price=90))
+    assert len(store) == 2
+    assert [i.station_id for i in store.sellers_of(10)] == [2, 1]
+    assert [i.station_id for i in store.buyers_of(10)] == [2]
+    assert store.stations_in(traderusty.stellar_grid_key(0.0, 0.0, 0.0)) == [1, 2]
+    assert store.stations_in_system("Sol") == [1, 2]
+    assert store.stations_in_sector(traderusty.sector_for(0.0, 0.0, 0.0)[0]) == [1, 2]
+    assert store.station_system(2) == "Sol"
+
+
+def test_canonical_commodity():
+    ltd = traderusty.canonical_commodity("lowtemperaturediamond")
+    assert ltd.name == "Low Temperature Diamonds"
+    assert traderusty.canonical_commodity("$lowtemperaturediamond_name;") == ltd
+    assert traderusty.canonical_commodity("LowTemperatureDiamond") == ltd
+    assert traderusty.canonical_commodity("low temperature diamonds") == ltd
+    assert traderusty.canonical_commodity("Unobtainium") is None
+    assert traderusty.commodity_by_id(ltd.id) == ltd
+    assert ltd in traderusty.commodities()
+    assert ltd.category == "Minerals"
+
+
+def test_commodity_categories():
+    assert "Legal Drugs" in traderusty.commodity_categories()
+    drugs = [c.name for c in traderusty.commodities("legal drugs")]
+    assert "Narcotics" in drugs and "Gold" not in drugs
+    with pytest.raises(ValueError, match="unknown commodity category"):
+        traderusty.commodities("Narcotics")
+
+    gold = traderusty.canonical_commodity("Gold").id
+    tea = traderusty.canonical_commodity("Tea").id
+    store = traderusty.MarketStore()
+    store.add_station(1, "Sol", 0.0, 0.0, 0.0)
+    store.insert(traderusty.StationItem(1, gold, supply_price=9000))
+    store.insert(traderusty.StationItem(1, tea, supply_price=1000))
+    assert [i.item_id for i in store.station_items_in(1, "Metals")] == [gold]
+    assert [i.item_id for i in store.category_listings("Foods")] == [tea]
+
+
+def test_name_index():
+    index = traderusty.NameIndex()
+    for id, name in enumerate(["Eravate", "Eranin", "Ereduba Erebus"]):
+        index.insert(id, name)
+    assert [m[1] for m in index.search("era")] == ["Eravate", "Eranin"]
+    assert [m[0] for m in index.search("ere dub")] == [2]
+    assert index.similar("eravat")[0][1] == "Eravate"
+    assert index.suggest("Erevate") == [(0, "Eravate", 1)]
+    index.insert(3, "WP 12")
+    assert [m[0] for m in index.search("ＷＰ\u3000１２")] == [3]
+
+
+def test_canonical_name():
+    assert traderusty.canonical_name("Wp  12") == traderusty.canonical_name("ＷＰ　１２") == "wp 12"
+    assert traderusty.canonical_name("Straße") == "strasse"
+
+
+def test_name_hash():
+    assert traderusty.name_hash("Sol") == traderusty.name_hash("  SOL ") == 0x9D68AD134D4BD030
+    assert traderusty.station_name_hash("Sol", "Abraham Lincoln") == traderusty.name_hash("sol/abraham lincoln")
+
+
+def test_interner():
+    interner = traderusty.Interner()
+    sol = interner.intern("Sol")
+    assert interner.intern("Sol") == sol
+    assert interner.resolve(sol) == "Sol"
+    assert interner.get("Lave") is None
+    assert len(interner) == 1
+
+
+def test_route_export():
+    route = traderusty.Route("trade")
+    route.add_hop("Sol", 0.0, 0.0, 0.0, station="Abraham Lincoln")
+    route.buy("Gold", 720, 9000)
+    route.add_hop("Lave", 3.0, 0.0, 4.0, station="Lave Station")
+    route.sell("Gold", 720, 10500)
+    doc = json.loads(route.to_json())
+    assert doc["kind"] == "trade"
+    assert doc["total_distance"] == 5.0
+    assert doc["hops"][1]["actions"] == [{"action": "sell", "item": "Gold", "units": 720, "price": 10500}]
+    assert route.system_list() == "Sol\nLave"
+    rows = list(csv.DictReader(io.StringIO(route.to_csv())))
+    assert rows[0]["station"] == "Abraham Lincoln"
+    assert rows[1]["actions"] == "sell 720 Gold @ 10500"
+    with pytest.raises(IndexError):
+        traderusty.Route().refuel()
+
+
+def test_region_map(tmp_path):
+    regions = [
+        {"name": "Inner Orion Spur", "polygon": [[-100, -100], [100, -100], [100, 100], [-100, 100]]},
+        {"name": "Empyrean Straits", "polygon": [[100, -100], [300, -100], [300, 100], [100, 100]]},
+    ]
+    path = tmp_path / "regions.json"
+    path.write_text(json.dumps(regions))
+    region_map = traderusty.RegionMap.load(str(path))
+    assert len(region_map) == 2
+    assert region_map.region_for(0.0, 5000.0, 0.0) == "Inner Orion Spur"
+    assert region_map.region_for(1000.0, 0.0, 0.0) is None
+
+    store = traderusty.MarketStore()
+    store.add_station(1, "Sol", 0.0, 0.0, 0.0)
+    store.add_station(2, "Far", 200.0, 0.0, 0.0)
+    assert region_map.group_stations(store) == {"Inner Orion Spur": [1], "Empyrean Straits": [2]}
+    with pytest.raises(ValueError):
+        traderusty.RegionMap.from_json("{}")
+    with pytest.raises(traderusty.ParseError) as error:
+        traderusty.RegionMap.from_json('[\n  {"name": "x", "polygon": 5}\n]')
+    assert (error.value.line, error.value.snippet) == (2, '  {"name": "x", "polygon": 5}')
+    with pytest.raises(IOError):
+        traderusty.RegionMap.load(str(tmp_path / "missing.json"))
+
+    galaxy = traderusty.RegionMap.galaxy()
+    assert len(galaxy) == 42
+    assert galaxy.region_for(0.0, 0.0, 0.0) == "Inner Orion Spur"
+    assert galaxy.region_for(25.21875, -20.90625, 25899.96875) == "Galactic Centre"
+
+
+def test_market_file(tmp_path):
+    market = {
+        "timestamp": "2024-05-01T12:00:00Z", "event": "Market", "MarketID": 128016640,
+        "StationName": "Abraham Lincoln", "StarSystem": "Sol",
+        "Items": [
+            {"Name": "$gold_name;", "BuyPrice": 9100, "SellPrice": 8900,
+             "StockBracket": 2, "DemandBracket": "", "Stock": 120, "Demand": 0},
+            {"Name": "$newthing_name;", "BuyPrice": 1, "SellPrice": 1,
+             "StockBracket": 1, "DemandBracket": 1, "Stock": 1, "Demand": 1},
+        ],
+    }
+    path = tmp_path / "Market.json"
+    path.write_text(json.dumps(market))
+    market_file = traderusty.MarketFile.load(path)
+    assert (market_file.station, market_file.system) == ("Abraham Lincoln", "Sol")
+    assert market_file.unknown_items == ["$newthing_name;"]
+    snapshot = market_file.snapshot
+    assert (snapshot.station_id, snapshot.timestamp) == (128016640, 1714564800)
+    gold = snapshot.get(traderusty.canonical_commodity("Gold").id)
+    assert (gold.supply_price, gold.supply_units, gold.supply_level) == (9100, 120, 2)
+
+    store = traderusty.MarketStore()
+    store.insert_snapshot(snapshot)
+    assert len(store) == 1
+    with pytest.raises(traderusty.ParseError, match="invalid timestamp") as error:
+        traderusty.MarketFile.from_json('{\n"timestamp": "soon"}')
+    assert error.value.line == 2
+    with pytest.raises(IOError):
+        traderusty.MarketFile.load(tmp_path / "missing.json")
+
+    # leniently, items that don't parse are dropped and counted
+    market["Items"][1]["DemandBracket"] = 9
+    text = json.dumps(market)
+    with pytest.raises(traderusty.ParseError, match="bracket"):
+        traderusty.MarketFile.from_json(text)
+    lenient = traderusty.ParseOptions(strictness="lenient")
+    market_file = traderusty.MarketFile.from_json(text, parse_options=lenient)
+    assert (market_file.skipped, market_file.unknown_items) == (1, [])
+    assert traderusty.MarketFile.load(path).skipped == 0
+
+
+def test_outfitting_and_shipyard_files(tmp_path):
+    header = {"timestamp": "2024-05-01T12:00:00Z", "MarketID": 128016640,
+              "StationName": "Abraham Lincoln", "StarSystem": "Sol"}
+    path = tmp_path / "Outfitting.json"
+    path.write_text(json.dumps(dict(header, event="Outfitting", Items=[
+        {"id": 128049382, "Name": "Hpt_PulseLaser_Fixed_Medium", "BuyPrice": 16731},
+    ])))
+    outfitting = traderusty.OutfittingFile.load(path)
+    assert (outfitting.market_id, outfitting.station) == (128016640, "Abraham Lincoln")
+    [module] = outfitting.modules
+    assert (module.id, module.symbol, module.price) == (128049382, "hpt_pulselaser_fixed_medium", 16731)
+
+    shipyard = traderusty.ShipyardFile.from_json(json.dumps(dict(header, event="Shipyard", PriceList=[
+        {"id": 128671223, "ShipType": "empire_courier", "ShipType_Localised": "Imperial Courier",
+         "ShipPrice": 2462010},
+        {"id": 128049249, "ShipType": "sidewinder", "ShipPrice": 27480},
+    ])))
+    assert shipyard.timestamp == 1714564800
+    assert [(s.symbol, s.name) for s in shipyard.ships] == [
+        ("empire_courier", "Imperial Courier"), ("sidewinder", None)]
+    with pytest.raises(traderusty.ParseError, match="PriceList"):
+        traderusty.ShipyardFile.from_json(json.dumps(header))
+
+
+def test_system():
+    sol = traderusty.System("Sol", 0.0, 0.0, 0.0, id64=10477373803)
+    centauri = traderusty.System("Alpha Centauri", 3.0, 0.0, 4.0)
+    assert sol.distance_to(centauri) == pytest.approx(5.0)
+    assert (sol.id64, centauri.id64) == (10477373803, None)
+    assert sol == traderusty.System("Sol", 0.0, 0.0, 0.0, 10477373803)
+
+
+def test_download(tmp_path):
+    if not hasattr(traderusty, "download"):
+        return  # built without the "download" feature
+    import http.server
+
+    data = bytes(range(256)) * 400
+
+    class Handler(http.server.BaseHTTPRequestHandler):
+        def do_GET(self):
+            start = int(self.headers.get("Range", "bytes=0-")[6:-1])
+            self.send_response(206 if start else 200)
+            if start:
+                self.send_header("Content-Range", "bytes %d-%d/%d" % (start, len(data) - 1, len(data)))
+            self.send_header("Content-Length", str(len(data) - start))
+            self.end_headers()
+            self.wfile.write(data[start:])
+
+        def log_message(self, *args):
+            pass
+
+    server = http.server.HTTPServer(("127.0.0.1", 0), Handler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    url = "http://127.0.0.1:%d/galaxy.json.gz" % server.server_port
+    dest = tmp_path / "galaxy.json.gz"
+    part = tmp_path / "galaxy.json.gz.part"
+    try:
+        def stop(done, total):
+            if done:
+                raise KeyboardInterrupt
+        with pytest.raises(KeyboardInterrupt):
+            traderusty.download(url, dest, progress=stop)
+        assert not dest.exists() and 0 < part.stat().st_size < len(data)
+
+        seen = []
+        assert traderusty.download(url, dest, progress=lambda done, total: seen.append((done, total))) == len(data)
+        assert dest.read_bytes() == data and not part.exists()
+        assert seen[0][0] > 0 and seen[-1] == (len(data), len(data))
+
+        if hasattr(traderusty, "download_async"):
+            async def fetch(path):
+                return await traderusty.download_async(url, path)
+            again = tmp_path / "again.json.gz"
+            assert asyncio.run(fetch(again)) == len(data)
+            assert again.read_bytes() == data
+    finally:
+        server.shutdown()
+
+
+def test_edsm_client(tmp_path):
+    if not hasattr(traderusty, "EdsmClient"):
+        return  # built without the "edsm" feature
+    import http.server
+
+    requests = []
+
+    class Handler(http.server.BaseHTTPRequestHandler):
+        def do_GET(self):
+            requests.append(self.path)
+            if "Sol" in self.path:
+                body = b'{"name": "Sol", "id64": 10477373803, "coords": {"x": 0, "y": 0, "z": 0}}'
+            else:
+                body = b"[]"
+            self.send_response(200)
+            self.send_header("Content-Length", str(len(body)))
+            self.end_headers()
+            self.wfile.write(body)
+
+        def log_message(self, *args):
+            pass
+
+    server = http.server.HTTPServer(("127.0.0.1", 0), Handler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    try:
+        client = traderusty.EdsmClient("http://127.0.0.1:%d" % server.server_port, interval=0.0)
+        assert client.system("Sol") == traderusty.System("Sol", 0.0, 0.0, 0.0, 10477373803)
+        assert client.system("Nowhere") is None
+        assert client.sphere(0.0, 0.0, 0.0, 10.0) == []
+        with pytest.raises(ValueError):
+            traderusty.EdsmClient(interval=-1.0)
+
+        cached = traderusty.EdsmClient("http://127.0.0.1:%d" % server.server_port, interval=0.0,
+                                       cache_dir=tmp_path / "edsm")
+        del requests[:]
+        assert cached.system("Sol") == cached.system("Sol")
+        assert len(requests) == 1
+
+        if hasattr(client, "system_async"):
+            async def lookups():
+                return await asyncio.gather(client.system_async("Sol"), client.systems_async(["Nowhere"]),
+                                            client.sphere_async(0.0, 0.0, 0.0, 10.0))
+            sol, nowhere, sphere = asyncio.run(lookups())
+            assert (sol, nowhere, sphere) == (traderusty.System("Sol", 0.0, 0.0, 0.0, 10477373803), [], [])
+    finally:
+        server.shutdown()
+
+
+def test_spansh_client():
+    if not hasattr(traderusty, "SpanshClient"):
+        return  # built without the "spansh" feature
+    import http.server
+
+    class Handler(http.server.BaseHTTPRequestHandler):
+        def do_POST(self):
+            query = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+            if self.path == "/api/systems/search":
+                results = [{"name": "Sol", "id64": 10477373803, "x": 0, "y": 0, "z": 0}]
+            else:
+                assert query["filters"]["system_name"]["value"] == ["Sol"]
+                results = [{"name": "Abraham Lincoln", "system_name": "Sol", "market_id": 128016640,
+                            "market_updated_at": "2024-05-01 12:00:00+00",
+                            "market": [{"commodity": "Gold", "buy_price": 9100, "sell_price": 8900,
+                                        "supply": 120, "demand": 0}]}]
+            body = json.dumps({"count": len(results), "results": results}).encode()
+            self.send_response(200)
+            self.send_header("Content-Length", str(len(body)))
+            self.end_headers()
+            self.wfile.write(body)
+
+        def log_message(self, *args):
+            pass
+
+    server = http.server.HTTPServer(("127.0.0.1", 0), Handler)
+    threading.Thread(target=server.serve_forever, daemon=True).start()
+    try:
+        client = traderusty.SpanshClient("http://127.0.0.1:%d" % server.server_port, backoff=0.0)
+        assert client.systems(["Sol"]) == [traderusty.System("Sol", 0.0, 0.0, 0.0, 10477373803)]
+        [market] = client.markets(["Sol"])
+        assert (market.station, market.snapshot.station_id) == ("Abraham Lincoln", 128016640)
+        assert len(market.snapshot) == 1
+
+        if hasattr(client, "markets_async"):
+            async def lookups():
+                return await asyncio.gather(client.systems_async(["Sol"]), client.markets_async(["Sol"]))
+            systems, [market] = asyncio.run(lookups())
+            assert systems == [traderusty.System("Sol", 0.0, 0.0, 0.0, 10477373803)]
+            assert market.snapshot.station_id == 128016640
+    finally:
+        server.shutdown()
+
+
+def test_snapshot_cache(tmp_path):
+    if not hasattr(traderusty, "zstd_compress"):
+        return  # built without the "zstd" feature
+    snapshots = [
+        traderusty.MarketSnapshot(128000000 + station, 1714564800, [
+            traderusty.StationItem(0, item, demand_price=item * 137 + station % 9,
+                                   supply_price=item * 131, supply_units=item * 100, supply_level=2)
+            for item in range(1, 41)])
+        for station in range(300)]
+    dictionary = traderusty.train_zstd_dictionary(
+        [traderusty.snapshot_record(s) for s in snapshots], max_size=16384)
+    assert 0 < len(dictionary) <= 16384
+    record = traderusty.snapshot_record(snapshots[0])
+    compressed = traderusty.zstd_compress(record, dictionary)
+    assert len(compressed) < len(traderusty.zstd_compress(record))
+    assert traderusty.zstd_decompress(compressed, dictionary) == record
+    with pytest.raises(ValueError):
+        traderusty.zstd_decompress(b"not zstd")
+
+    path = tmp_path / "markets.zst"
+    traderusty.write_snapshot_cache(path, snapshots[:5], dictionary)
+    read = traderusty.read_snapshot_cache(path, dictionary)
+    assert [s.station_id for s in read] == [s.station_id for s in snapshots[:5]]
+    assert read[0].items == snapshots[0].items
+    with pytest.raises(IOError):
+        traderusty.read_snapshot_cache(tmp_path / "missing.zst")
+
+
+def test_inara_batch():
+    gold = traderusty.canonical_commodity("Gold").id
+    snapshot = traderusty.MarketSnapshot(128016640, 1714564800, [
+        traderusty.StationItem(0, gold, demand_price=8900, supply_price=9100, supply_units=120, supply_level=2),
+    ])
+    batch = traderusty.InaraBatch("TradeDangerous", "1.0", "secret", commander_name="Jameson")
+    batch.add_market("setMarket", "Sol", "Abraham Lincoln", snapshot)
+    assert len(batch) == 1
+    document = json.loads(batch.to_json())
+    assert document["header"]["APIkey"] == "secret"
+    [event] = document["events"]
+    assert event["eventTimestamp"] == "2024-05-01T12:00:00Z"
+    assert event["eventData"]["marketID"] == 128016640
+    assert event["eventData"]["commodities"][0]["name"] == "gold"
+
+
+def test_eddn_messages():
+    header = {"timestamp": "2024-05-01T12:00:00Z", "MarketID": 128016640,
+              "StationName": "Abraham Lincoln", "StarSystem": "Sol"}
+    market = traderusty.MarketFile.from_json(json.dumps(dict(header, Items=[
+        {"Name": "$gold_name;", "BuyPrice": 9100, "SellPrice": 8900, "MeanPrice": 9000,
+         "StockBracket": 2, "DemandBracket": "", "Stock": 120, "Demand": 0},
+        {"Name": "$drones_name;", "BuyPrice": 101, "SellPrice": 101, "MeanPrice": 101,
+         "StockBracket": 3, "DemandBracket": 0, "Stock": 9999, "Demand": 0},
+    ])))
+    message = traderusty.eddn_commodity_message(market, "Jameson", "TradeDangerous", "1.0",
+                                                gateway_timestamp=1714564805)
+    assert message["$schemaRef"] == traderusty.EDDN_COMMODITY_SCHEMA
+    assert message["header"] == {"uploaderID": "Jameson", "softwareName": "TradeDangerous",
+                                 "softwareVersion": "1.0",
+                                 "gatewayTimestamp": "2024-05-01T12:00:05Z"}
+    body = message["message"]
+    assert (body["marketId"], body["horizons"], body["odyssey"]) == (128016640, True, True)
+    assert body["commodities"] == [{
+        "name": "gold", "meanPrice": 9000, "buyPrice": 9100, "stock": 120, "stockBracket": 2,
+        "sellPrice": 8900, "demand": 0, "demandBracket": ""}]
+
+    outfitting = traderusty.OutfittingFile.from_json(json.dumps(dict(header, Items=[
+        {"id": 1, "Name": "Hpt_PulseLaser_Fixed_Medium", "BuyPrice": 16731},
+        {"id": 2, "Name": "PaintJob_Sidewinder_Blue", "BuyPrice": 0},
+    ])))
+    message = traderusty.eddn_outfitting_message(outfitting, "Jameson", "TD", "1.0", odyssey=False)
+    assert "gatewayTimestamp" not in message["header"]
+    assert message["message"]["modules"] == ["hpt_pulselaser_fixed_medium"]
+    assert message["message"]["odyssey"] is False
+
+    shipyard = traderusty.ShipyardFile.from_json(json.dumps(dict(header, PriceList=[
+        {"id": 1, "ShipType": "sidewinder", "ShipPrice": 27480},
+    ])))
+    message = traderusty.eddn_shipyard_message(shipyard, "Jameson", "TD", "1.0")
+    assert message["$schemaRef"] == traderusty.EDDN_SHIPYARD_SCHEMA
+    assert message["message"]["ships"] == ["sidewinder"]
+
+
+def test_eddn_validator():
+    header = {"uploaderID": "Jameson", "softwareName": "TD", "softwareVersion": "1.0"}
+    message = {"$schemaRef": traderusty.EDDN_SHIPYARD_SCHEMA, "header": header,
+               "message": {"systemName": "Sol", "stationName": "Abraham Lincoln", "marketId": 1,
+                           "timestamp": "2024-05-01T12:00:00Z", "ships": ["sidewinder"]}}
+    validator = traderusty.EddnValidator()
+    assert validator.validate(message) == []
+    assert validator.validate(json.dumps(message)) == []
+    message["header"] = dict(header, softwareName="")
+    message["message"]["ships"] = [1]
+    assert validator.validate(message) == ["header.softwareName: is blank",
+                                           "message.ships[0]: expected a string"]
+    assert validator.validate("not json")[0].startswith("not JSON")
+    assert validator.counts == {traderusty.EDDN_SHIPYARD_SCHEMA: (2, 1), "": (0, 1)}
+    validator.reset()
+    assert validator.counts == {}
+
+
+def test_grid_stats():
+    positions = [(0.0, 0.0, 0.0), (1.0, 1.0, 1.0), (2.0, 2.0, 2.0), (100.0, 0.0, 0.0)]
+    stats = traderusty.grid_stats(positions)
+    assert stats["cells"] == 2
+    assert stats["histogram"] == [1, 1]
+    assert stats["max_occupancy"] == 3
+    assert stats["empty_ratio"] == 0.5
+    assert traderusty.grid_stats(positions, cell_size=1.0)["cells"] == 4
+    single = array.array("f", [c for pos in positions for c in pos])
+    assert traderusty.grid_stats(single) == stats
+    with pytest.raises(traderusty.SpatialError):
+        traderusty.grid_stats(positions, cell_size=0.0)
+
+
+def test_jump_graph():
+    graph = traderusty.JumpGraph([(0.0, 0.0, 0.0), (10.0, 0.0, 0.0), (20.0, 0.0, 0.0), (45.0, 0.0, 0.0)], 15.0)
+    assert len(graph) == 4
+    assert graph.edge_count == 4
+    positions = [(0.0, 0.0, 0.0), (10.0, 0.0, 0.0), (20.0, 0.0, 0.0), (45.0, 0.0, 0.0)]
+    assert traderusty.JumpGraph(positions, 15.0, threads=1).edge_count == 4
+    single = array.array("f", [c for pos in positions for c in pos])
+    assert traderusty.JumpGraph(single, 15.0).edge_count == 4
+    assert graph.neighbours(1) == [(0, 10.0), (2, 10.0)]
+    assert graph.neighbours(3) == []
+    assert graph.degree(0) == 1
+    stats = graph.stats
+    assert (stats["nodes"], stats["edges"], stats["max_degree"]) == (4, 4, 2)
+    assert stats["seconds"] >= 0.0
+
+
+
+def test_thread_count():
+    cores = traderusty.thread_count()
+    traderusty.set_thread_count(2)
+    try:
+        assert traderusty.thread_count() == 2
+    finally:
+        traderusty.set_thread_count(0)
+    assert traderusty.thread_count() == cores
+
+
+def test_metrics(tmp_path):
+    traderusty.reset_metrics()
+    path = tmp_path / "market.prices"
+    path.write_text("@ SOL/Abraham Lincoln\nGold 9500 9000\nSilver 5000 4800\n")
+    with traderusty.PricesReader(path) as reader:
+        assert len(list(reader)) == 2
+    traderusty.merge_prices_dir(tmp_path)
+    metrics = traderusty.get_metrics()
+    assert metrics["records_parsed"] >= 4
+    assert metrics["bytes_read"] >= 2 * path.stat().st_size
+    assert metrics["stages"]["merge_prices_dir"]["runs"] >= 1
+    assert metrics["stages"]["merge_prices_dir"]["seconds"] >= 0.0
+    traderusty.reset_metrics()
+    assert "merge_prices_dir" not in traderusty.get_metrics()["stages"]
+
+
+def test_tracing(tmp_path):
+    if not hasattr(traderusty, "enable_tracing"):
+        return
+
+    # A stand-in for the opentelemetry API that records the spans it's handed.
+    class Span:
+        def __init__(self, name, context, attributes):
+            self.name, self.parent, self.attributes = name, context, dict(attributes)
+            self.events, self.ended = [], False
+
+        def set_attributes(self, attributes):
+            self.attributes.update(attributes)
+
+        def add_event(self, name, attributes=None, timestamp=None):
+            self.events.append(name)
+
+        def end(self, end_time=None):
+            self.ended = end_time
+
+    class Tracer:
+        def __init__(self):
+            self.spans = []
+
+        def start_span(self, name, context=None, attributes=None, start_time=None):
+            span = Span(name, context, attributes or {})
+            span.started = start_time
+            self.spans.append(span)
+            return span
+
+    trace = types.ModuleType("opentelemetry.trace")
+    trace.set_span_in_context = lambda span: span
+    trace.get_current_span = lambda: None
+    package = types.ModuleType("opentelemetry")
+    package.trace = trace
+    saved = {name: sys.modules.get(name) for name in ("opentelemetry", "opentelemetry.trace")}
+    sys.modules.update({"opentelemetry": package, "opentelemetry.trace": trace})
+    tracer = Tracer()
+    try:
+        traderusty.enable_tracing(tracer=tracer)
+        (tmp_path / "market.prices").write_text("@ SOL/Abraham Lincoln\nGold 9500 9000\n")
+        traderusty.merge_prices_dir(tmp_path)
+        traderusty.disable_tracing()
+        traderusty.merge_prices_dir(tmp_path)
+    finally:
+        for name, module in saved.items():
+            if module is None:
+                sys.modules.pop(name, None)
+            else:
+                sys.modules[name] = module
+
+    spans = {span.name: span for span in tracer.spans}
+    assert len(tracer.spans) == 2
+    merge, read = spans["merge_prices_dir"], spans["read_file"]
+    assert merge.parent is None and read.parent is merge
+    assert merge.attributes["code.namespace"] == "traderusty_core::merge"
+    assert read.attributes["source"].endswith("market.prices")
+    assert merge.started <= read.started <= read.ended <= merge.ended
+
+def test_router_refuels():
+    graph = traderusty.JumpGraph([(i * 10.0, 0.0, 0.0) for i in range(4)], 15.0)
+    ship = traderusty.LinearFuel(2.0, 0.1, 2.0)
+    router = traderusty.Router(graph, ["K", "L", "M", "T"])
+    cost, waypoints = router.route(0, 3, ship)
+    assert [w[0] for w in waypoints] == [0, 1, 2, 3]
+    assert [w[2] for w in waypoints] == [False, False, True, False]
+    assert cost > 3.0
+
+    stranded = traderusty.Router(graph, ["K", "L", "Neutron Star", "T"])
+    assert stranded.route(0, 3, ship) is None
+    cancel = traderusty.CancelToken()
+    cancel.cancel()
+    assert router.route(0, 3, ship, cancel=cancel) is None
+
+
+def test_ship_jump_range():
+    ship = traderusty.Ship(280.0, 32.0, 1050.0, 5.0, fsd_class=5, fsd_rating="A")
+    assert ship.max_jump_range() == pytest.approx(39.476, abs=0.01)
+    assert ship.jump_range(boost=traderusty.NEUTRON_BOOST) == pytest.approx(4 * ship.max_jump_range())
+    assert ship.fuel_for_jump(ship.max_jump_range()) == pytest.approx(5.0)
+    assert ship.fuel_for_jump(100.0) is None
+    with pytest.raises(ValueError):
+        traderusty.Ship(280.0, 32.0, 1050.0, 5.0, fsd_class=9)
+
+    graph = traderusty.JumpGraph([(i * 30.0, 0.0, 0.0) for i in range(4)], ship.max_jump_range())
+    router = traderusty.Router(graph, ["K", "G", "M", "F"])
+    cost, waypoints = router.route(0, 3, ship)
+    assert len(waypoints) == 4
+
+
+def test_router_neutron_mode():
+    positions = [(0.0, 0.0, 0.0), (10.0, 0.0, 0.0), (50.0, 0.0, 0.0), (60.0, 0.0, 0.0)]
+    stars = ["G", "Neutron Star", "K", "M"]
+    graph = traderusty.JumpGraph(positions, 15.0, boosts=traderusty.neutron_boosts(stars))
+    ship = traderusty.LinearFuel(10.0, 0.1, 2.0)
+    assert traderusty.Router(graph, stars).route(0, 3, ship) is None
+    cost, waypoints = traderusty.Router(graph, stars, neutron=True).route(0, 3, ship)
+    assert [w[0] for w in waypoints] == [0, 1, 3]
+    assert [w[3] for w in waypoints] == [False, True, False]
+
+
+def test_trade_loops():
+    store = traderusty.MarketStore()
+    store.add_station(1, "A", 0.0, 0.0, 0.0)
+    store.add_station(2, "B", 10.0, 0.0, 0.0)
+    store.insert(traderusty.StationItem(1, 10, supply_price=100, supply_units=1000))
+    store.insert(traderusty.StationItem(2, 10, demand_price=300))
+    store.insert(traderusty.StationItem(2, 20, supply_price=50, supply_units=1000))
+    store.insert(traderusty.StationItem(1, 20, demand_price=120))
+
+    assert traderusty.best_load(store, 1, 2, 100, 1_000_000) == [(10, 100, 100, 300)]
+    assert traderusty.best_load(store, 1, 2, 100, 5_000) == [(10, 50, 100, 300)]
+
+    # limited supply of the best item: fill the rest of the hold with the next
+    store.insert(traderusty.StationItem(1, 10, supply_price=100, supply_units=30))
+    store.insert(traderusty.StationItem(1, 30, supply_price=100, supply_units=1000))
+    store.insert(traderusty.StationItem(2, 30, demand_price=150))
+    assert traderusty.best_load(store, 1, 2, 100, 1_000_000) == [(10, 30, 100, 300), (30, 70, 100, 150)]
+    store.insert(traderusty.StationItem(1, 10, supply_price=100, supply_units=1000))
+
+    loops = traderusty.find_trade_loops(store, 15.0, 100, 1_000_000)
+    assert len(loops) == 1
+    assert loops[0].stations == [1, 2]
+    assert loops[0].profit == 27_000
+    assert loops[0].loads == [[(10, 100, 100, 300)], [(20, 100, 50, 120)]]
+    assert traderusty.find_trade_loops(store, 5.0, 100, 1_000_000) == []
+
+    hits, misses, entries = store.cache_stats()
+    traderusty.find_trade_loops(store, 15.0, 100, 1_000_000)
+    assert store.cache_stats()[0] > hits
+    generation = store.generation
+    store.insert(traderusty.StationItem(2, 20, supply_price=10, supply_units=1000))
+    assert store.generation > generation
+    assert traderusty.find_trade_loops(store, 15.0, 100, 1_000_000)[0].profit == 31_000
+    store.clear_cache()
+    assert store.cache_stats() == (0, 0, 0)
+
+
+def test_write_station_items(tmp_path):
+    db_path = str(tmp_path / "TradeDangerous.db")
+    with sqlite3.connect(db_path) as conn:
+        conn.execute(
+            "CREATE TABLE StationItem (station_id INTEGER NOT NULL, item_id INTEGER NOT NULL,"
+            " demand_price INT NOT NULL, demand_units INT NOT NULL, demand_level INT NOT NULL,"
+            " supply_price INT NOT NULL, supply_units INT NOT NULL, supply_level INT NOT NULL,"
+            " modified DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP, from_live INTEGER DEFAULT 0 NOT NULL,"
+            " PRIMARY KEY (station_id, item_id)) WITHOUT ROWID"
+        )
+    items = [traderusty.StationItem(1, i, demand_price=i * 10, modified=1714521600) for i in range(1, 151)]
+    assert traderusty.write_station_items(db_path, items, batch_size=40) == 150
+    assert traderusty.write_station_items(db_path, [traderusty.StationItem(1, 5, demand_price=1)]) == 1
+    with sqlite3.connect(db_path) as conn:
+        assert conn.execute("SELECT COUNT(*) FROM StationItem").fetchone() == (150,)
+        assert conn.execute("SELECT demand_price, modified FROM StationItem WHERE item_id = 5").fetchone() == (
+            1,
+            "1970-01-01 00:00:00",
+        )
+    with pytest.raises(IOError):
+        traderusty.write_station_items(str(tmp_path / "missing.db"), items)
+    # validating writes nothing
+    assert traderusty.write_station_items(db_path, [traderusty.StationItem(1, 5, demand_price=2)], validate_only=True) == 1
+    with sqlite3.connect(db_path) as conn:
+        assert conn.execute("SELECT demand_price FROM StationItem WHERE item_id = 5").fetchone() == (1,)
+
+
+def test_migrate_database(tmp_path):
+    db_path = str(tmp_path / "cache.db")
+    assert traderusty.migrate_database(db_path) == 3
+    assert traderusty.migrate_database(db_path) == 3
+    items = [traderusty.StationItem(1, 1, demand_price=10)]
+    assert traderusty.write_station_items(db_path, items) == 1
+    with sqlite3.connect(db_path) as conn:
+        conn.execute("PRAGMA user_version = 99")
+    with pytest.raises(IOError):
+        traderusty.migrate_database(db_path)
+
+
+def test_synthetic_station_ids(tmp_path):
+    db_path = str(tmp_path / "cache.db")
+    traderusty.migrate_database(db_path)
+    carrier, other = traderusty.synthetic_station_ids(db_path, [("Sol", "Dora's Explorer"), ("Sol", "Galileo")])
+    assert carrier != other and carrier >= 0xF0000000
+    assert traderusty.synthetic_station_ids(db_path, [("SOL", "dora's explorer")]) == [carrier]
+
+
+
+def test_import_prices(tmp_path):
+    db_path = tmp_path / "cache.db"
+    traderusty.migrate_database(db_path)
+    path = tmp_path / "import.prices"
+    path.write_text("@ SOL/Abraham Lincoln\nGold 9500 9000\nSilver 5000 0\nTea x\n@ SOL/Nowhere\nGold 1 1\n")
+    stats = traderusty.import_prices(db_path, path, {("sol", "ABRAHAM LINCOLN"): 7}, batch_size=1, queue_depth=1)
+    assert (stats["records"], stats["written"], stats["unknown_stations"]) == (3, 2, 1)
+    assert len(stats["errors"]) == 1 and "line 4" in stats["errors"][0]
+    assert stats["queues"]["parsed"]["sent"] == 3
+    assert stats["queues"]["rows"]["capacity"] == 1
+    with sqlite3.connect(db_path) as conn:
+        assert conn.execute("SELECT COUNT(*) FROM StationItem WHERE station_id = 7").fetchone() == (2,)
+    with pytest.raises(traderusty.ParseError):
+        traderusty.import_prices(db_path, path, {}, parse_options=traderusty.ParseOptions(max_errors=0))
+    cancel = traderusty.CancelToken()
+    cancel.cancel()
+    assert cancel.cancelled
+    with pytest.raises(traderusty.ImportError_, match="cancelled"):
+        traderusty.import_prices(db_path, path, {}, batch_size=1, cancel=cancel)
+
+
+def test_import_prices_validate_only(tmp_path):
+    db_path = tmp_path / "cache.db"
+    path = tmp_path / "contributed.prices"
+    path.write_text("@ SOL/Abraham Lincoln\nGold 9500 9000\nSilver 0 0\nTea x\nCoffee 1\n@ SOL/Nowhere\nGold 1 1\n")
+    strict = traderusty.ParseOptions(max_errors=0)
+    stats = traderusty.import_prices(db_path, path, {("Sol", "Abraham Lincoln"): 7}, parse_options=strict, validate_only=True)
+    assert (stats["records"], stats["written"]) == (3, 2)
+    assert [error.split(",")[0] for error in stats["errors"]] == ["line 4", "line 5"]
+    assert len(stats["warnings"]) == 1 and stats["warnings"][0].startswith("line 3")
+    assert stats["unresolved"] == ["line 7: unknown station SOL/Nowhere"]
+    assert not db_path.exists()
+
+
+def test_import_prices_async(tmp_path):
+    if not hasattr(traderusty, "import_prices_async"):
+        return
+    db_path = tmp_path / "cache.db"
+    traderusty.migrate_database(db_path)
+    path = tmp_path / "import.prices"
+    path.write_text("@ SOL/Abraham Lincoln\nGold 9500 9000\nSilver 5000 0\n")
+    graph = traderusty.JumpGraph([(i * 10.0, 0.0, 0.0) for i in range(4)], 15.0)
+    router = traderusty.Router(graph, ["K", "K", "K", "K"])
+
+    async def main():
+        stats = await traderusty.import_prices_async(db_path, path, {("Sol", "Abraham Lincoln"): 7})
+        assert stats["written"] == 2
+        cost, waypoints = await router.route_async(0, 3, traderusty.LinearFuel(2.0, 0.1, 2.0))
+        assert [w[0] for w in waypoints] == [0, 1, 2, 3]
+        task = asyncio.ensure_future(traderusty.import_prices_async(db_path, path, {}))
+        task.cancel()
+        with pytest.raises(asyncio.CancelledError):
+            await task
+
+    asyncio.run(main())
+
+def test_listings_to_csv():
+    items = [
+        traderusty.StationItem(1, 5, supply_price=450, supply_units=100),
+        traderusty.StationItem(2, 5, demand_price=700),
+    ]
+    rows = list(csv.DictReader(io.StringIO(traderusty.listings_to_csv(items))))
+    assert [row["station_id"] for row in rows] == ["1", "2"]
+    assert rows[0]["supply_price"] == "450"
+    assert rows[1]["demand_price"] == "700"
+
+
+def test_prices_reader(tmp_path):
+    path = tmp_path / "market.prices"
+    path.write_text(
+        "@ SOL/Abraham Lincoln\n"
+        "   + Chemicals\n"
+        "      Hydrogen Fuel   105   110   2079L   28430H   2024-05-01 12:00:00\n"
+        "      Explosives      265     0       ?        -\n"
+        "      Broken          abc     0\n"
+        "      Water           300   280       -        -\n"
+    )
+    reader = traderusty.PricesReader(str(path), batch_size=1)
+    assert iter(reader) is reader
+    fuel = next(reader)
+    assert (fuel.system, fuel.station, fuel.category) == ("SOL", "Abraham Lincoln", "Chemicals")
+    assert (fuel.item, fuel.demand_price, fuel.supply_price) == ("Hydrogen Fuel", 105, 110)
+    assert (fuel.supply_units, fuel.supply_level) == (28430, 3)
+    assert fuel.modified == 1714564800
+    assert next(reader).modified is None
+    with pytest.raises(ValueError, match="line 5, column 7") as error:
+        next(reader)
+    assert (error.value.line, error.value.column) == (5, 7)
+    assert error.value.offset == path.read_text().index("Broken")
+    assert error.value.snippet == "      Broken          abc     0"
+    assert [record.item for record in reader] == ["Water"]
+    with pytest.raises(IOError):
+        traderusty.PricesReader(str(tmp_path / "missing.prices"))
+
+    with traderusty.PricesReader(str(path)) as reader:
+        assert next(reader).item == "Hydrogen Fuel"
+        assert not reader.closed
+    assert reader.closed
+    reader.close()
+    with pytest.raises(ValueError, match="closed"):
+        next(reader)
+    # the file isn't held open after the block
+    path.unlink()
+
+
+def test_prices_reader_lenient(tmp_path):
+    path = tmp_path / "edited.prices"
+    path.write_text("@ SOL/Abraham Lincoln\nGold 9500 9000 300 M 120 h\n")
+    with pytest.raises(traderusty.ParseError):
+        next(traderusty.PricesReader(path))
+    gold = next(traderusty.PricesReader(path, parse_options=LENIENT))
+    assert (gold.demand_units, gold.demand_level, gold.supply_units, gold.supply_level) == (300, 2, 120, 3)
+    path.write_text("@ SOL/Abraham Lincoln\nGold 9.500 9.000 1.234M -\n")
+    gold = next(traderusty.PricesReader(path, parse_options=LENIENT_COMMA))
+    assert (gold.demand_price, gold.demand_units) == (9500, 1234)
+
+
+def test_prices_reader_warnings(tmp_path):
+    path = tmp_path / "odd.prices"
+    path.write_text(
+        "@ SOL/Abraham Lincoln\n"
+        "Gold 9500 9000 - -\n"
+        "Unobtainium 1 2 - -\n"
+        "Tea 0 0 - - 2999-01-01 00:00:00\n"
+    )
+    reader = traderusty.PricesReader(path, batch_size=10, known_items=["Gold", "Tea"])
+    next(reader)
+    assert reader.warnings == []
+    next(reader)
+    [unknown] = reader.warnings
+    assert (unknown.kind, unknown.line, unknown.column) == ("unknown_item", 3, 1)
+    assert unknown.snippet == "Unobtainium 1 2 - -"
+    next(reader)
+    assert [w.kind for w in reader.warnings] == ["unknown_item", "future_timestamp", "zero_price"]
+    assert "line 4" in str(reader.warnings[1])
+    assert list(reader) == []
+
+
+def test_prices_reader_checkpoint(tmp_path):
+    path = tmp_path / "growing.prices"
+    path.write_text("@ SOL/Abraham Lincoln\n+ Metals\nGold 1 2\nSilver 3 4\n")
+    reader = traderusty.PricesReader(path)
+    next(reader)
+    # the checkpoint follows the records handed out, not the read-ahead
+    checkpoint = reader.checkpoint
+    assert (checkpoint.line, checkpoint.station, checkpoint.category) == (3, "Abraham Lincoln", "Metals")
+    assert list(reader) != []
+    end = reader.checkpoint
+    assert end.offset == path.stat().st_size
+
+    with path.open("a") as f:
+        f.write("Tea 5 6\n")
+    saved = traderusty.Checkpoint(end.offset, end.line, end.system, end.station, end.category)
+    assert saved == end
+    [tea] = traderusty.PricesReader(path, checkpoint=saved)
+    assert (tea.item, tea.line, tea.category) == ("Tea", 5, "Metals")
+    assert [r.item for r in traderusty.PricesReader(path, checkpoint=checkpoint)] == ["Silver", "Tea"]
+    assert "offset=" in repr(saved)
+
+
+def test_merge_prices_dir(tmp_path):
+    (tmp_path / "a.prices").write_text(
+        "@ SOL/Abraham Lincoln\nGold 100 0 - - 2024-05-01 12:00:00\nSilver 50 0 - - 2024-05-01 12:00:00\n"
+    )
+    (tmp_path / "b.prices").write_text("@ SOL/Abraham Lincoln\nGold 200 0 - - 2024-06-01 12:00:00\nTea x\n")
+    records, errors = traderusty.merge_prices_dir(tmp_path)
+    assert [(r.item, r.demand_price) for r in records] == [("Gold", 200), ("Silver", 50)]
+    assert len(errors) == 1 and "b.prices: line 3" in errors[0]
+    with pytest.raises(traderusty.ParseError, match="b.prices") as error:
+        traderusty.merge_prices_dir(tmp_path, parse_options=traderusty.ParseOptions(max_errors=0))
+    assert error.value.line == 3
+    with pytest.raises(IOError):
+        traderusty.merge_prices_dir(tmp_path / "missing")
+
+
+def test_gzip_prices(tmp_path):
+    compressed = gzip.compress(b"@ SOL/Abraham Lincoln\nGold 100 0\nSilver 50 0\n")
+    (tmp_path / "listings.prices").write_bytes(compressed)
+    with traderusty.PricesReader(tmp_path / "listings.prices") as reader:
+        assert [r.item for r in reader] == ["Gold", "Silver"]
+    (tmp_path / "listings.prices").write_bytes(compressed[:-5])
+    with pytest.raises(IOError, match=f"truncated at byte {len(compressed) - 5}"):
+        traderusty.merge_prices_dir(tmp_path)
+
+
+
+def test_memory_budget(tmp_path):
+    options = traderusty.ReadOptions(memory_budget=1)
+    assert options.memory_budget == 1 and "memory_budget=1" in repr(options)
+    for name, price in [("a", 100), ("b", 200)]:
+        (tmp_path / f"{name}.prices").write_text(f"@ SOL/A\nGold {price} 0\n@ SOL/{name}\nTea {price} 0\n")
+    records, errors = traderusty.merge_prices_dir(tmp_path, options)
+    assert [(r.station, r.demand_price) for r in records] == [("A", 200), ("a", 100), ("b", 200)]
+    assert not errors
+    with traderusty.PricesReader(tmp_path / "a.prices", options=options) as reader:
+        assert [r.item for r in reader] == ["Gold", "Tea"]
+    options.memory_budget = None
+    assert "memory_budget=None" in repr(options)
+
+def test_bloom_filter(tmp_path):
+    stations = traderusty.BloomFilter(100, 0.001)
+    stations.add_station("Sol", "Abraham Lincoln")
+    stations.add_market_id(128016640)
+    assert stations.contains_station("SOL", "abraham lincoln")
+    assert stations.contains_market_id(128016640) and not stations.contains_market_id(1)
+    assert len(stations) == 2
+    restored = traderusty.BloomFilter.from_bytes(stations.to_bytes())
+    assert restored.contains_station("Sol", "Abraham Lincoln")
+    with pytest.raises(ValueError):
+        traderusty.BloomFilter.from_bytes(b"nope")
+    with pytest.raises(ValueError):
+        traderusty.BloomFilter(100, 0.0)
+
+    path = tmp_path / "markets.prices"
+    path.write_text("@ SOL/Abraham Lincoln\nGold 1 2\n@ SOL/Daedalus\nGold 3 4\nSilver x\n")
+    with traderusty.PricesReader(path, stations=stations) as reader:
+        assert [r.station for r in reader] == ["Abraham Lincoln"]
+        assert reader.skipped == 2
+
+
+def test_summarize_prices(tmp_path):
+    path = tmp_path / "dump.prices"
+    path.write_text("@ SOL/Abraham Lincoln\nGold 1 2\nSilver 3 4\n"
+                    "@ sol/daedalus\nGOLD 1 2\nTea x\n@ LHS 3447/Bluford Orbital\nGold 5 6\n")
+    assert traderusty.summarize_prices(path) == {
+        "records": 4, "errors": 1, "warnings": 0,
+        "systems": 2, "stations": 3, "commodities": 2}
+    with traderusty.PricesReader(path, count_distinct=True) as reader:
+        assert reader.distinct == {"systems": 0, "stations": 0, "commodities": 0}
+        next(reader)
+        assert reader.distinct["stations"] >= 1
+    with traderusty.PricesReader(path) as reader:
+        assert reader.distinct is None
+    with pytest.raises(IOError):
+        traderusty.summarize_prices(tmp_path / "missing.prices")
+
+
+def test_diff_dumps(tmp_path):
+    old, new = tmp_path / "old.prices", tmp_path / "new.prices"
+    old.write_text("@ SOL/Abraham Lincoln\nGold 100 0 - - 2024-05-01 12:00:00\n"
+                   "@ SOL/Daedalus\nGold 110 0 - - 2024-05-01 12:00:00\n"
+                   "@ LHS 3447/Bluford Orbital\nGold 300 0 - - 2024-05-01 12:00:00\n")
+    new.write_text("@ SOL/Abraham Lincoln\nGold 105 0 - - 2024-06-01 12:00:00\n"
+                   "@ SOL/Daedalus\nGold 110 0 - - 2024-06-01 12:00:00\n"
+                   "@ LTT 1873/Wakata Station\nGold 130 0 - - 2024-06-01 12:00:00\nTea x\n")
+    changeset = traderusty.diff_dumps(old, new)
+    assert [(r.station, r.demand_price) for r in changeset.added] == [("Wakata Station", 130)]
+    assert [(r.station, r.demand_price) for r in changeset.changed] == [("Abraham Lincoln", 105)]
+    assert changeset.removed == [("LHS 3447", "Bluford Orbital")]
+    assert len(changeset.errors) == 1 and "new.prices: line 7" in changeset.errors[0]
+    assert changeset and not traderusty.diff_dumps(old, old)
+    with pytest.raises(traderusty.ParseError, match="new.prices"):
+        traderusty.diff_dumps(old, new, parse_options=traderusty.ParseOptions(max_errors=0))
+    with pytest.raises(IOError):
+        traderusty.diff_dumps(old, tmp_path / "missing.prices")
+
+
+def test_prices_reader_max_errors(tmp_path):
+    path = tmp_path / "broken.prices"
+    path.write_text("@ SOL/Abraham Lincoln\nGold x\n; a note\nSilver y\nPalladium 1 2 - -\n")
+    options = traderusty.ParseOptions(max_errors=1, comment_char=";")
+    reader = traderusty.PricesReader(path, parse_options=options)
+    with pytest.raises(traderusty.ParseError):
+        next(reader)
+    with pytest.raises(traderusty.ParseError):
+        next(reader)
+    assert list(reader) == []
+
+
+def test_exception_hierarchy():
+    for error in (traderusty.ParseError, traderusty.ImportError_, traderusty.RouteError, traderusty.SpatialError):
+        assert issubclass(error, traderusty.TradeRustyError)
+    assert issubclass(traderusty.TradeRustyError, Exception)
+    # each is also the builtin it replaced
+    for error in (traderusty.ParseError, traderusty.RouteError, traderusty.SpatialError):
+        assert issubclass(error, ValueError)
+    assert issubclass(traderusty.ImportError_, IOError)
+    assert traderusty.ParseError.__module__ == "traderusty"
+    with pytest.raises(traderusty.ParseError):
+        traderusty.parse_supply_level("x")
+    with pytest.raises(traderusty.SpatialError):
+        traderusty.procedural_name("Wregoe", 0.0, 0.0, 0.0, "z")
+    with pytest.raises(traderusty.TradeRustyError):
+        traderusty.Route("scenic")
+    with pytest.raises(traderusty.ParseError):
+        traderusty.procedural_boxel_origin("nonsense", 0.0, 0.0, 0.0)
+
+
+def test_market_store_threads():
+    store = traderusty.MarketStore()
+
+    def fill(station_id):
+        store.add_station(station_id, "Sol", 0.0, 0.0, 0.0)
+        for item_id in range(200):
+            store.insert(traderusty.StationItem(station_id, item_id, supply_price=item_id + 1))
+            store.sellers_of(item_id)
+
+    threads = [threading.Thread(target=fill, args=(station_id,)) for station_id in range(8)]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+    assert len(store) == 8 * 200
+    assert store.stations_in_system("Sol") == list(range(8))
+
+
+def test_column_store(tmp_path):
+    store = traderusty.MarketStore()
+    store.insert(traderusty.StationItem(2, 10, demand_price=150, supply_level=3, modified=1714564800))
+    store.insert(traderusty.StationItem(1, 20, supply_price=90))
+    store.insert(traderusty.StationItem(1, 10, supply_price=100))
+    path = tmp_path / "listings.cols"
+    assert traderusty.write_column_store(path, store) == 3
+    columns = traderusty.ColumnStore(path)
+    assert len(columns) == 3
+    assert columns.column("station_id") == [1, 1, 2]
+    assert columns.column("supply_level") == [0, 0, 3]
+    assert columns.row(2) == store.get(2, 10)
+    assert columns.get(1, 20) == store.get(1, 20)
+    assert columns.get(3, 10) is None
+    assert [item.item_id for item in columns.station_items(1)] == [10, 20]
+    with pytest.raises(IndexError):
+        columns.row(3)
+    with pytest.raises(ValueError):
+        columns.column("price")
+    with pytest.raises(OSError):
+        traderusty.ColumnStore(tmp_path / "missing.cols")
+
+
+def test_query():
+    store = traderusty.MarketStore()
+    store.add_station(1, "Sol", 0, 0, 0)
+    store.add_station(2, "Alpha Centauri", 3, 4, 0)
+    store.add_station(3, "Far Away", 300, 0, 0)
+    store.insert(traderusty.StationItem(1, 10, supply_price=100, modified=1714564800))
+    store.insert(traderusty.StationItem(2, 10, supply_price=90, demand_price=120, modified=1714564800))
+    store.insert(traderusty.StationItem(3, 10, supply_price=80, modified=1714564800))
+    store.insert(traderusty.StationItem(2, 20, demand_price=50, modified=1714000000))
+    near = traderusty.Query().side("buy").filter(10).within((0, 0, 0), 10)
+    found = near.order_by("profit").run(store)
+    assert found["station_id"] == [2, 1]
+    assert found["supply_price"] == [90, 100]
+    assert found["distance"] == [5.0, 0.0]
+    assert near.order_by("distance").limit(1).run(store)["station_id"] == [1]
+    fresh = traderusty.Query().station(2).now(1714564800).max_age(1).run(store)
+    assert fresh["item_id"] == [10]
+    assert fresh["distance"] == []
+    assert traderusty.Query().side("sell").run(store)["demand_price"] == [120, 50]
+    with pytest.raises(ValueError):
+        traderusty.Query().side("trade")
+    with pytest.raises(ValueError):
+        traderusty.Query().order_by("price")
+    with pytest.raises(ValueError, match="radius"):
+        traderusty.Query().within((0, 0, 0), -1)
+    assert traderusty.Query().within((0, 0, 0), 50_000).run(store)["station_id"] == [1, 2, 2, 3]
+
+
+def test_aggregate_prices(tmp_path):
+    path = tmp_path / "listings.prices"
+    path.write_text(
+        "@ SOL/Abraham Lincoln\n"
+        "   + Metals\n"
+        "      Gold                 9000    8500          0?     100M\n"
+        "@ WOLF 359/Lomas Orbital\n"
+        "   + Metals\n"
+        "      Gold                 9400       0          0?       -\n"
+    )
+    gold = traderusty.canonical_commodity("gold").id
+    galaxy = traderusty.aggregate_prices(path)
+    assert galaxy["region"] == [None]
+    assert galaxy["item_id"] == [gold]
+    assert galaxy["sell_mean"] == [9200.0]
+    assert (galaxy["buy_count"], galaxy["buy_min"], galaxy["buy_max"]) == ([1], [8500], [8500])
+
+    bubble = {"name": "Bubble", "polygon": [[-50, -50], [50, -50], [50, 50], [-50, 50]]}
+    regions = traderusty.RegionMap.from_json(json.dumps([bubble]))
+    by_region = traderusty.aggregate_prices(path, regions, {"SOL": (0.0, 0.0, 0.0)})
+    assert by_region["region"] == [None, "Bubble"]
+    assert by_region["sell_mean"] == [9200.0, 9000.0]
+
+    store = traderusty.MarketStore()
+    store.add_station(1, "Sol", 0, 0, 0)
+    store.insert(traderusty.StationItem(1, gold, demand_price=9000))
+    aggregates = traderusty.aggregate_store(store, regions)
+    assert aggregates["region"] == [None, "Bubble"]
+    assert aggregates["buy_count"] == [0, 0]
+    assert math.isnan(aggregates["buy_mean"][0])
+
+
+def test_stdin():
+    script = (
+        "import traderusty\n"
+        "print(traderusty.count_file_lines('-'))\n"
+    )
+    env = dict(os.environ, PYTHONPATH=os.pathsep.join(sys.path))
+    result = subprocess.run([sys.executable, "-c", script], input=b"a\nb\nc\n", capture_output=True, env=env)
+    assert result.stdout.strip() == b"3", result.stderr
+    script = (
+        "import traderusty\n"
+        "print(','.join(record.item for record in traderusty.PricesReader('-')))\n"
+    )
+    prices = b"@ SOL/Abraham Lincoln\nGold 9500 9000\nSilver 5000 4800\n"
+    result = subprocess.run([sys.executable, "-c", script], input=prices, capture_output=True, env=env)
+    assert result.stdout.strip() == b"Gold,Silver", result.stderr
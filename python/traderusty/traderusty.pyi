@@ -1,4 +1,596 @@
 import os
+from typing import Any, Awaitable, Callable, Dict, List, Optional, Tuple, Union
 
-def count_file_lines(path: os.PathLike) -> int: ...
+StrPath = Union[str, bytes, os.PathLike]
+# A list of (x, y, z) tuples, or a float64/float32 buffer (numpy array,
+# array.array, ...) shaped (n, 3) or flat.
+Positions = Union[List[Tuple[float, float, float]], Any]
+
+class TradeRustyError(Exception): ...
+class ParseError(TradeRustyError, ValueError):
+    # Set when the error is about a file or document.
+    offset: int
+    line: int
+    column: int
+    snippet: str
+
+class ImportError_(TradeRustyError, IOError): ...
+class RouteError(TradeRustyError, ValueError): ...
+class SpatialError(TradeRustyError, ValueError): ...
+
+def enable_logging(level: int = 20) -> None: ...
+def disable_logging() -> None: ...
+# Only when built with the "opentelemetry" feature.
+def enable_tracing(level: int = 20, tracer: Optional[Any] = None) -> None: ...
+def disable_tracing() -> None: ...
+def flush_tracing() -> None: ...
+
+class ReadOptions:
+    buffer_size: int
+    read_ahead: int
+    hint_sequential: bool
+    direct_io: bool
+    prefetch: bool
+    memory_budget: Optional[int]
+    def __init__(
+        self,
+        buffer_size: int = 131072,
+        read_ahead: int = 0,
+        hint_sequential: bool = False,
+        direct_io: bool = False,
+        prefetch: bool = False,
+        memory_budget: Optional[int] = None,
+    ) -> None: ...
+
+class ParseOptions:
+    strictness: str
+    max_errors: Optional[int]
+    comment_char: str
+    decimal: str
+    def __init__(
+        self,
+        strictness: str = "strict",
+        max_errors: Optional[int] = None,
+        comment_char: str = "#",
+        decimal: str = ".",
+    ) -> None: ...
+
+def count_file_lines(path: StrPath, options: Optional[ReadOptions] = None) -> int: ...
+def validate_utf8(path: StrPath, options: Optional[ReadOptions] = None) -> Optional[int]: ...
+def tail_lines(path: StrPath, n: int) -> List[str]: ...
+
+class FileMismatch:
+    path: str
+    expected: str
+    actual: Optional[str]
+    error: Optional[str]
+
+def get_metrics() -> Dict[str, Any]: ...
+def reset_metrics() -> None: ...
+def set_thread_count(threads: int) -> None: ...
+def thread_count() -> int: ...
+def hash_files(paths: List[StrPath], options: Optional[ReadOptions] = None, threads: Optional[int] = None) -> List[str]: ...
+def verify_files(
+    paths: List[StrPath],
+    expected_hashes: List[str],
+    options: Optional[ReadOptions] = None,
+    threads: Optional[int] = None,
+) -> List[FileMismatch]: ...
+def read_line_at(path: StrPath, index: LineIndex, n: int) -> str: ...
+def read_lines_at(path: StrPath, index: LineIndex, start: int, count: int) -> List[str]: ...
+def parse_supply_level(reading: str, options: Optional[ParseOptions] = None) -> Tuple[int, int]: ...
+def parse_number(text: str, options: Optional[ParseOptions] = None) -> int: ...
+def parse_decimal(text: str, options: Optional[ParseOptions] = None) -> float: ...
+def stellar_grid_key(x: float, y: float, z: float) -> int: ...
+def stellar_grid_keys(positions: Positions) -> List[int]: ...
+def level_grid_key(x: float, y: float, z: float, level: int) -> int: ...
+def parent_grid_key(key: int) -> Optional[int]: ...
+def child_grid_keys(key: int) -> List[int]: ...
+def sector_for(x: float, y: float, z: float) -> Tuple[int, Tuple[int, int, int], Tuple[float, float, float]]: ...
+def sector_from_id(sector_id: int) -> Tuple[Tuple[int, int, int], Tuple[float, float, float]]: ...
+def procedural_name(sector_name: str, x: float, y: float, z: float, mass_code: str, n2: int = 0) -> str: ...
+def procedural_boxel_origin(suffix: str, x: float, y: float, z: float) -> Tuple[float, float, float]: ...
+def id64_position(id64: int) -> Tuple[float, float, float]: ...
+def id64_for_position(x: float, y: float, z: float, mass_code: str, n2: int = 0) -> int: ...
+
+class PriceRecord:
+    system: str
+    station: str
+    category: str
+    item: str
+    demand_price: int
+    demand_units: int
+    demand_level: int
+    supply_price: int
+    supply_units: int
+    supply_level: int
+    modified: Optional[int]
+    line: int
+
+class ParseWarning:
+    kind: str
+    message: str
+    offset: int
+    line: int
+    column: int
+    snippet: str
+
+class Checkpoint:
+    offset: int
+    line: int
+    system: str
+    station: str
+    category: str
+    def __init__(
+        self,
+        offset: int = 0,
+        line: int = 0,
+        system: str = "",
+        station: str = "",
+        category: str = "",
+    ) -> None: ...
+
+class BloomFilter:
+    def __init__(self, expected: int, false_positive_rate: float = 0.01) -> None: ...
+    def add_station(self, system: str, station: str) -> None: ...
+    def add_market_id(self, market_id: int) -> None: ...
+    def contains_station(self, system: str, station: str) -> bool: ...
+    def contains_market_id(self, market_id: int) -> bool: ...
+    def to_bytes(self) -> bytes: ...
+    @staticmethod
+    def from_bytes(data: bytes) -> "BloomFilter": ...
+    def __len__(self) -> int: ...
+
+class PricesReader:
+    line: int
+    warnings: List[ParseWarning]
+    checkpoint: Checkpoint
+    def __init__(
+        self,
+        path: StrPath,
+        batch_size: int = 1000,
+        options: Optional[ReadOptions] = None,
+        parse_options: Optional[ParseOptions] = None,
+        known_items: Optional[List[str]] = None,
+        checkpoint: Optional[Checkpoint] = None,
+        stations: Optional[BloomFilter] = None,
+        count_distinct: bool = False,
+    ) -> None: ...
+    skipped: int
+    distinct: Optional[Dict[str, int]]
+    closed: bool
+    def close(self) -> None: ...
+    def __enter__(self) -> "PricesReader": ...
+    def __exit__(self, exc_type: object, exc_value: object, traceback: object) -> bool: ...
+    def __iter__(self) -> "PricesReader": ...
+    def __next__(self) -> PriceRecord: ...
+
+def merge_prices_dir(
+    path: StrPath,
+    options: Optional[ReadOptions] = None,
+    parse_options: Optional[ParseOptions] = None,
+    threads: Optional[int] = None,
+) -> Tuple[List[PriceRecord], List[str]]: ...
+
+def summarize_prices(
+    path: StrPath,
+    options: Optional[ReadOptions] = None,
+    parse_options: Optional[ParseOptions] = None,
+) -> Dict[str, int]: ...
+
+class DumpChangeset:
+    added: List[PriceRecord]
+    changed: List[PriceRecord]
+    removed: List[Tuple[str, str]]
+    errors: List[str]
+    def __bool__(self) -> bool: ...
+
+def diff_dumps(
+    old: StrPath,
+    new: StrPath,
+    options: Optional[ReadOptions] = None,
+    parse_options: Optional[ParseOptions] = None,
+    threads: Optional[int] = None,
+) -> DumpChangeset: ...
+
+class RevLines:
+    def __init__(self, path: StrPath, block_size: int = 65536) -> None: ...
+    closed: bool
+    def close(self) -> None: ...
+    def __enter__(self) -> "RevLines": ...
+    def __exit__(self, exc_type: object, exc_value: object, traceback: object) -> bool: ...
+    def __iter__(self) -> "RevLines": ...
+    def __next__(self) -> str: ...
+
+class LineIndex:
+    def __init__(self, path: StrPath, options: Optional[ReadOptions] = None) -> None: ...
+    @staticmethod
+    def open(path: StrPath, index_path: Optional[StrPath] = None, options: Optional[ReadOptions] = None) -> "LineIndex": ...
+    @staticmethod
+    def load(path: StrPath, index_path: Optional[StrPath] = None) -> Optional["LineIndex"]: ...
+    def save(self, path: StrPath, index_path: Optional[StrPath] = None) -> None: ...
+    file_len: int
+    mapped: bool
+    def offset(self, n: int) -> int: ...
+    def __len__(self) -> int: ...
+
+class StationItem:
+    station_id: int
+    item_id: int
+    demand_price: int
+    demand_units: int
+    demand_level: int
+    supply_price: int
+    supply_units: int
+    supply_level: int
+    modified: int
+    def __init__(
+        self, station_id: int, item_id: int,
+        demand_price: int = 0, demand_units: int = 0, demand_level: int = 0,
+        supply_price: int = 0, supply_units: int = 0, supply_level: int = 0,
+        modified: int = 0,
+    ) -> None: ...
+
+class MarketSnapshot:
+    station_id: int
+    timestamp: int
+    items: List[StationItem]
+    def __init__(self, station_id: int, timestamp: int, items: List[StationItem]) -> None: ...
+    def get(self, item_id: int) -> Optional[StationItem]: ...
+    def __len__(self) -> int: ...
+
+class MarketDiff:
+    added: List[StationItem]
+    removed: List[StationItem]
+    changed: List[Tuple[StationItem, StationItem]]
+    def __bool__(self) -> bool: ...
+
+def diff_markets(old: MarketSnapshot, new: MarketSnapshot) -> MarketDiff: ...
+def listings_to_csv(items: List[StationItem]) -> str: ...
+
+class Commodity:
+    id: int
+    symbol: str
+    name: str
+    category: str
+
+def canonical_commodity(name: str) -> Optional[Commodity]: ...
+def commodity_by_id(id: int) -> Optional[Commodity]: ...
+def commodities(category: Optional[str] = None) -> List[Commodity]: ...
+def commodity_categories() -> List[str]: ...
+
+class MarketStore:
+    generation: int
+    def __init__(self) -> None: ...
+    def add_station(self, station_id: int, system: str, x: float, y: float, z: float) -> None: ...
+    def station_system(self, station_id: int) -> Optional[str]: ...
+    def stations_in_system(self, system: str) -> List[int]: ...
+    def insert(self, item: StationItem) -> None: ...
+    def insert_snapshot(self, snapshot: MarketSnapshot) -> None: ...
+    def remove(self, station_id: int, item_id: int) -> Optional[StationItem]: ...
+    def remove_market(self, station_id: int) -> int: ...
+    def get(self, station_id: int, item_id: int) -> Optional[StationItem]: ...
+    def station_items(self, station_id: int) -> List[StationItem]: ...
+    def station_items_in(self, station_id: int, category: str) -> List[StationItem]: ...
+    def category_listings(self, category: str) -> List[StationItem]: ...
+    def sellers_of(self, item_id: int) -> List[StationItem]: ...
+    def buyers_of(self, item_id: int) -> List[StationItem]: ...
+    def stations_in(self, grid_key: int) -> List[int]: ...
+    def stations_in_sector(self, sector_id: int) -> List[int]: ...
+    def cache_stats(self) -> Tuple[int, int, int]: ...
+    def clear_cache(self) -> None: ...
+    def __len__(self) -> int: ...
+
+class ColumnStore:
+    def __init__(self, path: StrPath) -> None: ...
+    def row(self, idx: int) -> StationItem: ...
+    def get(self, station_id: int, item_id: int) -> Optional[StationItem]: ...
+    def station_items(self, station_id: int) -> List[StationItem]: ...
+    def column(self, name: str) -> List[int]: ...
+    def __len__(self) -> int: ...
+
+def write_column_store(path: StrPath, store: MarketStore) -> int: ...
+
+class Query:
+    def __init__(self) -> None: ...
+    def filter(self, item_id: int) -> Query: ...
+    def station(self, station_id: int) -> Query: ...
+    def side(self, side: str) -> Query: ...
+    def within(self, origin: Tuple[float, float, float], ly: float) -> Query: ...
+    def max_age(self, days: float) -> Query: ...
+    def now(self, now: int) -> Query: ...
+    def order_by(self, order: str) -> Query: ...
+    def limit(self, n: int) -> Query: ...
+    def run(self, store: MarketStore) -> Dict[str, List[Union[int, float]]]: ...
+
+DEFAULT_BATCH_SIZE: int
+
+def write_station_items(
+    db_path: StrPath, items: List[StationItem], batch_size: int = DEFAULT_BATCH_SIZE, validate_only: bool = False
+) -> int: ...
+def migrate_database(db_path: StrPath) -> int: ...
+def synthetic_station_ids(db_path: StrPath, stations: List[Tuple[str, str]]) -> List[int]: ...
+
+DEFAULT_QUEUE_DEPTH: int
+
+def import_prices(
+    db_path: StrPath,
+    path: StrPath,
+    stations: Dict[Tuple[str, str], int],
+    batch_size: int = DEFAULT_BATCH_SIZE,
+    queue_depth: int = DEFAULT_QUEUE_DEPTH,
+    options: Optional[ReadOptions] = None,
+    parse_options: Optional[ParseOptions] = None,
+    cancel: Optional[CancelToken] = None,
+    validate_only: bool = False,
+) -> Dict[str, Any]: ...
+
+class CancelToken:
+    cancelled: bool
+    def __init__(self) -> None: ...
+    def cancel(self) -> None: ...
+
+def canonical_name(name: str) -> str: ...
+def name_hash(name: str) -> int: ...
+def station_name_hash(system: str, station: str) -> int: ...
+
+class NameIndex:
+    def __init__(self) -> None: ...
+    def insert(self, id: int, name: str) -> None: ...
+    def search(self, query: str, limit: int = 50) -> List[Tuple[int, str, float]]: ...
+    def similar(self, query: str, limit: int = 10, threshold: float = 0.3) -> List[Tuple[int, str, float]]: ...
+    def suggest(self, name: str, max_distance: int = 2, limit: int = 5) -> List[Tuple[int, str, int]]: ...
+    def __len__(self) -> int: ...
+
+class Interner:
+    def __init__(self) -> None: ...
+    def intern(self, name: str) -> int: ...
+    def get(self, name: str) -> Optional[int]: ...
+    def resolve(self, id: int) -> Optional[str]: ...
+    def __len__(self) -> int: ...
+
+class Route:
+    jumps: int
+    total_distance: float
+    def __init__(self, kind: str = "nav") -> None: ...
+    def add_hop(self, system: str, x: float, y: float, z: float, station: Optional[str] = None) -> None: ...
+    def refuel(self) -> None: ...
+    def buy(self, item: str, units: int, price: int) -> None: ...
+    def sell(self, item: str, units: int, price: int) -> None: ...
+    def to_json(self, indent: Optional[int] = None) -> str: ...
+    def to_csv(self) -> str: ...
+    def system_list(self) -> str: ...
+    def __len__(self) -> int: ...
+
+class MarketFile:
+    station: str
+    system: str
+    snapshot: MarketSnapshot
+    unknown_items: List[str]
+    skipped: int
+    @staticmethod
+    def load(path: StrPath, parse_options: Optional[ParseOptions] = None) -> "MarketFile": ...
+    @staticmethod
+    def from_json(json: str, parse_options: Optional[ParseOptions] = None) -> "MarketFile": ...
+
+class ModuleListing:
+    id: int
+    symbol: str
+    price: int
+
+class OutfittingFile:
+    market_id: int
+    timestamp: int
+    station: str
+    system: str
+    modules: List[ModuleListing]
+    skipped: int
+    @staticmethod
+    def load(path: StrPath, parse_options: Optional[ParseOptions] = None) -> "OutfittingFile": ...
+    @staticmethod
+    def from_json(json: str, parse_options: Optional[ParseOptions] = None) -> "OutfittingFile": ...
+
+class ShipListing:
+    id: int
+    symbol: str
+    name: Optional[str]
+    price: int
+
+class ShipyardFile:
+    market_id: int
+    timestamp: int
+    station: str
+    system: str
+    ships: List[ShipListing]
+    skipped: int
+    @staticmethod
+    def load(path: StrPath, parse_options: Optional[ParseOptions] = None) -> "ShipyardFile": ...
+    @staticmethod
+    def from_json(json: str, parse_options: Optional[ParseOptions] = None) -> "ShipyardFile": ...
+
+class System:
+    name: str
+    id64: Optional[int]
+    x: float
+    y: float
+    z: float
+    def __init__(self, name: str, x: float, y: float, z: float, id64: Optional[int] = None) -> None: ...
+    def distance_to(self, other: System) -> float: ...
+
+def read_system_csv(text: str, parse_options: Optional[ParseOptions] = None) -> List[System]: ...
+def read_station_csv(text: str, parse_options: Optional[ParseOptions] = None) -> List[Tuple[str, str, float]]: ...
+
+# Only when built with the "download" feature.
+def download(url: str, dest: StrPath, progress: Optional[Callable[[int, Optional[int]], Any]] = None, timeout: float = 30.0, retries: int = 5, backoff: float = 2.0, max_backoff: float = 300.0, jitter: float = 0.25, on_retry: Optional[Callable[[int, float, str], Any]] = None) -> int: ...
+# Only when built with the "download" and "asyncio" features.
+def download_async(url: str, dest: StrPath, progress: Optional[Callable[[int, Optional[int]], Any]] = None, timeout: float = 30.0, retries: int = 5, backoff: float = 2.0, max_backoff: float = 300.0, jitter: float = 0.25, on_retry: Optional[Callable[[int, float, str], Any]] = None) -> Awaitable[int]: ...
+
+# Only when built with the "edsm" feature.
+class EdsmClient:
+    def __init__(self, base_url: str = "https://www.edsm.net", interval: float = 10.0, timeout: float = 30.0, retries: int = 3, backoff: float = 1.0, max_backoff: float = 300.0, jitter: float = 0.25, on_retry: Optional[Callable[[int, float, str], Any]] = None, cache_dir: Optional[StrPath] = None, cache_ttl: float = 86400.0) -> None: ...
+    def system(self, name: str) -> Optional[System]: ...
+    def systems(self, names: List[str]) -> List[System]: ...
+    def sphere(self, x: float, y: float, z: float, radius: float, min_radius: float = 0.0) -> List[System]: ...
+    # Only when also built with the "asyncio" feature.
+    def system_async(self, name: str) -> Awaitable[Optional[System]]: ...
+    def systems_async(self, names: List[str]) -> Awaitable[List[System]]: ...
+    def sphere_async(self, x: float, y: float, z: float, radius: float, min_radius: float = 0.0) -> Awaitable[List[System]]: ...
+
+# Only when built with the "spansh" feature.
+class SpanshClient:
+    def __init__(self, base_url: str = "https://spansh.co.uk", timeout: float = 30.0, retries: int = 3, backoff: float = 1.0, max_backoff: float = 300.0, jitter: float = 0.25, on_retry: Optional[Callable[[int, float, str], Any]] = None) -> None: ...
+    def systems(self, names: List[str]) -> List[System]: ...
+    def systems_near(self, x: float, y: float, z: float, radius: float, limit: int = 100) -> List[System]: ...
+    def markets(self, systems: List[str], limit: int = 1000) -> List[MarketFile]: ...
+    # Only when also built with the "asyncio" feature.
+    def systems_async(self, names: List[str]) -> Awaitable[List[System]]: ...
+    def systems_near_async(self, x: float, y: float, z: float, radius: float, limit: int = 100) -> Awaitable[List[System]]: ...
+    def markets_async(self, systems: List[str], limit: int = 1000) -> Awaitable[List[MarketFile]]: ...
+
+# Only when built with the "zstd" feature.
+def train_zstd_dictionary(samples: List[bytes], max_size: int = 112640) -> bytes: ...
+def snapshot_record(snapshot: MarketSnapshot) -> bytes: ...
+def zstd_compress(data: bytes, dictionary: Optional[bytes] = None, level: int = 3) -> bytes: ...
+def zstd_decompress(data: bytes, dictionary: Optional[bytes] = None) -> bytes: ...
+def write_snapshot_cache(path: StrPath, snapshots: List[MarketSnapshot], dictionary: Optional[bytes] = None, level: int = 3) -> None: ...
+def read_snapshot_cache(path: StrPath, dictionary: Optional[bytes] = None) -> List[MarketSnapshot]: ...
+
+# Only when built with the "asyncio" feature.
+def import_prices_async(
+    db_path: StrPath,
+    path: StrPath,
+    stations: Dict[Tuple[str, str], int],
+    batch_size: int = DEFAULT_BATCH_SIZE,
+    queue_depth: int = DEFAULT_QUEUE_DEPTH,
+    options: Optional[ReadOptions] = None,
+    parse_options: Optional[ParseOptions] = None,
+    validate_only: bool = False,
+) -> Awaitable[Dict[str, Any]]: ...
+
+class InaraBatch:
+    def __init__(self, app_name: str, app_version: str, api_key: str, commander_name: Optional[str] = None, commander_frontier_id: Optional[str] = None, is_being_developed: bool = False) -> None: ...
+    def add_market(self, event_name: str, system: str, station: str, snapshot: MarketSnapshot) -> None: ...
+    def to_json(self, indent: Optional[int] = None) -> str: ...
+    def __len__(self) -> int: ...
+
+EDDN_COMMODITY_SCHEMA: str
+EDDN_OUTFITTING_SCHEMA: str
+EDDN_SHIPYARD_SCHEMA: str
+
+def eddn_commodity_message(market: MarketFile, uploader_id: str, software_name: str, software_version: str, horizons: bool = True, odyssey: bool = True, gateway_timestamp: Optional[int] = None) -> Dict[str, Any]: ...
+def eddn_outfitting_message(outfitting: OutfittingFile, uploader_id: str, software_name: str, software_version: str, horizons: bool = True, odyssey: bool = True, gateway_timestamp: Optional[int] = None) -> Dict[str, Any]: ...
+def eddn_shipyard_message(shipyard: ShipyardFile, uploader_id: str, software_name: str, software_version: str, horizons: bool = True, odyssey: bool = True, gateway_timestamp: Optional[int] = None) -> Dict[str, Any]: ...
+
+class EddnValidator:
+    # {schema: (accepted, rejected)}; messages naming no known schema count under "".
+    counts: Dict[str, Tuple[int, int]]
+    def __init__(self) -> None: ...
+    def validate(self, message: Union[str, Dict[str, Any]]) -> List[str]: ...
+    def reset(self) -> None: ...
+
+class RegionMap:
+    names: List[str]
+    @staticmethod
+    def galaxy() -> "RegionMap": ...
+    @staticmethod
+    def load(path: StrPath) -> "RegionMap": ...
+    @staticmethod
+    def from_json(json: str) -> "RegionMap": ...
+    def region_for(self, x: float, y: float, z: float) -> Optional[str]: ...
+    def group_stations(self, store: MarketStore) -> Dict[str, List[int]]: ...
+    def __len__(self) -> int: ...
+
+def aggregate_prices(
+    path: StrPath,
+    regions: Optional[RegionMap] = None,
+    positions: Optional[Dict[str, Tuple[float, float, float]]] = None,
+    options: Optional[ReadOptions] = None,
+    parse_options: Optional[ParseOptions] = None,
+) -> Dict[str, List[Any]]: ...
+def aggregate_store(store: MarketStore, regions: Optional[RegionMap] = None) -> Dict[str, List[Any]]: ...
+
+def grid_stats(positions: Positions, cell_size: float = 32.0) -> Dict[str, Any]: ...
+
+class JumpGraph:
+    jump_range: float
+    node_count: int
+    edge_count: int
+    stats: Dict[str, float]
+    def __init__(
+        self,
+        positions: Positions,
+        jump_range: float,
+        boosts: Optional[List[float]] = None,
+        threads: Optional[int] = None,
+    ) -> None: ...
+    def neighbours(self, node: int) -> List[Tuple[int, float]]: ...
+    def degree(self, node: int) -> int: ...
+    def __len__(self) -> int: ...
+
+class LinearFuel:
+    def __init__(self, tank: float, fuel_per_ly: float, max_fuel_per_jump: float) -> None: ...
+
+NEUTRON_BOOST: float
+WHITE_DWARF_BOOST: float
+
+def neutron_boosts(star_classes: List[str]) -> List[float]: ...
+
+class Ship:
+    def __init__(
+        self,
+        unladen_mass: float,
+        tank: float,
+        optimal_mass: float,
+        max_fuel_per_jump: float,
+        fsd_class: int = 5,
+        fsd_rating: str = "A",
+        cargo: float = 0.0,
+        guardian_boost: float = 0.0,
+    ) -> None: ...
+    def jump_range(self, fuel: Optional[float] = None, boost: float = 1.0) -> float: ...
+    def max_jump_range(self) -> float: ...
+    def fuel_for_jump(self, distance: float, fuel: Optional[float] = None) -> Optional[float]: ...
+
+class Router:
+    def __init__(
+        self,
+        graph: JumpGraph,
+        star_classes: List[str],
+        jump_cost: float = 1.0,
+        fuel_cost: float = 0.1,
+        refuel_cost: float = 1.0,
+        neutron: bool = False,
+    ) -> None: ...
+    def route(
+        self,
+        start: int,
+        goal: int,
+        fuel_model: Union[LinearFuel, Ship],
+        fuel: Optional[float] = None,
+        cancel: Optional[CancelToken] = None,
+    ) -> Optional[Tuple[float, List[Tuple[int, float, bool, bool]]]]: ...
+    # Only when built with the "asyncio" feature.
+    def route_async(
+        self, start: int, goal: int, fuel_model: Union[LinearFuel, Ship], fuel: Optional[float] = None
+    ) -> Awaitable[Optional[Tuple[float, List[Tuple[int, float, bool, bool]]]]]: ...
+
+class TradeLoop:
+    stations: List[int]
+    profit: int
+    profit_per_leg: float
+    loads: List[List[Tuple[int, int, int, int]]]
+    def __len__(self) -> int: ...
+
+def best_load(
+    store: MarketStore, from_station: int, to_station: int, capacity: int, credits: int
+) -> List[Tuple[int, int, int, int]]: ...
+def find_trade_loops(
+    store: MarketStore,
+    max_distance: float,
+    capacity: int,
+    credits: int,
+    max_legs: int = 2,
+    limit: int = 10,
+    threads: Optional[int] = None,
+) -> List[TradeLoop]: ...
 
@@ -0,0 +1,695 @@
+use std::path::PathBuf;
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+use std::time::Duration;
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+use traderusty_core::http::RetryPolicy;
+use traderusty_core::options::{self, DecimalSeparator, ParseOptions, ReadOptions, Strictness};
+use traderusty_core::{multigrid, rusty, sector, transforms};
+
+mod pyaggregate;
+#[cfg(feature = "asyncio")]
+mod pyasync;
+mod pybloom;
+mod pycancel;
+mod pycolstore;
+#[cfg(feature = "zstd")]
+mod pycompress;
+mod pydb;
+#[cfg(feature = "download")]
+mod pydownload;
+mod pyeddn;
+#[cfg(feature = "edsm")]
+mod pyedsm;
+mod pyerrors;
+mod pyinara;
+mod pyjournal;
+mod pylines;
+mod pylogging;
+mod pymarket;
+mod pymetrics;
+mod pynames;
+#[cfg(feature = "opentelemetry")]
+mod pyotel;
+mod pypool;
+mod pyprices;
+mod pyquery;
+mod pyregion;
+mod pyroute;
+#[cfg(feature = "spansh")]
+mod pyspansh;
+mod pysystem;
+mod pytrade;
+mod pyverify;
+
+use pyerrors::{ParseError, SpatialError};
+
+/// Tunables for the file-based functions: read size and OS hints.
+#[pyclass(name = "ReadOptions")]
+#[derive(Clone, Default)]
+pub struct PyReadOptions {
+    inner: ReadOptions,
+}
+
+#[pymethods]
+impl PyReadOptions {
+    #[new]
+    #[pyo3(signature = (
+        buffer_size=options::DEFAULT_BUFFER_SIZE, read_ahead=0, hint_sequential=false, direct_io=false,
+        prefetch=false, memory_budget=None,
+    ))]
+    fn new(
+        buffer_size: usize,
+        read_ahead: usize,
+        hint_sequential: bool,
+        direct_io: bool,
+        prefetch: bool,
+        memory_budget: Option<usize>,
+    ) -> Self {
+        Self {
+            inner: ReadOptions {
+                buffer_size,
+                read_ahead,
+                hint_sequential,
+                direct_io,
+                prefetch,
+                memory_budget,
+            },
+        }
+    }
+
+    #[getter]
+    fn buffer_size(&self) -> usize {
+        self.inner.buffer_size
+    }
+
+    #[setter]
+    fn set_buffer_size(&mut self, value: usize) {
+        self.inner.buffer_size = value;
+    }
+
+    #[getter]
+    fn read_ahead(&self) -> usize {
+        self.inner.read_ahead
+    }
+
+    #[setter]
+    fn set_read_ahead(&mut self, value: usize) {
+        self.inner.read_ahead = value;
+    }
+
+    #[getter]
+    fn hint_sequential(&self) -> bool {
+        self.inner.hint_sequential
+    }
+
+    #[setter]
+    fn set_hint_sequential(&mut self, value: bool) {
+        self.inner.hint_sequential = value;
+    }
+
+    #[getter]
+    fn direct_io(&self) -> bool {
+        self.inner.direct_io
+    }
+
+    #[setter]
+    fn set_direct_io(&mut self, value: bool) {
+        self.inner.direct_io = value;
+    }
+
+    #[getter]
+    fn prefetch(&self) -> bool {
+        self.inner.prefetch
+    }
+
+    #[setter]
+    fn set_prefetch(&mut self, value: bool) {
+        self.inner.prefetch = value;
+    }
+
+    /// Rough cap in bytes on the records an import holds in memory, or None.
+    #[getter]
+    fn memory_budget(&self) -> Option<usize> {
+        self.inner.memory_budget
+    }
+
+    #[setter]
+    fn set_memory_budget(&mut self, value: Option<usize>) {
+        self.inner.memory_budget = value;
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ReadOptions(buffer_size={}, read_ahead={}, hint_sequential={}, direct_io={}, prefetch={}, memory_budget={})",
+            self.inner.buffer_size,
+            self.inner.read_ahead,
+            py_bool(self.inner.hint_sequential),
+            py_bool(self.inner.direct_io),
+            py_bool(self.inner.prefetch),
+            self.inner
+                .memory_budget
+                .map_or_else(|| "None".to_string(), |budget| budget.to_string()),
+        )
+    }
+}
+
+fn py_bool(value: bool) -> &'static str {
+    if value {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+/// Resolves the optional options argument of the file-based functions.
+pub fn read_options(options: Option<PyRef<'_, PyReadOptions>>) -> ReadOptions {
+    options.map(|o| o.inner.clone()).unwrap_or_default()
+}
+
+/// Settings shared by the parsers: strictness ("strict" or "lenient"), how
+/// many malformed records to tolerate before giving up (None for any
+/// number), the comment character and the decimal separator ("." or ","),
+/// which decides how parse_decimal and the System.csv and Station.csv
+/// readers read fractions and which thousands separator lenient whole
+/// numbers may have.
+#[pyclass(name = "ParseOptions")]
+#[derive(Clone, Default)]
+pub struct PyParseOptions {
+    inner: ParseOptions,
+}
+
+#[pymethods]
+impl PyParseOptions {
+    #[new]
+    #[pyo3(signature = (
+        strictness=PyStrictness(Strictness::Strict), max_errors=None, comment_char='#',
+        decimal=PyDecimalSeparator(DecimalSeparator::Point),
+    ))]
+    fn new(
+        strictness: PyStrictness,
+        max_errors: Option<usize>,
+        comment_char: char,
+        decimal: PyDecimalSeparator,
+    ) -> Self {
+        Self {
+            inner: ParseOptions {
+                strictness: strictness.0,
+                max_errors,
+                comment_char,
+                decimal: decimal.0,
+            },
+        }
+    }
+
+    #[getter]
+    fn strictness(&self) -> &'static str {
+        strictness_name(self.inner.strictness)
+    }
+
+    #[setter]
+    fn set_strictness(&mut self, value: PyStrictness) {
+        self.inner.strictness = value.0;
+    }
+
+    #[getter]
+    fn max_errors(&self) -> Option<usize> {
+        self.inner.max_errors
+    }
+
+    #[setter]
+    fn set_max_errors(&mut self, value: Option<usize>) {
+        self.inner.max_errors = value;
+    }
+
+    #[getter]
+    fn comment_char(&self) -> char {
+        self.inner.comment_char
+    }
+
+    #[setter]
+    fn set_comment_char(&mut self, value: char) {
+        self.inner.comment_char = value;
+    }
+
+    #[getter]
+    fn decimal(&self) -> char {
+        self.inner.decimal.decimal()
+    }
+
+    #[setter]
+    fn set_decimal(&mut self, value: PyDecimalSeparator) {
+        self.inner.decimal = value.0;
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ParseOptions(strictness={:?}, max_errors={}, comment_char={:?}, decimal={:?})",
+            strictness_name(self.inner.strictness),
+            self.inner
+                .max_errors
+                .map_or_else(|| "None".to_string(), |max| max.to_string()),
+            self.inner.comment_char.to_string(),
+            self.inner.decimal.decimal().to_string(),
+        )
+    }
+}
+
+fn strictness_name(strictness: Strictness) -> &'static str {
+    match strictness {
+        Strictness::Strict => "strict",
+        Strictness::Lenient => "lenient",
+    }
+}
+
+/// Resolves the optional parse options argument of the parsers.
+pub fn parse_options(options: Option<PyRef<'_, PyParseOptions>>) -> ParseOptions {
+    options.map(|o| o.inner.clone()).unwrap_or_default()
+}
+
+/// Strictness from Python: "strict" or "lenient".
+pub struct PyStrictness(pub Strictness);
+
+impl<'py> FromPyObject<'py> for PyStrictness {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match ob.extract::<String>()?.as_str() {
+            "strict" => Ok(Self(Strictness::Strict)),
+            "lenient" => Ok(Self(Strictness::Lenient)),
+            _ => Err(PyValueError::new_err(
+                "strictness must be 'strict' or 'lenient'",
+            )),
+        }
+    }
+}
+
+/// A filesystem path from Python: a str, bytes or any os.PathLike. Goes
+/// through os.fsdecode so names that aren't valid UTF-8 survive intact.
+pub struct FsPath(pub PathBuf);
+
+impl<'py> FromPyObject<'py> for FsPath {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let decoded = ob.py().import("os")?.call_method1("fsdecode", (ob,))?;
+        Ok(Self(decoded.extract()?))
+    }
+}
+
+/// A decimal separator from Python: "." or ",".
+pub struct PyDecimalSeparator(pub DecimalSeparator);
+
+impl<'py> FromPyObject<'py> for PyDecimalSeparator {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        ob.extract::<char>()
+            .ok()
+            .and_then(DecimalSeparator::from_char)
+            .map(Self)
+            .ok_or_else(|| PyValueError::new_err("decimal must be '.' or ','"))
+    }
+}
+
+/// (x, y, z) positions from Python: a list of tuples, or a buffer of
+/// float64s or float32s (a numpy array, array.array, ...) shaped (n, 3) or
+/// flat. float32 buffers stay float32.
+pub enum Positions {
+    F64(Vec<[f64; 3]>),
+    F32(Vec<[f32; 3]>),
+}
+
+impl Positions {
+    /// The positions as f64, widening float32 exactly.
+    pub fn into_f64(self) -> Vec<[f64; 3]> {
+        match self {
+            Self::F64(positions) => positions,
+            Self::F32(positions) => positions
+                .into_iter()
+                .map(|pos| pos.map(f64::from))
+                .collect(),
+        }
+    }
+}
+
+impl<'py> FromPyObject<'py> for Positions {
+    fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let view = match ob
+            .py()
+            .import("builtins")?
+            .getattr("memoryview")?
+            .call1((ob,))
+        {
+            Ok(view) => view,
+            Err(_) => {
+                let positions: Vec<(f64, f64, f64)> = ob.extract()?;
+                return Ok(Self::F64(
+                    positions.into_iter().map(|(x, y, z)| [x, y, z]).collect(),
+                ));
+            }
+        };
+        let format: String = view.getattr("format")?.extract()?;
+        // native byte order: no prefix, '@', '=' or the platform's own
+        let native = if cfg!(target_endian = "little") {
+            '<'
+        } else {
+            '>'
+        };
+        let code = match format.as_bytes() {
+            [code] => Some(*code as char),
+            [order, code] if matches!(*order as char, '@' | '=') || *order as char == native => {
+                Some(*code as char)
+            }
+            _ => None,
+        };
+        if !matches!(code, Some('d' | 'f')) {
+            return Err(SpatialError::new_err(format!(
+                "positions must be float64 or float32, not {:?}",
+                format
+            )));
+        }
+        let shape: Vec<usize> = view.getattr("shape")?.extract()?;
+        if !matches!(shape.as_slice(), [n] if n % 3 == 0) && !matches!(shape.as_slice(), [_, 3]) {
+            return Err(SpatialError::new_err(format!(
+                "positions must have shape (n, 3), not {:?}",
+                shape
+            )));
+        }
+        let bytes: Vec<u8> = view.call_method0("tobytes")?.extract()?;
+        Ok(if code == Some('d') {
+            Self::F64(
+                bytes
+                    .chunks_exact(24)
+                    .map(|pos| {
+                        [0, 8, 16].map(|i| f64::from_ne_bytes(pos[i..i + 8].try_into().unwrap()))
+                    })
+                    .collect(),
+            )
+        } else {
+            Self::F32(
+                bytes
+                    .chunks_exact(12)
+                    .map(|pos| {
+                        [0, 4, 8].map(|i| f32::from_ne_bytes(pos[i..i + 4].try_into().unwrap()))
+                    })
+                    .collect(),
+            )
+        })
+    }
+}
+
+/// A duration from Python, in seconds.
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+pub fn seconds(name: &str, seconds: f64) -> PyResult<Duration> {
+    Duration::try_from_secs_f64(seconds).map_err(|_| {
+        PyValueError::new_err(format!("{} must be a non-negative number of seconds", name))
+    })
+}
+
+/// The RetryPolicy of the network bindings' retry arguments. on_retry is
+/// called with the retry's number, its wait in seconds and the error before
+/// each retry; an exception from it is reported as unraisable and doesn't
+/// stop the retries.
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+pub fn retry_policy(
+    retries: usize,
+    backoff: f64,
+    max_backoff: f64,
+    jitter: f64,
+    on_retry: Option<Py<PyAny>>,
+) -> PyResult<RetryPolicy> {
+    if !(0. ..=1.).contains(&jitter) {
+        return Err(PyValueError::new_err("jitter must be between 0 and 1"));
+    }
+    let mut policy = RetryPolicy::new(retries, seconds("backoff", backoff)?)
+        .max_backoff(seconds("max_backoff", max_backoff)?)
+        .jitter(jitter);
+    if let Some(callback) = on_retry {
+        policy = policy.on_retry(move |retry| {
+            Python::with_gil(|py| {
+                let args = (
+                    retry.attempt,
+                    retry.delay.as_secs_f64(),
+                    retry.error.to_string(),
+                );
+                if let Err(e) = callback.call1(py, args) {
+                    e.write_unraisable(py, None);
+                }
+            })
+        });
+    }
+    Ok(policy)
+}
+
+/// Returns the number of lines in a given file, or in standard input if the
+/// path is "-".
+#[pyfunction]
+#[pyo3(signature = (path, options=None))]
+fn count_file_lines(path: FsPath, options: Option<PyRef<'_, PyReadOptions>>) -> PyResult<usize> {
+    rusty::count_file_lines_with(path.0, &read_options(options))
+        .map_err(|e| PyIOError::new_err(format!("{}", e)))
+}
+
+/// Checks a file is valid UTF-8, returning the byte offset of the first invalid
+/// sequence or None if the file is clean.
+#[pyfunction]
+#[pyo3(signature = (path, options=None))]
+fn validate_utf8(path: FsPath, options: Option<PyRef<'_, PyReadOptions>>) -> PyResult<Option<u64>> {
+    rusty::validate_utf8_with(path.0, &read_options(options))
+        .map_err(|e| PyIOError::new_err(format!("{}", e)))
+}
+
+/// Parses a supply level string into a tuple of ints (units, level).
+/// Leniently, whitespace around the reading and before the level suffix
+/// (" 300M", "300 M") is ignored, as are thousands separators in the units.
+#[pyfunction]
+#[pyo3(signature = (reading, options=None))]
+fn parse_supply_level(
+    reading: &str,
+    options: Option<PyRef<'_, PyParseOptions>>,
+) -> PyResult<(i64, i32)> {
+    let options = parse_options(options);
+    let parsed = if options.lenient() {
+        rusty::parse_supply_level_lenient(reading, options.decimal)
+    } else {
+        rusty::parse_supply_level(reading)
+    };
+    parsed.map_err(|e| ParseError::new_err(format!("{}: {}", e, reading)))
+}
+
+/// Parses a whole number. Leniently, thousands separators such as
+/// "1,234,567" or "1_234_567" are accepted ("1.234.567" with decimal ",").
+#[pyfunction]
+#[pyo3(signature = (text, options=None))]
+fn parse_number(text: &str, options: Option<PyRef<'_, PyParseOptions>>) -> PyResult<i64> {
+    rusty::parse_number(text, &parse_options(options))
+        .map_err(|e| ParseError::new_err(format!("{}: {}", e, text)))
+}
+
+/// Parses a number with a fractional part written with the options'
+/// decimal separator, "." or ",". Numbers in the other convention raise
+/// ParseError rather than being misread.
+#[pyfunction]
+#[pyo3(signature = (text, options=None))]
+fn parse_decimal(text: &str, options: Option<PyRef<'_, PyParseOptions>>) -> PyResult<f64> {
+    rusty::parse_decimal(text, &parse_options(options))
+        .map_err(|e| ParseError::new_err(format!("{}: {}", e, text)))
+}
+
+/// Returns the 64-bit stellar-grid key of the 32ly cell containing x, y, z.
+#[pyfunction]
+fn stellar_grid_key(x: f64, y: f64, z: f64) -> u64 {
+    rusty::stellar_grid_key(x, y, z)
+}
+
+/// Returns the stellar-grid keys of (x, y, z) positions, as
+/// stellar_grid_key would one at a time.
+#[pyfunction]
+fn stellar_grid_keys(py: Python<'_>, positions: Positions) -> Vec<u64> {
+    py.allow_threads(|| match &positions {
+        Positions::F64(positions) => rusty::stellar_grid_keys(positions),
+        Positions::F32(positions) => rusty::stellar_grid_keys(positions),
+    })
+}
+
+/// Returns the key of the cell containing x, y, z at a grid level: 0 for
+/// 4ly cells, 1 for 32ly, 2 for 256ly. Keys of all levels share one space.
+#[pyfunction]
+fn level_grid_key(x: f64, y: f64, z: f64, level: u8) -> PyResult<u64> {
+    if level >= multigrid::LEVELS {
+        return Err(SpatialError::new_err(format!(
+            "invalid grid level: {}",
+            level
+        )));
+    }
+    Ok(multigrid::level_key(x, y, z, level))
+}
+
+/// Raises SpatialError unless key is a level_grid_key.
+fn check_level_key(key: u64) -> PyResult<()> {
+    match multigrid::key_level(key) {
+        Some(_) => Ok(()),
+        None => Err(SpatialError::new_err(format!(
+            "not a level grid key: {:#x}",
+            key
+        ))),
+    }
+}
+
+/// Returns the key of the cell a level up containing a level_grid_key's
+/// cell, or None at the coarsest level. Raises SpatialError if key isn't
+/// a level_grid_key.
+#[pyfunction]
+fn parent_grid_key(key: u64) -> PyResult<Option<u64>> {
+    check_level_key(key)?;
+    Ok(multigrid::parent_key(key))
+}
+
+/// Returns the keys of the 512 cells a level down making up a
+/// level_grid_key's cell; empty at the finest level. Raises SpatialError
+/// if key isn't a level_grid_key.
+#[pyfunction]
+fn child_grid_keys(key: u64) -> PyResult<Vec<u64>> {
+    check_level_key(key)?;
+    Ok(multigrid::child_keys(key))
+}
+
+/// Returns (sector_id, (sx, sy, sz), (ox, oy, oz)) for the 1280ly sector
+/// containing x, y, z: its packed id, per-axis index, and corner coordinates.
+#[pyfunction]
+fn sector_for(x: f64, y: f64, z: f64) -> (u64, (i32, i32, i32), (f64, f64, f64)) {
+    let sector = sector::sector_for(x, y, z);
+    let [sx, sy, sz] = sector.index;
+    let [ox, oy, oz] = sector.origin;
+    (sector.id, (sx, sy, sz), (ox, oy, oz))
+}
+
+/// Returns ((sx, sy, sz), (ox, oy, oz)) for a sector id from sector_for.
+#[pyfunction]
+fn sector_from_id(sector_id: u64) -> ((i32, i32, i32), (f64, f64, f64)) {
+    let sector = sector::sector_from_id(sector_id);
+    let [sx, sy, sz] = sector.index;
+    let [ox, oy, oz] = sector.origin;
+    ((sx, sy, sz), (ox, oy, oz))
+}
+
+/// Builds the procedural name of a system at x, y, z in the named sector,
+/// e.g. "Wregoe XQ-L c21-0". mass_code is 'a' to 'h'.
+#[pyfunction]
+#[pyo3(signature = (sector_name, x, y, z, mass_code, n2=0))]
+fn procedural_name(
+    sector_name: &str,
+    x: f64,
+    y: f64,
+    z: f64,
+    mass_code: char,
+    n2: u32,
+) -> PyResult<String> {
+    let code = (mass_code as u32).wrapping_sub('a' as u32);
+    if code > 7 {
+        return Err(SpatialError::new_err(format!(
+            "invalid mass code: {}",
+            mass_code
+        )));
+    }
+    Ok(sector::procedural_name(
+        sector_name,
+        x,
+        y,
+        z,
+        code as u8,
+        n2,
+    ))
+}
+
+/// Returns the (x, y, z) corner of the boxel a procedural suffix like
+/// "XQ-L c21-0" names within the sector containing the given position.
+#[pyfunction]
+fn procedural_boxel_origin(suffix: &str, x: f64, y: f64, z: f64) -> PyResult<(f64, f64, f64)> {
+    let parsed = sector::ProceduralSuffix::parse(suffix)
+        .ok_or_else(|| ParseError::new_err(format!("invalid procedural suffix: {}", suffix)))?;
+    let origin = parsed.origin(sector::sector_index(x, y, z));
+    Ok((origin[0], origin[1], origin[2]))
+}
+
+/// Returns the (x, y, z) center of the boxel a system's id64 places it in,
+/// which is within half a boxel (5ly to 640ly) of the system.
+#[pyfunction]
+fn id64_position(id64: u64) -> (f64, f64, f64) {
+    let [x, y, z] = transforms::id64_position(id64);
+    (x, y, z)
+}
+
+/// Returns the id64 a system numbered n2 in the boxel containing x, y, z
+/// would have. mass_code is 'a' to 'h'.
+#[pyfunction]
+#[pyo3(signature = (x, y, z, mass_code, n2=0))]
+fn id64_for_position(x: f64, y: f64, z: f64, mass_code: char, n2: u64) -> PyResult<u64> {
+    let code = (mass_code as u32).wrapping_sub('a' as u32);
+    if code > 7 {
+        return Err(SpatialError::new_err(format!(
+            "invalid mass code: {}",
+            mass_code
+        )));
+    }
+    transforms::Id64Location::for_position([x, y, z], code as u8, n2)
+        .and_then(|location| location.to_id64())
+        .ok_or_else(|| SpatialError::new_err(format!("no id64 for ({}, {}, {})", x, y, z)))
+}
+
+/// The traderusty extension module: Python bindings for traderusty-core.
+#[pymodule(gil_used = false)]
+#[pyo3(name = "traderusty")]
+fn traderusty(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    pyerrors::register(m)?;
+    m.add_function(wrap_pyfunction!(pylogging::enable_logging, m)?)?;
+    m.add_function(wrap_pyfunction!(pylogging::disable_logging, m)?)?;
+    m.add_class::<PyReadOptions>()?;
+    m.add_class::<PyParseOptions>()?;
+    m.add_function(wrap_pyfunction!(count_file_lines, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_utf8, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_supply_level, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_number, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_decimal, m)?)?;
+    m.add_function(wrap_pyfunction!(stellar_grid_key, m)?)?;
+    m.add_function(wrap_pyfunction!(stellar_grid_keys, m)?)?;
+    m.add_function(wrap_pyfunction!(level_grid_key, m)?)?;
+    m.add_function(wrap_pyfunction!(parent_grid_key, m)?)?;
+    m.add_function(wrap_pyfunction!(child_grid_keys, m)?)?;
+    m.add_function(wrap_pyfunction!(sector_for, m)?)?;
+    m.add_function(wrap_pyfunction!(sector_from_id, m)?)?;
+    m.add_function(wrap_pyfunction!(procedural_name, m)?)?;
+    m.add_function(wrap_pyfunction!(procedural_boxel_origin, m)?)?;
+    m.add_function(wrap_pyfunction!(id64_position, m)?)?;
+    m.add_function(wrap_pyfunction!(id64_for_position, m)?)?;
+    pyaggregate::register(m)?;
+    pybloom::register(m)?;
+    pyeddn::register(m)?;
+    pyinara::register(m)?;
+    pyjournal::register(m)?;
+    pylines::register(m)?;
+    pymarket::register(m)?;
+    pymetrics::register(m)?;
+    pycancel::register(m)?;
+    pycolstore::register(m)?;
+    pydb::register(m)?;
+    pynames::register(m)?;
+    pypool::register(m)?;
+    pyprices::register(m)?;
+    pyquery::register(m)?;
+    pyregion::register(m)?;
+    pyroute::register(m)?;
+    pysystem::register(m)?;
+    pytrade::register(m)?;
+    pyverify::register(m)?;
+    #[cfg(feature = "download")]
+    pydownload::register(m)?;
+    #[cfg(feature = "edsm")]
+    pyedsm::register(m)?;
+    #[cfg(feature = "spansh")]
+    pyspansh::register(m)?;
+    #[cfg(feature = "zstd")]
+    pycompress::register(m)?;
+    #[cfg(feature = "asyncio")]
+    pyasync::register(m)?;
+    #[cfg(feature = "opentelemetry")]
+    pyotel::register(m)?;
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Python bindings for price aggregation.
+
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use traderusty_core::aggregate::{self, PriceAggregates};
+use traderusty_core::source::FileSource;
+
+use crate::pymarket::PyMarketStore;
+use crate::pyregion::PyRegionMap;
+use crate::{read_options, FsPath, PyParseOptions, PyReadOptions};
+
+/// Aggregates as a dict of equal-length lists: "region" (None for the
+/// whole galaxy), "item_id", and the count, mean, min and max of the sell
+/// and buy prices.
+fn aggregates_dict(py: Python<'_>, aggregates: PriceAggregates) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("region", aggregates.region)?;
+    dict.set_item(
+        "item_id",
+        aggregates
+            .item_id
+            .into_iter()
+            .map(u32::from)
+            .collect::<Vec<_>>(),
+    )?;
+    dict.set_item("sell_count", aggregates.sell_count)?;
+    dict.set_item("sell_mean", aggregates.sell_mean)?;
+    dict.set_item("sell_min", aggregates.sell_min)?;
+    dict.set_item("sell_max", aggregates.sell_max)?;
+    dict.set_item("buy_count", aggregates.buy_count)?;
+    dict.set_item("buy_mean", aggregates.buy_mean)?;
+    dict.set_item("buy_min", aggregates.buy_min)?;
+    dict.set_item("buy_max", aggregates.buy_max)?;
+    Ok(dict.into())
+}
+
+/// The mean, lowest and highest sell and buy prices of each commodity in a
+/// .prices file, read in one pass, for the galaxy and, given regions and
+/// the positions of systems by name, for each region. A mean is NaN where
+/// nobody trades that way.
+#[pyfunction]
+#[pyo3(signature = (path, regions=None, positions=None, options=None, parse_options=None))]
+fn aggregate_prices(
+    py: Python<'_>,
+    path: FsPath,
+    regions: Option<&PyRegionMap>,
+    positions: Option<HashMap<String, (f64, f64, f64)>>,
+    options: Option<PyRef<'_, PyReadOptions>>,
+    parse_options: Option<PyRef<'_, PyParseOptions>>,
+) -> PyResult<PyObject> {
+    let source = FileSource::new(&path.0, &read_options(options));
+    let parse_options = crate::parse_options(parse_options);
+    let positions = positions.unwrap_or_default();
+    let position = |system: &str| positions.get(system).map(|&(x, y, z)| [x, y, z]);
+    let aggregates = py
+        .allow_threads(|| {
+            let regions = regions.map(|regions| &regions.inner);
+            aggregate::aggregate_prices(&source, &parse_options, regions, position)
+        })
+        .map_err(|e| PyIOError::new_err(format!("{}", e)))?;
+    aggregates_dict(py, aggregates)
+}
+
+/// The mean, lowest and highest sell and buy prices of each commodity in a
+/// store, for the galaxy and, given regions, for each region.
+#[pyfunction]
+#[pyo3(signature = (store, regions=None))]
+fn aggregate_store(
+    py: Python<'_>,
+    store: &PyMarketStore,
+    regions: Option<&PyRegionMap>,
+) -> PyResult<PyObject> {
+    let regions = regions.map(|regions| &regions.inner);
+    let aggregates = store.with_read(py, |store| aggregate::aggregate_store(store, regions));
+    aggregates_dict(py, aggregates)
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(aggregate_prices, m)?)?;
+    m.add_function(wrap_pyfunction!(aggregate_store, m)?)?;
+    Ok(())
+}
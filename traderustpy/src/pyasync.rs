@@ -0,0 +1,101 @@
+//! Awaitable variants of long-running calls, for asyncio code.
+//!
+//! The work runs on the blocking pool of a tokio runtime the module starts
+//! on first use, so it ties up neither the event loop nor one of asyncio's
+//! executor threads. Cancelling the awaiting task cancels the work's
+//! CancelToken: an import stops at its next batch and rolls back, a route
+//! search gives up, a download stops at its next chunk. EDSM and Spansh
+//! lookups have no cancellation point, so cancelling one only stops
+//! waiting for it.
+
+use std::collections::HashMap;
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use traderusty_core::cancel::CancelToken;
+use traderusty_core::db::{self, DEFAULT_BATCH_SIZE};
+use traderusty_core::pipeline::{self, PipelineOptions, DEFAULT_QUEUE_DEPTH};
+use traderusty_core::source::FileSource;
+
+use crate::pydb::{import_stats_dict, pipeline_error, station_table};
+use crate::{read_options, FsPath, PyParseOptions, PyReadOptions};
+
+/// Cancels its token when dropped along with the future awaiting the work.
+struct CancelOnDrop(CancelToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Runs `work` off the event loop, returning an awaitable of its result.
+/// Cancelling the awaiting task cancels the token `work` is handed.
+pub fn spawn_cancellable<'py, T, F>(py: Python<'py>, work: F) -> PyResult<Bound<'py, PyAny>>
+where
+    F: FnOnce(CancelToken) -> PyResult<T> + Send + 'static,
+    T: for<'a> IntoPyObject<'a> + Send + 'static,
+{
+    let token = CancelToken::default();
+    let guard = CancelOnDrop(token.clone());
+    pyo3_async_runtimes::tokio::future_into_py(py, async move {
+        // Dropped with this future when the task is cancelled.
+        let _guard = guard;
+        pyo3_async_runtimes::tokio::get_runtime()
+            .spawn_blocking(move || work(token))
+            .await
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?
+    })
+}
+
+/// import_prices as a coroutine. Cancelling it rolls the import back.
+#[pyfunction]
+#[pyo3(signature = (
+    db_path, path, stations, batch_size=DEFAULT_BATCH_SIZE, queue_depth=DEFAULT_QUEUE_DEPTH,
+    options=None, parse_options=None, validate_only=false,
+))]
+#[allow(clippy::too_many_arguments)]
+fn import_prices_async<'py>(
+    py: Python<'py>,
+    db_path: FsPath,
+    path: FsPath,
+    stations: HashMap<(String, String), u32>,
+    batch_size: usize,
+    queue_depth: usize,
+    options: Option<PyRef<'py, PyReadOptions>>,
+    parse_options: Option<PyRef<'py, PyParseOptions>>,
+    validate_only: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let stations = station_table(stations);
+    let source = FileSource::new(&path.0, &read_options(options));
+    let parse_options = crate::parse_options(parse_options);
+    spawn_cancellable(py, move |cancel| {
+        let pipeline_options = PipelineOptions {
+            batch_size,
+            queue_depth,
+            cancel: Some(cancel),
+        };
+        let stats = if validate_only {
+            pipeline::validate_prices(&source, &stations, &pipeline_options, &parse_options)
+        } else {
+            db::open_database(&db_path.0)
+                .map_err(Into::into)
+                .and_then(|mut conn| {
+                    pipeline::import_prices(
+                        &mut conn,
+                        &source,
+                        &stations,
+                        &pipeline_options,
+                        &parse_options,
+                    )
+                })
+        }
+        .map_err(pipeline_error)?;
+        Python::with_gil(|py| import_stats_dict(py, &stats))
+    })
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(import_prices_async, m)?)?;
+    Ok(())
+}
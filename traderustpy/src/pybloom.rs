@@ -0,0 +1,69 @@
+//! Python bindings for the station Bloom filter.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use traderusty_core::bloom::{market_key, BloomFilter};
+
+/// A Bloom filter of stations, by market id or by system and station name.
+/// Membership tests can give false positives at about the rate it was sized
+/// for, never false negatives. Pass one as PricesReader's `stations` to read
+/// only those stations.
+#[pyclass(name = "BloomFilter")]
+pub struct PyBloomFilter {
+    pub inner: BloomFilter,
+}
+
+#[pymethods]
+impl PyBloomFilter {
+    #[new]
+    #[pyo3(signature = (expected, false_positive_rate=0.01))]
+    fn new(expected: usize, false_positive_rate: f64) -> PyResult<Self> {
+        if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+            return Err(PyValueError::new_err(
+                "false_positive_rate must be between 0 and 1",
+            ));
+        }
+        Ok(Self {
+            inner: BloomFilter::new(expected, false_positive_rate),
+        })
+    }
+
+    fn add_station(&mut self, system: &str, station: &str) {
+        self.inner.insert_station(system, station);
+    }
+
+    fn add_market_id(&mut self, market_id: u64) {
+        self.inner.insert(market_key(market_id));
+    }
+
+    fn contains_station(&self, system: &str, station: &str) -> bool {
+        self.inner.contains_station(system, station)
+    }
+
+    fn contains_market_id(&self, market_id: u64) -> bool {
+        self.inner.contains(market_key(market_id))
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.to_bytes())
+    }
+
+    /// Reads back a filter saved with to_bytes().
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<Self> {
+        let inner =
+            BloomFilter::from_bytes(data).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// Stations added, counting repeats.
+    fn __len__(&self) -> usize {
+        self.inner.len() as usize
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBloomFilter>()?;
+    Ok(())
+}
@@ -0,0 +1,35 @@
+//! Python bindings for cancellation tokens.
+
+use pyo3::prelude::*;
+use traderusty_core::cancel::CancelToken;
+
+/// Cancels a running import or route search from another thread: pass it
+/// as the call's `cancel` and call cancel() on it. An import rolls back, a
+/// route search returns None.
+#[pyclass(name = "CancelToken", frozen)]
+#[derive(Default)]
+pub struct PyCancelToken {
+    pub inner: CancelToken,
+}
+
+#[pymethods]
+impl PyCancelToken {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn cancel(&self) {
+        self.inner.cancel();
+    }
+
+    #[getter]
+    fn cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyCancelToken>()?;
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Python bindings for the memory-mapped column store.
+
+use pyo3::exceptions::{PyIOError, PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use traderusty_core::colstore::{self, ColumnStore, COLUMNS};
+use traderusty_core::ids::{ItemId, StationId};
+
+use crate::pymarket::{PyMarketStore, PyStationItem};
+use crate::FsPath;
+
+fn io_error(e: std::io::Error) -> PyErr {
+    PyIOError::new_err(format!("{}", e))
+}
+
+/// StationItem listings in a column store file, memory-mapped. Opening
+/// reads only the file's header; listings are read as they're asked for.
+#[pyclass(name = "ColumnStore", frozen)]
+pub struct PyColumnStore {
+    inner: ColumnStore,
+}
+
+#[pymethods]
+impl PyColumnStore {
+    #[new]
+    fn new(path: FsPath) -> PyResult<Self> {
+        Ok(Self {
+            inner: ColumnStore::open(&path.0).map_err(io_error)?,
+        })
+    }
+
+    /// The listing in row idx; rows are ordered by station, then item.
+    fn row(&self, idx: usize) -> PyResult<PyStationItem> {
+        if idx >= self.inner.len() {
+            return Err(PyIndexError::new_err("row out of range"));
+        }
+        Ok(self.inner.row(idx).into())
+    }
+
+    fn get(&self, station_id: u32, item_id: u32) -> Option<PyStationItem> {
+        self.inner
+            .get(StationId(station_id), ItemId(item_id))
+            .map(Into::into)
+    }
+
+    /// All the listings of a station, ordered by item.
+    fn station_items(&self, station_id: u32) -> Vec<PyStationItem> {
+        self.inner
+            .station_items(StationId(station_id))
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    }
+
+    /// Every value of one column, named as StationItem's fields are.
+    fn column(&self, py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        let store = &self.inner;
+        let column = match name {
+            "station_id" => store
+                .station_id()
+                .iter()
+                .map(u32::from)
+                .collect::<Vec<_>>()
+                .into_pyobject(py)?,
+            "item_id" => store
+                .item_id()
+                .iter()
+                .map(u32::from)
+                .collect::<Vec<_>>()
+                .into_pyobject(py)?,
+            "demand_price" => store.demand_price().to_vec().into_pyobject(py)?,
+            "demand_units" => store.demand_units().to_vec().into_pyobject(py)?,
+            "demand_level" => store.demand_level().to_vec().into_pyobject(py)?,
+            "supply_price" => store.supply_price().to_vec().into_pyobject(py)?,
+            "supply_units" => store.supply_units().to_vec().into_pyobject(py)?,
+            "supply_level" => store.supply_level().to_vec().into_pyobject(py)?,
+            "modified" => store.modified().to_vec().into_pyobject(py)?,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "no column {:?}; there are {}",
+                    name,
+                    COLUMNS.join(", ")
+                )));
+            }
+        };
+        Ok(column.unbind())
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+/// Writes a store's listings to a column store file and returns how many
+/// there were.
+#[pyfunction]
+fn write_column_store(py: Python<'_>, path: FsPath, store: &PyMarketStore) -> PyResult<usize> {
+    let items: Vec<_> = store.with_read(py, |store| store.iter().collect());
+    py.allow_threads(|| colstore::write_column_store(&path.0, &items))
+        .map_err(|e| PyIOError::new_err(format!("{}: {}", path.0.display(), e)))
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyColumnStore>()?;
+    m.add_function(wrap_pyfunction!(write_column_store, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,115 @@
+//! Python bindings for zstd cache compression.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use traderusty_core::compress::{self, DEFAULT_DICTIONARY_SIZE, DEFAULT_LEVEL};
+
+use crate::pymarket::PyMarketSnapshot;
+use crate::FsPath;
+
+/// Trains a zstd dictionary on sample records, such as snapshot_record()s.
+#[pyfunction]
+#[pyo3(signature = (samples, max_size=DEFAULT_DICTIONARY_SIZE))]
+fn train_zstd_dictionary<'py>(
+    py: Python<'py>,
+    samples: Vec<Vec<u8>>,
+    max_size: usize,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let dictionary = py
+        .allow_threads(|| compress::train_dictionary(&samples, max_size))
+        .map_err(|e| PyValueError::new_err(format!("{}", e)))?;
+    Ok(PyBytes::new(py, &dictionary))
+}
+
+/// A snapshot as a record of the snapshot cache format, uncompressed.
+#[pyfunction]
+fn snapshot_record<'py>(py: Python<'py>, snapshot: &PyMarketSnapshot) -> Bound<'py, PyBytes> {
+    PyBytes::new(py, &compress::snapshot_record(&snapshot.inner))
+}
+
+/// Compresses bytes with zstd, using a dictionary if one is given.
+#[pyfunction]
+#[pyo3(signature = (data, dictionary=None, level=DEFAULT_LEVEL))]
+fn zstd_compress<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    dictionary: Option<&[u8]>,
+    level: i32,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let compressed = compress::compress(data, dictionary.unwrap_or_default(), level)
+        .map_err(|e| PyValueError::new_err(format!("{}", e)))?;
+    Ok(PyBytes::new(py, &compressed))
+}
+
+/// Decompresses what zstd_compress wrote, given the same dictionary.
+#[pyfunction]
+#[pyo3(signature = (data, dictionary=None))]
+fn zstd_decompress<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    dictionary: Option<&[u8]>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let data = compress::decompress(data, dictionary.unwrap_or_default())
+        .map_err(|e| PyValueError::new_err(format!("{}", e)))?;
+    Ok(PyBytes::new(py, &data))
+}
+
+/// Writes snapshots to a zstd-compressed snapshot cache file.
+#[pyfunction]
+#[pyo3(signature = (path, snapshots, dictionary=None, level=DEFAULT_LEVEL))]
+fn write_snapshot_cache(
+    py: Python<'_>,
+    path: FsPath,
+    snapshots: Vec<PyRef<'_, PyMarketSnapshot>>,
+    dictionary: Option<&[u8]>,
+    level: i32,
+) -> PyResult<()> {
+    let snapshots: Vec<_> = snapshots.iter().map(|snapshot| &snapshot.inner).collect();
+    py.allow_threads(|| {
+        let file = BufWriter::new(File::create(&path.0)?);
+        compress::write_snapshots(
+            file,
+            snapshots.iter().copied(),
+            dictionary.unwrap_or_default(),
+            level,
+        )?
+        .into_inner()
+        .map_err(|e| e.into_error())?;
+        Ok(())
+    })
+    .map_err(|e: std::io::Error| PyIOError::new_err(format!("{}: {}", path.0.display(), e)))
+}
+
+/// Reads back the snapshots of a snapshot cache file.
+#[pyfunction]
+#[pyo3(signature = (path, dictionary=None))]
+fn read_snapshot_cache(
+    py: Python<'_>,
+    path: FsPath,
+    dictionary: Option<&[u8]>,
+) -> PyResult<Vec<PyMarketSnapshot>> {
+    let snapshots = py
+        .allow_threads(|| {
+            let file = BufReader::new(File::open(&path.0)?);
+            compress::read_snapshots(file, dictionary.unwrap_or_default())
+        })
+        .map_err(|e| PyIOError::new_err(format!("{}: {}", path.0.display(), e)))?;
+    Ok(snapshots
+        .into_iter()
+        .map(|inner| PyMarketSnapshot { inner })
+        .collect())
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(train_zstd_dictionary, m)?)?;
+    m.add_function(wrap_pyfunction!(snapshot_record, m)?)?;
+    m.add_function(wrap_pyfunction!(zstd_compress, m)?)?;
+    m.add_function(wrap_pyfunction!(zstd_decompress, m)?)?;
+    m.add_function(wrap_pyfunction!(write_snapshot_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(read_snapshot_cache, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,210 @@
+//! Python bindings for writing to the TradeDangerous database.
+
+use std::collections::HashMap;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use traderusty_core::db::{self, DbError, DEFAULT_BATCH_SIZE};
+use traderusty_core::ids::StationId;
+use traderusty_core::market::StationItemColumns;
+use traderusty_core::migrate;
+use traderusty_core::pipeline::{
+    self, ImportStats, PipelineError, PipelineOptions, QueueStats, DEFAULT_QUEUE_DEPTH,
+};
+use traderusty_core::source::FileSource;
+use traderusty_core::synthetic::SyntheticIds;
+
+use crate::pycancel::PyCancelToken;
+use crate::pyerrors::ImportError_;
+use crate::pymarket::PyStationItem;
+use crate::pyprices::merge_error;
+use crate::{read_options, FsPath, PyParseOptions, PyReadOptions};
+
+fn db_error(e: DbError) -> PyErr {
+    ImportError_::new_err(format!("{}", e))
+}
+
+/// Writes StationItem rows to the database at `db_path` in one transaction
+/// (opening it in WAL mode),
+/// replacing existing rows for the same station and item. Returns the
+/// number of rows written. Rows are sent batch_size at a time.
+///
+/// With validate_only the transaction is rolled back rather than
+/// committed: rows the database refuses fail as they would, but nothing
+/// changes, and the count is of the rows that would be written.
+#[pyfunction]
+#[pyo3(signature = (db_path, items, batch_size=DEFAULT_BATCH_SIZE, validate_only=false))]
+fn write_station_items(
+    py: Python<'_>,
+    db_path: FsPath,
+    items: Vec<PyRef<'_, PyStationItem>>,
+    batch_size: usize,
+    validate_only: bool,
+) -> PyResult<usize> {
+    let columns: StationItemColumns = items.iter().map(|item| &item.inner).collect();
+    py.allow_threads(|| {
+        let mut conn = db::open_database(&db_path.0)?;
+        if validate_only {
+            db::validate_station_items(&mut conn, &columns, batch_size)
+        } else {
+            db::write_station_items(&mut conn, &columns, batch_size)
+        }
+    })
+    .map_err(db_error)
+}
+
+/// Upgrades the database at `db_path` to the latest schema, returning the
+/// schema version.
+#[pyfunction]
+fn migrate_database(py: Python<'_>, db_path: FsPath) -> PyResult<u32> {
+    py.allow_threads(|| {
+        let mut conn = db::open_database(&db_path.0)?;
+        migrate::migrate(&mut conn)
+    })
+    .map_err(db_error)
+}
+
+/// Returns the synthetic station IDs of (system, station) names without a
+/// MarketID, allocating and saving IDs for ones the database at `db_path`
+/// doesn't have yet, so a station keeps its ID from one import to the
+/// next. The database must be migrated.
+#[pyfunction]
+fn synthetic_station_ids(
+    py: Python<'_>,
+    db_path: FsPath,
+    stations: Vec<(String, String)>,
+) -> PyResult<Vec<u32>> {
+    py.allow_threads(|| {
+        let mut conn = db::open_database(&db_path.0)?;
+        let mut ids = SyntheticIds::load(&conn)?;
+        let allocated = stations
+            .iter()
+            .map(|(system, station)| ids.station(system, station).0)
+            .collect();
+        ids.save(&mut conn)?;
+        Ok(allocated)
+    })
+    .map_err(db_error)
+}
+
+fn queue_dict<'py>(py: Python<'py>, stats: &QueueStats) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("capacity", stats.capacity)?;
+    dict.set_item("sent", stats.sent)?;
+    dict.set_item("max_depth", stats.max_depth)?;
+    dict.set_item("blocked", stats.blocked)?;
+    dict.set_item("blocked_seconds", stats.blocked_time.as_secs_f64())?;
+    Ok(dict)
+}
+
+pub fn import_stats_dict(py: Python<'_>, stats: &ImportStats) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("records", stats.records)?;
+    dict.set_item("written", stats.written)?;
+    dict.set_item("unknown_stations", stats.unknown_stations)?;
+    dict.set_item("unknown_items", stats.unknown_items)?;
+    let errors: Vec<String> = stats.errors.iter().map(|e| e.to_string()).collect();
+    dict.set_item("errors", errors)?;
+    let warnings: Vec<String> = stats.warnings.iter().map(|w| w.to_string()).collect();
+    dict.set_item("warnings", warnings)?;
+    let unresolved: Vec<String> = stats.unresolved.iter().map(|u| u.to_string()).collect();
+    dict.set_item("unresolved", unresolved)?;
+    let queues = PyDict::new(py);
+    queues.set_item("parsed", queue_dict(py, &stats.parsed_queue)?)?;
+    queues.set_item("rows", queue_dict(py, &stats.rows_queue)?)?;
+    dict.set_item("queues", queues)?;
+    Ok(dict.into_any().unbind())
+}
+
+pub fn pipeline_error(e: PipelineError) -> PyErr {
+    match e {
+        PipelineError::Prices(e) => merge_error(e),
+        PipelineError::Db(e) => db_error(e),
+        PipelineError::Cancelled => ImportError_::new_err(e.to_string()),
+    }
+}
+
+/// Keys a station table given as {(system, station): id} the way the
+/// import looks stations up.
+pub fn station_table(
+    stations: HashMap<(String, String), u32>,
+) -> HashMap<(String, String), StationId> {
+    stations
+        .into_iter()
+        .map(|((system, station), id)| (pipeline::station_key(&system, &station), StationId(id)))
+        .collect()
+}
+
+/// Imports a .prices file into the StationItem table at `db_path` in one
+/// transaction, parsing, resolving and writing on separate threads joined
+/// by queues of at most `queue_depth` batches, so a slow database throttles
+/// the parser. `stations` maps (system, station) names to station ids;
+/// records of other stations or unknown items are counted and skipped.
+/// Items get the ids of the database's Item table if it has one.
+/// Returns a dict of counts, parse error and warning messages and, under
+/// "queues", how full each queue got and how long its sender waited on it.
+/// Raises ParseError once more than parse_options.max_errors lines fail,
+/// writing nothing, and ImportError_ if cancelled through `cancel`.
+///
+/// With validate_only the database isn't touched: every line is checked
+/// and every error collected, whatever max_errors says, "written" counts
+/// the rows an import would write, and "unresolved" says on which lines
+/// the unknown stations and items are.
+#[pyfunction]
+#[pyo3(signature = (
+    db_path, path, stations, batch_size=DEFAULT_BATCH_SIZE, queue_depth=DEFAULT_QUEUE_DEPTH,
+    options=None, parse_options=None, cancel=None, validate_only=false,
+))]
+#[allow(clippy::too_many_arguments)]
+fn import_prices(
+    py: Python<'_>,
+    db_path: FsPath,
+    path: FsPath,
+    stations: HashMap<(String, String), u32>,
+    batch_size: usize,
+    queue_depth: usize,
+    options: Option<PyRef<'_, PyReadOptions>>,
+    parse_options: Option<PyRef<'_, PyParseOptions>>,
+    cancel: Option<PyRef<'_, PyCancelToken>>,
+    validate_only: bool,
+) -> PyResult<PyObject> {
+    let stations = station_table(stations);
+    let pipeline_options = PipelineOptions {
+        batch_size,
+        queue_depth,
+        cancel: cancel.map(|token| token.inner.clone()),
+    };
+    let source = FileSource::new(&path.0, &read_options(options));
+    let parse_options = crate::parse_options(parse_options);
+    let stats = py
+        .allow_threads(|| {
+            if validate_only {
+                return pipeline::validate_prices(
+                    &source,
+                    &stations,
+                    &pipeline_options,
+                    &parse_options,
+                );
+            }
+            let mut conn = db::open_database(&db_path.0)?;
+            pipeline::import_prices(
+                &mut conn,
+                &source,
+                &stations,
+                &pipeline_options,
+                &parse_options,
+            )
+        })
+        .map_err(pipeline_error)?;
+    import_stats_dict(py, &stats)
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("DEFAULT_BATCH_SIZE", DEFAULT_BATCH_SIZE)?;
+    m.add("DEFAULT_QUEUE_DEPTH", DEFAULT_QUEUE_DEPTH)?;
+    m.add_function(wrap_pyfunction!(import_prices, m)?)?;
+    m.add_function(wrap_pyfunction!(write_station_items, m)?)?;
+    m.add_function(wrap_pyfunction!(migrate_database, m)?)?;
+    m.add_function(wrap_pyfunction!(synthetic_station_ids, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,134 @@
+//! Python bindings for resumable dump downloads.
+
+use std::io;
+
+use pyo3::prelude::*;
+use traderusty_core::cancel::CancelToken;
+use traderusty_core::download::{Downloader, DEFAULT_BACKOFF, DEFAULT_RETRIES};
+use traderusty_core::http::{DEFAULT_JITTER, DEFAULT_MAX_BACKOFF};
+
+use crate::pyerrors::http_error;
+use crate::{retry_policy, seconds, FsPath};
+
+/// Downloads url to dest by way of dest + ".part", carrying on from where
+/// an earlier, interrupted download left off, and returns its size. Failed
+/// attempts are retried with a doubling backoff, capped at max_backoff and
+/// with up to jitter of each wait taken off at random; on_retry, if given,
+/// is called with the retry's number, wait and error. progress, if given, is
+/// called with the bytes downloaded so far and the total size, or None if
+/// the server doesn't say; an exception from it stops the download and is
+/// raised. The GIL is released meanwhile.
+#[pyfunction]
+#[pyo3(signature = (
+    url,
+    dest,
+    progress=None,
+    timeout=30.0,
+    retries=DEFAULT_RETRIES,
+    backoff=DEFAULT_BACKOFF.as_secs_f64(),
+    max_backoff=DEFAULT_MAX_BACKOFF.as_secs_f64(),
+    jitter=DEFAULT_JITTER,
+    on_retry=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn download(
+    py: Python<'_>,
+    url: &str,
+    dest: FsPath,
+    progress: Option<Py<PyAny>>,
+    timeout: f64,
+    retries: usize,
+    backoff: f64,
+    max_backoff: f64,
+    jitter: f64,
+    on_retry: Option<Py<PyAny>>,
+) -> PyResult<u64> {
+    let downloader = Downloader::new(seconds("timeout", timeout)?).retry_policy(retry_policy(
+        retries,
+        backoff,
+        max_backoff,
+        jitter,
+        on_retry,
+    )?);
+    py.allow_threads(|| run(&downloader, url, &dest, progress.as_ref(), None))
+}
+
+/// download as a coroutine. Cancelling it stops the download at its next
+/// progress report, leaving the .part file to resume from.
+#[cfg(feature = "asyncio")]
+#[pyfunction]
+#[pyo3(signature = (
+    url,
+    dest,
+    progress=None,
+    timeout=30.0,
+    retries=DEFAULT_RETRIES,
+    backoff=DEFAULT_BACKOFF.as_secs_f64(),
+    max_backoff=DEFAULT_MAX_BACKOFF.as_secs_f64(),
+    jitter=DEFAULT_JITTER,
+    on_retry=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn download_async<'py>(
+    py: Python<'py>,
+    url: String,
+    dest: FsPath,
+    progress: Option<Py<PyAny>>,
+    timeout: f64,
+    retries: usize,
+    backoff: f64,
+    max_backoff: f64,
+    jitter: f64,
+    on_retry: Option<Py<PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let downloader = Downloader::new(seconds("timeout", timeout)?).retry_policy(retry_policy(
+        retries,
+        backoff,
+        max_backoff,
+        jitter,
+        on_retry,
+    )?);
+    crate::pyasync::spawn_cancellable(py, move |cancel| {
+        run(&downloader, &url, &dest, progress.as_ref(), Some(&cancel))
+    })
+}
+
+/// Runs a download without the GIL, taking it to report progress. An
+/// exception from `progress` is raised as it was; once `cancel` is
+/// cancelled the download stops.
+fn run(
+    downloader: &Downloader,
+    url: &str,
+    dest: &FsPath,
+    progress: Option<&Py<PyAny>>,
+    cancel: Option<&CancelToken>,
+) -> PyResult<u64> {
+    let mut raised = None;
+    let result = downloader.download(url, &dest.0, |done, total| {
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "download cancelled",
+            ));
+        }
+        let Some(progress) = progress else {
+            return Ok(());
+        };
+        Python::with_gil(|py| progress.call1(py, (done, total)).map(drop)).map_err(|e| {
+            let message = e.to_string();
+            raised = Some(e);
+            io::Error::other(message)
+        })
+    });
+    match raised {
+        Some(e) => Err(e),
+        None => result.map_err(http_error),
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(download, m)?)?;
+    #[cfg(feature = "asyncio")]
+    m.add_function(wrap_pyfunction!(download_async, m)?)?;
+    Ok(())
+}
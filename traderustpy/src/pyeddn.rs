@@ -0,0 +1,176 @@
+//! Python bindings for building and checking EDDN messages.
+
+use std::collections::HashMap;
+
+use pyo3::prelude::*;
+use serde_json::Value;
+use traderusty_core::eddn::{self, EddnHeader};
+use traderusty_core::eddnschema::Validator;
+
+use crate::pyjournal::{PyMarketFile, PyOutfittingFile, PyShipyardFile};
+
+/// A message as the dict json.loads would give for it.
+fn to_dict(py: Python<'_>, message: Value) -> PyResult<PyObject> {
+    let json = py.import("json")?;
+    Ok(json.call_method1("loads", (message.to_string(),))?.unbind())
+}
+
+fn header(
+    uploader_id: String,
+    software_name: String,
+    software_version: String,
+    horizons: bool,
+    odyssey: bool,
+    gateway_timestamp: Option<i64>,
+) -> EddnHeader {
+    EddnHeader {
+        uploader_id,
+        software_name,
+        software_version,
+        gateway_timestamp,
+        horizons,
+        odyssey,
+    }
+}
+
+/// The EDDN commodity/3 message for a parsed Market.json.
+#[pyfunction]
+#[pyo3(signature = (
+    market, uploader_id, software_name, software_version,
+    horizons=true, odyssey=true, gateway_timestamp=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn eddn_commodity_message(
+    py: Python<'_>,
+    market: &PyMarketFile,
+    uploader_id: String,
+    software_name: String,
+    software_version: String,
+    horizons: bool,
+    odyssey: bool,
+    gateway_timestamp: Option<i64>,
+) -> PyResult<PyObject> {
+    let header = header(
+        uploader_id,
+        software_name,
+        software_version,
+        horizons,
+        odyssey,
+        gateway_timestamp,
+    );
+    to_dict(py, eddn::commodity_message(&market.inner, &header))
+}
+
+/// The EDDN outfitting/2 message for a parsed Outfitting.json.
+#[pyfunction]
+#[pyo3(signature = (
+    outfitting, uploader_id, software_name, software_version,
+    horizons=true, odyssey=true, gateway_timestamp=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn eddn_outfitting_message(
+    py: Python<'_>,
+    outfitting: &PyOutfittingFile,
+    uploader_id: String,
+    software_name: String,
+    software_version: String,
+    horizons: bool,
+    odyssey: bool,
+    gateway_timestamp: Option<i64>,
+) -> PyResult<PyObject> {
+    let header = header(
+        uploader_id,
+        software_name,
+        software_version,
+        horizons,
+        odyssey,
+        gateway_timestamp,
+    );
+    to_dict(py, eddn::outfitting_message(&outfitting.inner, &header))
+}
+
+/// The EDDN shipyard/2 message for a parsed Shipyard.json.
+#[pyfunction]
+#[pyo3(signature = (
+    shipyard, uploader_id, software_name, software_version,
+    horizons=true, odyssey=true, gateway_timestamp=None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn eddn_shipyard_message(
+    py: Python<'_>,
+    shipyard: &PyShipyardFile,
+    uploader_id: String,
+    software_name: String,
+    software_version: String,
+    horizons: bool,
+    odyssey: bool,
+    gateway_timestamp: Option<i64>,
+) -> PyResult<PyObject> {
+    let header = header(
+        uploader_id,
+        software_name,
+        software_version,
+        horizons,
+        odyssey,
+        gateway_timestamp,
+    );
+    to_dict(py, eddn::shipyard_message(&shipyard.inner, &header))
+}
+
+/// Checks EDDN messages from the relay against the bundled schemas before
+/// they're decoded, counting those accepted and rejected per schema.
+#[pyclass(name = "EddnValidator")]
+#[derive(Default)]
+pub struct PyEddnValidator {
+    inner: Validator,
+}
+
+#[pymethods]
+impl PyEddnValidator {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks a message, given as received or as the dict json.loads gives
+    /// for it. Returns what's wrong with it, or [] if nothing is.
+    fn validate(&mut self, py: Python<'_>, message: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+        let text: String = match message.extract() {
+            Ok(text) => text,
+            Err(_) => py
+                .import("json")?
+                .call_method1("dumps", (message,))?
+                .extract()?,
+        };
+        let result = py.allow_threads(|| self.inner.validate_json(&text));
+        Ok(result.err().map_or_else(Vec::new, |violations| {
+            violations.iter().map(|v| v.to_string()).collect()
+        }))
+    }
+
+    /// {schema: (accepted, rejected)}, with messages naming no known schema
+    /// under "".
+    #[getter]
+    fn counts(&self) -> HashMap<String, (u64, u64)> {
+        self.inner
+            .counts()
+            .iter()
+            .map(|(uri, counts)| (uri.clone(), (counts.accepted, counts.rejected)))
+            .collect()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEddnValidator>()?;
+    m.add_function(wrap_pyfunction!(eddn_commodity_message, m)?)?;
+    m.add_function(wrap_pyfunction!(eddn_outfitting_message, m)?)?;
+    m.add_function(wrap_pyfunction!(eddn_shipyard_message, m)?)?;
+    m.add("EDDN_COMMODITY_SCHEMA", eddn::COMMODITY_SCHEMA)?;
+    m.add("EDDN_OUTFITTING_SCHEMA", eddn::OUTFITTING_SCHEMA)?;
+    m.add("EDDN_SHIPYARD_SCHEMA", eddn::SHIPYARD_SCHEMA)?;
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Python bindings for the EDSM client.
+
+use pyo3::prelude::*;
+use traderusty_core::edsm::{
+    EdsmClient, DEFAULT_BACKOFF, DEFAULT_INTERVAL, DEFAULT_RETRIES, EDSM_URL,
+};
+use traderusty_core::http::{DEFAULT_JITTER, DEFAULT_MAX_BACKOFF};
+use traderusty_core::system::System;
+use traderusty_core::ttlcache::{ResponseCache, DEFAULT_TTL};
+
+#[cfg(feature = "asyncio")]
+use crate::pyasync::spawn_cancellable;
+use crate::pyerrors::http_error;
+use crate::pysystem::PySystem;
+use crate::{retry_policy, seconds, FsPath};
+
+fn to_py_systems(systems: Vec<System>) -> Vec<PySystem> {
+    systems.into_iter().map(PySystem::from).collect()
+}
+
+/// Looks systems up on EDSM, keeping to its rate limit. Requests block,
+/// sleeping when the limit calls for it. Failed requests are retried as
+/// SpanshClient's are. With a cache_dir, responses are kept there and
+/// answered from for cache_ttl seconds. The GIL is released meanwhile.
+///
+/// The `_async` methods are coroutines. A lookup can't be interrupted, so
+/// cancelling one only stops it being waited for: the request in flight,
+/// and any wait for the rate limit, still run their course.
+#[pyclass(name = "EdsmClient", frozen)]
+pub struct PyEdsmClient {
+    inner: EdsmClient,
+}
+
+#[pymethods]
+impl PyEdsmClient {
+    #[new]
+    #[pyo3(signature = (
+        base_url=EDSM_URL,
+        interval=DEFAULT_INTERVAL.as_secs_f64(),
+        timeout=30.0,
+        retries=DEFAULT_RETRIES,
+        backoff=DEFAULT_BACKOFF.as_secs_f64(),
+        max_backoff=DEFAULT_MAX_BACKOFF.as_secs_f64(),
+        jitter=DEFAULT_JITTER,
+        on_retry=None,
+        cache_dir=None,
+        cache_ttl=DEFAULT_TTL.as_secs_f64(),
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        base_url: &str,
+        interval: f64,
+        timeout: f64,
+        retries: usize,
+        backoff: f64,
+        max_backoff: f64,
+        jitter: f64,
+        on_retry: Option<Py<PyAny>>,
+        cache_dir: Option<FsPath>,
+        cache_ttl: f64,
+    ) -> PyResult<Self> {
+        let retry = retry_policy(retries, backoff, max_backoff, jitter, on_retry)?;
+        let mut inner = EdsmClient::new(
+            base_url,
+            seconds("interval", interval)?,
+            seconds("timeout", timeout)?,
+        )
+        .retry_policy(retry);
+        if let Some(dir) = cache_dir {
+            let ttl = seconds("cache_ttl", cache_ttl)?;
+            inner = inner.cache(ResponseCache::new(dir.0, ttl)?);
+        }
+        Ok(Self { inner })
+    }
+
+    /// The named system, or None if EDSM has no coordinates for it.
+    fn system(&self, py: Python<'_>, name: &str) -> PyResult<Option<PySystem>> {
+        let system = py
+            .allow_threads(|| self.inner.system(name))
+            .map_err(http_error)?;
+        Ok(system.map(PySystem::from))
+    }
+
+    /// The named systems EDSM has coordinates for.
+    fn systems(&self, py: Python<'_>, names: Vec<String>) -> PyResult<Vec<PySystem>> {
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let systems = py
+            .allow_threads(|| self.inner.systems(&names))
+            .map_err(http_error)?;
+        Ok(to_py_systems(systems))
+    }
+
+    /// Systems between min_radius and radius ly of a point.
+    #[pyo3(signature = (x, y, z, radius, min_radius=0.0))]
+    fn sphere(
+        &self,
+        py: Python<'_>,
+        x: f64,
+        y: f64,
+        z: f64,
+        radius: f64,
+        min_radius: f64,
+    ) -> PyResult<Vec<PySystem>> {
+        let systems = py
+            .allow_threads(|| self.inner.sphere([x, y, z], radius, min_radius))
+            .map_err(http_error)?;
+        Ok(to_py_systems(systems))
+    }
+
+    /// system as a coroutine.
+    #[cfg(feature = "asyncio")]
+    fn system_async<'py>(slf: &Bound<'py, Self>, name: String) -> PyResult<Bound<'py, PyAny>> {
+        let client = slf.clone().unbind();
+        spawn_cancellable(slf.py(), move |_| {
+            let system = client.get().inner.system(&name).map_err(http_error)?;
+            Ok(system.map(PySystem::from))
+        })
+    }
+
+    /// systems as a coroutine.
+    #[cfg(feature = "asyncio")]
+    fn systems_async<'py>(
+        slf: &Bound<'py, Self>,
+        names: Vec<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let client = slf.clone().unbind();
+        spawn_cancellable(slf.py(), move |_| {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            let systems = client.get().inner.systems(&names).map_err(http_error)?;
+            Ok(to_py_systems(systems))
+        })
+    }
+
+    /// sphere as a coroutine.
+    #[cfg(feature = "asyncio")]
+    #[pyo3(signature = (x, y, z, radius, min_radius=0.0))]
+    fn sphere_async<'py>(
+        slf: &Bound<'py, Self>,
+        x: f64,
+        y: f64,
+        z: f64,
+        radius: f64,
+        min_radius: f64,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let client = slf.clone().unbind();
+        spawn_cancellable(slf.py(), move |_| {
+            let systems = client
+                .get()
+                .inner
+                .sphere([x, y, z], radius, min_radius)
+                .map_err(http_error)?;
+            Ok(to_py_systems(systems))
+        })
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEdsmClient>()?;
+    Ok(())
+}
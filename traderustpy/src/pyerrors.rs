@@ -0,0 +1,128 @@
+//! Python exceptions for the crate's errors, so callers can catch a category
+//! rather than matching on ValueError messages. Failures to open or read a
+//! file still raise the builtin IOError.
+//!
+//! Each category also subclasses the builtin exception it replaced, so
+//! ParseError, RouteError and SpatialError are ValueErrors and ImportError_
+//! is an IOError, and code written before the categories existed still
+//! catches them.
+//!
+//! A ParseError about a file or document also has `offset`, `line`,
+//! `column` and `snippet` attributes locating the failure.
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyDict, PyType};
+use pyo3::{PyErrArguments, PyTypeInfo};
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+use traderusty_core::http::HttpError;
+use traderusty_core::span::Span;
+
+create_exception!(
+    traderusty,
+    TradeRustyError,
+    PyException,
+    "Base class of the errors raised by traderusty."
+);
+
+/// An exception class deriving from both TradeRustyError and a builtin,
+/// which create_exception! can't make as it takes a single base.
+macro_rules! category {
+    ($name:ident, $builtin:ty, $doc:literal) => {
+        #[doc = $doc]
+        pub struct $name;
+
+        impl $name {
+            pub fn type_object(py: Python<'_>) -> &Bound<'_, PyType> {
+                static TYPE: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+                TYPE.get_or_init(py, || {
+                    category_type(py, stringify!($name), <$builtin>::type_object(py), $doc)
+                        .expect(concat!("failed to create ", stringify!($name)))
+                })
+                .bind(py)
+            }
+
+            pub fn new_err<A>(args: A) -> PyErr
+            where
+                A: PyErrArguments + Send + Sync + 'static,
+            {
+                Python::with_gil(|py| PyErr::from_type(Self::type_object(py).clone(), args))
+            }
+        }
+    };
+}
+
+/// `type(name, (TradeRustyError, builtin), {...})`.
+fn category_type(
+    py: Python<'_>,
+    name: &str,
+    builtin: Bound<'_, PyType>,
+    doc: &str,
+) -> PyResult<Py<PyType>> {
+    let dict = PyDict::new(py);
+    dict.set_item("__doc__", doc)?;
+    dict.set_item("__module__", "traderusty")?;
+    let bases = (TradeRustyError::type_object(py), builtin);
+    Ok(PyType::type_object(py)
+        .call1((name, bases, dict))?
+        .downcast_into::<PyType>()?
+        .unbind())
+}
+
+category!(
+    ParseError,
+    PyValueError,
+    "Malformed input: a .prices line, supply reading, name or JSON document."
+);
+category!(
+    ImportError_,
+    PyIOError,
+    "Writing to or migrating the database failed."
+);
+category!(
+    RouteError,
+    PyValueError,
+    "An invalid route, ship or route export."
+);
+category!(
+    SpatialError,
+    PyValueError,
+    "Invalid coordinates, sectors or boxels."
+);
+
+/// A ParseError with the attributes of a span. Should setting them fail,
+/// the error is raised without them rather than replaced by that failure.
+pub fn parse_error(message: String, span: &Span) -> PyErr {
+    Python::with_gil(|py| {
+        let err = ParseError::new_err(message);
+        let value = err.value(py);
+        let _ = value
+            .setattr("offset", span.offset)
+            .and_then(|_| value.setattr("line", span.line))
+            .and_then(|_| value.setattr("column", span.column))
+            .and_then(|_| value.setattr("snippet", &span.snippet));
+        err
+    })
+}
+
+/// IOError for a failed request, ParseError for a response that wasn't
+/// what was expected.
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+pub fn http_error(e: HttpError) -> PyErr {
+    match e {
+        HttpError::Json(_) => ParseError::new_err(format!("{}", e)),
+        _ => pyo3::exceptions::PyIOError::new_err(format!("{}", e)),
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add("TradeRustyError", py.get_type::<TradeRustyError>())?;
+    m.add("ParseError", ParseError::type_object(py))?;
+    m.add("ImportError_", ImportError_::type_object(py))?;
+    m.add("RouteError", RouteError::type_object(py))?;
+    m.add("SpatialError", SpatialError::type_object(py))?;
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! Python bindings for the Inara batch export.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use traderusty_core::inara::{InaraBatch, InaraHeader};
+
+use crate::pymarket::PyMarketSnapshot;
+
+/// Market snapshots as the JSON body of an Inara batch API request.
+#[pyclass(name = "InaraBatch")]
+pub struct PyInaraBatch {
+    inner: InaraBatch,
+}
+
+#[pymethods]
+impl PyInaraBatch {
+    #[new]
+    #[pyo3(signature = (
+        app_name, app_version, api_key,
+        commander_name=None, commander_frontier_id=None, is_being_developed=false,
+    ))]
+    fn new(
+        app_name: String,
+        app_version: String,
+        api_key: String,
+        commander_name: Option<String>,
+        commander_frontier_id: Option<String>,
+        is_being_developed: bool,
+    ) -> Self {
+        Self {
+            inner: InaraBatch::new(InaraHeader {
+                app_name,
+                app_version,
+                is_being_developed,
+                api_key,
+                commander_name,
+                commander_frontier_id,
+            }),
+        }
+    }
+
+    /// Adds an event carrying a station's market.
+    fn add_market(
+        &mut self,
+        event_name: &str,
+        system: &str,
+        station: &str,
+        snapshot: &PyMarketSnapshot,
+    ) {
+        self.inner
+            .push_market(event_name, system, station, &snapshot.inner);
+    }
+
+    /// The batch as JSON; pretty-printed if indent is given.
+    #[pyo3(signature = (indent=None))]
+    fn to_json(&self, indent: Option<usize>) -> PyResult<String> {
+        self.inner
+            .to_json(indent)
+            .map_err(|e| PyValueError::new_err(format!("{}", e)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyInaraBatch>()?;
+    Ok(())
+}
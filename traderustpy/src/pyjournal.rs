@@ -0,0 +1,302 @@
+//! Python bindings for the files the game writes when docked.
+
+use std::fs;
+
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use traderusty_core::journal::{
+    MarketFile, ModuleListing, OutfittingFile, ShipListing, ShipyardFile,
+};
+use traderusty_core::options::ParseOptions;
+use traderusty_core::span::Span;
+
+use crate::pyerrors::parse_error;
+use crate::pymarket::PyMarketSnapshot;
+use crate::{FsPath, PyParseOptions};
+
+fn read_json(path: &FsPath) -> PyResult<String> {
+    fs::read_to_string(&path.0)
+        .map_err(|e| PyIOError::new_err(format!("{}: {}", path.0.display(), e)))
+}
+
+fn parse_json<T>(
+    json: &str,
+    parse_options: Option<PyRef<'_, PyParseOptions>>,
+    parse: fn(&str, &ParseOptions) -> serde_json::Result<T>,
+) -> PyResult<T> {
+    parse(json, &crate::parse_options(parse_options)).map_err(|e| {
+        parse_error(
+            format!("{}", e),
+            &Span::at_line_column(json, e.line(), e.column()),
+        )
+    })
+}
+
+/// The docked station's market, parsed from the game's Market.json.
+#[pyclass(name = "MarketFile", frozen)]
+pub struct PyMarketFile {
+    pub inner: MarketFile,
+}
+
+#[pymethods]
+impl PyMarketFile {
+    /// Reads a Market.json file.
+    #[staticmethod]
+    #[pyo3(signature = (path, parse_options=None))]
+    fn load(path: FsPath, parse_options: Option<PyRef<'_, PyParseOptions>>) -> PyResult<Self> {
+        Self::from_json(&read_json(&path)?, parse_options)
+    }
+
+    /// Parses the contents of a Market.json file.
+    #[staticmethod]
+    #[pyo3(signature = (json, parse_options=None))]
+    fn from_json(json: &str, parse_options: Option<PyRef<'_, PyParseOptions>>) -> PyResult<Self> {
+        let inner = parse_json(json, parse_options, MarketFile::from_json_with)?;
+        Ok(Self { inner })
+    }
+
+    /// Entries dropped because they didn't parse, when parsed leniently.
+    #[getter]
+    fn skipped(&self) -> usize {
+        self.inner.skipped
+    }
+
+    #[getter]
+    fn station(&self) -> &str {
+        &self.inner.station
+    }
+
+    #[getter]
+    fn system(&self) -> &str {
+        &self.inner.system
+    }
+
+    /// The market, keyed by the station's MarketID.
+    #[getter]
+    fn snapshot(&self) -> PyMarketSnapshot {
+        PyMarketSnapshot {
+            inner: self.inner.snapshot.clone(),
+        }
+    }
+
+    /// Names of listed items that aren't in the commodity table.
+    #[getter]
+    fn unknown_items(&self) -> Vec<String> {
+        self.inner.unknown_items.clone()
+    }
+}
+
+impl From<MarketFile> for PyMarketFile {
+    fn from(inner: MarketFile) -> Self {
+        Self { inner }
+    }
+}
+
+/// A module for sale: FDev's id, its lowercase symbol and the price.
+#[pyclass(name = "ModuleListing", frozen)]
+#[derive(Clone)]
+pub struct PyModuleListing {
+    inner: ModuleListing,
+}
+
+#[pymethods]
+impl PyModuleListing {
+    #[getter]
+    fn id(&self) -> u64 {
+        self.inner.id
+    }
+
+    #[getter]
+    fn symbol(&self) -> &str {
+        &self.inner.symbol
+    }
+
+    #[getter]
+    fn price(&self) -> i64 {
+        self.inner.price
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ModuleListing(id={}, symbol={:?}, price={})",
+            self.inner.id, self.inner.symbol, self.inner.price
+        )
+    }
+}
+
+/// The docked station's outfitting, parsed from the game's Outfitting.json.
+#[pyclass(name = "OutfittingFile", frozen)]
+pub struct PyOutfittingFile {
+    pub inner: OutfittingFile,
+}
+
+#[pymethods]
+impl PyOutfittingFile {
+    /// Reads an Outfitting.json file.
+    #[staticmethod]
+    #[pyo3(signature = (path, parse_options=None))]
+    fn load(path: FsPath, parse_options: Option<PyRef<'_, PyParseOptions>>) -> PyResult<Self> {
+        Self::from_json(&read_json(&path)?, parse_options)
+    }
+
+    /// Parses the contents of an Outfitting.json file.
+    #[staticmethod]
+    #[pyo3(signature = (json, parse_options=None))]
+    fn from_json(json: &str, parse_options: Option<PyRef<'_, PyParseOptions>>) -> PyResult<Self> {
+        let inner = parse_json(json, parse_options, OutfittingFile::from_json_with)?;
+        Ok(Self { inner })
+    }
+
+    /// Entries dropped because they didn't parse, when parsed leniently.
+    #[getter]
+    fn skipped(&self) -> usize {
+        self.inner.skipped
+    }
+
+    #[getter]
+    fn market_id(&self) -> u32 {
+        self.inner.market_id.0
+    }
+
+    #[getter]
+    fn timestamp(&self) -> i64 {
+        self.inner.timestamp
+    }
+
+    #[getter]
+    fn station(&self) -> &str {
+        &self.inner.station
+    }
+
+    #[getter]
+    fn system(&self) -> &str {
+        &self.inner.system
+    }
+
+    #[getter]
+    fn modules(&self) -> Vec<PyModuleListing> {
+        self.inner
+            .modules
+            .iter()
+            .map(|inner| PyModuleListing {
+                inner: inner.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A ship for sale: FDev's id, its lowercase symbol, the display name if the
+/// game gave one, and the price.
+#[pyclass(name = "ShipListing", frozen)]
+#[derive(Clone)]
+pub struct PyShipListing {
+    inner: ShipListing,
+}
+
+#[pymethods]
+impl PyShipListing {
+    #[getter]
+    fn id(&self) -> u64 {
+        self.inner.id
+    }
+
+    #[getter]
+    fn symbol(&self) -> &str {
+        &self.inner.symbol
+    }
+
+    #[getter]
+    fn name(&self) -> Option<&str> {
+        self.inner.name.as_deref()
+    }
+
+    #[getter]
+    fn price(&self) -> i64 {
+        self.inner.price
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ShipListing(id={}, symbol={:?}, name={:?}, price={})",
+            self.inner.id, self.inner.symbol, self.inner.name, self.inner.price
+        )
+    }
+}
+
+/// The docked station's shipyard, parsed from the game's Shipyard.json.
+#[pyclass(name = "ShipyardFile", frozen)]
+pub struct PyShipyardFile {
+    pub inner: ShipyardFile,
+}
+
+#[pymethods]
+impl PyShipyardFile {
+    /// Reads a Shipyard.json file.
+    #[staticmethod]
+    #[pyo3(signature = (path, parse_options=None))]
+    fn load(path: FsPath, parse_options: Option<PyRef<'_, PyParseOptions>>) -> PyResult<Self> {
+        Self::from_json(&read_json(&path)?, parse_options)
+    }
+
+    /// Parses the contents of a Shipyard.json file.
+    #[staticmethod]
+    #[pyo3(signature = (json, parse_options=None))]
+    fn from_json(json: &str, parse_options: Option<PyRef<'_, PyParseOptions>>) -> PyResult<Self> {
+        let inner = parse_json(json, parse_options, ShipyardFile::from_json_with)?;
+        Ok(Self { inner })
+    }
+
+    /// Entries dropped because they didn't parse, when parsed leniently.
+    #[getter]
+    fn skipped(&self) -> usize {
+        self.inner.skipped
+    }
+
+    #[getter]
+    fn market_id(&self) -> u32 {
+        self.inner.market_id.0
+    }
+
+    #[getter]
+    fn timestamp(&self) -> i64 {
+        self.inner.timestamp
+    }
+
+    #[getter]
+    fn station(&self) -> &str {
+        &self.inner.station
+    }
+
+    #[getter]
+    fn system(&self) -> &str {
+        &self.inner.system
+    }
+
+    #[getter]
+    fn ships(&self) -> Vec<PyShipListing> {
+        self.inner
+            .ships
+            .iter()
+            .map(|inner| PyShipListing {
+                inner: inner.clone(),
+            })
+            .collect()
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMarketFile>()?;
+    m.add_class::<PyModuleListing>()?;
+    m.add_class::<PyOutfittingFile>()?;
+    m.add_class::<PyShipListing>()?;
+    m.add_class::<PyShipyardFile>()?;
+    Ok(())
+}
@@ -0,0 +1,222 @@
+//! Python bindings for reading lines from the end of a file.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyIOError, PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use traderusty_core::lines::{self, LineIndex, BLOCK_SIZE};
+
+use crate::{read_options, FsPath, PyReadOptions};
+
+/// Number of lines read per trip into Rust.
+const BATCH_SIZE: usize = 1000;
+
+fn io_error(e: std::io::Error) -> PyErr {
+    PyIOError::new_err(format!("{}", e))
+}
+
+/// Returns the last n lines of a file (without line endings), reading
+/// backwards from the end rather than through the whole file.
+#[pyfunction]
+fn tail_lines(py: Python<'_>, path: FsPath, n: usize) -> PyResult<Vec<String>> {
+    py.allow_threads(|| lines::tail_lines(&path.0, n))
+        .map_err(io_error)
+}
+
+/// Iterates over the lines of a file from the last to the first, without
+/// line endings, reading backwards block_size bytes at a time. Handy for
+/// finding the newest entry of an append-only file without reading the
+/// rest. Use it in a `with` block (or call close()) to release the file
+/// promptly.
+#[pyclass(name = "RevLines")]
+pub struct PyRevLines {
+    /// None once closed.
+    lines: Option<lines::RevLines<File>>,
+    pending: VecDeque<std::io::Result<String>>,
+}
+
+#[pymethods]
+impl PyRevLines {
+    #[new]
+    #[pyo3(signature = (path, block_size=BLOCK_SIZE))]
+    fn new(path: FsPath, block_size: usize) -> PyResult<Self> {
+        Ok(Self {
+            lines: Some(lines::rev_lines(path.0, block_size).map_err(io_error)?),
+            pending: VecDeque::new(),
+        })
+    }
+
+    #[getter]
+    fn closed(&self) -> bool {
+        self.lines.is_none()
+    }
+
+    /// Closes the file; closing twice is harmless.
+    fn close(&mut self) {
+        self.lines = None;
+        self.pending.clear();
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> bool {
+        self.close();
+        false
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<String>> {
+        let Self { lines, pending } = self;
+        let lines = lines
+            .as_mut()
+            .ok_or_else(|| PyValueError::new_err("I/O operation on closed reader"))?;
+        if pending.is_empty() {
+            py.allow_threads(|| pending.extend(lines.by_ref().take(BATCH_SIZE)));
+        }
+        pending.pop_front().transpose().map_err(io_error)
+    }
+}
+
+/// Where each line of a file starts, built by reading the file once. Pass
+/// it to read_line_at and read_lines_at to jump straight to a line. An index
+/// can be saved to a sidecar file (by default the file's name plus
+/// ".lineidx") and loaded again while the file is unchanged.
+#[pyclass(name = "LineIndex", frozen)]
+pub struct PyLineIndex {
+    inner: LineIndex,
+}
+
+#[pymethods]
+impl PyLineIndex {
+    #[new]
+    #[pyo3(signature = (path, options=None))]
+    fn new(
+        py: Python<'_>,
+        path: FsPath,
+        options: Option<PyRef<'_, PyReadOptions>>,
+    ) -> PyResult<Self> {
+        let options = read_options(options);
+        let inner = py
+            .allow_threads(|| LineIndex::build(&path.0, &options))
+            .map_err(io_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Loads the index from its sidecar file if that's still current,
+    /// otherwise indexes the file and saves the sidecar for next time.
+    #[staticmethod]
+    #[pyo3(signature = (path, index_path=None, options=None))]
+    fn open(
+        py: Python<'_>,
+        path: FsPath,
+        index_path: Option<FsPath>,
+        options: Option<PyRef<'_, PyReadOptions>>,
+    ) -> PyResult<Self> {
+        let options = read_options(options);
+        let index_path = sidecar_path(&path, index_path);
+        let inner = py
+            .allow_threads(|| LineIndex::open(&path.0, index_path, &options))
+            .map_err(io_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Maps the index from its sidecar file, or returns None if there isn't
+    /// one or the file has changed since it was saved.
+    #[staticmethod]
+    #[pyo3(signature = (path, index_path=None))]
+    fn load(py: Python<'_>, path: FsPath, index_path: Option<FsPath>) -> PyResult<Option<Self>> {
+        let index_path = sidecar_path(&path, index_path);
+        let inner = py
+            .allow_threads(|| LineIndex::load(&path.0, index_path))
+            .map_err(io_error)?;
+        Ok(inner.map(|inner| Self { inner }))
+    }
+
+    /// Writes the index of the file at path to a sidecar file.
+    #[pyo3(signature = (path, index_path=None))]
+    fn save(&self, py: Python<'_>, path: FsPath, index_path: Option<FsPath>) -> PyResult<()> {
+        let index_path = sidecar_path(&path, index_path);
+        py.allow_threads(|| self.inner.save(&path.0, index_path))
+            .map_err(io_error)
+    }
+
+    /// True if the index was loaded from a sidecar file.
+    #[getter]
+    fn mapped(&self) -> bool {
+        self.inner.is_mapped()
+    }
+
+    /// Byte offset line n starts at.
+    fn offset(&self, n: usize) -> PyResult<u64> {
+        self.inner
+            .offset(n)
+            .ok_or_else(|| PyIndexError::new_err("line index out of range"))
+    }
+
+    /// Length of the file when it was indexed.
+    #[getter]
+    fn file_len(&self) -> u64 {
+        self.inner.file_len()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "LineIndex(lines={}, file_len={})",
+            self.inner.len(),
+            self.inner.file_len()
+        )
+    }
+}
+
+fn sidecar_path(path: &FsPath, index_path: Option<FsPath>) -> PathBuf {
+    index_path.map_or_else(|| lines::index_path(&path.0), |index_path| index_path.0)
+}
+
+/// Returns line n (0-based) of a file, found with its LineIndex. Raises
+/// IndexError past the last line and IOError if the file has changed size
+/// since it was indexed.
+#[pyfunction]
+fn read_line_at(path: FsPath, index: &PyLineIndex, n: usize) -> PyResult<String> {
+    lines::read_line_at(path.0, &index.inner, n)
+        .map_err(io_error)?
+        .ok_or_else(|| PyIndexError::new_err("line index out of range"))
+}
+
+/// Returns up to count lines of a file from line start (0-based), found
+/// with its LineIndex; fewer if the file ends first.
+#[pyfunction]
+fn read_lines_at(
+    py: Python<'_>,
+    path: FsPath,
+    index: &PyLineIndex,
+    start: usize,
+    count: usize,
+) -> PyResult<Vec<String>> {
+    py.allow_threads(|| lines::read_lines_at(&path.0, &index.inner, start, count))
+        .map_err(io_error)
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyRevLines>()?;
+    m.add_class::<PyLineIndex>()?;
+    m.add_function(wrap_pyfunction!(read_line_at, m)?)?;
+    m.add_function(wrap_pyfunction!(read_lines_at, m)?)?;
+    m.add_function(wrap_pyfunction!(tail_lines, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Forwards `tracing` events from the Rust side to Python's `logging` module,
+//! so that long-running operations show up in the host application's logs.
+//!
+//! Events are sent to the logger named after the module that emitted them,
+//! e.g. "traderusty.rusty", with the names of any enclosing spans prefixed to
+//! the message.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Once;
+
+use pyo3::prelude::*;
+use tracing::field::{Field, Visit};
+use tracing::subscriber::Interest;
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, Filter, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+// Python's standard logging levels; TRACE doesn't have one so we use 5.
+const PY_TRACE: u32 = 5;
+const PY_DEBUG: u32 = 10;
+pub const PY_INFO: u32 = 20;
+const PY_WARNING: u32 = 30;
+const PY_ERROR: u32 = 40;
+
+/// Python level below which events are discarded before taking the GIL;
+/// u32::MAX means forwarding is disabled.
+static MIN_PY_LEVEL: AtomicU32 = AtomicU32::new(u32::MAX);
+static INSTALL: Once = Once::new();
+
+/// Maps a tracing level onto the equivalent Python logging level.
+pub fn python_level(level: &Level) -> u32 {
+    match *level {
+        Level::TRACE => PY_TRACE,
+        Level::DEBUG => PY_DEBUG,
+        Level::INFO => PY_INFO,
+        Level::WARN => PY_WARNING,
+        Level::ERROR => PY_ERROR,
+    }
+}
+
+/// Maps an event target like "traderusty::rusty" to a logger name.
+pub fn logger_name(target: &str) -> String {
+    target.replace("::", ".")
+}
+
+/// Collects an event's message and fields into a single line.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+}
+
+/// Lets through spans and events at or above a Python level held in an
+/// atomic, so each layer can be switched on and off on its own.
+pub struct MinLevel(pub &'static AtomicU32);
+
+impl<S> Filter<S> for MinLevel {
+    fn callsite_enabled(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The level can be changed at runtime, so don't let tracing cache a verdict.
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: &Context<'_, S>) -> bool {
+        python_level(metadata.level()) >= self.0.load(Ordering::Relaxed)
+    }
+}
+
+struct PythonLoggingLayer;
+
+impl<S> Layer<S> for PythonLoggingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let mut line = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                line.push_str(span.name());
+                line.push_str(": ");
+            }
+        }
+        line.push_str(&visitor.message);
+        line.push_str(&visitor.fields);
+
+        let level = python_level(metadata.level());
+        let name = logger_name(metadata.target());
+        Python::with_gil(|py| {
+            let result = py
+                .import("logging")
+                .and_then(|logging| logging.call_method1("getLogger", (name,)))
+                .and_then(|logger| logger.call_method1("log", (level, line)));
+            // There's nowhere sensible to report a failure to log to.
+            if let Err(e) = result {
+                e.print(py);
+            }
+        });
+    }
+}
+
+/// Installs the subscriber feeding Python, once per process. Its layers
+/// stay quiet until their levels are set.
+pub fn install() -> PyResult<()> {
+    let mut result = Ok(());
+    INSTALL.call_once(|| {
+        let subscriber = tracing_subscriber::registry()
+            .with(PythonLoggingLayer.with_filter(MinLevel(&MIN_PY_LEVEL)));
+        #[cfg(feature = "opentelemetry")]
+        let subscriber = subscriber.with(crate::pyotel::layer());
+        result = tracing::subscriber::set_global_default(subscriber).map_err(|e| {
+            pyo3::exceptions::PyRuntimeError::new_err(format!(
+                "unable to install logging bridge: {}",
+                e
+            ))
+        });
+    });
+    result
+}
+
+/// Starts forwarding events at or above the given Python logging level to the
+/// `logging` module. Can be called again to change the level.
+#[pyfunction]
+#[pyo3(signature = (level=PY_INFO))]
+pub fn enable_logging(level: u32) -> PyResult<()> {
+    MIN_PY_LEVEL.store(level, Ordering::Relaxed);
+    install()
+}
+
+/// Stops forwarding events to Python.
+#[pyfunction]
+pub fn disable_logging() {
+    MIN_PY_LEVEL.store(u32::MAX, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python_level() {
+        assert_eq!(python_level(&Level::TRACE), 5);
+        assert_eq!(python_level(&Level::DEBUG), 10);
+        assert_eq!(python_level(&Level::INFO), 20);
+        assert_eq!(python_level(&Level::WARN), 30);
+        assert_eq!(python_level(&Level::ERROR), 40);
+    }
+
+    #[test]
+    fn test_logger_name() {
+        assert_eq!(logger_name("traderusty"), "traderusty");
+        assert_eq!(logger_name("traderusty::rusty"), "traderusty.rusty");
+    }
+}
@@ -0,0 +1,486 @@
+//! Python bindings for the market types.
+
+use std::sync::RwLock;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use traderusty_core::commodities::{self, Category, Commodity};
+use traderusty_core::export;
+use traderusty_core::ids::{ItemId, StationId};
+use traderusty_core::market::{self, MarketDiff, MarketSnapshot, StationItem};
+use traderusty_core::store::MarketStore;
+use traderusty_core::trade::LoadCache;
+
+/// One commodity's listing at a station (a StationItem row).
+#[pyclass(name = "StationItem", frozen)]
+#[derive(Clone)]
+pub struct PyStationItem {
+    pub inner: StationItem,
+}
+
+#[pymethods]
+impl PyStationItem {
+    #[new]
+    #[pyo3(signature = (
+        station_id, item_id,
+        demand_price=0, demand_units=0, demand_level=0,
+        supply_price=0, supply_units=0, supply_level=0,
+        modified=0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        station_id: u32,
+        item_id: u32,
+        demand_price: i32,
+        demand_units: i64,
+        demand_level: i32,
+        supply_price: i32,
+        supply_units: i64,
+        supply_level: i32,
+        modified: i64,
+    ) -> Self {
+        Self {
+            inner: StationItem {
+                station_id: StationId(station_id),
+                item_id: ItemId(item_id),
+                demand_price,
+                demand_units,
+                demand_level,
+                supply_price,
+                supply_units,
+                supply_level,
+                modified,
+            },
+        }
+    }
+
+    #[getter]
+    fn station_id(&self) -> u32 {
+        self.inner.station_id.0
+    }
+
+    #[getter]
+    fn item_id(&self) -> u32 {
+        self.inner.item_id.0
+    }
+
+    #[getter]
+    fn demand_price(&self) -> i32 {
+        self.inner.demand_price
+    }
+
+    #[getter]
+    fn demand_units(&self) -> i64 {
+        self.inner.demand_units
+    }
+
+    #[getter]
+    fn demand_level(&self) -> i32 {
+        self.inner.demand_level
+    }
+
+    #[getter]
+    fn supply_price(&self) -> i32 {
+        self.inner.supply_price
+    }
+
+    #[getter]
+    fn supply_units(&self) -> i64 {
+        self.inner.supply_units
+    }
+
+    #[getter]
+    fn supply_level(&self) -> i32 {
+        self.inner.supply_level
+    }
+
+    #[getter]
+    fn modified(&self) -> i64 {
+        self.inner.modified
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+
+    fn __repr__(&self) -> String {
+        let i = &self.inner;
+        format!(
+            "StationItem(station_id={}, item_id={}, demand_price={}, demand_units={}, demand_level={}, supply_price={}, supply_units={}, supply_level={}, modified={})",
+            i.station_id, i.item_id, i.demand_price, i.demand_units, i.demand_level,
+            i.supply_price, i.supply_units, i.supply_level, i.modified,
+        )
+    }
+}
+
+impl From<StationItem> for PyStationItem {
+    fn from(inner: StationItem) -> Self {
+        Self { inner }
+    }
+}
+
+/// A station's complete commodity market at a point in time.
+#[pyclass(name = "MarketSnapshot", frozen)]
+#[derive(Clone)]
+pub struct PyMarketSnapshot {
+    pub inner: MarketSnapshot,
+}
+
+#[pymethods]
+impl PyMarketSnapshot {
+    #[new]
+    fn new(station_id: u32, timestamp: i64, items: Vec<PyRef<'_, PyStationItem>>) -> Self {
+        let items = items.iter().map(|i| i.inner.clone()).collect();
+        Self {
+            inner: MarketSnapshot::new(StationId(station_id), timestamp, items),
+        }
+    }
+
+    #[getter]
+    fn station_id(&self) -> u32 {
+        self.inner.station_id.0
+    }
+
+    #[getter]
+    fn timestamp(&self) -> i64 {
+        self.inner.timestamp
+    }
+
+    #[getter]
+    fn items(&self) -> Vec<PyStationItem> {
+        self.inner.items().iter().cloned().map(Into::into).collect()
+    }
+
+    /// Returns the listing for an item, or None if the station doesn't list it.
+    fn get(&self, item_id: u32) -> Option<PyStationItem> {
+        self.inner.get(ItemId(item_id)).cloned().map(Into::into)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.items().len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MarketSnapshot(station_id={}, timestamp={}, items=<{}>)",
+            self.inner.station_id,
+            self.inner.timestamp,
+            self.inner.items().len()
+        )
+    }
+}
+
+/// Listings added, removed, and re-priced between two snapshots.
+#[pyclass(name = "MarketDiff", frozen)]
+pub struct PyMarketDiff {
+    inner: MarketDiff,
+}
+
+#[pymethods]
+impl PyMarketDiff {
+    #[getter]
+    fn added(&self) -> Vec<PyStationItem> {
+        self.inner.added.iter().cloned().map(Into::into).collect()
+    }
+
+    #[getter]
+    fn removed(&self) -> Vec<PyStationItem> {
+        self.inner.removed.iter().cloned().map(Into::into).collect()
+    }
+
+    /// List of (old, new) listing pairs whose prices changed.
+    #[getter]
+    fn changed(&self) -> Vec<(PyStationItem, PyStationItem)> {
+        self.inner
+            .changed
+            .iter()
+            .map(|(o, n)| (o.clone().into(), n.clone().into()))
+            .collect()
+    }
+
+    fn __bool__(&self) -> bool {
+        !self.inner.is_empty()
+    }
+}
+
+/// An entry of the built-in commodity table: the FDev id, which is also
+/// TradeDangerous' item_id, the FDev symbol, the TradeDangerous display name
+/// and category.
+#[pyclass(name = "Commodity", frozen)]
+#[derive(Clone)]
+pub struct PyCommodity {
+    inner: Commodity,
+}
+
+#[pymethods]
+impl PyCommodity {
+    #[getter]
+    fn id(&self) -> u32 {
+        self.inner.id.0
+    }
+
+    #[getter]
+    fn symbol(&self) -> &'static str {
+        self.inner.symbol
+    }
+
+    #[getter]
+    fn name(&self) -> &'static str {
+        self.inner.name
+    }
+
+    #[getter]
+    fn category(&self) -> &'static str {
+        self.inner.category.name()
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+
+    fn __hash__(&self) -> u64 {
+        self.inner.id.0 as u64
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Commodity(id={}, symbol={:?}, name={:?}, category={:?})",
+            self.inner.id,
+            self.inner.symbol,
+            self.inner.name,
+            self.inner.category.name()
+        )
+    }
+}
+
+impl From<Commodity> for PyCommodity {
+    fn from(inner: Commodity) -> Self {
+        Self { inner }
+    }
+}
+
+/// Looks up a commodity by FDev symbol, journal name ("$gold_name;"), EDDN
+/// name or display name, ignoring case, spacing and punctuation.
+#[pyfunction]
+fn canonical_commodity(name: &str) -> Option<PyCommodity> {
+    commodities::canonical_commodity(name).map(PyCommodity::from)
+}
+
+/// Looks up a commodity by item id.
+#[pyfunction]
+fn commodity_by_id(id: u32) -> Option<PyCommodity> {
+    commodities::commodity_by_id(ItemId(id)).map(PyCommodity::from)
+}
+
+fn category(name: &str) -> PyResult<Category> {
+    Category::from_name(name)
+        .ok_or_else(|| PyValueError::new_err(format!("unknown commodity category: {:?}", name)))
+}
+
+/// Every commodity in the built-in table, or in one category, by category.
+#[pyfunction(name = "commodities", signature = (category=None))]
+fn all_commodities(category: Option<&str>) -> PyResult<Vec<PyCommodity>> {
+    let category = category.map(self::category).transpose()?;
+    Ok(commodities::commodities()
+        .filter(|commodity| category.is_none_or(|category| commodity.category == category))
+        .map(PyCommodity::from)
+        .collect())
+}
+
+/// The names of the commodity categories.
+#[pyfunction]
+fn commodity_categories() -> Vec<&'static str> {
+    Category::ALL.iter().map(Category::name).collect()
+}
+
+/// Compares two snapshots of a station's market.
+#[pyfunction]
+fn diff_markets(old: &PyMarketSnapshot, new: &PyMarketSnapshot) -> PyMarketDiff {
+    PyMarketDiff {
+        inner: market::diff(&old.inner, &new.inner),
+    }
+}
+
+/// Listings as CSV with a header row naming the StationItem columns.
+#[pyfunction]
+fn listings_to_csv(items: Vec<PyRef<'_, PyStationItem>>) -> String {
+    export::listings_csv(items.iter().map(|item| &item.inner))
+}
+
+/// All loaded station listings, indexed by station, commodity and grid cell.
+/// Safe to share between threads: readers run concurrently and writers take
+/// the store in turn.
+#[pyclass(name = "MarketStore", frozen)]
+#[derive(Default)]
+pub struct PyMarketStore {
+    inner: RwLock<MarketStore>,
+    /// Trade results computed from this store, reused until it changes.
+    pub loads: LoadCache,
+}
+
+fn to_py_items(items: Vec<StationItem>) -> Vec<PyStationItem> {
+    items.into_iter().map(Into::into).collect()
+}
+
+/// Station ids as Python sees them, plain ints.
+pub fn ids(stations: Vec<StationId>) -> Vec<u32> {
+    stations.into_iter().map(u32::from).collect()
+}
+
+impl PyMarketStore {
+    /// Runs `f` on the store under its read lock, taken without the GIL: a
+    /// thread waiting for the lock mustn't keep the GIL from one holding it.
+    pub fn with_read<R: Send>(
+        &self,
+        py: Python<'_>,
+        f: impl FnOnce(&MarketStore) -> R + Send,
+    ) -> R {
+        py.allow_threads(|| f(&self.inner.read().unwrap()))
+    }
+
+    /// Runs `f` on the store under its write lock, taken without the GIL.
+    fn with_write<R: Send>(
+        &self,
+        py: Python<'_>,
+        f: impl FnOnce(&mut MarketStore) -> R + Send,
+    ) -> R {
+        py.allow_threads(|| f(&mut self.inner.write().unwrap()))
+    }
+}
+
+#[pymethods]
+impl PyMarketStore {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers (or moves) a station in a system at the system's coordinates.
+    fn add_station(&self, py: Python<'_>, station_id: u32, system: &str, x: f64, y: f64, z: f64) {
+        self.with_write(py, |store| {
+            store.add_station(StationId(station_id), system, x, y, z)
+        });
+    }
+
+    fn station_system(&self, py: Python<'_>, station_id: u32) -> Option<String> {
+        self.with_read(py, |store| {
+            store
+                .station_system(StationId(station_id))
+                .map(str::to_string)
+        })
+    }
+
+    fn stations_in_system(&self, py: Python<'_>, system: &str) -> Vec<u32> {
+        ids(self.with_read(py, |store| store.stations_in_system(system)))
+    }
+
+    /// Adds a listing, replacing any existing one for the same station and item.
+    fn insert(&self, py: Python<'_>, item: &PyStationItem) {
+        let item = item.inner.clone();
+        self.with_write(py, |store| store.insert(item));
+    }
+
+    /// Replaces a station's whole market with a snapshot.
+    fn insert_snapshot(&self, py: Python<'_>, snapshot: &PyMarketSnapshot) {
+        self.with_write(py, |store| store.insert_snapshot(&snapshot.inner));
+    }
+
+    fn remove(&self, py: Python<'_>, station_id: u32, item_id: u32) -> Option<PyStationItem> {
+        self.with_write(py, |store| {
+            store.remove(StationId(station_id), ItemId(item_id))
+        })
+        .map(Into::into)
+    }
+
+    fn remove_market(&self, py: Python<'_>, station_id: u32) -> usize {
+        self.with_write(py, |store| store.remove_market(StationId(station_id)))
+    }
+
+    fn get(&self, py: Python<'_>, station_id: u32, item_id: u32) -> Option<PyStationItem> {
+        self.with_read(py, |store| {
+            store.get(StationId(station_id), ItemId(item_id))
+        })
+        .map(Into::into)
+    }
+
+    fn station_items(&self, py: Python<'_>, station_id: u32) -> Vec<PyStationItem> {
+        to_py_items(self.with_read(py, |store| store.station_items(StationId(station_id))))
+    }
+
+    /// Listings of a station in one commodity category, ordered by item.
+    fn station_items_in(
+        &self,
+        py: Python<'_>,
+        station_id: u32,
+        category: &str,
+    ) -> PyResult<Vec<PyStationItem>> {
+        let category = self::category(category)?;
+        Ok(to_py_items(self.with_read(py, |store| {
+            store.station_items_in(StationId(station_id), category)
+        })))
+    }
+
+    /// Listings of every item in a commodity category, ordered by item then
+    /// station.
+    fn category_listings(&self, py: Python<'_>, category: &str) -> PyResult<Vec<PyStationItem>> {
+        let category = self::category(category)?;
+        Ok(to_py_items(
+            self.with_read(py, |store| store.category_listings(category)),
+        ))
+    }
+
+    /// Listings you can buy an item from, cheapest first.
+    fn sellers_of(&self, py: Python<'_>, item_id: u32) -> Vec<PyStationItem> {
+        to_py_items(self.with_read(py, |store| store.sellers_of(ItemId(item_id))))
+    }
+
+    /// Listings you can sell an item to, best paying first.
+    fn buyers_of(&self, py: Python<'_>, item_id: u32) -> Vec<PyStationItem> {
+        to_py_items(self.with_read(py, |store| store.buyers_of(ItemId(item_id))))
+    }
+
+    /// Station ids in the stellar grid cell with the given key.
+    fn stations_in(&self, py: Python<'_>, grid_key: u64) -> Vec<u32> {
+        ids(self.with_read(py, |store| store.stations_in(grid_key)))
+    }
+
+    /// Station ids in the sector with the given sector id.
+    fn stations_in_sector(&self, py: Python<'_>, sector_id: u64) -> Vec<u32> {
+        ids(self.with_read(py, |store| store.stations_in_sector(sector_id)))
+    }
+
+    /// Counter that changes whenever the store is modified.
+    #[getter]
+    fn generation(&self, py: Python<'_>) -> u64 {
+        self.with_read(py, MarketStore::generation)
+    }
+
+    /// (hits, misses, entries) of the cache of trade results.
+    fn cache_stats(&self) -> (u64, u64, usize) {
+        self.loads.stats()
+    }
+
+    /// Discards cached trade results.
+    fn clear_cache(&self) {
+        self.loads.clear();
+    }
+
+    fn __len__(&self, py: Python<'_>) -> usize {
+        self.with_read(py, MarketStore::len)
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyStationItem>()?;
+    m.add_class::<PyMarketSnapshot>()?;
+    m.add_class::<PyMarketDiff>()?;
+    m.add_class::<PyMarketStore>()?;
+    m.add_class::<PyCommodity>()?;
+    m.add_function(wrap_pyfunction!(canonical_commodity, m)?)?;
+    m.add_function(wrap_pyfunction!(commodity_by_id, m)?)?;
+    m.add_function(wrap_pyfunction!(all_commodities, m)?)?;
+    m.add_function(wrap_pyfunction!(commodity_categories, m)?)?;
+    m.add_function(wrap_pyfunction!(diff_markets, m)?)?;
+    m.add_function(wrap_pyfunction!(listings_to_csv, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+//! Python bindings for the metrics registry.
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use traderusty_core::metrics;
+
+/// A snapshot of the module's counters (records_parsed, bytes_read,
+/// cache_hits, cache_misses, route_nodes_expanded) and, under "stages", the
+/// runs and total seconds of each timed stage that has run, e.g.
+/// "merge_prices_dir" or "import_prices.write". Counts are since the module
+/// was loaded or reset_metrics() was last called.
+#[pyfunction]
+fn get_metrics(py: Python<'_>) -> PyResult<PyObject> {
+    let snapshot = metrics::snapshot();
+    let dict = PyDict::new(py);
+    for (name, value) in snapshot.counters {
+        dict.set_item(name, value)?;
+    }
+    let stages = PyDict::new(py);
+    for (name, time) in snapshot.stages {
+        let stage = PyDict::new(py);
+        stage.set_item("runs", time.runs)?;
+        stage.set_item("seconds", time.total.as_secs_f64())?;
+        stages.set_item(name, stage)?;
+    }
+    dict.set_item("stages", stages)?;
+    Ok(dict.into_any().unbind())
+}
+
+/// Zeroes the counters and forgets the stage times.
+#[pyfunction]
+fn reset_metrics() {
+    metrics::reset();
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(get_metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(reset_metrics, m)?)?;
+    Ok(())
+}
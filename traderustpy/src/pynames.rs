@@ -0,0 +1,127 @@
+//! Python bindings for the name index.
+
+use pyo3::prelude::*;
+use traderusty_core::hash;
+use traderusty_core::intern::Interner;
+use traderusty_core::names::{self, Match, NameIndex};
+
+fn to_tuples(matches: Vec<Match>) -> Vec<(u64, String, f64)> {
+    matches
+        .into_iter()
+        .map(|m| (m.id, m.name, m.score))
+        .collect()
+}
+
+/// Substring and fuzzy lookup over system or station names. Matches are
+/// returned as (id, name, score) tuples.
+#[pyclass(name = "NameIndex")]
+pub struct PyNameIndex {
+    inner: NameIndex,
+}
+
+#[pymethods]
+impl PyNameIndex {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: NameIndex::new(),
+        }
+    }
+
+    fn insert(&mut self, id: u64, name: &str) {
+        self.inner.insert(id, name);
+    }
+
+    /// Names containing every word of the query, case-insensitively.
+    #[pyo3(signature = (query, limit=50))]
+    fn search(&self, query: &str, limit: usize) -> Vec<(u64, String, f64)> {
+        to_tuples(self.inner.search(query, limit))
+    }
+
+    /// Names ranked by trigram similarity to the query, best first.
+    #[pyo3(signature = (query, limit=10, threshold=0.3))]
+    fn similar(&self, query: &str, limit: usize, threshold: f64) -> Vec<(u64, String, f64)> {
+        to_tuples(self.inner.similar(query, limit, threshold))
+    }
+
+    /// Typo corrections within max_distance edits, as (id, name, distance)
+    /// tuples closest first.
+    #[pyo3(signature = (name, max_distance=2, limit=5))]
+    fn suggest(&self, name: &str, max_distance: usize, limit: usize) -> Vec<(u64, String, usize)> {
+        self.inner
+            .suggest(name, max_distance, limit)
+            .into_iter()
+            .map(|s| (s.id, s.name, s.distance))
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+/// Two-way mapping between names and small integer ids.
+#[pyclass(name = "Interner")]
+pub struct PyInterner {
+    inner: Interner,
+}
+
+#[pymethods]
+impl PyInterner {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: Interner::new(),
+        }
+    }
+
+    /// Returns the id for a name, assigning a new one if needed.
+    fn intern(&mut self, name: &str) -> u32 {
+        self.inner.intern(name)
+    }
+
+    /// Returns the id for a name, or None if it hasn't been interned.
+    fn get(&self, name: &str) -> Option<u32> {
+        self.inner.get(name)
+    }
+
+    /// Returns the name for an id, or None if the id is unknown.
+    fn resolve(&self, id: u32) -> Option<String> {
+        self.inner.resolve(id).map(str::to_string)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+/// The form names are matched in: NFKC normalized, case folded and with
+/// whitespace collapsed, so "WP 12", "Wp  12" and "ＷＰ　１２" are equal.
+#[pyfunction]
+fn canonical_name(name: &str) -> String {
+    names::canonical_name(name)
+}
+
+/// A 64-bit hash of a name's canonical form that is the same in every
+/// version: FNV-1a of its UTF-8 bytes, then splitmix64's finalizer. Safe
+/// to key caches on.
+#[pyfunction]
+fn name_hash(name: &str) -> u64 {
+    hash::name_hash(name)
+}
+
+/// name_hash of a station's full name, its canonical system and station
+/// names joined by "/".
+#[pyfunction]
+fn station_name_hash(system: &str, station: &str) -> u64 {
+    hash::station_name_hash(system, station)
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(canonical_name, m)?)?;
+    m.add_function(wrap_pyfunction!(name_hash, m)?)?;
+    m.add_function(wrap_pyfunction!(station_name_hash, m)?)?;
+    m.add_class::<PyNameIndex>()?;
+    m.add_class::<PyInterner>()?;
+    Ok(())
+}
@@ -0,0 +1,352 @@
+//! Mirrors `tracing` spans into OpenTelemetry through Python's
+//! `opentelemetry` API, so imports and route searches show up in whatever
+//! tracing backend the host application already exports to.
+//!
+//! Each span becomes an OpenTelemetry span started by the configured tracer,
+//! a child of its enclosing Rust span or, at the top, of whatever span is
+//! current in Python. Span fields become attributes, events inside a span
+//! become span events, and an error event marks the span failed. Rust code
+//! embedding the crate gets the same spans from tracing-opentelemetry.
+//!
+//! Spans are recorded on the Rust side, with their times, and a finished
+//! top-level span is handed with everything under it to an exporter thread
+//! that takes the GIL to replay it through the tracer. Only starting a
+//! top-level span takes the GIL on the traced thread, to look up the
+//! current Python span.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::pylogging::{self, MinLevel, PY_INFO};
+
+/// Python level below which spans aren't mirrored; u32::MAX means off.
+static MIN_PY_LEVEL: AtomicU32 = AtomicU32::new(u32::MAX);
+static TRACER: Mutex<Option<Py<PyAny>>> = Mutex::new(None);
+
+/// Top-level spans handed to the exporter thread and not yet exported.
+static PENDING: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+
+enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+/// A span's or event's fields, as OpenTelemetry attribute values.
+#[derive(Default)]
+struct Fields {
+    message: Option<String>,
+    values: Vec<(&'static str, Value)>,
+}
+
+impl Fields {
+    fn attributes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for (name, value) in &self.values {
+            match value {
+                Value::Bool(value) => dict.set_item(name, value)?,
+                Value::Int(value) => dict.set_item(name, value)?,
+                Value::Float(value) => dict.set_item(name, value)?,
+                Value::Str(value) => dict.set_item(name, value)?,
+            }
+        }
+        Ok(dict)
+    }
+}
+
+impl Visit for Fields {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.values.push((field.name(), Value::Bool(value)));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.values.push((field.name(), Value::Int(value)));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let value = i64::try_from(value).map_or_else(|_| Value::Str(value.to_string()), Value::Int);
+        self.values.push((field.name(), value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.values.push((field.name(), Value::Float(value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = Some(value.to_string()),
+            name => self.values.push((name, Value::Str(value.to_string()))),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message = Some(format!("{:?}", value)),
+            name => self.values.push((name, Value::Str(format!("{:?}", value)))),
+        }
+    }
+}
+
+/// Nanoseconds since the unix epoch, as OpenTelemetry takes times.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+struct SpanEvent {
+    time: u64,
+    message: String,
+    fields: Fields,
+    error: bool,
+}
+
+/// A span as recorded while it's open, kept in its extensions, and the
+/// spans that finished under it.
+struct Recorded {
+    name: &'static str,
+    target: &'static str,
+    start: u64,
+    end: u64,
+    fields: Fields,
+    events: Vec<SpanEvent>,
+    children: Vec<Recorded>,
+    /// For a top-level span, the Python span current when it started.
+    parent: Option<Py<PyAny>>,
+}
+
+impl Recorded {
+    /// Starts the OpenTelemetry span, replays the events and the children
+    /// under it, and ends it.
+    fn export(
+        self,
+        py: Python<'_>,
+        tracer: &Bound<'_, PyAny>,
+        parent: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<()> {
+        let trace = py.import("opentelemetry.trace")?;
+        let kwargs = PyDict::new(py);
+        if let Some(parent) = parent.or_else(|| self.parent.map(|parent| parent.into_bound(py))) {
+            kwargs.set_item(
+                "context",
+                trace.call_method1("set_span_in_context", (parent,))?,
+            )?;
+        }
+        let attributes = self.fields.attributes(py)?;
+        attributes.set_item("code.namespace", self.target)?;
+        kwargs.set_item("attributes", attributes)?;
+        kwargs.set_item("start_time", self.start)?;
+        let span = tracer.call_method("start_span", (self.name,), Some(&kwargs))?;
+        for event in self.events {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("timestamp", event.time)?;
+            let attributes = event.fields.attributes(py)?;
+            span.call_method("add_event", (&event.message, attributes), Some(&kwargs))?;
+            if event.error {
+                let error = trace.getattr("StatusCode")?.getattr("ERROR")?;
+                let status = trace.call_method1("Status", (error, &event.message))?;
+                span.call_method1("set_status", (status,))?;
+            }
+        }
+        for child in self.children {
+            child.export(py, tracer, Some(span.clone()))?;
+        }
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("end_time", self.end)?;
+        span.call_method("end", (), Some(&kwargs))?;
+        Ok(())
+    }
+}
+
+/// Exports the spans sent to it, in a thread of its own.
+fn exporter() -> &'static Mutex<Sender<Recorded>> {
+    static EXPORTER: OnceLock<Mutex<Sender<Recorded>>> = OnceLock::new();
+    EXPORTER.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Recorded>();
+        thread::Builder::new()
+            .name("traderusty-otel".to_string())
+            .spawn(move || {
+                for recorded in receiver {
+                    Python::with_gil(|py| {
+                        let tracer = TRACER
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .as_ref()
+                            .map(|tracer| tracer.clone_ref(py));
+                        if let Some(tracer) = tracer {
+                            // There's nowhere sensible to report a failure to trace to.
+                            if let Err(e) = recorded.export(py, tracer.bind(py), None) {
+                                e.print(py);
+                            }
+                        }
+                    });
+                    let (pending, exported) = &PENDING;
+                    *pending.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+                    exported.notify_all();
+                }
+            })
+            .expect("failed to start the OpenTelemetry exporter thread");
+        Mutex::new(sender)
+    })
+}
+
+/// The Python span that's current, to put a top-level span under.
+fn current_python_span() -> Option<Py<PyAny>> {
+    Python::with_gil(|py| {
+        let span = py
+            .import("opentelemetry.trace")
+            .and_then(|trace| trace.call_method0("get_current_span"));
+        match span {
+            Ok(span) => Some(span.unbind()),
+            Err(e) => {
+                e.print(py);
+                None
+            }
+        }
+    })
+}
+
+struct OpenTelemetryLayer;
+
+impl<S> Layer<S> for OpenTelemetryLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        let nested = span
+            .parent()
+            .is_some_and(|parent| parent.extensions().get::<Recorded>().is_some());
+        let parent = match nested {
+            true => None,
+            false => current_python_span(),
+        };
+        let metadata = attrs.metadata();
+        span.extensions_mut().insert(Recorded {
+            name: metadata.name(),
+            target: metadata.target(),
+            start: now(),
+            end: 0,
+            fields,
+            events: Vec::new(),
+            children: Vec::new(),
+            parent,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(recorded) = extensions.get_mut::<Recorded>() {
+            values.record(&mut recorded.fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(recorded) = extensions.get_mut::<Recorded>() else {
+            return;
+        };
+        let metadata = event.metadata();
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        recorded.events.push(SpanEvent {
+            time: now(),
+            message: fields
+                .message
+                .take()
+                .unwrap_or_else(|| metadata.name().to_string()),
+            fields,
+            error: *metadata.level() == Level::ERROR,
+        });
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(mut recorded) = span.extensions_mut().remove::<Recorded>() else {
+            return;
+        };
+        recorded.end = now();
+        if recorded.parent.is_none() {
+            if let Some(parent) = span.parent() {
+                if let Some(into) = parent.extensions_mut().get_mut::<Recorded>() {
+                    into.children.push(recorded);
+                    return;
+                }
+            }
+        }
+        let (pending, _) = &PENDING;
+        *pending.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        if exporter().lock().unwrap().send(recorded).is_err() {
+            *pending.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+        }
+    }
+}
+
+/// The layer for the subscriber `pylogging::install` sets up.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    OpenTelemetryLayer.with_filter(MinLevel(&MIN_PY_LEVEL))
+}
+
+/// Starts mirroring spans at or above the given Python logging level to
+/// OpenTelemetry, through `tracer` or else the "traderusty" tracer of the
+/// global tracer provider. Can be called again to change either.
+#[pyfunction]
+#[pyo3(signature = (level=PY_INFO, tracer=None))]
+fn enable_tracing(py: Python<'_>, level: u32, tracer: Option<Py<PyAny>>) -> PyResult<()> {
+    let tracer = match tracer {
+        Some(tracer) => tracer,
+        None => py
+            .import("opentelemetry.trace")?
+            .call_method1("get_tracer", ("traderusty",))?
+            .unbind(),
+    };
+    *TRACER.lock().unwrap_or_else(|e| e.into_inner()) = Some(tracer);
+    MIN_PY_LEVEL.store(level, Ordering::Relaxed);
+    pylogging::install()
+}
+
+/// Waits until the spans that have finished are exported to the tracer.
+#[pyfunction]
+fn flush_tracing(py: Python<'_>) {
+    py.allow_threads(|| {
+        let (pending, exported) = &PENDING;
+        let pending = pending.lock().unwrap_or_else(|e| e.into_inner());
+        drop(exported.wait_while(pending, |pending| *pending > 0));
+    });
+}
+
+/// Stops mirroring spans, once those that have finished are exported.
+/// Spans already started still end.
+#[pyfunction]
+fn disable_tracing(py: Python<'_>) {
+    MIN_PY_LEVEL.store(u32::MAX, Ordering::Relaxed);
+    flush_tracing(py);
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(enable_tracing, m)?)?;
+    m.add_function(wrap_pyfunction!(disable_tracing, m)?)?;
+    m.add_function(wrap_pyfunction!(flush_tracing, m)?)?;
+    Ok(())
+}
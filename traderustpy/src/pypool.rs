@@ -0,0 +1,25 @@
+//! Python bindings for the thread pool settings.
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use traderusty_core::pool;
+
+/// Runs the module's parallel work (merges, diffs, hashing, graph builds,
+/// trade searches) on `threads` threads from now on; 0 goes back to one per
+/// core. Those calls also take `threads=` to override it for one call.
+#[pyfunction]
+fn set_thread_count(threads: usize) -> PyResult<()> {
+    pool::set_thread_count(threads).map_err(|e| PyRuntimeError::new_err(e.to_string()))
+}
+
+/// The number of threads parallel work currently runs on.
+#[pyfunction]
+fn thread_count() -> usize {
+    pool::thread_count()
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(set_thread_count, m)?)?;
+    m.add_function(wrap_pyfunction!(thread_count, m)?)?;
+    Ok(())
+}
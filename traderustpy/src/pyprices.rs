@@ -0,0 +1,600 @@
+//! Python bindings for the .prices parser.
+
+use std::collections::VecDeque;
+use std::mem;
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use traderusty_core::delta::{self, Changeset};
+use traderusty_core::input::InputReader;
+use traderusty_core::merge::{self, MergeError};
+use traderusty_core::pool;
+use traderusty_core::prices::{
+    self, Checkpoint, DistinctCounts, ParseWarning, PriceRecord, PricesError,
+};
+use traderusty_core::source::FileSource;
+
+use crate::pybloom::PyBloomFilter;
+use crate::pyerrors::parse_error;
+use crate::{read_options, FsPath, PyParseOptions, PyReadOptions};
+
+/// Default number of records parsed per trip into Rust.
+const DEFAULT_BATCH_SIZE: usize = 1000;
+
+pub fn prices_error(e: PricesError) -> PyErr {
+    match e {
+        PricesError::Io(e) => PyIOError::new_err(format!("{}", e)),
+        PricesError::Parse { ref span, .. } => parse_error(format!("{}", e), span),
+    }
+}
+
+/// One item line of a .prices file.
+#[pyclass(name = "PriceRecord", frozen)]
+pub struct PyPriceRecord {
+    inner: PriceRecord,
+}
+
+#[pymethods]
+impl PyPriceRecord {
+    #[getter]
+    fn system(&self) -> &str {
+        &self.inner.system
+    }
+
+    #[getter]
+    fn station(&self) -> &str {
+        &self.inner.station
+    }
+
+    #[getter]
+    fn category(&self) -> &str {
+        &self.inner.category
+    }
+
+    #[getter]
+    fn item(&self) -> &str {
+        &self.inner.item
+    }
+
+    #[getter]
+    fn demand_price(&self) -> i32 {
+        self.inner.demand_price
+    }
+
+    #[getter]
+    fn demand_units(&self) -> i64 {
+        self.inner.demand_units
+    }
+
+    #[getter]
+    fn demand_level(&self) -> i32 {
+        self.inner.demand_level
+    }
+
+    #[getter]
+    fn supply_price(&self) -> i32 {
+        self.inner.supply_price
+    }
+
+    #[getter]
+    fn supply_units(&self) -> i64 {
+        self.inner.supply_units
+    }
+
+    #[getter]
+    fn supply_level(&self) -> i32 {
+        self.inner.supply_level
+    }
+
+    /// Seconds since the unix epoch, or None if the line had no timestamp.
+    #[getter]
+    fn modified(&self) -> Option<i64> {
+        self.inner.modified
+    }
+
+    #[getter]
+    fn line(&self) -> usize {
+        self.inner.line
+    }
+
+    fn __repr__(&self) -> String {
+        let r = &self.inner;
+        format!(
+            "PriceRecord(system={:?}, station={:?}, item={:?}, demand_price={}, supply_price={}, line={})",
+            r.system, r.station, r.item, r.demand_price, r.supply_price, r.line,
+        )
+    }
+}
+
+/// Something odd about a line that was still read: kind is "unknown_item",
+/// "future_timestamp" or "zero_price".
+#[pyclass(name = "ParseWarning", frozen)]
+#[derive(Clone)]
+pub struct PyParseWarning {
+    inner: ParseWarning,
+}
+
+#[pymethods]
+impl PyParseWarning {
+    #[getter]
+    fn kind(&self) -> &'static str {
+        self.inner.kind.name()
+    }
+
+    #[getter]
+    fn message(&self) -> &str {
+        &self.inner.message
+    }
+
+    #[getter]
+    fn offset(&self) -> u64 {
+        self.inner.span.offset
+    }
+
+    #[getter]
+    fn line(&self) -> usize {
+        self.inner.span.line
+    }
+
+    #[getter]
+    fn column(&self) -> usize {
+        self.inner.span.column
+    }
+
+    #[getter]
+    fn snippet(&self) -> &str {
+        &self.inner.span.snippet
+    }
+
+    fn __str__(&self) -> String {
+        self.inner.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ParseWarning(kind={:?}, line={}, column={}, message={:?})",
+            self.inner.kind.name(),
+            self.inner.span.line,
+            self.inner.span.column,
+            self.inner.message,
+        )
+    }
+}
+
+/// How far a PricesReader got, to carry on from later. Its fields can be
+/// saved anywhere and passed back to the constructor.
+#[pyclass(name = "Checkpoint", eq)]
+#[derive(Clone, PartialEq)]
+pub struct PyCheckpoint {
+    inner: Checkpoint,
+}
+
+#[pymethods]
+impl PyCheckpoint {
+    #[new]
+    #[pyo3(signature = (offset=0, line=0, system=String::new(), station=String::new(), category=String::new()))]
+    fn new(offset: u64, line: usize, system: String, station: String, category: String) -> Self {
+        Self {
+            inner: Checkpoint {
+                offset,
+                line,
+                system,
+                station,
+                category,
+            },
+        }
+    }
+
+    /// Byte offset of the next line to read.
+    #[getter]
+    fn offset(&self) -> u64 {
+        self.inner.offset
+    }
+
+    /// Lines read before it.
+    #[getter]
+    fn line(&self) -> usize {
+        self.inner.line
+    }
+
+    #[getter]
+    fn system(&self) -> &str {
+        &self.inner.system
+    }
+
+    #[getter]
+    fn station(&self) -> &str {
+        &self.inner.station
+    }
+
+    #[getter]
+    fn category(&self) -> &str {
+        &self.inner.category
+    }
+
+    fn __repr__(&self) -> String {
+        let c = &self.inner;
+        format!(
+            "Checkpoint(offset={}, line={}, system={:?}, station={:?}, category={:?})",
+            c.offset, c.line, c.system, c.station, c.category,
+        )
+    }
+}
+
+/// Iterates over the records of a .prices file. Records are parsed in
+/// batches with the GIL released; a malformed line raises ParseError when
+/// it's reached, and iteration can carry on past it. Use it in a `with`
+/// block (or call close()) to release the file promptly. A path of "-"
+/// reads standard input. parse_options chooses strictness, the comment
+/// character and the decimal separator; with max_errors set, iteration ends
+/// after the ParseError that goes over the limit.
+///
+/// Suspicious lines that still parse add a ParseWarning to `warnings` as
+/// their record is reached. Given known_items, items not among them are
+/// warned about.
+///
+/// `checkpoint` is where the records handed out so far end; passing it back
+/// as the checkpoint argument carries on from there.
+///
+/// Given a BloomFilter as `stations`, only those stations are read; the
+/// item lines of others are skipped without being parsed.
+#[pyclass(name = "PricesReader")]
+pub struct PyPricesReader {
+    /// None once closed.
+    reader: Option<prices::PricesReader<InputReader>>,
+    /// Parsed records with the warnings raised by their lines and the
+    /// checkpoint after them.
+    pending: VecDeque<(
+        Result<PriceRecord, PricesError>,
+        Vec<ParseWarning>,
+        Checkpoint,
+    )>,
+    batch_size: usize,
+    /// Caps a batch's records in bytes, from ReadOptions.memory_budget.
+    memory_budget: Option<usize>,
+    warnings: Vec<ParseWarning>,
+    checkpoint: Checkpoint,
+}
+
+impl PyPricesReader {
+    fn open(&self) -> PyResult<&prices::PricesReader<InputReader>> {
+        self.reader
+            .as_ref()
+            .ok_or_else(|| PyValueError::new_err("I/O operation on closed reader"))
+    }
+}
+
+#[pymethods]
+impl PyPricesReader {
+    #[new]
+    #[pyo3(signature = (
+        path, batch_size=DEFAULT_BATCH_SIZE, options=None, parse_options=None, known_items=None,
+        checkpoint=None, stations=None, count_distinct=false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        path: FsPath,
+        batch_size: usize,
+        options: Option<PyRef<'_, PyReadOptions>>,
+        parse_options: Option<PyRef<'_, PyParseOptions>>,
+        known_items: Option<Vec<String>>,
+        checkpoint: Option<PyRef<'_, PyCheckpoint>>,
+        stations: Option<PyRef<'_, PyBloomFilter>>,
+        count_distinct: bool,
+    ) -> PyResult<Self> {
+        let checkpoint = checkpoint.map(|c| c.inner.clone()).unwrap_or_default();
+        let options = read_options(options);
+        let mut reader = prices::resume_prices(
+            path.0,
+            &options,
+            &crate::parse_options(parse_options),
+            &checkpoint,
+        )
+        .map_err(|e| PyIOError::new_err(format!("{}", e)))?;
+        if let Some(items) = known_items {
+            reader = reader.known_items(items);
+        }
+        if let Some(stations) = stations {
+            reader = reader.only_stations(stations.inner.clone());
+        }
+        if count_distinct {
+            reader = reader.count_distinct();
+        }
+        let reader_checkpoint = reader.checkpoint();
+        Ok(Self {
+            reader: Some(reader),
+            pending: VecDeque::new(),
+            batch_size: batch_size.max(1),
+            memory_budget: options.memory_budget,
+            warnings: Vec::new(),
+            checkpoint: reader_checkpoint,
+        })
+    }
+
+    /// Where the records handed out so far end.
+    #[getter]
+    fn checkpoint(&self) -> PyCheckpoint {
+        PyCheckpoint {
+            inner: self.checkpoint.clone(),
+        }
+    }
+
+    /// Warnings about the lines read so far, oldest first.
+    #[getter]
+    fn warnings(&self) -> Vec<PyParseWarning> {
+        self.warnings
+            .iter()
+            .map(|inner| PyParseWarning {
+                inner: inner.clone(),
+            })
+            .collect()
+    }
+
+    /// Number of lines consumed from the file so far.
+    #[getter]
+    fn line(&self) -> PyResult<usize> {
+        Ok(self.open()?.line())
+    }
+
+    /// Item lines skipped so far for being under a station not in
+    /// `stations`.
+    #[getter]
+    fn skipped(&self) -> PyResult<usize> {
+        Ok(self.open()?.skipped())
+    }
+
+    /// Estimated distinct systems, stations and commodities read so far, if
+    /// count_distinct was asked for.
+    #[getter]
+    fn distinct(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.open()?
+            .distinct()
+            .map(|distinct| distinct_dict(py, distinct))
+            .transpose()
+    }
+
+    #[getter]
+    fn closed(&self) -> bool {
+        self.reader.is_none()
+    }
+
+    /// Closes the file. Records already parsed are discarded; closing twice
+    /// is harmless.
+    fn close(&mut self) {
+        self.reader = None;
+        self.pending.clear();
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> bool {
+        self.close();
+        false
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyPriceRecord>> {
+        self.open()?;
+        if self.pending.is_empty() {
+            let Self {
+                reader,
+                pending,
+                batch_size,
+                memory_budget,
+                ..
+            } = self;
+            let reader = reader.as_mut().expect("checked open");
+            py.allow_threads(|| {
+                // Stop a batch at an error so the records before it are
+                // handed out first, and short of the memory budget.
+                let mut held = 0;
+                for _ in 0..*batch_size {
+                    let Some(record) = reader.next() else {
+                        break;
+                    };
+                    let failed = record.is_err();
+                    if let Ok(record) = &record {
+                        held += mem::size_of::<PriceRecord>() + record.heap_size();
+                    }
+                    pending.push_back((record, reader.take_warnings(), reader.checkpoint()));
+                    if failed || memory_budget.is_some_and(|budget| held >= budget) {
+                        break;
+                    }
+                }
+            });
+        }
+        match self.pending.pop_front() {
+            None => {
+                // trailing blank or comment lines are done with too
+                self.checkpoint = self.open()?.checkpoint();
+                Ok(None)
+            }
+            Some((record, mut warnings, checkpoint)) => {
+                self.warnings.append(&mut warnings);
+                self.checkpoint = checkpoint;
+                record
+                    .map(|inner| Some(PyPriceRecord { inner }))
+                    .map_err(prices_error)
+            }
+        }
+    }
+}
+
+fn distinct_dict(py: Python<'_>, distinct: &DistinctCounts) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item("systems", distinct.systems())?;
+    dict.set_item("stations", distinct.stations())?;
+    dict.set_item("commodities", distinct.commodities())?;
+    Ok(dict.into())
+}
+
+/// Counts what a .prices file holds in one streaming pass: records, errors
+/// (lines that failed to parse), warnings, and estimates of the distinct
+/// systems, stations and commodities. A quick check of a download before
+/// importing it.
+#[pyfunction]
+#[pyo3(signature = (path, options=None, parse_options=None))]
+fn summarize_prices(
+    py: Python<'_>,
+    path: FsPath,
+    options: Option<PyRef<'_, PyReadOptions>>,
+    parse_options: Option<PyRef<'_, PyParseOptions>>,
+) -> PyResult<PyObject> {
+    let source = FileSource::new(&path.0, &read_options(options));
+    let parse_options = crate::parse_options(parse_options);
+    let summary = py
+        .allow_threads(|| prices::summarize_prices(&source, &parse_options))
+        .map_err(|e| PyIOError::new_err(format!("{}", e)))?;
+    let dict = distinct_dict(py, &summary.distinct)?;
+    let items = dict.bind(py);
+    items.set_item("records", summary.records)?;
+    items.set_item("errors", summary.errors)?;
+    items.set_item("warnings", summary.warnings)?;
+    Ok(dict)
+}
+
+pub fn merge_error(e: MergeError) -> PyErr {
+    match &e.error {
+        PricesError::Io(_) => PyIOError::new_err(e.to_string()),
+        PricesError::Parse { span, .. } => parse_error(e.to_string(), span),
+    }
+}
+
+/// Merges the .prices files in a directory, such as EDMC's per-docking
+/// exports: for each station and item the newest listing wins, with lines
+/// that have no timestamp dated by their file. Returns the records, sorted
+/// by system, station and item, and messages about the lines left out.
+/// Raises ParseError once more than parse_options.max_errors lines fail.
+#[pyfunction]
+#[pyo3(signature = (path, options=None, parse_options=None, threads=None))]
+fn merge_prices_dir(
+    py: Python<'_>,
+    path: FsPath,
+    options: Option<PyRef<'_, PyReadOptions>>,
+    parse_options: Option<PyRef<'_, PyParseOptions>>,
+    threads: Option<usize>,
+) -> PyResult<(Vec<PyPriceRecord>, Vec<String>)> {
+    let (options, parse_options) = (read_options(options), crate::parse_options(parse_options));
+    let merged = py
+        .allow_threads(|| {
+            pool::install(threads, || {
+                merge::merge_prices_dir(&path.0, &options, &parse_options)
+            })
+        })
+        .map_err(merge_error)?;
+    Ok((
+        merged
+            .records
+            .into_iter()
+            .map(|inner| PyPriceRecord { inner })
+            .collect(),
+        merged.errors.iter().map(|e| e.to_string()).collect(),
+    ))
+}
+
+fn to_py_records(records: &[PriceRecord]) -> Vec<PyPriceRecord> {
+    records
+        .iter()
+        .map(|inner| PyPriceRecord {
+            inner: inner.clone(),
+        })
+        .collect()
+}
+
+/// The changes from one .prices dump to another, a station's market at a
+/// time.
+#[pyclass(name = "DumpChangeset", frozen)]
+pub struct PyDumpChangeset {
+    inner: Changeset,
+}
+
+#[pymethods]
+impl PyDumpChangeset {
+    /// Listings of the stations only in the new dump.
+    #[getter]
+    fn added(&self) -> Vec<PyPriceRecord> {
+        to_py_records(&self.inner.added)
+    }
+
+    /// The whole new markets of the stations whose markets changed.
+    #[getter]
+    fn changed(&self) -> Vec<PyPriceRecord> {
+        to_py_records(&self.inner.changed)
+    }
+
+    /// (system, station) of the stations only in the old dump.
+    #[getter]
+    fn removed(&self) -> Vec<(String, String)> {
+        self.inner.removed.clone()
+    }
+
+    /// Messages about the lines left out.
+    #[getter]
+    fn errors(&self) -> Vec<String> {
+        self.inner.errors.iter().map(|e| e.to_string()).collect()
+    }
+
+    fn __bool__(&self) -> bool {
+        !self.inner.is_empty()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DumpChangeset(added={}, changed={}, removed={})",
+            self.inner.added.len(),
+            self.inner.changed.len(),
+            self.inner.removed.len()
+        )
+    }
+}
+
+/// Compares two .prices dumps: stations added, removed, or whose market
+/// changed in anything but timestamps. Raises ParseError once more than
+/// parse_options.max_errors lines fail.
+#[pyfunction]
+#[pyo3(signature = (old, new, options=None, parse_options=None, threads=None))]
+fn diff_dumps(
+    py: Python<'_>,
+    old: FsPath,
+    new: FsPath,
+    options: Option<PyRef<'_, PyReadOptions>>,
+    parse_options: Option<PyRef<'_, PyParseOptions>>,
+    threads: Option<usize>,
+) -> PyResult<PyDumpChangeset> {
+    let (options, parse_options) = (read_options(options), crate::parse_options(parse_options));
+    let inner = py
+        .allow_threads(|| {
+            pool::install(threads, || {
+                let (old, new) = (
+                    FileSource::new(&old.0, &options),
+                    FileSource::new(&new.0, &options),
+                );
+                delta::diff_dumps(&old, &new, &parse_options)
+            })
+        })
+        .map_err(merge_error)?;
+    Ok(PyDumpChangeset { inner })
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyPriceRecord>()?;
+    m.add_class::<PyCheckpoint>()?;
+    m.add_class::<PyParseWarning>()?;
+    m.add_class::<PyPricesReader>()?;
+    m.add_function(wrap_pyfunction!(merge_prices_dir, m)?)?;
+    m.add_function(wrap_pyfunction!(summarize_prices, m)?)?;
+    m.add_class::<PyDumpChangeset>()?;
+    m.add_function(wrap_pyfunction!(diff_dumps, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+//! Python bindings for market store queries.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use traderusty_core::ids::{ItemId, StationId};
+use traderusty_core::query::{Order, Query, QueryResult, Side};
+
+use crate::pymarket::{ids, PyMarketStore};
+
+fn side(name: &str) -> PyResult<Side> {
+    match name {
+        "buy" => Ok(Side::Buy),
+        "sell" => Ok(Side::Sell),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown side {:?}; expected \"buy\" or \"sell\"",
+            name
+        ))),
+    }
+}
+
+fn order(name: &str) -> PyResult<Order> {
+    match name {
+        "profit" => Ok(Order::Profit),
+        "distance" => Ok(Order::Distance),
+        "age" => Ok(Order::Age),
+        "units" => Ok(Order::Units),
+        "station" => Ok(Order::Station),
+        _ => Err(PyValueError::new_err(format!(
+            "unknown order {:?}; there are profit, distance, age, units, station",
+            name
+        ))),
+    }
+}
+
+/// A query's results as a dict of equal-length lists, one per StationItem
+/// field plus "distance".
+fn result_dict(py: Python<'_>, result: QueryResult) -> PyResult<PyObject> {
+    let listings = result.listings;
+    let dict = PyDict::new(py);
+    dict.set_item("station_id", ids(listings.station_id))?;
+    dict.set_item(
+        "item_id",
+        listings
+            .item_id
+            .into_iter()
+            .map(u32::from)
+            .collect::<Vec<_>>(),
+    )?;
+    dict.set_item("demand_price", listings.demand_price)?;
+    dict.set_item("demand_units", listings.demand_units)?;
+    dict.set_item("demand_level", listings.demand_level)?;
+    dict.set_item("supply_price", listings.supply_price)?;
+    dict.set_item("supply_units", listings.supply_units)?;
+    dict.set_item("supply_level", listings.supply_level)?;
+    dict.set_item("modified", listings.modified)?;
+    dict.set_item("distance", result.distances)?;
+    Ok(dict.into())
+}
+
+/// A query of a MarketStore's listings, built up a condition at a time:
+/// `Query().side("buy").filter(gold).within(origin, 20).limit(25)`. Each
+/// method returns a new Query; `run` answers it.
+#[pyclass(name = "Query", frozen)]
+#[derive(Clone, Default)]
+pub struct PyQuery {
+    inner: Query,
+}
+
+impl PyQuery {
+    fn with(&self, change: impl FnOnce(Query) -> Query) -> Self {
+        Self {
+            inner: change(self.inner.clone()),
+        }
+    }
+}
+
+#[pymethods]
+impl PyQuery {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only listings of this commodity; called more than once, of any of
+    /// them.
+    fn filter(&self, item_id: u32) -> Self {
+        self.with(|query| query.filter(ItemId(item_id)))
+    }
+
+    /// Only listings at this station; called more than once, at any of them.
+    fn station(&self, station_id: u32) -> Self {
+        self.with(|query| query.station(StationId(station_id)))
+    }
+
+    /// Only stations selling ("buy") or buying ("sell") the commodity.
+    fn side(&self, side: &str) -> PyResult<Self> {
+        let side = self::side(side)?;
+        Ok(self.with(|query| query.side(side)))
+    }
+
+    /// Only stations within ly of origin, an (x, y, z) tuple. Raises
+    /// ValueError if ly is negative or NaN.
+    fn within(&self, origin: (f64, f64, f64), ly: f64) -> PyResult<Self> {
+        if ly.is_nan() || ly < 0. {
+            return Err(PyValueError::new_err(format!(
+                "radius must be 0 or more, not {}",
+                ly
+            )));
+        }
+        let (x, y, z) = origin;
+        Ok(self.with(|query| query.within([x, y, z], ly)))
+    }
+
+    /// Only listings updated in the last days days.
+    fn max_age(&self, days: f64) -> Self {
+        self.with(|query| query.max_age(days))
+    }
+
+    /// The unix time ages are measured from, by default the clock's.
+    fn now(&self, now: i64) -> Self {
+        self.with(|query| query.now(now))
+    }
+
+    /// "profit", "distance", "age", "units" or "station".
+    fn order_by(&self, order: &str) -> PyResult<Self> {
+        let order = self::order(order)?;
+        Ok(self.with(|query| query.order_by(order)))
+    }
+
+    fn limit(&self, n: usize) -> Self {
+        self.with(|query| query.limit(n))
+    }
+
+    /// The matching listings, best first, as a dict of lists keyed by
+    /// StationItem field, with "distance" from the origin (empty without
+    /// one).
+    fn run(&self, py: Python<'_>, store: &PyMarketStore) -> PyResult<PyObject> {
+        let result = store.with_read(py, |store| self.inner.run(store));
+        result_dict(py, result)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.inner)
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyQuery>()?;
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! Python bindings for galactic region lookup.
+
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use traderusty_core::region::RegionMap;
+use traderusty_core::span::Span;
+
+use crate::pyerrors::parse_error;
+use crate::pymarket::{ids, PyMarketStore};
+use crate::FsPath;
+
+/// Named galactic regions, embedded or loaded from JSON polygon data.
+#[pyclass(name = "RegionMap", frozen)]
+pub struct PyRegionMap {
+    pub inner: RegionMap,
+}
+
+#[pymethods]
+impl PyRegionMap {
+    /// The galaxy's 42 named regions, from the embedded polygon data.
+    #[staticmethod]
+    fn galaxy() -> Self {
+        Self {
+            inner: RegionMap::galaxy().clone(),
+        }
+    }
+
+    /// Loads regions from a JSON file of [{"name": ..., "polygon": [[x, z], ...]}].
+    #[staticmethod]
+    fn load(path: FsPath) -> PyResult<Self> {
+        match RegionMap::load(path.0) {
+            Ok(inner) => Ok(Self { inner }),
+            Err(e) => Err(PyIOError::new_err(format!("{}", e))),
+        }
+    }
+
+    /// Parses regions from a JSON string.
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        match RegionMap::from_json(json) {
+            Ok(inner) => Ok(Self { inner }),
+            Err(e) => Err(parse_error(
+                format!("{}", e),
+                &Span::at_line_column(json, e.line(), e.column()),
+            )),
+        }
+    }
+
+    #[getter]
+    fn names(&self) -> Vec<String> {
+        self.inner
+            .regions()
+            .iter()
+            .map(|r| r.name.clone())
+            .collect()
+    }
+
+    /// Name of the region containing the coordinates, or None.
+    fn region_for(&self, x: f64, y: f64, z: f64) -> Option<String> {
+        self.inner.region_for(x, y, z).map(str::to_string)
+    }
+
+    /// Station ids in a store grouped by region name.
+    fn group_stations(&self, py: Python<'_>, store: &PyMarketStore) -> BTreeMap<String, Vec<u32>> {
+        store
+            .with_read(py, |store| self.inner.group(store.station_positions()))
+            .into_iter()
+            .map(|(region, stations)| (region, ids(stations)))
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyRegionMap>()?;
+    Ok(())
+}
@@ -0,0 +1,454 @@
+//! Python bindings for routes.
+
+use pyo3::exceptions::PyIndexError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use traderusty_core::cancel::CancelToken;
+use traderusty_core::fsd::{Fsd, Ship, NEUTRON_BOOST, WHITE_DWARF_BOOST};
+use traderusty_core::graph::JumpGraph;
+use traderusty_core::grid::{GridIndex, DEFAULT_CELL_SIZE};
+use traderusty_core::pool;
+use traderusty_core::route::{Action, Route, RouteKind};
+use traderusty_core::router::{self, FuelModel, LinearFuel, RouteCosts, Router, StarClass};
+
+use crate::pycancel::PyCancelToken;
+use crate::pyerrors::{RouteError, SpatialError};
+use crate::Positions;
+
+/// A nav or trade route: hops with positions, distances and actions.
+#[pyclass(name = "Route")]
+pub struct PyRoute {
+    pub inner: Route,
+}
+
+impl PyRoute {
+    fn last_hop(&mut self) -> PyResult<&mut traderusty_core::route::Hop> {
+        self.inner
+            .hops
+            .last_mut()
+            .ok_or_else(|| PyIndexError::new_err("route has no hops"))
+    }
+}
+
+#[pymethods]
+impl PyRoute {
+    #[new]
+    #[pyo3(signature = (kind="nav"))]
+    fn new(kind: &str) -> PyResult<Self> {
+        let kind = match kind {
+            "nav" => RouteKind::Nav,
+            "trade" => RouteKind::Trade,
+            _ => return Err(RouteError::new_err(format!("unknown route kind: {}", kind))),
+        };
+        Ok(Self {
+            inner: Route::new(kind),
+        })
+    }
+
+    /// Appends a hop; its distance from the previous hop is worked out.
+    #[pyo3(signature = (system, x, y, z, station=None))]
+    fn add_hop(&mut self, system: &str, x: f64, y: f64, z: f64, station: Option<&str>) {
+        self.inner.push(system, station, [x, y, z]);
+    }
+
+    /// Marks the last hop as a refuelling stop.
+    fn refuel(&mut self) -> PyResult<()> {
+        self.last_hop()?.actions.push(Action::Refuel);
+        Ok(())
+    }
+
+    /// Adds a purchase at the last hop.
+    fn buy(&mut self, item: &str, units: u32, price: i32) -> PyResult<()> {
+        self.last_hop()?.actions.push(Action::Buy {
+            item: item.to_string(),
+            units,
+            price,
+        });
+        Ok(())
+    }
+
+    /// Adds a sale at the last hop.
+    fn sell(&mut self, item: &str, units: u32, price: i32) -> PyResult<()> {
+        self.last_hop()?.actions.push(Action::Sell {
+            item: item.to_string(),
+            units,
+            price,
+        });
+        Ok(())
+    }
+
+    #[getter]
+    fn jumps(&self) -> usize {
+        self.inner.jumps()
+    }
+
+    #[getter]
+    fn total_distance(&self) -> f64 {
+        self.inner.total_distance()
+    }
+
+    /// The route as a JSON document; pretty-printed if indent is given.
+    #[pyo3(signature = (indent=None))]
+    fn to_json(&self, indent: Option<usize>) -> PyResult<String> {
+        self.inner
+            .to_json(indent)
+            .map_err(|e| RouteError::new_err(format!("{}", e)))
+    }
+
+    /// The hops as CSV, one row per hop after a header row.
+    fn to_csv(&self) -> String {
+        self.inner.to_csv()
+    }
+
+    /// The systems visited, one per line.
+    fn system_list(&self) -> String {
+        self.inner.system_list()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.hops.len()
+    }
+}
+
+/// Single-jump reachability between positions, as the basis for routing.
+/// Nodes are indexes into the positions the graph was built from.
+#[pyclass(name = "JumpGraph", frozen)]
+pub struct PyJumpGraph {
+    pub inner: JumpGraph,
+}
+
+#[pymethods]
+impl PyJumpGraph {
+    #[new]
+    #[pyo3(signature = (positions, jump_range, boosts=None, threads=None))]
+    fn new(
+        py: Python<'_>,
+        positions: Positions,
+        jump_range: f64,
+        boosts: Option<Vec<f64>>,
+        threads: Option<usize>,
+    ) -> Self {
+        let positions = positions.into_f64();
+        let boosts = boosts.unwrap_or_default();
+        let inner = py.allow_threads(|| {
+            pool::install(threads, || {
+                JumpGraph::build_boosted(&positions, jump_range, &boosts)
+            })
+        });
+        Self { inner }
+    }
+
+    #[getter]
+    fn jump_range(&self) -> f64 {
+        self.inner.jump_range
+    }
+
+    #[getter]
+    fn node_count(&self) -> usize {
+        self.inner.node_count()
+    }
+
+    #[getter]
+    fn edge_count(&self) -> usize {
+        self.inner.edge_count()
+    }
+
+    /// Build statistics: nodes, edges, max_degree, cells and seconds taken.
+    #[getter]
+    fn stats(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let stats = &self.inner.stats;
+        let dict = PyDict::new(py);
+        dict.set_item("nodes", stats.nodes)?;
+        dict.set_item("edges", stats.edges)?;
+        dict.set_item("max_degree", stats.max_degree)?;
+        dict.set_item("cells", stats.cells)?;
+        dict.set_item("seconds", stats.elapsed.as_secs_f64())?;
+        Ok(dict.into())
+    }
+
+    /// (node, distance) pairs reachable from a node in one jump.
+    fn neighbours(&self, node: u32) -> Vec<(u32, f32)> {
+        self.inner
+            .neighbours(node)
+            .iter()
+            .copied()
+            .zip(self.inner.distances(node).iter().copied())
+            .collect()
+    }
+
+    fn degree(&self, node: u32) -> usize {
+        self.inner.degree(node)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.node_count()
+    }
+}
+
+/// How evenly positions spread over grid cells of cell_size ly: points,
+/// cells (occupied), histogram (histogram[i] counts cells holding 2**i to
+/// 2**(i+1) - 1 points), max_occupancy, mean_occupancy and empty_ratio (of
+/// the occupied cells' bounding box).
+#[pyfunction]
+#[pyo3(signature = (positions, cell_size=DEFAULT_CELL_SIZE))]
+fn grid_stats(py: Python<'_>, positions: Positions, cell_size: f64) -> PyResult<PyObject> {
+    if cell_size.is_nan() || cell_size <= 0. {
+        return Err(SpatialError::new_err(format!(
+            "invalid cell size: {}",
+            cell_size
+        )));
+    }
+    let stats = py.allow_threads(|| match &positions {
+        Positions::F64(positions) => GridIndex::with_cell_size(positions, cell_size).grid_stats(),
+        Positions::F32(positions) => GridIndex::with_cell_size(positions, cell_size).grid_stats(),
+    });
+    let dict = PyDict::new(py);
+    dict.set_item("cell_size", stats.cell_size)?;
+    dict.set_item("points", stats.points)?;
+    dict.set_item("cells", stats.cells)?;
+    dict.set_item("histogram", stats.histogram)?;
+    dict.set_item("max_occupancy", stats.max_occupancy)?;
+    dict.set_item("mean_occupancy", stats.mean_occupancy)?;
+    dict.set_item("empty_ratio", stats.empty_ratio)?;
+    Ok(dict.into())
+}
+
+/// Fuel use proportional to jump distance, capped per jump.
+#[pyclass(name = "LinearFuel", frozen)]
+pub struct PyLinearFuel {
+    inner: LinearFuel,
+}
+
+#[pymethods]
+impl PyLinearFuel {
+    #[new]
+    fn new(tank: f64, fuel_per_ly: f64, max_fuel_per_jump: f64) -> Self {
+        Self {
+            inner: LinearFuel {
+                tank,
+                fuel_per_ly,
+                max_fuel_per_jump,
+            },
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "LinearFuel(tank={}, fuel_per_ly={}, max_fuel_per_jump={})",
+            self.inner.tank, self.inner.fuel_per_ly, self.inner.max_fuel_per_jump
+        )
+    }
+}
+
+/// A ship build, for jump range and fuel use from the FSD formula.
+#[pyclass(name = "Ship", frozen)]
+pub struct PyShip {
+    inner: Ship,
+}
+
+#[pymethods]
+impl PyShip {
+    #[new]
+    #[pyo3(signature = (
+        unladen_mass, tank, optimal_mass, max_fuel_per_jump,
+        fsd_class=5, fsd_rating='A', cargo=0.0, guardian_boost=0.0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        unladen_mass: f64,
+        tank: f64,
+        optimal_mass: f64,
+        max_fuel_per_jump: f64,
+        fsd_class: u8,
+        fsd_rating: char,
+        cargo: f64,
+        guardian_boost: f64,
+    ) -> PyResult<Self> {
+        let power_constant = Fsd::power_constant_for(fsd_class)
+            .ok_or_else(|| RouteError::new_err(format!("invalid FSD class: {}", fsd_class)))?;
+        let linear_constant = Fsd::linear_constant_for(fsd_rating)
+            .ok_or_else(|| RouteError::new_err(format!("invalid FSD rating: {}", fsd_rating)))?;
+        Ok(Self {
+            inner: Ship {
+                unladen_mass,
+                tank,
+                cargo,
+                fsd: Fsd {
+                    optimal_mass,
+                    max_fuel_per_jump,
+                    linear_constant,
+                    power_constant,
+                },
+                guardian_boost,
+            },
+        })
+    }
+
+    /// Range of one jump with `fuel` tons aboard (default a full tank),
+    /// multiplied by boost, e.g. 4.0 for a neutron supercharge.
+    #[pyo3(signature = (fuel=None, boost=1.0))]
+    fn jump_range(&self, fuel: Option<f64>, boost: f64) -> f64 {
+        self.inner
+            .jump_range(fuel.unwrap_or(self.inner.tank), boost)
+    }
+
+    fn max_jump_range(&self) -> f64 {
+        self.inner.max_jump_range()
+    }
+
+    /// Fuel needed to jump a distance, or None if it's out of range.
+    #[pyo3(signature = (distance, fuel=None))]
+    fn fuel_for_jump(&self, distance: f64, fuel: Option<f64>) -> Option<f64> {
+        self.inner
+            .fuel_for_jump(distance, fuel.unwrap_or(self.inner.tank))
+    }
+}
+
+/// Either kind of fuel model accepted by Router.route.
+#[derive(FromPyObject)]
+enum AnyFuel<'py> {
+    Linear(PyRef<'py, PyLinearFuel>),
+    Ship(PyRef<'py, PyShip>),
+}
+
+/// (node, fuel, refuelled, supercharged) for each system along a route.
+type Waypoints = Vec<(u32, f64, bool, bool)>;
+
+/// Fuel-aware routing over a JumpGraph, given each node's star class.
+#[pyclass(name = "Router", frozen)]
+pub struct PyRouter {
+    graph: Py<PyJumpGraph>,
+    stars: Vec<StarClass>,
+    costs: RouteCosts,
+    neutron: bool,
+}
+
+#[pymethods]
+impl PyRouter {
+    #[new]
+    #[pyo3(signature = (
+        graph, star_classes, jump_cost=1.0, fuel_cost=0.1, refuel_cost=1.0, neutron=false,
+    ))]
+    fn new(
+        graph: Py<PyJumpGraph>,
+        star_classes: Vec<String>,
+        jump_cost: f64,
+        fuel_cost: f64,
+        refuel_cost: f64,
+        neutron: bool,
+    ) -> Self {
+        Self {
+            graph,
+            stars: star_classes.iter().map(|c| StarClass::parse(c)).collect(),
+            costs: RouteCosts {
+                jump_cost,
+                fuel_cost,
+                refuel_cost,
+            },
+            neutron,
+        }
+    }
+
+    /// Cheapest route from start to goal as
+    /// (cost, [(node, fuel, refuelled, supercharged)]),
+    /// or None if the ship can't get there, or the search was cancelled
+    /// through `cancel`. Starts with a full tank unless fuel is given.
+    #[pyo3(signature = (start, goal, fuel_model, fuel=None, cancel=None))]
+    fn route(
+        &self,
+        py: Python<'_>,
+        start: u32,
+        goal: u32,
+        fuel_model: AnyFuel<'_>,
+        fuel: Option<f64>,
+        cancel: Option<PyRef<'_, PyCancelToken>>,
+    ) -> Option<(f64, Waypoints)> {
+        let cancel = cancel.map(|token| token.inner.clone());
+        match fuel_model {
+            AnyFuel::Linear(model) => {
+                let model = &model.inner;
+                py.allow_threads(|| self.search(start, goal, model, fuel, cancel))
+            }
+            AnyFuel::Ship(model) => {
+                let model = &model.inner;
+                py.allow_threads(|| self.search(start, goal, model, fuel, cancel))
+            }
+        }
+    }
+
+    /// route as a coroutine. Cancelling it stops the search.
+    #[cfg(feature = "asyncio")]
+    #[pyo3(signature = (start, goal, fuel_model, fuel=None))]
+    fn route_async<'py>(
+        slf: &Bound<'py, Self>,
+        start: u32,
+        goal: u32,
+        fuel_model: AnyFuel<'py>,
+        fuel: Option<f64>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let router = slf.clone().unbind();
+        let model = match fuel_model {
+            AnyFuel::Linear(model) => OwnedFuel::Linear(model.inner.clone()),
+            AnyFuel::Ship(model) => OwnedFuel::Ship(model.inner.clone()),
+        };
+        crate::pyasync::spawn_cancellable(slf.py(), move |cancel| {
+            let router = router.get();
+            let cancel = Some(cancel);
+            Ok(match &model {
+                OwnedFuel::Linear(model) => router.search(start, goal, model, fuel, cancel),
+                OwnedFuel::Ship(model) => router.search(start, goal, model, fuel, cancel),
+            })
+        })
+    }
+}
+
+/// A fuel model moved onto another thread.
+#[cfg(feature = "asyncio")]
+enum OwnedFuel {
+    Linear(LinearFuel),
+    Ship(Ship),
+}
+
+impl PyRouter {
+    fn search<F: FuelModel>(
+        &self,
+        start: u32,
+        goal: u32,
+        model: &F,
+        fuel: Option<f64>,
+        cancel: Option<CancelToken>,
+    ) -> Option<(f64, Waypoints)> {
+        let mut router = Router::new(&self.graph.get().inner, &self.stars);
+        router.costs = self.costs.clone();
+        router.neutron = self.neutron;
+        router.cancel = cancel;
+        let plan = router.route(start, goal, model, fuel.unwrap_or(model.tank()))?;
+        let waypoints = plan
+            .waypoints
+            .iter()
+            .map(|w| (w.node, w.fuel, w.refuelled, w.supercharged))
+            .collect();
+        Some((plan.cost, waypoints))
+    }
+}
+
+/// Per-node range multipliers for JumpGraph's boosts that give neutron
+/// stars their supercharged range.
+#[pyfunction]
+fn neutron_boosts(star_classes: Vec<String>) -> Vec<f64> {
+    let stars: Vec<StarClass> = star_classes.iter().map(|c| StarClass::parse(c)).collect();
+    router::neutron_boosts(&stars)
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyRoute>()?;
+    m.add_class::<PyJumpGraph>()?;
+    m.add_class::<PyLinearFuel>()?;
+    m.add_class::<PyShip>()?;
+    m.add("NEUTRON_BOOST", NEUTRON_BOOST)?;
+    m.add_function(wrap_pyfunction!(neutron_boosts, m)?)?;
+    m.add_function(wrap_pyfunction!(grid_stats, m)?)?;
+    m.add("WHITE_DWARF_BOOST", WHITE_DWARF_BOOST)?;
+    m.add_class::<PyRouter>()?;
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! Python bindings for the Spansh client.
+
+use pyo3::prelude::*;
+use traderusty_core::http::{DEFAULT_JITTER, DEFAULT_MAX_BACKOFF};
+use traderusty_core::spansh::{SpanshClient, DEFAULT_BACKOFF, DEFAULT_RETRIES, SPANSH_URL};
+
+#[cfg(feature = "asyncio")]
+use crate::pyasync::spawn_cancellable;
+use crate::pyerrors::http_error;
+use crate::pyjournal::PyMarketFile;
+use crate::pysystem::PySystem;
+use crate::{retry_policy, seconds};
+
+/// Searches Spansh for systems and station markets. Requests block and are
+/// retried with a doubling backoff, capped at max_backoff and with up to
+/// jitter of each wait taken off at random; on_retry, if given, is called
+/// with the retry's number, wait and error. The GIL is released meanwhile.
+///
+/// The `_async` methods are coroutines. A search can't be interrupted, so
+/// cancelling one only stops it being waited for: the requests still run
+/// their course.
+#[pyclass(name = "SpanshClient", frozen)]
+pub struct PySpanshClient {
+    inner: SpanshClient,
+}
+
+#[pymethods]
+impl PySpanshClient {
+    #[new]
+    #[pyo3(signature = (
+        base_url=SPANSH_URL,
+        timeout=30.0,
+        retries=DEFAULT_RETRIES,
+        backoff=DEFAULT_BACKOFF.as_secs_f64(),
+        max_backoff=DEFAULT_MAX_BACKOFF.as_secs_f64(),
+        jitter=DEFAULT_JITTER,
+        on_retry=None,
+    ))]
+    fn new(
+        base_url: &str,
+        timeout: f64,
+        retries: usize,
+        backoff: f64,
+        max_backoff: f64,
+        jitter: f64,
+        on_retry: Option<Py<PyAny>>,
+    ) -> PyResult<Self> {
+        let retry = retry_policy(retries, backoff, max_backoff, jitter, on_retry)?;
+        let inner = SpanshClient::new(base_url, seconds("timeout", timeout)?).retry_policy(retry);
+        Ok(Self { inner })
+    }
+
+    /// The named systems Spansh knows.
+    fn systems(&self, py: Python<'_>, names: Vec<String>) -> PyResult<Vec<PySystem>> {
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let systems = py
+            .allow_threads(|| self.inner.systems(&names))
+            .map_err(http_error)?;
+        Ok(systems.into_iter().map(PySystem::from).collect())
+    }
+
+    /// Up to limit systems within radius ly of a point, nearest first.
+    #[pyo3(signature = (x, y, z, radius, limit=100))]
+    fn systems_near(
+        &self,
+        py: Python<'_>,
+        x: f64,
+        y: f64,
+        z: f64,
+        radius: f64,
+        limit: usize,
+    ) -> PyResult<Vec<PySystem>> {
+        let systems = py
+            .allow_threads(|| self.inner.systems_near([x, y, z], radius, limit))
+            .map_err(http_error)?;
+        Ok(systems.into_iter().map(PySystem::from).collect())
+    }
+
+    /// The markets of the stations in the named systems, as MarketFiles.
+    #[pyo3(signature = (systems, limit=1000))]
+    fn markets(
+        &self,
+        py: Python<'_>,
+        systems: Vec<String>,
+        limit: usize,
+    ) -> PyResult<Vec<PyMarketFile>> {
+        let systems: Vec<&str> = systems.iter().map(String::as_str).collect();
+        let markets = py
+            .allow_threads(|| self.inner.markets(&systems, limit))
+            .map_err(http_error)?;
+        Ok(markets.into_iter().map(PyMarketFile::from).collect())
+    }
+
+    /// systems as a coroutine.
+    #[cfg(feature = "asyncio")]
+    fn systems_async<'py>(
+        slf: &Bound<'py, Self>,
+        names: Vec<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let client = slf.clone().unbind();
+        spawn_cancellable(slf.py(), move |_| {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            let systems = client.get().inner.systems(&names).map_err(http_error)?;
+            Ok(systems.into_iter().map(PySystem::from).collect::<Vec<_>>())
+        })
+    }
+
+    /// systems_near as a coroutine.
+    #[cfg(feature = "asyncio")]
+    #[pyo3(signature = (x, y, z, radius, limit=100))]
+    fn systems_near_async<'py>(
+        slf: &Bound<'py, Self>,
+        x: f64,
+        y: f64,
+        z: f64,
+        radius: f64,
+        limit: usize,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let client = slf.clone().unbind();
+        spawn_cancellable(slf.py(), move |_| {
+            let systems = client
+                .get()
+                .inner
+                .systems_near([x, y, z], radius, limit)
+                .map_err(http_error)?;
+            Ok(systems.into_iter().map(PySystem::from).collect::<Vec<_>>())
+        })
+    }
+
+    /// markets as a coroutine.
+    #[cfg(feature = "asyncio")]
+    #[pyo3(signature = (systems, limit=1000))]
+    fn markets_async<'py>(
+        slf: &Bound<'py, Self>,
+        systems: Vec<String>,
+        limit: usize,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let client = slf.clone().unbind();
+        spawn_cancellable(slf.py(), move |_| {
+            let systems: Vec<&str> = systems.iter().map(String::as_str).collect();
+            let markets = client
+                .get()
+                .inner
+                .markets(&systems, limit)
+                .map_err(http_error)?;
+            Ok(markets
+                .into_iter()
+                .map(PyMarketFile::from)
+                .collect::<Vec<_>>())
+        })
+    }
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySpanshClient>()?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Python bindings for star systems.
+
+use pyo3::prelude::*;
+use traderusty_core::ids::SystemId;
+use traderusty_core::system::System;
+use traderusty_core::tdcsv::{self, CsvError};
+
+use crate::pyerrors::parse_error;
+use crate::PyParseOptions;
+
+/// A star system: its name, SystemAddress where known, and coordinates.
+#[pyclass(name = "System", frozen)]
+#[derive(Clone)]
+pub struct PySystem {
+    pub inner: System,
+}
+
+#[pymethods]
+impl PySystem {
+    #[new]
+    #[pyo3(signature = (name, x, y, z, id64=None))]
+    fn new(name: String, x: f64, y: f64, z: f64, id64: Option<u64>) -> Self {
+        Self {
+            inner: System {
+                name,
+                id64: id64.map(SystemId),
+                x,
+                y,
+                z,
+            },
+        }
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    #[getter]
+    fn id64(&self) -> Option<u64> {
+        self.inner.id64.map(u64::from)
+    }
+
+    #[getter]
+    fn x(&self) -> f64 {
+        self.inner.x
+    }
+
+    #[getter]
+    fn y(&self) -> f64 {
+        self.inner.y
+    }
+
+    #[getter]
+    fn z(&self) -> f64 {
+        self.inner.z
+    }
+
+    /// Straight-line distance to another system in ly.
+    fn distance_to(&self, other: &PySystem) -> f64 {
+        self.inner.distance_to(&other.inner)
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+
+    fn __repr__(&self) -> String {
+        let s = &self.inner;
+        format!(
+            "System(name={:?}, x={}, y={}, z={}, id64={:?})",
+            s.name,
+            s.x,
+            s.y,
+            s.z,
+            s.id64.map(u64::from)
+        )
+    }
+}
+
+impl From<System> for PySystem {
+    fn from(inner: System) -> Self {
+        Self { inner }
+    }
+}
+
+fn csv_error(e: CsvError) -> PyErr {
+    parse_error(e.message, &e.span)
+}
+
+/// Reads the text of a TradeDangerous System.csv, with coordinates written
+/// in the parse options' decimal convention. Leniently, malformed rows are
+/// skipped.
+#[pyfunction]
+#[pyo3(signature = (text, parse_options=None))]
+fn read_system_csv(
+    text: &str,
+    parse_options: Option<PyRef<'_, PyParseOptions>>,
+) -> PyResult<Vec<PySystem>> {
+    let (systems, _) =
+        tdcsv::read_systems(text, &crate::parse_options(parse_options)).map_err(csv_error)?;
+    Ok(systems.into_iter().map(PySystem::from).collect())
+}
+
+/// Reads the text of a TradeDangerous Station.csv into (system, station,
+/// ls_from_star) tuples, with distances written in the parse options'
+/// decimal convention. Leniently, malformed rows are skipped.
+#[pyfunction]
+#[pyo3(signature = (text, parse_options=None))]
+fn read_station_csv(
+    text: &str,
+    parse_options: Option<PyRef<'_, PyParseOptions>>,
+) -> PyResult<Vec<(String, String, f64)>> {
+    let (stations, _) =
+        tdcsv::read_stations(text, &crate::parse_options(parse_options)).map_err(csv_error)?;
+    Ok(stations
+        .into_iter()
+        .map(|row| (row.system, row.name, row.ls_from_star))
+        .collect())
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySystem>()?;
+    m.add_function(wrap_pyfunction!(read_system_csv, m)?)?;
+    m.add_function(wrap_pyfunction!(read_station_csv, m)?)?;
+    Ok(())
+}
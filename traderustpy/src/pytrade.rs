@@ -0,0 +1,121 @@
+//! Python bindings for trade search.
+
+use pyo3::prelude::*;
+use traderusty_core::ids::StationId;
+use traderusty_core::pool;
+use traderusty_core::trade::{self, Load, LoopSearch, TradeLimits, TradeLoop};
+
+use crate::pymarket::{ids, PyMarketStore};
+
+/// (item_id, units, buy_price, sell_price) for each item in a load.
+type LoadTuples = Vec<(u32, u32, i32, i32)>;
+
+fn load_tuples(load: &Load) -> LoadTuples {
+    load.trades
+        .iter()
+        .map(|t| (t.item_id.0, t.units, t.buy_price, t.sell_price))
+        .collect()
+}
+
+/// A closed circuit of stations with the cargo to carry on each leg.
+#[pyclass(name = "TradeLoop", frozen)]
+pub struct PyTradeLoop {
+    inner: TradeLoop,
+}
+
+#[pymethods]
+impl PyTradeLoop {
+    /// Station ids in visiting order; the last leg returns to the first.
+    #[getter]
+    fn stations(&self) -> Vec<u32> {
+        ids(self.inner.stations.clone())
+    }
+
+    #[getter]
+    fn profit(&self) -> i64 {
+        self.inner.profit
+    }
+
+    #[getter]
+    fn profit_per_leg(&self) -> f64 {
+        self.inner.profit_per_leg()
+    }
+
+    /// For each leg, a list of (item_id, units, buy_price, sell_price).
+    #[getter]
+    fn loads(&self) -> Vec<LoadTuples> {
+        self.inner.loads.iter().map(load_tuples).collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.stations.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TradeLoop(stations={:?}, profit={})",
+            ids(self.inner.stations.clone()),
+            self.inner.profit
+        )
+    }
+}
+
+/// The most profitable load from one station to another, as a list of
+/// (item_id, units, buy_price, sell_price); empty if nothing turns a profit.
+#[pyfunction]
+fn best_load(
+    py: Python<'_>,
+    store: &PyMarketStore,
+    from_station: u32,
+    to_station: u32,
+    capacity: u32,
+    credits: i64,
+) -> LoadTuples {
+    let limits = TradeLimits { capacity, credits };
+    load_tuples(&store.with_read(py, |store| {
+        trade::best_load(
+            store,
+            StationId(from_station),
+            StationId(to_station),
+            &limits,
+        )
+    }))
+}
+
+/// Profitable loops of up to max_legs stations no more than max_distance
+/// ly apart, best profit per leg first.
+#[pyfunction]
+#[pyo3(signature = (store, max_distance, capacity, credits, max_legs=2, limit=10, threads=None))]
+#[allow(clippy::too_many_arguments)]
+fn find_trade_loops(
+    py: Python<'_>,
+    store: &PyMarketStore,
+    max_distance: f64,
+    capacity: u32,
+    credits: i64,
+    max_legs: usize,
+    limit: usize,
+    threads: Option<usize>,
+) -> Vec<PyTradeLoop> {
+    let search = LoopSearch {
+        max_distance,
+        max_legs,
+        limits: TradeLimits { capacity, credits },
+        limit,
+    };
+    let cache = &store.loads;
+    store
+        .with_read(py, |store| {
+            pool::install(threads, || trade::find_loops(store, &search, cache))
+        })
+        .into_iter()
+        .map(|inner| PyTradeLoop { inner })
+        .collect()
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyTradeLoop>()?;
+    m.add_function(wrap_pyfunction!(best_load, m)?)?;
+    m.add_function(wrap_pyfunction!(find_trade_loops, m)?)?;
+    Ok(())
+}
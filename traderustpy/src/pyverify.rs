@@ -0,0 +1,104 @@
+//! Python bindings for verifying files against their hashes.
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use traderusty_core::pool;
+use traderusty_core::verify::{self, Mismatch};
+
+use crate::{read_options, FsPath, PyReadOptions};
+
+/// A file that didn't match its expected hash: `actual` is its hash, or
+/// None with `error` saying why it couldn't be read.
+#[pyclass(name = "FileMismatch", frozen)]
+pub struct PyFileMismatch {
+    inner: Mismatch,
+}
+
+#[pymethods]
+impl PyFileMismatch {
+    #[getter]
+    fn path(&self) -> String {
+        self.inner.path.display().to_string()
+    }
+
+    #[getter]
+    fn expected(&self) -> &str {
+        &self.inner.expected
+    }
+
+    #[getter]
+    fn actual(&self) -> Option<&str> {
+        self.inner.actual.as_deref().ok()
+    }
+
+    #[getter]
+    fn error(&self) -> Option<String> {
+        self.inner.actual.as_ref().err().map(|e| e.to_string())
+    }
+
+    fn __repr__(&self) -> String {
+        let actual = match &self.inner.actual {
+            Ok(hash) => format!("{:?}", hash),
+            Err(e) => format!("error={:?}", e.to_string()),
+        };
+        format!(
+            "FileMismatch(path={:?}, expected={:?}, {})",
+            self.inner.path.display().to_string(),
+            self.inner.expected,
+            actual
+        )
+    }
+}
+
+/// The SHA-256 hashes of files, in lowercase hex, computed in parallel.
+#[pyfunction]
+#[pyo3(signature = (paths, options=None, threads=None))]
+fn hash_files(
+    py: Python<'_>,
+    paths: Vec<FsPath>,
+    options: Option<PyRef<'_, PyReadOptions>>,
+    threads: Option<usize>,
+) -> PyResult<Vec<String>> {
+    let options = read_options(options);
+    let paths: Vec<_> = paths.into_iter().map(|path| path.0).collect();
+    py.allow_threads(|| pool::install(threads, || verify::hash_files(&paths, &options)))
+        .map_err(|e| PyIOError::new_err(format!("{}", e)))
+}
+
+/// Hashes files in parallel and checks them against their expected SHA-256
+/// hashes (hex, any case). Returns the files that didn't match or couldn't
+/// be read; an empty list means all is well.
+#[pyfunction]
+#[pyo3(signature = (paths, expected_hashes, options=None, threads=None))]
+fn verify_files(
+    py: Python<'_>,
+    paths: Vec<FsPath>,
+    expected_hashes: Vec<String>,
+    options: Option<PyRef<'_, PyReadOptions>>,
+    threads: Option<usize>,
+) -> PyResult<Vec<PyFileMismatch>> {
+    if paths.len() != expected_hashes.len() {
+        return Err(PyValueError::new_err(
+            "paths and expected_hashes differ in length",
+        ));
+    }
+    let options = read_options(options);
+    let files: Vec<_> = paths
+        .into_iter()
+        .map(|path| path.0)
+        .zip(expected_hashes.iter().map(String::as_str))
+        .collect();
+    let mismatches =
+        py.allow_threads(|| pool::install(threads, || verify::verify_files(&files, &options)));
+    Ok(mismatches
+        .into_iter()
+        .map(|inner| PyFileMismatch { inner })
+        .collect())
+}
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyFileMismatch>()?;
+    m.add_function(wrap_pyfunction!(hash_files, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_files, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,298 @@
+//! `traderusty import`: loads a .prices dump into a TradeDangerous database
+//! through the streaming import pipeline, in one transaction.
+//!
+//! Progress goes to stderr while it runs, read off the process's metrics:
+//! redrawn in place on a terminal, a line every few seconds otherwise, as
+//! from a cron job.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use traderusty_core::chunkcache::{self, ChunkCache};
+use traderusty_core::clock::Stopwatch;
+use traderusty_core::db::{self, DEFAULT_BATCH_SIZE};
+use traderusty_core::metrics::{self, Counter};
+use traderusty_core::options::{ParseOptions, ReadOptions};
+use traderusty_core::pipeline::{self, ImportStats, PipelineOptions, DEFAULT_QUEUE_DEPTH};
+use traderusty_core::source::{DataSource, FileSource};
+
+use crate::Result;
+
+#[derive(clap::Args)]
+pub struct ImportArgs {
+    /// The TradeDangerous database, whose System and Station tables say
+    /// which stations there are.
+    #[arg(long, value_name = "FILE")]
+    db: PathBuf,
+    /// The .prices dump, gzip-compressed or not.
+    dump: PathBuf,
+    /// Accept hand edits: stray whitespace in readings, thousands
+    /// separators.
+    #[arg(long)]
+    lenient: bool,
+    /// Give up, writing nothing, after this many bad lines.
+    #[arg(long, value_name = "N")]
+    max_errors: Option<usize>,
+    /// Rows per batch handed between stages and written per INSERT.
+    #[arg(long, value_name = "ROWS", default_value_t = DEFAULT_BATCH_SIZE)]
+    batch_size: usize,
+    /// Batches that may wait between two stages.
+    #[arg(long, value_name = "BATCHES", default_value_t = DEFAULT_QUEUE_DEPTH)]
+    queue_depth: usize,
+    /// Keep a decompressed copy of a gzip dump here, so importing it again
+    /// doesn't decompress it again.
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Most the cache directory may hold, in MiB; the least recently used
+    /// copies are removed to stay under it.
+    #[arg(long, value_name = "MIB", default_value_t = chunkcache::DEFAULT_BUDGET >> 20)]
+    cache_size: u64,
+    /// Check the dump as it would be imported, without writing.
+    #[arg(long)]
+    dry_run: bool,
+    /// No progress output.
+    #[arg(long, short)]
+    quiet: bool,
+}
+
+/// How often progress is redrawn on a terminal, and logged otherwise.
+const REDRAW_EVERY: Duration = Duration::from_millis(250);
+const LOG_EVERY: Duration = Duration::from_secs(5);
+
+/// Reports progress on stderr until `done` is dropped. The metrics are
+/// process-wide, so only what's added after `bytes` and `records` counts.
+fn report_progress(size: u64, done: mpsc::Receiver<()>) {
+    let (bytes, records) = (
+        metrics::get(Counter::BytesRead),
+        metrics::get(Counter::RecordsParsed),
+    );
+    let terminal = io::stderr().is_terminal();
+    let interval = if terminal { REDRAW_EVERY } else { LOG_EVERY };
+    let stopwatch = Stopwatch::start();
+    let line = || {
+        let read = metrics::get(Counter::BytesRead) - bytes;
+        let percent = (read * 100).checked_div(size).unwrap_or(100).min(100);
+        format!(
+            "{:3}% {} records, {:.0}s",
+            percent,
+            metrics::get(Counter::RecordsParsed) - records,
+            stopwatch.elapsed().as_secs_f64()
+        )
+    };
+    while let Err(RecvTimeoutError::Timeout) = done.recv_timeout(interval) {
+        let mut err = io::stderr().lock();
+        let _ = if terminal {
+            write!(err, "\r{}", line())
+        } else {
+            writeln!(err, "{}", line())
+        };
+    }
+    if terminal {
+        eprintln!("\r{}", line());
+    }
+}
+
+/// The dump to read: a decompressed copy from the cache, if there's a cache.
+fn dump_source(args: &ImportArgs) -> Result<FileSource> {
+    let dump = FileSource::new(&args.dump, &ReadOptions::default());
+    let Some(dir) = &args.cache_dir else {
+        return Ok(dump);
+    };
+    let cache = ChunkCache::new(dir, args.cache_size << 20)?;
+    Ok(chunkcache::decompressed(&dump, &cache)?)
+}
+
+fn import(args: &ImportArgs, dump: &FileSource) -> Result<ImportStats> {
+    let pipeline_options = PipelineOptions {
+        batch_size: args.batch_size,
+        queue_depth: args.queue_depth,
+        cancel: None,
+    };
+    let parse_options = ParseOptions {
+        strictness: crate::strictness(args.lenient),
+        max_errors: args.max_errors,
+        ..Default::default()
+    };
+    let mut conn = db::open_database(&args.db)?;
+    let stations = pipeline::load_stations(&conn)?;
+    let stats = if args.dry_run {
+        pipeline::validate_prices(dump, &stations, &pipeline_options, &parse_options)?
+    } else {
+        pipeline::import_prices(
+            &mut conn,
+            dump,
+            &stations,
+            &pipeline_options,
+            &parse_options,
+        )?
+    };
+    Ok(stats)
+}
+
+fn report(
+    dump: &Path,
+    stats: &ImportStats,
+    dry_run: bool,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> Result<()> {
+    for error in &stats.errors {
+        writeln!(err, "{}: {}", dump.display(), error)?;
+    }
+    for warning in &stats.warnings {
+        writeln!(err, "{}: warning: {}", dump.display(), warning)?;
+    }
+    for unresolved in &stats.unresolved {
+        writeln!(err, "{}: {}", dump.display(), unresolved)?;
+    }
+    writeln!(
+        out,
+        "{} records, {} rows {}, {} errors, {} warnings",
+        stats.records,
+        stats.written,
+        if dry_run { "to write" } else { "written" },
+        stats.errors.len(),
+        stats.warnings.len()
+    )?;
+    writeln!(
+        out,
+        "skipped {} records for unknown stations, {} for unknown items",
+        stats.unknown_stations, stats.unknown_items
+    )?;
+    Ok(())
+}
+
+pub fn run(args: &ImportArgs, out: &mut impl Write, err: &mut impl Write) -> Result<ExitCode> {
+    let dump = dump_source(args)?;
+    let size = dump.size().unwrap_or(0);
+    let stats = thread::scope(|scope| {
+        let (done, finished) = mpsc::channel();
+        if !args.quiet {
+            scope.spawn(move || report_progress(size, finished));
+        }
+        let stats = import(args, &dump);
+        drop(done);
+        stats
+    })?;
+    report(&args.dump, &stats, args.dry_run, out, err)?;
+    Ok(if stats.errors.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+
+    #[test]
+    fn test_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let (db_path, dump) = (
+            dir.path().join("TradeDangerous.db"),
+            dir.path().join("x.prices"),
+        );
+        let mut conn = db::open_database(&db_path).unwrap();
+        traderusty_core::migrate::migrate(&mut conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE System (system_id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE Station (station_id INTEGER PRIMARY KEY, name TEXT, system_id INTEGER);
+             INSERT INTO System VALUES (1, 'Sol');
+             INSERT INTO Station VALUES (7, 'Abraham Lincoln', 1);
+             CREATE TABLE Item (item_id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO Item VALUES (42, 'Gold'), (43, 'Silver');",
+        )
+        .unwrap();
+        fs::write(
+            &dump,
+            "@ SOL/Abraham Lincoln\nGold 9500 9000\nSilver x\n@ SOL/Nowhere\nGold 1 1\n",
+        )
+        .unwrap();
+        let args = |extra: &[&str]| {
+            let mut argv = vec!["traderusty", "import", "--quiet", "--db"];
+            argv.extend([db_path.to_str().unwrap(), dump.to_str().unwrap()]);
+            argv.extend(extra);
+            match crate::Cli::parse_from(argv).command {
+                crate::Command::Import(args) => args,
+                _ => unreachable!(),
+            }
+        };
+        let rows = || -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM StationItem", [], |row| row.get(0))
+                .unwrap()
+        };
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = run(&args(&["--dry-run"]), &mut out, &mut err).unwrap();
+        assert_eq!(code, ExitCode::FAILURE);
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with("2 records, 1 rows to write, 1 errors"),
+            "{}",
+            out
+        );
+        let err = String::from_utf8(err).unwrap();
+        assert!(
+            err.contains("x.prices: line 5: unknown station SOL/Nowhere"),
+            "{}",
+            err
+        );
+        assert_eq!(rows(), 0);
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        run(&args(&[]), &mut out, &mut err).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with("2 records, 1 rows written, 1 errors"),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains("skipped 1 records for unknown stations"),
+            "{}",
+            out
+        );
+        assert!(String::from_utf8(err).unwrap().contains("x.prices: line 3"));
+        assert_eq!(rows(), 1);
+        // the row refers to the database's Gold, not the commodity table's
+        let item_id: u32 = conn
+            .query_row("SELECT item_id FROM StationItem", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(item_id, 42);
+
+        assert!(run(
+            &args(&["--max-errors", "0"]),
+            &mut Vec::new(),
+            &mut Vec::new()
+        )
+        .is_err());
+
+        // a gzip dump is decompressed into the cache once, and read from
+        // there after
+        let mut encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&dump).unwrap(),
+            flate2::Compression::fast(),
+        );
+        encoder
+            .write_all(b"@ SOL/Abraham Lincoln\nGold 9400 9000\n")
+            .unwrap();
+        encoder.finish().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let cached = args(&["--cache-dir", cache_dir.to_str().unwrap()]);
+        for _ in 0..2 {
+            let mut out = Vec::new();
+            run(&cached, &mut out, &mut Vec::new()).unwrap();
+            assert!(String::from_utf8(out)
+                .unwrap()
+                .starts_with("1 records, 1 rows written"));
+        }
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,229 @@
+//! `traderusty`: the fast paths of traderusty-core from the shell, for
+//! scripts and CI jobs without a Python environment.
+//!
+//! Output meant for other programs goes to stdout; errors and warnings go
+//! to stderr, prefixed with the file they're about. The exit status is 1
+//! when something failed, bad lines included, and 2 for bad arguments.
+
+use std::error::Error;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use traderusty_core::options::{ParseOptions, ReadOptions, Strictness};
+use traderusty_core::prices::{self, PricesError};
+use traderusty_core::rusty;
+
+mod import;
+mod route;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(name = "traderusty", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Counts the lines of a file, or of standard input given "-".
+    Count { file: PathBuf },
+    /// Parses a .prices file, gzip-compressed or not, reporting bad lines
+    /// and summing up what it holds.
+    ParsePrices {
+        file: PathBuf,
+        /// Print each record as a line of JSON instead of the summary.
+        #[arg(long)]
+        json: bool,
+        /// Accept hand edits: stray whitespace in readings, thousands
+        /// separators.
+        #[arg(long)]
+        lenient: bool,
+        /// Give up after this many bad lines.
+        #[arg(long, value_name = "N")]
+        max_errors: Option<usize>,
+    },
+    /// Imports a .prices dump into a TradeDangerous database, replacing the
+    /// prices of every station it lists.
+    Import(import::ImportArgs),
+    /// Plots the fewest jumps between two systems in a TradeDangerous
+    /// database, and with --capacity the best load to carry.
+    Route(route::RouteArgs),
+}
+
+fn strictness(lenient: bool) -> Strictness {
+    if lenient {
+        Strictness::Lenient
+    } else {
+        Strictness::Strict
+    }
+}
+
+fn count(file: &Path, out: &mut impl Write) -> Result<ExitCode> {
+    let lines = rusty::count_file_lines(file)?;
+    writeln!(out, "{}", lines)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn parse_prices(
+    file: &Path,
+    json: bool,
+    options: &ParseOptions,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> Result<ExitCode> {
+    let mut reader = prices::open_prices(file, &ReadOptions::default(), options)?.count_distinct();
+    let (mut records, mut errors, mut warnings) = (0, 0, 0);
+    while let Some(record) = reader.next() {
+        match record {
+            Ok(record) => {
+                records += 1;
+                if json {
+                    serde_json::to_writer(&mut *out, &record)?;
+                    writeln!(out)?;
+                }
+            }
+            Err(PricesError::Io(e)) => return Err(e.into()),
+            Err(e) => {
+                errors += 1;
+                writeln!(err, "{}: {}", file.display(), e)?;
+            }
+        }
+        for warning in reader.take_warnings() {
+            warnings += 1;
+            writeln!(err, "{}: warning: {}", file.display(), warning)?;
+        }
+    }
+    if !json {
+        let distinct = reader.distinct().cloned().unwrap_or_default();
+        writeln!(
+            out,
+            "{} records, {} errors, {} warnings",
+            records, errors, warnings
+        )?;
+        writeln!(
+            out,
+            "about {} systems, {} stations, {} commodities",
+            distinct.systems(),
+            distinct.stations(),
+            distinct.commodities()
+        )?;
+    }
+    Ok(if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn run(command: Command, out: &mut impl Write) -> Result<ExitCode> {
+    match command {
+        Command::Count { file } => count(&file, out),
+        Command::ParsePrices {
+            file,
+            json,
+            lenient,
+            max_errors,
+        } => {
+            let options = ParseOptions {
+                strictness: strictness(lenient),
+                max_errors,
+                ..Default::default()
+            };
+            parse_prices(&file, json, &options, out, &mut io::stderr())
+        }
+        Command::Import(args) => import::run(&args, out, &mut io::stderr()),
+        Command::Route(args) => route::run(&args, out),
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut out = BufWriter::new(io::stdout().lock());
+    let result = run(cli.command, &mut out).and_then(|code| {
+        out.flush()?;
+        Ok(code)
+    });
+    match result {
+        Ok(code) => code,
+        // Piped into head, say, and it stopped reading.
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("traderusty: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use tempfile::NamedTempFile;
+
+    const PRICES: &str = "\
+@ SOL/Abraham Lincoln
+   + Chemicals
+      Hydrogen Fuel       105    110    2079L   28430H  2024-05-01 12:00:00
+      Explosives          abc      0        ?        -
+";
+
+    fn prices_file() -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(PRICES.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from(["traderusty", "parse-prices", "x.prices", "--json"]);
+        assert!(matches!(
+            cli.unwrap().command,
+            Command::ParsePrices { json: true, .. }
+        ));
+        assert!(Cli::try_parse_from(["traderusty", "count"]).is_err());
+    }
+
+    #[test]
+    fn test_count() {
+        let file = prices_file();
+        let mut out = Vec::new();
+        assert_eq!(count(file.path(), &mut out).unwrap(), ExitCode::SUCCESS);
+        assert_eq!(out, b"4\n");
+        assert!(count(Path::new("no-such-file"), &mut out).is_err());
+    }
+
+    #[test]
+    fn test_parse_prices() {
+        let file = prices_file();
+        let options = ParseOptions::default();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = parse_prices(file.path(), true, &options, &mut out, &mut err).unwrap();
+        assert_eq!(code, ExitCode::FAILURE);
+        let out = String::from_utf8(out).unwrap();
+        let record: serde_json::Value = serde_json::from_str(out.trim_end()).unwrap();
+        assert_eq!(record["item"], "Hydrogen Fuel");
+        assert_eq!(record["supply_units"], 28430);
+        assert_eq!(record["modified"], 1714564800);
+        let err = String::from_utf8(err).unwrap();
+        assert!(err.contains(": line 4, column "), "{}", err);
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        parse_prices(file.path(), false, &options, &mut out, &mut err).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.starts_with("1 records, 1 errors, 0 warnings\nabout 1 systems, 1 stations"),
+            "{}",
+            out
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! `traderusty route`: plots a route between two systems from a
+//! TradeDangerous database, and with `--capacity` the best load to carry
+//! along it.
+//!
+//! The route is the fewest jumps within the jump range; fuel isn't
+//! modelled, since TD's System table doesn't say which stars can be
+//! scooped.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::ValueEnum;
+use traderusty_core::commodities::commodity_by_id;
+use traderusty_core::db;
+use traderusty_core::graph::JumpGraph;
+use traderusty_core::ids::{ItemId, StationId};
+use traderusty_core::names::canonical_name;
+use traderusty_core::route::{Action, Route, RouteKind};
+use traderusty_core::router::{LinearFuel, Router};
+use traderusty_core::store::MarketStore;
+use traderusty_core::system::System;
+use traderusty_core::trade::{best_load, Load, TradeLimits};
+
+use crate::Result;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// A hop per line, with what to buy and sell.
+    Text,
+    /// The route's JSON export document.
+    Json,
+    /// A CSV row per hop.
+    Csv,
+    /// System names only, for pasting into the galaxy map.
+    Systems,
+}
+
+#[derive(clap::Args)]
+pub struct RouteArgs {
+    /// The TradeDangerous database to load systems, stations and prices
+    /// from.
+    #[arg(long, value_name = "FILE", default_value = "data/TradeDangerous.db")]
+    db: PathBuf,
+    /// The system to start from.
+    #[arg(long, value_name = "SYSTEM")]
+    from: String,
+    /// The system to end at.
+    #[arg(long, value_name = "SYSTEM")]
+    to: String,
+    /// Longest jump in ly.
+    #[arg(long, value_name = "LY")]
+    jump: f64,
+    /// Cargo hold size in tons: plan a trade run, buying the best load at a
+    /// station in the first system to sell at one in the last.
+    #[arg(long, value_name = "TONS")]
+    capacity: Option<u32>,
+    /// Credits to spend on the load; unlimited if not given.
+    #[arg(long, value_name = "CR", requires = "capacity")]
+    credits: Option<i64>,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+/// Burns nothing, leaving the jump range as the only limit on a hop.
+const NO_FUEL: LinearFuel = LinearFuel {
+    tank: 0.,
+    fuel_per_ly: 0.,
+    max_fuel_per_jump: 0.,
+};
+
+fn find_system(systems: &[System], name: &str) -> Result<u32> {
+    let wanted = canonical_name(name);
+    let node = systems
+        .iter()
+        .position(|system| canonical_name(&system.name) == wanted)
+        .ok_or_else(|| format!("unknown system: {}", name))?;
+    Ok(node as u32)
+}
+
+/// The most profitable load between any station in `from` and any in `to`,
+/// as (source station, destination station, load).
+fn best_trade(
+    store: &MarketStore,
+    from: &str,
+    to: &str,
+    limits: &TradeLimits,
+) -> Option<(StationId, StationId, Load)> {
+    let destinations = store.stations_in_system(to);
+    store
+        .stations_in_system(from)
+        .into_iter()
+        .flat_map(|source| destinations.iter().map(move |&dest| (source, dest)))
+        .filter(|(source, dest)| source != dest)
+        .map(|(source, dest)| (source, dest, best_load(store, source, dest, limits)))
+        .filter(|(_, _, load)| load.profit() > 0)
+        .max_by_key(|(source, dest, load)| (load.profit(), std::cmp::Reverse((*source, *dest))))
+}
+
+/// An item's name in the database's Item table, or else the commodity
+/// table's.
+fn item_name(item_id: ItemId, items: &HashMap<ItemId, String>) -> String {
+    items
+        .get(&item_id)
+        .cloned()
+        .or_else(|| commodity_by_id(item_id).map(|commodity| commodity.name.to_string()))
+        .unwrap_or_else(|| format!("#{}", item_id))
+}
+
+/// Turns a nav route into a trade run: the load is bought at the first hop
+/// and sold at the last, which is repeated if both are in one system.
+fn add_trade(
+    route: &mut Route,
+    (source, dest, load): (StationId, StationId, Load),
+    names: &HashMap<StationId, String>,
+    items: &HashMap<ItemId, String>,
+) {
+    route.kind = RouteKind::Trade;
+    if route.hops.len() == 1 {
+        let hop = route.hops[0].clone();
+        route.push(&hop.system, None, [hop.x, hop.y, hop.z]);
+    }
+    let first = &mut route.hops[0];
+    first.station = names.get(&source).cloned();
+    first.actions = load
+        .trades
+        .iter()
+        .map(|trade| Action::Buy {
+            item: item_name(trade.item_id, items),
+            units: trade.units,
+            price: trade.buy_price,
+        })
+        .collect();
+    let last = route.hops.last_mut().unwrap();
+    last.station = names.get(&dest).cloned();
+    last.actions = load
+        .trades
+        .iter()
+        .map(|trade| Action::Sell {
+            item: item_name(trade.item_id, items),
+            units: trade.units,
+            price: trade.sell_price,
+        })
+        .collect();
+}
+
+fn write_text(route: &Route, out: &mut impl Write) -> Result<()> {
+    writeln!(
+        out,
+        "{} jumps, {:.2} ly",
+        route.jumps(),
+        route.total_distance()
+    )?;
+    let mut profit = 0;
+    for hop in &route.hops {
+        match &hop.station {
+            Some(station) => writeln!(out, "{:>8.2}  {}/{}", hop.distance, hop.system, station)?,
+            None => writeln!(out, "{:>8.2}  {}", hop.distance, hop.system)?,
+        }
+        for action in &hop.actions {
+            writeln!(out, "          {}", action)?;
+            match action {
+                Action::Buy { units, price, .. } => profit -= *units as i64 * *price as i64,
+                Action::Sell { units, price, .. } => profit += *units as i64 * *price as i64,
+                Action::Refuel => {}
+            }
+        }
+    }
+    if route.kind == RouteKind::Trade {
+        writeln!(out, "{} cr profit", profit)?;
+    }
+    Ok(())
+}
+
+pub fn run(args: &RouteArgs, out: &mut impl Write) -> Result<ExitCode> {
+    if !args.db.is_file() {
+        return Err(format!("{}: no such database", args.db.display()).into());
+    }
+    let conn = db::open_database(&args.db)?;
+    let systems = db::load_systems(&conn)?;
+    let (start, goal) = (
+        find_system(&systems, &args.from)?,
+        find_system(&systems, &args.to)?,
+    );
+    let positions: Vec<[f64; 3]> = systems.iter().map(System::position).collect();
+    let graph = JumpGraph::build(&positions, args.jump);
+    let plan = Router::new(&graph, &[])
+        .route(start, goal, &NO_FUEL, 0.)
+        .ok_or_else(|| {
+            format!(
+                "no route from {} to {} with {} ly jumps",
+                args.from, args.to, args.jump
+            )
+        })?;
+    let mut route = Route::new(RouteKind::Nav);
+    for waypoint in &plan.waypoints {
+        let system = &systems[waypoint.node as usize];
+        route.push(&system.name, None, system.position());
+    }
+
+    if let Some(capacity) = args.capacity {
+        let store = db::load_store(&conn)?;
+        let limits = TradeLimits {
+            capacity,
+            credits: args.credits.unwrap_or(i64::MAX),
+        };
+        let (from, to) = (&systems[start as usize].name, &systems[goal as usize].name);
+        let trade = best_trade(&store, from, to, &limits)
+            .ok_or_else(|| format!("nothing to trade from {} to {}", from, to))?;
+        let items = if db::has_table(&conn, "Item")? {
+            db::load_item_names(&conn)?
+        } else {
+            HashMap::new()
+        };
+        add_trade(&mut route, trade, &db::load_station_names(&conn)?, &items);
+    }
+
+    match args.format {
+        Format::Text => write_text(&route, out)?,
+        Format::Json => writeln!(out, "{}", route.to_json(Some(2))?)?,
+        Format::Csv => write!(out, "{}", route.to_csv())?,
+        Format::Systems => writeln!(out, "{}", route.system_list())?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_route() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("TradeDangerous.db");
+        let mut conn = db::open_database(&path).unwrap();
+        traderusty_core::migrate::migrate(&mut conn).unwrap();
+        // Gold's id is the database's own, not the commodity table's
+        conn.execute_batch(
+            "CREATE TABLE System (system_id INTEGER PRIMARY KEY, name TEXT, \
+                 pos_x REAL, pos_y REAL, pos_z REAL);
+             CREATE TABLE Station (station_id INTEGER PRIMARY KEY, name TEXT, system_id INTEGER);
+             INSERT INTO System VALUES (1, 'Sol', 0, 0, 0), (2, 'Barnard''s Star', 15, 0, 0),
+                 (3, 'Lave', 30, 0, 0), (4, 'Far', 100, 0, 0);
+             INSERT INTO Station VALUES (7, 'Abraham Lincoln', 1), (9, 'Lave Station', 3);
+             CREATE TABLE Item (item_id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO Item VALUES (5, 'Gold');
+             INSERT INTO StationItem VALUES
+                 (7, 5, 0, 0, 0, 9000, 1000, 3, '2024-05-01 00:00:00', 0),
+                 (9, 5, 9500, 500, 2, 0, 0, 0, '2024-05-01 00:00:00', 0);",
+        )
+        .unwrap();
+        let db_path = path.to_str().unwrap();
+        let route = |extra: &[&str]| {
+            let mut argv = vec!["traderusty", "route", "--db", db_path, "--jump", "20"];
+            argv.extend(extra);
+            let args = match crate::Cli::parse_from(argv).command {
+                crate::Command::Route(args) => args,
+                _ => unreachable!(),
+            };
+            let mut out = Vec::new();
+            run(&args, &mut out).map(|_| String::from_utf8(out).unwrap())
+        };
+
+        let out = route(&["--from", "sol", "--to", "lave", "--format", "systems"]).unwrap();
+        assert_eq!(out, "Sol\nBarnard's Star\nLave\n");
+        // names are matched by canonical name
+        let out = route(&[
+            "--from",
+            "ＳＯＬ",
+            "--to",
+            "barnard's  star",
+            "--format",
+            "systems",
+        ]);
+        assert_eq!(out.unwrap(), "Sol\nBarnard's Star\n");
+
+        let out = route(&["--from", "Sol", "--to", "Lave", "--capacity", "720"]).unwrap();
+        assert_eq!(
+            out,
+            "2 jumps, 30.00 ly\n    \
+                 0.00  Sol/Abraham Lincoln\n          buy 720 Gold @ 9000\n   \
+                15.00  Barnard's Star\n   \
+                15.00  Lave/Lave Station\n          sell 720 Gold @ 9500\n\
+             360000 cr profit\n"
+        );
+        let out = route(&[
+            "--from",
+            "Sol",
+            "--to",
+            "Lave",
+            "--capacity",
+            "720",
+            "--format",
+            "json",
+        ])
+        .unwrap();
+        assert!(out.contains("\"kind\": \"trade\""), "{}", out);
+
+        let err = route(&["--from", "Sol", "--to", "Far"]).unwrap_err();
+        assert_eq!(err.to_string(), "no route from Sol to Far with 20 ly jumps");
+        let err = route(&["--from", "Lave", "--to", "Sol", "--capacity", "720"]).unwrap_err();
+        assert_eq!(err.to_string(), "nothing to trade from Lave to Sol");
+        assert!(route(&["--from", "Sol", "--to", "Nowhere"]).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+//! Average, lowest and highest prices of each commodity, across the galaxy
+//! and within each region, worked out in one pass over a .prices dump or a
+//! MarketStore rather than a query per commodity.
+//!
+//! The sell price is what stations pay (the demand price) and the buy price
+//! what they charge (the supply price). A price of 0 means the station
+//! doesn't trade that way and isn't counted.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+use tracing::info;
+
+use crate::commodities::canonical_commodity;
+use crate::ids::ItemId;
+use crate::options::ParseOptions;
+use crate::prices::{read_source, PricesError};
+use crate::region::RegionMap;
+use crate::source::DataSource;
+use crate::store::MarketStore;
+
+/// Count, total, lowest and highest of a set of prices.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PriceStats {
+    pub count: u64,
+    pub sum: i64,
+    pub min: i32,
+    pub max: i32,
+}
+
+impl PriceStats {
+    /// Counts a price; 0 and below aren't prices and are ignored.
+    pub fn add(&mut self, price: i32) {
+        if price <= 0 {
+            return;
+        }
+        if self.count == 0 {
+            (self.min, self.max) = (price, price);
+        } else {
+            self.min = self.min.min(price);
+            self.max = self.max.max(price);
+        }
+        self.count += 1;
+        self.sum += i64::from(price);
+    }
+
+    pub fn merge(&mut self, other: &PriceStats) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// The mean price, NaN if there were none.
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            f64::NAN
+        } else {
+            self.sum as f64 / self.count as f64
+        }
+    }
+}
+
+/// Price statistics by commodity, one row per commodity for the whole
+/// galaxy and then one per region and commodity, column by column. Rows
+/// are ordered by region, in the RegionMap's order, then commodity. A
+/// commodity nobody sells has a sell count of 0, a NaN mean and a minimum
+/// and maximum of 0, and likewise for buying.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PriceAggregates {
+    /// The region the row covers; None for the whole galaxy.
+    pub region: Vec<Option<String>>,
+    /// The commodity's FDev id, which is both the commodity table's and
+    /// TradeDangerous' item_id, so a dump's rows and a store's line up.
+    pub item_id: Vec<ItemId>,
+    pub sell_count: Vec<u64>,
+    pub sell_mean: Vec<f64>,
+    pub sell_min: Vec<i32>,
+    pub sell_max: Vec<i32>,
+    pub buy_count: Vec<u64>,
+    pub buy_mean: Vec<f64>,
+    pub buy_min: Vec<i32>,
+    pub buy_max: Vec<i32>,
+}
+
+impl PriceAggregates {
+    pub fn len(&self) -> usize {
+        self.item_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.item_id.is_empty()
+    }
+
+    fn push(&mut self, region: Option<String>, item_id: ItemId, [sell, buy]: [PriceStats; 2]) {
+        self.region.push(region);
+        self.item_id.push(item_id);
+        self.sell_count.push(sell.count);
+        self.sell_mean.push(sell.mean());
+        self.sell_min.push(sell.min);
+        self.sell_max.push(sell.max);
+        self.buy_count.push(buy.count);
+        self.buy_mean.push(buy.mean());
+        self.buy_min.push(buy.min);
+        self.buy_max.push(buy.max);
+    }
+}
+
+/// Gathers listings' prices a listing at a time.
+#[derive(Clone, Debug, Default)]
+pub struct PriceAggregator<'a> {
+    regions: Option<&'a RegionMap>,
+    /// (region index or None for the galaxy, item) -> [sell, buy].
+    stats: BTreeMap<(Option<usize>, ItemId), [PriceStats; 2]>,
+    /// Region index of each position seen, as the bits of its coordinates:
+    /// a station's listings share its position, and polygon tests aren't
+    /// cheap.
+    region_cache: HashMap<[u64; 3], Option<usize>>,
+}
+
+impl<'a> PriceAggregator<'a> {
+    /// Gathers prices for the whole galaxy only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gathers prices for the whole galaxy and for each of `regions`.
+    pub fn by_region(regions: &'a RegionMap) -> Self {
+        Self {
+            regions: Some(regions),
+            ..Self::default()
+        }
+    }
+
+    fn region(&mut self, pos: [f64; 3]) -> Option<usize> {
+        let regions = self.regions?;
+        *self
+            .region_cache
+            .entry(pos.map(f64::to_bits))
+            .or_insert_with(|| regions.index_for(pos[0], pos[1], pos[2]))
+    }
+
+    /// Counts a listing's sell (demand) and buy (supply) prices, in its
+    /// region too if given its position.
+    pub fn add(&mut self, item_id: ItemId, sell: i32, buy: i32, pos: Option<[f64; 3]>) {
+        if sell <= 0 && buy <= 0 {
+            return;
+        }
+        let region = pos.and_then(|pos| self.region(pos));
+        let scopes = [Some(None), region.map(Some)];
+        for scope in scopes.into_iter().flatten() {
+            let [sell_stats, buy_stats] = self.stats.entry((scope, item_id)).or_default();
+            sell_stats.add(sell);
+            buy_stats.add(buy);
+        }
+    }
+
+    /// Folds in what another aggregator over the same regions gathered.
+    pub fn merge(&mut self, other: &PriceAggregator<'_>) {
+        for (key, [sell, buy]) in &other.stats {
+            let stats = self.stats.entry(*key).or_default();
+            stats[0].merge(sell);
+            stats[1].merge(buy);
+        }
+    }
+
+    pub fn finish(self) -> PriceAggregates {
+        let mut aggregates = PriceAggregates::default();
+        for ((region, item_id), stats) in self.stats {
+            let name = region
+                .zip(self.regions)
+                .map(|(idx, regions)| regions.regions()[idx].name.clone());
+            aggregates.push(name, item_id, stats);
+        }
+        aggregates
+    }
+}
+
+/// Price statistics of every listing in a store, by region if given
+/// regions; stations without a position count towards the galaxy only.
+pub fn aggregate_store(store: &MarketStore, regions: Option<&RegionMap>) -> PriceAggregates {
+    let mut aggregator = regions.map_or_else(PriceAggregator::new, PriceAggregator::by_region);
+    for item in store.iter() {
+        let pos = store.station_position(item.station_id);
+        aggregator.add(item.item_id, item.demand_price, item.supply_price, pos);
+    }
+    aggregator.finish()
+}
+
+/// Price statistics of the records of a .prices dump, streamed. Commodities
+/// are recognised by any of their names; records of unknown commodities and
+/// lines that fail to parse are skipped. With regions, `system_position`
+/// places each system, and systems it can't place count towards the galaxy
+/// only. Only I/O errors fail it.
+#[tracing::instrument(skip_all, fields(source = source.name()))]
+pub fn aggregate_prices<S: DataSource>(
+    source: &S,
+    parse_options: &ParseOptions,
+    regions: Option<&RegionMap>,
+    system_position: impl Fn(&str) -> Option<[f64; 3]>,
+) -> io::Result<PriceAggregates> {
+    let parse_options = ParseOptions {
+        max_errors: None,
+        ..parse_options.clone()
+    };
+    let mut reader = read_source(source, &parse_options)?;
+    let mut aggregator = regions.map_or_else(PriceAggregator::new, PriceAggregator::by_region);
+    // the system of the records before, and its position
+    let mut system: Option<(String, Option<[f64; 3]>)> = None;
+    let (mut records, mut skipped) = (0, 0);
+    while let Some(record) = reader.next() {
+        let record = match record {
+            Ok(record) => record,
+            Err(PricesError::Io(e)) => return Err(e),
+            Err(PricesError::Parse { .. }) => {
+                skipped += 1;
+                continue;
+            }
+        };
+        reader.take_warnings();
+        let Some(commodity) = canonical_commodity(&record.item) else {
+            skipped += 1;
+            continue;
+        };
+        let pos = match &system {
+            Some((name, pos)) if *name == record.system => *pos,
+            _ if regions.is_none() => None,
+            _ => {
+                let pos = system_position(&record.system);
+                system = Some((record.system.clone(), pos));
+                pos
+            }
+        };
+        aggregator.add(commodity.id, record.demand_price, record.supply_price, pos);
+        records += 1;
+    }
+    let aggregates = aggregator.finish();
+    info!(
+        records,
+        skipped,
+        rows = aggregates.len(),
+        "aggregated .prices prices"
+    );
+    Ok(aggregates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::StationId;
+    use crate::market::StationItem;
+    use crate::source::MemorySource;
+
+    const REGIONS: &str = r#"[
+        {"name": "Near", "polygon": [[-10, -10], [10, -10], [10, 10], [-10, 10]]},
+        {"name": "Far", "polygon": [[90, -10], [110, -10], [110, 10], [90, 10]]}
+    ]"#;
+
+    #[test]
+    fn test_price_stats() {
+        let mut stats = PriceStats::default();
+        assert!(stats.mean().is_nan());
+        for price in [300, 0, 100, -1, 200] {
+            stats.add(price);
+        }
+        assert_eq!((stats.count, stats.min, stats.max), (3, 100, 300));
+        assert_eq!(stats.mean(), 200.);
+        let mut other = PriceStats::default();
+        other.add(700);
+        stats.merge(&other);
+        stats.merge(&PriceStats::default());
+        assert_eq!((stats.count, stats.min, stats.max), (4, 100, 700));
+        assert_eq!(stats.mean(), 325.);
+    }
+
+    #[test]
+    fn test_aggregate_store() {
+        let mut store = MarketStore::new();
+        store.add_station(StationId(1), "Sol", 0., 0., 0.);
+        store.add_station(StationId(2), "Wolf 359", 100., 5., 0.);
+        for (station_id, item_id, demand_price, supply_price) in [
+            (1, 10, 120, 100),
+            (2, 10, 140, 0),
+            (3, 10, 160, 80),
+            (1, 20, 0, 0),
+            (2, 20, 0, 50),
+        ] {
+            store.insert(StationItem {
+                station_id: StationId(station_id),
+                item_id: ItemId(item_id),
+                demand_price,
+                supply_price,
+                ..Default::default()
+            });
+        }
+
+        let galaxy = aggregate_store(&store, None);
+        assert_eq!(galaxy.region, [None, None]);
+        assert_eq!(galaxy.item_id, [ItemId(10), ItemId(20)]);
+        assert_eq!(galaxy.sell_count, [3, 0]);
+        assert_eq!(galaxy.sell_mean[0], 140.);
+        assert!(galaxy.sell_mean[1].is_nan());
+        assert_eq!((galaxy.buy_min[0], galaxy.buy_max[0]), (80, 100));
+        assert_eq!(galaxy.buy_mean, [90., 50.]);
+
+        let regions = RegionMap::from_json(REGIONS).unwrap();
+        let by_region = aggregate_store(&store, Some(&regions));
+        let near = Some("Near".to_string());
+        let far = Some("Far".to_string());
+        assert_eq!(
+            by_region.region,
+            [None, None, near.clone(), far.clone(), far]
+        );
+        assert_eq!(by_region.item_id[2..], [ItemId(10), ItemId(10), ItemId(20)]);
+        assert_eq!(by_region.sell_max[2..], [120, 140, 0]);
+        assert_eq!(by_region.buy_count[2..], [1, 0, 1]);
+        assert_eq!(by_region.sell_count[..2], galaxy.sell_count);
+    }
+
+    #[test]
+    fn test_aggregate_prices() {
+        let prices = "\
+@ SOL/Abraham Lincoln
+   + Metals
+      Gold                 9000    8500          0?     100M
+@ WOLF 359/Lomas Orbital
+   + Metals
+      Gold                 9400       0          0?       -
+      Silver               4800    4600          0?      30L
+      Unobtainium           100      90          0?       1L
+@ SOL/Daedalus
+   + Metals
+      Gold                 9200    8600          0?      50M
+";
+        let source = MemorySource::new("listings.prices", prices.as_bytes().to_vec());
+        let regions = RegionMap::from_json(REGIONS).unwrap();
+        let position = |system: &str| match system {
+            "SOL" => Some([0., 0., 0.]),
+            _ => None,
+        };
+        let aggregates =
+            aggregate_prices(&source, &ParseOptions::default(), Some(&regions), position).unwrap();
+        let gold = canonical_commodity("gold").unwrap().id;
+        let silver = canonical_commodity("silver").unwrap().id;
+        let mut items = [gold, silver];
+        items.sort();
+        assert_eq!(&aggregates.item_id[..2], items);
+        let row = aggregates
+            .item_id
+            .iter()
+            .position(|&id| id == gold)
+            .unwrap();
+        assert_eq!(aggregates.sell_count[row], 3);
+        assert_eq!(aggregates.sell_mean[row], 9200.);
+        assert_eq!(aggregates.buy_mean[row], 8550.);
+        // Wolf 359 has no position, so only Sol's stations are near
+        assert_eq!(aggregates.region[2..], [Some("Near".to_string())]);
+        assert_eq!(aggregates.item_id[2], gold);
+        assert_eq!(aggregates.sell_mean[2], 9100.);
+
+        let without = aggregate_prices(&source, &ParseOptions::default(), None, |_| None).unwrap();
+        assert_eq!(without.len(), 2);
+
+        // the same listings loaded into a store, as from TradeDangerous'
+        // database, aggregate under the same ids
+        let mut store = MarketStore::new();
+        for (station_id, item_id, demand_price, supply_price) in [
+            (1, gold, 9000, 8500),
+            (2, gold, 9400, 0),
+            (2, silver, 4800, 4600),
+            (3, gold, 9200, 8600),
+        ] {
+            store.insert(StationItem {
+                station_id: StationId(station_id),
+                item_id,
+                demand_price,
+                supply_price,
+                ..Default::default()
+            });
+        }
+        assert_eq!(aggregate_store(&store, None), without);
+    }
+}
@@ -0,0 +1,183 @@
+//! A Bloom filter of stations, so streaming importers can drop the records
+//! of stations nobody asked for before they reach a database lookup.
+//!
+//! A Bloom filter answers "definitely not" or "probably": a station it
+//! hasn't seen is reported present only at the false positive rate it was
+//! sized for. So the filter holds the stations the caller wants, and
+//! records of any other station are skipped without a lookup; the rare
+//! false positive just goes on to the lookup that would have happened
+//! anyway.
+//!
+//! Stations are keyed by their market id where the data has one (journal,
+//! EDDN), or by a hash of their canonical system and station names where it
+//! doesn't (.prices). Keys and the serialized form are stable across builds
+//! and platforms, so a filter can be saved and handed to another process.
+
+use std::io;
+
+use crate::hash::{fnv1a, mix, FNV_OFFSET_BASIS};
+use crate::names::canonical_name;
+
+/// Leading bytes of a serialized filter.
+const MAGIC: &[u8; 4] = b"TRBF";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 4 + 8 + 8;
+
+/// The key of a station named by its system and station names.
+pub fn station_key(system: &str, station: &str) -> u64 {
+    let hash = fnv1a(FNV_OFFSET_BASIS, canonical_name(system).as_bytes());
+    fnv1a(fnv1a(hash, b"/"), canonical_name(station).as_bytes())
+}
+
+/// The key of a station known by its market id.
+pub fn market_key(market_id: u64) -> u64 {
+    fnv1a(FNV_OFFSET_BASIS, &market_id.to_le_bytes())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BloomFilter {
+    words: Vec<u64>,
+    bits: u64,
+    hashes: u32,
+    /// Keys inserted, counting repeats.
+    len: u64,
+}
+
+impl BloomFilter {
+    /// A filter sized for `expected` keys at the given false positive rate.
+    pub fn new(expected: usize, false_positive_rate: f64) -> Self {
+        let rate = false_positive_rate.clamp(1e-9, 0.5);
+        let expected = expected.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-expected * rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let hashes = ((bits as f64 / expected) * ln2).round().clamp(1.0, 32.0) as u32;
+        Self {
+            words: vec![0; bits.div_ceil(64) as usize],
+            bits,
+            hashes,
+            len: 0,
+        }
+    }
+
+    /// Keys inserted so far, counting repeats.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The bits a key sets, by double hashing.
+    fn positions(&self, key: u64) -> impl Iterator<Item = u64> {
+        let (bits, first) = (self.bits, mix(key));
+        let step = mix(first) | 1;
+        (0..self.hashes as u64).map(move |i| first.wrapping_add(i.wrapping_mul(step)) % bits)
+    }
+
+    pub fn insert(&mut self, key: u64) {
+        for bit in self.positions(key) {
+            self.words[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+    }
+
+    /// False if the key was definitely never inserted.
+    pub fn contains(&self, key: u64) -> bool {
+        self.positions(key)
+            .all(|bit| self.words[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    pub fn insert_station(&mut self, system: &str, station: &str) {
+        self.insert(station_key(system, station));
+    }
+
+    pub fn contains_station(&self, system: &str, station: &str) -> bool {
+        self.contains(station_key(system, station))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.words.len() * 8);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.hashes.to_le_bytes());
+        bytes.extend_from_slice(&self.bits.to_le_bytes());
+        bytes.extend_from_slice(&self.len.to_le_bytes());
+        for word in &self.words {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Reads back a filter written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(invalid("not a Bloom filter"));
+        }
+        if bytes[4] != VERSION {
+            return Err(invalid("unsupported Bloom filter version"));
+        }
+        let hashes = u32::from_le_bytes(bytes[5..9].try_into().unwrap());
+        let bits = u64::from_le_bytes(bytes[9..17].try_into().unwrap());
+        let len = u64::from_le_bytes(bytes[17..25].try_into().unwrap());
+        let body = &bytes[HEADER_LEN..];
+        if hashes == 0 || bits == 0 || body.len() as u64 != bits.div_ceil(64) * 8 {
+            return Err(invalid("truncated or corrupt Bloom filter"));
+        }
+        let words = body
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        Ok(Self {
+            words,
+            bits,
+            hashes,
+            len,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for id in 0..1000u64 {
+            filter.insert(market_key(id));
+        }
+        assert_eq!(filter.len(), 1000);
+        assert!((0..1000u64).all(|id| filter.contains(market_key(id))));
+        let false_positives = (1000..11000u64)
+            .filter(|&id| filter.contains(market_key(id)))
+            .count();
+        assert!(false_positives < 200, "{} false positives", false_positives);
+
+        filter.insert_station("Sol", "Abraham Lincoln");
+        assert!(filter.contains_station("SOL", "abraham  lincoln"));
+        assert!(!BloomFilter::new(10, 0.01).contains_station("Sol", "Abraham Lincoln"));
+    }
+
+    #[test]
+    fn test_serialization() {
+        let mut filter = BloomFilter::new(100, 0.001);
+        filter.insert_station("Sol", "Daedalus");
+        let bytes = filter.to_bytes();
+        assert_eq!(&bytes[..4], b"TRBF");
+        let read = BloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(read, filter);
+        assert!(read.contains_station("Sol", "Daedalus"));
+
+        assert!(BloomFilter::from_bytes(b"TRBF").is_err());
+        assert!(BloomFilter::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        let err = BloomFilter::from_bytes(&newer).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported Bloom filter version");
+    }
+}
@@ -0,0 +1,436 @@
+//! A directory of expensive intermediate artifacts, such as decompressed
+//! dumps, kept under a byte budget so a re-run after a tweak starts warm
+//! instead of redoing the work.
+//!
+//! Each artifact is a file named by the SHA-256 of its key. When the files
+//! add up to more than the budget, the least recently used go first.
+//! Recency is kept in the files' modification times, so it carries over
+//! from one run to the next. An artifact bigger than the whole budget isn't
+//! kept at all, and writing it stops as soon as it's past the budget.
+//!
+//! The cache isn't shared between processes: two running at once over one
+//! directory each keep their own accounts and may evict each other's
+//! files, which then count as misses.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use tracing::debug;
+
+use crate::gzip;
+use crate::source::{open_decoded, DataSource, FileSource};
+use crate::verify::sha256_hex;
+
+/// Default budget: 4 GiB.
+pub const DEFAULT_BUDGET: u64 = 4 << 30;
+
+const EXTENSION: &str = "chunk";
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+    size: u64,
+    /// When it was last used, on the cache's own clock.
+    used: u64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    files: HashMap<String, Entry>,
+    size: u64,
+    clock: u64,
+}
+
+impl Entries {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn insert(&mut self, name: String, size: u64) {
+        let used = self.tick();
+        if let Some(old) = self.files.insert(name, Entry { size, used }) {
+            self.size -= old.size;
+        }
+        self.size += size;
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Entry> {
+        let entry = self.files.remove(name)?;
+        self.size -= entry.size;
+        Some(entry)
+    }
+
+    /// The least recently used file other than `keep`.
+    fn oldest(&self, keep: &str) -> Option<String> {
+        self.files
+            .iter()
+            .filter(|(name, _)| name.as_str() != keep)
+            .min_by_key(|(_, entry)| entry.used)
+            .map(|(name, _)| name.clone())
+    }
+}
+
+/// The error a Limited writer fails with once past its limit.
+#[derive(Debug)]
+struct OverBudget;
+
+impl fmt::Display for OverBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "artifact is bigger than the chunk cache's budget")
+    }
+}
+
+impl std::error::Error for OverBudget {}
+
+/// Counts the bytes written through it, failing with OverBudget rather
+/// than writing past `limit`.
+struct Limited<W> {
+    inner: W,
+    written: u64,
+    limit: u64,
+}
+
+impl<W: Write> Write for Limited<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written + buf.len() as u64 > self.limit {
+            return Err(io::Error::other(OverBudget));
+        }
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn is_over_budget(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|inner| inner.is::<OverBudget>())
+}
+
+#[derive(Debug)]
+pub struct ChunkCache {
+    dir: PathBuf,
+    budget: u64,
+    entries: Mutex<Entries>,
+}
+
+impl ChunkCache {
+    /// A cache in `dir`, created if it doesn't exist, holding at most
+    /// `budget` bytes. Files already there are taken up, and the oldest
+    /// evicted if they're over the budget.
+    pub fn new(dir: impl AsRef<Path>, budget: u64) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let mut found = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            let name = entry.file_name().to_string_lossy().into_owned();
+            found.push((modified, name, meta.len()));
+        }
+        found.sort();
+        let mut entries = Entries::default();
+        for (_, name, size) in found {
+            entries.insert(name, size);
+        }
+        let cache = Self {
+            dir: dir.to_path_buf(),
+            budget,
+            entries: Mutex::new(entries),
+        };
+        cache.evict(&mut cache.entries.lock().unwrap(), "")?;
+        debug!(
+            dir = %dir.display(),
+            files = cache.len(),
+            bytes = cache.size(),
+            "opened chunk cache"
+        );
+        Ok(cache)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// Bytes in the cache.
+    pub fn size(&self) -> u64 {
+        self.entries.lock().unwrap().size
+    }
+
+    /// Artifacts in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn file_name(key: &str) -> String {
+        format!("{}.{}", sha256_hex(key.as_bytes()), EXTENSION)
+    }
+
+    /// Removes least recently used files, other than `keep`, until the
+    /// cache is within its budget.
+    fn evict(&self, entries: &mut Entries, keep: &str) -> io::Result<()> {
+        while entries.size > self.budget {
+            let Some(name) = entries.oldest(keep) else {
+                break;
+            };
+            let entry = entries.remove(&name).unwrap();
+            debug!(file = name, bytes = entry.size, "evicting from chunk cache");
+            match fs::remove_file(self.dir.join(&name)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The path of the artifact stored for `key`, if there is one, marking
+    /// it used. For reading artifacts too big to want in memory; one may be
+    /// evicted by a later `put`, which doesn't affect a reader that already
+    /// has it open.
+    pub fn path(&self, key: &str) -> Option<PathBuf> {
+        let name = Self::file_name(key);
+        let path = self.dir.join(&name);
+        let mut entries = self.entries.lock().unwrap();
+        entries.files.get(&name)?;
+        // marking it used on disk keeps its place for the next run
+        match File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+        {
+            Ok(()) => {
+                let used = entries.tick();
+                entries.files.get_mut(&name).unwrap().used = used;
+                Some(path)
+            }
+            Err(e) => {
+                // deleted from under us, most likely
+                debug!(path = %path.display(), "chunk cache file unusable: {}", e);
+                entries.remove(&name);
+                None
+            }
+        }
+    }
+
+    /// The artifact stored for `key`, if there is one.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(key)?).ok()
+    }
+
+    /// Stores `data` for `key`, replacing any artifact already stored for
+    /// it. Returns its path, or None if it's bigger than the budget and so
+    /// wasn't kept.
+    pub fn put(&self, key: &str, data: &[u8]) -> io::Result<Option<PathBuf>> {
+        self.put_with(key, |out| out.write_all(data))
+    }
+
+    /// Stores what `write` writes for `key`, without holding it all in
+    /// memory. The file is only put in place once `write` has succeeded.
+    /// Once more than the budget has been written, writes fail, and if
+    /// `write` passes that failure on, nothing is stored and None returned.
+    pub fn put_with(
+        &self,
+        key: &str,
+        write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<Option<PathBuf>> {
+        let mut out = Limited {
+            inner: BufWriter::new(tempfile::NamedTempFile::new_in(&self.dir)?),
+            written: 0,
+            limit: self.budget,
+        };
+        match write(&mut out) {
+            Ok(()) => {}
+            Err(e) if is_over_budget(&e) => {
+                debug!(key, budget = self.budget, "too big for chunk cache");
+                self.remove(key)?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        }
+        let file = out.inner.into_inner().map_err(|e| e.into_error())?;
+        let size = file.as_file().metadata()?.len();
+        let name = Self::file_name(key);
+        let path = self.dir.join(&name);
+        let mut entries = self.entries.lock().unwrap();
+        file.persist(&path).map_err(|e| e.error)?;
+        entries.insert(name.clone(), size);
+        self.evict(&mut entries, &name)?;
+        Ok(Some(path))
+    }
+
+    /// Removes the artifact stored for `key`, returning whether there was
+    /// one.
+    pub fn remove(&self, key: &str) -> io::Result<bool> {
+        let name = Self::file_name(key);
+        if self.entries.lock().unwrap().remove(&name).is_none() {
+            return Ok(false);
+        }
+        match fs::remove_file(self.dir.join(&name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(true),
+        }
+    }
+
+    /// Removes every artifact.
+    pub fn clear(&self) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        for name in entries.files.keys() {
+            fs::remove_file(self.dir.join(name))?;
+        }
+        *entries = Entries::default();
+        Ok(())
+    }
+}
+
+/// A gzip-compressed file decompressed into the cache, so it's only
+/// decompressed once: the cached copy if the file's been decompressed since
+/// it last changed, else a new one. The copy is dated as the file was.
+///
+/// Returns the source unchanged when there's no point or no way to cache
+/// it: it isn't gzip, it's standard input or has no modification time to
+/// tell versions apart, or decompressed it's bigger than the budget.
+pub fn decompressed(source: &FileSource, cache: &ChunkCache) -> io::Result<FileSource> {
+    let (Some(size), Some(modified)) = (source.size(), source.modified()) else {
+        return Ok(source.clone());
+    };
+    let path = fs::canonicalize(source.path())?;
+    let key = format!("decompressed {} {} {}", path.display(), size, modified);
+    let cached =
+        |path: PathBuf| FileSource::new(path, source.options()).modified_at(Some(modified));
+    if let Some(path) = cache.path(&key) {
+        debug!(source = source.name(), "using cached decompressed copy");
+        return Ok(cached(path));
+    }
+    if !gzip::is_gzip(source.open()?.fill_buf()?) {
+        return Ok(source.clone());
+    }
+    debug!(source = source.name(), "decompressing into chunk cache");
+    let stored = cache.put_with(&key, |out| {
+        let (mut reader, _) = open_decoded(source)?;
+        io::copy(&mut reader, out).map(drop)
+    })?;
+    Ok(stored.map_or_else(|| source.clone(), cached))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::ReadOptions;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Read;
+
+    #[test]
+    fn test_chunk_cache_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ChunkCache::new(dir.path(), 10).unwrap();
+        assert!(cache.is_empty());
+        assert!(cache.put("a", b"aaaa").unwrap().is_some());
+        cache.put("b", b"bbbb").unwrap();
+        assert_eq!(cache.get("a").unwrap(), b"aaaa");
+        // over budget: b, used longest ago, goes
+        cache.put("c", b"cccc").unwrap();
+        assert_eq!((cache.len(), cache.size()), (2, 8));
+        assert_eq!(cache.get("b"), None);
+        assert!(cache.get("a").is_some() && cache.get("c").is_some());
+        // replacing an artifact counts only the new one
+        cache.put("c", b"cc").unwrap();
+        assert_eq!(cache.size(), 6);
+        // too big to keep, and nothing else is evicted for it
+        assert_eq!(cache.put("huge", &[0; 11]).unwrap(), None);
+        assert_eq!((cache.len(), cache.get("huge")), (2, None));
+        assert!(cache.remove("a").unwrap());
+        assert!(!cache.remove("a").unwrap());
+        assert_eq!(cache.size(), 2);
+    }
+
+    #[test]
+    fn test_chunk_cache_stops_writing_past_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ChunkCache::new(dir.path(), 1000).unwrap();
+        let mut writes = 0;
+        let stored = cache
+            .put_with("endless", |out| loop {
+                writes += 1;
+                out.write_all(&[0; 300])?;
+            })
+            .unwrap();
+        assert_eq!(stored, None);
+        assert_eq!(writes, 4);
+        assert!(cache.is_empty());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+        // other failures are passed on
+        let failed = cache.put_with("broken", |_| Err(io::Error::other("broken")));
+        assert_eq!(failed.unwrap_err().to_string(), "broken");
+    }
+
+    #[test]
+    fn test_chunk_cache_reopens() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ChunkCache::new(dir.path(), 100).unwrap();
+        for key in ["old", "middle", "new"] {
+            cache.put(key, key.as_bytes()).unwrap();
+            // file times need to differ to order the files on reopening
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        cache.path("old").unwrap();
+        fs::write(dir.path().join("notes.txt"), "not ours").unwrap();
+        drop(cache);
+
+        // reopened with a smaller budget: "middle" is now the oldest
+        let cache = ChunkCache::new(dir.path(), 9).unwrap();
+        assert_eq!(cache.size(), 6);
+        assert_eq!(cache.get("middle"), None);
+        assert_eq!(cache.get("old").unwrap(), b"old");
+        cache.clear().unwrap();
+        assert!(cache.is_empty());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_decompressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ChunkCache::new(dir.path().join("cache"), DEFAULT_BUDGET).unwrap();
+        let dump = dir.path().join("listings.prices.gz");
+        let mut encoder = GzEncoder::new(File::create(&dump).unwrap(), Compression::fast());
+        encoder.write_all(b"@ SOL/Galileo\n").unwrap();
+        encoder.finish().unwrap();
+        let source = FileSource::new(&dump, &ReadOptions::default());
+
+        let first = decompressed(&source, &cache).unwrap();
+        assert_ne!(first.path(), source.path());
+        assert_eq!(first.modified(), source.modified());
+        let mut text = String::new();
+        first.open().unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(text, "@ SOL/Galileo\n");
+        assert_eq!(decompressed(&source, &cache).unwrap().path(), first.path());
+        assert_eq!(cache.len(), 1);
+
+        // a plain file is read where it is
+        let plain = dir.path().join("plain.prices");
+        fs::write(&plain, "@ SOL/Galileo\n").unwrap();
+        let source = FileSource::new(&plain, &ReadOptions::default());
+        assert_eq!(decompressed(&source, &cache).unwrap().path(), plain);
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,44 @@
+//! Reading the clock, where there is one. On wasm32-unknown-unknown there
+//! isn't, and std's Instant::now and SystemTime::now panic; there timings
+//! come out as zero and the current time as unknown.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const HAS_CLOCK: bool = !cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+/// Measures the time since it was started.
+#[derive(Clone, Copy, Debug)]
+pub struct Stopwatch(Option<Instant>);
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self(HAS_CLOCK.then(Instant::now))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.0.map_or(Duration::ZERO, |started| started.elapsed())
+    }
+}
+
+/// Seconds since the Unix epoch, or None without a clock.
+pub fn unix_time() -> Option<i64> {
+    HAS_CLOCK.then(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as i64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock() {
+        let stopwatch = Stopwatch::start();
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(stopwatch.elapsed() >= Duration::from_millis(2));
+        // 2024-01-01
+        assert!(unix_time().unwrap() > 1_704_067_200);
+    }
+}
@@ -0,0 +1,1139 @@
+//! A read-optimised on-disk copy of the StationItem listings, stored column
+//! by column and memory-mapped, so a query tool can open a full-galaxy
+//! dataset in milliseconds instead of reading every row out of SQLite.
+//!
+//! The file is a header, a directory giving each column's place, size and
+//! encoding, then the columns themselves, each starting on an 8-byte
+//! boundary. Values are little-endian. Rows are ordered by station, then
+//! item, so a station's listings are a run of rows found by binary search.
+//! Opening checks only the header and directory; the columns are read
+//! through the mapping as they're used.
+//!
+//! Prices are plain, every value at its full width. IDs and timestamps are
+//! bit-packed in blocks of 64: station IDs, sorted, as the differences
+//! between neighbours, and item IDs and timestamps, which cluster, as
+//! offsets from the block's least value. Every value of a block takes the
+//! same number of bits, so a block decodes in straight-line loops the
+//! compiler vectorizes, and any row is found through the block directory
+//! without reading the blocks before it. Units are mostly small, so they're
+//! zigzag varints, with an index of where every 64th
+//! starts so a row is found without decoding the column up to it. Levels
+//! are the 2-bit codes of `level_code`, four to a byte, followed by the
+//! rows whose level has no code and their levels.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::Path;
+
+use memmap2::Mmap;
+use tracing::info;
+
+use crate::ids::{ItemId, StationId};
+use crate::market::{level_code, level_from_code, StationItem, StationItemColumns};
+
+const MAGIC: &[u8; 8] = b"TRCOLS01";
+
+/// Magic, row count, column count.
+const HEADER_LEN: usize = 24;
+
+/// Offset, length and encoding of one column.
+const DIRECTORY_ENTRY_LEN: usize = 24;
+
+/// Values stored one after another at their full width.
+const PLAIN: u64 = 0;
+
+/// Zigzag LEB128 varints, after the index of where each block starts.
+const VARINT: u64 = 1;
+
+/// 2-bit level codes, then the levels that have none.
+const LEVELS: u64 = 2;
+
+/// Bit-packed blocks of differences between neighbouring values.
+const DELTA: u64 = 3;
+
+/// Bit-packed blocks of offsets from the block's least value.
+const FRAME: u64 = 4;
+
+/// Values per block of a varint column.
+const VARINT_BLOCK: usize = 64;
+
+/// Values per block of a delta or frame column.
+const PACKED_BLOCK: usize = 64;
+
+/// Base, reference and where the packed values are, of one block.
+const BLOCK_ENTRY_LEN: usize = 24;
+
+/// The columns in the order they're stored.
+pub const COLUMNS: [&str; 9] = [
+    "station_id",
+    "item_id",
+    "demand_price",
+    "demand_units",
+    "demand_level",
+    "supply_price",
+    "supply_units",
+    "supply_level",
+    "modified",
+];
+
+/// Each column's encoding, and value width if it's plain.
+const LAYOUT: [(u64, usize); 9] = [
+    (DELTA, 0),
+    (FRAME, 0),
+    (PLAIN, 4),
+    (VARINT, 0),
+    (LEVELS, 0),
+    (PLAIN, 4),
+    (VARINT, 0),
+    (LEVELS, 0),
+    (FRAME, 0),
+];
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn padding(len: usize) -> usize {
+    len.next_multiple_of(8) - len
+}
+
+fn padded(len: usize) -> usize {
+    len.next_multiple_of(8)
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn varint_len(value: u64) -> usize {
+    (64 - (value | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+fn write_varint(out: &mut impl Write, mut value: u64) -> io::Result<()> {
+    let (mut buf, mut len) = ([0; 10], 0);
+    loop {
+        buf[len] = value as u8 & 0x7f;
+        value >>= 7;
+        len += 1;
+        if value == 0 {
+            break;
+        }
+        buf[len - 1] |= 0x80;
+    }
+    out.write_all(&buf[..len])
+}
+
+/// The varint at the start of `bytes` and its length. A damaged file gives
+/// wrong values rather than a panic.
+fn read_varint(bytes: &[u8]) -> (u64, usize) {
+    let mut value = 0;
+    for (n, byte) in bytes.iter().take(10).enumerate() {
+        value |= u64::from(byte & 0x7f) << (7 * n);
+        if byte & 0x80 == 0 {
+            return (value, n + 1);
+        }
+    }
+    (value, bytes.len().min(10))
+}
+
+/// Bytes of a levels column's codes, padded, for `rows` rows.
+fn level_codes_len(rows: usize) -> usize {
+    padded(rows.div_ceil(4))
+}
+
+/// A value stored little-endian at a fixed width.
+pub trait Plain: Copy + 'static {
+    const WIDTH: usize;
+
+    fn from_le(bytes: &[u8]) -> Self;
+
+    fn write_le(self, out: &mut impl Write) -> io::Result<()>;
+}
+
+macro_rules! plain {
+    ($($ty:ty),*) => {
+        $(impl Plain for $ty {
+            const WIDTH: usize = std::mem::size_of::<$ty>();
+
+            fn from_le(bytes: &[u8]) -> Self {
+                <$ty>::from_le_bytes(bytes.try_into().unwrap())
+            }
+
+            fn write_le(self, out: &mut impl Write) -> io::Result<()> {
+                out.write_all(&self.to_le_bytes())
+            }
+        })*
+    };
+}
+
+plain!(i32, i64, u32, u64);
+
+/// An integer stored bit-packed.
+pub trait Packed: Copy + 'static {
+    fn from_i64(value: i64) -> Self;
+
+    fn to_i64(self) -> i64;
+}
+
+impl Packed for i64 {
+    fn from_i64(value: i64) -> Self {
+        value
+    }
+
+    fn to_i64(self) -> i64 {
+        self
+    }
+}
+
+impl Packed for StationId {
+    fn from_i64(value: i64) -> Self {
+        Self(value as u32)
+    }
+
+    fn to_i64(self) -> i64 {
+        self.0.into()
+    }
+}
+
+impl Packed for ItemId {
+    fn from_i64(value: i64) -> Self {
+        Self(value as u32)
+    }
+
+    fn to_i64(self) -> i64 {
+        self.0.into()
+    }
+}
+
+/// One column of a ColumnStore, read through the mapping.
+#[derive(Clone, Copy, Debug)]
+pub struct Column<'a, T> {
+    bytes: &'a [u8],
+    kind: PhantomData<T>,
+}
+
+impl<'a, T: Plain> Column<'a, T> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            kind: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len() / T::WIDTH
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The value in row `idx`.
+    ///
+    /// # Panics
+    ///
+    /// If `idx` is out of range.
+    pub fn get(&self, idx: usize) -> T {
+        T::from_le(&self.bytes[idx * T::WIDTH..(idx + 1) * T::WIDTH])
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = T> + 'a {
+        self.bytes.chunks_exact(T::WIDTH).map(T::from_le)
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+}
+
+/// A varint column of a ColumnStore.
+#[derive(Clone, Copy, Debug)]
+pub struct VarintColumn<'a> {
+    /// Where each block of values starts in `data`.
+    index: Column<'a, u64>,
+    data: &'a [u8],
+    len: usize,
+}
+
+impl<'a> VarintColumn<'a> {
+    fn new(bytes: &'a [u8], len: usize) -> Self {
+        let (index, data) = bytes.split_at(len.div_ceil(VARINT_BLOCK) * 8);
+        Self {
+            index: Column::new(index),
+            data,
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The value in row `idx`, decoded from the start of its block.
+    ///
+    /// # Panics
+    ///
+    /// If `idx` is out of range.
+    pub fn get(&self, idx: usize) -> i64 {
+        assert!(idx < self.len, "row {} out of range", idx);
+        let mut at = self.index.get(idx / VARINT_BLOCK) as usize;
+        for _ in 0..idx % VARINT_BLOCK {
+            at += read_varint(self.data.get(at..).unwrap_or_default()).1;
+        }
+        unzigzag(read_varint(self.data.get(at..).unwrap_or_default()).0)
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = i64> + 'a {
+        let data = self.data;
+        let mut at = 0;
+        (0..self.len).map(move |_| {
+            let (value, len) = read_varint(data.get(at..).unwrap_or_default());
+            at += len;
+            unzigzag(value)
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<i64> {
+        self.iter().collect()
+    }
+}
+
+/// A delta or frame column of a ColumnStore: a directory of blocks, then
+/// their packed values as 64-bit words.
+#[derive(Clone, Copy, Debug)]
+pub struct PackedColumn<'a, T> {
+    blocks: &'a [u8],
+    words: &'a [u8],
+    len: usize,
+    delta: bool,
+    kind: PhantomData<T>,
+}
+
+impl<'a, T: Packed> PackedColumn<'a, T> {
+    fn new(bytes: &'a [u8], len: usize, delta: bool) -> Self {
+        let (blocks, words) = bytes.split_at(len.div_ceil(PACKED_BLOCK) * BLOCK_ENTRY_LEN);
+        Self {
+            blocks,
+            words,
+            len,
+            delta,
+            kind: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// A block's base and reference, its packed words and their width.
+    fn block(&self, block: usize) -> (i64, i64, &'a [u8], u32) {
+        let entry = block * BLOCK_ENTRY_LEN;
+        let base = read_u64(self.blocks, entry) as i64;
+        let reference = read_u64(self.blocks, entry + 8) as i64;
+        let place = read_u64(self.blocks, entry + 16);
+        let (word, bits) = ((place >> 8) as usize, (place & 0xff).min(64) as u32);
+        // a damaged file gives wrong values rather than a panic
+        let words = word
+            .checked_mul(8)
+            .and_then(|at| self.words.get(at..at + bits as usize * 8))
+            .unwrap_or_default();
+        let bits = if words.is_empty() { 0 } else { bits };
+        (base, reference, words, bits)
+    }
+
+    /// Decodes block `block` whole.
+    fn decode(&self, block: usize) -> [i64; PACKED_BLOCK] {
+        let (base, reference, words, bits) = self.block(block);
+        // the words copied out with one spare, so every value reads the
+        // word after its own without a branch
+        let mut packed = [0u64; PACKED_BLOCK + 1];
+        for (word, bytes) in packed.iter_mut().zip(words.chunks_exact(8)) {
+            *word = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        let mask = mask(bits);
+        let mut values = [0; PACKED_BLOCK];
+        for (n, value) in values.iter_mut().enumerate() {
+            let bit = n * bits as usize;
+            let (word, shift) = (bit / 64, bit % 64);
+            let low = packed[word] >> shift;
+            let high = (packed[word + 1] << 1) << (63 - shift);
+            *value = ((low | high) & mask) as i64;
+        }
+        if self.delta {
+            let mut previous = base;
+            for value in values.iter_mut().skip(1) {
+                previous = previous.wrapping_add(reference).wrapping_add(*value);
+                *value = previous;
+            }
+            values[0] = base;
+        } else {
+            for value in &mut values {
+                *value = base.wrapping_add(*value);
+            }
+        }
+        values
+    }
+
+    /// The value in row `idx`.
+    ///
+    /// # Panics
+    ///
+    /// If `idx` is out of range.
+    pub fn get(&self, idx: usize) -> T {
+        assert!(idx < self.len, "row {} out of range", idx);
+        let (base, reference, words, bits) = self.block(idx / PACKED_BLOCK);
+        let n = idx % PACKED_BLOCK;
+        let value = if !self.delta {
+            base.wrapping_add(unpack_or_zero(words, bits, n) as i64)
+        } else {
+            (1..=n).fold(base, |value, n| {
+                value
+                    .wrapping_add(reference)
+                    .wrapping_add(unpack_or_zero(words, bits, n) as i64)
+            })
+        };
+        T::from_i64(value)
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = T> + 'a {
+        let column = *self;
+        let mut values = [0; PACKED_BLOCK];
+        (0..self.len).map(move |idx| {
+            if idx % PACKED_BLOCK == 0 {
+                values = column.decode(idx / PACKED_BLOCK);
+            }
+            T::from_i64(values[idx % PACKED_BLOCK])
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        let mut values = Vec::with_capacity(self.len);
+        for block in 0..self.len.div_ceil(PACKED_BLOCK) {
+            let decoded = self.decode(block);
+            let rest = self.len - values.len();
+            values.extend(
+                decoded[..rest.min(PACKED_BLOCK)]
+                    .iter()
+                    .map(|&v| T::from_i64(v)),
+            );
+        }
+        values
+    }
+}
+
+/// Value `n` of `bits` bits packed little-endian into `words`.
+fn unpack(words: &[u8], bits: u32, n: usize) -> u64 {
+    let bit = n * bits as usize;
+    let (word, shift) = (bit / 64, (bit % 64) as u32);
+    let mut value = read_u64(words, word * 8) >> shift;
+    if shift + bits > 64 {
+        value |= read_u64(words, word * 8 + 8) << (64 - shift);
+    }
+    value & mask(bits)
+}
+
+fn unpack_or_zero(words: &[u8], bits: u32, n: usize) -> u64 {
+    if bits == 0 {
+        0
+    } else {
+        unpack(words, bits, n)
+    }
+}
+
+fn mask(bits: u32) -> u64 {
+    u64::MAX.checked_shr(64 - bits).unwrap_or(0)
+}
+
+/// A levels column of a ColumnStore, read with the units column whose
+/// values its codes depend on.
+#[derive(Clone, Copy, Debug)]
+pub struct LevelColumn<'a> {
+    codes: &'a [u8],
+    /// Rows whose level has no code, in order, and their levels.
+    exception_rows: Column<'a, u64>,
+    exception_levels: Column<'a, i32>,
+    units: VarintColumn<'a>,
+}
+
+impl<'a> LevelColumn<'a> {
+    fn new(bytes: &'a [u8], units: VarintColumn<'a>) -> Self {
+        let codes_len = level_codes_len(units.len());
+        let exceptions = read_u64(bytes, codes_len) as usize;
+        let rows_at = codes_len + 8;
+        let levels_at = rows_at + exceptions * 8;
+        Self {
+            codes: &bytes[..codes_len],
+            exception_rows: Column::new(&bytes[rows_at..levels_at]),
+            exception_levels: Column::new(&bytes[levels_at..levels_at + exceptions * 4]),
+            units,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    fn code(&self, idx: usize) -> u8 {
+        self.codes[idx / 4] >> (idx % 4 * 2)
+    }
+
+    fn level(&self, idx: usize, units: i64) -> i32 {
+        let rows = self.exception_rows;
+        let n = partition_point(rows.len(), |n| rows.get(n) < idx as u64);
+        if n < rows.len() && rows.get(n) == idx as u64 {
+            self.exception_levels.get(n)
+        } else {
+            level_from_code(self.code(idx), units)
+        }
+    }
+
+    /// The level in row `idx`.
+    ///
+    /// # Panics
+    ///
+    /// If `idx` is out of range.
+    pub fn get(&self, idx: usize) -> i32 {
+        self.level(idx, self.units.get(idx))
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = i32> + 'a {
+        let column = *self;
+        let mut next_exception = 0;
+        self.units.iter().enumerate().map(move |(idx, units)| {
+            let rows = column.exception_rows;
+            if next_exception < rows.len() && rows.get(next_exception) == idx as u64 {
+                next_exception += 1;
+                column.exception_levels.get(next_exception - 1)
+            } else {
+                level_from_code(column.code(idx), units)
+            }
+        })
+    }
+
+    pub fn to_vec(&self) -> Vec<i32> {
+        self.iter().collect()
+    }
+}
+
+/// A column store file, mapped.
+#[derive(Debug)]
+pub struct ColumnStore {
+    map: Mmap,
+    rows: usize,
+    /// Where each column's bytes are in the mapping.
+    columns: [Range<usize>; COLUMNS.len()],
+}
+
+impl ColumnStore {
+    /// Maps a file written by `write_column_store`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let store = Self::map(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        info!(path = %path.display(), rows = store.rows, "opened column store");
+        Ok(store)
+    }
+
+    fn map(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the mapping is read-only and every read is bounds checked
+        // against its length; a file truncated underneath us would fault,
+        // the same caveat as any memory-mapped file.
+        let map = unsafe { Mmap::map(&file)? };
+        let directory_end = HEADER_LEN + COLUMNS.len() * DIRECTORY_ENTRY_LEN;
+        if map.len() < directory_end || &map[..8] != MAGIC {
+            return Err(invalid("not a column store"));
+        }
+        if read_u64(&map, 16) != COLUMNS.len() as u64 {
+            return Err(invalid("column store has unexpected columns"));
+        }
+        let rows = usize::try_from(read_u64(&map, 8)).map_err(|_| invalid("too many rows"))?;
+        let mut columns: [Range<usize>; COLUMNS.len()] = Default::default();
+        for (n, (name, (encoding, width))) in COLUMNS.iter().zip(LAYOUT).enumerate() {
+            let entry = HEADER_LEN + n * DIRECTORY_ENTRY_LEN;
+            let (offset, len) = (read_u64(&map, entry), read_u64(&map, entry + 8));
+            if read_u64(&map, entry + 16) != encoding {
+                return Err(invalid(&format!("column {} has an unknown encoding", name)));
+            }
+            let in_file = offset
+                .checked_add(len)
+                .is_some_and(|end| end <= map.len() as u64);
+            if !in_file {
+                return Err(invalid(&format!("column {} is truncated", name)));
+            }
+            let column = offset as usize..(offset + len) as usize;
+            if !column_fits(&map[column.clone()], rows, encoding, width) {
+                return Err(invalid(&format!("column {} is the wrong size", name)));
+            }
+            columns[n] = column;
+        }
+        Ok(Self { map, rows, columns })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    fn bytes(&self, n: usize) -> &[u8] {
+        &self.map[self.columns[n].clone()]
+    }
+
+    fn column<T: Plain>(&self, n: usize) -> Column<'_, T> {
+        Column::new(self.bytes(n))
+    }
+
+    fn packed_column<T: Packed>(&self, n: usize) -> PackedColumn<'_, T> {
+        PackedColumn::new(self.bytes(n), self.rows, LAYOUT[n].0 == DELTA)
+    }
+
+    fn varint_column(&self, n: usize) -> VarintColumn<'_> {
+        VarintColumn::new(self.bytes(n), self.rows)
+    }
+
+    fn level_column(&self, n: usize, units: usize) -> LevelColumn<'_> {
+        LevelColumn::new(self.bytes(n), self.varint_column(units))
+    }
+
+    pub fn station_id(&self) -> PackedColumn<'_, StationId> {
+        self.packed_column(0)
+    }
+
+    pub fn item_id(&self) -> PackedColumn<'_, ItemId> {
+        self.packed_column(1)
+    }
+
+    pub fn demand_price(&self) -> Column<'_, i32> {
+        self.column(2)
+    }
+
+    pub fn demand_units(&self) -> VarintColumn<'_> {
+        self.varint_column(3)
+    }
+
+    pub fn demand_level(&self) -> LevelColumn<'_> {
+        self.level_column(4, 3)
+    }
+
+    pub fn supply_price(&self) -> Column<'_, i32> {
+        self.column(5)
+    }
+
+    pub fn supply_units(&self) -> VarintColumn<'_> {
+        self.varint_column(6)
+    }
+
+    pub fn supply_level(&self) -> LevelColumn<'_> {
+        self.level_column(7, 6)
+    }
+
+    pub fn modified(&self) -> PackedColumn<'_, i64> {
+        self.packed_column(8)
+    }
+
+    /// Reassembles row `idx`.
+    ///
+    /// # Panics
+    ///
+    /// If `idx` is out of range.
+    pub fn row(&self, idx: usize) -> StationItem {
+        let demand_units = self.demand_units().get(idx);
+        let supply_units = self.supply_units().get(idx);
+        StationItem {
+            station_id: self.station_id().get(idx),
+            item_id: self.item_id().get(idx),
+            demand_price: self.demand_price().get(idx),
+            demand_units,
+            demand_level: self.demand_level().level(idx, demand_units),
+            supply_price: self.supply_price().get(idx),
+            supply_units,
+            supply_level: self.supply_level().level(idx, supply_units),
+            modified: self.modified().get(idx),
+        }
+    }
+
+    /// Every row, in order, read column by column a block at a time.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = StationItem> + '_ {
+        let mut station_id = self.station_id().iter();
+        let mut item_id = self.item_id().iter();
+        let mut demand_price = self.demand_price().iter();
+        let mut demand_units = self.demand_units().iter();
+        let mut demand_level = self.demand_level().iter();
+        let mut supply_price = self.supply_price().iter();
+        let mut supply_units = self.supply_units().iter();
+        let mut supply_level = self.supply_level().iter();
+        let mut modified = self.modified().iter();
+        // every column has a value for every row
+        (0..self.rows).map(move |_| StationItem {
+            station_id: station_id.next().unwrap(),
+            item_id: item_id.next().unwrap(),
+            demand_price: demand_price.next().unwrap(),
+            demand_units: demand_units.next().unwrap(),
+            demand_level: demand_level.next().unwrap(),
+            supply_price: supply_price.next().unwrap(),
+            supply_units: supply_units.next().unwrap(),
+            supply_level: supply_level.next().unwrap(),
+            modified: modified.next().unwrap(),
+        })
+    }
+
+    /// The rows holding a station's listings.
+    pub fn station_rows(&self, station_id: StationId) -> Range<usize> {
+        let stations = self.station_id();
+        let first = partition_point(self.rows, |idx| stations.get(idx) < station_id);
+        let end = partition_point(self.rows, |idx| stations.get(idx) <= station_id);
+        first..end
+    }
+
+    /// All the listings of a station, ordered by item.
+    pub fn station_items(&self, station_id: StationId) -> Vec<StationItem> {
+        self.station_rows(station_id)
+            .map(|idx| self.row(idx))
+            .collect()
+    }
+
+    /// Returns a single listing.
+    pub fn get(&self, station_id: StationId, item_id: ItemId) -> Option<StationItem> {
+        let rows = self.station_rows(station_id);
+        let items = self.item_id();
+        let idx = rows.start + partition_point(rows.len(), |n| items.get(rows.start + n) < item_id);
+        (idx < rows.end && items.get(idx) == item_id).then(|| self.row(idx))
+    }
+
+    /// Every row, copied out column by column.
+    pub fn to_columns(&self) -> StationItemColumns {
+        StationItemColumns {
+            station_id: self.station_id().to_vec(),
+            item_id: self.item_id().to_vec(),
+            demand_price: self.demand_price().to_vec(),
+            demand_units: self.demand_units().to_vec(),
+            demand_level: self.demand_level().to_vec(),
+            supply_price: self.supply_price().to_vec(),
+            supply_units: self.supply_units().to_vec(),
+            supply_level: self.supply_level().to_vec(),
+            modified: self.modified().to_vec(),
+        }
+    }
+}
+
+/// Whether a column's bytes are the size its encoding needs for `rows`.
+fn column_fits(bytes: &[u8], rows: usize, encoding: u64, width: usize) -> bool {
+    match encoding {
+        PLAIN => rows.checked_mul(width) == Some(bytes.len()),
+        DELTA | FRAME => rows
+            .div_ceil(PACKED_BLOCK)
+            .checked_mul(BLOCK_ENTRY_LEN)
+            .is_some_and(|blocks| blocks <= bytes.len()),
+        VARINT => rows
+            .div_ceil(VARINT_BLOCK)
+            .checked_mul(8)
+            .is_some_and(|index| index <= bytes.len()),
+        LEVELS => {
+            let codes_len = level_codes_len(rows);
+            if bytes.len() < codes_len + 8 {
+                return false;
+            }
+            let exceptions = read_u64(bytes, codes_len);
+            exceptions
+                .checked_mul(12)
+                .and_then(|len| usize::try_from(len).ok())
+                .and_then(|len| len.checked_add(codes_len + 8))
+                == Some(bytes.len())
+        }
+        _ => false,
+    }
+}
+
+/// The first of `0..len` for which `pred` is false, `pred` being true for
+/// some prefix of the range.
+fn partition_point(len: usize, pred: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if pred(mid) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// Writes one field of every row as a plain column, returning its length.
+fn write_plain<T: Plain>(
+    out: &mut impl Write,
+    rows: &[&StationItem],
+    field: impl Fn(&StationItem) -> T,
+) -> io::Result<usize> {
+    for item in rows {
+        field(item).write_le(out)?;
+    }
+    Ok(rows.len() * T::WIDTH)
+}
+
+/// Writes one field of every row as a delta or frame column, returning its
+/// length.
+fn write_packed(
+    out: &mut impl Write,
+    rows: &[&StationItem],
+    delta: bool,
+    field: impl Fn(&StationItem) -> i64,
+) -> io::Result<usize> {
+    // what's packed for each value: its difference from the one before, or
+    // the value itself, less the block's reference
+    let block_values = |block: &[&StationItem]| -> (i64, i64, Vec<u64>) {
+        let values: Vec<i64> = block.iter().map(|item| field(item)).collect();
+        if delta {
+            let deltas: Vec<i64> = values.windows(2).map(|w| w[1].wrapping_sub(w[0])).collect();
+            let reference = deltas.iter().copied().min().unwrap_or(0);
+            let packed = std::iter::once(0)
+                .chain(deltas.iter().map(|d| d.wrapping_sub(reference) as u64))
+                .collect();
+            (values[0], reference, packed)
+        } else {
+            let base = values.iter().copied().min().unwrap_or(0);
+            let packed = values.iter().map(|v| v.wrapping_sub(base) as u64).collect();
+            (base, 0, packed)
+        }
+    };
+    let bits = |packed: &[u64]| 64 - packed.iter().fold(0, |all, v| all | v).leading_zeros();
+
+    let mut word = 0;
+    for block in rows.chunks(PACKED_BLOCK) {
+        let (base, reference, packed) = block_values(block);
+        let bits = bits(&packed);
+        base.write_le(out)?;
+        reference.write_le(out)?;
+        ((word << 8) | u64::from(bits)).write_le(out)?;
+        word += u64::from(bits);
+    }
+    for block in rows.chunks(PACKED_BLOCK) {
+        let (_, _, packed) = block_values(block);
+        let bits = bits(&packed);
+        let mut words = vec![0u64; bits as usize];
+        for (n, value) in packed.into_iter().enumerate().filter(|_| bits > 0) {
+            let bit = n * bits as usize;
+            let (at, shift) = (bit / 64, (bit % 64) as u32);
+            words[at] |= value << shift;
+            if shift + bits > 64 {
+                words[at + 1] |= value >> (64 - shift);
+            }
+        }
+        for word in words {
+            word.write_le(out)?;
+        }
+    }
+    Ok(rows.len().div_ceil(PACKED_BLOCK) * BLOCK_ENTRY_LEN + word as usize * 8)
+}
+
+/// Writes one field of every row as a varint column, returning its length.
+fn write_varints(
+    out: &mut impl Write,
+    rows: &[&StationItem],
+    field: impl Fn(&StationItem) -> i64,
+) -> io::Result<usize> {
+    let mut at = 0;
+    for (idx, item) in rows.iter().enumerate() {
+        if idx % VARINT_BLOCK == 0 {
+            (at as u64).write_le(out)?;
+        }
+        at += varint_len(zigzag(field(item)));
+    }
+    for item in rows {
+        write_varint(out, zigzag(field(item)))?;
+    }
+    Ok(rows.len().div_ceil(VARINT_BLOCK) * 8 + at)
+}
+
+/// Writes a level and its units of every row as a levels column, returning
+/// its length.
+fn write_levels(
+    out: &mut impl Write,
+    rows: &[&StationItem],
+    field: impl Fn(&StationItem) -> (i32, i64),
+) -> io::Result<usize> {
+    let mut exceptions = Vec::new();
+    let mut byte = 0;
+    for (idx, item) in rows.iter().enumerate() {
+        let (level, units) = field(item);
+        let code = level_code(level, units).unwrap_or_else(|| {
+            exceptions.push((idx as u64, level));
+            0
+        });
+        byte |= code << (idx % 4 * 2);
+        if idx % 4 == 3 || idx + 1 == rows.len() {
+            out.write_all(&[byte])?;
+            byte = 0;
+        }
+    }
+    out.write_all(&[0; 8][..padding(rows.len().div_ceil(4))])?;
+    (exceptions.len() as u64).write_le(out)?;
+    for (row, _) in &exceptions {
+        row.write_le(out)?;
+    }
+    for (_, level) in &exceptions {
+        level.write_le(out)?;
+    }
+    Ok(level_codes_len(rows.len()) + 8 + exceptions.len() * 12)
+}
+
+/// Writes a column store file of listings, returning how many rows it
+/// holds. Where a station and item are listed more than once, the last
+/// listing is kept. The file is written whole and then renamed into place,
+/// so a reader never maps half of it.
+pub fn write_column_store<'a>(
+    path: impl AsRef<Path>,
+    items: impl IntoIterator<Item = &'a StationItem>,
+) -> io::Result<usize> {
+    let path = path.as_ref();
+    let rows: BTreeMap<(StationId, ItemId), &StationItem> = items
+        .into_iter()
+        .map(|item| ((item.station_id, item.item_id), item))
+        .collect();
+    let rows: Vec<&StationItem> = rows.into_values().collect();
+
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let file = tempfile::NamedTempFile::new_in(dir.unwrap_or(Path::new(".")))?;
+    let mut out = BufWriter::new(file);
+    out.write_all(MAGIC)?;
+    (rows.len() as u64).write_le(&mut out)?;
+    (COLUMNS.len() as u64).write_le(&mut out)?;
+    // the directory is filled in once the columns' sizes are known
+    out.write_all(&[0; COLUMNS.len() * DIRECTORY_ENTRY_LEN])?;
+
+    let mut lens = Vec::with_capacity(COLUMNS.len());
+    let mut column = |out: &mut BufWriter<_>, len: usize| {
+        lens.push(len);
+        out.write_all(&[0; 8][..padding(len)])
+    };
+    let len = write_packed(&mut out, &rows, true, |item| item.station_id.to_i64())?;
+    column(&mut out, len)?;
+    let len = write_packed(&mut out, &rows, false, |item| item.item_id.to_i64())?;
+    column(&mut out, len)?;
+    let len = write_plain(&mut out, &rows, |item| item.demand_price)?;
+    column(&mut out, len)?;
+    let len = write_varints(&mut out, &rows, |item| item.demand_units)?;
+    column(&mut out, len)?;
+    let len = write_levels(&mut out, &rows, |item| {
+        (item.demand_level, item.demand_units)
+    })?;
+    column(&mut out, len)?;
+    let len = write_plain(&mut out, &rows, |item| item.supply_price)?;
+    column(&mut out, len)?;
+    let len = write_varints(&mut out, &rows, |item| item.supply_units)?;
+    column(&mut out, len)?;
+    let len = write_levels(&mut out, &rows, |item| {
+        (item.supply_level, item.supply_units)
+    })?;
+    column(&mut out, len)?;
+    let len = write_packed(&mut out, &rows, false, |item| item.modified)?;
+    column(&mut out, len)?;
+
+    out.seek(SeekFrom::Start(HEADER_LEN as u64))?;
+    let mut offset = HEADER_LEN + COLUMNS.len() * DIRECTORY_ENTRY_LEN;
+    for (len, (encoding, _)) in lens.into_iter().zip(LAYOUT) {
+        (offset as u64).write_le(&mut out)?;
+        (len as u64).write_le(&mut out)?;
+        encoding.write_le(&mut out)?;
+        offset += padded(len);
+    }
+
+    let file = out.into_inner().map_err(|e| e.into_error())?;
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+    info!(path = %path.display(), rows = rows.len(), "wrote column store");
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(station_id: u32, item_id: u32, demand_price: i32, supply_level: i32) -> StationItem {
+        StationItem {
+            station_id: StationId(station_id),
+            item_id: ItemId(item_id),
+            demand_price,
+            demand_units: 1 << 40,
+            supply_level,
+            modified: 1714564800 + item_id as i64,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_column_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("listings.cols");
+        let items = [
+            item(7, 30, 100, 3),
+            item(2, 10, 200, -1),
+            item(7, 10, 300, 0),
+            item(2, 10, 250, 2),
+            item(9, 5, 400, 1),
+        ];
+        assert_eq!(write_column_store(&path, &items).unwrap(), 4);
+        let store = ColumnStore::open(&path).unwrap();
+        assert_eq!(store.len(), 4);
+        assert_eq!(
+            store.station_id().to_vec(),
+            [2, 7, 7, 9].map(StationId).to_vec()
+        );
+        assert_eq!(store.item_id().get(2), ItemId(30));
+        // the last listing of station 2's item 10 wins
+        assert_eq!(store.row(0), items[3]);
+        assert_eq!(store.station_rows(StationId(7)), 1..3);
+        assert_eq!(
+            store.station_items(StationId(7)),
+            [items[2].clone(), items[0].clone()]
+        );
+        assert_eq!(store.station_rows(StationId(8)), 3..3);
+        assert_eq!(store.get(StationId(9), ItemId(5)), Some(items[4].clone()));
+        assert_eq!(store.get(StationId(7), ItemId(20)), None);
+        assert_eq!(store.get(StationId(1), ItemId(10)), None);
+        assert_eq!(
+            store
+                .iter()
+                .collect::<Vec<_>>()
+                .iter()
+                .collect::<StationItemColumns>(),
+            store.to_columns()
+        );
+    }
+
+    #[test]
+    fn test_column_store_packs_units_and_levels() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("listings.cols");
+        let units = |n: i64| [-1, 0, 1, 63, 64, 1 << 20, i64::MAX, -(1 << 40)][n as usize % 8];
+        let items: Vec<StationItem> = (0..300)
+            .map(|n| StationItem {
+                station_id: StationId(n / 10),
+                item_id: ItemId(n % 10),
+                demand_units: units(n.into()),
+                demand_level: [-1, 0, 1, 2, 3][n as usize % 5],
+                supply_units: n.into(),
+                supply_level: if n == 0 { 0 } else { 2 },
+                ..Default::default()
+            })
+            .collect();
+        write_column_store(&path, &items).unwrap();
+        let store = ColumnStore::open(&path).unwrap();
+        let demand_units = store.demand_units();
+        assert_eq!(
+            demand_units.to_vec(),
+            items.iter().map(|i| i.demand_units).collect::<Vec<_>>()
+        );
+        for (idx, item) in items.iter().enumerate() {
+            assert_eq!(demand_units.get(idx), item.demand_units);
+            assert_eq!(store.row(idx), *item);
+        }
+        assert_eq!(store.iter().collect::<Vec<_>>(), items);
+        assert_eq!(store.supply_level().get(0), 0);
+        assert_eq!(
+            store.demand_level().to_vec(),
+            store.to_columns().demand_level
+        );
+        // small units take a byte or two, where plain they'd take eight
+        assert!(std::fs::metadata(&path).unwrap().len() < 300 * 40);
+    }
+
+    #[test]
+    fn test_column_store_packs_ids_and_timestamps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("listings.cols");
+        let items: Vec<StationItem> = (0..1000u32)
+            .map(|n| StationItem {
+                station_id: StationId(n / 3 * 7),
+                item_id: ItemId(128049152 + n % 3 * 40),
+                modified: 1714564800 + (n as i64 * 7919) % 86400,
+                ..Default::default()
+            })
+            .chain([
+                StationItem {
+                    station_id: StationId(u32::MAX),
+                    modified: i64::MIN,
+                    ..Default::default()
+                },
+                StationItem {
+                    station_id: StationId(u32::MAX),
+                    item_id: ItemId(u32::MAX),
+                    modified: i64::MAX,
+                    ..Default::default()
+                },
+            ])
+            .collect();
+        write_column_store(&path, &items).unwrap();
+        let store = ColumnStore::open(&path).unwrap();
+        let (stations, modified) = (store.station_id(), store.modified());
+        for (idx, item) in items.iter().enumerate() {
+            assert_eq!(stations.get(idx), item.station_id);
+            assert_eq!(store.item_id().get(idx), item.item_id);
+            assert_eq!(modified.get(idx), item.modified);
+        }
+        assert_eq!(stations.to_vec(), stations.iter().collect::<Vec<_>>());
+        assert_eq!(store.iter().collect::<Vec<_>>(), items);
+        assert_eq!(
+            store.get(StationId(u32::MAX), ItemId(u32::MAX)),
+            items.last().cloned()
+        );
+        assert_eq!(store.station_rows(StationId(700)), 300..303);
+        // a station ID takes a few bits and an item ID and a timestamp a
+        // few bytes together, where plain they'd take 16 bytes
+        let size: usize = [0, 1, 8].map(|n| store.columns[n].len()).iter().sum();
+        assert!(size < 1002 * 6, "{}", size);
+    }
+
+    #[test]
+    fn test_column_store_rejects_bad_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("listings.cols");
+        write_column_store(&path, &[]).unwrap();
+        assert!(ColumnStore::open(&path).unwrap().is_empty());
+        let odd_level = StationItem {
+            demand_level: 7,
+            ..item(1, 1, 1, 1)
+        };
+        write_column_store(&path, [&odd_level]).unwrap();
+        assert_eq!(ColumnStore::open(&path).unwrap().row(0), odd_level);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 8);
+        std::fs::write(&path, &bytes).unwrap();
+        let error = ColumnStore::open(&path).unwrap_err();
+        assert!(
+            error.to_string().contains("modified is truncated"),
+            "{}",
+            error
+        );
+        std::fs::write(&path, b"TRLIDX01").unwrap();
+        assert!(ColumnStore::open(&path).is_err());
+        assert!(ColumnStore::open(dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,690 @@
+//! The built-in commodity table, so that every data source resolves an item
+//! to the same id.
+//!
+//! Each commodity has its FDev symbol, the lowercase internal name the
+//! journal and Market.json use (`lowtemperaturediamond`, also written
+//! `$lowtemperaturediamond_name;`), and its TradeDangerous display name
+//! ("Low Temperature Diamonds"). EDDN and the companion API use the symbol
+//! in CamelCase (`LowTemperatureDiamond`), which resolves the same way since
+//! lookups ignore case, spacing and punctuation.
+//!
+//! Every commodity also belongs to one of the market's categories, so
+//! constraints like "no Weapons" can be checked without the game's data.
+//!
+//! Ids are FDev's numeric commodity ids, the `id` of Market.json's items,
+//! which TradeDangerous also uses as its item_id.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::ids::ItemId;
+use crate::names::canonical_name;
+
+use Category::*;
+
+/// The market's commodity categories. Limpets aren't traded between
+/// stations and are NonMarketable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Category {
+    Chemicals,
+    ConsumerItems,
+    Foods,
+    IndustrialMaterials,
+    LegalDrugs,
+    Machinery,
+    Medicines,
+    Metals,
+    Minerals,
+    NonMarketable,
+    Salvage,
+    Slavery,
+    Technology,
+    Textiles,
+    Waste,
+    Weapons,
+}
+
+impl Category {
+    pub const ALL: [Category; 16] = [
+        Chemicals,
+        ConsumerItems,
+        Foods,
+        IndustrialMaterials,
+        LegalDrugs,
+        Machinery,
+        Medicines,
+        Metals,
+        Minerals,
+        NonMarketable,
+        Salvage,
+        Slavery,
+        Technology,
+        Textiles,
+        Waste,
+        Weapons,
+    ];
+
+    /// The TradeDangerous category name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Chemicals => "Chemicals",
+            ConsumerItems => "Consumer Items",
+            Foods => "Foods",
+            IndustrialMaterials => "Industrial Materials",
+            LegalDrugs => "Legal Drugs",
+            Machinery => "Machinery",
+            Medicines => "Medicines",
+            Metals => "Metals",
+            Minerals => "Minerals",
+            NonMarketable => "NonMarketable",
+            Salvage => "Salvage",
+            Slavery => "Slavery",
+            Technology => "Technology",
+            Textiles => "Textiles",
+            Waste => "Waste",
+            Weapons => "Weapons",
+        }
+    }
+
+    /// Looks a category up by name, ignoring case, spacing and punctuation,
+    /// so "Legal Drugs" and "legaldrugs" both work.
+    pub fn from_name(name: &str) -> Option<Category> {
+        let key = lookup_key(name);
+        Self::ALL
+            .into_iter()
+            .find(|category| lookup_key(category.name()) == key)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Commodity {
+    pub id: ItemId,
+    pub symbol: &'static str,
+    pub name: &'static str,
+    pub category: Category,
+}
+
+/// (FDev id, symbol, display name, category), by category.
+const COMMODITIES: &[(u32, &str, &str, Category)] = &[
+    (128049204, "explosives", "Explosives", Chemicals),
+    (128049202, "hydrogenfuel", "Hydrogen Fuel", Chemicals),
+    (
+        128673850,
+        "hydrogenperoxide",
+        "Hydrogen Peroxide",
+        Chemicals,
+    ),
+    (128673851, "liquidoxygen", "Liquid Oxygen", Chemicals),
+    (128049203, "mineraloil", "Mineral Oil", Chemicals),
+    (128672304, "nerveagents", "Nerve Agents", Chemicals),
+    (128049205, "pesticides", "Pesticides", Chemicals),
+    (
+        128672305,
+        "surfacestabilisers",
+        "Surface Stabilisers",
+        Chemicals,
+    ),
+    (
+        128672303,
+        "syntheticreagents",
+        "Synthetic Reagents",
+        Chemicals,
+    ),
+    (128961249, "tritium", "Tritium", Chemicals),
+    (128049166, "water", "Water", Chemicals),
+    (128049241, "clothing", "Clothing", ConsumerItems),
+    (
+        128049240,
+        "consumertechnology",
+        "Consumer Technology",
+        ConsumerItems,
+    ),
+    (
+        128049238,
+        "domesticappliances",
+        "Domestic Appliances",
+        ConsumerItems,
+    ),
+    (
+        128672314,
+        "evacuationshelter",
+        "Evacuation Shelter",
+        ConsumerItems,
+    ),
+    (
+        128682046,
+        "survivalequipment",
+        "Survival Equipment",
+        ConsumerItems,
+    ),
+    (128049177, "algae", "Algae", Foods),
+    (128049182, "animalmeat", "Animal Meat", Foods),
+    (128049189, "coffee", "Coffee", Foods),
+    (128049183, "fish", "Fish", Foods),
+    (128049184, "foodcartridges", "Food Cartridges", Foods),
+    (
+        128049178,
+        "fruitandvegetables",
+        "Fruit and Vegetables",
+        Foods,
+    ),
+    (128049180, "grain", "Grain", Foods),
+    (128049185, "syntheticmeat", "Synthetic Meat", Foods),
+    (128049188, "tea", "Tea", Foods),
+    (
+        128672302,
+        "ceramiccomposites",
+        "Ceramic Composites",
+        IndustrialMaterials,
+    ),
+    (
+        128673856,
+        "cmmcomposite",
+        "CMM Composite",
+        IndustrialMaterials,
+    ),
+    (
+        128673857,
+        "coolinghoses",
+        "Micro-Weave Cooling Hoses",
+        IndustrialMaterials,
+    ),
+    (
+        128673855,
+        "insulatingmembrane",
+        "Insulating Membrane",
+        IndustrialMaterials,
+    ),
+    (128672701, "metaalloys", "Meta-Alloys", IndustrialMaterials),
+    (
+        128673858,
+        "neofabricinsulation",
+        "Neofabric Insulation",
+        IndustrialMaterials,
+    ),
+    (128049197, "polymers", "Polymers", IndustrialMaterials),
+    (
+        128049199,
+        "semiconductors",
+        "Semiconductors",
+        IndustrialMaterials,
+    ),
+    (
+        128049200,
+        "superconductors",
+        "Superconductors",
+        IndustrialMaterials,
+    ),
+    (128049214, "beer", "Beer", LegalDrugs),
+    (128667019, "bootlegliquor", "Bootleg Liquor", LegalDrugs),
+    (128049216, "liquor", "Liquor", LegalDrugs),
+    (128049213, "tobacco", "Tobacco", LegalDrugs),
+    (128049215, "wine", "Wine", LegalDrugs),
+    (
+        128064028,
+        "atmosphericextractors",
+        "Atmospheric Processors",
+        Machinery,
+    ),
+    (
+        128672309,
+        "buildingfabricators",
+        "Building Fabricators",
+        Machinery,
+    ),
+    (128049217, "cropharvesters", "Crop Harvesters", Machinery),
+    (
+        128673861,
+        "emergencypowercells",
+        "Emergency Power Cells",
+        Machinery,
+    ),
+    (128673866, "exhaustmanifold", "Exhaust Manifold", Machinery),
+    (
+        128672307,
+        "geologicalequipment",
+        "Geological Equipment",
+        Machinery,
+    ),
+    (
+        128673868,
+        "heatsinkinterlink",
+        "Heatsink Interlink",
+        Machinery,
+    ),
+    (128673860, "hnshockmount", "HN Shock Mount", Machinery),
+    (128673874, "iondistributor", "Ion Distributor", Machinery),
+    (
+        128673869,
+        "magneticemittercoil",
+        "Magnetic Emitter Coil",
+        Machinery,
+    ),
+    (128049218, "marinesupplies", "Marine Equipment", Machinery),
+    (
+        128673873,
+        "microcontrollers",
+        "Micro Controllers",
+        Machinery,
+    ),
+    (
+        128049221,
+        "mineralextractors",
+        "Mineral Extractors",
+        Machinery,
+    ),
+    (
+        128673870,
+        "modularterminals",
+        "Modular Terminals",
+        Machinery,
+    ),
+    (128673862, "powerconverter", "Power Converter", Machinery),
+    (128049222, "powergenerators", "Power Generators", Machinery),
+    (
+        128673863,
+        "powergridassembly",
+        "Energy Grid Assembly",
+        Machinery,
+    ),
+    (
+        128673864,
+        "powertransferconduits",
+        "Power Transfer Bus",
+        Machinery,
+    ),
+    (128673865, "radiationbaffle", "Radiation Baffle", Machinery),
+    (
+        128673867,
+        "reinforcedmountingplate",
+        "Reinforced Mounting Plate",
+        Machinery,
+    ),
+    (
+        128672313,
+        "skimercomponents",
+        "Skimmer Components",
+        Machinery,
+    ),
+    (
+        128672308,
+        "thermalcoolingunits",
+        "Thermal Cooling Units",
+        Machinery,
+    ),
+    (128049223, "waterpurifiers", "Water Purifiers", Machinery),
+    (
+        128672306,
+        "advancedmedicines",
+        "Advanced Medicines",
+        Medicines,
+    ),
+    (
+        128049208,
+        "agriculturalmedicines",
+        "Agri-Medicines",
+        Medicines,
+    ),
+    (128049210, "basicmedicines", "Basic Medicines", Medicines),
+    (
+        128049670,
+        "combatstabilisers",
+        "Combat Stabilisers",
+        Medicines,
+    ),
+    (
+        128049209,
+        "performanceenhancers",
+        "Performance Enhancers",
+        Medicines,
+    ),
+    (128049669, "progenitorcells", "Progenitor Cells", Medicines),
+    (128049176, "aluminium", "Aluminium", Metals),
+    (128049168, "beryllium", "Beryllium", Metals),
+    (128672123, "bismuth", "Bismuth", Metals),
+    (128049162, "cobalt", "Cobalt", Metals),
+    (128049175, "copper", "Copper", Metals),
+    (128049170, "gallium", "Gallium", Metals),
+    (128049154, "gold", "Gold", Metals),
+    (128668549, "hafnium178", "Hafnium 178", Metals),
+    (128049169, "indium", "Indium", Metals),
+    (128672121, "lanthanum", "Lanthanum", Metals),
+    (128049173, "lithium", "Lithium", Metals),
+    (128671118, "osmium", "Osmium", Metals),
+    (128049153, "palladium", "Palladium", Metals),
+    (128049152, "platinum", "Platinum", Metals),
+    (128672124, "praseodymium", "Praseodymium", Metals),
+    (128672125, "samarium", "Samarium", Metals),
+    (128049155, "silver", "Silver", Metals),
+    (128049171, "tantalum", "Tantalum", Metals),
+    (128672122, "thallium", "Thallium", Metals),
+    (128672126, "thorium", "Thorium", Metals),
+    (128049174, "titanium", "Titanium", Metals),
+    (128049172, "uranium", "Uranium", Metals),
+    (128924331, "alexandrite", "Alexandrite", Minerals),
+    (128049165, "bauxite", "Bauxite", Minerals),
+    (128924329, "benitoite", "Benitoite", Minerals),
+    (128049156, "bertrandite", "Bertrandite", Minerals),
+    (128672300, "bromellite", "Bromellite", Minerals),
+    (128049159, "coltan", "Coltan", Minerals),
+    (128672294, "cryolite", "Cryolite", Minerals),
+    (128049158, "gallite", "Gallite", Minerals),
+    (128672295, "goslarite", "Goslarite", Minerals),
+    (128924330, "grandidierite", "Grandidierite", Minerals),
+    (128049157, "indite", "Indite", Minerals),
+    (128672299, "jadeite", "Jadeite", Minerals),
+    (128049161, "lepidolite", "Lepidolite", Minerals),
+    (128673853, "lithiumhydroxide", "Lithium Hydroxide", Minerals),
+    (
+        128673848,
+        "lowtemperaturediamond",
+        "Low Temperature Diamonds",
+        Minerals,
+    ),
+    (128673854, "methaneclathrate", "Methane Clathrate", Minerals),
+    (
+        128673852,
+        "methanolmonohydratecrystals",
+        "Methanol Monohydrate Crystals",
+        Minerals,
+    ),
+    (128672296, "moissanite", "Moissanite", Minerals),
+    (128924327, "monazite", "Monazite", Minerals),
+    (128924328, "musgravite", "Musgravite", Minerals),
+    (128924332, "opal", "Void Opal", Minerals),
+    (128668550, "painite", "Painite", Minerals),
+    (128672297, "pyrophyllite", "Pyrophyllite", Minerals),
+    (128924325, "rhodplumsite", "Rhodplumsite", Minerals),
+    (128049163, "rutile", "Rutile", Minerals),
+    (128924326, "serendibite", "Serendibite", Minerals),
+    (128672298, "taaffeite", "Taaffeite", Minerals),
+    (128049160, "uraninite", "Uraninite", Minerals),
+    (128666756, "ancientartefact", "Ancient Artefact", Salvage),
+    (128666752, "usscargoblackbox", "Black Box", Salvage),
+    (128672127, "comercialsamples", "Commercial Samples", Salvage),
+    (128672159, "diplomaticbag", "Diplomatic Bag", Salvage),
+    (
+        128666753,
+        "encriptedcorrespondence",
+        "Encrypted Correspondence",
+        Salvage,
+    ),
+    (
+        128672128,
+        "encripteddatastorage",
+        "Encrypted Data Storage",
+        Salvage,
+    ),
+    (
+        128666758,
+        "usscargoexperimentalchemicals",
+        "Experimental Chemicals",
+        Salvage,
+    ),
+    (
+        128666755,
+        "usscargomilitaryplans",
+        "Military Plans",
+        Salvage,
+    ),
+    (
+        128666760,
+        "usscargoprototypetech",
+        "Prototype Tech",
+        Salvage,
+    ),
+    (128666757, "usscargorareartwork", "Rare Artwork", Salvage),
+    (
+        128666759,
+        "usscargorebeltransmissions",
+        "Rebel Transmissions",
+        Salvage,
+    ),
+    (
+        128672160,
+        "wreckagecomponents",
+        "Salvageable Wreckage",
+        Salvage,
+    ),
+    (
+        128666761,
+        "usscargotechnicalblueprints",
+        "Technical Blueprints",
+        Salvage,
+    ),
+    (128668547, "unknownartifact", "Thargoid Sensor", Salvage),
+    (128737287, "unknownartifact2", "Thargoid Probe", Salvage),
+    (128793113, "unknownartifact3", "Thargoid Link", Salvage),
+    (128666754, "usscargotradedata", "Trade Data", Salvage),
+    (
+        128671444,
+        "trinketsoffortune",
+        "Trinkets Of Hidden Fortune",
+        Salvage,
+    ),
+    (128667728, "imperialslaves", "Imperial Slaves", Slavery),
+    (128049243, "slaves", "Slaves", Slavery),
+    (
+        128049231,
+        "advancedcatalysers",
+        "Advanced Catalysers",
+        Technology,
+    ),
+    (128049229, "animalmonitors", "Animal Monitors", Technology),
+    (
+        128049230,
+        "aquaponicsystems",
+        "Aquaponic Systems",
+        Technology,
+    ),
+    (128049228, "autofabricators", "Auto-Fabricators", Technology),
+    (
+        128049672,
+        "bioreducinglichen",
+        "Bioreducing Lichen",
+        Technology,
+    ),
+    (
+        128049225,
+        "computercomponents",
+        "Computer Components",
+        Technology,
+    ),
+    (
+        128049226,
+        "hazardousenvironmentsuits",
+        "H.E. Suits",
+        Technology,
+    ),
+    (
+        128049232,
+        "terrainenrichmentsystems",
+        "Land Enrichment Systems",
+        Technology,
+    ),
+    (
+        128682044,
+        "medicaldiagnosticequipment",
+        "Medical Diagnostic Equipment",
+        Technology,
+    ),
+    (
+        128049220,
+        "heliostaticfurnaces",
+        "Microbial Furnaces",
+        Technology,
+    ),
+    (128672310, "mutomimager", "Muon Imager", Technology),
+    (
+        128049671,
+        "resonatingseparators",
+        "Resonating Separators",
+        Technology,
+    ),
+    (128049227, "robotics", "Robotics", Technology),
+    (
+        128672311,
+        "structuralregulators",
+        "Structural Regulators",
+        Technology,
+    ),
+    (128049190, "leather", "Leather", Textiles),
+    (128049191, "naturalfabrics", "Natural Fabrics", Textiles),
+    (128049193, "syntheticfabrics", "Synthetic Fabrics", Textiles),
+    (128049244, "biowaste", "Biowaste", Waste),
+    (128049246, "chemicalwaste", "Chemical Waste", Waste),
+    (128049248, "scrap", "Scrap", Waste),
+    (128049245, "toxicwaste", "Toxic Waste", Waste),
+    (128049234, "battleweapons", "Battle Weapons", Weapons),
+    (128672312, "landmines", "Landmines", Weapons),
+    (128049236, "nonlethalweapons", "Non-Lethal Weapons", Weapons),
+    (128049233, "personalweapons", "Personal Weapons", Weapons),
+    (128049235, "reactivearmour", "Reactive Armour", Weapons),
+    (128066403, "drones", "Limpet", NonMarketable),
+    (128049212, "basicnarcotics", "Narcotics", LegalDrugs),
+];
+
+/// What a name is looked up by: its canonical form with only the letters
+/// and digits kept, after unwrapping the journal's "$symbol_name;".
+fn lookup_key(name: &str) -> String {
+    let name = name.trim();
+    let name = name
+        .strip_prefix('$')
+        .and_then(|symbol| symbol.strip_suffix("_name;"))
+        .unwrap_or(name);
+    canonical_name(name)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+fn commodity(idx: usize) -> Commodity {
+    let (id, symbol, name, category) = COMMODITIES[idx];
+    Commodity {
+        id: ItemId(id),
+        symbol,
+        name,
+        category,
+    }
+}
+
+fn index() -> &'static HashMap<String, usize> {
+    static INDEX: OnceLock<HashMap<String, usize>> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        let mut index = HashMap::with_capacity(COMMODITIES.len() * 2);
+        for (idx, (_, symbol, name, _)) in COMMODITIES.iter().enumerate() {
+            index.insert(lookup_key(symbol), idx);
+            index.insert(lookup_key(name), idx);
+        }
+        index
+    })
+}
+
+/// Resolves a commodity from its FDev symbol, journal name, EDDN name or
+/// display name.
+pub fn canonical_commodity(name: &str) -> Option<Commodity> {
+    index().get(&lookup_key(name)).map(|&idx| commodity(idx))
+}
+
+fn id_index() -> &'static HashMap<ItemId, usize> {
+    static INDEX: OnceLock<HashMap<ItemId, usize>> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        COMMODITIES
+            .iter()
+            .enumerate()
+            .map(|(idx, (id, ..))| (ItemId(*id), idx))
+            .collect()
+    })
+}
+
+pub fn commodity_by_id(id: ItemId) -> Option<Commodity> {
+    id_index().get(&id).map(|&idx| commodity(idx))
+}
+
+/// Every commodity, by category.
+pub fn commodities() -> impl Iterator<Item = Commodity> {
+    (0..COMMODITIES.len()).map(commodity)
+}
+
+/// The category of an item id, or None if it isn't in the table.
+pub fn item_category(item_id: ItemId) -> Option<Category> {
+    commodity_by_id(item_id).map(|commodity| commodity.category)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_commodity() {
+        let ltd = canonical_commodity("lowtemperaturediamond").unwrap();
+        assert_eq!(ltd.name, "Low Temperature Diamonds");
+        for name in [
+            "$lowtemperaturediamond_name;",
+            "LowTemperatureDiamond",
+            "Low Temperature Diamonds",
+            "low  temperature diamonds",
+        ] {
+            assert_eq!(canonical_commodity(name), Some(ltd), "{}", name);
+        }
+        assert_eq!(
+            canonical_commodity("Agri-Medicines").unwrap().symbol,
+            "agriculturalmedicines"
+        );
+        assert_eq!(
+            canonical_commodity("agri medicines").unwrap().symbol,
+            "agriculturalmedicines"
+        );
+        assert_eq!(
+            canonical_commodity("HE Suits").unwrap().symbol,
+            "hazardousenvironmentsuits"
+        );
+        assert_eq!(canonical_commodity("Drones").unwrap().name, "Limpet");
+        assert_eq!(canonical_commodity("Unobtainium"), None);
+        assert_eq!(canonical_commodity(""), None);
+    }
+
+    #[test]
+    fn test_commodity_ids() {
+        assert_eq!(
+            commodity_by_id(ItemId(128049204)).unwrap().symbol,
+            "explosives"
+        );
+        assert_eq!(canonical_commodity("Gold").unwrap().id, ItemId(128049154));
+        assert_eq!(commodity_by_id(ItemId(0)), None);
+        assert_eq!(commodity_by_id(ItemId(1)), None);
+        assert_eq!(id_index().len(), COMMODITIES.len(), "ids repeat");
+        for commodity in commodities() {
+            assert_eq!(commodity_by_id(commodity.id), Some(commodity));
+        }
+    }
+
+    #[test]
+    fn test_categories() {
+        let category = |name| canonical_commodity(name).unwrap().category;
+        assert_eq!(category("Gold"), Metals);
+        assert_eq!(category("Painite"), Minerals);
+        assert_eq!(category("Narcotics"), LegalDrugs);
+        assert_eq!(category("Thargoid Sensor"), Salvage);
+        assert_eq!(category("Limpet"), NonMarketable);
+        assert_eq!(item_category(ItemId(0)), None);
+
+        assert_eq!(Category::from_name("legal drugs"), Some(LegalDrugs));
+        assert_eq!(Category::from_name("ConsumerItems"), Some(ConsumerItems));
+        assert_eq!(Category::from_name("Narcotics"), None);
+        for category in Category::ALL {
+            assert_eq!(Category::from_name(category.name()), Some(category));
+            assert!(commodities().any(|c| c.category == category));
+        }
+    }
+
+    #[test]
+    fn test_lookup_keys_unique() {
+        // a symbol or name resolving to a different commodity than its own
+        // would mean two entries collide
+        for commodity in commodities() {
+            assert_eq!(canonical_commodity(commodity.symbol), Some(commodity));
+            assert_eq!(canonical_commodity(commodity.name), Some(commodity));
+        }
+    }
+}
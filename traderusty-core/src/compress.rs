@@ -0,0 +1,242 @@
+//! zstd compression for the files this crate writes for itself, with an
+//! optional dictionary trained on samples of them. Built with the "zstd"
+//! feature.
+//!
+//! Market records are small and much alike: the same item ids, columns and
+//! price ranges station after station. zstd on its own has little to go on
+//! in a record that size; a dictionary trained on a few hundred of them
+//! gives it the repeated parts up front, and snapshots shrink to a fraction
+//! of what they'd otherwise be. The same dictionary has to be given to read
+//! them back.
+//!
+//! Everything is written as one zstd stream, so readers decode as they go
+//! rather than inflating a whole file first.
+//!
+//! A snapshot cache is a sequence of snapshot records, each a header line
+//! and a line per listing:
+//!
+//! ```text
+//! @128016640,1714564800
+//! 42,8900,0,0,9100,120,2,1714564800
+//! ```
+//!
+//! (item id, demand price, units and level, supply price, units and level,
+//! modified.)
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::ids::{ItemId, StationId};
+use crate::market::{MarketSnapshot, StationItem};
+
+/// zstd's default compression level.
+pub const DEFAULT_LEVEL: i32 = 3;
+
+/// Default largest dictionary `train_dictionary` builds: zstd's own default.
+pub const DEFAULT_DICTIONARY_SIZE: usize = 112_640;
+
+/// Trains a dictionary of at most `max_size` bytes on sample records. zstd
+/// needs a fair number of samples, a few hundred or more, to train on.
+pub fn train_dictionary<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, max_size)
+}
+
+/// Compresses `data`, with a dictionary unless `dictionary` is empty.
+pub fn compress(data: &[u8], dictionary: &[u8], level: i32) -> io::Result<Vec<u8>> {
+    let mut out = writer(Vec::new(), dictionary, level)?;
+    out.write_all(data)?;
+    out.finish()
+}
+
+/// Decompresses what `compress` wrote with the same dictionary.
+pub fn decompress(data: &[u8], dictionary: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    reader(data, dictionary)?.read_to_end(&mut out)?;
+    Ok(out)
+}
+
+/// A writer compressing into `inner`. Call `finish` on it to end the
+/// stream.
+pub fn writer<W: Write>(
+    inner: W,
+    dictionary: &[u8],
+    level: i32,
+) -> io::Result<zstd::Encoder<'static, W>> {
+    zstd::Encoder::with_dictionary(inner, level, dictionary)
+}
+
+/// A reader decompressing from `inner` as it's read.
+pub fn reader<R: BufRead>(inner: R, dictionary: &[u8]) -> io::Result<zstd::Decoder<'static, R>> {
+    zstd::Decoder::with_dictionary(inner, dictionary)
+}
+
+/// A snapshot as a record of the cache format, uncompressed. Also what to
+/// train a dictionary on.
+pub fn snapshot_record(snapshot: &MarketSnapshot) -> Vec<u8> {
+    let mut record = format!("@{},{}\n", snapshot.station_id, snapshot.timestamp);
+    for item in snapshot.items() {
+        record.push_str(&format!(
+            "{},{},{},{},{},{},{},{}\n",
+            item.item_id,
+            item.demand_price,
+            item.demand_units,
+            item.demand_level,
+            item.supply_price,
+            item.supply_units,
+            item.supply_level,
+            item.modified,
+        ));
+    }
+    record.into_bytes()
+}
+
+/// Writes snapshots to `out` as a compressed snapshot cache.
+pub fn write_snapshots<'a, W: Write>(
+    out: W,
+    snapshots: impl IntoIterator<Item = &'a MarketSnapshot>,
+    dictionary: &[u8],
+    level: i32,
+) -> io::Result<W> {
+    let mut out = writer(out, dictionary, level)?;
+    for snapshot in snapshots {
+        out.write_all(&snapshot_record(snapshot))?;
+    }
+    out.finish()
+}
+
+fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("snapshot cache line {}: {}", line, message),
+    )
+}
+
+fn fields<const N: usize>(text: &str, line: usize) -> io::Result<[i64; N]> {
+    let mut fields = [0; N];
+    let mut parts = text.split(',');
+    for field in fields.iter_mut() {
+        *field = parts
+            .next()
+            .and_then(|part| part.parse().ok())
+            .ok_or_else(|| invalid(line, "bad field"))?;
+    }
+    if parts.next().is_some() {
+        return Err(invalid(line, "too many fields"));
+    }
+    Ok(fields)
+}
+
+/// Reads back the snapshots of a compressed snapshot cache, decoding it as
+/// it goes.
+pub fn read_snapshots<R: BufRead>(input: R, dictionary: &[u8]) -> io::Result<Vec<MarketSnapshot>> {
+    let mut snapshots = Vec::new();
+    let mut current: Option<(StationId, i64, Vec<StationItem>)> = None;
+    for (number, line) in BufReader::new(reader(input, dictionary)?)
+        .lines()
+        .enumerate()
+    {
+        let (number, line) = (number + 1, line?);
+        if let Some(header) = line.strip_prefix('@') {
+            let [station_id, timestamp] = fields(header, number)?;
+            if let Some((station_id, timestamp, items)) = current.take() {
+                snapshots.push(MarketSnapshot::new(station_id, timestamp, items));
+            }
+            current = Some((StationId(station_id as u32), timestamp, Vec::new()));
+            continue;
+        }
+        let Some((station_id, _, items)) = current.as_mut() else {
+            return Err(invalid(number, "listing before any snapshot"));
+        };
+        let [item_id, demand_price, demand_units, demand_level, supply_price, supply_units, supply_level, modified] =
+            fields(&line, number)?;
+        items.push(StationItem {
+            station_id: *station_id,
+            item_id: ItemId(item_id as u32),
+            demand_price: demand_price as i32,
+            demand_units,
+            demand_level: demand_level as i32,
+            supply_price: supply_price as i32,
+            supply_units,
+            supply_level: supply_level as i32,
+            modified,
+        });
+    }
+    if let Some((station_id, timestamp, items)) = current {
+        snapshots.push(MarketSnapshot::new(station_id, timestamp, items));
+    }
+    Ok(snapshots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Markets much like each other, as real ones are.
+    fn snapshots(count: u32) -> Vec<MarketSnapshot> {
+        (0..count)
+            .map(|station| {
+                let items = (1..=40)
+                    .map(|item| StationItem {
+                        item_id: ItemId(item * 3),
+                        demand_price: (item * 137 + station % 9) as i32,
+                        demand_units: (item * 1000) as i64,
+                        demand_level: (item % 4) as i32,
+                        supply_price: (item * 131 + station % 7) as i32,
+                        supply_units: (station % 3 * item * 100) as i64,
+                        supply_level: ((item + 1) % 4) as i32,
+                        modified: 1714564800 + station as i64 * 60,
+                        ..Default::default()
+                    })
+                    .collect();
+                MarketSnapshot::new(
+                    StationId(128000000 + station),
+                    1714564800 + station as i64 * 60,
+                    items,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = b"station_id,item_id\n1,2\n".repeat(20);
+        for dictionary in [&b""[..], &b"station_id,item_id\n"[..]] {
+            let compressed = compress(&data, dictionary, DEFAULT_LEVEL).unwrap();
+            assert!(compressed.len() < data.len());
+            assert_eq!(decompress(&compressed, dictionary).unwrap(), data);
+        }
+        assert!(decompress(b"not zstd", b"").is_err());
+    }
+
+    #[test]
+    fn test_snapshot_cache() {
+        let snapshots = snapshots(3);
+        let cache = write_snapshots(Vec::new(), &snapshots, b"", DEFAULT_LEVEL).unwrap();
+        assert_eq!(read_snapshots(&cache[..], b"").unwrap(), snapshots);
+        let empty = write_snapshots(Vec::new(), &[], b"", DEFAULT_LEVEL).unwrap();
+        assert!(read_snapshots(&empty[..], b"").unwrap().is_empty());
+
+        let bad = compress(b"1,2,3\n", b"", DEFAULT_LEVEL).unwrap();
+        let err = read_snapshots(&bad[..], b"").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err
+            .to_string()
+            .contains("line 1: listing before any snapshot"));
+        let bad = compress(b"@1,2\n1,2,x,4,5,6,7,8\n", b"", DEFAULT_LEVEL).unwrap();
+        let err = read_snapshots(&bad[..], b"").unwrap_err();
+        assert!(err.to_string().contains("line 2: bad field"));
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let samples: Vec<Vec<u8>> = snapshots(500).iter().map(snapshot_record).collect();
+        let dictionary = train_dictionary(&samples, 16 * 1024).unwrap();
+        assert!(!dictionary.is_empty() && dictionary.len() <= 16 * 1024);
+
+        let record = snapshot_record(&snapshots(501)[500]);
+        let plain = compress(&record, b"", DEFAULT_LEVEL).unwrap();
+        let trained = compress(&record, &dictionary, DEFAULT_LEVEL).unwrap();
+        assert!(trained.len() < plain.len() / 2);
+        assert_eq!(decompress(&trained, &dictionary).unwrap(), record);
+        assert!(decompress(&trained, b"").is_err());
+    }
+}
@@ -0,0 +1,741 @@
+//! Reading and writing TradeDangerous' SQLite database.
+//!
+//! Connections are opened in WAL mode with synchronous=NORMAL: a crash can
+//! lose the last transactions but never corrupt the file, and readers (TD
+//! itself) aren't blocked while an import writes.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::time::Duration;
+
+use rusqlite::{params_from_iter, types::Value, Connection, Transaction};
+use tracing::{info, warn};
+
+use crate::commodities::{canonical_commodity, commodity_by_id};
+use crate::ids::{ItemId, StationId};
+use crate::market::{StationItem, StationItemColumns};
+use crate::store::MarketStore;
+use crate::system::System;
+
+/// Rows buffered before a Batcher writes them out.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// SQLite's historical limit on bound parameters per statement, which
+/// caps how many rows a single multi-row INSERT can carry.
+const MAX_PARAMS_PER_STATEMENT: usize = 999;
+
+#[derive(Debug)]
+pub enum DbError {
+    Sqlite(rusqlite::Error),
+    /// Writing one batch of rows failed; rows are counted from 0 in the
+    /// order they were pushed.
+    Batch {
+        batch: usize,
+        first_row: usize,
+        rows: usize,
+        source: rusqlite::Error,
+    },
+    /// The database's schema is newer than this version of the crate knows.
+    UnknownVersion {
+        found: u32,
+        latest: u32,
+    },
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::Sqlite(e) => write!(f, "{}", e),
+            DbError::Batch {
+                batch,
+                first_row,
+                rows,
+                source,
+            } => write!(
+                f,
+                "batch {} (rows {}..{}) failed: {}",
+                batch,
+                first_row,
+                first_row + rows,
+                source
+            ),
+            DbError::UnknownVersion { found, latest } => write!(
+                f,
+                "database schema version {} is newer than the latest known ({})",
+                found, latest
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        DbError::Sqlite(e)
+    }
+}
+
+/// How long a connection waits for another writer before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Opens (or creates) a database for importing into.
+#[tracing::instrument(skip_all, fields(path = %path.as_ref().display()))]
+pub fn open_database(path: impl AsRef<Path>) -> Result<Connection, DbError> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    let mode: String =
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    if !mode.eq_ignore_ascii_case("wal") {
+        // in-memory databases can't use WAL; that's fine for them.
+        warn!(mode, "database is not in WAL mode");
+    }
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    Ok(conn)
+}
+
+/// Runs `body` in a transaction, committing if it succeeds and rolling
+/// back if it returns an error (or panics).
+pub fn in_transaction<T, F>(conn: &mut Connection, body: F) -> Result<T, DbError>
+where
+    F: FnOnce(&Transaction) -> Result<T, DbError>,
+{
+    let tx = conn.transaction()?;
+    match body(&tx) {
+        Ok(value) => {
+            tx.commit()?;
+            Ok(value)
+        }
+        Err(e) => {
+            warn!("rolling back: {}", e);
+            tx.rollback()?;
+            Err(e)
+        }
+    }
+}
+
+/// A record that can be written to a table by a Batcher.
+pub trait Row {
+    const TABLE: &'static str;
+    const COLUMNS: &'static [&'static str];
+    /// How a column's value is written if it isn't a plain "?", e.g. to
+    /// convert it with an SQL function.
+    const PLACEHOLDERS: &'static [(&'static str, &'static str)] = &[];
+    /// What an insert does when the row's key is already there: REPLACE
+    /// overwrites the old row, IGNORE keeps it.
+    const ON_CONFLICT: &'static str = "REPLACE";
+
+    /// Appends the record's values in COLUMNS order.
+    fn bind(&self, params: &mut Vec<Value>);
+}
+
+impl Row for StationItem {
+    const TABLE: &'static str = "StationItem";
+    const COLUMNS: &'static [&'static str] = &[
+        "station_id",
+        "item_id",
+        "demand_price",
+        "demand_units",
+        "demand_level",
+        "supply_price",
+        "supply_units",
+        "supply_level",
+        "modified",
+    ];
+    /// Modified is bound as unix seconds and stored in TD's
+    /// "YYYY-MM-DD HH:MM:SS" format.
+    const PLACEHOLDERS: &'static [(&'static str, &'static str)] =
+        &[("modified", "datetime(?, 'unixepoch')")];
+
+    fn bind(&self, params: &mut Vec<Value>) {
+        params.extend([
+            Value::Integer(self.station_id.0 as i64),
+            Value::Integer(self.item_id.0 as i64),
+            Value::Integer(self.demand_price as i64),
+            Value::Integer(self.demand_units),
+            Value::Integer(self.demand_level as i64),
+            Value::Integer(self.supply_price as i64),
+            Value::Integer(self.supply_units),
+            Value::Integer(self.supply_level as i64),
+            Value::Integer(self.modified),
+        ]);
+    }
+}
+
+/// Builds `INSERT OR <conflict> INTO <table> (...) VALUES (...), (...)` for
+/// a number of rows.
+fn insert_statement<R: Row>(rows: usize) -> String {
+    let placeholders: Vec<&str> = R::COLUMNS
+        .iter()
+        .map(|column| {
+            R::PLACEHOLDERS
+                .iter()
+                .find(|(name, _)| name == column)
+                .map_or("?", |(_, placeholder)| placeholder)
+        })
+        .collect();
+    let row = format!("({})", placeholders.join(", "));
+    format!(
+        "INSERT OR {} INTO {} ({}) VALUES {}",
+        R::ON_CONFLICT,
+        R::TABLE,
+        R::COLUMNS.join(", "),
+        vec![row.as_str(); rows].join(", ")
+    )
+}
+
+/// Buffers records and writes them with multi-row `INSERT OR REPLACE` (or
+/// IGNORE) statements every `batch_size` rows. Each batch is written under
+/// a savepoint, so a failed one leaves none of its rows behind; callers
+/// wanting all-or-nothing writes run it inside a transaction.
+pub struct Batcher<'c, R: Row> {
+    conn: &'c Connection,
+    batch_size: usize,
+    pending: Vec<Value>,
+    pending_rows: usize,
+    /// Rows pushed before the pending ones.
+    flushed_rows: usize,
+    batches: usize,
+    written: usize,
+    _row: PhantomData<R>,
+}
+
+impl<'c, R: Row> Batcher<'c, R> {
+    pub fn new(conn: &'c Connection, batch_size: usize) -> Self {
+        let batch_size = batch_size.max(1);
+        Self {
+            conn,
+            batch_size,
+            pending: Vec::with_capacity(batch_size * R::COLUMNS.len()),
+            pending_rows: 0,
+            flushed_rows: 0,
+            batches: 0,
+            written: 0,
+            _row: PhantomData,
+        }
+    }
+
+    /// Adds a record, writing the batch out if it's full.
+    pub fn push(&mut self, row: &R) -> Result<(), DbError> {
+        row.bind(&mut self.pending);
+        self.pending_rows += 1;
+        if self.pending_rows >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes out any buffered records. A failed batch is rolled back,
+    /// dropped and reported with the range of rows it held.
+    pub fn flush(&mut self) -> Result<(), DbError> {
+        if self.pending_rows == 0 {
+            return Ok(());
+        }
+        let (batch, first_row, rows) = (self.batches, self.flushed_rows, self.pending_rows);
+        let params = std::mem::take(&mut self.pending);
+        self.batches += 1;
+        self.flushed_rows += rows;
+        self.pending_rows = 0;
+        match self.write(&params, rows) {
+            Ok(changed) => {
+                self.written += changed;
+                Ok(())
+            }
+            Err(source) => {
+                warn!(
+                    table = R::TABLE,
+                    batch, first_row, rows, "batch failed: {}", source
+                );
+                Err(DbError::Batch {
+                    batch,
+                    first_row,
+                    rows,
+                    source,
+                })
+            }
+        }
+    }
+
+    /// Writes one batch under a savepoint, returning how many rows changed.
+    fn write(&self, params: &[Value], rows: usize) -> rusqlite::Result<usize> {
+        self.conn.execute_batch("SAVEPOINT batcher_flush")?;
+        match self.write_statements(params, rows) {
+            Ok(changed) => {
+                self.conn.execute_batch("RELEASE batcher_flush")?;
+                Ok(changed)
+            }
+            Err(e) => {
+                self.conn
+                    .execute_batch("ROLLBACK TO batcher_flush; RELEASE batcher_flush")?;
+                Err(e)
+            }
+        }
+    }
+
+    fn write_statements(&self, params: &[Value], rows: usize) -> rusqlite::Result<usize> {
+        let columns = R::COLUMNS.len();
+        let per_statement = (MAX_PARAMS_PER_STATEMENT / columns).max(1);
+        let mut full = self
+            .conn
+            .prepare_cached(&insert_statement::<R>(per_statement))?;
+        let mut changed = 0;
+        for chunk in params.chunks(per_statement * columns) {
+            let chunk_rows = chunk.len() / columns;
+            changed += if chunk_rows == per_statement {
+                full.execute(params_from_iter(chunk))?
+            } else {
+                self.conn
+                    .prepare(&insert_statement::<R>(chunk_rows))?
+                    .execute(params_from_iter(chunk))?
+            };
+        }
+        debug_assert_eq!(params.len(), rows * columns);
+        Ok(changed)
+    }
+
+    /// Flushes the remaining records and returns how many rows were written
+    /// (rows an IGNORE insert skipped aren't counted).
+    pub fn finish(mut self) -> Result<usize, DbError> {
+        self.flush()?;
+        Ok(self.written)
+    }
+}
+
+/// Writes rows into the StationItem table in a single transaction, each row
+/// replacing any existing row for the same (station_id, item_id). Returns the
+/// number of rows written; on error nothing is written.
+#[tracing::instrument(skip(conn, columns), fields(rows = columns.len()))]
+pub fn write_station_items(
+    conn: &mut Connection,
+    columns: &StationItemColumns,
+    batch_size: usize,
+) -> Result<usize, DbError> {
+    let written = in_transaction(conn, |tx| push_station_items(tx, columns, batch_size))?;
+    info!(rows = written, "wrote StationItem rows");
+    Ok(written)
+}
+
+/// Checks rows as write_station_items would write them: they're written in
+/// a transaction that's then rolled back, so rows the database would
+/// refuse fail the same way but the database is left as it was. Returns
+/// the number of rows that would be written.
+#[tracing::instrument(skip(conn, columns), fields(rows = columns.len()))]
+pub fn validate_station_items(
+    conn: &mut Connection,
+    columns: &StationItemColumns,
+    batch_size: usize,
+) -> Result<usize, DbError> {
+    let tx = conn.transaction()?;
+    let written = push_station_items(&tx, columns, batch_size);
+    tx.rollback()?;
+    info!(rows = written.as_ref().ok(), "validated StationItem rows");
+    written
+}
+
+fn push_station_items(
+    tx: &Transaction,
+    columns: &StationItemColumns,
+    batch_size: usize,
+) -> Result<usize, DbError> {
+    let mut batcher = Batcher::<StationItem>::new(tx, batch_size);
+    for idx in 0..columns.len() {
+        batcher.push(&columns.row(idx))?;
+    }
+    batcher.finish()
+}
+
+/// Every system in TD's System table, in system_id order.
+pub fn load_systems(conn: &Connection) -> Result<Vec<System>, DbError> {
+    let mut stmt =
+        conn.prepare("SELECT name, pos_x, pos_y, pos_z FROM System ORDER BY system_id")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(System {
+            name: row.get(0)?,
+            id64: None,
+            x: row.get(1)?,
+            y: row.get(2)?,
+            z: row.get(3)?,
+        })
+    })?;
+    let systems = rows.collect::<Result<Vec<_>, _>>()?;
+    info!(systems = systems.len(), "loaded systems");
+    Ok(systems)
+}
+
+/// station_id -> name for every station in TD's Station table.
+pub fn load_station_names(conn: &Connection) -> Result<HashMap<StationId, String>, DbError> {
+    let mut stmt = conn.prepare("SELECT station_id, name FROM Station")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<Result<HashMap<_, _>, _>>()?)
+}
+
+/// item_id -> name for every item in TD's Item table.
+pub fn load_item_names(conn: &Connection) -> Result<HashMap<ItemId, String>, DbError> {
+    let mut stmt = conn.prepare("SELECT item_id, name FROM Item")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<Result<HashMap<_, _>, _>>()?)
+}
+
+/// Reads a TradeDangerous database's Item table as the commodity table's
+/// id of each item -> the database's item_id, matching them by name, so
+/// records get the ids the database's StationItem rows refer to. Items the
+/// commodity table doesn't know are left out.
+pub fn load_items(conn: &Connection) -> Result<HashMap<ItemId, ItemId>, DbError> {
+    let mut stmt = conn.prepare("SELECT name, item_id FROM Item")?;
+    let rows = stmt.query_map([], |row| {
+        let name: String = row.get(0)?;
+        Ok((canonical_commodity(&name).map(|c| c.id), row.get(1)?))
+    })?;
+    let mut items = HashMap::new();
+    for row in rows {
+        if let (Some(commodity), item_id) = row? {
+            items.insert(commodity, item_id);
+        }
+    }
+    info!(items = items.len(), "loaded items");
+    Ok(items)
+}
+
+/// Whether the database has a table of this name.
+pub fn has_table(conn: &Connection, table: &str) -> Result<bool, DbError> {
+    let found = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(found)
+}
+
+/// Loads every station, placed at its system's coordinates, and every
+/// StationItem row into a MarketStore, with the categories of the items of
+/// the Item table if there is one.
+#[tracing::instrument(skip_all)]
+pub fn load_store(conn: &Connection) -> Result<MarketStore, DbError> {
+    let mut store = MarketStore::new();
+    if has_table(conn, "Item")? {
+        for (commodity, item_id) in load_items(conn)? {
+            if let Some(commodity) = commodity_by_id(commodity) {
+                store.set_category(item_id, commodity.category);
+            }
+        }
+    }
+    let mut stmt = conn.prepare(
+        "SELECT Station.station_id, System.name, pos_x, pos_y, pos_z \
+         FROM Station JOIN System USING (system_id)",
+    )?;
+    let stations = stmt
+        .query_map([], |row| {
+            let system: String = row.get(1)?;
+            Ok((row.get(0)?, system, [row.get(2)?, row.get(3)?, row.get(4)?]))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    store.add_stations(&stations);
+    let mut stmt = conn.prepare(
+        "SELECT station_id, item_id, demand_price, demand_units, demand_level, \
+         supply_price, supply_units, supply_level, \
+         CAST(COALESCE(strftime('%s', modified), 0) AS INTEGER) FROM StationItem",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        store.insert(StationItem {
+            station_id: row.get(0)?,
+            item_id: row.get(1)?,
+            demand_price: row.get(2)?,
+            demand_units: row.get(3)?,
+            demand_level: row.get(4)?,
+            supply_price: row.get(5)?,
+            supply_units: row.get(6)?,
+            supply_level: row.get(7)?,
+            modified: row.get(8)?,
+        });
+    }
+    info!(listings = store.len(), "loaded market store");
+    Ok(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commodities::Category;
+    use crate::market::StationItem;
+
+    /// The columns of TD's StationItem table that the writer fills.
+    const STATION_ITEM_TABLE: &str = "CREATE TABLE StationItem (
+        station_id INTEGER NOT NULL,
+        item_id INTEGER NOT NULL,
+        demand_price INT NOT NULL,
+        demand_units INT NOT NULL,
+        demand_level INT NOT NULL,
+        supply_price INT NOT NULL,
+        supply_units INT NOT NULL,
+        supply_level INT NOT NULL,
+        modified DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        from_live INTEGER DEFAULT 0 NOT NULL,
+        PRIMARY KEY (station_id, item_id)
+    ) WITHOUT ROWID";
+
+    fn item(station_id: u32, item_id: u32, demand_price: i32) -> StationItem {
+        StationItem {
+            station_id: StationId(station_id),
+            item_id: ItemId(item_id),
+            demand_price,
+            demand_units: 100,
+            demand_level: 2,
+            supply_level: -1,
+            modified: 1714521600,
+            ..Default::default()
+        }
+    }
+
+    fn open() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(STATION_ITEM_TABLE, []).unwrap();
+        conn
+    }
+
+    fn count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM StationItem", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_write_station_items() {
+        let mut conn = open();
+        // more than one full statement plus a remainder
+        let rows: Vec<StationItem> = (0..250).map(|i| item(i / 10, i % 10, i as i32)).collect();
+        let columns: StationItemColumns = rows.iter().collect();
+        assert_eq!(
+            write_station_items(&mut conn, &columns, DEFAULT_BATCH_SIZE).unwrap(),
+            250
+        );
+        assert_eq!(count(&conn), 250);
+
+        let (price, level, modified): (i32, i32, String) = conn
+            .query_row(
+                "SELECT demand_price, demand_level, modified FROM StationItem \
+                 WHERE station_id = 12 AND item_id = 3",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((price, level), (123, 2));
+        assert_eq!(modified, "2024-05-01 00:00:00");
+    }
+
+    #[test]
+    fn test_validate_station_items() {
+        let mut conn = open();
+        let columns: StationItemColumns = [item(1, 1, 10), item(1, 2, 20)].iter().collect();
+        assert_eq!(
+            validate_station_items(&mut conn, &columns, DEFAULT_BATCH_SIZE).unwrap(),
+            2
+        );
+        assert_eq!(count(&conn), 0);
+        // failures show as they would in a write
+        conn.execute_batch("DROP TABLE StationItem").unwrap();
+        assert!(validate_station_items(&mut conn, &columns, DEFAULT_BATCH_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_write_station_items_replaces() {
+        let mut conn = open();
+        let columns: StationItemColumns = [item(1, 1, 10), item(1, 2, 20)].iter().collect();
+        write_station_items(&mut conn, &columns, DEFAULT_BATCH_SIZE).unwrap();
+        let columns: StationItemColumns = [item(1, 2, 99)].iter().collect();
+        write_station_items(&mut conn, &columns, DEFAULT_BATCH_SIZE).unwrap();
+        assert_eq!(count(&conn), 2);
+        let price: i32 = conn
+            .query_row(
+                "SELECT demand_price FROM StationItem WHERE item_id = 2",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(price, 99);
+        assert_eq!(
+            write_station_items(&mut conn, &StationItemColumns::new(), DEFAULT_BATCH_SIZE).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_write_station_items_is_atomic() {
+        let mut conn = open();
+        let mut rows: Vec<StationItem> = (0..150).map(|i| item(1, i, 1)).collect();
+        rows[120].station_id = StationId(0);
+        conn.execute_batch(
+            "CREATE TRIGGER reject BEFORE INSERT ON StationItem \
+             WHEN NEW.station_id = 0 BEGIN SELECT RAISE(ABORT, 'bad station'); END",
+        )
+        .unwrap();
+        let columns: StationItemColumns = rows.iter().collect();
+        assert!(write_station_items(&mut conn, &columns, DEFAULT_BATCH_SIZE).is_err());
+        assert_eq!(count(&conn), 0);
+    }
+
+    #[test]
+    fn test_insert_statement() {
+        assert_eq!(
+            insert_statement::<StationItem>(2),
+            "INSERT OR REPLACE INTO StationItem (station_id, item_id, demand_price, \
+             demand_units, demand_level, supply_price, supply_units, supply_level, modified) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, datetime(?, 'unixepoch')), \
+             (?, ?, ?, ?, ?, ?, ?, ?, datetime(?, 'unixepoch'))"
+        );
+    }
+
+    #[test]
+    fn test_batcher_reports_failed_batch() {
+        let conn = open();
+        conn.execute_batch(
+            "CREATE TRIGGER reject BEFORE INSERT ON StationItem \
+             WHEN NEW.station_id = 0 BEGIN SELECT RAISE(ABORT, 'bad station'); END",
+        )
+        .unwrap();
+        let mut batcher = Batcher::<StationItem>::new(&conn, 10);
+        let mut failures = Vec::new();
+        for i in 0..35 {
+            let station_id = if i == 17 { 0 } else { 1 };
+            if let Err(e) = batcher.push(&item(station_id, i, 1)) {
+                failures.push(e);
+            }
+        }
+        // the bad row takes its batch with it, the others are written
+        assert_eq!(batcher.finish().unwrap(), 25);
+        assert_eq!(count(&conn), 25);
+        assert_eq!(failures.len(), 1);
+        match &failures[0] {
+            DbError::Batch {
+                batch,
+                first_row,
+                rows,
+                ..
+            } => assert_eq!((*batch, *first_row, *rows), (1, 10, 10)),
+            e => panic!("unexpected error {}", e),
+        }
+        assert!(failures[0]
+            .to_string()
+            .starts_with("batch 1 (rows 10..20) failed"));
+    }
+
+    #[test]
+    fn test_batcher_rolls_back_failed_batch() {
+        // the bad row is in the batch's second statement; the first
+        // statement's rows mustn't stay behind
+        let conn = open();
+        conn.execute_batch(
+            "CREATE TRIGGER reject BEFORE INSERT ON StationItem \
+             WHEN NEW.station_id = 0 BEGIN SELECT RAISE(ABORT, 'bad station'); END",
+        )
+        .unwrap();
+        let mut batcher = Batcher::<StationItem>::new(&conn, 300);
+        for i in 0..250 {
+            let station_id = if i == 150 { 0 } else { i + 1 };
+            batcher.push(&item(station_id, 1, 1)).unwrap();
+        }
+        assert!(batcher.finish().is_err());
+        assert_eq!(count(&conn), 0);
+        assert!(conn.is_autocommit());
+    }
+
+    #[test]
+    fn test_batcher_splits_statements() {
+        // 300 rows in one batch need three statements of 111 rows at most
+        let conn = open();
+        let mut batcher = Batcher::<StationItem>::new(&conn, 300);
+        for i in 0..300 {
+            batcher.push(&item(i, 1, 1)).unwrap();
+        }
+        assert_eq!(count(&conn), 300);
+        assert_eq!(batcher.finish().unwrap(), 300);
+    }
+
+    #[test]
+    fn test_open_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("TradeDangerous.db");
+        let conn = open_database(&path).unwrap();
+        let mode: String = conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        let synchronous: i64 = conn
+            .pragma_query_value(None, "synchronous", |row| row.get(0))
+            .unwrap();
+        assert_eq!(synchronous, 1);
+        let foreign_keys: bool = conn
+            .pragma_query_value(None, "foreign_keys", |row| row.get(0))
+            .unwrap();
+        assert!(foreign_keys);
+        assert!(open_database(dir.path().join("no/such/dir.db")).is_err());
+    }
+
+    #[test]
+    fn test_in_transaction() {
+        let mut conn = open();
+        let written = in_transaction(&mut conn, |tx| {
+            tx.execute(
+                "INSERT INTO StationItem VALUES (1, 1, 0, 0, 0, 0, 0, 0, 0, 0)",
+                [],
+            )?;
+            Ok(1)
+        })
+        .unwrap();
+        assert_eq!(written, 1);
+
+        let failed: Result<(), DbError> = in_transaction(&mut conn, |tx| {
+            tx.execute(
+                "INSERT INTO StationItem VALUES (1, 2, 0, 0, 0, 0, 0, 0, 0, 0)",
+                [],
+            )?;
+            tx.execute("INSERT INTO NoSuchTable VALUES (1)", [])?;
+            Ok(())
+        });
+        assert!(failed.is_err());
+        assert_eq!(count(&conn), 1);
+    }
+
+    #[test]
+    fn test_load_store() {
+        let conn = open();
+        conn.execute_batch(
+            "CREATE TABLE System (system_id INTEGER PRIMARY KEY, name TEXT, \
+                 pos_x REAL, pos_y REAL, pos_z REAL);
+             CREATE TABLE Station (station_id INTEGER PRIMARY KEY, name TEXT, system_id INTEGER);
+             INSERT INTO System VALUES (1, 'Sol', 0, 0, 0), (2, 'Lave', 75.75, 48.75, 70.75);
+             INSERT INTO Station VALUES (7, 'Abraham Lincoln', 1), (9, 'Lave Station', 2);
+             INSERT INTO StationItem VALUES \
+                 (7, 42, 9500, 10, 2, 0, 0, 0, '2024-05-01 00:00:00', 0);
+             CREATE TABLE Item (item_id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO Item VALUES (42, 'Gold');",
+        )
+        .unwrap();
+        let systems = load_systems(&conn).unwrap();
+        assert_eq!(systems.len(), 2);
+        assert_eq!((systems[1].name.as_str(), systems[1].x), ("Lave", 75.75));
+        assert_eq!(
+            load_station_names(&conn).unwrap()[&StationId(9)],
+            "Lave Station"
+        );
+
+        let store = load_store(&conn).unwrap();
+        assert_eq!(store.station_system(StationId(9)), Some("Lave"));
+        assert_eq!(
+            store.station_position(StationId(9)),
+            Some([75.75, 48.75, 70.75])
+        );
+        assert_eq!(store.len(), 1);
+        assert_eq!(
+            store.get(StationId(7), ItemId(42)).unwrap().modified,
+            1714521600
+        );
+        // 42 is Gold in this database, whatever the commodity table says
+        assert_eq!(load_item_names(&conn).unwrap()[&ItemId(42)], "Gold");
+        assert_eq!(store.category(ItemId(42)), Some(Category::Metals));
+        assert_eq!(store.category_listings(Category::Metals).len(), 1);
+        assert!(load_store(&Connection::open_in_memory().unwrap()).is_err());
+    }
+}
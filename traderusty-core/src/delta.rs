@@ -0,0 +1,250 @@
+//! The changes between two .prices dumps, so a database that has imported
+//! the old one can apply just what differs instead of importing the new one
+//! whole.
+//!
+//! The changeset works a station's market at a time: stations only in the
+//! new dump are added, stations only in the old are removed, and a station
+//! in both is changed if any of its listings was added, dropped, or differs
+//! in price, units or level. A changed station comes with its whole new
+//! market, to replace the old one with. Timestamps aren't compared, so a
+//! market surveyed again and found the same isn't a change.
+//!
+//! Names are compared in canonical form, as in merges, and where a dump
+//! lists an item at a station more than once the later line wins. The two
+//! dumps are parsed in parallel; lines that fail to parse are left out and
+//! reported, up to the ParseOptions' `max_errors`.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use tracing::info;
+
+use crate::merge::{read_file, MergeError};
+use crate::metrics;
+use crate::names::canonical_name;
+use crate::options::ParseOptions;
+use crate::prices::PriceRecord;
+use crate::source::DataSource;
+
+/// A dump's listings by canonical (system, station), then canonical item.
+type Markets = BTreeMap<(String, String), BTreeMap<String, PriceRecord>>;
+
+#[derive(Debug, Default)]
+pub struct Changeset {
+    /// Listings of the stations only in the new dump.
+    pub added: Vec<PriceRecord>,
+    /// The new listings of the stations whose markets changed.
+    pub changed: Vec<PriceRecord>,
+    /// (system, station) of the stations only in the old dump.
+    pub removed: Vec<(String, String)>,
+    /// Lines that failed to parse and were left out.
+    pub errors: Vec<MergeError>,
+}
+
+impl Changeset {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// True if two listings of an item differ in anything but when they were
+/// seen.
+fn listing_differs(old: &PriceRecord, new: &PriceRecord) -> bool {
+    (
+        old.demand_price,
+        old.demand_units,
+        old.demand_level,
+        old.supply_price,
+        old.supply_units,
+        old.supply_level,
+    ) != (
+        new.demand_price,
+        new.demand_units,
+        new.demand_level,
+        new.supply_price,
+        new.supply_units,
+        new.supply_level,
+    )
+}
+
+fn market_differs(
+    old: &BTreeMap<String, PriceRecord>,
+    new: &BTreeMap<String, PriceRecord>,
+) -> bool {
+    old.len() != new.len()
+        || old
+            .iter()
+            .zip(new)
+            .any(|((old_item, old), (new_item, new))| {
+                old_item != new_item || listing_differs(old, new)
+            })
+}
+
+/// A dump's markets, and the lines that failed to parse.
+fn read_markets<S: DataSource>(
+    source: &S,
+    parse_options: &ParseOptions,
+) -> Result<(Markets, Vec<MergeError>), MergeError> {
+    let (records, errors) = read_file(source, parse_options)?;
+    let errors = errors
+        .into_iter()
+        .map(|error| MergeError {
+            path: PathBuf::from(source.name()),
+            error,
+        })
+        .collect();
+    let mut markets = Markets::new();
+    for record in records {
+        let station = (
+            canonical_name(&record.system),
+            canonical_name(&record.station),
+        );
+        markets
+            .entry(station)
+            .or_default()
+            .insert(canonical_name(&record.item), record);
+    }
+    Ok((markets, errors))
+}
+
+/// The changes from the `old` dump to the `new` one.
+#[tracing::instrument(skip_all, fields(old = old.name(), new = new.name()))]
+pub fn diff_dumps<A: DataSource + Sync, B: DataSource + Sync>(
+    old: &A,
+    new: &B,
+    parse_options: &ParseOptions,
+) -> Result<Changeset, MergeError> {
+    let _timer = metrics::time_stage("diff_dumps");
+    let (old_read, new_read) = rayon::join(
+        || read_markets(old, parse_options),
+        || read_markets(new, parse_options),
+    );
+    let ((mut old_markets, old_errors), (new_markets, new_errors)) = (old_read?, new_read?);
+
+    let mut changeset = Changeset::default();
+    for error in old_errors.into_iter().chain(new_errors) {
+        if parse_options.too_many_errors(changeset.errors.len() + 1) {
+            return Err(error);
+        }
+        changeset.errors.push(error);
+    }
+    for (station, market) in new_markets {
+        match old_markets.remove(&station) {
+            None => changeset.added.extend(market.into_values()),
+            Some(old_market) if market_differs(&old_market, &market) => {
+                changeset.changed.extend(market.into_values())
+            }
+            Some(_) => {}
+        }
+    }
+    changeset.removed = old_markets
+        .into_values()
+        .filter_map(|market| {
+            let record = market.into_values().next()?;
+            Some((record.system, record.station))
+        })
+        .collect();
+    info!(
+        added = changeset.added.len(),
+        changed = changeset.changed.len(),
+        removed = changeset.removed.len(),
+        errors = changeset.errors.len(),
+        "diffed .prices dumps"
+    );
+    Ok(changeset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::ReadOptions;
+    use crate::source::{FileSource, MemorySource};
+    use std::fs;
+
+    const OLD: &str = "@ SOL/Abraham Lincoln\n\
+                       Gold 100 0 - - 2024-05-01 12:00:00\n\
+                       Silver 50 0 - - 2024-05-01 12:00:00\n\
+                       @ SOL/Daedalus\n\
+                       Gold 110 0 - - 2024-05-01 12:00:00\n\
+                       @ LHS 3447/Bluford Orbital\n\
+                       Gold 300 0 - - 2024-05-01 12:00:00\n\
+                       @ ALPHA CENTAURI/Hutton Orbital\n\
+                       Gold 120 0 - - 2024-05-01 12:00:00\n";
+
+    fn diff(old: &str, new: &str, max_errors: Option<usize>) -> Result<Changeset, MergeError> {
+        let old = MemorySource::new("old.prices", old.as_bytes());
+        let new = MemorySource::new("new.prices", new.as_bytes());
+        let parse_options = ParseOptions {
+            max_errors,
+            ..Default::default()
+        };
+        diff_dumps(&old, &new, &parse_options)
+    }
+
+    fn listings(records: &[PriceRecord]) -> Vec<(&str, &str, i32)> {
+        records
+            .iter()
+            .map(|r| (r.station.as_str(), r.item.as_str(), r.demand_price))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_dumps() {
+        let new = "@ SOL/Abraham Lincoln\n\
+                   Gold 100 0 - - 2024-06-01 12:00:00\n\
+                   Silver 55 0 - - 2024-06-01 12:00:00\n\
+                   @ Sol/daedalus\n\
+                   Gold 110 0 - - 2024-06-01 12:00:00\n\
+                   @ ALPHA CENTAURI/Hutton Orbital\n\
+                   Gold 120 0 - - 2024-06-01 12:00:00\n\
+                   Silver 60 0 - - 2024-06-01 12:00:00\n\
+                   @ LTT 1873/Wakata Station\n\
+                   Gold 130 0 - - 2024-06-01 12:00:00\n";
+        let changeset = diff(OLD, new, None).unwrap();
+        assert!(changeset.errors.is_empty());
+        assert_eq!(
+            listings(&changeset.added),
+            [("Wakata Station", "Gold", 130)]
+        );
+        // Abraham Lincoln's Silver price changed and Hutton Orbital gained
+        // Silver; Daedalus was only seen again
+        assert_eq!(
+            listings(&changeset.changed),
+            [
+                ("Hutton Orbital", "Gold", 120),
+                ("Hutton Orbital", "Silver", 60),
+                ("Abraham Lincoln", "Gold", 100),
+                ("Abraham Lincoln", "Silver", 55),
+            ]
+        );
+        assert_eq!(
+            changeset.removed,
+            [("LHS 3447".to_string(), "Bluford Orbital".to_string())]
+        );
+        assert!(diff(OLD, OLD, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diff_dumps_errors() {
+        let new = "@ SOL/Abraham Lincoln\nGold lots 0\n";
+        let changeset = diff(OLD, new, None).unwrap();
+        assert_eq!(changeset.errors.len(), 1);
+        assert!(changeset.errors[0].path.ends_with("new.prices"));
+        assert_eq!(changeset.removed.len(), 4);
+
+        let err = diff(new, new, Some(1)).unwrap_err();
+        assert!(err.to_string().contains("new.prices"));
+        let dir = tempfile::tempdir().unwrap();
+        let missing = FileSource::new(dir.path().join("missing.prices"), &ReadOptions::default());
+        let old = MemorySource::new("old.prices", OLD.as_bytes());
+        assert!(diff_dumps(&old, &missing, &ParseOptions::default()).is_err());
+
+        // the dumps can come from different kinds of source
+        let path = dir.path().join("new.prices");
+        fs::write(&path, OLD).unwrap();
+        let new = FileSource::new(&path, &ReadOptions::default());
+        assert!(diff_dumps(&old, &new, &ParseOptions::default())
+            .unwrap()
+            .is_empty());
+    }
+}
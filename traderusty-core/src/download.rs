@@ -0,0 +1,421 @@
+//! Downloading dump files, such as Spansh's galaxy dumps, over connections
+//! that can't be trusted to stay up for the length of a 30 GB fetch. Built
+//! with the "download" feature.
+//!
+//! The file is written to `<dest>.part` and only renamed to `dest` once
+//! complete, so a half-finished download is never mistaken for a dump. If
+//! the transfer breaks, the download carries on from the end of the .part
+//! file with an HTTP range request, whether on a retry or in a later run.
+//! The server's ETag or Last-Modified is kept beside it in
+//! `<dest>.part.validator` and sent as If-Range, so a dump replaced
+//! mid-download, even between runs, is fetched again whole rather than
+//! spliced onto the old one. A server that ignores ranges gets the file
+//! from the start.
+//!
+//! Failures that may not happen again (connection trouble, 429, 5xx) are
+//! retried according to the downloader's RetryPolicy; an attempt that got
+//! some bytes through resets the count, so a flaky connection wears through
+//! a big file instead of giving up partway.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::http::{HttpError, RetryPolicy};
+
+/// Times a failed attempt is retried by default, and the wait before the
+/// first retry.
+pub const DEFAULT_RETRIES: usize = 5;
+pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(2);
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Where a download's bytes go until it's complete.
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+/// Where the ETag or Last-Modified of the file being downloaded to a .part
+/// file is kept.
+fn validator_path(part: &Path) -> PathBuf {
+    let mut name = part.file_name().unwrap_or_default().to_os_string();
+    name.push(".validator");
+    part.with_file_name(name)
+}
+
+/// The validator saved for a .part file, if it has one and any bytes.
+fn load_validator(part: &Path) -> Option<String> {
+    if fs::metadata(part).map_or(0, |meta| meta.len()) == 0 {
+        return None;
+    }
+    fs::read_to_string(validator_path(part))
+        .ok()
+        .filter(|validator| !validator.is_empty())
+}
+
+/// Saves or, given None, removes the validator for a .part file.
+fn save_validator(part: &Path, validator: Option<&str>) -> io::Result<()> {
+    let path = validator_path(part);
+    match validator {
+        Some(validator) => fs::write(path, validator),
+        None => match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// The start and total length of a Content-Range like "bytes 100-199/200",
+/// or "bytes */200" as a 416 gives; the total is None if given as "*".
+fn parse_content_range(value: &str) -> Option<(Option<u64>, Option<u64>)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let start = match range.trim() {
+        "*" => None,
+        range => Some(range.split_once('-')?.0.parse().ok()?),
+    };
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some((start, total))
+}
+
+/// How one attempt ended.
+enum Attempt {
+    Complete,
+    /// The server answered, but the file isn't all there yet.
+    Incomplete,
+}
+
+/// Why an attempt failed: the transfer, which may be retried, or the
+/// progress callback, which stops the download.
+enum Failure {
+    Http(HttpError),
+    Stopped(io::Error),
+}
+
+impl<E: Into<HttpError>> From<E> for Failure {
+    fn from(e: E) -> Self {
+        Failure::Http(e.into())
+    }
+}
+
+pub struct Downloader {
+    agent: ureq::Agent,
+    retry: RetryPolicy,
+}
+
+impl Downloader {
+    /// A downloader giving up on a connection that's silent for longer than
+    /// `timeout`. The whole download may take as long as it takes.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(timeout)
+                .timeout_read(timeout)
+                .build(),
+            retry: RetryPolicy::new(DEFAULT_RETRIES, DEFAULT_BACKOFF),
+        }
+    }
+
+    /// Retries failed attempts up to `retries` times in a row, waiting
+    /// `backoff` before the first retry and twice as long before each one
+    /// after.
+    pub fn retries(mut self, retries: usize, backoff: Duration) -> Self {
+        self.retry.retries = retries;
+        self.retry.backoff = backoff;
+        self
+    }
+
+    /// Retries failed attempts according to `retry`.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Downloads `url` to `dest`, returning its size. `progress` is called
+    /// with the bytes downloaded so far and the total, if known, as they
+    /// arrive; an error from it stops the download, leaving the .part file
+    /// to resume from.
+    pub fn download(
+        &self,
+        url: &str,
+        dest: &Path,
+        mut progress: impl FnMut(u64, Option<u64>) -> io::Result<()>,
+    ) -> Result<u64, HttpError> {
+        let part = part_path(dest);
+        let mut validator = load_validator(&part);
+        let mut attempt = 0;
+        loop {
+            let before = fs::metadata(&part).map_or(0, |meta| meta.len());
+            let result = self.attempt(url, &part, &mut validator, &mut progress);
+            let after = fs::metadata(&part).map_or(0, |meta| meta.len());
+            if after > before {
+                attempt = 0;
+            }
+            match result {
+                Ok(Attempt::Complete) => break,
+                Ok(Attempt::Incomplete) if attempt < self.retry.retries => {
+                    attempt += 1;
+                    warn!(url, attempt, bytes = after, "download cut short, resuming");
+                }
+                Ok(Attempt::Incomplete) => {
+                    return Err(HttpError::Io(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!("download stopped at byte {}", after),
+                    )))
+                }
+                Err(Failure::Http(e)) if e.is_transient() && attempt < self.retry.retries => {
+                    attempt += 1;
+                    self.retry.wait(url, attempt, &e);
+                }
+                Err(Failure::Http(e)) => return Err(e),
+                Err(Failure::Stopped(e)) => return Err(HttpError::Io(e)),
+            }
+        }
+        let size = fs::metadata(&part)?.len();
+        fs::rename(&part, dest)?;
+        save_validator(&part, None)?;
+        info!(url, dest = %dest.display(), size, "downloaded");
+        Ok(size)
+    }
+
+    /// One request, picking up from the end of the .part file.
+    fn attempt(
+        &self,
+        url: &str,
+        part: &Path,
+        validator: &mut Option<String>,
+        progress: &mut impl FnMut(u64, Option<u64>) -> io::Result<()>,
+    ) -> Result<Attempt, Failure> {
+        let offset = fs::metadata(part).map_or(0, |meta| meta.len());
+        // Ranges count bytes as sent, so the server mustn't compress them.
+        let mut request = self.agent.get(url).set("Accept-Encoding", "identity");
+        if offset > 0 {
+            request = request.set("Range", &format!("bytes={}-", offset));
+            if let Some(validator) = validator.as_deref() {
+                request = request.set("If-Range", validator);
+            }
+        }
+        let response = match request.call() {
+            Ok(response) => response,
+            // Asked for a range past the end: the file may be all there.
+            Err(ureq::Error::Status(416, response)) => {
+                let total = response
+                    .header("Content-Range")
+                    .and_then(parse_content_range)
+                    .and_then(|(_, total)| total);
+                if total == Some(offset) {
+                    progress(offset, total).map_err(Failure::Stopped)?;
+                    return Ok(Attempt::Complete);
+                }
+                File::create(part)?;
+                *validator = None;
+                save_validator(part, None)?;
+                return Ok(Attempt::Incomplete);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let served = response
+            .header("ETag")
+            .or_else(|| response.header("Last-Modified"))
+            .map(str::to_string);
+        let content_length = response
+            .header("Content-Length")
+            .and_then(|length| length.parse::<u64>().ok());
+        let (mut file, mut downloaded, total) = match response.status() {
+            206 => {
+                let range = response
+                    .header("Content-Range")
+                    .and_then(parse_content_range);
+                let Some((Some(start), total)) = range.filter(|(start, _)| *start == Some(offset))
+                else {
+                    // Not the range asked for; start again rather than guess.
+                    warn!(url, offset, "unexpected Content-Range, restarting download");
+                    File::create(part)?;
+                    *validator = None;
+                    save_validator(part, None)?;
+                    return Ok(Attempt::Incomplete);
+                };
+                if validator.is_none() && served.is_some() {
+                    *validator = served;
+                    save_validator(part, validator.as_deref())?;
+                }
+                let file = OpenOptions::new().append(true).open(part)?;
+                (file, start, total)
+            }
+            _ => {
+                if offset > 0 {
+                    info!(
+                        url,
+                        offset, "server sent the whole file, restarting download"
+                    );
+                }
+                // a new start, so whatever the server says it's sending
+                *validator = served;
+                save_validator(part, validator.as_deref())?;
+                (File::create(part)?, 0, content_length)
+            }
+        };
+        progress(downloaded, total).map_err(Failure::Stopped)?;
+        let mut body = response.into_reader();
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            let n = match body.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            file.write_all(&buffer[..n])?;
+            downloaded += n as u64;
+            progress(downloaded, total).map_err(Failure::Stopped)?;
+        }
+        file.sync_all()?;
+        match total {
+            Some(total) if downloaded < total => Ok(Attempt::Incomplete),
+            _ => Ok(Attempt::Complete),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::testing;
+
+    /// A response with a raw body, which may be shorter than it claims.
+    fn raw(status: &str, headers: &str, length: usize, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+            status, length, headers, body
+        )
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 100-199/200"),
+            Some((Some(100), Some(200)))
+        );
+        assert_eq!(parse_content_range("bytes 0-9/*"), Some((Some(0), None)));
+        assert_eq!(parse_content_range("bytes */200"), Some((None, Some(200))));
+        assert_eq!(parse_content_range("items 0-9/10"), None);
+    }
+
+    #[test]
+    fn test_download_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("galaxy.json.gz");
+        let part = part_path(&dest);
+        assert_eq!(part, dir.path().join("galaxy.json.gz.part"));
+        // an earlier run got the first 5 bytes
+        fs::write(&part, "01234").unwrap();
+        let (url, server) = testing::serve(vec![
+            // cut off after 5 of the 15 bytes promised
+            raw(
+                "206 Partial Content",
+                "Content-Range: bytes 5-19/20\r\nETag: \"v1\"\r\n",
+                15,
+                "56789",
+            ),
+            raw(
+                "206 Partial Content",
+                "Content-Range: bytes 10-19/20\r\n",
+                10,
+                "abcdefghij",
+            ),
+        ]);
+        let mut seen = Vec::new();
+        let downloader = Downloader::new(Duration::from_secs(5)).retries(2, Duration::ZERO);
+        let size = downloader
+            .download(&format!("{}/galaxy.json.gz", url), &dest, |done, total| {
+                seen.push((done, total));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(server.join().unwrap().len(), 2);
+        assert_eq!(size, 20);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "0123456789abcdefghij");
+        assert!(!part.exists());
+        assert_eq!(seen.first(), Some(&(5, Some(20))));
+        assert_eq!(seen.last(), Some(&(20, Some(20))));
+    }
+
+    #[test]
+    fn test_download_resumes_in_later_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("galaxy.json.gz");
+        let part = part_path(&dest);
+        let downloader = Downloader::new(Duration::from_secs(5)).retries(0, Duration::ZERO);
+
+        // the first run is cut off after 5 of 10 bytes
+        let (url, server) = testing::serve(vec![raw("200 OK", "ETag: \"v1\"\r\n", 10, "01234")]);
+        assert!(downloader.download(&url, &dest, |_, _| Ok(())).is_err());
+        server.join().unwrap();
+        assert_eq!(fs::read_to_string(validator_path(&part)).unwrap(), "\"v1\"");
+
+        // the next sends the ETag it had, so the server can refuse to splice
+        let (url, server) = testing::serve_with_headers(vec![raw(
+            "206 Partial Content",
+            "Content-Range: bytes 5-9/10\r\n",
+            5,
+            "56789",
+        )]);
+        assert_eq!(downloader.download(&url, &dest, |_, _| Ok(())).unwrap(), 10);
+        let requests = server.join().unwrap();
+        assert!(requests[0].1.contains(&"If-Range: \"v1\"".to_string()));
+        assert!(requests[0].1.contains(&"Range: bytes=5-".to_string()));
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "0123456789");
+        assert!(!validator_path(&part).exists());
+    }
+
+    #[test]
+    fn test_download_restarts_and_completes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("dump.prices");
+        let part = part_path(&dest);
+        let downloader = Downloader::new(Duration::from_secs(5)).retries(0, Duration::ZERO);
+
+        // a server ignoring the range sends it all, replacing the part
+        fs::write(&part, "stale").unwrap();
+        let (url, server) = testing::serve(vec![raw("200 OK", "", 6, "fresh!")]);
+        downloader.download(&url, &dest, |_, _| Ok(())).unwrap();
+        server.join().unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "fresh!");
+
+        // everything was already there
+        fs::write(&part, "done").unwrap();
+        let (url, server) = testing::serve(vec![raw(
+            "416 Range Not Satisfiable",
+            "Content-Range: bytes */4\r\n",
+            0,
+            "",
+        )]);
+        assert_eq!(downloader.download(&url, &dest, |_, _| Ok(())).unwrap(), 4);
+        server.join().unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "done");
+
+        // a progress callback's error stops it, keeping what arrived
+        let (url, server) = testing::serve(vec![raw("200 OK", "", 3, "abc")]);
+        let err = downloader
+            .download(&url, &dest, |done, _| match done {
+                0 => Ok(()),
+                _ => Err(io::Error::other("stop")),
+            })
+            .unwrap_err();
+        server.join().unwrap();
+        assert!(err.to_string().contains("stop"), "{}", err);
+        assert_eq!(fs::read_to_string(&part).unwrap(), "abc");
+
+        let (url, server) = testing::serve(vec![raw("404 Not Found", "", 0, "")]);
+        let err = downloader.download(&url, &dest, |_, _| Ok(())).unwrap_err();
+        server.join().unwrap();
+        assert!(!err.is_transient());
+    }
+}
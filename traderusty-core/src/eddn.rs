@@ -0,0 +1,240 @@
+//! The EDDN messages an uploader sends for what the game wrote on docking,
+//! built from the parsed Market.json, Outfitting.json and Shipyard.json so
+//! plugins don't each reimplement the conversion.
+//!
+//! Each message is the schema it follows, a header naming the uploader and
+//! its software, and the message proper:
+//!
+//! ```json
+//! {"$schemaRef": "https://eddn.edcd.io/schemas/commodity/3",
+//!  "header": {"uploaderID": "...", "softwareName": "...", "softwareVersion": "..."},
+//!  "message": {"systemName": "Sol", "stationName": "...", "marketId": 128016640,
+//!              "timestamp": "2024-05-01T12:00:00Z", "horizons": true, "odyssey": true,
+//!              "commodities": [...]}}
+//! ```
+//!
+//! The gateway stamps each message with a `gatewayTimestamp` as it relays
+//! it; one is only included here when the header is given one, as for
+//! replaying relayed traffic.
+//!
+//! As EDDN requires: limpets and other non-marketable items aren't listed,
+//! brackets with no level are sent as "", and outfitting lists only
+//! weapons, internals and armour.
+
+use serde_json::{json, Value};
+
+use crate::commodities::{commodity_by_id, Category};
+use crate::journal::{format_iso_timestamp, MarketFile, OutfittingFile, ShipyardFile};
+
+pub const COMMODITY_SCHEMA: &str = "https://eddn.edcd.io/schemas/commodity/3";
+pub const OUTFITTING_SCHEMA: &str = "https://eddn.edcd.io/schemas/outfitting/2";
+pub const SHIPYARD_SCHEMA: &str = "https://eddn.edcd.io/schemas/shipyard/2";
+
+/// Who is sending, and which game they're playing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EddnHeader {
+    pub uploader_id: String,
+    pub software_name: String,
+    pub software_version: String,
+    /// When the gateway relayed the message, in seconds since the epoch.
+    pub gateway_timestamp: Option<i64>,
+    pub horizons: bool,
+    pub odyssey: bool,
+}
+
+impl EddnHeader {
+    fn envelope(&self, schema: &str, mut message: Value) -> Value {
+        let mut header = json!({
+            "uploaderID": self.uploader_id,
+            "softwareName": self.software_name,
+            "softwareVersion": self.software_version,
+        });
+        if let Some(timestamp) = self.gateway_timestamp {
+            header["gatewayTimestamp"] = json!(format_iso_timestamp(timestamp));
+        }
+        message["horizons"] = json!(self.horizons);
+        message["odyssey"] = json!(self.odyssey);
+        json!({"$schemaRef": schema, "header": header, "message": message})
+    }
+}
+
+/// A level as an EDDN bracket: 1 to 3, or "" for none or unknown.
+fn bracket(level: i32) -> Value {
+    match level {
+        1..=3 => json!(level),
+        _ => json!(""),
+    }
+}
+
+/// A commodity/3 message for a market.
+pub fn commodity_message(market: &MarketFile, header: &EddnHeader) -> Value {
+    let snapshot = &market.snapshot;
+    let commodities: Vec<Value> = snapshot
+        .items()
+        .iter()
+        .filter_map(|item| {
+            let commodity = commodity_by_id(item.item_id)?;
+            if commodity.category == Category::NonMarketable {
+                return None;
+            }
+            Some(json!({
+                "name": commodity.symbol,
+                "meanPrice": market.mean_prices.get(&item.item_id).copied().unwrap_or(0),
+                "buyPrice": item.supply_price,
+                "stock": item.supply_units,
+                "stockBracket": bracket(item.supply_level),
+                "sellPrice": item.demand_price,
+                "demand": item.demand_units,
+                "demandBracket": bracket(item.demand_level),
+            }))
+        })
+        .collect();
+    let message = json!({
+        "systemName": market.system,
+        "stationName": market.station,
+        "marketId": snapshot.station_id,
+        "timestamp": format_iso_timestamp(snapshot.timestamp),
+        "commodities": commodities,
+    });
+    header.envelope(COMMODITY_SCHEMA, message)
+}
+
+/// True for the modules EDDN takes: weapons, internals and armour.
+fn is_outfitting_module(symbol: &str) -> bool {
+    symbol.starts_with("hpt_") || symbol.starts_with("int_") || symbol.contains("_armour_")
+}
+
+/// An outfitting/2 message for a station's outfitting.
+pub fn outfitting_message(outfitting: &OutfittingFile, header: &EddnHeader) -> Value {
+    let modules: Vec<&str> = outfitting
+        .modules
+        .iter()
+        .map(|module| module.symbol.as_str())
+        .filter(|symbol| is_outfitting_module(symbol))
+        .collect();
+    let message = json!({
+        "systemName": outfitting.system,
+        "stationName": outfitting.station,
+        "marketId": outfitting.market_id,
+        "timestamp": format_iso_timestamp(outfitting.timestamp),
+        "modules": modules,
+    });
+    header.envelope(OUTFITTING_SCHEMA, message)
+}
+
+/// A shipyard/2 message for a station's shipyard.
+pub fn shipyard_message(shipyard: &ShipyardFile, header: &EddnHeader) -> Value {
+    let ships: Vec<&str> = shipyard
+        .ships
+        .iter()
+        .map(|ship| ship.symbol.as_str())
+        .collect();
+    let message = json!({
+        "systemName": shipyard.system,
+        "stationName": shipyard.station,
+        "marketId": shipyard.market_id,
+        "timestamp": format_iso_timestamp(shipyard.timestamp),
+        "ships": ships,
+    });
+    header.envelope(SHIPYARD_SCHEMA, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> EddnHeader {
+        EddnHeader {
+            uploader_id: "Jameson".into(),
+            software_name: "TradeDangerous".into(),
+            software_version: "1.0".into(),
+            horizons: true,
+            ..Default::default()
+        }
+    }
+
+    const STATION: &str = r#""timestamp": "2024-05-01T12:00:00Z", "MarketID": 128016640,
+        "StationName": "Abraham Lincoln", "StarSystem": "Sol""#;
+
+    #[test]
+    fn test_commodity_message() {
+        let market = MarketFile::from_json(&format!(
+            r#"{{{}, "Items": [
+                {{"Name": "$gold_name;", "BuyPrice": 9100, "SellPrice": 8900, "MeanPrice": 9000,
+                  "StockBracket": 2, "DemandBracket": "", "Stock": 120, "Demand": 0}},
+                {{"Name": "$drones_name;", "BuyPrice": 101, "SellPrice": 101, "MeanPrice": 101,
+                  "StockBracket": 3, "DemandBracket": 0, "Stock": 9999, "Demand": 0}}
+            ]}}"#,
+            STATION
+        ))
+        .unwrap();
+        let message = commodity_message(&market, &header());
+        assert_eq!(
+            message,
+            json!({
+                "$schemaRef": "https://eddn.edcd.io/schemas/commodity/3",
+                "header": {
+                    "uploaderID": "Jameson",
+                    "softwareName": "TradeDangerous",
+                    "softwareVersion": "1.0",
+                },
+                "message": {
+                    "systemName": "Sol",
+                    "stationName": "Abraham Lincoln",
+                    "marketId": 128016640,
+                    "timestamp": "2024-05-01T12:00:00Z",
+                    "horizons": true,
+                    "odyssey": false,
+                    "commodities": [{
+                        "name": "gold", "meanPrice": 9000, "buyPrice": 9100, "stock": 120,
+                        "stockBracket": 2, "sellPrice": 8900, "demand": 0, "demandBracket": "",
+                    }],
+                },
+            })
+        );
+
+        let relayed = EddnHeader {
+            gateway_timestamp: Some(1714564805),
+            ..header()
+        };
+        let message = commodity_message(&market, &relayed);
+        assert_eq!(
+            message["header"]["gatewayTimestamp"],
+            json!("2024-05-01T12:00:05Z")
+        );
+    }
+
+    #[test]
+    fn test_outfitting_and_shipyard_messages() {
+        let outfitting = OutfittingFile::from_json(&format!(
+            r#"{{{}, "Items": [
+                {{"id": 1, "Name": "Hpt_PulseLaser_Fixed_Medium", "BuyPrice": 16731}},
+                {{"id": 2, "Name": "Int_CargoRack_Size1_Class1", "BuyPrice": 1000}},
+                {{"id": 3, "Name": "Sidewinder_Armour_Grade1", "BuyPrice": 0}},
+                {{"id": 4, "Name": "PaintJob_Sidewinder_Blue", "BuyPrice": 0}}
+            ]}}"#,
+            STATION
+        ))
+        .unwrap();
+        let message = outfitting_message(&outfitting, &header());
+        assert_eq!(message["$schemaRef"], json!(OUTFITTING_SCHEMA));
+        assert_eq!(
+            message["message"]["modules"],
+            json!([
+                "hpt_pulselaser_fixed_medium",
+                "int_cargorack_size1_class1",
+                "sidewinder_armour_grade1",
+            ])
+        );
+
+        let shipyard = ShipyardFile::from_json(&format!(
+            r#"{{{}, "PriceList": [{{"id": 1, "ShipType": "sidewinder", "ShipPrice": 27480}}]}}"#,
+            STATION
+        ))
+        .unwrap();
+        let message = shipyard_message(&shipyard, &header());
+        assert_eq!(message["$schemaRef"], json!(SHIPYARD_SCHEMA));
+        assert_eq!(message["message"]["ships"], json!(["sidewinder"]));
+        assert_eq!(message["message"]["marketId"], json!(128016640));
+    }
+}
@@ -0,0 +1,406 @@
+//! Checking EDDN messages from the relay against the schemas they claim to
+//! follow, before anything is decoded from them.
+//!
+//! The schemas are bundled as tables of the fields each requires and the
+//! type each field must have, covering what the store relies on rather than
+//! every constraint EDDN's JSON schemas make. A message is rejected if its
+//! `$schemaRef` isn't one of them (test schemas included), if a required
+//! field is missing or any field has the wrong type, or if its header names
+//! no sensible software. Every problem is reported, with the path to the
+//! field, not just the first.
+//!
+//! A Validator counts the messages it accepts and rejects per schema, so a
+//! misbehaving uploader shows up as a climbing reject count.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::eddn::{COMMODITY_SCHEMA, OUTFITTING_SCHEMA, SHIPYARD_SCHEMA};
+use crate::journal::parse_iso_timestamp;
+
+/// The longest softwareName taken as sane.
+const MAX_SOFTWARE_NAME: usize = 64;
+
+/// What a field's value must be.
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    /// A string with something other than whitespace in it.
+    Name,
+    Integer,
+    Boolean,
+    /// An ISO 8601 UTC timestamp.
+    Timestamp,
+    /// 0 to 3, or "" for none.
+    Bracket,
+    /// A non-empty array of strings.
+    Names,
+    /// An array of objects with these fields.
+    Objects(&'static [Field]),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Field {
+    name: &'static str,
+    kind: Kind,
+    required: bool,
+}
+
+const fn required(name: &'static str, kind: Kind) -> Field {
+    Field {
+        name,
+        kind,
+        required: true,
+    }
+}
+
+const fn optional(name: &'static str, kind: Kind) -> Field {
+    Field {
+        name,
+        kind,
+        required: false,
+    }
+}
+
+const HEADER: &[Field] = &[
+    required("uploaderID", Kind::Name),
+    required("softwareName", Kind::Name),
+    required("softwareVersion", Kind::Name),
+    optional("gatewayTimestamp", Kind::Timestamp),
+];
+
+const COMMODITY: &[Field] = &[
+    required("name", Kind::Name),
+    required("meanPrice", Kind::Integer),
+    required("buyPrice", Kind::Integer),
+    required("stock", Kind::Integer),
+    required("stockBracket", Kind::Bracket),
+    required("sellPrice", Kind::Integer),
+    required("demand", Kind::Integer),
+    required("demandBracket", Kind::Bracket),
+];
+
+/// A bundled schema: its `$schemaRef` and the fields of its message.
+#[derive(Debug)]
+pub struct Schema {
+    pub uri: &'static str,
+    message: &'static [Field],
+}
+
+pub const SCHEMAS: &[Schema] = &[
+    Schema {
+        uri: COMMODITY_SCHEMA,
+        message: &[
+            required("systemName", Kind::Name),
+            required("stationName", Kind::Name),
+            required("marketId", Kind::Integer),
+            required("timestamp", Kind::Timestamp),
+            optional("horizons", Kind::Boolean),
+            optional("odyssey", Kind::Boolean),
+            required("commodities", Kind::Objects(COMMODITY)),
+        ],
+    },
+    Schema {
+        uri: OUTFITTING_SCHEMA,
+        message: &[
+            required("systemName", Kind::Name),
+            required("stationName", Kind::Name),
+            required("marketId", Kind::Integer),
+            required("timestamp", Kind::Timestamp),
+            optional("horizons", Kind::Boolean),
+            optional("odyssey", Kind::Boolean),
+            required("modules", Kind::Names),
+        ],
+    },
+    Schema {
+        uri: SHIPYARD_SCHEMA,
+        message: &[
+            required("systemName", Kind::Name),
+            required("stationName", Kind::Name),
+            required("marketId", Kind::Integer),
+            required("timestamp", Kind::Timestamp),
+            optional("horizons", Kind::Boolean),
+            optional("odyssey", Kind::Boolean),
+            required("ships", Kind::Names),
+        ],
+    },
+];
+
+/// Something wrong with a message, at the path of the field concerned,
+/// like "message.commodities[2].buyPrice".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub path: String,
+    pub problem: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "{}", self.problem),
+            path => write!(f, "{}: {}", path, self.problem),
+        }
+    }
+}
+
+fn violation(path: &str, problem: impl Into<String>) -> Violation {
+    Violation {
+        path: path.to_string(),
+        problem: problem.into(),
+    }
+}
+
+/// Records why `value` isn't of `kind`, if it isn't, and what's wrong
+/// inside it if it is.
+fn check_kind(value: &Value, kind: Kind, path: &str, found: &mut Vec<Violation>) {
+    let problem = match (kind, value) {
+        (Kind::Name, Value::String(s)) if s.trim().is_empty() => Some("is blank"),
+        (Kind::Name, Value::String(_)) => None,
+        (Kind::Name, _) => Some("expected a string"),
+        (Kind::Integer, Value::Number(n)) if n.is_i64() || n.is_u64() => None,
+        (Kind::Integer, _) => Some("expected an integer"),
+        (Kind::Boolean, Value::Bool(_)) => None,
+        (Kind::Boolean, _) => Some("expected true or false"),
+        (Kind::Timestamp, Value::String(s)) if parse_iso_timestamp(s).is_some() => None,
+        (Kind::Timestamp, _) => Some("expected an ISO 8601 timestamp"),
+        (Kind::Bracket, Value::String(s)) if s.is_empty() => None,
+        (Kind::Bracket, Value::Number(n)) if n.as_u64().is_some_and(|n| n <= 3) => None,
+        (Kind::Bracket, _) => Some("expected 0 to 3 or \"\""),
+        (Kind::Names, Value::Array(items)) if items.is_empty() => Some("is empty"),
+        (Kind::Names, Value::Array(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                check_kind(item, Kind::Name, &format!("{}[{}]", path, i), found);
+            }
+            None
+        }
+        (Kind::Names, _) => Some("expected an array of strings"),
+        (Kind::Objects(fields), Value::Array(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                check_fields(item, fields, &format!("{}[{}]", path, i), found);
+            }
+            None
+        }
+        (Kind::Objects(_), _) => Some("expected an array of objects"),
+    };
+    if let Some(problem) = problem {
+        found.push(violation(path, problem));
+    }
+}
+
+fn check_fields(value: &Value, fields: &[Field], path: &str, found: &mut Vec<Violation>) {
+    let Some(object) = value.as_object() else {
+        found.push(violation(path, "expected an object"));
+        return;
+    };
+    for field in fields {
+        let path = format!("{}.{}", path, field.name);
+        match object.get(field.name) {
+            Some(value) => check_kind(value, field.kind, &path, found),
+            None if field.required => found.push(violation(&path, "is missing")),
+            None => {}
+        }
+    }
+}
+
+/// What more than a type check asks of a header: a softwareName that reads
+/// like the name of a program.
+fn check_software(header: &Value, found: &mut Vec<Violation>) {
+    let Some(name) = header.get("softwareName").and_then(Value::as_str) else {
+        return;
+    };
+    let problem = if name.len() > MAX_SOFTWARE_NAME {
+        "is too long"
+    } else if name.chars().any(char::is_control) {
+        "has control characters in it"
+    } else if name != name.trim() {
+        "has leading or trailing whitespace"
+    } else {
+        return;
+    };
+    found.push(violation("header.softwareName", problem));
+}
+
+/// The bundled schema a message claims to follow, by its `$schemaRef`.
+pub fn schema_for(message: &Value) -> Option<&'static Schema> {
+    let uri = message.get("$schemaRef")?.as_str()?;
+    SCHEMAS.iter().find(|schema| schema.uri == uri)
+}
+
+/// Checks a message against the bundled schema it names, returning that
+/// schema or everything wrong with the message.
+pub fn validate(message: &Value) -> Result<&'static Schema, Vec<Violation>> {
+    if !message.is_object() {
+        return Err(vec![violation("", "expected an object")]);
+    }
+    let Some(schema) = schema_for(message) else {
+        let problem = match message.get("$schemaRef") {
+            Some(Value::String(uri)) => format!("unknown schema {}", uri),
+            Some(_) => "expected a string".to_string(),
+            None => "is missing".to_string(),
+        };
+        return Err(vec![violation("$schemaRef", problem)]);
+    };
+    let mut found = Vec::new();
+    match message.get("header") {
+        Some(header) => {
+            check_fields(header, HEADER, "header", &mut found);
+            check_software(header, &mut found);
+        }
+        None => found.push(violation("header", "is missing")),
+    }
+    match message.get("message") {
+        Some(body) => check_fields(body, schema.message, "message", &mut found),
+        None => found.push(violation("message", "is missing")),
+    }
+    match found.is_empty() {
+        true => Ok(schema),
+        false => Err(found),
+    }
+}
+
+/// Messages accepted and rejected under one schema.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SchemaCounts {
+    pub accepted: u64,
+    pub rejected: u64,
+}
+
+/// Validates messages, counting what it accepts and rejects by schema.
+/// Messages naming no bundled schema, or that aren't JSON, count under "".
+#[derive(Clone, Debug, Default)]
+pub struct Validator {
+    counts: BTreeMap<String, SchemaCounts>,
+}
+
+impl Validator {
+    pub fn validate(&mut self, message: &Value) -> Result<&'static Schema, Vec<Violation>> {
+        let result = validate(message);
+        let uri = schema_for(message).map_or("", |schema| schema.uri);
+        let counts = self.counts.entry(uri.to_string()).or_default();
+        match result {
+            Ok(_) => counts.accepted += 1,
+            Err(_) => counts.rejected += 1,
+        }
+        result
+    }
+
+    /// Parses and validates a message as it came off the wire.
+    pub fn validate_json(&mut self, text: &str) -> Result<&'static Schema, Vec<Violation>> {
+        match serde_json::from_str::<Value>(text) {
+            Ok(message) => self.validate(&message),
+            Err(e) => {
+                self.counts.entry(String::new()).or_default().rejected += 1;
+                Err(vec![violation("", format!("not JSON: {}", e))])
+            }
+        }
+    }
+
+    /// Counts by schema URI.
+    pub fn counts(&self) -> &BTreeMap<String, SchemaCounts> {
+        &self.counts
+    }
+
+    pub fn reset(&mut self) {
+        self.counts.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eddn::{commodity_message, EddnHeader};
+    use crate::journal::MarketFile;
+    use serde_json::json;
+
+    fn market() -> Value {
+        let market = MarketFile::from_json(
+            r#"{"timestamp": "2024-05-01T12:00:00Z", "MarketID": 128016640,
+                "StationName": "Abraham Lincoln", "StarSystem": "Sol", "Items": [
+                {"Name": "$gold_name;", "BuyPrice": 9100, "SellPrice": 8900, "MeanPrice": 9000,
+                 "StockBracket": 2, "DemandBracket": "", "Stock": 120, "Demand": 0}]}"#,
+        )
+        .unwrap();
+        let header = EddnHeader {
+            uploader_id: "Jameson".into(),
+            software_name: "TradeDangerous".into(),
+            software_version: "1.0".into(),
+            gateway_timestamp: Some(1714564805),
+            ..Default::default()
+        };
+        commodity_message(&market, &header)
+    }
+
+    #[test]
+    fn test_validate() {
+        // what we build passes
+        assert_eq!(validate(&market()).unwrap().uri, COMMODITY_SCHEMA);
+
+        let mut message = market();
+        message["header"]["softwareName"] = json!(" TD\n");
+        message["message"]["marketId"] = json!("128016640");
+        message["message"]["commodities"][0]["stockBracket"] = json!(4);
+        message["message"]["commodities"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("buyPrice");
+        message["message"]["timestamp"] = json!("yesterday");
+        let problems: Vec<String> = validate(&message)
+            .unwrap_err()
+            .iter()
+            .map(Violation::to_string)
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "header.softwareName: has control characters in it",
+                "message.marketId: expected an integer",
+                "message.timestamp: expected an ISO 8601 timestamp",
+                "message.commodities[0].buyPrice: is missing",
+                "message.commodities[0].stockBracket: expected 0 to 3 or \"\"",
+            ]
+        );
+
+        let mut test = market();
+        test["$schemaRef"] = json!(format!("{}/test", COMMODITY_SCHEMA));
+        assert_eq!(
+            validate(&test).unwrap_err()[0].problem,
+            format!("unknown schema {}/test", COMMODITY_SCHEMA)
+        );
+        let shipyard = json!({
+            "$schemaRef": SHIPYARD_SCHEMA,
+            "header": {"uploaderID": "J", "softwareName": "TD", "softwareVersion": "1"},
+            "message": {"systemName": "Sol", "stationName": "A", "marketId": 1,
+                        "timestamp": "2024-05-01T12:00:00Z", "ships": []},
+        });
+        assert_eq!(
+            validate(&shipyard).unwrap_err(),
+            [violation("message.ships", "is empty")]
+        );
+    }
+
+    #[test]
+    fn test_validator_counts() {
+        let mut validator = Validator::default();
+        validator.validate(&market()).unwrap();
+        validator.validate(&market()).unwrap();
+        let mut bad = market();
+        bad["message"]["systemName"] = json!("");
+        validator.validate(&bad).unwrap_err();
+        validator.validate_json("{").unwrap_err();
+        validator
+            .validate_json(r#"{"$schemaRef": "https://example.com/x"}"#)
+            .unwrap_err();
+        let counts = validator.counts();
+        assert_eq!(
+            counts[COMMODITY_SCHEMA],
+            SchemaCounts {
+                accepted: 2,
+                rejected: 1
+            }
+        );
+        assert_eq!(counts[""].rejected, 2);
+        validator.reset();
+        assert!(validator.counts().is_empty());
+    }
+}
@@ -0,0 +1,338 @@
+//! A client for EDSM's system lookups, so coordinates for systems an import
+//! hasn't seen can be filled in as it goes. Built with the "edsm" feature.
+//!
+//! EDSM allows 360 requests an hour per address. The client spaces its
+//! requests out to stay inside that, and if EDSM still says the limit has
+//! been reached (HTTP 429 or an x-rate-limit-remaining of 0) it waits out
+//! the x-rate-limit-reset it was given before going again.
+//!
+//! Given a ResponseCache, the client answers from it while its responses
+//! are fresh, and only asks EDSM about the rest.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::http::{HttpError, RateLimiter, RetryPolicy};
+use crate::ids::SystemId;
+use crate::system::System;
+#[cfg(feature = "fs")]
+use crate::ttlcache::ResponseCache;
+
+pub const EDSM_URL: &str = "https://www.edsm.net";
+
+/// Default spacing between requests: 360 an hour.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Names sent per request by `systems`.
+const NAMES_PER_REQUEST: usize = 50;
+
+/// Times a failed request is retried by default, and the wait before the
+/// first retry.
+pub const DEFAULT_RETRIES: usize = 3;
+pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+struct EdsmCoords {
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+#[derive(Deserialize)]
+struct EdsmSystem {
+    name: String,
+    id64: Option<SystemId>,
+    coords: Option<EdsmCoords>,
+}
+
+/// EDSM answers a single-system lookup with an object, or `[]` when it
+/// doesn't know the system, and everything else with a list.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EdsmSystems {
+    One(EdsmSystem),
+    Many(Vec<EdsmSystem>),
+}
+
+/// Decodes a response into the systems in it that have coordinates.
+pub fn decode_systems(json: &str) -> serde_json::Result<Vec<System>> {
+    let systems = match serde_json::from_str(json)? {
+        EdsmSystems::One(system) => vec![system],
+        EdsmSystems::Many(systems) => systems,
+    };
+    Ok(systems
+        .into_iter()
+        .filter_map(|system| {
+            let coords = system.coords?;
+            Some(System {
+                name: system.name,
+                id64: system.id64,
+                x: coords.x,
+                y: coords.y,
+                z: coords.z,
+            })
+        })
+        .collect())
+}
+
+/// How long until the rate limit resets, if the response says it's used up.
+fn exhausted_reset(response: &ureq::Response) -> Option<Duration> {
+    let header = |name| response.header(name)?.trim().parse::<u64>().ok();
+    match (
+        header("x-rate-limit-remaining"),
+        header("x-rate-limit-reset"),
+    ) {
+        (Some(0), reset) => Some(Duration::from_secs(reset.unwrap_or(0))),
+        _ => None,
+    }
+}
+
+pub struct EdsmClient {
+    agent: ureq::Agent,
+    base_url: String,
+    limiter: Mutex<RateLimiter>,
+    retry: RetryPolicy,
+    #[cfg(feature = "fs")]
+    cache: Option<ResponseCache>,
+}
+
+impl EdsmClient {
+    /// A client for the EDSM at `base_url`, spacing requests `interval`
+    /// apart and giving up on any that take longer than `timeout`.
+    pub fn new(base_url: &str, interval: Duration, timeout: Duration) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            limiter: Mutex::new(RateLimiter::new(interval)),
+            retry: RetryPolicy::new(DEFAULT_RETRIES, DEFAULT_BACKOFF),
+            #[cfg(feature = "fs")]
+            cache: None,
+        }
+    }
+
+    /// Retries failed requests according to `retry`. The rate limit is
+    /// kept to on top of its waits.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Keeps responses in `cache`, and answers from it while they're
+    /// fresh. Requests are cached whole, so a batch of names is a hit only
+    /// if the same batch was asked for before.
+    #[cfg(feature = "fs")]
+    pub fn cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Waits for the rate limiter, then sends a request.
+    fn send(&self, request: &ureq::Request) -> Result<ureq::Response, HttpError> {
+        let mut limiter = self.limiter.lock().unwrap();
+        let delay = limiter.delay(Instant::now());
+        if !delay.is_zero() {
+            debug!(?delay, "waiting for EDSM rate limit");
+            thread::sleep(delay);
+        }
+        let result = request.clone().call();
+        let reset = match &result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => exhausted_reset(response),
+            Err(_) => None,
+        };
+        limiter.record(Instant::now(), reset);
+        Ok(result?)
+    }
+
+    /// GETs an api-v1 endpoint and decodes the systems in the response.
+    fn get(&self, endpoint: &str, query: &[(&str, String)]) -> Result<Vec<System>, HttpError> {
+        let url = format!("{}/api-v1/{}", self.base_url, endpoint);
+        let mut request = self
+            .agent
+            .get(&url)
+            .query("showId", "1")
+            .query("showCoordinates", "1");
+        for (param, value) in query {
+            request = request.query(param, value);
+        }
+        #[cfg(feature = "fs")]
+        if let Some(body) = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.get(request.url()))
+        {
+            return Ok(decode_systems(&body)?);
+        }
+        let response = self.retry.run(&url, || self.send(&request))?;
+        let body = response.into_string()?;
+        let systems = decode_systems(&body)?;
+        #[cfg(feature = "fs")]
+        if let Some(cache) = &self.cache {
+            // a cache that can't be written shouldn't fail a lookup that worked
+            if let Err(e) = cache.put(request.url(), &body) {
+                tracing::warn!(dir = %cache.dir().display(), "can't cache EDSM response: {}", e);
+            }
+        }
+        Ok(systems)
+    }
+
+    /// Looks up one system, None if EDSM doesn't know it or has no
+    /// coordinates for it.
+    pub fn system(&self, name: &str) -> Result<Option<System>, HttpError> {
+        let systems = self.get("system", &[("systemName", name.to_string())])?;
+        Ok(systems.into_iter().next())
+    }
+
+    /// Looks up many systems, a batch of names per request. Systems EDSM
+    /// doesn't know, or has no coordinates for, are left out.
+    pub fn systems(&self, names: &[&str]) -> Result<Vec<System>, HttpError> {
+        let mut systems = Vec::new();
+        for batch in names.chunks(NAMES_PER_REQUEST) {
+            let query: Vec<(&str, String)> = batch
+                .iter()
+                .map(|name| ("systemName[]", name.to_string()))
+                .collect();
+            systems.extend(self.get("systems", &query)?);
+        }
+        Ok(systems)
+    }
+
+    /// The systems between `min_radius` and `radius` ly of a point. EDSM
+    /// caps the radius at 100 ly.
+    pub fn sphere(
+        &self,
+        center: [f64; 3],
+        radius: f64,
+        min_radius: f64,
+    ) -> Result<Vec<System>, HttpError> {
+        let query = [
+            ("x", center[0].to_string()),
+            ("y", center[1].to_string()),
+            ("z", center[2].to_string()),
+            ("radius", radius.to_string()),
+            ("minRadius", min_radius.to_string()),
+        ];
+        self.get("sphere-systems", &query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::testing::{response, serve};
+
+    fn client(url: &str) -> EdsmClient {
+        EdsmClient::new(url, Duration::ZERO, Duration::from_secs(5))
+            .retry_policy(RetryPolicy::new(DEFAULT_RETRIES, Duration::ZERO))
+    }
+
+    #[test]
+    fn test_decode_systems() {
+        let sol = r#"{"name": "Sol", "id": 27, "id64": 10477373803,
+                      "coords": {"x": 0, "y": 0, "z": 0}}"#;
+        let systems = decode_systems(sol).unwrap();
+        assert_eq!(
+            systems,
+            [System {
+                name: "Sol".into(),
+                id64: Some(SystemId(10477373803)),
+                x: 0.,
+                y: 0.,
+                z: 0.,
+            }]
+        );
+        assert!(decode_systems("[]").unwrap().is_empty());
+        let many = r#"[{"name": "A", "coords": {"x": 1, "y": 2.5, "z": -3}},
+                       {"name": "B"}]"#;
+        let systems = decode_systems(many).unwrap();
+        assert_eq!(systems.len(), 1);
+        assert_eq!(
+            (systems[0].id64, systems[0].position()),
+            (None, [1., 2.5, -3.])
+        );
+        assert!(decode_systems("{\"coords\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_client_requests() {
+        let (url, server) = serve(vec![
+            response(
+                "200 OK",
+                "",
+                r#"{"name": "Sol", "coords": {"x": 0, "y": 0, "z": 0}}"#,
+            ),
+            response("200 OK", "", "[]"),
+            response(
+                "200 OK",
+                "",
+                r#"[{"name": "Sol", "coords": {"x": 0, "y": 0, "z": 0}},
+                    {"name": "Alpha Centauri", "coords": {"x": 3.03, "y": -0.09, "z": 3.16}}]"#,
+            ),
+            response("200 OK", "", "[]"),
+        ]);
+        let client = client(&url);
+        assert_eq!(client.system("Sol").unwrap().unwrap().name, "Sol");
+        assert_eq!(client.system("Nowhere").unwrap(), None);
+        let systems = client.systems(&["Sol", "Alpha Centauri"]).unwrap();
+        assert_eq!(systems.len(), 2);
+        assert!(client.sphere([0., 0., 0.], 10., 0.).unwrap().is_empty());
+
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests[0].0,
+            "GET /api-v1/system?showId=1&showCoordinates=1&systemName=Sol HTTP/1.1"
+        );
+        assert!(requests[2]
+            .0
+            .contains("systemName%5B%5D=Sol&systemName%5B%5D=Alpha+Centauri"));
+        assert!(requests[3].0.starts_with("GET /api-v1/sphere-systems?"));
+        assert!(requests[3].0.contains("&radius=10&minRadius=0"));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_client_cache() {
+        use crate::ttlcache::DEFAULT_TTL;
+
+        let dir = tempfile::tempdir().unwrap();
+        let sol = r#"{"name": "Sol", "coords": {"x": 0, "y": 0, "z": 0}}"#;
+        let (url, server) = serve(vec![
+            response("200 OK", "", sol),
+            response("200 OK", "", "[]"),
+            response("200 OK", "", "not json"),
+        ]);
+        let cache = ResponseCache::new(dir.path(), DEFAULT_TTL).unwrap();
+        let client = client(&url).cache(cache.clone());
+        assert_eq!(client.system("Sol").unwrap().unwrap().name, "Sol");
+        assert_eq!(client.system("Sol").unwrap().unwrap().name, "Sol");
+        assert_eq!(client.system("Nowhere").unwrap(), None);
+        assert_eq!(client.system("Nowhere").unwrap(), None);
+        // what EDSM couldn't answer isn't kept
+        assert!(client.system("Garbled").is_err());
+        assert_eq!(server.join().unwrap().len(), 3);
+        assert_eq!(cache.purge().unwrap(), 0);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_client_rate_limited() {
+        let (url, server) = serve(vec![
+            response(
+                "429 Too Many Requests",
+                "x-rate-limit-remaining: 0\r\nx-rate-limit-reset: 0\r\n",
+                "",
+            ),
+            response("200 OK", "", "[]"),
+            response("400 Bad Request", "", ""),
+            response("200 OK", "", "not json"),
+        ]);
+        let client = client(&url);
+        assert_eq!(client.system("Sol").unwrap(), None);
+        assert!(matches!(client.system("Sol"), Err(HttpError::Http(_))));
+        assert!(matches!(client.system("Sol"), Err(HttpError::Json(_))));
+        assert_eq!(server.join().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,133 @@
+//! CSV output of query results for spreadsheets, and the JSON writing the
+//! JSON export formats share.
+//!
+//! Fields are quoted only when they need it (they contain a comma, quote or
+//! line break), with embedded quotes doubled and CRLF line endings, as in
+//! RFC 4180 and Python's csv module defaults.
+
+use std::fmt::{Display, Write};
+
+use serde::Serialize;
+
+use crate::market::StationItem;
+
+/// Column names for StationItem rows, matching the TD table.
+pub const STATION_ITEM_HEADER: [&str; 9] = [
+    "station_id",
+    "item_id",
+    "demand_price",
+    "demand_units",
+    "demand_level",
+    "supply_price",
+    "supply_units",
+    "supply_level",
+    "modified",
+];
+
+/// Builds a CSV document a row at a time.
+#[derive(Default)]
+pub struct CsvWriter {
+    out: String,
+}
+
+impl CsvWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_row<I, T>(&mut self, fields: I)
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        for (idx, field) in fields.into_iter().enumerate() {
+            if idx > 0 {
+                self.out.push(',');
+            }
+            let field = field.to_string();
+            if field.contains([',', '"', '\r', '\n']) {
+                write!(self.out, "\"{}\"", field.replace('"', "\"\"")).unwrap();
+            } else {
+                self.out.push_str(&field);
+            }
+        }
+        self.out.push_str("\r\n");
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+/// Listings (a price list, or the sellers or buyers of an item) as CSV with
+/// a header row.
+pub fn listings_csv<'a>(items: impl IntoIterator<Item = &'a StationItem>) -> String {
+    let mut csv = CsvWriter::new();
+    csv.write_row(STATION_ITEM_HEADER);
+    for item in items {
+        csv.write_row([
+            item.station_id.0 as i64,
+            item.item_id.0 as i64,
+            item.demand_price as i64,
+            item.demand_units,
+            item.demand_level as i64,
+            item.supply_price as i64,
+            item.supply_units,
+            item.supply_level as i64,
+            item.modified,
+        ]);
+    }
+    csv.finish()
+}
+
+/// Serializes a document to JSON, pretty-printed with the given indent or
+/// compact if None.
+pub fn to_json<T: Serialize>(document: &T, indent: Option<usize>) -> serde_json::Result<String> {
+    match indent {
+        None => serde_json::to_string(document),
+        Some(width) => {
+            let indent = " ".repeat(width);
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            let mut out = Vec::new();
+            let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+            document.serialize(&mut serializer)?;
+            Ok(String::from_utf8(out).expect("serde_json writes UTF-8"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{ItemId, StationId};
+
+    #[test]
+    fn test_csv_quoting() {
+        let mut csv = CsvWriter::new();
+        csv.write_row(["plain", "with,comma", "say \"hi\"", "two\nlines", ""]);
+        csv.write_row([1, 2]);
+        assert_eq!(
+            csv.finish(),
+            "plain,\"with,comma\",\"say \"\"hi\"\"\",\"two\nlines\",\r\n1,2\r\n"
+        );
+    }
+
+    #[test]
+    fn test_listings_csv() {
+        let item = StationItem {
+            station_id: StationId(7),
+            item_id: ItemId(3),
+            supply_price: 450,
+            supply_units: 1000,
+            supply_level: -1,
+            modified: 1700000000,
+            ..Default::default()
+        };
+        let csv = listings_csv([&item]);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines[0], STATION_ITEM_HEADER.join(","));
+        assert_eq!(lines[1], "7,3,0,0,0,450,1000,-1,1700000000");
+        assert_eq!(lines[2], "");
+        assert_eq!(listings_csv([]), STATION_ITEM_HEADER.join(",") + "\r\n");
+    }
+}
@@ -0,0 +1,196 @@
+//! Frame shift drive jump range and fuel use.
+//!
+//! A drive burns `linear * (distance * mass / optimal_mass) ^ power / 1000`
+//! tons of fuel for a jump, up to its maximum fuel per jump, so the range is
+//! that formula solved for distance. A Guardian FSD booster adds a fixed
+//! number of ly to the range, with fuel use scaled so that a maximum-range
+//! jump still takes the maximum fuel. Boost multipliers (FSD injection,
+//! white dwarf or neutron supercharge) multiply the range of a single jump.
+
+/// Supercharge multiplier from a neutron star's jet cone.
+pub const NEUTRON_BOOST: f64 = 4.0;
+/// Supercharge multiplier from a white dwarf's jet cone.
+pub const WHITE_DWARF_BOOST: f64 = 1.5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fsd {
+    /// Ship mass in tons at which the drive is most efficient.
+    pub optimal_mass: f64,
+    pub max_fuel_per_jump: f64,
+    /// The rating constant (12 for A rated drives).
+    pub linear_constant: f64,
+    /// The class constant (2.45 for class 5 drives).
+    pub power_constant: f64,
+}
+
+impl Fsd {
+    /// The linear constant for a drive's rating letter.
+    pub fn linear_constant_for(rating: char) -> Option<f64> {
+        match rating.to_ascii_uppercase() {
+            'A' => Some(12.),
+            'B' => Some(10.),
+            'C' => Some(8.),
+            'D' => Some(10.),
+            'E' => Some(11.),
+            _ => None,
+        }
+    }
+
+    /// The power constant for a drive's size class.
+    pub fn power_constant_for(class: u8) -> Option<f64> {
+        match class {
+            2..=7 => Some(2.0 + 0.15 * (class - 2) as f64),
+            _ => None,
+        }
+    }
+
+    /// Distance in ly a jump burning `fuel` tons covers at a given mass,
+    /// before any booster.
+    pub fn base_range(&self, mass: f64, fuel: f64) -> f64 {
+        let fuel = fuel.clamp(0., self.max_fuel_per_jump);
+        (self.optimal_mass / mass)
+            * (1000. * fuel / self.linear_constant).powf(1. / self.power_constant)
+    }
+
+    /// Fuel in tons to jump a distance at a given mass, before any booster.
+    pub fn base_fuel(&self, mass: f64, distance: f64) -> f64 {
+        self.linear_constant * (distance * mass / self.optimal_mass).powf(self.power_constant)
+            / 1000.
+    }
+}
+
+/// The parts of a ship's build that decide how far it jumps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ship {
+    /// Hull and modules, without fuel or cargo.
+    pub unladen_mass: f64,
+    pub tank: f64,
+    pub cargo: f64,
+    pub fsd: Fsd,
+    /// ly added by a Guardian FSD booster, 0 if none is fitted.
+    pub guardian_boost: f64,
+}
+
+impl Ship {
+    pub fn mass(&self, fuel: f64) -> f64 {
+        self.unladen_mass + self.cargo + fuel
+    }
+
+    /// Range of a single jump with `fuel` tons aboard, multiplied by boost
+    /// (1.0 for an ordinary jump).
+    pub fn jump_range(&self, fuel: f64, boost: f64) -> f64 {
+        let range = self.fsd.base_range(self.mass(fuel), fuel);
+        if range <= 0. {
+            return 0.;
+        }
+        (range + self.guardian_boost) * boost
+    }
+
+    /// Range of a jump with a full tank, the usual "jump range" figure.
+    pub fn max_jump_range(&self) -> f64 {
+        self.jump_range(self.tank, 1.)
+    }
+
+    /// Fuel in tons needed to jump a distance carrying `fuel` tons, or None
+    /// if the distance is out of range.
+    pub fn fuel_for_jump(&self, distance: f64, fuel: f64) -> Option<f64> {
+        let mass = self.mass(fuel);
+        let base = self.fsd.base_range(mass, self.fsd.max_fuel_per_jump);
+        if distance > base + self.guardian_boost {
+            return None;
+        }
+        // With a booster, scale the distance down onto the unboosted curve.
+        let effective = distance * base / (base + self.guardian_boost);
+        Some(self.fsd.base_fuel(mass, effective))
+    }
+}
+
+impl crate::router::FuelModel for Ship {
+    fn tank(&self) -> f64 {
+        self.tank
+    }
+
+    /// Uses the mass with a full tank, which slightly overestimates the fuel
+    /// used as the tank empties and so errs on the side of not stranding.
+    fn fuel_for(&self, distance: f64) -> Option<f64> {
+        self.fuel_for_jump(distance, self.tank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 5A drive in a light explorer build.
+    fn explorer() -> Ship {
+        Ship {
+            unladen_mass: 280.,
+            tank: 32.,
+            cargo: 0.,
+            fsd: Fsd {
+                optimal_mass: 1050.,
+                max_fuel_per_jump: 5.,
+                linear_constant: Fsd::linear_constant_for('A').unwrap(),
+                power_constant: Fsd::power_constant_for(5).unwrap(),
+            },
+            guardian_boost: 0.,
+        }
+    }
+
+    #[test]
+    fn test_fsd_constants() {
+        assert_eq!(Fsd::linear_constant_for('a'), Some(12.));
+        assert_eq!(Fsd::linear_constant_for('C'), Some(8.));
+        assert_eq!(Fsd::linear_constant_for('F'), None);
+        assert!((Fsd::power_constant_for(2).unwrap() - 2.0).abs() < 1e-9);
+        assert!((Fsd::power_constant_for(5).unwrap() - 2.45).abs() < 1e-9);
+        assert!((Fsd::power_constant_for(7).unwrap() - 2.75).abs() < 1e-9);
+        assert_eq!(Fsd::power_constant_for(8), None);
+    }
+
+    #[test]
+    fn test_jump_range() {
+        let ship = explorer();
+        let range = ship.max_jump_range();
+        // 1050 / 312 * (5000 / 12) ^ (1 / 2.45)
+        assert!((range - 39.48).abs() < 0.01, "{}", range);
+        // lighter tank, longer jump; cargo shortens it
+        assert!(ship.jump_range(10., 1.) > range);
+        let laden = Ship {
+            cargo: 100.,
+            ..explorer()
+        };
+        assert!(laden.max_jump_range() < range);
+        assert_eq!(ship.jump_range(0., 1.), 0.);
+        assert!((ship.jump_range(32., NEUTRON_BOOST) - 4. * range).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_fuel_for_jump() {
+        let ship = explorer();
+        let range = ship.max_jump_range();
+        let full = ship.fuel_for_jump(range, 32.).unwrap();
+        assert!((full - 5.).abs() < 1e-9);
+        let half = ship.fuel_for_jump(range / 2., 32.).unwrap();
+        assert!(half < 2.5);
+        assert!(ship.fuel_for_jump(range + 0.1, 32.).is_none());
+    }
+
+    #[test]
+    fn test_guardian_boost() {
+        let boosted = Ship {
+            guardian_boost: 10.5,
+            ..explorer()
+        };
+        let range = boosted.max_jump_range();
+        assert!((range - explorer().max_jump_range() - 10.5).abs() < 1e-9);
+        let fuel = boosted.fuel_for_jump(range, 32.).unwrap();
+        assert!((fuel - 5.).abs() < 1e-9);
+        assert!(
+            boosted
+                .fuel_for_jump(explorer().max_jump_range(), 32.)
+                .unwrap()
+                < 5.
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Which systems can reach which in a single jump.
+//!
+//! The graph is stored in compressed sparse row (CSR) form: the neighbours of
+//! node n are `targets[offsets[n]..offsets[n + 1]]`, with the matching
+//! distances alongside. Nodes are indexes into the positions the graph was
+//! built from; mapping those back to systems is up to the caller.
+
+use std::time::Duration;
+
+use rayon::prelude::*;
+use tracing::{debug, info};
+
+use crate::clock::Stopwatch;
+use crate::grid::GridIndex;
+use crate::metrics;
+
+/// Figures from building a graph, for sizing and progress reporting.
+#[derive(Clone, Debug, Default)]
+pub struct GraphStats {
+    pub nodes: usize,
+    pub edges: usize,
+    pub max_degree: usize,
+    /// Number of occupied stellar grid cells that were expanded.
+    pub cells: usize,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct JumpGraph {
+    /// Start of each node's neighbours in targets; one more entry than nodes.
+    offsets: Vec<u32>,
+    targets: Vec<u32>,
+    /// Distance in ly to each target, f32 to keep the graph compact.
+    distances: Vec<f32>,
+    pub jump_range: f64,
+    pub stats: GraphStats,
+}
+
+type Neighbours = Vec<(u32, f32)>;
+
+impl JumpGraph {
+    /// Builds the graph of every pair of positions no more than jump_range
+    /// apart. Each node's neighbours are ordered by index.
+    pub fn build(positions: &[[f64; 3]], jump_range: f64) -> Self {
+        Self::build_boosted(positions, jump_range, &[])
+    }
+
+    /// Builds the graph with the range of jumps leaving node n multiplied by
+    /// boosts[n], e.g. for neutron star supercharges. Nodes without a boost
+    /// (including all of them if boosts is empty) use jump_range, so edges
+    /// leaving boosted nodes may have no edge back.
+    ///
+    /// Grid cells are expanded in parallel on the rayon thread pool, then the
+    /// per-node neighbour lists are laid out into the CSR arrays in order.
+    #[tracing::instrument(skip(positions, boosts), fields(nodes = positions.len()))]
+    pub fn build_boosted(positions: &[[f64; 3]], jump_range: f64, boosts: &[f64]) -> Self {
+        let stopwatch = Stopwatch::start();
+        let _timer = metrics::time_stage("graph_build");
+        let grid = GridIndex::new(positions);
+        debug!(cells = grid.cell_count(), "indexed positions");
+
+        let expanded: Vec<Vec<(u32, Neighbours)>> = grid
+            .cells()
+            .par_iter()
+            .map(|(_, nodes)| {
+                nodes
+                    .iter()
+                    .map(|&node| {
+                        let boost = boosts.get(node as usize).copied().unwrap_or(1.);
+                        let mut neighbours: Neighbours = grid
+                            .within(grid.position(node), jump_range * boost)
+                            .into_iter()
+                            .filter(|(target, _)| *target != node)
+                            .map(|(target, distance)| (target, distance as f32))
+                            .collect();
+                        neighbours.sort_unstable_by_key(|(target, _)| *target);
+                        (node, neighbours)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut per_node: Vec<Neighbours> = vec![Vec::new(); positions.len()];
+        for (node, neighbours) in expanded.into_iter().flatten() {
+            per_node[node as usize] = neighbours;
+        }
+
+        let edges = per_node.iter().map(Vec::len).sum();
+        let mut graph = Self {
+            offsets: Vec::with_capacity(positions.len() + 1),
+            targets: Vec::with_capacity(edges),
+            distances: Vec::with_capacity(edges),
+            jump_range,
+            ..Default::default()
+        };
+        graph.offsets.push(0);
+        for neighbours in per_node {
+            for (target, distance) in neighbours {
+                graph.targets.push(target);
+                graph.distances.push(distance);
+            }
+            graph.offsets.push(graph.targets.len() as u32);
+        }
+
+        graph.stats = GraphStats {
+            nodes: graph.node_count(),
+            edges: graph.edge_count(),
+            max_degree: (0..graph.node_count() as u32)
+                .map(|node| graph.degree(node))
+                .max()
+                .unwrap_or(0),
+            cells: grid.cell_count(),
+            elapsed: stopwatch.elapsed(),
+        };
+        info!(
+            edges = graph.stats.edges,
+            max_degree = graph.stats.max_degree,
+            elapsed_ms = graph.stats.elapsed.as_millis() as u64,
+            "built jump graph"
+        );
+        graph
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    /// Number of directed edges; unboosted jumps appear once in each direction.
+    pub fn edge_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    fn span(&self, node: u32) -> std::ops::Range<usize> {
+        let node = node as usize;
+        if node >= self.node_count() {
+            return 0..0;
+        }
+        self.offsets[node] as usize..self.offsets[node + 1] as usize
+    }
+
+    /// Nodes reachable from a node in one jump.
+    pub fn neighbours(&self, node: u32) -> &[u32] {
+        &self.targets[self.span(node)]
+    }
+
+    /// Distances matching `neighbours(node)`.
+    pub fn distances(&self, node: u32) -> &[f32] {
+        &self.distances[self.span(node)]
+    }
+
+    pub fn degree(&self, node: u32) -> usize {
+        self.span(node).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::distance;
+
+    fn line() -> Vec<[f64; 3]> {
+        vec![[0., 0., 0.], [10., 0., 0.], [20., 0., 0.], [45., 0., 0.]]
+    }
+
+    #[test]
+    fn test_graph_build() {
+        let graph = JumpGraph::build(&line(), 15.);
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(graph.neighbours(0), &[1]);
+        assert_eq!(graph.neighbours(1), &[0, 2]);
+        assert_eq!(graph.distances(1), &[10., 10.]);
+        assert!(graph.neighbours(3).is_empty());
+        assert!(graph.neighbours(99).is_empty());
+        assert_eq!(graph.edge_count(), 4);
+        assert_eq!(graph.degree(1), 2);
+        assert_eq!(graph.stats.nodes, 4);
+        assert_eq!(graph.stats.edges, 4);
+        assert_eq!(graph.stats.max_degree, 2);
+        assert_eq!(graph.stats.cells, 2);
+
+        let graph = JumpGraph::build(&line(), 25.);
+        assert_eq!(graph.neighbours(0), &[1, 2]);
+        assert_eq!(graph.neighbours(3), &[2]);
+        assert_eq!(JumpGraph::build(&[], 25.).node_count(), 0);
+    }
+
+    #[test]
+    fn test_graph_boosted() {
+        let graph = JumpGraph::build_boosted(&line(), 15., &[1., 1., 4.]);
+        assert_eq!(graph.neighbours(2), &[0, 1, 3]);
+        // the boost only applies leaving the boosted node
+        assert_eq!(graph.neighbours(3), &[] as &[u32]);
+        assert_eq!(graph.neighbours(0), &[1]);
+        assert_eq!(graph.stats.max_degree, 3);
+    }
+
+    #[test]
+    fn test_graph_matches_brute_force() {
+        let positions: Vec<[f64; 3]> = (0..200)
+            .map(|i| {
+                let f = i as f64;
+                [(f * 13.7) % 150., (f * 5.3) % 30., (f * 29.1) % 150.]
+            })
+            .collect();
+        let graph = JumpGraph::build(&positions, 22.5);
+        for (node, pos) in positions.iter().enumerate() {
+            let expected: Vec<u32> = (0..positions.len() as u32)
+                .filter(|t| *t as usize != node && distance(*pos, positions[*t as usize]) <= 22.5)
+                .collect();
+            assert_eq!(graph.neighbours(node as u32), expected.as_slice());
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Points bucketed by grid cell, for "everything within r ly" lookups
+//! without comparing against every point.
+//!
+//! Cells are the stellar grid's 32ly unless built `with_cell_size`;
+//! `grid_stats` shows how well a size suits a dataset. Too large and the
+//! dense cells around Sol hold thousands of points each, too small and most
+//! of the cells a search visits are empty.
+
+use std::collections::HashMap;
+
+use crate::rusty::{grid_keys, pack_grid_key, unpack_grid_key, GridCells};
+use crate::spatial::{widen, Coordinate, SpatialIndex};
+
+/// The stellar grid's cell size in ly, which `GridIndex::new` uses.
+pub const DEFAULT_CELL_SIZE: f64 = 32.;
+
+/// How evenly points spread over a GridIndex's cells.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GridStats {
+    pub cell_size: f64,
+    pub points: usize,
+    /// Number of occupied cells.
+    pub cells: usize,
+    /// histogram[i] is the number of cells holding 2^i to 2^(i+1) - 1
+    /// points.
+    pub histogram: Vec<usize>,
+    pub max_occupancy: usize,
+    /// Mean points per occupied cell.
+    pub mean_occupancy: f64,
+    /// The share of the cells in the occupied cells' bounding box that are
+    /// empty.
+    pub empty_ratio: f64,
+}
+
+/// Positions' components may be f64 or f32, see `Coordinate`.
+pub struct GridIndex<'a, T: Coordinate = f64> {
+    positions: &'a [[T; 3]],
+    cell_size: f64,
+    /// grid key -> indexes into positions.
+    cells: HashMap<u64, Vec<u32>>,
+}
+
+impl<'a, T: Coordinate> GridIndex<'a, T> {
+    pub fn new(positions: &'a [[T; 3]]) -> Self {
+        Self::with_cell_size(positions, DEFAULT_CELL_SIZE)
+    }
+
+    /// Buckets positions into cells of `cell_size` ly a side. Cell indexes
+    /// are 16-bit, so covering the whole galaxy needs cells of 4ly or more.
+    pub fn with_cell_size(positions: &'a [[T; 3]], cell_size: f64) -> Self {
+        let mut cells: HashMap<u64, Vec<u32>> = HashMap::new();
+        for (idx, key) in grid_keys(positions, cell_size).into_iter().enumerate() {
+            cells.entry(key).or_default().push(idx as u32);
+        }
+        Self {
+            positions,
+            cell_size,
+            cells,
+        }
+    }
+
+    pub fn cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    /// Number of occupied cells.
+    pub fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// The occupied cells and the indexes of the points in each.
+    pub fn cells(&self) -> &HashMap<u64, Vec<u32>> {
+        &self.cells
+    }
+
+    pub fn position(&self, idx: u32) -> [f64; 3] {
+        widen(self.positions[idx as usize])
+    }
+
+    /// Indexes of the points in cells that come within range of a position.
+    /// Some will be further than range away.
+    pub fn candidates(&self, pos: [f64; 3], range: f64) -> Vec<u32> {
+        GridCells::in_sphere(pos, range, self.cell_size)
+            .filter_map(|(gx, gy, gz)| self.cells.get(&pack_grid_key(gx, gy, gz)))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Indexes and distances of the points within range of a position.
+    pub fn within(&self, pos: [f64; 3], range: f64) -> Vec<(u32, f64)> {
+        self.candidates(pos, range)
+            .into_iter()
+            .filter_map(|idx| {
+                let distance = distance(pos, self.position(idx));
+                (distance <= range).then_some((idx, distance))
+            })
+            .collect()
+    }
+
+    /// Occupancy figures for tuning the cell size.
+    pub fn grid_stats(&self) -> GridStats {
+        let mut histogram = Vec::new();
+        let (mut min, mut max) = ([i16::MAX; 3], [i16::MIN; 3]);
+        for (key, points) in &self.cells {
+            let bucket = points.len().ilog2() as usize;
+            if histogram.len() <= bucket {
+                histogram.resize(bucket + 1, 0);
+            }
+            histogram[bucket] += 1;
+            let (gx, gy, gz) = unpack_grid_key(*key);
+            for (axis, index) in [gx, gy, gz].into_iter().enumerate() {
+                min[axis] = min[axis].min(index);
+                max[axis] = max[axis].max(index);
+            }
+        }
+        let bounding: f64 = (0..3)
+            .map(|axis| (max[axis] as f64 - min[axis] as f64 + 1.).max(0.))
+            .product();
+        let cells = self.cells.len();
+        GridStats {
+            cell_size: self.cell_size,
+            points: self.positions.len(),
+            cells,
+            histogram,
+            max_occupancy: self.cells.values().map(Vec::len).max().unwrap_or(0),
+            mean_occupancy: if cells > 0 {
+                self.positions.len() as f64 / cells as f64
+            } else {
+                0.
+            },
+            empty_ratio: if cells > 0 {
+                1. - cells as f64 / bounding
+            } else {
+                0.
+            },
+        }
+    }
+}
+
+impl<T: Coordinate> SpatialIndex for GridIndex<'_, T> {
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn position(&self, idx: u32) -> [f64; 3] {
+        GridIndex::position(self, idx)
+    }
+
+    fn candidates(&self, pos: [f64; 3], range: f64) -> Vec<u32> {
+        GridIndex::candidates(self, pos, range)
+    }
+
+    fn within(&self, pos: [f64; 3], range: f64) -> Vec<(u32, f64)> {
+        GridIndex::within(self, pos, range)
+    }
+}
+
+/// The key of the cell of a given size containing a position, packed as
+/// by pack_grid_key; with 32ly cells it's the stellar grid key.
+pub fn cell_key(pos: [f64; 3], cell_size: f64) -> u64 {
+    let [gx, gy, gz] = pos.map(|component| (component / cell_size).floor() as i16);
+    pack_grid_key(gx, gy, gz)
+}
+
+/// Straight-line distance between two positions in ly.
+pub fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let (dx, dy, dz) = (a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+    (dx * dx + dy * dy + dz * dz).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rusty::stellar_grid_key;
+
+    #[test]
+    fn test_grid_within() {
+        let positions = [
+            [0., 0., 0.],
+            [10., 0., 0.],
+            [0., 40., 0.],
+            [-31., 0., -31.],
+            [500., 500., 500.],
+        ];
+        let grid = GridIndex::new(&positions);
+        assert_eq!(grid.cell_count(), 4);
+
+        let mut near: Vec<u32> = grid.within([0.; 3], 45.).iter().map(|n| n.0).collect();
+        near.sort_unstable();
+        assert_eq!(near, vec![0, 1, 2, 3]);
+
+        let mut near: Vec<u32> = grid.within([0.; 3], 40.).iter().map(|n| n.0).collect();
+        near.sort_unstable();
+        assert_eq!(near, vec![0, 1, 2]);
+        assert!(grid.within([1000.; 3], 100.).is_empty());
+    }
+
+    #[test]
+    fn test_cell_key() {
+        for pos in [[0.; 3], [-1., 33., 64.], [-40213.5, 1200.25, 65000.]] {
+            assert_eq!(cell_key(pos, 32.), stellar_grid_key(pos[0], pos[1], pos[2]));
+        }
+        assert_eq!(cell_key([9., 0., -1.], 4.), pack_grid_key(2, 0, -1));
+    }
+
+    #[test]
+    fn test_grid_stats() {
+        let positions = [
+            [0., 0., 0.],
+            [1., 1., 1.],
+            [2., 2., 2.],
+            [3., 3., 3.],
+            [10., 0., 0.],
+            [100., 0., 0.],
+        ];
+        let stats = GridIndex::new(&positions).grid_stats();
+        assert_eq!(stats.points, 6);
+        assert_eq!(stats.cells, 2);
+        assert_eq!(stats.histogram, vec![1, 0, 1]);
+        assert_eq!(stats.max_occupancy, 5);
+        assert_eq!(stats.mean_occupancy, 3.);
+        // cells 0 and 3 along x: 2 of 4 occupied
+        assert_eq!(stats.empty_ratio, 0.5);
+
+        let stats = GridIndex::with_cell_size(&positions, 4.).grid_stats();
+        assert_eq!((stats.cell_size, stats.cells), (4., 3));
+        assert_eq!(stats.histogram, vec![2, 0, 1]);
+        assert_eq!(
+            GridIndex::<f64>::new(&[]).grid_stats().histogram,
+            Vec::<usize>::new()
+        );
+    }
+
+    #[test]
+    fn test_grid_within_matches_scan() {
+        let positions: Vec<[f64; 3]> = (0..300)
+            .map(|i| {
+                let f = i as f64;
+                [
+                    (f * 7.3) % 200. - 100.,
+                    (f * 3.1) % 50. - 25.,
+                    (f * 11.7) % 200. - 100.,
+                ]
+            })
+            .collect();
+        for cell_size in [4., 32., 100.] {
+            let grid = GridIndex::with_cell_size(&positions, cell_size);
+            for range in [5., 20., 75.] {
+                for origin in positions.iter().step_by(17) {
+                    let mut found: Vec<u32> =
+                        grid.within(*origin, range).iter().map(|n| n.0).collect();
+                    found.sort_unstable();
+                    let expected: Vec<u32> = (0..positions.len() as u32)
+                        .filter(|i| distance(*origin, positions[*i as usize]) <= range)
+                        .collect();
+                    assert_eq!(found, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_grid_f32_positions() {
+        let wide = [[-31.9, 0., 32.], [100.25, -7.5, 3.], [0.1, 0.2, 0.3]];
+        let narrow = wide.map(|pos| pos.map(|component| component as f32));
+        let grid = GridIndex::new(&narrow);
+        // the f32 positions are indexed at their own values, not the f64s
+        // they were rounded from
+        assert_eq!(grid.position(2), widen(narrow[2]));
+        assert_ne!(grid.position(2), wide[2]);
+        assert_eq!(grid.cell_count(), GridIndex::new(&wide).cell_count());
+        let mut near: Vec<u32> = grid.within([0.; 3], 50.).iter().map(|n| n.0).collect();
+        near.sort_unstable();
+        assert_eq!(near, vec![0, 2]);
+    }
+}
@@ -0,0 +1,163 @@
+//! Reading gzip-compressed dumps, with a damaged file reported as damaged.
+//!
+//! A download cut short leaves a stream without its end, and bad memory,
+//! disks or transfers flip bits that the stream's CRC-32 catches. Left to
+//! the decompressor, the first surfaces as "unexpected end of file" or as a
+//! file that simply stops, and the second as "corrupt deflate stream", far
+//! into an import. Here both become InvalidData errors saying "file is
+//! truncated at byte N" or "file is corrupt near byte N", N counting bytes
+//! of the compressed file. Concatenated members, as `cat a.gz b.gz` makes,
+//! read as one stream.
+
+use std::io::{self, BufRead, Read};
+
+use flate2::bufread::MultiGzDecoder;
+
+/// The first two bytes of every gzip member.
+pub const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// True if data starting with `header` is gzip-compressed.
+pub fn is_gzip(header: &[u8]) -> bool {
+    header.starts_with(&MAGIC)
+}
+
+/// Counts the compressed bytes the decoder takes, and notices the end.
+struct Counted<R> {
+    inner: R,
+    consumed: u64,
+    at_end: bool,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.consumed += n as u64;
+        self.at_end |= n == 0 && !buf.is_empty();
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Counted<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let buf = self.inner.fill_buf()?;
+        self.at_end |= buf.is_empty();
+        Ok(buf)
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.consumed += amount as u64;
+        self.inner.consume(amount);
+    }
+}
+
+/// Decompresses a gzip stream, failing on a truncated or corrupt one. The
+/// damage is reported once, after which the stream reads as ended.
+pub struct GzipReader<R: BufRead> {
+    decoder: MultiGzDecoder<Counted<R>>,
+    failed: bool,
+}
+
+impl<R: BufRead> GzipReader<R> {
+    pub fn new(inner: R) -> Self {
+        let counted = Counted {
+            inner,
+            consumed: 0,
+            at_end: false,
+        };
+        Self {
+            decoder: MultiGzDecoder::new(counted),
+            failed: false,
+        }
+    }
+
+    fn damaged(&mut self, e: io::Error) -> io::Error {
+        self.failed = true;
+        let counted = self.decoder.get_ref();
+        let message = if counted.at_end || e.kind() == io::ErrorKind::UnexpectedEof {
+            format!("file is truncated at byte {}", counted.consumed)
+        } else {
+            format!("file is corrupt near byte {}: {}", counted.consumed, e)
+        };
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+}
+
+impl<R: BufRead> Read for GzipReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.failed {
+            return Ok(0);
+        }
+        self.decoder.read(buf).map_err(|e| self.damaged(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn read_all(compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        GzipReader::new(compressed).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_gzip_reader() {
+        let data: Vec<u8> = (0..200_000u32)
+            .flat_map(|i| format!("line {}\n", i * 7919 % 100_003).into_bytes())
+            .collect();
+        let compressed = compress(&data);
+        assert!(is_gzip(&compressed));
+        assert!(!is_gzip(b"@ SOL"));
+        assert_eq!(read_all(&compressed).unwrap(), data);
+        // concatenated members read as one
+        let twice = [compressed.clone(), compressed.clone()].concat();
+        assert_eq!(read_all(&twice).unwrap().len(), data.len() * 2);
+
+        for cut in [
+            compressed.len() / 2,
+            compressed.len() - 4,
+            compressed.len() - 1,
+        ] {
+            let err = read_all(&compressed[..cut]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(
+                err.to_string(),
+                format!("file is truncated at byte {}", cut)
+            );
+        }
+        let mut reader = GzipReader::new(&compressed[..100]);
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        assert_eq!(reader.read(&mut [0; 16]).unwrap(), 0);
+
+        // a flipped bit in the data fails the CRC, or the deflate stream
+        let mut corrupt = compressed.clone();
+        let middle = corrupt.len() / 2;
+        corrupt[middle] ^= 0x10;
+        let err = read_all(&corrupt).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string().starts_with("file is corrupt near byte"),
+            "{}",
+            err
+        );
+        let mut bad_crc = compressed;
+        let crc = bad_crc.len() - 8;
+        bad_crc[crc] ^= 1;
+        let err = read_all(&bad_crc).unwrap_err();
+        assert!(
+            err.to_string().starts_with("file is corrupt near byte"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Hashes that must come out the same in every build and on every platform,
+//! for keys and fingerprints that are written to files.
+
+use crate::names::canonical_name;
+
+/// FNV-1a's starting hash.
+pub const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a, which is stable across builds and platforms unlike the std
+/// hashers.
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// splitmix64's finalizer, spreading FNV's weak low bits over the word.
+pub fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// A 64-bit hash of a system or station name, defined so that it can be
+/// reproduced anywhere: FNV-1a over the UTF-8 bytes of the name's
+/// canonical form (`names::canonical_name`), then `mix`.
+///
+/// The values are part of the crate's interface and don't change between
+/// versions, so caches keyed on them stay valid across upgrades. The one
+/// exception is a name whose canonical form changes, which only a newer
+/// Unicode version's case folding can do.
+pub fn name_hash(name: &str) -> u64 {
+    mix(fnv1a(FNV_OFFSET_BASIS, canonical_name(name).as_bytes()))
+}
+
+/// `name_hash` of a station's full name: the canonical system and station
+/// names joined by "/".
+pub fn station_name_hash(system: &str, station: &str) -> u64 {
+    let hash = fnv1a(FNV_OFFSET_BASIS, canonical_name(system).as_bytes());
+    mix(fnv1a(fnv1a(hash, b"/"), canonical_name(station).as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_hash_is_stable() {
+        assert_eq!(name_hash("Sol"), name_hash("  SOL "));
+        assert_eq!(
+            station_name_hash("Sol", "Abraham Lincoln"),
+            name_hash("sol/abraham lincoln")
+        );
+        assert_ne!(name_hash("Sol"), name_hash("Lave"));
+        // pinned: caches out there are keyed on these
+        assert_eq!(name_hash(""), 0xf52a_15e9_a9b5_e89b);
+        assert_eq!(name_hash("Sol"), 0x9d68_ad13_4d4b_d030);
+        assert_eq!(
+            station_name_hash("Sol", "Abraham Lincoln"),
+            0x37d4_cd96_aedf_d319
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! HyperLogLog counters, estimating how many distinct values a stream holds
+//! in a few kilobytes however long it runs.
+//!
+//! Each value's hash picks one of 2^precision registers, which keeps the
+//! longest run of leading zeros seen in the rest of the hash; the harmonic
+//! mean of the registers estimates the count. Small counts, where many
+//! registers are still empty, use linear counting instead. The standard
+//! error is about 1.04 / sqrt(2^precision): 1.6% at the default precision.
+
+use crate::hash::{fnv1a, mix, FNV_OFFSET_BASIS};
+
+/// Default precision: 4096 registers.
+pub const DEFAULT_PRECISION: u8 = 12;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+    precision: u8,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_PRECISION)
+    }
+}
+
+impl HyperLogLog {
+    /// A counter with 2^precision registers; precision is clamped to 4..=16.
+    pub fn new(precision: u8) -> Self {
+        let precision = precision.clamp(4, 16);
+        Self {
+            registers: vec![0; 1 << precision],
+            precision,
+        }
+    }
+
+    pub fn insert_hash(&mut self, hash: u64) {
+        let hash = mix(hash);
+        let index = (hash >> (64 - self.precision)) as usize;
+        let rest = hash << self.precision;
+        let rank = (rest.leading_zeros() + 1).min(64 - self.precision as u32 + 1) as u8;
+        let register = &mut self.registers[index];
+        *register = (*register).max(rank);
+    }
+
+    pub fn insert(&mut self, value: &str) {
+        self.insert_hash(fnv1a(FNV_OFFSET_BASIS, value.as_bytes()));
+    }
+
+    /// The estimated number of distinct values inserted.
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&register| 2f64.powi(-(register as i32)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self
+            .registers
+            .iter()
+            .filter(|&&register| register == 0)
+            .count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn within(estimate: u64, actual: u64, tolerance: f64) -> bool {
+        (estimate as f64 - actual as f64).abs() <= actual as f64 * tolerance
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut counter = HyperLogLog::default();
+        assert_eq!(counter.estimate(), 0);
+        for round in 0..3 {
+            for i in 0..50 {
+                counter.insert(&format!("system {}", i));
+            }
+            assert_eq!(counter.estimate(), 50, "round {}", round);
+        }
+        for i in 0..100_000 {
+            counter.insert(&format!("station {}", i));
+        }
+        let estimate = counter.estimate();
+        assert!(within(estimate, 100_050, 0.05), "{}", estimate);
+
+        let mut coarse = HyperLogLog::new(1);
+        assert_eq!(coarse.registers.len(), 16);
+        for i in 0..5000 {
+            coarse.insert(&i.to_string());
+        }
+        assert!(
+            within(coarse.estimate(), 5000, 0.6),
+            "{}",
+            coarse.estimate()
+        );
+    }
+}
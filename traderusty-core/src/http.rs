@@ -0,0 +1,388 @@
+//! What the online API clients share: their error type, spacing requests
+//! out to keep to a rate limit, telling failures worth retrying from ones
+//! that aren't, and how those are retried.
+//!
+//! A RetryPolicy waits longer before each retry, doubling from its backoff
+//! up to a cap, and takes a random share (the jitter) off each wait, so the
+//! clients that all failed at once when a community API fell over don't all
+//! come back at once too.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+#[cfg(feature = "edsm")]
+use std::time::Instant;
+
+use tracing::warn;
+
+/// The longest a RetryPolicy waits by default.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// The share of each wait a RetryPolicy takes off at random by default.
+pub const DEFAULT_JITTER: f64 = 0.25;
+
+#[derive(Debug)]
+pub enum HttpError {
+    /// The request failed or the server answered with an error status.
+    Http(Box<ureq::Error>),
+    Io(io::Error),
+    /// The response wasn't the JSON expected.
+    Json(serde_json::Error),
+}
+
+impl HttpError {
+    /// True for failures that may well not happen again: the connection
+    /// failing, the server being busy (429) or falling over (5xx).
+    pub fn is_transient(&self) -> bool {
+        match self {
+            HttpError::Http(e) => match **e {
+                ureq::Error::Status(status, _) => status == 429 || status >= 500,
+                ureq::Error::Transport(_) => true,
+            },
+            HttpError::Io(_) => true,
+            HttpError::Json(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Http(e) => write!(f, "request failed: {}", e),
+            HttpError::Io(e) => write!(f, "response unreadable: {}", e),
+            HttpError::Json(e) => write!(f, "response malformed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<ureq::Error> for HttpError {
+    fn from(e: ureq::Error) -> Self {
+        HttpError::Http(Box::new(e))
+    }
+}
+
+impl From<io::Error> for HttpError {
+    fn from(e: io::Error) -> Self {
+        HttpError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for HttpError {
+    fn from(e: serde_json::Error) -> Self {
+        HttpError::Json(e)
+    }
+}
+
+/// A retry about to be made, as a RetryPolicy's callback is told of it.
+#[derive(Debug)]
+pub struct Retry<'a> {
+    /// 1 for the first retry.
+    pub attempt: usize,
+    /// The wait before it.
+    pub delay: Duration,
+    /// The failure being retried.
+    pub error: &'a HttpError,
+}
+
+type RetryCallback = Arc<dyn Fn(&Retry<'_>) + Send + Sync>;
+
+/// How transient failures are retried: up to `retries` times, waiting
+/// `backoff` before the first retry and twice as long before each one
+/// after, up to `max_backoff`, less a random share of up to `jitter`.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub retries: usize,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Between 0 (no randomness) and 1 (anything from no wait up).
+    pub jitter: f64,
+    on_retry: Option<RetryCallback>,
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("retries", &self.retries)
+            .field("backoff", &self.backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("jitter", &self.jitter)
+            .field("on_retry", &self.on_retry.is_some())
+            .finish()
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(retries: usize, backoff: Duration) -> Self {
+        Self {
+            retries,
+            backoff,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            jitter: DEFAULT_JITTER,
+            on_retry: None,
+        }
+    }
+
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0., 1.);
+        self
+    }
+
+    /// Calls `callback` before each retry, e.g. to report it to the user.
+    pub fn on_retry(mut self, callback: impl Fn(&Retry<'_>) + Send + Sync + 'static) -> Self {
+        self.on_retry = Some(Arc::new(callback));
+        self
+    }
+
+    /// The wait before retry `attempt`, before jitter.
+    pub fn backoff_for(&self, attempt: usize) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(31) as u32;
+        self.backoff
+            .saturating_mul(1 << doublings)
+            .min(self.max_backoff)
+    }
+
+    /// The wait before retry `attempt`, with the jitter taken off.
+    pub fn delay(&self, attempt: usize) -> Duration {
+        self.backoff_for(attempt)
+            .mul_f64(1. - self.jitter.clamp(0., 1.) * random_fraction())
+    }
+
+    /// Reports retry `attempt` of a request that failed with `error`, then
+    /// waits for it.
+    pub fn wait(&self, request: &str, attempt: usize, error: &HttpError) {
+        let delay = self.delay(attempt);
+        warn!(request, attempt, ?delay, %error, "request failed, retrying");
+        if let Some(callback) = &self.on_retry {
+            callback(&Retry {
+                attempt,
+                delay,
+                error,
+            });
+        }
+        thread::sleep(delay);
+    }
+
+    /// Makes a request until it succeeds, fails in a way that isn't worth
+    /// retrying, or runs out of retries. `request` names it in the log.
+    pub fn run<T>(
+        &self,
+        request: &str,
+        mut send: impl FnMut() -> Result<T, HttpError>,
+    ) -> Result<T, HttpError> {
+        let mut attempt = 0;
+        loop {
+            match send() {
+                Err(e) if e.is_transient() && attempt < self.retries => {
+                    attempt += 1;
+                    self.wait(request, attempt, &e);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// A number in [0, 1) that's different each call; good enough to spread
+/// retries out, which is all it's for.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Keeps requests at least `interval` apart, and holds off entirely until
+/// a reset the server has announced.
+#[cfg(feature = "edsm")]
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next: Option<Instant>,
+}
+
+#[cfg(feature = "edsm")]
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: None,
+        }
+    }
+
+    /// How long a request made at `now` has to wait.
+    pub fn delay(&self, now: Instant) -> Duration {
+        self.next
+            .map_or(Duration::ZERO, |next| next.saturating_duration_since(now))
+    }
+
+    /// Notes a request made at `now`, and the server's word on how long
+    /// until requests may resume, if it's run out.
+    pub fn record(&mut self, now: Instant, reset: Option<Duration>) {
+        let next = now + reset.unwrap_or(self.interval).max(self.interval);
+        self.next = Some(self.next.map_or(next, |prev| prev.max(next)));
+    }
+}
+
+/// A local server for the clients' tests to talk to.
+#[cfg(test)]
+pub mod testing {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves one canned response per connection, and hands back the
+    /// request line and body of each request it was sent.
+    pub fn serve(responses: Vec<String>) -> (String, thread::JoinHandle<Vec<(String, String)>>) {
+        let (url, handle) = serve_with_headers(responses);
+        let handle = thread::spawn(move || {
+            let requests = handle.join().unwrap();
+            requests
+                .into_iter()
+                .map(|(line, _, body)| (line, body))
+                .collect()
+        });
+        (url, handle)
+    }
+
+    /// A request's line, header lines and body.
+    pub type Request = (String, Vec<String>, String);
+
+    /// Like serve, but also hands back each request's header lines.
+    pub fn serve_with_headers(
+        responses: Vec<String>,
+    ) -> (String, thread::JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut length = 0;
+                let mut headers = Vec::new();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    headers.push(header.trim_end().to_string());
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                requests.push((
+                    line.trim_end().to_string(),
+                    headers,
+                    String::from_utf8(body).unwrap(),
+                ));
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    /// An HTTP response with a JSON body and any extra header lines.
+    pub fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+            status,
+            body.len(),
+            headers,
+            body
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "edsm")]
+    #[test]
+    fn test_rate_limiter() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(Duration::from_secs(10));
+        assert_eq!(limiter.delay(start), Duration::ZERO);
+        limiter.record(start, None);
+        assert_eq!(
+            limiter.delay(start + Duration::from_secs(4)),
+            Duration::from_secs(6)
+        );
+        assert_eq!(
+            limiter.delay(start + Duration::from_secs(11)),
+            Duration::ZERO
+        );
+        // a reset pushes the next request out, and a shorter one later
+        // doesn't pull it back in
+        limiter.record(start, Some(Duration::from_secs(60)));
+        limiter.record(start + Duration::from_secs(1), None);
+        assert_eq!(limiter.delay(start), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::new(3, Duration::from_secs(2))
+            .max_backoff(Duration::from_secs(5))
+            .jitter(0.);
+        let waits: Vec<u64> = (1..=4).map(|n| policy.delay(n).as_secs()).collect();
+        assert_eq!(waits, [2, 4, 5, 5]);
+        let jittery = policy.clone().jitter(0.5);
+        for _ in 0..100 {
+            let delay = jittery.delay(1);
+            assert!(delay > Duration::from_secs(1) && delay <= Duration::from_secs(2));
+        }
+
+        // only transient failures are retried, each one reported
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let policy = RetryPolicy::new(2, Duration::ZERO)
+            .on_retry(move |retry| log.lock().unwrap().push(retry.attempt));
+        let busy = || HttpError::Io(io::Error::other("busy"));
+        let mut calls = 0;
+        let result: Result<(), _> = policy.run("test", || {
+            calls += 1;
+            Err(busy())
+        });
+        assert!(result.is_err());
+        assert_eq!((calls, seen.lock().unwrap().clone()), (3, vec![1, 2]));
+        let mut calls = 0;
+        let json = || serde_json::from_str::<u32>("x").unwrap_err();
+        let result: Result<(), _> = policy.run("test", || {
+            calls += 1;
+            Err(json().into())
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let (url, server) = testing::serve(vec![
+            testing::response("503 Service Unavailable", "", ""),
+            testing::response("404 Not Found", "", ""),
+        ]);
+        let get = || HttpError::from(ureq::get(&url).call().unwrap_err());
+        assert!(get().is_transient());
+        assert!(!get().is_transient());
+        server.join().unwrap();
+        let json = serde_json::from_str::<u32>("x").unwrap_err();
+        assert!(!HttpError::from(json).is_transient());
+    }
+}
@@ -0,0 +1,163 @@
+//! Distinct types for the IDs of systems, stations and commodities, so one
+//! can't be passed where another is wanted. Each wraps the integer the game
+//! and TradeDangerous use and converts to and from it with `From`. In JSON,
+//! in the database and in Python they're plain integers.
+
+use std::fmt;
+
+#[cfg(feature = "fs")]
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+
+/// A system's SystemAddress, the game's id64.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct SystemId(pub u64);
+
+/// A station's ID: TradeDangerous' station_id, which for stations with a
+/// market is the game's MarketID.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct StationId(pub u32);
+
+/// A commodity's ID: TradeDangerous' item_id, the game's commodity ID.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ItemId(pub u32);
+
+impl fmt::Display for SystemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for StationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<u64> for SystemId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+impl From<SystemId> for u64 {
+    fn from(id: SystemId) -> Self {
+        id.0
+    }
+}
+
+impl From<u32> for StationId {
+    fn from(id: u32) -> Self {
+        Self(id)
+    }
+}
+
+impl From<StationId> for u32 {
+    fn from(id: StationId) -> Self {
+        id.0
+    }
+}
+
+impl From<u32> for ItemId {
+    fn from(id: u32) -> Self {
+        Self(id)
+    }
+}
+
+impl From<ItemId> for u32 {
+    fn from(id: ItemId) -> Self {
+        id.0
+    }
+}
+
+// SQLite's integers are i64, which a SystemAddress can overflow; like the
+// game's, those are stored as the i64 with the same bits.
+
+#[cfg(feature = "fs")]
+impl ToSql for SystemId {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok((self.0 as i64).into())
+    }
+}
+
+#[cfg(feature = "fs")]
+impl FromSql for SystemId {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        i64::column_result(value).map(|id| Self(id as u64))
+    }
+}
+
+#[cfg(feature = "fs")]
+impl ToSql for StationId {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        self.0.to_sql()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl FromSql for StationId {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        u32::column_result(value).map(Self)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl ToSql for ItemId {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        self.0.to_sql()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl FromSql for ItemId {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        u32::column_result(value).map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_are_plain_integers() {
+        assert_eq!(
+            serde_json::to_string(&StationId(128016640)).unwrap(),
+            "128016640"
+        );
+        assert_eq!(serde_json::from_str::<ItemId>("42").unwrap(), ItemId(42));
+        assert_eq!(SystemId(10477373803).to_string(), "10477373803");
+        assert_eq!(u32::from(StationId::from(7)), 7);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_ids_in_sqlite() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        // a SystemAddress past i64::MAX survives the trip as its bits
+        let big = SystemId(u64::MAX - 5);
+        let (system, station): (SystemId, StationId) = conn
+            .query_row("SELECT ?, ?", (big, StationId(9)), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((system, station), (big, StationId(9)));
+        let negative: rusqlite::Result<ItemId> = conn.query_row("SELECT -1", [], |row| row.get(0));
+        assert!(negative.is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Market snapshots in the JSON of Inara's batch API, so data parsed here can
+//! be forwarded to Inara as it is.
+//!
+//! A batch is a header naming the app and the commander it's sent for, then
+//! a list of events, each with a name, an ISO 8601 timestamp and its data:
+//!
+//! ```json
+//! {"header": {"appName": "...", "appVersion": "...", "isBeingDeveloped": false,
+//!             "APIkey": "...", "commanderName": "..."},
+//!  "events": [{"eventName": "...", "eventTimestamp": "2024-05-01T12:00:00Z",
+//!              "eventData": {"starsystemName": "Sol", "stationName": "...",
+//!                            "marketID": 128016640, "commodities": [...]}}]}
+//! ```
+//!
+//! The event name is the caller's to choose. Commodities are named by their
+//! FDev symbol, and listings of items not in the commodity table are left
+//! out.
+
+use serde::Serialize;
+
+use crate::commodities::commodity_by_id;
+use crate::export;
+use crate::ids::StationId;
+use crate::journal::format_iso_timestamp;
+use crate::market::MarketSnapshot;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InaraHeader {
+    pub app_name: String,
+    pub app_version: String,
+    pub is_being_developed: bool,
+    #[serde(rename = "APIkey")]
+    pub api_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commander_name: Option<String>,
+    #[serde(
+        rename = "commanderFrontierID",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub commander_frontier_id: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InaraCommodity {
+    name: &'static str,
+    buy_price: i32,
+    sell_price: i32,
+    stock: i64,
+    stock_bracket: i32,
+    demand: i64,
+    demand_bracket: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InaraMarket {
+    starsystem_name: String,
+    station_name: String,
+    #[serde(rename = "marketID")]
+    market_id: StationId,
+    commodities: Vec<InaraCommodity>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InaraEvent {
+    event_name: String,
+    event_timestamp: String,
+    event_data: InaraMarket,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct InaraBatch {
+    header: InaraHeader,
+    events: Vec<InaraEvent>,
+}
+
+impl InaraBatch {
+    pub fn new(header: InaraHeader) -> Self {
+        Self {
+            header,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Adds an event carrying a station's market, timestamped with the
+    /// snapshot. Unknown levels are sent as bracket 0.
+    pub fn push_market(
+        &mut self,
+        event_name: &str,
+        system: &str,
+        station: &str,
+        snapshot: &MarketSnapshot,
+    ) {
+        let commodities = snapshot
+            .items()
+            .iter()
+            .filter_map(|item| {
+                Some(InaraCommodity {
+                    name: commodity_by_id(item.item_id)?.symbol,
+                    buy_price: item.supply_price,
+                    sell_price: item.demand_price,
+                    stock: item.supply_units,
+                    stock_bracket: item.supply_level.max(0),
+                    demand: item.demand_units,
+                    demand_bracket: item.demand_level.max(0),
+                })
+            })
+            .collect();
+        self.events.push(InaraEvent {
+            event_name: event_name.to_string(),
+            event_timestamp: format_iso_timestamp(snapshot.timestamp),
+            event_data: InaraMarket {
+                starsystem_name: system.to_string(),
+                station_name: station.to_string(),
+                market_id: snapshot.station_id,
+                commodities,
+            },
+        });
+    }
+
+    /// The batch as the JSON body of an Inara API request, pretty-printed
+    /// with the given indent or compact if None.
+    pub fn to_json(&self, indent: Option<usize>) -> serde_json::Result<String> {
+        export::to_json(self, indent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commodities::canonical_commodity;
+    use crate::ids::ItemId;
+    use crate::market::StationItem;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_inara_batch() {
+        let header = InaraHeader {
+            app_name: "TradeDangerous".into(),
+            app_version: "1.0".into(),
+            api_key: "secret".into(),
+            commander_name: Some("Jameson".into()),
+            ..Default::default()
+        };
+        let mut batch = InaraBatch::new(header);
+        let gold = canonical_commodity("Gold").unwrap().id;
+        let snapshot = MarketSnapshot::new(
+            StationId(128016640),
+            1714564800,
+            vec![
+                StationItem {
+                    item_id: gold,
+                    demand_price: 8900,
+                    supply_price: 9100,
+                    supply_units: 120,
+                    supply_level: 2,
+                    demand_level: -1,
+                    ..Default::default()
+                },
+                StationItem {
+                    item_id: ItemId(9999),
+                    ..Default::default()
+                },
+            ],
+        );
+        batch.push_market("setMarket", "Sol", "Abraham Lincoln", &snapshot);
+        assert_eq!(batch.len(), 1);
+
+        let json: Value = serde_json::from_str(&batch.to_json(None).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "header": {
+                    "appName": "TradeDangerous", "appVersion": "1.0",
+                    "isBeingDeveloped": false, "APIkey": "secret",
+                    "commanderName": "Jameson",
+                },
+                "events": [{
+                    "eventName": "setMarket",
+                    "eventTimestamp": "2024-05-01T12:00:00Z",
+                    "eventData": {
+                        "starsystemName": "Sol", "stationName": "Abraham Lincoln",
+                        "marketID": 128016640,
+                        "commodities": [{
+                            "name": "gold", "buyPrice": 9100, "sellPrice": 8900,
+                            "stock": 120, "stockBracket": 2, "demand": 0, "demandBracket": 0,
+                        }],
+                    },
+                }],
+            })
+        );
+        assert!(batch
+            .to_json(Some(2))
+            .unwrap()
+            .contains("\n  \"events\": ["));
+    }
+}
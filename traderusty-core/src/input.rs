@@ -0,0 +1,805 @@
+//! Opening input files according to ReadOptions.
+//!
+//! The OS hints are passed on where the platform has them: on Linux
+//! `hint_sequential` becomes POSIX_FADV_SEQUENTIAL, which widens the kernel's
+//! read-ahead window, and `read_ahead` keeps a POSIX_FADV_WILLNEED request
+//! that many bytes in front of the read position. On Windows
+//! `hint_sequential` opens the file with FILE_FLAG_SEQUENTIAL_SCAN. Hints are
+//! advisory: if the OS refuses one, reading carries on without it.
+//!
+//! `direct_io` bypasses the page cache (O_DIRECT on Linux,
+//! FILE_FLAG_NO_BUFFERING on Windows) so a huge import doesn't evict
+//! everything else from memory. Direct reads must be of aligned sizes into
+//! aligned memory, so they go through a staging buffer. Filesystems that
+//! don't support it (tmpfs, some network shares) get ordinary reads.
+//!
+//! With `prefetch` the file is read on a background thread into one buffer
+//! while the caller works through the other, so parsing doesn't sit idle
+//! waiting on the disk.
+//!
+//! A filename of "-" reads standard input instead, so the readers can sit at
+//! the end of a shell pipeline.
+//!
+//! On Windows, paths too long for the classic 260 character limit are
+//! rewritten to the `\\?\` extended-length form (`\\?\UNC\` for shares)
+//! before opening.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+
+use crate::gzip::GzipReader;
+use crate::metrics::{self, Counter};
+use crate::options::ReadOptions;
+
+#[cfg(windows)]
+const FILE_FLAG_SEQUENTIAL_SCAN: u32 = 0x0800_0000;
+#[cfg(windows)]
+const FILE_FLAG_NO_BUFFERING: u32 = 0x2000_0000;
+
+/// Alignment of direct reads' memory, size and file offset; covers the
+/// sector size of any current disk.
+pub const DIRECT_IO_ALIGN: usize = 4096;
+
+/// Aligned staging area for direct reads.
+struct DirectBuffer {
+    storage: Vec<u8>,
+    /// Offset of the first aligned byte of storage.
+    start: usize,
+    capacity: usize,
+    /// Bytes of the current block, and how many have been handed out.
+    filled: usize,
+    consumed: usize,
+}
+
+impl DirectBuffer {
+    fn new(size: usize) -> Self {
+        let capacity = size.max(1).div_ceil(DIRECT_IO_ALIGN) * DIRECT_IO_ALIGN;
+        let storage = vec![0; capacity + DIRECT_IO_ALIGN];
+        let start = storage.as_ptr().align_offset(DIRECT_IO_ALIGN);
+        Self {
+            storage,
+            start,
+            capacity,
+            filled: 0,
+            consumed: 0,
+        }
+    }
+
+    fn read(&mut self, file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+        if self.consumed == self.filled {
+            let block = &mut self.storage[self.start..self.start + self.capacity];
+            self.filled = file.read(block)?;
+            self.consumed = 0;
+        }
+        let available = &self.storage[self.start + self.consumed..self.start + self.filled];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.consumed += count;
+        Ok(count)
+    }
+}
+
+/// A file opened for reading, which keeps the OS prefetching ahead of it.
+pub struct InputFile {
+    file: File,
+    read_ahead: u64,
+    /// Bytes read so far.
+    position: u64,
+    /// End of the range the OS has been asked to prefetch.
+    advised_to: u64,
+    /// Set when the file was opened for direct I/O.
+    direct: Option<DirectBuffer>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn fadvise(file: &File, offset: u64, len: u64, advice: libc::c_int) {
+    use std::os::unix::io::AsRawFd;
+    let result = unsafe {
+        libc::posix_fadvise(
+            file.as_raw_fd(),
+            offset as libc::off_t,
+            len as libc::off_t,
+            advice,
+        )
+    };
+    if result != 0 {
+        tracing::debug!(advice, error = %io::Error::from_raw_os_error(result), "fadvise refused");
+    }
+}
+
+/// Longest path Windows opens without the extended-length prefix.
+#[cfg(any(windows, test))]
+const MAX_PATH: usize = 259;
+
+/// Rewrites a Windows path that's longer than MAX_PATH once resolved
+/// against `cwd` into extended-length form. The prefix turns off Windows'
+/// own path clean-up, so separators, "." and ".." are dealt with here.
+///
+/// A drive-relative path ("C:foo") resolves against `cwd` when it names
+/// the same drive. Windows keeps a separate current directory for each
+/// other drive, which isn't known here, so those are left as given.
+#[cfg(any(windows, test))]
+fn extended_length_path(path: &str, cwd: &str) -> String {
+    let is_prefixed = |p: &str| p.starts_with(r"\\?\") || p.starts_with(r"\\.\");
+    if is_prefixed(path) {
+        return path.to_string();
+    }
+    let has_drive = |p: &str| p.len() >= 2 && p.as_bytes()[1] == b':';
+    let path = path.replace('/', r"\");
+    let absolute = has_drive(&path) && path[2..].starts_with('\\');
+    let resolved = if absolute || path.starts_with(r"\\") {
+        path
+    } else if has_drive(&path) {
+        if !has_drive(cwd) || !path[..1].eq_ignore_ascii_case(&cwd[..1]) {
+            return path;
+        }
+        format!(r"{}\{}", cwd.trim_end_matches(['\\', '/']), &path[2..])
+    } else if path.starts_with('\\') && has_drive(cwd) {
+        // rooted on the current drive
+        format!("{}{}", &cwd[..2], path)
+    } else {
+        format!(r"{}\{}", cwd.trim_end_matches(['\\', '/']), path)
+    };
+    if resolved.len() <= MAX_PATH || is_prefixed(&resolved) {
+        return resolved;
+    }
+
+    let (prefix, rest) = match resolved.strip_prefix(r"\\") {
+        Some(share) => (r"\\?\UNC".to_string(), share),
+        None => (format!(r"\\?\{}", &resolved[..2]), &resolved[2..]),
+    };
+    let mut parts = vec![prefix.as_str()];
+    for part in rest.split('\\') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.len() > 1 {
+                    parts.pop();
+                }
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join(r"\")
+}
+
+/// The path to hand the OS for a filename.
+fn os_path(filename: &Path) -> PathBuf {
+    #[cfg(windows)]
+    if let (Some(name), Ok(cwd)) = (filename.to_str(), std::env::current_dir()) {
+        return extended_length_path(name, &cwd.to_string_lossy()).into();
+    }
+    filename.into()
+}
+
+/// Opens a file, with direct I/O if asked for; returns whether it was.
+fn open_file(filename: &Path, options: &ReadOptions) -> io::Result<(File, bool)> {
+    let path = os_path(filename);
+    #[allow(unused_mut)]
+    let mut flags = 0;
+    #[cfg(windows)]
+    if options.hint_sequential {
+        flags |= FILE_FLAG_SEQUENTIAL_SCAN;
+    }
+    let open = |flags| {
+        let mut open = OpenOptions::new();
+        open.read(true);
+        #[cfg(any(unix, windows))]
+        {
+            #[cfg(unix)]
+            use std::os::unix::fs::OpenOptionsExt;
+            #[cfg(windows)]
+            use std::os::windows::fs::OpenOptionsExt;
+            open.custom_flags(flags);
+        }
+        open.open(&path)
+    };
+
+    #[cfg(any(target_os = "linux", target_os = "android", windows))]
+    if options.direct_io {
+        #[cfg(unix)]
+        let direct_flags = flags | libc::O_DIRECT;
+        #[cfg(windows)]
+        let direct_flags = flags | FILE_FLAG_NO_BUFFERING;
+        match open(direct_flags) {
+            Ok(file) => return Ok((file, true)),
+            // Not found, permission denied etc. would fail the same way again.
+            Err(e) if e.kind() != io::ErrorKind::InvalidInput => return Err(e),
+            Err(e) => {
+                tracing::info!(filename = %filename.display(), error = %e, "direct I/O unsupported, reading buffered")
+            }
+        }
+    }
+    Ok((open(flags)?, false))
+}
+
+/// Opens a file for reading at arbitrary positions, for the readers that
+/// work backwards from the end. Standard input can't be used.
+pub fn open_seekable(filename: impl AsRef<Path>) -> io::Result<File> {
+    let filename = filename.as_ref();
+    if filename == Path::new(STDIN_PATH) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "standard input can't be read from the end",
+        ));
+    }
+    File::open(os_path(filename))
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", filename.display(), e)))
+}
+
+impl InputFile {
+    pub fn open(filename: impl AsRef<Path>, options: &ReadOptions) -> io::Result<Self> {
+        let filename = filename.as_ref();
+        // Say which file, since the OS error alone rarely makes it obvious.
+        let (file, direct) = open_file(filename, options)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", filename.display(), e)))?;
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if options.hint_sequential {
+            fadvise(&file, 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+        }
+
+        let mut input = Self {
+            file,
+            read_ahead: options.read_ahead as u64,
+            position: 0,
+            advised_to: 0,
+            direct: direct.then(|| DirectBuffer::new(options.buffer_size)),
+        };
+        input.advise();
+        Ok(input)
+    }
+
+    /// Moves the read position to `offset`. Direct reads have to start on
+    /// an aligned offset, so those seek to the block holding it and skip
+    /// the bytes in front.
+    fn seek_to(&mut self, offset: u64) -> io::Result<()> {
+        let start = match &mut self.direct {
+            Some(direct) => {
+                // whatever is staged is from the old position
+                direct.filled = 0;
+                direct.consumed = 0;
+                offset - offset % DIRECT_IO_ALIGN as u64
+            }
+            None => offset,
+        };
+        self.file.seek(SeekFrom::Start(start))?;
+        self.position = start;
+        self.advised_to = start;
+        self.advise();
+        io::copy(&mut self.by_ref().take(offset - start), &mut io::sink())?;
+        Ok(())
+    }
+
+    /// Asks for the next stretch to be prefetched once the read position
+    /// is half way through the last one, so requests aren't made per read.
+    fn advise(&mut self) {
+        if self.read_ahead == 0 || self.position + self.read_ahead / 2 < self.advised_to {
+            return;
+        }
+        let end = self.position + self.read_ahead;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        fadvise(
+            &self.file,
+            self.advised_to,
+            end - self.advised_to,
+            libc::POSIX_FADV_WILLNEED,
+        );
+        self.advised_to = end;
+    }
+}
+
+impl Read for InputFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = match &mut self.direct {
+            Some(direct) => direct.read(&mut self.file, buf)?,
+            None => self.file.read(buf)?,
+        };
+        self.position += bytes_read as u64;
+        metrics::add(Counter::BytesRead, bytes_read as u64);
+        self.advise();
+        Ok(bytes_read)
+    }
+}
+
+/// Reads a source on a background thread, two buffers at a time: while the
+/// caller consumes one, the worker fills the other.
+pub struct PrefetchReader {
+    current: Vec<u8>,
+    consumed: usize,
+    /// Filled buffers from the worker; an empty one marks the end. Only
+    /// ever used through get_mut, the Mutex just makes the reader Sync.
+    full: Mutex<Receiver<io::Result<Vec<u8>>>>,
+    /// Spent buffers going back to the worker. Dropped before joining so a
+    /// worker waiting for one gives up.
+    empty: Option<SyncSender<Vec<u8>>>,
+    worker: Option<JoinHandle<()>>,
+    done: bool,
+}
+
+impl PrefetchReader {
+    pub fn new<R: Read + Send + 'static>(mut source: R, buffer_size: usize) -> Self {
+        let buffer_size = buffer_size.max(1);
+        let (full_tx, full) = sync_channel(1);
+        let (empty, empty_rx) = sync_channel::<Vec<u8>>(2);
+        empty.send(Vec::new()).unwrap();
+        empty.send(Vec::new()).unwrap();
+        let worker = thread::spawn(move || {
+            while let Ok(mut buffer) = empty_rx.recv() {
+                buffer.resize(buffer_size, 0);
+                let result = loop {
+                    match source.read(&mut buffer) {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        result => break result,
+                    }
+                };
+                let finished = !matches!(result, Ok(n) if n > 0);
+                let message = result.map(|bytes_read| {
+                    buffer.truncate(bytes_read);
+                    buffer
+                });
+                // The reader hanging up just means it's finished with us.
+                if full_tx.send(message).is_err() || finished {
+                    break;
+                }
+            }
+        });
+        Self {
+            current: Vec::new(),
+            consumed: 0,
+            full: Mutex::new(full),
+            empty: Some(empty),
+            worker: Some(worker),
+            done: false,
+        }
+    }
+}
+
+impl PrefetchReader {
+    /// Stops dropping the reader from waiting for the worker. For sources
+    /// like stdin where a read can block indefinitely; the worker still
+    /// exits after its current read.
+    pub fn detach(mut self) -> Self {
+        self.worker = None;
+        self
+    }
+}
+
+impl BufRead for PrefetchReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.consumed == self.current.len() && !self.done {
+            let spent = std::mem::take(&mut self.current);
+            if let Some(empty) = &self.empty {
+                let _ = empty.send(spent);
+            }
+            self.consumed = 0;
+            let full = self.full.get_mut().unwrap();
+            match full.recv() {
+                Ok(Ok(buffer)) if !buffer.is_empty() => self.current = buffer,
+                Ok(Err(e)) => {
+                    self.done = true;
+                    return Err(e);
+                }
+                _ => self.done = true,
+            }
+        }
+        Ok(&self.current[self.consumed..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.consumed = (self.consumed + amount).min(self.current.len());
+    }
+}
+
+impl Read for PrefetchReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl Drop for PrefetchReader {
+    fn drop(&mut self) {
+        // Hang up both channels so the worker stops, then wait for it so the
+        // file is closed by the time we're gone.
+        self.empty = None;
+        let (_, full) = sync_channel(0);
+        drop(std::mem::replace(self.full.get_mut().unwrap(), full));
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The filename that means standard input.
+pub const STDIN_PATH: &str = "-";
+
+/// The reader the file-based APIs parse from.
+pub enum InputReader {
+    Buffered(BufReader<InputFile>),
+    Stdin(BufReader<io::Stdin>),
+    Prefetch(PrefetchReader),
+    /// A gzip file, decompressed from another reader.
+    Gzip(Box<BufReader<GzipReader<InputReader>>>),
+}
+
+impl InputReader {
+    pub fn open(
+        filename: impl AsRef<Path>,
+        options: &ReadOptions,
+        buffer_size: usize,
+    ) -> io::Result<Self> {
+        Self::open_at(filename, options, buffer_size, 0)
+    }
+
+    /// Opens a file to read from `offset` bytes in. Standard input can only
+    /// be read from the start.
+    pub fn open_at(
+        filename: impl AsRef<Path>,
+        options: &ReadOptions,
+        buffer_size: usize,
+        offset: u64,
+    ) -> io::Result<Self> {
+        if filename.as_ref() == Path::new(STDIN_PATH) {
+            if offset > 0 {
+                return Err(stdin_offset_error());
+            }
+            let stdin = io::stdin();
+            return Ok(if options.prefetch {
+                InputReader::Prefetch(PrefetchReader::new(stdin, buffer_size).detach())
+            } else {
+                InputReader::Stdin(BufReader::with_capacity(buffer_size, stdin))
+            });
+        }
+        let mut file = InputFile::open(filename, options)?;
+        if offset > 0 {
+            file.seek_to(offset)?;
+        }
+        Ok(Self::from_file(file, options, buffer_size))
+    }
+
+    /// Opens a file and hands its first bytes to `start`, which says how
+    /// far in to read from: a caller can look at what kind of file it is
+    /// before choosing, without opening it twice.
+    pub fn open_sniffed(
+        filename: impl AsRef<Path>,
+        options: &ReadOptions,
+        buffer_size: usize,
+        start: impl FnOnce(&[u8]) -> u64,
+    ) -> io::Result<Self> {
+        if filename.as_ref() == Path::new(STDIN_PATH) {
+            let mut reader = Self::open(filename, options, buffer_size)?;
+            if start(reader.fill_buf()?) > 0 {
+                return Err(stdin_offset_error());
+            }
+            return Ok(reader);
+        }
+        let mut head = BufReader::with_capacity(buffer_size, InputFile::open(filename, options)?);
+        let offset = start(head.fill_buf()?);
+        let mut file = head.into_inner();
+        file.seek_to(offset)?;
+        Ok(Self::from_file(file, options, buffer_size))
+    }
+
+    fn from_file(file: InputFile, options: &ReadOptions, buffer_size: usize) -> Self {
+        if options.prefetch {
+            InputReader::Prefetch(PrefetchReader::new(file, buffer_size))
+        } else {
+            InputReader::Buffered(BufReader::with_capacity(buffer_size, file))
+        }
+    }
+}
+
+fn stdin_offset_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "standard input can't be read from an offset",
+    )
+}
+
+impl Read for InputReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            InputReader::Buffered(reader) => reader.read(buf),
+            InputReader::Stdin(reader) => reader.read(buf),
+            InputReader::Prefetch(reader) => reader.read(buf),
+            InputReader::Gzip(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl BufRead for InputReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            InputReader::Buffered(reader) => reader.fill_buf(),
+            InputReader::Stdin(reader) => reader.fill_buf(),
+            InputReader::Prefetch(reader) => reader.fill_buf(),
+            InputReader::Gzip(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match self {
+            InputReader::Buffered(reader) => reader.consume(amount),
+            InputReader::Stdin(reader) => reader.consume(amount),
+            InputReader::Prefetch(reader) => reader.consume(amount),
+            InputReader::Gzip(reader) => reader.consume(amount),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_input_file_reads_with_hints() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        tmpfile.write_all(&data).unwrap();
+        let options = ReadOptions {
+            buffer_size: 4096,
+            read_ahead: 16384,
+            hint_sequential: true,
+            direct_io: false,
+            prefetch: false,
+            memory_budget: None,
+        };
+        let mut input = InputFile::open(tmpfile.path().to_str().unwrap(), &options).unwrap();
+        assert_eq!(input.advised_to, 16384);
+
+        let mut chunk = vec![0; 4096];
+        input.read_exact(&mut chunk).unwrap();
+        assert_eq!(input.advised_to, 16384);
+        input.read_exact(&mut chunk).unwrap();
+        assert_eq!(input.advised_to, 8192 + 16384);
+
+        let mut rest = Vec::new();
+        input.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &data[8192..]);
+        assert!(input.advised_to >= data.len() as u64);
+    }
+
+    #[test]
+    fn test_input_file_direct_io() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i % 253) as u8).collect();
+        tmpfile.write_all(&data).unwrap();
+        // deliberately not a multiple of the alignment
+        let options = ReadOptions {
+            buffer_size: 5000,
+            direct_io: true,
+            ..Default::default()
+        };
+        let mut input = InputFile::open(tmpfile.path().to_str().unwrap(), &options).unwrap();
+        if let Some(direct) = &input.direct {
+            assert_eq!(direct.capacity, 8192);
+            assert_eq!(
+                direct.storage[direct.start..].as_ptr() as usize % DIRECT_IO_ALIGN,
+                0
+            );
+        }
+        let mut small = [0; 100];
+        input.read_exact(&mut small).unwrap();
+        assert_eq!(&small[..], &data[..100]);
+        let mut rest = Vec::new();
+        input.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &data[100..]);
+        assert_eq!(input.position, data.len() as u64);
+
+        let missing = ReadOptions {
+            direct_io: true,
+            ..Default::default()
+        };
+        assert!(InputFile::open("/no/such/file", &missing).is_err());
+    }
+
+    /// Hands out its data a few bytes at a time, then fails.
+    struct Trickle {
+        data: Vec<u8>,
+        fail: bool,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.data.is_empty() && self.fail {
+                return Err(io::Error::other("disk on fire"));
+            }
+            let count = self.data.len().min(buf.len()).min(7);
+            buf[..count].copy_from_slice(&self.data[..count]);
+            self.data.drain(..count);
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn test_prefetch_reader() {
+        let data: Vec<u8> = (0..2_000u32).map(|i| (i % 255) as u8).collect();
+        for buffer_size in [3, 64, 100_000] {
+            let source = Trickle {
+                data: data.clone(),
+                fail: false,
+            };
+            let mut reader = PrefetchReader::new(source, buffer_size);
+            let mut out = Vec::new();
+            reader.read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+            assert_eq!(reader.read(&mut [0; 4]).unwrap(), 0);
+        }
+
+        let source = Trickle {
+            data: b"line one\nline two\n".to_vec(),
+            fail: false,
+        };
+        let lines: Vec<String> = PrefetchReader::new(source, 4)
+            .lines()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(lines, ["line one", "line two"]);
+    }
+
+    #[test]
+    fn test_prefetch_reader_errors_and_drop() {
+        let source = Trickle {
+            data: b"abc".to_vec(),
+            fail: true,
+        };
+        let mut reader = PrefetchReader::new(source, 16);
+        let mut out = Vec::new();
+        let err = reader.read_to_end(&mut out).unwrap_err();
+        assert_eq!(err.to_string(), "disk on fire");
+        assert_eq!(out, b"abc");
+
+        // dropping part way through mustn't hang on the worker
+        let source = Trickle {
+            data: vec![1; 100_000],
+            fail: false,
+        };
+        let mut reader = PrefetchReader::new(source, 8);
+        reader.read_exact(&mut [0; 10]).unwrap();
+        drop(reader);
+    }
+
+    #[test]
+    fn test_extended_length_path() {
+        let long = "d".repeat(250);
+        let cwd = r"C:\Users\Cmdr\OneDrive";
+        assert_eq!(
+            extended_length_path(r"C:\short.prices", cwd),
+            r"C:\short.prices"
+        );
+        assert_eq!(
+            extended_length_path("short.prices", cwd),
+            r"C:\Users\Cmdr\OneDrive\short.prices"
+        );
+        assert_eq!(
+            extended_length_path(&format!(r"C:\dumps\{}\.\x\..\listings.csv", long), cwd),
+            format!(r"\\?\C:\dumps\{}\listings.csv", long)
+        );
+        assert_eq!(
+            extended_length_path(&format!("C:/dumps/{}/a.csv", long), cwd),
+            format!(r"\\?\C:\dumps\{}\a.csv", long)
+        );
+        assert_eq!(
+            extended_length_path(&format!(r"\\nas\share\{}\a.csv", long), cwd),
+            format!(r"\\?\UNC\nas\share\{}\a.csv", long)
+        );
+        assert_eq!(
+            extended_length_path(&format!(r"{}\a.csv", long), cwd),
+            format!(r"\\?\C:\Users\Cmdr\OneDrive\{}\a.csv", long)
+        );
+        assert_eq!(
+            extended_length_path(&format!(r"\dumps\{}\a.csv", long), cwd),
+            format!(r"\\?\C:\dumps\{}\a.csv", long)
+        );
+        assert_eq!(
+            extended_length_path("C:short.prices", cwd),
+            r"C:\Users\Cmdr\OneDrive\short.prices"
+        );
+        assert_eq!(
+            extended_length_path(&format!(r"c:{}\a.csv", long), cwd),
+            format!(r"\\?\C:\Users\Cmdr\OneDrive\{}\a.csv", long)
+        );
+        // another drive's current directory isn't known
+        let other = format!(r"D:{}\a.csv", long);
+        assert_eq!(extended_length_path(&other, cwd), other);
+        let prefixed = format!(r"\\?\C:\{}", long);
+        assert_eq!(extended_length_path(&prefixed, cwd), prefixed);
+    }
+
+    #[test]
+    fn test_open_error_names_file() {
+        let err = match InputFile::open("no-such-file.prices", &ReadOptions::default()) {
+            Err(err) => err,
+            Ok(_) => panic!("opened a missing file"),
+        };
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with("no-such-file.prices: "));
+    }
+
+    #[test]
+    fn test_input_reader_stdin() {
+        let options = ReadOptions {
+            prefetch: false,
+            ..Default::default()
+        };
+        let reader = InputReader::open(STDIN_PATH, &options, 64).unwrap();
+        assert!(matches!(reader, InputReader::Stdin(_)));
+        // a file that happens to be called "-" can still be read by a path
+        // with a directory in front of it
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("-"), b"dash\n").unwrap();
+        let mut reader = InputReader::open(dir.path().join("-"), &options, 64).unwrap();
+        assert!(matches!(reader, InputReader::Buffered(_)));
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "dash\n");
+    }
+
+    #[test]
+    fn test_input_reader_open_at() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        tmpfile.write_all(&data).unwrap();
+        for (direct_io, prefetch) in [(false, false), (true, false), (true, true)] {
+            let options = ReadOptions {
+                direct_io,
+                prefetch,
+                ..Default::default()
+            };
+            // deliberately not aligned
+            let mut reader = InputReader::open_at(tmpfile.path(), &options, 4096, 5000).unwrap();
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+            assert_eq!(rest, &data[5000..], "direct_io={}", direct_io);
+        }
+        let err = InputReader::open_at(STDIN_PATH, &ReadOptions::default(), 64, 1).err();
+        assert_eq!(err.unwrap().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_input_reader_open_sniffed() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        tmpfile.write_all(&data).unwrap();
+        for direct_io in [false, true] {
+            let options = ReadOptions {
+                direct_io,
+                ..Default::default()
+            };
+            for offset in [0, 5000] {
+                let mut head = Vec::new();
+                let mut reader = InputReader::open_sniffed(tmpfile.path(), &options, 4096, |h| {
+                    head.extend_from_slice(h);
+                    offset
+                })
+                .unwrap();
+                assert!(head.starts_with(&data[..64]));
+                let mut rest = Vec::new();
+                reader.read_to_end(&mut rest).unwrap();
+                assert_eq!(rest, &data[offset as usize..], "direct_io={}", direct_io);
+            }
+        }
+    }
+
+    #[test]
+    fn test_input_file_without_hints() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        tmpfile.write_all(b"hello").unwrap();
+        let mut input =
+            InputFile::open(tmpfile.path().to_str().unwrap(), &ReadOptions::default()).unwrap();
+        let mut text = String::new();
+        input.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "hello");
+        assert_eq!(input.advised_to, 0);
+    }
+}
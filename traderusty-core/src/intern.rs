@@ -0,0 +1,85 @@
+//! String interning: maps each distinct name to a small integer id and back,
+//! so that records can carry 4-byte ids instead of their own copy of a name.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Default)]
+pub struct Interner {
+    ids: HashMap<Arc<str>, u32>,
+    names: Vec<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns the id for a name, allocating the next id if it's new.
+    pub fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len() as u32;
+        let name: Arc<str> = Arc::from(name);
+        self.names.push(name.clone());
+        self.ids.insert(name, id);
+        id
+    }
+
+    /// Returns the id of a name if it has been interned.
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    /// Returns the name for an id.
+    pub fn resolve(&self, id: u32) -> Option<&str> {
+        self.names.get(id as usize).map(|name| name.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_round_trip() {
+        let mut interner = Interner::new();
+        let sol = interner.intern("Sol");
+        let lave = interner.intern("Lave");
+        assert_ne!(sol, lave);
+        assert_eq!(interner.intern("Sol"), sol);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.resolve(sol), Some("Sol"));
+        assert_eq!(interner.resolve(lave), Some("Lave"));
+        assert_eq!(interner.resolve(99), None);
+    }
+
+    #[test]
+    fn test_intern_get() {
+        let mut interner = Interner::new();
+        assert_eq!(interner.get("Sol"), None);
+        let sol = interner.intern("Sol");
+        assert_eq!(interner.get("Sol"), Some(sol));
+        // interning is exact, not case-folded
+        assert_eq!(interner.get("SOL"), None);
+    }
+
+    #[test]
+    fn test_intern_ids_are_dense() {
+        let mut interner = Interner::new();
+        let ids: Vec<u32> = ["a", "b", "a", "c", "b"]
+            .iter()
+            .map(|n| interner.intern(n))
+            .collect();
+        assert_eq!(ids, vec![0, 1, 0, 2, 1]);
+    }
+}
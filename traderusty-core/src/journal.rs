@@ -0,0 +1,570 @@
+//! The files the game writes beside its journal when you dock, so local
+//! tools can update their data without waiting on EDDN.
+//!
+//! Market.json holds the docked station's commodity market in the same
+//! shape as the companion API:
+//!
+//! ```json
+//! {"timestamp": "2024-05-01T12:00:00Z", "event": "Market",
+//!  "MarketID": 128016640, "StationName": "Abraham Lincoln", "StarSystem": "Sol",
+//!  "Items": [{"Name": "$gold_name;", "BuyPrice": 9100, "SellPrice": 8900,
+//!             "Stock": 120, "Demand": 1, "StockBracket": 2, "DemandBracket": 0}, ...]}
+//! ```
+//!
+//! Items are resolved through the commodity table; ones it doesn't know are
+//! left out of the snapshot and reported by name.
+//!
+//! Outfitting.json and Shipyard.json have the same station header, with the
+//! modules for sale under "Items" and the ships under "PriceList". Modules
+//! and ships keep the game's own ids and symbols, lowercased as EDDN has
+//! them, since there's no table of them here.
+//!
+//! Parsed leniently (see [`ParseOptions`]), an item, module or ship that
+//! doesn't parse is dropped and counted in `skipped` instead of failing the
+//! whole file, until more than `max_errors` have been.
+
+use std::collections::BTreeMap;
+
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::commodities::canonical_commodity;
+use crate::ids::{ItemId, StationId};
+use crate::market::{MarketSnapshot, StationItem};
+use crate::options::ParseOptions;
+use crate::prices::parse_timestamp;
+
+/// Parses an ISO 8601 UTC timestamp, "2024-05-01T12:00:00Z", into seconds
+/// since the unix epoch.
+pub fn parse_iso_timestamp(text: &str) -> Option<i64> {
+    let (date, time) = text.strip_suffix('Z').unwrap_or(text).split_once('T')?;
+    parse_timestamp(date, time)
+}
+
+/// Days since 1970-01-01 as a (year, month, day) date.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Formats seconds since the unix epoch as "2024-05-01T12:00:00Z".
+pub fn format_iso_timestamp(timestamp: i64) -> String {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(86400));
+    let secs = timestamp.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+fn timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse_iso_timestamp(&text)
+        .ok_or_else(|| de::Error::custom(format!("invalid timestamp: {:?}", text)))
+}
+
+/// Brackets are 0-3, or "" when the game has nothing to say.
+fn bracket<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Bracket {
+        Level(i32),
+        Blank(String),
+    }
+    match Bracket::deserialize(deserializer)? {
+        Bracket::Level(level) if (0..=3).contains(&level) => Ok(level),
+        Bracket::Blank(text) if text.is_empty() => Ok(0),
+        _ => Err(de::Error::custom("expected a bracket from 0 to 3")),
+    }
+}
+
+/// Parses a file whose `list` field is a list of `I`. Leniently, entries
+/// that aren't an `I` are dropped and counted; otherwise, or once too many
+/// are dropped, the error is the one parsing strictly gives, so it says
+/// where in the file the trouble is.
+fn parse_file<T: DeserializeOwned, I: DeserializeOwned>(
+    json: &str,
+    list: &str,
+    options: &ParseOptions,
+) -> serde_json::Result<(T, usize)> {
+    let err = match serde_json::from_str(json) {
+        Ok(file) => return Ok((file, 0)),
+        Err(err) if !options.lenient() => return Err(err),
+        Err(err) => err,
+    };
+    let mut value: Value = serde_json::from_str(json)?;
+    let mut skipped = 0;
+    if let Some(Value::Array(entries)) = value.get_mut(list) {
+        entries.retain(|entry| {
+            let parses = I::deserialize(entry).is_ok();
+            skipped += usize::from(!parses);
+            parses
+        });
+    }
+    if options.too_many_errors(skipped) {
+        return Err(err);
+    }
+    T::deserialize(value)
+        .map(|file| (file, skipped))
+        .map_err(|_| err)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MarketItem {
+    name: String,
+    buy_price: i32,
+    sell_price: i32,
+    #[serde(default)]
+    mean_price: Option<i32>,
+    stock: i64,
+    demand: i64,
+    #[serde(deserialize_with = "bracket")]
+    stock_bracket: i32,
+    #[serde(deserialize_with = "bracket")]
+    demand_bracket: i32,
+}
+
+#[derive(Deserialize)]
+struct RawMarket {
+    #[serde(deserialize_with = "timestamp")]
+    timestamp: i64,
+    #[serde(rename = "MarketID")]
+    market_id: StationId,
+    #[serde(rename = "StationName")]
+    station: String,
+    #[serde(rename = "StarSystem")]
+    system: String,
+    #[serde(rename = "Items")]
+    items: Vec<MarketItem>,
+}
+
+/// A station's market: a parsed Market.json, or the same from elsewhere.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarketFile {
+    pub station: String,
+    pub system: String,
+    /// The market keyed by the station's MarketID, with item ids from the
+    /// commodity table.
+    pub snapshot: MarketSnapshot,
+    /// Names of listed items that aren't in the commodity table.
+    pub unknown_items: Vec<String>,
+    /// The galactic average price of each item, where the source gives it.
+    pub mean_prices: BTreeMap<ItemId, i32>,
+    /// Items dropped because they didn't parse.
+    pub skipped: usize,
+}
+
+impl MarketFile {
+    /// Builds a market from listings named the way any data source names
+    /// them, resolving the names to item ids.
+    pub fn new(
+        market_id: StationId,
+        station: String,
+        system: String,
+        timestamp: i64,
+        listings: impl IntoIterator<Item = (String, StationItem)>,
+    ) -> Self {
+        let mut items = Vec::new();
+        let mut unknown_items = Vec::new();
+        for (name, item) in listings {
+            match canonical_commodity(&name) {
+                Some(commodity) => items.push(StationItem {
+                    item_id: commodity.id,
+                    ..item
+                }),
+                None => unknown_items.push(name),
+            }
+        }
+        Self {
+            station,
+            system,
+            snapshot: MarketSnapshot::new(market_id, timestamp, items),
+            unknown_items,
+            mean_prices: BTreeMap::new(),
+            skipped: 0,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Self::from_json_with(json, &ParseOptions::default())
+    }
+
+    pub fn from_json_with(json: &str, options: &ParseOptions) -> serde_json::Result<Self> {
+        let (raw, skipped): (RawMarket, _) = parse_file::<_, MarketItem>(json, "Items", options)?;
+        let mean_prices = raw
+            .items
+            .iter()
+            .filter_map(|item| Some((canonical_commodity(&item.name)?.id, item.mean_price?)))
+            .collect();
+        let listings = raw.items.into_iter().map(|item| {
+            // The game quotes a buy price for goods it has none of.
+            let supplied = item.stock > 0;
+            let listing = StationItem {
+                station_id: raw.market_id,
+                item_id: ItemId(0),
+                demand_price: item.sell_price,
+                demand_units: item.demand,
+                demand_level: item.demand_bracket,
+                supply_price: if supplied { item.buy_price } else { 0 },
+                supply_units: item.stock,
+                supply_level: if supplied { item.stock_bracket } else { 0 },
+                modified: raw.timestamp,
+            };
+            (item.name, listing)
+        });
+        let mut market = Self::new(
+            raw.market_id,
+            raw.station,
+            raw.system,
+            raw.timestamp,
+            listings,
+        );
+        market.mean_prices = mean_prices;
+        market.skipped = skipped;
+        Ok(market)
+    }
+}
+
+/// A module for sale.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ModuleListing {
+    /// FDev's module id.
+    pub id: u64,
+    /// The module's symbol, "hpt_pulselaser_fixed_medium".
+    #[serde(rename = "Name")]
+    pub symbol: String,
+    #[serde(rename = "BuyPrice")]
+    pub price: i64,
+}
+
+/// A parsed Outfitting.json.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct OutfittingFile {
+    #[serde(deserialize_with = "timestamp")]
+    pub timestamp: i64,
+    #[serde(rename = "MarketID")]
+    pub market_id: StationId,
+    #[serde(rename = "StationName")]
+    pub station: String,
+    #[serde(rename = "StarSystem")]
+    pub system: String,
+    #[serde(rename = "Items")]
+    pub modules: Vec<ModuleListing>,
+    /// Modules dropped because they didn't parse.
+    #[serde(skip)]
+    pub skipped: usize,
+}
+
+impl OutfittingFile {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Self::from_json_with(json, &ParseOptions::default())
+    }
+
+    pub fn from_json_with(json: &str, options: &ParseOptions) -> serde_json::Result<Self> {
+        let (mut outfitting, skipped): (Self, _) =
+            parse_file::<_, ModuleListing>(json, "Items", options)?;
+        outfitting.skipped = skipped;
+        for module in outfitting.modules.iter_mut() {
+            module.symbol.make_ascii_lowercase();
+        }
+        Ok(outfitting)
+    }
+}
+
+/// A ship for sale.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ShipListing {
+    /// FDev's ship id.
+    pub id: u64,
+    /// The ship's symbol, "empire_courier".
+    #[serde(rename = "ShipType")]
+    pub symbol: String,
+    /// The display name, "Imperial Courier", where the game gives one.
+    #[serde(rename = "ShipType_Localised")]
+    pub name: Option<String>,
+    #[serde(rename = "ShipPrice")]
+    pub price: i64,
+}
+
+/// A parsed Shipyard.json.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct ShipyardFile {
+    #[serde(deserialize_with = "timestamp")]
+    pub timestamp: i64,
+    #[serde(rename = "MarketID")]
+    pub market_id: StationId,
+    #[serde(rename = "StationName")]
+    pub station: String,
+    #[serde(rename = "StarSystem")]
+    pub system: String,
+    #[serde(rename = "PriceList")]
+    pub ships: Vec<ShipListing>,
+    /// Ships dropped because they didn't parse.
+    #[serde(skip)]
+    pub skipped: usize,
+}
+
+impl ShipyardFile {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Self::from_json_with(json, &ParseOptions::default())
+    }
+
+    pub fn from_json_with(json: &str, options: &ParseOptions) -> serde_json::Result<Self> {
+        let (mut shipyard, skipped): (Self, _) =
+            parse_file::<_, ShipListing>(json, "PriceList", options)?;
+        shipyard.skipped = skipped;
+        for ship in shipyard.ships.iter_mut() {
+            ship.symbol.make_ascii_lowercase();
+        }
+        Ok(shipyard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Strictness;
+
+    const MARKET: &str = r#"{
+        "timestamp": "2024-05-01T12:00:00Z", "event": "Market",
+        "MarketID": 128016640, "StationName": "Abraham Lincoln",
+        "StationType": "Orbis", "StarSystem": "Sol",
+        "Items": [
+            {"id": 128049152, "Name": "$platinum_name;", "Name_Localised": "Platinum",
+             "Category": "$MARKET_category_metals;", "BuyPrice": 0, "SellPrice": 31000,
+             "MeanPrice": 30000, "StockBracket": 0, "DemandBracket": 3,
+             "Stock": 0, "Demand": 500, "Consumer": true, "Producer": false, "Rare": false},
+            {"id": 128049202, "Name": "$gold_name;", "BuyPrice": 9100, "SellPrice": 8900,
+             "StockBracket": 2, "DemandBracket": "", "Stock": 120, "Demand": 0},
+            {"id": 128049204, "Name": "$explosives_name;", "BuyPrice": 200, "SellPrice": 180,
+             "StockBracket": 0, "DemandBracket": 0, "Stock": 0, "Demand": 0},
+            {"id": 999, "Name": "$newthing_name;", "BuyPrice": 1, "SellPrice": 1,
+             "StockBracket": 1, "DemandBracket": 1, "Stock": 1, "Demand": 1}
+        ]
+    }"#;
+
+    fn id(name: &str) -> ItemId {
+        canonical_commodity(name).unwrap().id
+    }
+
+    #[test]
+    fn test_parse_iso_timestamp() {
+        assert_eq!(
+            parse_iso_timestamp("2024-05-01T12:00:00Z"),
+            Some(1714564800)
+        );
+        assert_eq!(parse_iso_timestamp("2024-05-01T12:00:00"), Some(1714564800));
+        assert_eq!(parse_iso_timestamp("2024-05-01 12:00:00"), None);
+        assert_eq!(parse_iso_timestamp(""), None);
+    }
+
+    #[test]
+    fn test_format_iso_timestamp() {
+        assert_eq!(format_iso_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_iso_timestamp(1714564800), "2024-05-01T12:00:00Z");
+        assert_eq!(format_iso_timestamp(951868799), "2000-02-29T23:59:59Z");
+        for timestamp in [-86401, 1, 68256000, 4102444800] {
+            let text = format_iso_timestamp(timestamp);
+            assert_eq!(parse_iso_timestamp(&text), Some(timestamp), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_market_file() {
+        let market = MarketFile::from_json(MARKET).unwrap();
+        assert_eq!(market.station, "Abraham Lincoln");
+        assert_eq!(market.system, "Sol");
+        assert_eq!(market.unknown_items, ["$newthing_name;"]);
+        let snapshot = &market.snapshot;
+        assert_eq!(
+            (snapshot.station_id, snapshot.timestamp),
+            (StationId(128016640), 1714564800)
+        );
+        assert_eq!(snapshot.items().len(), 3);
+        assert_eq!(market.mean_prices.get(&id("Platinum")), Some(&30000));
+        assert_eq!(market.mean_prices.get(&id("Gold")), None);
+
+        let gold = snapshot.get(id("Gold")).unwrap();
+        assert_eq!(
+            *gold,
+            StationItem {
+                station_id: StationId(128016640),
+                item_id: id("Gold"),
+                demand_price: 8900,
+                demand_units: 0,
+                demand_level: 0,
+                supply_price: 9100,
+                supply_units: 120,
+                supply_level: 2,
+                modified: 1714564800,
+            }
+        );
+        let platinum = snapshot.get(id("Platinum")).unwrap();
+        assert_eq!((platinum.demand_price, platinum.demand_level), (31000, 3));
+        assert_eq!(platinum.supply_price, 0);
+        // no stock, so no buy price either
+        assert_eq!(snapshot.get(id("Explosives")).unwrap().supply_price, 0);
+    }
+
+    #[test]
+    fn test_market_file_errors() {
+        let bad_time = MARKET.replace("2024-05-01T12:00:00Z", "yesterday");
+        let err = MarketFile::from_json(&bad_time).unwrap_err();
+        assert!(err.to_string().contains("invalid timestamp"), "{}", err);
+        assert_eq!(err.line(), 2);
+
+        let bad_bracket = MARKET.replace("\"DemandBracket\": 3", "\"DemandBracket\": 7");
+        let err = MarketFile::from_json(&bad_bracket).unwrap_err();
+        assert!(err.to_string().contains("bracket"), "{}", err);
+
+        let big_id = MARKET.replace("128016640", "99999999999");
+        assert!(MarketFile::from_json(&big_id).is_err());
+
+        // the journal's Market event has no items, only Market.json does
+        let event = MarketFile::from_json(
+            r#"{"timestamp": "2024-05-01T12:00:00Z", "MarketID": 1,
+                "StationName": "A", "StarSystem": "B"}"#,
+        );
+        assert!(event
+            .unwrap_err()
+            .to_string()
+            .contains("missing field `Items`"));
+    }
+
+    #[test]
+    fn test_lenient_files() {
+        let lenient = ParseOptions {
+            strictness: Strictness::Lenient,
+            ..ParseOptions::default()
+        };
+        let bad_bracket = MARKET.replace("\"DemandBracket\": 3", "\"DemandBracket\": 7");
+        let market = MarketFile::from_json_with(&bad_bracket, &lenient).unwrap();
+        assert_eq!(market.skipped, 1);
+        assert!(market.snapshot.get(id("Platinum")).is_none());
+        assert!(market.snapshot.get(id("Gold")).is_some());
+        assert_eq!(MarketFile::from_json(MARKET).unwrap().skipped, 0);
+
+        // past max_errors the strict error comes back, line and all
+        let strict_err = MarketFile::from_json(&bad_bracket).unwrap_err();
+        let options = ParseOptions {
+            max_errors: Some(0),
+            ..lenient.clone()
+        };
+        let err = MarketFile::from_json_with(&bad_bracket, &options).unwrap_err();
+        assert_eq!(err.to_string(), strict_err.to_string());
+        // and the station header is needed either way
+        let bad_time = MARKET.replace("2024-05-01T12:00:00Z", "yesterday");
+        assert_eq!(
+            MarketFile::from_json_with(&bad_time, &lenient)
+                .unwrap_err()
+                .line(),
+            2
+        );
+
+        let outfitting = OutfittingFile::from_json_with(
+            r#"{"timestamp": "2024-05-01T12:00:00Z", "MarketID": 1,
+                "StationName": "A", "StarSystem": "B",
+                "Items": [{"id": 1, "Name": "x"}, {"id": 2, "Name": "Y", "BuyPrice": 3}]}"#,
+            &lenient,
+        )
+        .unwrap();
+        assert_eq!((outfitting.modules.len(), outfitting.skipped), (1, 1));
+        assert_eq!(outfitting.modules[0].symbol, "y");
+        let shipyard = ShipyardFile::from_json_with(
+            r#"{"timestamp": "2024-05-01T12:00:00Z", "MarketID": 1,
+                "StationName": "A", "StarSystem": "B",
+                "PriceList": [{"id": 1, "ShipType": "x", "ShipPrice": "free"}]}"#,
+            &lenient,
+        )
+        .unwrap();
+        assert_eq!((shipyard.ships.len(), shipyard.skipped), (0, 1));
+    }
+
+    #[test]
+    fn test_outfitting_file() {
+        let outfitting = OutfittingFile::from_json(
+            r#"{"timestamp": "2024-05-01T12:00:00Z", "event": "Outfitting",
+                "MarketID": 128016640, "StationName": "Abraham Lincoln",
+                "StarSystem": "Sol", "Horizons": true,
+                "Items": [
+                    {"id": 128049382, "Name": "Hpt_PulseLaser_Fixed_Medium", "BuyPrice": 16731},
+                    {"id": 128064338, "Name": "int_cargorack_size1_class1", "BuyPrice": 1000}
+                ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            (outfitting.market_id, outfitting.timestamp),
+            (StationId(128016640), 1714564800)
+        );
+        assert_eq!(outfitting.station, "Abraham Lincoln");
+        assert_eq!(
+            outfitting.modules[0],
+            ModuleListing {
+                id: 128049382,
+                symbol: "hpt_pulselaser_fixed_medium".into(),
+                price: 16731,
+            }
+        );
+        assert_eq!(outfitting.modules.len(), 2);
+
+        let err = OutfittingFile::from_json(
+            r#"{"timestamp": "2024-05-01T12:00:00Z", "MarketID": 1,
+                "StationName": "A", "StarSystem": "B", "Items": [{"id": 1, "Name": "x"}]}"#,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("missing field `BuyPrice`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_shipyard_file() {
+        let shipyard = ShipyardFile::from_json(
+            r#"{"timestamp": "2024-05-01T12:00:00Z", "event": "Shipyard",
+                "MarketID": 128016640, "StationName": "Abraham Lincoln",
+                "StarSystem": "Sol", "Horizons": true, "AllowCobraMkIV": false,
+                "PriceList": [
+                    {"id": 128049249, "ShipType": "sidewinder", "ShipPrice": 27480},
+                    {"id": 128671223, "ShipType": "Empire_Courier",
+                     "ShipType_Localised": "Imperial Courier", "ShipPrice": 2462010}
+                ]}"#,
+        )
+        .unwrap();
+        assert_eq!(shipyard.system, "Sol");
+        assert_eq!(
+            shipyard.ships,
+            [
+                ShipListing {
+                    id: 128049249,
+                    symbol: "sidewinder".into(),
+                    name: None,
+                    price: 27480,
+                },
+                ShipListing {
+                    id: 128671223,
+                    symbol: "empire_courier".into(),
+                    name: Some("Imperial Courier".into()),
+                    price: 2462010,
+                },
+            ]
+        );
+        assert!(ShipyardFile::from_json("{}").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! The pure Rust side of traderusty: reading and parsing TradeDangerous and
+//! Elite: Dangerous data, the stellar grid and sectors, trade and route
+//! searches, and the SQLite database. Nothing here depends on Python; the
+//! traderustpy crate wraps it as the `traderusty` extension module.
+//!
+//! The "fs" feature, on by default, brings in everything that touches the
+//! filesystem or native libraries: opening files, line indexes, the SQLite
+//! database and imports. Without it the crate builds for wasm32, where the
+//! grid, sector, route and trade code and the parsers work on data in
+//! memory, such as .prices text read with `prices::read_prices`.
+
+pub mod aggregate;
+pub mod bloom;
+pub mod cancel;
+#[cfg(feature = "fs")]
+pub mod chunkcache;
+pub mod clock;
+#[cfg(feature = "fs")]
+pub mod colstore;
+pub mod commodities;
+#[cfg(feature = "zstd")]
+pub mod compress;
+#[cfg(feature = "fs")]
+pub mod db;
+#[cfg(feature = "fs")]
+pub mod delta;
+#[cfg(feature = "download")]
+pub mod download;
+pub mod eddn;
+pub mod eddnschema;
+#[cfg(feature = "edsm")]
+pub mod edsm;
+pub mod export;
+pub mod fsd;
+pub mod graph;
+pub mod grid;
+pub mod gzip;
+pub mod hash;
+pub mod hll;
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+pub mod http;
+pub mod ids;
+pub mod inara;
+#[cfg(feature = "fs")]
+pub mod input;
+pub mod intern;
+pub mod journal;
+#[cfg(feature = "fs")]
+pub mod lines;
+pub mod market;
+#[cfg(feature = "fs")]
+pub mod merge;
+pub mod metrics;
+#[cfg(feature = "fs")]
+pub mod migrate;
+pub mod multigrid;
+pub mod names;
+pub mod octree;
+pub mod options;
+#[cfg(feature = "fs")]
+pub mod pipeline;
+pub mod pool;
+pub mod prices;
+pub mod query;
+pub mod region;
+pub mod route;
+pub mod router;
+pub mod rusty;
+pub mod sector;
+pub mod source;
+pub mod span;
+#[cfg(feature = "spansh")]
+pub mod spansh;
+pub mod spatial;
+#[cfg(feature = "fs")]
+pub mod spill;
+pub mod store;
+pub mod synthetic;
+pub mod system;
+pub mod tdcsv;
+pub mod topn;
+pub mod trade;
+pub mod transforms;
+#[cfg(feature = "fs")]
+pub mod ttlcache;
+#[cfg(feature = "fs")]
+pub mod verify;
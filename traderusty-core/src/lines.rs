@@ -0,0 +1,655 @@
+//! Reading lines of a file without scanning it from the start.
+//!
+//! Append-only files (logs, .prices dumps) have their newest entries last,
+//! so these readers seek to the end and work backwards a block at a time,
+//! reading only as much of the file as the lines they return take up.
+//!
+//! For lines in the middle, a LineIndex records where every line starts.
+//! Building one takes a full scan, after which any line or run of lines can
+//! be read with a single seek. An index can be saved to a sidecar file and
+//! memory-mapped back in by later runs, which skips the scan as long as the
+//! file's fingerprint (its length and modification time, and the hash of
+//! its first and last blocks and of blocks sampled in between) still
+//! matches.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use memmap2::Mmap;
+
+use crate::hash::{fnv1a, FNV_OFFSET_BASIS};
+use crate::input::open_seekable;
+use crate::options::ReadOptions;
+use crate::rusty::open_reader;
+
+/// Bytes read per step when working backwards.
+pub const BLOCK_SIZE: usize = 64 * 1024;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// A line without its terminator, invalid UTF-8 replaced.
+fn decode(line: &[u8]) -> String {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    String::from_utf8_lossy(line).into_owned()
+}
+
+/// Iterates over the lines of a file from the last to the first, reading it
+/// backwards a block at a time. Lines come without their terminators and
+/// with invalid UTF-8 replaced.
+pub struct RevLines<R> {
+    reader: R,
+    block_size: usize,
+    /// File position of buffer[0]; None until the end has been found.
+    start: Option<u64>,
+    /// The part of the file before the lines already returned.
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read + Seek> RevLines<R> {
+    pub fn new(reader: R, block_size: usize) -> Self {
+        Self {
+            reader,
+            block_size: block_size.max(1),
+            start: None,
+            buffer: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Puts the block before the buffer in front of it.
+    fn read_block(&mut self, start: u64) -> io::Result<u64> {
+        let size = start.min(self.block_size as u64) as usize;
+        let start = start - size as u64;
+        let mut block = vec![0; size + self.buffer.len()];
+        self.reader.seek(SeekFrom::Start(start))?;
+        self.reader.read_exact(&mut block[..size])?;
+        block[size..].copy_from_slice(&self.buffer);
+        self.buffer = block;
+        self.start = Some(start);
+        Ok(start)
+    }
+
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        let mut start = match self.start {
+            Some(start) => start,
+            None => {
+                let len = self.reader.seek(SeekFrom::End(0))?;
+                if len == 0 {
+                    self.done = true;
+                    return Ok(None);
+                }
+                let start = self.read_block(len)?;
+                // The newline ending the last line doesn't start another.
+                if self.buffer.last() == Some(&b'\n') {
+                    self.buffer.pop();
+                }
+                start
+            }
+        };
+        loop {
+            if let Some(idx) = self.buffer.iter().rposition(|&b| b == b'\n') {
+                let line = decode(&self.buffer[idx + 1..]);
+                self.buffer.truncate(idx);
+                return Ok(Some(line));
+            }
+            if start == 0 {
+                self.done = true;
+                let line = self.buffer.strip_prefix(BOM).unwrap_or(&self.buffer);
+                let line = decode(line);
+                self.buffer = Vec::new();
+                return Ok(Some(line));
+            }
+            start = self.read_block(start)?;
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for RevLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let line = self.next_line();
+        if line.is_err() {
+            self.done = true;
+        }
+        line.transpose()
+    }
+}
+
+/// Opens a file to read its lines last to first.
+pub fn rev_lines(filename: impl AsRef<Path>, block_size: usize) -> io::Result<RevLines<File>> {
+    Ok(RevLines::new(open_seekable(filename)?, block_size))
+}
+
+fn tail<R: Read + Seek>(reader: R, n: usize, block_size: usize) -> io::Result<Vec<String>> {
+    let mut lines = RevLines::new(reader, block_size)
+        .take(n)
+        .collect::<io::Result<Vec<String>>>()?;
+    lines.reverse();
+    Ok(lines)
+}
+
+/// Returns the last n lines of a file, oldest first, reading only as much
+/// of the end of the file as they take up.
+#[tracing::instrument(skip_all, fields(filename = %filename.as_ref().display()))]
+pub fn tail_lines(filename: impl AsRef<Path>, n: usize) -> io::Result<Vec<String>> {
+    tail(open_seekable(filename)?, n, BLOCK_SIZE)
+}
+
+/// Identifies a sidecar index file and its layout version.
+const INDEX_MAGIC: &[u8; 8] = b"TRLIDX02";
+
+/// Magic, file length, fingerprint and line count, then the offsets, all
+/// little-endian u64s.
+const INDEX_HEADER_LEN: usize = 32;
+
+/// Bytes hashed from each end of a file for its fingerprint.
+const FINGERPRINT_SAMPLE: u64 = 64 * 1024;
+
+/// Blocks hashed between the two ends, evenly spaced, and their size.
+const FINGERPRINT_INTERIOR_BLOCKS: u64 = 15;
+const FINGERPRINT_INTERIOR_SIZE: u64 = 4 * 1024;
+
+/// Extension added to a file's name for its default sidecar index.
+pub const INDEX_EXTENSION: &str = "lineidx";
+
+/// Hashes a file's length with its first and last blocks and a sample of
+/// the blocks in between, which catches appends, truncation and rewrites
+/// without reading the whole file.
+fn fingerprint<R: Read + Seek>(reader: &mut R) -> io::Result<(u64, u64)> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, &len.to_le_bytes());
+    let head = len.min(FINGERPRINT_SAMPLE);
+    let tail_start = (len - len.min(FINGERPRINT_SAMPLE)).max(head);
+    let interior = (1..=FINGERPRINT_INTERIOR_BLOCKS).filter_map(|n| {
+        let start = len / (FINGERPRINT_INTERIOR_BLOCKS + 1) * n;
+        let end = (start + FINGERPRINT_INTERIOR_SIZE).min(tail_start);
+        (start >= head && start < end).then(|| (start, end - start))
+    });
+    let blocks = [(0, head)]
+        .into_iter()
+        .chain(interior)
+        .chain([(tail_start, len - tail_start)]);
+    for (start, size) in blocks {
+        let mut block = vec![0; size as usize];
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(&mut block)?;
+        hash = fnv1a(hash, &block);
+    }
+    Ok((len, hash))
+}
+
+/// A file's fingerprint, with its modification time mixed into the hash so
+/// an edit that keeps the length and misses every sampled block is still
+/// noticed.
+fn file_fingerprint(filename: impl AsRef<Path>) -> io::Result<(u64, u64)> {
+    let mut file = open_seekable(filename)?;
+    let modified = file
+        .metadata()?
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    let (len, hash) = fingerprint(&mut file)?;
+    Ok((len, fnv1a(hash, &modified.to_le_bytes())))
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// The line offsets, either built in memory or mapped from a sidecar file.
+#[derive(Debug)]
+enum Offsets {
+    Owned(Vec<u64>),
+    Mapped(Mmap),
+}
+
+impl Offsets {
+    fn len(&self) -> usize {
+        match self {
+            Offsets::Owned(offsets) => offsets.len(),
+            Offsets::Mapped(map) => (map.len() - INDEX_HEADER_LEN) / 8,
+        }
+    }
+
+    fn get(&self, n: usize) -> Option<u64> {
+        match self {
+            Offsets::Owned(offsets) => offsets.get(n).copied(),
+            Offsets::Mapped(map) => {
+                (n < self.len()).then(|| read_u64(map, INDEX_HEADER_LEN + n * 8))
+            }
+        }
+    }
+}
+
+/// Where each line of a file starts, so lines can be read by number without
+/// scanning up to them.
+#[derive(Debug)]
+pub struct LineIndex {
+    /// Byte offset of the start of each line.
+    offsets: Offsets,
+    /// Length of the file when it was indexed.
+    file_len: u64,
+}
+
+fn stale(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The default sidecar index path for a file: its name plus ".lineidx".
+pub fn index_path(filename: impl AsRef<Path>) -> PathBuf {
+    let mut path = filename.as_ref().as_os_str().to_owned();
+    path.push(".");
+    path.push(INDEX_EXTENSION);
+    path.into()
+}
+
+impl LineIndex {
+    /// Indexes the lines of a reader whose first byte is at file position
+    /// `start`.
+    pub fn from_reader<R: BufRead>(mut reader: R, start: u64) -> io::Result<Self> {
+        let mut offsets = Vec::new();
+        let mut position = start;
+        let mut at_line_start = true;
+        loop {
+            let chunk = reader.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+            if at_line_start {
+                offsets.push(position);
+            }
+            let len = chunk.len();
+            for (idx, _) in chunk.iter().enumerate().filter(|(_, &b)| b == b'\n') {
+                if idx + 1 < len {
+                    offsets.push(position + idx as u64 + 1);
+                }
+            }
+            at_line_start = chunk[len - 1] == b'\n';
+            position += len as u64;
+            reader.consume(len);
+        }
+        Ok(Self {
+            offsets: Offsets::Owned(offsets),
+            file_len: position,
+        })
+    }
+
+    /// Indexes a file, skipping any byte-order mark.
+    #[tracing::instrument(skip_all, fields(filename = %filename.as_ref().display()))]
+    pub fn build(filename: impl AsRef<Path>, options: &ReadOptions) -> io::Result<Self> {
+        let (reader, skipped) = open_reader(filename, options)?;
+        Self::from_reader(reader, skipped as u64)
+    }
+
+    /// Writes the index to a sidecar file along with the fingerprint of the
+    /// file it indexes, which must not have changed since it was indexed.
+    pub fn save(&self, filename: impl AsRef<Path>, index_path: impl AsRef<Path>) -> io::Result<()> {
+        let (len, hash) = file_fingerprint(filename)?;
+        if len != self.file_len {
+            return Err(stale("file has changed since it was indexed"));
+        }
+        let index_path = index_path.as_ref();
+        let file = File::create(index_path)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", index_path.display(), e)))?;
+        let mut out = BufWriter::new(file);
+        out.write_all(INDEX_MAGIC)?;
+        for value in [self.file_len, hash, self.len() as u64] {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        for n in 0..self.len() {
+            out.write_all(&self.offsets.get(n).unwrap().to_le_bytes())?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()
+    }
+
+    /// Maps a sidecar index written by save. Returns None if it doesn't
+    /// exist or was made from a different version of the file, in which
+    /// case the file needs indexing again.
+    pub fn load(
+        filename: impl AsRef<Path>,
+        index_path: impl AsRef<Path>,
+    ) -> io::Result<Option<Self>> {
+        let index = match File::open(index_path.as_ref()) {
+            Ok(index) => index,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        // SAFETY: the mapping is read-only and every read is bounds checked
+        // against its length; a sidecar truncated underneath us would fault,
+        // the same caveat as any memory-mapped file.
+        let map = unsafe { Mmap::map(&index)? };
+        if map.len() < INDEX_HEADER_LEN
+            || &map[..8] != INDEX_MAGIC
+            || !(map.len() - INDEX_HEADER_LEN).is_multiple_of(8)
+            || read_u64(&map, 24) != ((map.len() - INDEX_HEADER_LEN) / 8) as u64
+        {
+            tracing::info!(index = %index_path.as_ref().display(), "ignoring malformed line index");
+            return Ok(None);
+        }
+        let (file_len, hash) = (read_u64(&map, 8), read_u64(&map, 16));
+        if file_fingerprint(filename)? != (file_len, hash) {
+            tracing::info!(index = %index_path.as_ref().display(), "line index is out of date");
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            offsets: Offsets::Mapped(map),
+            file_len,
+        }))
+    }
+
+    /// Loads a file's index from its sidecar if that's still current,
+    /// otherwise indexes the file and saves the sidecar for next time.
+    /// Failing to save isn't an error; the index is still returned.
+    #[tracing::instrument(skip_all, fields(filename = %filename.as_ref().display()))]
+    pub fn open(
+        filename: impl AsRef<Path>,
+        index_path: impl AsRef<Path>,
+        options: &ReadOptions,
+    ) -> io::Result<Self> {
+        let (filename, index_path) = (filename.as_ref(), index_path.as_ref());
+        if let Some(index) = Self::load(filename, index_path)? {
+            return Ok(index);
+        }
+        let index = Self::build(filename, options)?;
+        if let Err(e) = index.save(filename, index_path) {
+            tracing::warn!(index = %index_path.display(), error = %e, "couldn't save line index");
+        }
+        Ok(index)
+    }
+
+    /// True if the offsets come from a mapped sidecar file.
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.offsets, Offsets::Mapped(_))
+    }
+
+    /// Number of lines, counting an unterminated last line.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Byte offset line n starts at.
+    pub fn offset(&self, n: usize) -> Option<u64> {
+        self.offsets.get(n)
+    }
+
+    pub fn file_len(&self) -> u64 {
+        self.file_len
+    }
+
+    /// Reads up to `count` lines starting at line `start`; fewer if the
+    /// file ends first.
+    pub fn read_lines<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        start: usize,
+        count: usize,
+    ) -> io::Result<Vec<String>> {
+        let end = start.saturating_add(count).min(self.len());
+        if start >= end {
+            return Ok(Vec::new());
+        }
+        if reader.seek(SeekFrom::End(0))? != self.file_len {
+            return Err(stale("file has changed since it was indexed"));
+        }
+        let from = self.offsets.get(start).unwrap();
+        let to = self.offset(end).unwrap_or(self.file_len);
+        // Only possible if a sidecar was tampered with.
+        if from > to || to > self.file_len {
+            return Err(stale("line index is corrupt"));
+        }
+        let mut data = vec![0; (to - from) as usize];
+        reader.seek(SeekFrom::Start(from))?;
+        reader.read_exact(&mut data)?;
+        let text = data.strip_suffix(b"\n").unwrap_or(&data);
+        Ok(text.split(|&b| b == b'\n').map(decode).collect())
+    }
+}
+
+/// Reads `count` lines of a file from line `start` (0-based) using its
+/// index; fewer if the file ends first.
+pub fn read_lines_at(
+    filename: impl AsRef<Path>,
+    index: &LineIndex,
+    start: usize,
+    count: usize,
+) -> io::Result<Vec<String>> {
+    index.read_lines(&mut open_seekable(filename)?, start, count)
+}
+
+/// Reads line n (0-based) of a file using its index, or None if the file
+/// has fewer lines.
+pub fn read_line_at(
+    filename: impl AsRef<Path>,
+    index: &LineIndex,
+    n: usize,
+) -> io::Result<Option<String>> {
+    Ok(read_lines_at(filename, index, n, 1)?.pop())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use tempfile::NamedTempFile;
+
+    fn tail_of(text: &[u8], n: usize, block_size: usize) -> Vec<String> {
+        tail(Cursor::new(text), n, block_size).unwrap()
+    }
+
+    #[test]
+    fn test_tail() {
+        let text = b"one\ntwo\r\nthree\nfour\n";
+        for block_size in [1, 2, 3, 5, 64] {
+            assert_eq!(tail_of(text, 2, block_size), ["three", "four"]);
+            assert_eq!(
+                tail_of(text, 4, block_size),
+                ["one", "two", "three", "four"]
+            );
+            assert_eq!(
+                tail_of(text, 10, block_size),
+                ["one", "two", "three", "four"]
+            );
+            assert!(tail_of(text, 0, block_size).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_tail_edges() {
+        assert!(tail_of(b"", 3, 4).is_empty());
+        assert_eq!(tail_of(b"\n", 3, 4), [""]);
+        assert_eq!(tail_of(b"a\n\n", 3, 1), ["a", ""]);
+        // the last line needn't be terminated
+        assert_eq!(tail_of(b"a\nb", 1, 1), ["b"]);
+        assert_eq!(tail_of(b"\xEF\xBB\xBFa\nb\n", 5, 2), ["a", "b"]);
+        assert_eq!(tail_of(b"a\n\xff\n", 1, 8), ["\u{fffd}"]);
+    }
+
+    #[test]
+    fn test_tail_reads_only_the_end() {
+        // a reader that fails if it's asked for anything before `from`
+        struct End<'a>(Cursor<&'a [u8]>, u64);
+        impl Read for End<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                assert!(
+                    self.0.position() >= self.1,
+                    "read from {}",
+                    self.0.position()
+                );
+                self.0.read(buf)
+            }
+        }
+        impl Seek for End<'_> {
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+        let text = "line\n".repeat(1000);
+        let from = text.len() as u64 - 16;
+        let lines = tail(End(Cursor::new(text.as_bytes()), from), 2, 16).unwrap();
+        assert_eq!(lines, ["line", "line"]);
+    }
+
+    #[test]
+    fn test_rev_lines() {
+        let text = b"\xEF\xBB\xBFone\ntwo\n\nthree";
+        for block_size in [1, 2, 4, 64] {
+            let lines: Vec<String> = RevLines::new(Cursor::new(text), block_size)
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(lines, ["three", "", "two", "one"]);
+        }
+        assert_eq!(RevLines::new(Cursor::new(b""), 4).count(), 0);
+        let mut lines = RevLines::new(Cursor::new(b"\n"), 4);
+        assert_eq!(lines.next().unwrap().unwrap(), "");
+        assert!(lines.next().is_none());
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn test_tail_lines() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        for i in 0..10000 {
+            writeln!(tmpfile, "line {}", i).unwrap();
+        }
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            tail_lines(tmpfile.path(), 3).unwrap(),
+            ["line 9997", "line 9998", "line 9999"]
+        );
+        assert!(tail_lines("-", 3).is_err());
+
+        let mut lines = rev_lines(tmpfile.path(), 100).unwrap();
+        assert_eq!(lines.next().unwrap().unwrap(), "line 9999");
+        assert_eq!(lines.nth(9998).unwrap().unwrap(), "line 0");
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn test_line_index() {
+        let text = b"\xEF\xBB\xBFone\ntwo\r\n\nfour";
+        for buffer_size in [1, 2, 5, 64] {
+            let reader = io::BufReader::with_capacity(buffer_size, &text[3..]);
+            let index = LineIndex::from_reader(reader, 3).unwrap();
+            let offsets: Vec<u64> = (0..index.len()).filter_map(|n| index.offset(n)).collect();
+            assert_eq!(offsets, [3, 7, 12, 13]);
+            assert_eq!(index.file_len(), text.len() as u64);
+
+            let mut file = Cursor::new(text);
+            assert_eq!(
+                index.read_lines(&mut file, 0, 4).unwrap(),
+                ["one", "two", "", "four"]
+            );
+            assert_eq!(index.read_lines(&mut file, 1, 2).unwrap(), ["two", ""]);
+            assert_eq!(index.read_lines(&mut file, 3, 10).unwrap(), ["four"]);
+            assert!(index.read_lines(&mut file, 4, 1).unwrap().is_empty());
+            assert!(index.read_lines(&mut file, 0, 0).unwrap().is_empty());
+        }
+
+        let index = LineIndex::from_reader(&b"a\n\n"[..], 0).unwrap();
+        assert_eq!(index.len(), 2);
+        assert_eq!(LineIndex::from_reader(&b""[..], 0).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_read_line_at() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        for i in 0..10000 {
+            writeln!(tmpfile, "line {}", i).unwrap();
+        }
+        tmpfile.flush().unwrap();
+        let index = LineIndex::build(tmpfile.path(), &ReadOptions::default()).unwrap();
+        assert_eq!(index.len(), 10000);
+        assert_eq!(
+            read_line_at(tmpfile.path(), &index, 1234).unwrap().unwrap(),
+            "line 1234"
+        );
+        assert_eq!(read_line_at(tmpfile.path(), &index, 10000).unwrap(), None);
+        assert_eq!(
+            read_lines_at(tmpfile.path(), &index, 9998, 5).unwrap(),
+            ["line 9998", "line 9999"]
+        );
+
+        writeln!(tmpfile, "line 10000").unwrap();
+        tmpfile.flush().unwrap();
+        let err = read_line_at(tmpfile.path(), &index, 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let text = "line\n".repeat(100000);
+        let (len, hash) = fingerprint(&mut Cursor::new(text.as_bytes())).unwrap();
+        assert_eq!(len, text.len() as u64);
+        // a change at either end or in the length is noticed
+        let mut changed = text.clone().into_bytes();
+        changed[0] = b'L';
+        assert_ne!(fingerprint(&mut Cursor::new(&changed)).unwrap().1, hash);
+        changed = text.clone().into_bytes();
+        changed[text.len() - 2] = b'E';
+        assert_ne!(fingerprint(&mut Cursor::new(&changed)).unwrap().1, hash);
+        // as is one in the middle that keeps the length
+        changed = text.clone().into_bytes();
+        changed[text.len() / 2] = b'X';
+        assert_ne!(fingerprint(&mut Cursor::new(&changed)).unwrap().1, hash);
+        assert_ne!(
+            fingerprint(&mut Cursor::new(b"")).unwrap(),
+            fingerprint(&mut Cursor::new(b"\n")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_line_index_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.txt");
+        let sidecar = index_path(&path);
+        assert_eq!(sidecar, dir.path().join("log.txt.lineidx"));
+        let lines: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(&path, &lines).unwrap();
+
+        assert!(LineIndex::load(&path, &sidecar).unwrap().is_none());
+        let built = LineIndex::open(&path, &sidecar, &ReadOptions::default()).unwrap();
+        assert!(!built.is_mapped());
+        let loaded = LineIndex::open(&path, &sidecar, &ReadOptions::default()).unwrap();
+        assert!(loaded.is_mapped());
+        assert_eq!(
+            (loaded.len(), loaded.file_len()),
+            (built.len(), built.file_len())
+        );
+        assert_eq!(loaded.offset(999), built.offset(999));
+        assert_eq!(
+            read_line_at(&path, &loaded, 500).unwrap().unwrap(),
+            "line 500"
+        );
+        drop(loaded);
+
+        // appending makes the sidecar stale, and it's rebuilt
+        std::fs::write(&path, format!("{}line 1000\n", lines)).unwrap();
+        assert!(LineIndex::load(&path, &sidecar).unwrap().is_none());
+        let rebuilt = LineIndex::open(&path, &sidecar, &ReadOptions::default()).unwrap();
+        assert_eq!(rebuilt.len(), 1001);
+        assert!(LineIndex::load(&path, &sidecar).unwrap().is_some());
+
+        // so does touching it, since an edit that keeps the length and
+        // misses the sampled blocks only shows in the modification time
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(10))
+            .unwrap();
+        assert!(LineIndex::load(&path, &sidecar).unwrap().is_none());
+
+        // a sidecar that isn't an index is ignored
+        std::fs::write(&sidecar, b"not an index").unwrap();
+        assert!(LineIndex::load(&path, &sidecar).unwrap().is_none());
+    }
+}
@@ -0,0 +1,321 @@
+//! Station market data: individual commodity listings and whole-station
+//! snapshots that can be compared against each other.
+
+use std::cmp::Ordering;
+
+use crate::ids::{ItemId, StationId};
+
+/// One commodity's listing at a station, mirroring the columns of
+/// TradeDangerous' StationItem table. Levels use the same encoding as
+/// `parse_supply_level`: -1 unknown, 0 none, 1 low, 2 medium, 3 high.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StationItem {
+    pub station_id: StationId,
+    pub item_id: ItemId,
+    /// Price the station pays when you sell to it.
+    pub demand_price: i32,
+    pub demand_units: i64,
+    pub demand_level: i32,
+    /// Price the station charges when you buy from it.
+    pub supply_price: i32,
+    pub supply_units: i64,
+    pub supply_level: i32,
+    /// When the listing was observed, in seconds since the unix epoch.
+    pub modified: i64,
+}
+
+impl StationItem {
+    /// True if either of the prices differ between the two listings.
+    pub fn prices_differ(&self, other: &StationItem) -> bool {
+        self.demand_price != other.demand_price || self.supply_price != other.supply_price
+    }
+}
+
+/// A level as a 2-bit code, for compact storage. Levels 1 to 3 are their
+/// own codes. Code 0 stands for both 0, none, and -1, unknown: a reading
+/// gives 0 units with a level of none ("-") and otherwise doesn't, so the
+/// units tell the two apart. None for a level that code 0 would read back
+/// wrongly (unknown with 0 units, none with some) or that isn't a level.
+pub fn level_code(level: i32, units: i64) -> Option<u8> {
+    match level {
+        1..=3 => Some(level as u8),
+        0 if units == 0 => Some(0),
+        -1 if units != 0 => Some(0),
+        _ => None,
+    }
+}
+
+/// The level a `level_code` stands for, given the units it came with.
+/// Only the low two bits of `code` are looked at.
+pub fn level_from_code(code: u8, units: i64) -> i32 {
+    match code & 3 {
+        0 if units == 0 => 0,
+        0 => -1,
+        code => code as i32,
+    }
+}
+
+/// StationItem rows stored column by column, the shape parsers produce and
+/// bulk writers consume.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StationItemColumns {
+    pub station_id: Vec<StationId>,
+    pub item_id: Vec<ItemId>,
+    pub demand_price: Vec<i32>,
+    pub demand_units: Vec<i64>,
+    pub demand_level: Vec<i32>,
+    pub supply_price: Vec<i32>,
+    pub supply_units: Vec<i64>,
+    pub supply_level: Vec<i32>,
+    pub modified: Vec<i64>,
+}
+
+impl StationItemColumns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.station_id.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.station_id.is_empty()
+    }
+
+    pub fn push(&mut self, item: &StationItem) {
+        self.station_id.push(item.station_id);
+        self.item_id.push(item.item_id);
+        self.demand_price.push(item.demand_price);
+        self.demand_units.push(item.demand_units);
+        self.demand_level.push(item.demand_level);
+        self.supply_price.push(item.supply_price);
+        self.supply_units.push(item.supply_units);
+        self.supply_level.push(item.supply_level);
+        self.modified.push(item.modified);
+    }
+
+    /// Reassembles row `idx`.
+    pub fn row(&self, idx: usize) -> StationItem {
+        StationItem {
+            station_id: self.station_id[idx],
+            item_id: self.item_id[idx],
+            demand_price: self.demand_price[idx],
+            demand_units: self.demand_units[idx],
+            demand_level: self.demand_level[idx],
+            supply_price: self.supply_price[idx],
+            supply_units: self.supply_units[idx],
+            supply_level: self.supply_level[idx],
+            modified: self.modified[idx],
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a StationItem> for StationItemColumns {
+    fn from_iter<I: IntoIterator<Item = &'a StationItem>>(iter: I) -> Self {
+        let mut columns = Self::new();
+        for item in iter {
+            columns.push(item);
+        }
+        columns
+    }
+}
+
+/// The complete commodity market of a single station at a point in time.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MarketSnapshot {
+    pub station_id: StationId,
+    /// When the snapshot was taken, in seconds since the unix epoch.
+    pub timestamp: i64,
+    /// Listings ordered by item_id, one per item.
+    items: Vec<StationItem>,
+}
+
+impl MarketSnapshot {
+    /// Builds a snapshot from a station's listings. Listings are ordered by
+    /// item and stamped with the station's id; where an item is listed more
+    /// than once, the most recently modified listing is kept.
+    pub fn new(station_id: StationId, timestamp: i64, mut items: Vec<StationItem>) -> Self {
+        for item in items.iter_mut() {
+            item.station_id = station_id;
+        }
+        // Newest first within each item so dedup_by keeps the newest.
+        items.sort_by(|a, b| a.item_id.cmp(&b.item_id).then(b.modified.cmp(&a.modified)));
+        items.dedup_by_key(|item| item.item_id);
+        Self {
+            station_id,
+            timestamp,
+            items,
+        }
+    }
+
+    pub fn items(&self) -> &[StationItem] {
+        &self.items
+    }
+
+    /// Looks up the listing for a given item.
+    pub fn get(&self, item_id: ItemId) -> Option<&StationItem> {
+        self.items
+            .binary_search_by_key(&item_id, |item| item.item_id)
+            .ok()
+            .map(|idx| &self.items[idx])
+    }
+}
+
+/// What changed in a station's market between two snapshots.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MarketDiff {
+    /// Items listed in the new snapshot but not the old.
+    pub added: Vec<StationItem>,
+    /// Items listed in the old snapshot but not the new.
+    pub removed: Vec<StationItem>,
+    /// (old, new) pairs for items whose buy or sell price changed.
+    pub changed: Vec<(StationItem, StationItem)>,
+}
+
+impl MarketDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares two snapshots of a market, typically of the same station, and
+/// reports the listings that were added, removed, or changed price.
+pub fn diff(old: &MarketSnapshot, new: &MarketSnapshot) -> MarketDiff {
+    let mut result = MarketDiff::default();
+    let (mut olds, mut news) = (old.items.iter().peekable(), new.items.iter().peekable());
+
+    // Both lists are ordered by item_id, so walk them side by side.
+    loop {
+        let order = match (olds.peek(), news.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(o), Some(n)) => o.item_id.cmp(&n.item_id),
+        };
+        match order {
+            Ordering::Less => result.removed.push(olds.next().unwrap().clone()),
+            Ordering::Greater => result.added.push(news.next().unwrap().clone()),
+            Ordering::Equal => {
+                let (o, n) = (olds.next().unwrap(), news.next().unwrap());
+                if o.prices_differ(n) {
+                    result.changed.push((o.clone(), n.clone()));
+                }
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(item_id: u32, demand_price: i32, supply_price: i32) -> StationItem {
+        StationItem {
+            item_id: ItemId(item_id),
+            demand_price,
+            supply_price,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_snapshot_orders_and_stamps_items() {
+        let snapshot = MarketSnapshot::new(StationId(42), 1000, vec![item(3, 1, 2), item(1, 3, 4)]);
+        let ids: Vec<u32> = snapshot.items().iter().map(|i| i.item_id.0).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert!(snapshot
+            .items()
+            .iter()
+            .all(|i| i.station_id == StationId(42)));
+        assert_eq!(snapshot.get(ItemId(3)).unwrap().supply_price, 2);
+        assert!(snapshot.get(ItemId(2)).is_none());
+    }
+
+    #[test]
+    fn test_snapshot_keeps_newest_duplicate() {
+        let mut older = item(1, 100, 0);
+        older.modified = 10;
+        let mut newer = item(1, 200, 0);
+        newer.modified = 20;
+        let snapshot =
+            MarketSnapshot::new(StationId(1), 20, vec![older, newer.clone(), item(2, 1, 1)]);
+        assert_eq!(snapshot.items().len(), 2);
+        assert_eq!(snapshot.get(ItemId(1)).unwrap().demand_price, 200);
+    }
+
+    #[test]
+    fn test_columns_from_rows() {
+        let mut first = item(1, 10, 20);
+        first.station_id = StationId(7);
+        first.modified = 1700000000;
+        let rows = [first, item(2, 30, 40)];
+        let columns: StationItemColumns = rows.iter().collect();
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns.item_id, vec![ItemId(1), ItemId(2)]);
+        assert_eq!(columns.station_id, vec![StationId(7), StationId(0)]);
+        assert_eq!(columns.demand_price, vec![10, 30]);
+        assert_eq!(columns.supply_price, vec![20, 40]);
+        assert_eq!(columns.modified, vec![1700000000, 0]);
+        assert_eq!(columns.row(0), rows[0]);
+        assert_eq!(columns.row(1), rows[1]);
+    }
+
+    #[test]
+    fn test_level_codes() {
+        // what parse_supply_level gives for "?", "-", "0", "300?", "12L", "5H"
+        for (units, level) in [(-1, -1), (0, 0), (300, -1), (12, 1), (5, 3)] {
+            let code = level_code(level, units).unwrap();
+            assert!(code < 4);
+            assert_eq!(level_from_code(code, units), level);
+        }
+        assert_eq!(level_code(2, 0), Some(2));
+        // "0?" and a level of none with stock can't be coded
+        assert_eq!(level_code(-1, 0), None);
+        assert_eq!(level_code(0, 10), None);
+        assert_eq!(level_code(4, 10), None);
+        assert_eq!(level_from_code(0b110, 1), 2);
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let snapshot = MarketSnapshot::new(StationId(1), 0, vec![item(1, 10, 20), item(2, 30, 40)]);
+        assert!(diff(&snapshot, &snapshot).is_empty());
+    }
+
+    #[test]
+    fn test_diff_changes() {
+        let old = MarketSnapshot::new(
+            StationId(1),
+            0,
+            vec![item(1, 10, 20), item(2, 30, 40), item(4, 50, 60)],
+        );
+        let mut restocked = item(2, 30, 40);
+        restocked.supply_units = 5000;
+        let new = MarketSnapshot::new(
+            StationId(1),
+            10,
+            vec![restocked, item(3, 70, 80), item(4, 55, 60), item(5, 1, 1)],
+        );
+
+        let result = diff(&old, &new);
+        let added: Vec<u32> = result.added.iter().map(|i| i.item_id.0).collect();
+        let removed: Vec<u32> = result.removed.iter().map(|i| i.item_id.0).collect();
+        assert_eq!(added, vec![3, 5]);
+        assert_eq!(removed, vec![1]);
+        // a change in stock alone isn't a price change
+        assert_eq!(result.changed.len(), 1);
+        assert_eq!(result.changed[0].0.demand_price, 50);
+        assert_eq!(result.changed[0].1.demand_price, 55);
+    }
+
+    #[test]
+    fn test_diff_against_empty() {
+        let empty = MarketSnapshot::new(StationId(1), 0, vec![]);
+        let full = MarketSnapshot::new(StationId(1), 0, vec![item(1, 10, 20), item(2, 30, 40)]);
+        assert_eq!(diff(&empty, &full).added.len(), 2);
+        assert_eq!(diff(&full, &empty).removed.len(), 2);
+    }
+}
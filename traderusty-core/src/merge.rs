@@ -0,0 +1,414 @@
+//! Merging .prices fragments, as tools like EDMC export one per docking,
+//! into one consolidated set of records: a directory of them with
+//! merge_prices_dir, or any DataSources with merge_sources.
+//!
+//! For each station and item the listing with the newest timestamp wins.
+//! Lines without a timestamp are dated by their file's modification time,
+//! and on a tie the file that sorts later by name (the source listed later)
+//! wins. Names are compared in canonical form, so "WP 12" and "Wp  12" are
+//! the same system. Files are parsed in parallel.
+//!
+//! Given a memory budget, files are parsed a thread's worth at a time, and
+//! once the winning records outgrow the budget they're spilled to a sorted
+//! run on disk, all the runs being merged at the end. merge_prices_dir_into
+//! hands the merged records to a callback as the runs are merged, so they
+//! never need to be in memory at once; merge_prices_dir collects them.
+//!
+//! Lines that fail to parse are left out and reported with the file they
+//! came from; the merge only fails once more than the ParseOptions'
+//! `max_errors` have been seen, or on an I/O error.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use tracing::{info, Span};
+
+use crate::metrics;
+use crate::names::canonical_name;
+use crate::options::{ParseOptions, ReadOptions};
+use crate::prices::{read_source, PriceRecord, PricesError};
+use crate::source::{file_modified, DataSource, FileSource};
+use crate::spill::{entry_size, Runs};
+
+/// A failure in one of the merged files.
+#[derive(Debug)]
+pub struct MergeError {
+    pub path: PathBuf,
+    pub error: PricesError,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+pub struct MergedPrices {
+    /// The winning records, sorted by system, station and item.
+    pub records: Vec<PriceRecord>,
+    /// Lines that failed to parse and were left out.
+    pub errors: Vec<MergeError>,
+}
+
+/// The .prices files directly inside a directory, sorted by name.
+pub fn prices_files(dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let entries = fs::read_dir(dir)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", dir.display(), e)))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let is_prices = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("prices"));
+        if is_prices && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Seconds since the unix epoch the file was last modified, or 0 if the
+/// platform can't say.
+pub fn modified_time(path: &Path) -> i64 {
+    file_modified(path).unwrap_or(0)
+}
+
+/// Parses a whole source into its records and the errors between them.
+#[tracing::instrument(skip_all, fields(source = source.name()))]
+pub fn read_file<S: DataSource>(
+    source: &S,
+    parse_options: &ParseOptions,
+) -> Result<(Vec<PriceRecord>, Vec<PricesError>), MergeError> {
+    let failed = |error: PricesError| MergeError {
+        path: PathBuf::from(source.name()),
+        error,
+    };
+    let reader = read_source(source, parse_options).map_err(|e| failed(e.into()))?;
+    let (mut records, mut errors) = (Vec::new(), Vec::new());
+    for record in reader {
+        match record {
+            Ok(record) => records.push(record),
+            Err(PricesError::Io(e)) => return Err(failed(PricesError::Io(e))),
+            Err(e) => errors.push(e),
+        }
+    }
+    Ok((records, errors))
+}
+
+/// Merges the .prices files in a directory, newest listing wins.
+#[tracing::instrument(skip_all, fields(dir = %dir.as_ref().display()))]
+pub fn merge_prices_dir(
+    dir: impl AsRef<Path>,
+    options: &ReadOptions,
+    parse_options: &ParseOptions,
+) -> Result<MergedPrices, MergeError> {
+    let mut records = Vec::new();
+    let errors = merge_prices_dir_into(dir, options, parse_options, |record| records.push(record))?;
+    Ok(MergedPrices { records, errors })
+}
+
+/// Merges the .prices files in a directory, newest listing wins, passing
+/// the winning records to `emit` sorted by system, station and item.
+/// Returns the lines that failed to parse and were left out.
+pub fn merge_prices_dir_into(
+    dir: impl AsRef<Path>,
+    options: &ReadOptions,
+    parse_options: &ParseOptions,
+    emit: impl FnMut(PriceRecord),
+) -> Result<Vec<MergeError>, MergeError> {
+    let _timer = metrics::time_stage("merge_prices_dir");
+    let dir = dir.as_ref();
+    let files = prices_files(dir).map_err(|e| MergeError {
+        path: dir.to_path_buf(),
+        error: e.into(),
+    })?;
+    let sources: Vec<FileSource> = files
+        .iter()
+        .map(|path| FileSource::new(path, options))
+        .collect();
+    merge_sources_into(&sources, options.memory_budget, parse_options, emit)
+}
+
+/// Merges .prices data from any sources, as merge_prices_dir does a
+/// directory's files: newest listing wins, undated lines are dated by their
+/// source and, on a tie, the source listed later wins.
+pub fn merge_sources<S: DataSource + Sync>(
+    sources: &[S],
+    memory_budget: Option<usize>,
+    parse_options: &ParseOptions,
+) -> Result<MergedPrices, MergeError> {
+    let mut records = Vec::new();
+    let errors = merge_sources_into(sources, memory_budget, parse_options, |record| {
+        records.push(record)
+    })?;
+    Ok(MergedPrices { records, errors })
+}
+
+/// merge_sources, passing the winning records to `emit` sorted by system,
+/// station and item rather than collecting them. Past `memory_budget`
+/// bytes of records, they're spilled to disk.
+pub fn merge_sources_into<S: DataSource + Sync>(
+    sources: &[S],
+    memory_budget: Option<usize>,
+    parse_options: &ParseOptions,
+    mut emit: impl FnMut(PriceRecord),
+) -> Result<Vec<MergeError>, MergeError> {
+    // Without a budget every source is parsed at once; with one, only as
+    // many as there are threads to parse them.
+    let chunk_size = match memory_budget {
+        Some(_) => rayon::current_num_threads(),
+        None => sources.len(),
+    };
+    let spill_failed = |e: io::Error| MergeError {
+        path: std::env::temp_dir(),
+        error: e.into(),
+    };
+
+    let mut latest: BTreeMap<(String, String, String), (i64, PriceRecord)> = BTreeMap::new();
+    let (mut held, mut runs) = (0, Runs::default());
+    let mut errors = Vec::new();
+    let span = Span::current();
+    for chunk in sources.chunks(chunk_size.max(1)) {
+        let parsed: Vec<_> = chunk
+            .par_iter()
+            .map(|source| span.in_scope(|| read_file(source, parse_options)))
+            .collect();
+        for (source, parsed) in chunk.iter().zip(parsed) {
+            let (records, file_errors) = parsed?;
+            for error in file_errors {
+                let error = MergeError {
+                    path: PathBuf::from(source.name()),
+                    error,
+                };
+                if parse_options.too_many_errors(errors.len() + 1) {
+                    return Err(error);
+                }
+                errors.push(error);
+            }
+            let file_time = source.modified().unwrap_or(0);
+            for record in records {
+                let key = (
+                    canonical_name(&record.system),
+                    canonical_name(&record.station),
+                    canonical_name(&record.item),
+                );
+                let time = record.modified.unwrap_or(file_time);
+                let size = entry_size(&key, &record);
+                match latest.entry(key) {
+                    Entry::Occupied(mut entry) => {
+                        if entry.get().0 <= time {
+                            held = held + size - entry_size(entry.key(), &entry.get().1);
+                            entry.insert((time, record));
+                        }
+                    }
+                    Entry::Vacant(entry) => {
+                        held += size;
+                        entry.insert((time, record));
+                    }
+                }
+            }
+            if memory_budget.is_some_and(|budget| held > budget) {
+                runs.spill(mem::take(&mut latest)).map_err(spill_failed)?;
+                held = 0;
+            }
+        }
+    }
+    let mut merged = 0;
+    let mut emit = |record| {
+        merged += 1;
+        emit(record)
+    };
+    if runs.is_empty() {
+        latest.into_values().for_each(|(_, record)| emit(record));
+    } else {
+        runs.spill(latest).map_err(spill_failed)?;
+        info!(runs = runs.len(), "merging spilled runs");
+        runs.merge(&mut emit).map_err(spill_failed)?;
+    }
+    info!(
+        sources = sources.len(),
+        records = merged,
+        errors = errors.len(),
+        "merged .prices data"
+    );
+    Ok(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemorySource;
+
+    fn write(dir: &Path, name: &str, text: &str) {
+        fs::write(dir.join(name), text).unwrap();
+    }
+
+    fn merged(dir: &Path, max_errors: Option<usize>) -> Result<MergedPrices, MergeError> {
+        let parse_options = ParseOptions {
+            max_errors,
+            ..Default::default()
+        };
+        merge_prices_dir(dir, &ReadOptions::default(), &parse_options)
+    }
+
+    #[test]
+    fn test_merge_sources() {
+        let sources = [
+            MemorySource::new(
+                "a.prices",
+                &b"@ SOL/Abraham Lincoln\nGold 100 0\nSilver 50 0\n"[..],
+            )
+            .modified_at(1714564800),
+            MemorySource::new(
+                "b.prices",
+                &b"@ SOL/Abraham Lincoln\nGold 200 0\nTea x\n"[..],
+            )
+            .modified_at(1714564800),
+        ];
+        let merged = merge_sources(&sources, None, &ParseOptions::default()).unwrap();
+        // dated the same, so the later source wins
+        let prices: Vec<(&str, i32)> = merged
+            .records
+            .iter()
+            .map(|r| (r.item.as_str(), r.demand_price))
+            .collect();
+        assert_eq!(prices, [("Gold", 200), ("Silver", 50)]);
+        assert_eq!(merged.errors.len(), 1);
+        assert_eq!(merged.errors[0].path, Path::new("b.prices"));
+    }
+
+    #[test]
+    fn test_merge_newest_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "a.prices",
+            "@ SOL/Abraham Lincoln\n\
+             Gold 100 0 - - 2024-05-01 12:00:00\n\
+             Silver 50 0 - - 2024-05-01 12:00:00\n",
+        );
+        write(
+            dir.path(),
+            "b.prices",
+            "@ ＳＯＬ/abraham  lincoln\n\
+             Gold 200 0 - - 2024-06-01 12:00:00\n\
+             Silver 60 0 - - 2024-04-01 12:00:00\n\
+             @ LHS 3447/Bluford Orbital\n\
+             Gold 300 0 - - 2024-04-01 12:00:00\n",
+        );
+        // not a fragment
+        write(dir.path(), "notes.txt", "@ SOL/Abraham Lincoln\nGold 1 0\n");
+
+        let merged = merged(dir.path(), None).unwrap();
+        assert!(merged.errors.is_empty());
+        let prices: Vec<(&str, &str, i32)> = merged
+            .records
+            .iter()
+            .map(|r| (r.station.as_str(), r.item.as_str(), r.demand_price))
+            .collect();
+        assert_eq!(
+            prices,
+            [
+                ("Bluford Orbital", "Gold", 300),
+                ("abraham  lincoln", "Gold", 200),
+                ("Abraham Lincoln", "Silver", 50),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_untimed_and_ties() {
+        let dir = tempfile::tempdir().unwrap();
+        // untimed lines take the file's modification time, which is now:
+        // newer than the timestamped line, and tied between the two files
+        write(
+            dir.path(),
+            "1.prices",
+            "@ SOL/A\nGold 100 0 - - 2024-05-01 12:00:00\nTea 1 0\n",
+        );
+        write(dir.path(), "2.prices", "@ SOL/A\nGold 200 0\nTea 2 0\n");
+        let merged = merged(dir.path(), None).unwrap();
+        let prices: Vec<i32> = merged.records.iter().map(|r| r.demand_price).collect();
+        assert_eq!(prices, [200, 2]);
+    }
+
+    #[test]
+    fn test_merge_memory_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, price) in [("a", 100), ("b", 300), ("c", 200)] {
+            let text = format!(
+                "@ SOL/A\n\
+                 Gold {price} 0 - - 2024-05-01 12:00:00\n\
+                 Tea {price} 0 - - 2024-0{month}-01 12:00:00\n\
+                 @ SOL/{name}\nWine {price} 0\n",
+                month = price / 100,
+            );
+            write(dir.path(), &format!("{}.prices", name), &text);
+        }
+        let unbounded = merged(dir.path(), None).unwrap().records;
+        for budget in [1, 1000, usize::MAX] {
+            let options = ReadOptions {
+                memory_budget: Some(budget),
+                ..Default::default()
+            };
+            let bounded = merge_prices_dir(dir.path(), &options, &ParseOptions::default())
+                .unwrap()
+                .records;
+            assert_eq!(bounded, unbounded, "budget {}", budget);
+        }
+        let mut streamed = Vec::new();
+        let options = ReadOptions {
+            memory_budget: Some(1),
+            ..Default::default()
+        };
+        merge_prices_dir_into(dir.path(), &options, &ParseOptions::default(), |record| {
+            streamed.push(record)
+        })
+        .unwrap();
+        assert_eq!(streamed, unbounded);
+        let prices: Vec<(&str, i32)> = unbounded
+            .iter()
+            .map(|r| (r.item.as_str(), r.demand_price))
+            .collect();
+        assert_eq!(
+            prices,
+            [
+                ("Gold", 200),
+                ("Tea", 300),
+                ("Wine", 100),
+                ("Wine", 300),
+                ("Wine", 200)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "a.prices", "@ SOL/A\nGold x\nTea 1 0\n");
+        write(dir.path(), "b.prices", "@ SOL/B\nWine 1 2 3X -\n");
+        let merged_all = merged(dir.path(), None).unwrap();
+        assert_eq!(merged_all.records.len(), 1);
+        let errors: Vec<String> = merged_all.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("a.prices: line 2, column 1"));
+        assert!(errors[1].contains("b.prices: line 2, column 10"));
+
+        let err = merged(dir.path(), Some(1)).err().unwrap();
+        assert!(err.path.ends_with("b.prices"));
+        assert!(matches!(err.error, PricesError::Parse { .. }));
+
+        let missing = merged(&dir.path().join("missing"), None).err().unwrap();
+        assert!(matches!(missing.error, PricesError::Io(_)));
+    }
+}
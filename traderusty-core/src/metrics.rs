@@ -0,0 +1,144 @@
+//! Process-wide counters and stage timers, so a slow import or search in
+//! the field can be diagnosed from a snapshot instead of a profiler.
+//!
+//! Counters are relaxed atomics. Hot loops keep a local count and add it
+//! every so often rather than touching the shared counter per item. Stage
+//! timers add up the wall time and number of runs of named stages; a stage
+//! running on several threads at once counts each thread's time.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::clock::Stopwatch;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Counter {
+    /// .prices records parsed.
+    RecordsParsed,
+    /// Bytes read from files.
+    BytesRead,
+    /// Trade loads served from a cache.
+    CacheHits,
+    /// Trade loads computed for want of a cached one.
+    CacheMisses,
+    /// Labels expanded by route searches.
+    RouteNodesExpanded,
+}
+
+impl Counter {
+    pub const ALL: [Counter; 5] = [
+        Counter::RecordsParsed,
+        Counter::BytesRead,
+        Counter::CacheHits,
+        Counter::CacheMisses,
+        Counter::RouteNodesExpanded,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Counter::RecordsParsed => "records_parsed",
+            Counter::BytesRead => "bytes_read",
+            Counter::CacheHits => "cache_hits",
+            Counter::CacheMisses => "cache_misses",
+            Counter::RouteNodesExpanded => "route_nodes_expanded",
+        }
+    }
+}
+
+static COUNTERS: [AtomicU64; Counter::ALL.len()] =
+    [const { AtomicU64::new(0) }; Counter::ALL.len()];
+
+static STAGES: Mutex<BTreeMap<&'static str, StageTime>> = Mutex::new(BTreeMap::new());
+
+pub fn add(counter: Counter, n: u64) {
+    if n > 0 {
+        COUNTERS[counter as usize].fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+pub fn get(counter: Counter) -> u64 {
+    COUNTERS[counter as usize].load(Ordering::Relaxed)
+}
+
+/// How often a stage ran and for how long in all.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StageTime {
+    pub runs: u64,
+    pub total: Duration,
+}
+
+/// Times a stage until dropped.
+pub struct StageTimer {
+    stage: &'static str,
+    stopwatch: Stopwatch,
+}
+
+impl Drop for StageTimer {
+    fn drop(&mut self) {
+        let elapsed = self.stopwatch.elapsed();
+        let mut stages = STAGES.lock().unwrap_or_else(|e| e.into_inner());
+        let time = stages.entry(self.stage).or_default();
+        time.runs += 1;
+        time.total += elapsed;
+    }
+}
+
+pub fn time_stage(stage: &'static str) -> StageTimer {
+    StageTimer {
+        stage,
+        stopwatch: Stopwatch::start(),
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Every counter by name, in Counter::ALL order.
+    pub counters: Vec<(&'static str, u64)>,
+    /// The stages that have run, by name.
+    pub stages: BTreeMap<&'static str, StageTime>,
+}
+
+pub fn snapshot() -> Snapshot {
+    Snapshot {
+        counters: Counter::ALL
+            .iter()
+            .map(|&counter| (counter.name(), get(counter)))
+            .collect(),
+        stages: STAGES.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+    }
+}
+
+/// Zeroes the counters and forgets the stage times.
+pub fn reset() {
+    for counter in &COUNTERS {
+        counter.store(0, Ordering::Relaxed);
+    }
+    STAGES.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Other tests bump the same counters concurrently, so only check that
+    // these went up by at least as much as added here.
+    #[test]
+    fn test_metrics() {
+        let before = get(Counter::RouteNodesExpanded);
+        add(Counter::RouteNodesExpanded, 5);
+        assert!(get(Counter::RouteNodesExpanded) >= before + 5);
+        {
+            let _timer = time_stage("test_metrics");
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        drop(time_stage("test_metrics"));
+        let snapshot = snapshot();
+        assert_eq!(snapshot.counters.len(), Counter::ALL.len());
+        assert_eq!(snapshot.counters[4].0, "route_nodes_expanded");
+        let time = snapshot.stages["test_metrics"];
+        assert_eq!(time.runs, 2);
+        assert!(time.total >= Duration::from_millis(2));
+    }
+}
@@ -0,0 +1,204 @@
+//! Versioned schema migrations for the database the crate writes to.
+//!
+//! The schema version is kept in SQLite's `user_version` pragma. Each
+//! migration is either embedded SQL or a Rust hook (for changes SQL alone
+//! can't express conditionally, like adding a column only if it's missing),
+//! and runs in its own transaction together with the version bump, so an
+//! interrupted upgrade resumes where it stopped. New migrations are only
+//! ever appended to MIGRATIONS.
+
+use rusqlite::{Connection, Transaction};
+use tracing::info;
+
+use crate::db::{in_transaction, DbError};
+
+pub enum Step {
+    Sql(&'static str),
+    Hook(fn(&Transaction) -> rusqlite::Result<()>),
+}
+
+pub struct Migration {
+    /// The schema version after this migration has run.
+    pub version: u32,
+    pub description: &'static str,
+    pub step: Step,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create StationItem",
+        step: Step::Sql(
+            "CREATE TABLE IF NOT EXISTS StationItem (
+                station_id INTEGER NOT NULL,
+                item_id INTEGER NOT NULL,
+                demand_price INT NOT NULL,
+                demand_units INT NOT NULL,
+                demand_level INT NOT NULL,
+                supply_price INT NOT NULL,
+                supply_units INT NOT NULL,
+                supply_level INT NOT NULL,
+                modified DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (station_id, item_id)
+            ) WITHOUT ROWID",
+        ),
+    },
+    Migration {
+        version: 2,
+        description: "add StationItem.from_live",
+        step: Step::Hook(|tx| {
+            add_column_if_missing(tx, "StationItem", "from_live", "INTEGER DEFAULT 0 NOT NULL")
+        }),
+    },
+    Migration {
+        version: 3,
+        description: "create SyntheticSystem and SyntheticStation",
+        step: Step::Sql(
+            "CREATE TABLE IF NOT EXISTS SyntheticSystem (
+                name TEXT NOT NULL PRIMARY KEY,
+                id INTEGER NOT NULL UNIQUE
+            ) WITHOUT ROWID;
+            CREATE TABLE IF NOT EXISTS SyntheticStation (
+                system TEXT NOT NULL,
+                station TEXT NOT NULL,
+                id INTEGER NOT NULL UNIQUE,
+                PRIMARY KEY (system, station)
+            ) WITHOUT ROWID",
+        ),
+    },
+];
+
+/// Adds a column to a table unless it already has it; databases created by
+/// older TD releases may or may not.
+pub fn add_column_if_missing(
+    tx: &Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists: bool = tx.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        tx.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))?;
+    }
+    Ok(())
+}
+
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Brings the database up to the latest schema version, returning it.
+/// Databases from a newer version of the crate are refused rather than
+/// written to with an outdated idea of the schema.
+pub fn migrate(conn: &mut Connection) -> Result<u32, DbError> {
+    run_migrations(conn, MIGRATIONS)
+}
+
+#[tracing::instrument(skip_all)]
+pub fn run_migrations(conn: &mut Connection, migrations: &[Migration]) -> Result<u32, DbError> {
+    let latest = migrations.last().map_or(0, |m| m.version);
+    let mut version = schema_version(conn)?;
+    if version > latest {
+        return Err(DbError::UnknownVersion {
+            found: version,
+            latest,
+        });
+    }
+    let pending: Vec<&Migration> = migrations.iter().filter(|m| m.version > version).collect();
+    for migration in pending {
+        info!(
+            version = migration.version,
+            description = migration.description,
+            "migrating"
+        );
+        in_transaction(conn, |tx| {
+            match migration.step {
+                Step::Sql(sql) => tx.execute_batch(sql)?,
+                Step::Hook(hook) => hook(tx)?,
+            }
+            tx.pragma_update(None, "user_version", migration.version)?;
+            Ok(())
+        })?;
+        version = migration.version;
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM pragma_table_info(?1)")
+            .unwrap();
+        let names = stmt.query_map([table], |row| row.get(0)).unwrap();
+        names.map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_migrate_new_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        assert_eq!(migrate(&mut conn).unwrap(), 3);
+        assert_eq!(schema_version(&conn).unwrap(), 3);
+        assert!(columns(&conn, "StationItem").contains(&"from_live".to_string()));
+        // running again does nothing
+        assert_eq!(migrate(&mut conn).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_migrate_existing_td_database() {
+        // TD databases already have the table, with or without from_live
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE StationItem (station_id INTEGER, item_id INTEGER, \
+             from_live INTEGER DEFAULT 0 NOT NULL);
+             INSERT INTO StationItem VALUES (1, 2, 1);",
+        )
+        .unwrap();
+        assert_eq!(migrate(&mut conn).unwrap(), 3);
+        let live: i64 = conn
+            .query_row("SELECT from_live FROM StationItem", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(live, 1);
+    }
+
+    #[test]
+    fn test_migrate_refuses_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", 99).unwrap();
+        match migrate(&mut conn) {
+            Err(DbError::UnknownVersion { found, latest }) => assert_eq!((found, latest), (99, 3)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        const BROKEN: &[Migration] = &[
+            Migration {
+                version: 1,
+                description: "good",
+                step: Step::Sql("CREATE TABLE A (x INTEGER)"),
+            },
+            Migration {
+                version: 2,
+                description: "bad",
+                step: Step::Sql("CREATE TABLE B (x INTEGER); SELECT * FROM Missing"),
+            },
+        ];
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert!(run_migrations(&mut conn, BROKEN).is_err());
+        assert_eq!(schema_version(&conn).unwrap(), 1);
+        assert!(columns(&conn, "B").is_empty());
+        assert_eq!(columns(&conn, "A"), vec!["x"]);
+    }
+}
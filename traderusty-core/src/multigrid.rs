@@ -0,0 +1,238 @@
+//! Grid keys at several resolutions sharing one key space: 4ly, 32ly and
+//! 256ly cells, each 8 cells to a side of the cell a level up. A search can
+//! pre-filter on coarse cells and refine into the fine cells inside them by
+//! walking `child_keys`, or go the other way with `parent_key`.
+//!
+//! The 32ly level's cells are the stellar grid's cells (`stellar_grid_key`),
+//! though the keys differ: a level key carries its level in bits 48..56
+//! above the x, y and z cell indexes packed as by `pack_grid_key`, so keys
+//! from different levels never collide.
+
+use crate::rusty::{pack_grid_key, unpack_grid_key, GridCells};
+
+/// Cell sizes in ly, finest first; a key's level indexes into this.
+pub const CELL_SIZES: [f64; 3] = [4., 32., 256.];
+
+/// The number of levels, and one more than the coarsest level.
+pub const LEVELS: u8 = CELL_SIZES.len() as u8;
+
+/// Cells a level up are this many cells to a side.
+pub const BRANCHING: i32 = 8;
+
+const LEVEL_SHIFT: u32 = 48;
+const INDEX_MASK: u64 = (1 << LEVEL_SHIFT) - 1;
+
+fn cell_index(component: f64, level: u8) -> i16 {
+    (component / CELL_SIZES[level as usize]).floor() as i16
+}
+
+fn pack(level: u8, (gx, gy, gz): (i16, i16, i16)) -> u64 {
+    ((level as u64) << LEVEL_SHIFT) | (pack_grid_key(gx, gy, gz) & INDEX_MASK)
+}
+
+/// The key of the cell at `level` containing x, y, z.
+///
+/// Panics if `level` isn't below LEVELS.
+pub fn level_key(x: f64, y: f64, z: f64, level: u8) -> u64 {
+    assert!(level < LEVELS, "grid level {} out of range", level);
+    pack(
+        level,
+        (
+            cell_index(x, level),
+            cell_index(y, level),
+            cell_index(z, level),
+        ),
+    )
+}
+
+/// The level a key was made at, or None if its top bits don't name one
+/// (it isn't a level key).
+pub fn key_level(key: u64) -> Option<u8> {
+    let level = key >> LEVEL_SHIFT;
+    (level < LEVELS as u64).then_some(level as u8)
+}
+
+/// Recovers (level, (x, y, z) cell indexes) from a level key, or None if
+/// it isn't one.
+pub fn unpack_level_key(key: u64) -> Option<(u8, (i16, i16, i16))> {
+    key_level(key).map(|level| (level, unpack_grid_key(key & INDEX_MASK)))
+}
+
+/// The corners of the cell a key names, min then max, or None if it isn't
+/// a level key.
+pub fn key_bounds(key: u64) -> Option<([f64; 3], [f64; 3])> {
+    let (level, (gx, gy, gz)) = unpack_level_key(key)?;
+    let size = CELL_SIZES[level as usize];
+    let min = [gx as f64 * size, gy as f64 * size, gz as f64 * size];
+    Some((min, [min[0] + size, min[1] + size, min[2] + size]))
+}
+
+/// The key of the cell a level up containing this one, or None at the
+/// coarsest level or if it isn't a level key.
+pub fn parent_key(key: u64) -> Option<u64> {
+    let (level, (gx, gy, gz)) = unpack_level_key(key)?;
+    let up = |index: i16| (index as i32).div_euclid(BRANCHING) as i16;
+    (level + 1 < LEVELS).then(|| pack(level + 1, (up(gx), up(gy), up(gz))))
+}
+
+/// The keys of the BRANCHING^3 cells a level down that make up this one,
+/// empty at the finest level or if it isn't a level key.
+pub fn child_keys(key: u64) -> Vec<u64> {
+    let (level, (gx, gy, gz)) = match unpack_level_key(key) {
+        Some((level, cell)) if level > 0 => (level, cell),
+        _ => return Vec::new(),
+    };
+    let down = |index: i16| index as i32 * BRANCHING..(index as i32 + 1) * BRANCHING;
+    let mut keys = Vec::with_capacity((BRANCHING * BRANCHING * BRANCHING) as usize);
+    for cy in down(gy) {
+        for cx in down(gx) {
+            for cz in down(gz) {
+                keys.push(pack(level - 1, (cx as i16, cy as i16, cz as i16)));
+            }
+        }
+    }
+    keys
+}
+
+/// The keys of every cell at `level` overlapping the box between two
+/// corners.
+///
+/// Panics if `level` isn't below LEVELS.
+pub fn level_keys_in_box(min: [f64; 3], max: [f64; 3], level: u8) -> Vec<u64> {
+    iter_level_keys_in_box(min, max, level).collect()
+}
+
+/// Like level_keys_in_box, but produces the keys as they're iterated
+/// rather than all up front.
+///
+/// Panics if `level` isn't below LEVELS.
+pub fn iter_level_keys_in_box(
+    min: [f64; 3],
+    max: [f64; 3],
+    level: u8,
+) -> impl Iterator<Item = u64> {
+    assert!(level < LEVELS, "grid level {} out of range", level);
+    GridCells::in_box(min, max, CELL_SIZES[level as usize]).map(move |cell| pack(level, cell))
+}
+
+/// The keys of the cells at `level` with any part within radius of a
+/// point, produced as they're iterated.
+///
+/// Panics if `level` isn't below LEVELS.
+pub fn iter_level_keys_in_sphere(
+    center: [f64; 3],
+    radius: f64,
+    level: u8,
+) -> impl Iterator<Item = u64> {
+    assert!(level < LEVELS, "grid level {} out of range", level);
+    GridCells::in_sphere(center, radius, CELL_SIZES[level as usize])
+        .map(move |cell| pack(level, cell))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rusty::stellar_grid_key;
+
+    #[test]
+    fn test_level_key() {
+        assert_eq!(level_key(0., 0., 0., 0), 0);
+        assert_eq!(
+            unpack_level_key(level_key(5., -1., 9., 0)),
+            Some((0, (1, -1, 2)))
+        );
+        assert_eq!(
+            unpack_level_key(level_key(-33., 300., 0., 2)),
+            Some((2, (-1, 1, 0)))
+        );
+        // same cell index, different levels: different keys
+        assert_ne!(level_key(1., 1., 1., 0), level_key(1., 1., 1., 1));
+        assert_eq!(key_level(level_key(-1., -1., -1., 1)), Some(1));
+        // the 32ly level has the stellar grid's cells
+        let (x, y, z) = (-40213.5, 1200.25, 65000.);
+        assert_eq!(
+            unpack_level_key(level_key(x, y, z, 1)).unwrap().1,
+            unpack_grid_key(stellar_grid_key(x, y, z))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn test_level_key_bad_level() {
+        level_key(0., 0., 0., LEVELS);
+    }
+
+    #[test]
+    fn test_key_bounds() {
+        assert_eq!(
+            key_bounds(level_key(5., -1., 9., 0)),
+            Some(([4., -4., 8.], [8., 0., 12.]))
+        );
+        assert_eq!(
+            key_bounds(level_key(-1., 0., 0., 2)),
+            Some(([-256., 0., 0.], [0., 256., 256.]))
+        );
+    }
+
+    #[test]
+    fn test_not_level_keys() {
+        // a stellar grid key with a negative y has all its top bits set
+        let key = stellar_grid_key(0., -100., 0.);
+        assert_eq!(key_level(key), None);
+        assert_eq!(unpack_level_key(key), None);
+        assert_eq!(key_bounds(key), None);
+        assert_eq!(parent_key(key), None);
+        assert!(child_keys(key).is_empty());
+        assert_eq!(key_level((LEVELS as u64) << LEVEL_SHIFT), None);
+    }
+
+    #[test]
+    fn test_parent_key() {
+        let fine = level_key(-1., 33., 255., 0);
+        let medium = parent_key(fine).unwrap();
+        assert_eq!(medium, level_key(-1., 33., 255., 1));
+        let coarse = parent_key(medium).unwrap();
+        assert_eq!(coarse, level_key(-1., 33., 255., 2));
+        assert_eq!(parent_key(coarse), None);
+    }
+
+    #[test]
+    fn test_child_keys() {
+        let coarse = level_key(-100., 0., 100., 2);
+        let children = child_keys(coarse);
+        assert_eq!(children.len(), 512);
+        assert!(children
+            .iter()
+            .all(|&child| parent_key(child) == Some(coarse)));
+        assert!(children.contains(&level_key(-100., 0., 100., 1)));
+        // exactly the medium cells inside the coarse one
+        let (min, max) = key_bounds(coarse).unwrap();
+        let mut inside = level_keys_in_box(min, max.map(|v| v - 1.), 1);
+        let mut children = children;
+        inside.sort_unstable();
+        children.sort_unstable();
+        assert_eq!(children, inside);
+        assert!(child_keys(level_key(0., 0., 0., 0)).is_empty());
+    }
+
+    #[test]
+    fn test_level_keys_in_box() {
+        let keys = level_keys_in_box([-1., 0., 0.], [3., 3., 0.], 0);
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&level_key(-1., 0., 0., 0)));
+        assert!(keys.contains(&level_key(3., 0., 0., 0)));
+        assert_eq!(level_keys_in_box([-600.; 3], [600.; 3], 2).len(), 6 * 6 * 6);
+    }
+
+    #[test]
+    fn test_iter_level_keys_in_sphere() {
+        // a 520ly sphere at the origin reaches the coarse cells either side,
+        // but not the corners of the 6x6x6 box around it
+        let keys: Vec<u64> = iter_level_keys_in_sphere([0.; 3], 520., 2).collect();
+        assert!(keys.contains(&level_key(-513., 0., 0., 2)));
+        assert!(!keys.contains(&level_key(-513., -513., -513., 2)));
+        assert!(keys.iter().all(|&key| key_level(key) == Some(2)));
+        let fine = iter_level_keys_in_sphere([1000., 0., -50.], 2., 0);
+        assert_eq!(fine.count(), 8);
+    }
+}
@@ -0,0 +1,378 @@
+//! Name lookup for systems and stations: partial (substring) matches and
+//! fuzzy matches ranked by trigram similarity, without scanning every name.
+//!
+//! Names are matched in canonical form: NFKC normalized, case folded and with
+//! runs of whitespace collapsed, so "WP 12", "Wp  12" and the full-width
+//! "ＷＰ　１２" are all the same name.
+
+use std::collections::HashMap;
+
+use caseless::default_case_fold_str;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::topn::top_n;
+
+/// Packs three characters into a single trigram key; chars are at most 21 bits.
+fn pack(a: char, b: char, c: char) -> u64 {
+    ((a as u64) << 42) | ((b as u64) << 21) | (c as u64)
+}
+
+/// The canonical form of a system or station name for comparison. Folding
+/// can undo normalization, so the folded name is normalized again.
+pub fn canonical_name(name: &str) -> String {
+    let folded = default_case_fold_str(&name.nfkc().collect::<String>());
+    let normalized: String = folded.nfkc().collect();
+    normalized.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The distinct trigrams of a folded string, without padding.
+fn trigrams(folded: &str) -> Vec<u64> {
+    let chars: Vec<char> = folded.chars().collect();
+    let mut grams: Vec<u64> = chars.windows(3).map(|w| pack(w[0], w[1], w[2])).collect();
+    grams.sort_unstable();
+    grams.dedup();
+    grams
+}
+
+/// The distinct trigrams of a folded string padded with two leading and one
+/// trailing space, so that short names and word starts carry weight.
+fn padded_trigrams(folded: &str) -> Vec<u64> {
+    trigrams(&format!("  {} ", folded))
+}
+
+/// A fuzzy match and its similarity, from 0 (nothing shared) to 1 (identical).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub id: u64,
+    pub name: String,
+    pub score: f64,
+}
+
+/// A typo-correction candidate and its edit distance from the query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion {
+    pub id: u64,
+    pub name: String,
+    pub distance: usize,
+}
+
+/// Levenshtein distance between two strings, giving up as soon as it must
+/// exceed `bound`, in which case None is returned.
+pub fn bounded_levenshtein(a: &[char], b: &[char], bound: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > bound {
+        return None;
+    }
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut row = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        row[0] = i + 1;
+        let mut row_min = row[0];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != cb);
+            row[j + 1] = substitute.min(prev[j + 1] + 1).min(row[j] + 1);
+            row_min = row_min.min(row[j + 1]);
+        }
+        if row_min > bound {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    Some(prev[b.len()]).filter(|d| *d <= bound)
+}
+
+#[derive(Default)]
+pub struct NameIndex {
+    ids: Vec<u64>,
+    names: Vec<String>,
+    folded: Vec<String>,
+    /// Number of distinct padded trigrams per entry, for similarity scoring.
+    gram_counts: Vec<u32>,
+    /// Unpadded trigram -> entries containing it, for substring search.
+    substrings: HashMap<u64, Vec<u32>>,
+    /// Padded trigram -> entries containing it, for similarity search.
+    similar: HashMap<u64, Vec<u32>>,
+}
+
+impl NameIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Adds a name to the index under a caller-chosen id (system or station id).
+    pub fn insert(&mut self, id: u64, name: &str) {
+        let entry = self.names.len() as u32;
+        let folded = canonical_name(name);
+        for gram in trigrams(&folded) {
+            self.substrings.entry(gram).or_default().push(entry);
+        }
+        let grams = padded_trigrams(&folded);
+        for gram in grams.iter() {
+            self.similar.entry(*gram).or_default().push(entry);
+        }
+        self.gram_counts.push(grams.len() as u32);
+        self.ids.push(id);
+        self.names.push(name.to_string());
+        self.folded.push(folded);
+    }
+
+    fn to_match(&self, entry: u32, score: f64) -> Match {
+        Match {
+            id: self.ids[entry as usize],
+            name: self.names[entry as usize].clone(),
+            score,
+        }
+    }
+
+    /// Finds names containing every whitespace-separated word of the query,
+    /// case-insensitively; "ere dub" matches "Eredub" and "Dub Erewhon".
+    /// Results are in insertion order.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<Match> {
+        let query = canonical_name(query);
+        let words: Vec<&str> = query.split(' ').filter(|w| !w.is_empty()).collect();
+        if words.is_empty() {
+            return Vec::new();
+        }
+
+        // Narrow down to entries that have every trigram of every word; words
+        // too short to have a trigram can only be checked by the final filter.
+        let mut candidates: Option<Vec<u32>> = None;
+        for gram in words.iter().flat_map(|w| trigrams(w)) {
+            let postings = match self.substrings.get(&gram) {
+                Some(postings) => postings,
+                None => return Vec::new(),
+            };
+            candidates = Some(match candidates {
+                None => postings.clone(),
+                Some(current) => intersect(&current, postings),
+            });
+        }
+        let candidates = candidates.unwrap_or_else(|| (0..self.names.len() as u32).collect());
+
+        candidates
+            .into_iter()
+            .filter(|entry| {
+                let folded = &self.folded[*entry as usize];
+                words.iter().all(|w| folded.contains(w))
+            })
+            .take(limit)
+            .map(|entry| self.to_match(entry, 1.0))
+            .collect()
+    }
+
+    /// Ranks names by trigram (Jaccard) similarity to the query, returning at
+    /// most `limit` matches scoring at least `threshold`, best first.
+    pub fn similar(&self, query: &str, limit: usize, threshold: f64) -> Vec<Match> {
+        let grams = padded_trigrams(&canonical_name(query));
+        let mut shared: HashMap<u32, u32> = HashMap::new();
+        for gram in grams.iter() {
+            if let Some(postings) = self.similar.get(gram) {
+                for entry in postings {
+                    *shared.entry(*entry).or_default() += 1;
+                }
+            }
+        }
+
+        let scored = shared
+            .into_iter()
+            .map(|(entry, common)| {
+                let total = grams.len() as u32 + self.gram_counts[entry as usize] - common;
+                (entry, common as f64 / total as f64)
+            })
+            .filter(|(_, score)| *score >= threshold);
+        top_n(scored, limit, |a, b| {
+            b.1.total_cmp(&a.1).then(a.0.cmp(&b.0))
+        })
+        .into_iter()
+        .map(|(entry, score)| self.to_match(entry, score))
+        .collect()
+    }
+
+    /// Suggests corrections for a possibly misspelled name: names within
+    /// `max_distance` edits of the query (ignoring case and spacing), closest
+    /// first, at most `limit` of them.
+    pub fn suggest(&self, query: &str, max_distance: usize, limit: usize) -> Vec<Suggestion> {
+        let folded = canonical_name(query);
+        let query_chars: Vec<char> = folded.chars().collect();
+
+        // Each edit can break at most three padded trigrams, so a name within
+        // max_distance must still share this many with the query; when the
+        // query is too short for that to rule anything out, check everything.
+        let grams = padded_trigrams(&folded);
+        let needed = grams.len().saturating_sub(3 * max_distance) as u32;
+        let candidates: Vec<u32> = if needed == 0 {
+            (0..self.names.len() as u32).collect()
+        } else {
+            let mut shared: HashMap<u32, u32> = HashMap::new();
+            for gram in grams.iter() {
+                if let Some(postings) = self.similar.get(gram) {
+                    for entry in postings {
+                        *shared.entry(*entry).or_default() += 1;
+                    }
+                }
+            }
+            shared
+                .into_iter()
+                .filter(|(_, common)| *common >= needed)
+                .map(|(entry, _)| entry)
+                .collect()
+        };
+
+        let suggestions = candidates.into_iter().filter_map(|entry| {
+            let chars: Vec<char> = self.folded[entry as usize].chars().collect();
+            bounded_levenshtein(&query_chars, &chars, max_distance).map(|distance| Suggestion {
+                id: self.ids[entry as usize],
+                name: self.names[entry as usize].clone(),
+                distance,
+            })
+        });
+        top_n(suggestions, limit, |a, b| {
+            a.distance
+                .cmp(&b.distance)
+                .then_with(|| a.name.cmp(&b.name))
+                .then(a.id.cmp(&b.id))
+        })
+    }
+}
+
+/// Intersection of two ascending posting lists.
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_index() -> NameIndex {
+        let mut index = NameIndex::new();
+        assert_eq!(index.len(), 0);
+        for (id, name) in [
+            (1, "Eravate"),
+            (2, "Eranin"),
+            (3, "Dubbuennel"),
+            (4, "Ereduba Erebus"),
+            (5, "LHS 3447"),
+            (6, "Lave"),
+        ] {
+            index.insert(id, name);
+        }
+        assert_eq!(index.len(), 6);
+        index
+    }
+
+    fn ids(matches: &[Match]) -> Vec<u64> {
+        matches.iter().map(|m| m.id).collect()
+    }
+
+    #[test]
+    fn test_canonical_name() {
+        assert_eq!(canonical_name("  WP   12 "), "wp 12");
+        assert_eq!(canonical_name("Wp\t12"), "wp 12");
+        // full-width letters, digits and the ideographic space
+        assert_eq!(canonical_name("ＷＰ\u{3000}１２"), "wp 12");
+        // composed and decomposed accents, and folds that lowercasing misses
+        assert_eq!(canonical_name("Lavé"), "lavé");
+        assert_eq!(canonical_name("Lave\u{301}"), "lavé");
+        assert_eq!(canonical_name("STRAẞE"), canonical_name("strasse"));
+        assert_eq!(canonical_name("ﬁnch"), "finch");
+    }
+
+    #[test]
+    fn test_search_substring() {
+        let index = sample_index();
+        assert_eq!(ids(&index.search("era", 10)), vec![1, 2]);
+        assert_eq!(ids(&index.search("ERAV", 10)), vec![1]);
+        assert_eq!(ids(&index.search("ere dub", 10)), vec![4]);
+        assert_eq!(ids(&index.search("dub", 10)), vec![3, 4]);
+        assert_eq!(ids(&index.search("3447", 10)), vec![5]);
+        assert!(index.search("xyz", 10).is_empty());
+        assert!(index.search("", 10).is_empty());
+    }
+
+    #[test]
+    fn test_search_short_words() {
+        let index = sample_index();
+        // no trigrams, so every name gets checked
+        assert_eq!(ids(&index.search("la", 10)), vec![6]);
+        assert_eq!(ids(&index.search("e", 2)), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_similar() {
+        let index = sample_index();
+        let matches = index.similar("Eravat", 3, 0.2);
+        assert_eq!(matches[0].id, 1);
+        assert!(matches[0].score > 0.5 && matches[0].score < 1.0);
+
+        let exact = index.similar("lave", 1, 0.0);
+        assert_eq!(ids(&exact), vec![6]);
+        assert_eq!(exact[0].score, 1.0);
+
+        assert!(index.similar("qqqq", 10, 0.1).is_empty());
+    }
+
+    fn lev(a: &str, b: &str, bound: usize) -> Option<usize> {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        bounded_levenshtein(&a, &b, bound)
+    }
+
+    #[test]
+    fn test_bounded_levenshtein() {
+        assert_eq!(lev("eravate", "eravate", 0), Some(0));
+        assert_eq!(lev("eravat", "eravate", 2), Some(1));
+        assert_eq!(lev("erevate", "eravate", 2), Some(1));
+        assert_eq!(lev("kitten", "sitting", 3), Some(3));
+        assert_eq!(lev("kitten", "sitting", 2), None);
+        assert_eq!(lev("", "abc", 3), Some(3));
+        assert_eq!(lev("abc", "", 2), None);
+        assert_eq!(lev("lavé", "lave", 1), Some(1));
+    }
+
+    #[test]
+    fn test_suggest() {
+        let index = sample_index();
+        let names: Vec<String> = index
+            .suggest("Eravat", 2, 5)
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec!["Eravate"]);
+
+        // ties are broken by name
+        let suggestions = index.suggest("erani", 3, 5);
+        assert_eq!(suggestions[0].name, "Eranin");
+        assert_eq!(suggestions[0].distance, 1);
+
+        assert_eq!(index.suggest("lav", 1, 5)[0].id, 6);
+        assert!(index.suggest("zzzzzzzz", 2, 5).is_empty());
+        assert_eq!(index.suggest("e", 10, 2).len(), 2);
+    }
+
+    #[test]
+    fn test_intersect() {
+        assert_eq!(intersect(&[1, 3, 5, 7], &[2, 3, 7, 9]), vec![3, 7]);
+        assert!(intersect(&[1, 2], &[]).is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+//! Points in an octree: cubes split into eight until few enough points are
+//! left in each, so crowded space gets small cells and empty space big
+//! ones. Around Sol, where tens of thousands of systems share a handful of
+//! 32ly grid cells, a radius search through a GridIndex checks every one of
+//! them; the octree only checks the leaves the sphere reaches.
+//!
+//! The tree is built once and never changed. Its nodes live in one Vec,
+//! each split node's eight children stored together, and each node's
+//! points are a contiguous run of `points`.
+
+use crate::grid::distance;
+use crate::spatial::{widen, Coordinate, SpatialIndex};
+
+/// Most points a leaf holds unless it's at MAX_DEPTH.
+pub const DEFAULT_LEAF_CAPACITY: usize = 32;
+
+/// Leaves this deep aren't split further, however full; coincident points
+/// would otherwise split forever.
+pub const MAX_DEPTH: u32 = 20;
+
+#[derive(Clone, Debug)]
+struct Node {
+    /// The corner with the lowest coordinates.
+    min: [f64; 3],
+    size: f64,
+    /// Index in nodes of the first of eight children, or 0 for a leaf (the
+    /// root is never anyone's child).
+    children: u32,
+    /// The node's run of points.
+    start: u32,
+    end: u32,
+}
+
+impl Node {
+    /// Squared distance from a position to the nearest point of the cube.
+    fn gap_squared(&self, pos: [f64; 3]) -> f64 {
+        (0..3)
+            .map(|axis| {
+                let gap = (self.min[axis] - pos[axis])
+                    .max(pos[axis] - (self.min[axis] + self.size))
+                    .max(0.);
+                gap * gap
+            })
+            .sum()
+    }
+
+    fn octant(&self, pos: [f64; 3]) -> usize {
+        let half = self.size / 2.;
+        (0..3)
+            .filter(|&axis| pos[axis] >= self.min[axis] + half)
+            .map(|axis| 1 << axis)
+            .sum()
+    }
+}
+
+/// Positions' components may be f64 or f32, see `Coordinate`.
+pub struct Octree<'a, T: Coordinate = f64> {
+    positions: &'a [[T; 3]],
+    nodes: Vec<Node>,
+    /// Indexes into positions, ordered so each node's are together.
+    points: Vec<u32>,
+    depth: u32,
+}
+
+impl<'a, T: Coordinate> Octree<'a, T> {
+    pub fn new(positions: &'a [[T; 3]]) -> Self {
+        Self::with_leaf_capacity(positions, DEFAULT_LEAF_CAPACITY)
+    }
+
+    /// Builds the tree, splitting nodes holding more than `capacity` points.
+    pub fn with_leaf_capacity(positions: &'a [[T; 3]], capacity: usize) -> Self {
+        let (mut min, mut max) = ([f64::INFINITY; 3], [f64::NEG_INFINITY; 3]);
+        for pos in positions {
+            let pos = widen(*pos);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(pos[axis]);
+                max[axis] = max[axis].max(pos[axis]);
+            }
+        }
+        let size = (0..3).map(|axis| max[axis] - min[axis]).fold(0., f64::max);
+        let root = Node {
+            min: if positions.is_empty() { [0.; 3] } else { min },
+            // a little slack so the furthest points fall inside, not on, the
+            // far faces
+            size: size * (1. + f64::EPSILON * 4.) + f64::MIN_POSITIVE,
+            children: 0,
+            start: 0,
+            end: positions.len() as u32,
+        };
+        let mut tree = Self {
+            positions,
+            nodes: vec![root],
+            points: (0..positions.len() as u32).collect(),
+            depth: 0,
+        };
+        tree.split(0, 0, capacity.max(1));
+        tree
+    }
+
+    fn split(&mut self, node: usize, depth: u32, capacity: usize) {
+        self.depth = self.depth.max(depth);
+        let (start, end) = (
+            self.nodes[node].start as usize,
+            self.nodes[node].end as usize,
+        );
+        if end - start <= capacity || depth >= MAX_DEPTH {
+            return;
+        }
+        let parent = self.nodes[node].clone();
+        let positions = self.positions;
+        self.points[start..end].sort_by_key(|&idx| parent.octant(widen(positions[idx as usize])));
+
+        let first = self.nodes.len();
+        self.nodes[node].children = first as u32;
+        let half = parent.size / 2.;
+        let mut run = start;
+        for octant in 0..8 {
+            let run_end = run
+                + self.points[run..end]
+                    .iter()
+                    .take_while(|&&idx| parent.octant(widen(positions[idx as usize])) == octant)
+                    .count();
+            let offset = |axis: usize| if octant & (1 << axis) != 0 { half } else { 0. };
+            self.nodes.push(Node {
+                min: [
+                    parent.min[0] + offset(0),
+                    parent.min[1] + offset(1),
+                    parent.min[2] + offset(2),
+                ],
+                size: half,
+                children: 0,
+                start: run as u32,
+                end: run_end as u32,
+            });
+            run = run_end;
+        }
+        for child in first..first + 8 {
+            self.split(child, depth + 1, capacity);
+        }
+    }
+
+    /// Number of nodes, split and leaf.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// How many levels below the root the deepest leaf is.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Calls `visit` with the run of points of every leaf the sphere reaches.
+    fn leaves_within(&self, pos: [f64; 3], range: f64, mut visit: impl FnMut(&[u32])) {
+        if self.positions.is_empty() {
+            return;
+        }
+        let limit = range * range;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if node.gap_squared(pos) > limit {
+                continue;
+            }
+            if node.children == 0 {
+                visit(&self.points[node.start as usize..node.end as usize]);
+            } else {
+                stack.extend(node.children as usize..node.children as usize + 8);
+            }
+        }
+    }
+}
+
+impl<T: Coordinate> SpatialIndex for Octree<'_, T> {
+    fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn position(&self, idx: u32) -> [f64; 3] {
+        widen(self.positions[idx as usize])
+    }
+
+    fn candidates(&self, pos: [f64; 3], range: f64) -> Vec<u32> {
+        let mut found = Vec::new();
+        self.leaves_within(pos, range, |points| found.extend_from_slice(points));
+        found
+    }
+
+    fn within(&self, pos: [f64; 3], range: f64) -> Vec<(u32, f64)> {
+        let mut found = Vec::new();
+        self.leaves_within(pos, range, |points| {
+            for &idx in points {
+                let distance = distance(pos, self.position(idx));
+                if distance <= range {
+                    found.push((idx, distance));
+                }
+            }
+        });
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::GridIndex;
+
+    /// A dense bubble of 2000 points within 50ly of the origin and a sparse
+    /// rim of 200 out to 5000ly.
+    fn bubble_and_rim() -> Vec<[f64; 3]> {
+        let mut seed = 12345u64;
+        let mut next = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64 * 2. - 1.
+        };
+        let mut positions: Vec<[f64; 3]> = (0..2000)
+            .map(|_| [next() * 50., next() * 50., next() * 50.])
+            .collect();
+        positions.extend((0..200).map(|_| [next() * 5000., next() * 500., next() * 5000.]));
+        positions
+    }
+
+    fn sorted(mut found: Vec<(u32, f64)>) -> Vec<u32> {
+        found.sort_unstable_by_key(|(idx, _)| *idx);
+        found.into_iter().map(|(idx, _)| idx).collect()
+    }
+
+    #[test]
+    fn test_octree_matches_grid() {
+        let positions = bubble_and_rim();
+        let octree = Octree::new(&positions);
+        let grid = GridIndex::new(&positions);
+        let indexes: [&dyn SpatialIndex; 2] = [&octree, &grid];
+        for range in [1., 10., 40., 800.] {
+            for origin in positions.iter().step_by(97) {
+                let expected: Vec<u32> = (0..positions.len() as u32)
+                    .filter(|i| distance(*origin, positions[*i as usize]) <= range)
+                    .collect();
+                for index in indexes {
+                    assert_eq!(sorted(index.within(*origin, range)), expected);
+                }
+            }
+        }
+        assert_eq!(octree.len(), 2200);
+        assert!(octree.depth() > 3);
+        assert_eq!((octree.node_count() - 1) % 8, 0);
+    }
+
+    #[test]
+    fn test_octree_candidates_in_dense_space() {
+        // the octree narrows a small search in the bubble down far more
+        // than the 32ly grid cells can
+        let positions = bubble_and_rim();
+        let octree = Octree::new(&positions);
+        let grid = GridIndex::new(&positions);
+        let (octree_found, grid_found) = (
+            octree.candidates([0.; 3], 2.).len(),
+            grid.candidates([0.; 3], 2.).len(),
+        );
+        assert!(
+            octree_found * 4 < grid_found,
+            "{} {}",
+            octree_found,
+            grid_found
+        );
+    }
+
+    #[test]
+    fn test_octree_edge_cases() {
+        assert!(Octree::<f64>::new(&[]).within([0.; 3], 100.).is_empty());
+        assert!(Octree::<f64>::new(&[]).is_empty());
+
+        // coincident points stop splitting at MAX_DEPTH
+        let same = vec![[1., 2., 3.]; 100];
+        let octree = Octree::with_leaf_capacity(&same, 4);
+        assert_eq!(octree.depth(), MAX_DEPTH);
+        assert_eq!(octree.within([1., 2., 3.], 0.).len(), 100);
+
+        let corners = [[0., 0., 0.], [10., 10., 10.]];
+        let octree = Octree::with_leaf_capacity(&corners, 1);
+        assert_eq!(sorted(octree.within([10., 10., 10.], 0.)), vec![1]);
+        assert_eq!(sorted(octree.within([5.; 3], 9.)), vec![0, 1]);
+    }
+}
@@ -0,0 +1,170 @@
+/// Default size of the chunks files are read in.
+pub const DEFAULT_BUFFER_SIZE: usize = 128 * 1024;
+
+/// Smallest buffer the readers will work with; the UTF-8 validator needs to be
+/// able to hold a partial multi-byte sequence plus at least one new byte.
+pub const MIN_BUFFER_SIZE: usize = 4;
+
+/// Tunables for the file-based APIs.
+///
+/// The defaults work well on local disks, but network shares and some
+/// filesystems prefer much larger reads, so callers can override them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Number of bytes requested from the OS per read.
+    pub buffer_size: usize,
+    /// Number of bytes ahead of the read position the OS should be asked to
+    /// prefetch; 0 leaves read-ahead up to the OS. Advisory, and only acted
+    /// on where the OS supports it (Linux).
+    pub read_ahead: usize,
+    /// Tell the OS the file will be read once, front to back. Advisory.
+    pub hint_sequential: bool,
+    /// Read around the OS page cache, where the filesystem allows it.
+    pub direct_io: bool,
+    /// Read on a background thread, overlapping I/O with parsing. Off by
+    /// default: the thread only pays for itself on files large enough for
+    /// the disk, rather than the parser, to be the bottleneck.
+    pub prefetch: bool,
+    /// Rough cap, in bytes, on the records an import holds in memory at
+    /// once. Past it merges spill sorted runs to temporary files and readers
+    /// hand out shorter batches. None leaves memory use unbounded.
+    pub memory_budget: Option<usize>,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            read_ahead: 0,
+            hint_sequential: false,
+            direct_io: false,
+            prefetch: false,
+            memory_budget: None,
+        }
+    }
+}
+
+impl ReadOptions {
+    /// Allocates a zeroed read buffer of the configured size, clamped to
+    /// MIN_BUFFER_SIZE.
+    pub fn make_buffer(&self) -> Vec<u8> {
+        vec![0; self.buffer_size.max(MIN_BUFFER_SIZE)]
+    }
+}
+
+/// The character that starts the fractional part of a number, which also
+/// decides the thousands separator: "1,234.5" with a point, "1.234,5" with a
+/// comma as much of Europe writes them. '_' groups digits either way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecimalSeparator {
+    #[default]
+    Point,
+    Comma,
+}
+
+impl DecimalSeparator {
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            '.' => Some(DecimalSeparator::Point),
+            ',' => Some(DecimalSeparator::Comma),
+            _ => None,
+        }
+    }
+
+    pub fn decimal(self) -> char {
+        match self {
+            DecimalSeparator::Point => '.',
+            DecimalSeparator::Comma => ',',
+        }
+    }
+
+    pub fn thousands(self) -> char {
+        match self {
+            DecimalSeparator::Point => ',',
+            DecimalSeparator::Comma => '.',
+        }
+    }
+}
+
+/// How forgiving the parsers are of malformed input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Only the documented formats are accepted.
+    #[default]
+    Strict,
+    /// Hand-edited input is accepted where its meaning is clear: stray
+    /// whitespace in readings, thousands separators.
+    Lenient,
+}
+
+/// Settings shared by the parsers, so a caller can pick hard-failing
+/// validation or best-effort imports in one place.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub strictness: Strictness,
+    /// Stop after this many malformed records; the error that goes over
+    /// the limit is the last thing returned. None carries on regardless,
+    /// Some(0) stops at the first.
+    pub max_errors: Option<usize>,
+    /// Lines starting with this character are comments.
+    pub comment_char: char,
+    pub decimal: DecimalSeparator,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            strictness: Strictness::Strict,
+            max_errors: None,
+            comment_char: '#',
+            decimal: DecimalSeparator::Point,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub fn lenient(&self) -> bool {
+        self.strictness == Strictness::Lenient
+    }
+
+    /// True once `errors` malformed records are more than allowed.
+    pub fn too_many_errors(&self, errors: usize) -> bool {
+        self.max_errors.is_some_and(|max| errors > max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_options_default() {
+        let options = ReadOptions::default();
+        assert_eq!(options.buffer_size, DEFAULT_BUFFER_SIZE);
+        assert_eq!(options.make_buffer().len(), DEFAULT_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = ParseOptions::default();
+        assert!(!options.lenient());
+        assert!(!options.too_many_errors(1000));
+        let options = ParseOptions {
+            strictness: Strictness::Lenient,
+            max_errors: Some(1),
+            ..Default::default()
+        };
+        assert!(options.lenient());
+        assert!(!options.too_many_errors(1));
+        assert!(options.too_many_errors(2));
+    }
+
+    #[test]
+    fn test_read_options_buffer_clamped() {
+        let options = ReadOptions {
+            buffer_size: 0,
+            ..Default::default()
+        };
+        assert_eq!(options.make_buffer().len(), MIN_BUFFER_SIZE);
+    }
+}
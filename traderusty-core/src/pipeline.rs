@@ -0,0 +1,865 @@
+//! Importing .prices data into the database as a three-stage pipeline: a
+//! parser thread reads records from a DataSource, a transform thread
+//! resolves them to StationItem rows, and the calling thread writes the
+//! rows.
+//!
+//! The stages hand batches over bounded channels, so at most `queue_depth`
+//! batches ever wait between two stages. When SQLite falls behind, the queue
+//! in front of the writer fills, the transform blocks on it, and the parser
+//! blocks in turn, rather than reading ahead without limit.
+//!
+//! Each channel keeps its queue's high-water mark and how often and for how
+//! long its sender was blocked, which shows where the bottleneck is: a
+//! parser blocked on a full queue means the writer is slow, queues that stay
+//! empty mean parsing is.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SendError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rusqlite::Connection;
+use tracing::{info, warn, Span};
+
+use crate::cancel::CancelToken;
+use crate::commodities::canonical_commodity;
+use crate::db::{has_table, load_items, Batcher, DbError, DEFAULT_BATCH_SIZE};
+use crate::ids::{ItemId, StationId};
+use crate::market::StationItem;
+use crate::merge::MergeError;
+use crate::metrics;
+use crate::names::canonical_name;
+use crate::options::ParseOptions;
+use crate::prices::{read_source, ParseWarning, PriceRecord, PricesError};
+use crate::source::DataSource;
+
+/// Batches that may wait between two stages.
+pub const DEFAULT_QUEUE_DEPTH: usize = 4;
+
+/// How a channel's queue behaved over a run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    pub capacity: usize,
+    /// Batches sent.
+    pub sent: u64,
+    /// The most batches waiting at once.
+    pub max_depth: usize,
+    /// Sends that found the queue full and had to wait.
+    pub blocked: u64,
+    /// Time spent waiting in those sends.
+    pub blocked_time: Duration,
+}
+
+#[derive(Default)]
+struct Gauge {
+    capacity: usize,
+    sent: AtomicU64,
+    depth: AtomicUsize,
+    max_depth: AtomicUsize,
+    blocked: AtomicU64,
+    blocked_nanos: AtomicU64,
+}
+
+impl Gauge {
+    fn stats(&self) -> QueueStats {
+        QueueStats {
+            capacity: self.capacity,
+            sent: self.sent.load(Ordering::Relaxed),
+            max_depth: self.max_depth.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+            blocked_time: Duration::from_nanos(self.blocked_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The sending half of a bounded channel; `send` blocks while it's full.
+pub struct BoundedSender<T> {
+    inner: SyncSender<T>,
+    gauge: Arc<Gauge>,
+}
+
+impl<T> BoundedSender<T> {
+    /// Sends a value, waiting for room. Fails only once the receiver is
+    /// gone, handing the value back.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let gauge = &self.gauge;
+        let depth = gauge.depth.fetch_add(1, Ordering::Relaxed) + 1;
+        let sent = match self.inner.try_send(value) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(value)) => {
+                let started = Instant::now();
+                let sent = self.inner.send(value);
+                gauge.blocked.fetch_add(1, Ordering::Relaxed);
+                gauge
+                    .blocked_nanos
+                    .fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                sent
+            }
+            Err(TrySendError::Disconnected(value)) => Err(SendError(value)),
+        };
+        match sent {
+            Ok(()) => {
+                gauge.sent.fetch_add(1, Ordering::Relaxed);
+                gauge
+                    .max_depth
+                    .fetch_max(depth.min(gauge.capacity), Ordering::Relaxed);
+            }
+            Err(_) => {
+                gauge.depth.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+        sent
+    }
+}
+
+/// The receiving half of a bounded channel, iterating until every sender
+/// is gone.
+pub struct BoundedReceiver<T> {
+    inner: Receiver<T>,
+    gauge: Arc<Gauge>,
+}
+
+impl<T> Iterator for BoundedReceiver<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let value = self.inner.recv().ok()?;
+        self.gauge.depth.fetch_sub(1, Ordering::Relaxed);
+        Some(value)
+    }
+}
+
+/// A channel holding at most `capacity` values (at least 1).
+pub fn bounded<T>(capacity: usize) -> (BoundedSender<T>, BoundedReceiver<T>) {
+    let capacity = capacity.max(1);
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    let gauge = Arc::new(Gauge {
+        capacity,
+        ..Default::default()
+    });
+    (
+        BoundedSender {
+            inner: sender,
+            gauge: gauge.clone(),
+        },
+        BoundedReceiver {
+            inner: receiver,
+            gauge,
+        },
+    )
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    Prices(MergeError),
+    Db(DbError),
+    /// The PipelineOptions' cancel token was cancelled.
+    Cancelled,
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::Prices(e) => write!(f, "{}", e),
+            PipelineError::Db(e) => write!(f, "{}", e),
+            PipelineError::Cancelled => write!(f, "import cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl From<DbError> for PipelineError {
+    fn from(e: DbError) -> Self {
+        PipelineError::Db(e)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PipelineOptions {
+    /// Records per batch handed between stages, and rows per INSERT batch.
+    pub batch_size: usize,
+    /// Batches that may wait between two stages.
+    pub queue_depth: usize,
+    /// Checked by the parser between batches; once cancelled the import
+    /// stops and rolls back.
+    pub cancel: Option<CancelToken>,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+            queue_depth: DEFAULT_QUEUE_DEPTH,
+            cancel: None,
+        }
+    }
+}
+
+/// A record that named a station or item the import doesn't know.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unresolved {
+    /// 1-based line the record came from.
+    pub line: usize,
+    pub system: String,
+    pub station: String,
+    /// The unknown item, or None when it's the station that's unknown.
+    pub item: Option<String>,
+}
+
+impl fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.item {
+            Some(item) => write!(
+                f,
+                "line {}: unknown item {} at {}/{}",
+                self.line, item, self.system, self.station
+            ),
+            None => write!(
+                f,
+                "line {}: unknown station {}/{}",
+                self.line, self.system, self.station
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportStats {
+    /// Records parsed.
+    pub records: usize,
+    /// Rows written.
+    pub written: usize,
+    /// Records skipped for a station missing from the station table.
+    pub unknown_stations: usize,
+    /// Records skipped for an item that isn't a known commodity.
+    pub unknown_items: usize,
+    /// Lines that failed to parse and were left out.
+    pub errors: Vec<PricesError>,
+    /// Lines that were read despite something odd about them.
+    pub warnings: Vec<ParseWarning>,
+    /// Where the unknown stations and items are, when validating: a
+    /// station once per run of its records, an item at every record.
+    pub unresolved: Vec<Unresolved>,
+    /// The queue from the parser to the transform.
+    pub parsed_queue: QueueStats,
+    /// The queue from the transform to the writer.
+    pub rows_queue: QueueStats,
+}
+
+/// Batches of a stage's output, or the failure that ends the import; the
+/// failure travels down the pipeline so the writer rolls back.
+type Batch<T> = Result<Vec<T>, PipelineError>;
+
+/// Imports .prices data into the StationItem table in one transaction.
+/// `stations` maps canonical (system, station) names to station ids;
+/// records of other stations, and of unknown items, are counted and
+/// skipped. Items get the ids of the database's Item table, matched by
+/// name, or the commodity table's FDev ids if it has none. Lines without a
+/// timestamp are dated by the source's modification time. Fails once more
+/// than the ParseOptions' `max_errors` lines fail to parse, writing
+/// nothing.
+#[tracing::instrument(skip_all, fields(source = source.name()))]
+pub fn import_prices<S: DataSource + Sync>(
+    conn: &mut Connection,
+    source: &S,
+    stations: &HashMap<(String, String), StationId>,
+    pipeline: &PipelineOptions,
+    parse_options: &ParseOptions,
+) -> Result<ImportStats, PipelineError> {
+    let batch_size = pipeline.batch_size.max(1);
+    let items = if has_table(conn, "Item")? {
+        Some(load_items(conn)?)
+    } else {
+        None
+    };
+    let stats = run(
+        source,
+        stations,
+        items.as_ref(),
+        pipeline,
+        parse_options,
+        false,
+        |rows| write(conn, batch_size, rows),
+    )?;
+    info!(
+        records = stats.records,
+        written = stats.written,
+        unknown_stations = stats.unknown_stations,
+        unknown_items = stats.unknown_items,
+        parser_blocked = stats.parsed_queue.blocked,
+        writer_backlog = stats.rows_queue.max_depth,
+        "imported .prices file"
+    );
+    Ok(stats)
+}
+
+/// Checks .prices data as `import_prices` would import it, without a
+/// database: every line is parsed and resolved, and every error and
+/// warning collected however many there are, but nothing is written.
+/// `written` counts the rows an import would write, and `unresolved` says
+/// where the unknown stations and items are.
+#[tracing::instrument(skip_all, fields(source = source.name()))]
+pub fn validate_prices<S: DataSource + Sync>(
+    source: &S,
+    stations: &HashMap<(String, String), StationId>,
+    pipeline: &PipelineOptions,
+    parse_options: &ParseOptions,
+) -> Result<ImportStats, PipelineError> {
+    let parse_options = ParseOptions {
+        max_errors: None,
+        ..parse_options.clone()
+    };
+    let stats = run(
+        source,
+        stations,
+        None,
+        pipeline,
+        &parse_options,
+        true,
+        count,
+    )?;
+    info!(
+        records = stats.records,
+        errors = stats.errors.len(),
+        warnings = stats.warnings.len(),
+        unknown_stations = stats.unknown_stations,
+        unknown_items = stats.unknown_items,
+        "validated .prices file"
+    );
+    Ok(stats)
+}
+
+/// Runs the parser and transform stages on their own threads, handing the
+/// rows to `sink` on this one, which returns how many it took. `locate`
+/// has the transform note where unknown stations and items are.
+fn run<S: DataSource + Sync>(
+    source: &S,
+    stations: &HashMap<(String, String), StationId>,
+    items: Option<&HashMap<ItemId, ItemId>>,
+    pipeline: &PipelineOptions,
+    parse_options: &ParseOptions,
+    locate: bool,
+    sink: impl FnOnce(BoundedReceiver<Batch<StationItem>>) -> Result<usize, PipelineError>,
+) -> Result<ImportStats, PipelineError> {
+    let batch_size = pipeline.batch_size.max(1);
+    let cancel = pipeline.cancel.as_ref();
+    let (parsed_tx, parsed_rx) = bounded::<Batch<PriceRecord>>(pipeline.queue_depth);
+    let (rows_tx, rows_rx) = bounded::<Batch<StationItem>>(pipeline.queue_depth);
+    let (parsed_gauge, rows_gauge) = (parsed_rx.gauge.clone(), rows_rx.gauge.clone());
+    let file_time = source.modified().unwrap_or(0);
+
+    // The stages' spans belong under this one, whichever thread they run on.
+    let span = Span::current();
+    let (parsed, transformed, written) = thread::scope(|scope| {
+        let parser = scope.spawn(|| {
+            span.in_scope(|| parse(source, batch_size, parse_options, cancel, parsed_tx))
+        });
+        let transform = scope.spawn(|| {
+            span.in_scope(|| transform(file_time, stations, items, locate, parsed_rx, rows_tx))
+        });
+        // A failed sink drops the receiver, which stops the other stages.
+        let written = sink(rows_rx);
+        (
+            parser.join().expect("parser panicked"),
+            transform.join().expect("transform panicked"),
+            written,
+        )
+    });
+    let Transformed {
+        unknown_stations,
+        unknown_items,
+        unresolved,
+    } = transformed;
+    Ok(ImportStats {
+        records: parsed.records,
+        written: written?,
+        unknown_stations,
+        unknown_items,
+        errors: parsed.errors,
+        warnings: parsed.warnings,
+        unresolved,
+        parsed_queue: parsed_gauge.stats(),
+        rows_queue: rows_gauge.stats(),
+    })
+}
+
+/// The writer stage, in one transaction that's rolled back if a batch
+/// fails or an earlier stage sends a failure instead of a batch.
+#[tracing::instrument(skip_all)]
+fn write(
+    conn: &mut Connection,
+    batch_size: usize,
+    input: BoundedReceiver<Batch<StationItem>>,
+) -> Result<usize, PipelineError> {
+    let _timer = metrics::time_stage("import_prices.write");
+    let tx = conn.transaction().map_err(DbError::from)?;
+    let mut batcher = Batcher::<StationItem>::new(&tx, batch_size);
+    for batch in input {
+        let rows = batch.inspect_err(|e| warn!("rolling back: {}", e))?;
+        rows.iter().try_for_each(|row| batcher.push(row))?;
+    }
+    let written = batcher.finish()?;
+    tx.commit().map_err(DbError::from)?;
+    Ok(written)
+}
+
+/// The sink of a validation, which only counts the rows.
+fn count(input: BoundedReceiver<Batch<StationItem>>) -> Result<usize, PipelineError> {
+    input
+        .into_iter()
+        .try_fold(0, |rows, batch| Ok(rows + batch?.len()))
+}
+
+/// What the parser stage read.
+#[derive(Default)]
+struct Parsed {
+    records: usize,
+    /// Lines that failed.
+    errors: Vec<PricesError>,
+    warnings: Vec<ParseWarning>,
+}
+
+/// The parser stage.
+#[tracing::instrument(skip_all)]
+fn parse<S: DataSource>(
+    source: &S,
+    batch_size: usize,
+    parse_options: &ParseOptions,
+    cancel: Option<&CancelToken>,
+    out: BoundedSender<Batch<PriceRecord>>,
+) -> Parsed {
+    let _timer = metrics::time_stage("import_prices.parse");
+    let cancelled = || cancel.is_some_and(CancelToken::is_cancelled);
+    let failed = |error: PricesError| {
+        Err(PipelineError::Prices(MergeError {
+            path: PathBuf::from(source.name()),
+            error,
+        }))
+    };
+    let mut parsed = Parsed::default();
+    let mut reader = match read_source(source, parse_options) {
+        Ok(reader) => reader,
+        Err(e) => {
+            let _ = out.send(failed(e.into()));
+            return parsed;
+        }
+    };
+    let mut batch = Vec::with_capacity(batch_size);
+    while let Some(record) = reader.next() {
+        parsed.warnings.append(&mut reader.take_warnings());
+        match record {
+            Ok(record) => {
+                parsed.records += 1;
+                batch.push(record);
+                if batch.len() == batch_size {
+                    if cancelled() {
+                        let _ = out.send(Err(PipelineError::Cancelled));
+                        return parsed;
+                    }
+                    let full = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+                    if out.send(Ok(full)).is_err() {
+                        return parsed;
+                    }
+                }
+            }
+            Err(PricesError::Io(e)) => {
+                let _ = out.send(failed(PricesError::Io(e)));
+                return parsed;
+            }
+            Err(e) if parse_options.too_many_errors(parsed.errors.len() + 1) => {
+                let _ = out.send(failed(e));
+                return parsed;
+            }
+            Err(e) => parsed.errors.push(e),
+        }
+    }
+    if cancelled() {
+        let _ = out.send(Err(PipelineError::Cancelled));
+    } else if !batch.is_empty() {
+        let _ = out.send(Ok(batch));
+    }
+    parsed
+}
+
+/// What the transform stage left out.
+#[derive(Default)]
+struct Transformed {
+    /// Records skipped for an unknown station.
+    unknown_stations: usize,
+    /// Records skipped for an unknown item.
+    unknown_items: usize,
+    unresolved: Vec<Unresolved>,
+}
+
+/// The transform stage, dating undated records `file_time` and giving items
+/// their ids in `items` if given. With `locate`, where the records it skips
+/// are is noted too.
+#[tracing::instrument(skip_all)]
+fn transform(
+    file_time: i64,
+    stations: &HashMap<(String, String), StationId>,
+    items: Option<&HashMap<ItemId, ItemId>>,
+    locate: bool,
+    input: BoundedReceiver<Batch<PriceRecord>>,
+    out: BoundedSender<Batch<StationItem>>,
+) -> Transformed {
+    let _timer = metrics::time_stage("import_prices.transform");
+    let mut transformed = Transformed::default();
+    let unresolved = |record: &PriceRecord, item: Option<&str>| Unresolved {
+        line: record.line,
+        system: record.system.clone(),
+        station: record.station.clone(),
+        item: item.map(str::to_string),
+    };
+    let mut station: Option<((String, String), Option<StationId>)> = None;
+    for batch in input {
+        let records = match batch {
+            Ok(records) => records,
+            Err(e) => {
+                let _ = out.send(Err(e));
+                break;
+            }
+        };
+        let mut rows = Vec::with_capacity(records.len());
+        for record in records {
+            // Records come grouped by station, so look each up once.
+            let key = (
+                canonical_name(&record.system),
+                canonical_name(&record.station),
+            );
+            if station.as_ref().is_none_or(|(last, _)| *last != key) {
+                let id = stations.get(&key).copied();
+                if locate && id.is_none() {
+                    transformed.unresolved.push(unresolved(&record, None));
+                }
+                station = Some((key, id));
+            }
+            let Some(station_id) = station.as_ref().and_then(|(_, id)| *id) else {
+                transformed.unknown_stations += 1;
+                continue;
+            };
+            let item_id = canonical_commodity(&record.item).and_then(|commodity| match items {
+                Some(items) => items.get(&commodity.id).copied(),
+                None => Some(commodity.id),
+            });
+            let Some(item_id) = item_id else {
+                if locate {
+                    transformed
+                        .unresolved
+                        .push(unresolved(&record, Some(&record.item)));
+                }
+                transformed.unknown_items += 1;
+                continue;
+            };
+            rows.push(StationItem {
+                station_id,
+                item_id,
+                demand_price: record.demand_price,
+                demand_units: record.demand_units,
+                demand_level: record.demand_level,
+                supply_price: record.supply_price,
+                supply_units: record.supply_units,
+                supply_level: record.supply_level,
+                modified: record.modified.unwrap_or(file_time),
+            });
+        }
+        if out.send(Ok(rows)).is_err() {
+            break;
+        }
+    }
+    transformed
+}
+
+/// Keys a station table the way `import_prices` looks stations up.
+pub fn station_key(system: &str, station: &str) -> (String, String) {
+    (canonical_name(system), canonical_name(station))
+}
+
+/// Reads the station table `import_prices` takes from a TradeDangerous
+/// database's System and Station tables.
+pub fn load_stations(conn: &Connection) -> Result<HashMap<(String, String), StationId>, DbError> {
+    let mut stmt = conn.prepare(
+        "SELECT System.name, Station.name, Station.station_id \
+         FROM Station JOIN System USING (system_id)",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let (system, station): (String, String) = (row.get(0)?, row.get(1)?);
+        Ok((station_key(&system, &station), row.get(2)?))
+    })?;
+    let stations = rows.collect::<Result<HashMap<_, _>, _>>()?;
+    info!(stations = stations.len(), "loaded stations");
+    Ok(stations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrate::migrate;
+    use crate::options::ReadOptions;
+    use crate::source::{FileSource, MemorySource};
+    use std::fs;
+
+    #[test]
+    fn test_bounded_channel() {
+        let (sender, mut receiver) = bounded(2);
+        let producer = thread::spawn(move || {
+            for i in 0..10 {
+                sender.send(i).unwrap();
+            }
+        });
+        // let the producer fill the queue and block on it
+        thread::sleep(Duration::from_millis(50));
+        let received: Vec<i32> = receiver.by_ref().collect();
+        producer.join().unwrap();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+        let stats = receiver.gauge.stats();
+        assert_eq!((stats.capacity, stats.sent, stats.max_depth), (2, 10, 2));
+        assert!(stats.blocked >= 1);
+        assert!(stats.blocked_time > Duration::ZERO);
+
+        let (sender, receiver) = bounded(0);
+        drop(receiver);
+        assert_eq!(sender.send(1).unwrap_err().0, 1);
+    }
+
+    fn database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_load_stations() {
+        let conn = database();
+        conn.execute_batch(
+            "CREATE TABLE System (system_id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE Station (station_id INTEGER PRIMARY KEY, name TEXT, system_id INTEGER);
+             INSERT INTO System VALUES (1, 'Sol'), (2, 'Lave');
+             INSERT INTO Station VALUES (7, 'Abraham Lincoln', 1), (9, 'Lave Station', 2);",
+        )
+        .unwrap();
+        let stations = load_stations(&conn).unwrap();
+        assert_eq!(stations.len(), 2);
+        assert_eq!(
+            stations[&station_key("SOL", "abraham lincoln")],
+            StationId(7)
+        );
+        assert!(load_stations(&Connection::open_in_memory().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_import_prices() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("import.prices");
+        fs::write(
+            &path,
+            "@ SOL/Abraham Lincoln\n\
+             Gold 9500 9000 - - 2024-05-01 00:00:00\n\
+             Silver 5000 0\n\
+             Unobtainium 1 1\n\
+             Tea x\n\
+             @ SOL/Nowhere\n\
+             Gold 1 1\n",
+        )
+        .unwrap();
+        let stations = HashMap::from([(station_key("Sol", "abraham  lincoln"), StationId(7))]);
+        let mut conn = database();
+        let pipeline = PipelineOptions {
+            batch_size: 1,
+            queue_depth: 1,
+            ..Default::default()
+        };
+        let stats = import_prices(
+            &mut conn,
+            &FileSource::new(&path, &ReadOptions::default()),
+            &stations,
+            &pipeline,
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            (
+                stats.records,
+                stats.written,
+                stats.unknown_stations,
+                stats.unknown_items
+            ),
+            (4, 2, 1, 1)
+        );
+        assert_eq!(stats.errors.len(), 1);
+        assert_eq!(stats.parsed_queue.sent, 4);
+        assert_eq!(stats.rows_queue.capacity, 1);
+        let modified: String = conn
+            .query_row(
+                "SELECT modified FROM StationItem WHERE station_id = 7 AND item_id = ?",
+                [canonical_commodity("Gold").unwrap().id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(modified, "2024-05-01 00:00:00");
+
+        // too many errors: nothing is written
+        let mut conn = database();
+        let strict = ParseOptions {
+            max_errors: Some(0),
+            ..Default::default()
+        };
+        let err = import_prices(
+            &mut conn,
+            &FileSource::new(&path, &ReadOptions::default()),
+            &stations,
+            &pipeline,
+            &strict,
+        )
+        .unwrap_err();
+        assert!(matches!(err, PipelineError::Prices(_)), "{}", err);
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM StationItem", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 0);
+
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let cancelled = PipelineOptions {
+            cancel: Some(cancel),
+            ..pipeline.clone()
+        };
+        let err = import_prices(
+            &mut conn,
+            &FileSource::new(&path, &ReadOptions::default()),
+            &stations,
+            &cancelled,
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, PipelineError::Cancelled), "{}", err);
+
+        let missing = dir.path().join("missing.prices");
+        let err = import_prices(
+            &mut conn,
+            &FileSource::new(missing, &ReadOptions::default()),
+            &stations,
+            &pipeline,
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("missing.prices"), "{}", err);
+    }
+
+    #[test]
+    fn test_import_prices_uses_database_item_ids() {
+        let mut conn = database();
+        conn.execute_batch(
+            "CREATE TABLE Item (item_id INTEGER PRIMARY KEY, name TEXT);
+             INSERT INTO Item VALUES (5, 'Gold'), (6, 'Silver'), (7, 'Something New');",
+        )
+        .unwrap();
+        let items = load_items(&conn).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[&canonical_commodity("gold").unwrap().id], ItemId(5));
+
+        let source = MemorySource::new(
+            "import.prices",
+            "@ SOL/Abraham Lincoln\nGold 9500 9000\nSilver 5000 0\nTea 10 12\n".as_bytes(),
+        );
+        let stations = HashMap::from([(station_key("Sol", "Abraham Lincoln"), StationId(7))]);
+        let stats = import_prices(
+            &mut conn,
+            &source,
+            &stations,
+            &PipelineOptions::default(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        // Tea isn't in the database's Item table
+        assert_eq!((stats.written, stats.unknown_items), (2, 1));
+        let ids: Vec<u32> = conn
+            .prepare("SELECT item_id FROM StationItem ORDER BY item_id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(ids, [5, 6]);
+    }
+
+    #[test]
+    fn test_validate_prices() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contributed.prices");
+        fs::write(
+            &path,
+            "@ SOL/Abraham Lincoln\n\
+             Gold 9500 9000\n\
+             Silver 0 0\n\
+             Tea x\n\
+             Coffee 1\n\
+             Unobtainium 5 5\n\
+             @ SOL/Nowhere\n\
+             Gold 1 1\n\
+             Silver 1 1\n",
+        )
+        .unwrap();
+        let stations = HashMap::from([(station_key("Sol", "Abraham Lincoln"), StationId(7))]);
+        // max_errors doesn't cut a validation short
+        let strict = ParseOptions {
+            max_errors: Some(0),
+            ..Default::default()
+        };
+        let stats = validate_prices(
+            &FileSource::new(&path, &ReadOptions::default()),
+            &stations,
+            &PipelineOptions::default(),
+            &strict,
+        )
+        .unwrap();
+        assert_eq!(
+            (stats.records, stats.written, stats.unknown_stations),
+            (5, 2, 2)
+        );
+        // each unknown station is placed once, each unknown item every time
+        let unresolved: Vec<String> = stats.unresolved.iter().map(|u| u.to_string()).collect();
+        assert_eq!(
+            unresolved,
+            [
+                "line 6: unknown item Unobtainium at SOL/Abraham Lincoln",
+                "line 8: unknown station SOL/Nowhere",
+            ]
+        );
+        let errors: Vec<usize> = stats
+            .errors
+            .iter()
+            .map(|e| match e {
+                PricesError::Parse { span, .. } => span.line,
+                PricesError::Io(e) => panic!("{}", e),
+            })
+            .collect();
+        assert_eq!(errors, [4, 5]);
+        assert_eq!(stats.warnings.len(), 1);
+        assert_eq!(stats.warnings[0].span.line, 3);
+
+        // the same data from memory validates the same
+        let memory = MemorySource::new("contributed.prices", fs::read(&path).unwrap());
+        let stats =
+            validate_prices(&memory, &stations, &PipelineOptions::default(), &strict).unwrap();
+        assert_eq!(
+            (stats.records, stats.written, stats.errors.len()),
+            (5, 2, 2)
+        );
+
+        let missing = dir.path().join("missing.prices");
+        let err = validate_prices(
+            &FileSource::new(missing, &ReadOptions::default()),
+            &stations,
+            &PipelineOptions::default(),
+            &strict,
+        )
+        .unwrap_err();
+        assert!(matches!(err, PipelineError::Prices(_)), "{}", err);
+    }
+}
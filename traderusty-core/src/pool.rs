@@ -0,0 +1,108 @@
+//! The thread pool the crate's parallel work runs on.
+//!
+//! By default that's rayon's global pool, a thread per core, which is
+//! unkind when TradeDangerous runs alongside the game. `set_thread_count`
+//! replaces it for the whole process, and `install` lets a single call ask
+//! for its own number of threads. Work that runs on the calling thread, like
+//! parsing one file, isn't affected.
+//!
+//! Pools `install` starts are kept, one per size asked for, so calls asking
+//! for the same number of threads share them rather than each starting and
+//! stopping its own.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use tracing::{info, warn};
+
+/// The pool set by `set_thread_count`, or None for rayon's global pool.
+static POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+
+/// The pools `install` has started, by number of threads.
+static SIZED: Mutex<BTreeMap<usize, Arc<ThreadPool>>> = Mutex::new(BTreeMap::new());
+
+fn build(threads: usize) -> Result<ThreadPool, ThreadPoolBuildError> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("traderusty-{}", i))
+        .build()
+}
+
+/// Runs the crate's parallel work on `threads` threads from now on; 0 goes
+/// back to a thread per core. Work already running keeps its threads.
+pub fn set_thread_count(threads: usize) -> Result<(), ThreadPoolBuildError> {
+    let pool = match threads {
+        0 => None,
+        _ => Some(Arc::new(build(threads)?)),
+    };
+    *POOL.write().unwrap_or_else(|e| e.into_inner()) = pool;
+    info!(threads, "set thread count");
+    Ok(())
+}
+
+/// The number of threads parallel work currently runs on.
+pub fn thread_count() -> usize {
+    match &*POOL.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(pool) => pool.current_num_threads(),
+        None => rayon::current_num_threads(),
+    }
+}
+
+/// The pool of `threads` threads `install` uses, started the first time
+/// it's asked for.
+fn sized(threads: usize) -> Result<Arc<ThreadPool>, ThreadPoolBuildError> {
+    let mut pools = SIZED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(pool.clone());
+    }
+    let pool = Arc::new(build(threads)?);
+    pools.insert(threads, pool.clone());
+    Ok(pool)
+}
+
+/// Runs `op` with its parallel work on `threads` threads if given, else on
+/// the pool set by `set_thread_count`. If a pool of that size can't be
+/// started, the work runs on the current pool instead.
+pub fn install<R: Send>(threads: Option<usize>, op: impl FnOnce() -> R + Send) -> R {
+    let pool = match threads.filter(|&threads| threads > 0) {
+        Some(threads) => match sized(threads) {
+            Ok(pool) => Some(pool),
+            Err(e) => {
+                warn!(threads, "couldn't start a thread pool: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let pool = pool.or_else(|| POOL.read().unwrap_or_else(|e| e.into_inner()).clone());
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install() {
+        assert_eq!(install(Some(3), rayon::current_num_threads), 3);
+        // pools are kept by size
+        assert!(Arc::ptr_eq(&sized(3).unwrap(), &sized(3).unwrap()));
+        assert!(!Arc::ptr_eq(&sized(3).unwrap(), &sized(4).unwrap()));
+        assert_eq!(
+            install(Some(0), rayon::current_num_threads),
+            install(None, rayon::current_num_threads)
+        );
+
+        // the only test touching the process-wide pool
+        set_thread_count(2).unwrap();
+        assert_eq!(thread_count(), 2);
+        assert_eq!(install(None, rayon::current_num_threads), 2);
+        assert_eq!(install(Some(1), rayon::current_num_threads), 1);
+        set_thread_count(0).unwrap();
+        assert_eq!(thread_count(), rayon::current_num_threads());
+    }
+}
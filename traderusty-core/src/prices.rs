@@ -0,0 +1,1202 @@
+//! Streaming parser for TradeDangerous .prices files.
+//!
+//! A .prices file lists station markets as:
+//!
+//! ```text
+//! # comment
+//! @ SOL/Abraham Lincoln
+//!    + Chemicals
+//!       Hydrogen Fuel   105   110   2079L   28430H   2024-05-01 12:00:00
+//! ```
+//!
+//! Item lines give the name, the price the station pays, the price it
+//! charges, then optionally the demand and supply readings (in the form
+//! `parse_supply_level` takes) and a UTC timestamp. Records are produced one
+//! at a time so arbitrarily large files can be read in constant memory.
+//!
+//! Parsing follows ParseOptions. Leniently, a reading split from its level
+//! by a space ("300 M"), as hand edits tend to leave them, is put back
+//! together, and prices and units may have thousands separators
+//! ("1,234,567" or "1_234_567"; "1.234.567" when the file was written with a
+//! decimal comma). `max_errors` bounds how many malformed lines are reported
+//! before the reader gives up.
+//!
+//! Parse errors carry a Span locating the failing field: its byte offset in
+//! the file, line, column and the text of the line.
+//!
+//! Lines that parse but look wrong (an item that isn't in the known items,
+//! a timestamp in the future, units listed at a zero price) produce
+//! ParseWarnings alongside the record rather than failing it; collect them
+//! with `take_warnings`.
+//!
+//! A reader's `checkpoint` records how far it got: the offset of the next
+//! line and the station and category in force there. `resume_prices` picks
+//! up from one, so a tool following an appended file, or an import that was
+//! interrupted, doesn't parse the whole file again.
+//!
+//! Given a BloomFilter of stations with `only_stations`, a reader skips the
+//! item lines of stations not in it without parsing them.
+//!
+//! With `count_distinct`, a reader estimates the distinct systems, stations
+//! and commodities it has read, in constant memory (see hll.rs), and
+//! `summarize_prices` streams a whole file for its counts, a quick sanity
+//! check of a download before committing to a long import.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, BufRead};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::bloom::BloomFilter;
+use crate::clock;
+use crate::hll::HyperLogLog;
+#[cfg(feature = "fs")]
+use crate::input::InputReader;
+use crate::metrics::{self, Counter};
+use crate::names::canonical_name;
+use crate::options::ParseOptions;
+#[cfg(feature = "fs")]
+use crate::options::ReadOptions;
+#[cfg(feature = "fs")]
+use crate::rusty::{open_decoded, open_decoded_at};
+use crate::rusty::{parse_number, parse_supply_level, parse_supply_level_lenient, skip_bom};
+use crate::source::{self, DataSource, DecodedReader};
+use crate::span::Span;
+
+/// One item line of a .prices file, with the station and category it was
+/// listed under.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PriceRecord {
+    pub system: String,
+    pub station: String,
+    pub category: String,
+    pub item: String,
+    pub demand_price: i32,
+    pub demand_units: i64,
+    pub demand_level: i32,
+    pub supply_price: i32,
+    pub supply_units: i64,
+    pub supply_level: i32,
+    /// Seconds since the unix epoch, if the line had a timestamp.
+    pub modified: Option<i64>,
+    /// 1-based line number the record came from.
+    pub line: usize,
+}
+
+impl PriceRecord {
+    /// Bytes the record's strings take on the heap.
+    pub fn heap_size(&self) -> usize {
+        self.system.capacity()
+            + self.station.capacity()
+            + self.category.capacity()
+            + self.item.capacity()
+    }
+}
+
+#[derive(Debug)]
+pub enum PricesError {
+    Io(io::Error),
+    Parse { span: Span, message: String },
+}
+
+impl fmt::Display for PricesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PricesError::Io(e) => write!(f, "{}", e),
+            PricesError::Parse { span, message } => write!(f, "{}: {}", span, message),
+        }
+    }
+}
+
+impl std::error::Error for PricesError {}
+
+impl From<io::Error> for PricesError {
+    fn from(e: io::Error) -> Self {
+        PricesError::Io(e)
+    }
+}
+
+/// How far ahead of the clock a timestamp can be before it's suspicious.
+const CLOCK_SKEW: i64 = 15 * 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WarningKind {
+    UnknownItem,
+    FutureTimestamp,
+    ZeroPrice,
+}
+
+impl WarningKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarningKind::UnknownItem => "unknown_item",
+            WarningKind::FutureTimestamp => "future_timestamp",
+            WarningKind::ZeroPrice => "zero_price",
+        }
+    }
+}
+
+/// Something odd about a line that was still read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub kind: WarningKind,
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Days in a month of the proleptic Gregorian calendar.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses "YYYY-MM-DD" and "HH:MM:SS" into seconds since the unix epoch.
+/// A day the month doesn't have ("2024-02-30") is rejected rather than
+/// rolled over into the next month.
+pub fn parse_timestamp(date: &str, time: &str) -> Option<i64> {
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if !(1..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || !(0..=23).contains(&hour)
+        || !(0..=59).contains(&minute)
+        || !(0..=60).contains(&second)
+    {
+        return None;
+    }
+    // The year range keeps the arithmetic from overflowing.
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+fn is_price(token: &str, options: &ParseOptions) -> bool {
+    if options.lenient() {
+        !token.starts_with('-') && parse_number(token, options).is_ok()
+    } else {
+        !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit())
+    }
+}
+
+/// Rejoins readings whose level was separated from the units, e.g.
+/// ["300", "M", "-"] becomes ["300M", "-"]. Each reading comes with the
+/// token it starts at, to point errors at.
+fn join_split_readings<'a>(tokens: &[&'a str], options: &ParseOptions) -> Vec<(&'a str, String)> {
+    let mut readings: Vec<(&str, String)> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let is_level = matches!(*token, "l" | "L" | "m" | "M" | "h" | "H" | "?");
+        match readings.last_mut() {
+            Some((_, units)) if is_level && is_price(units, options) => units.push_str(token),
+            _ => readings.push((token, token.to_string())),
+        }
+    }
+    readings
+}
+
+/// Where a PricesReader got to. Only meaningful for the same file, or the
+/// same file with more appended.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Byte offset of the next line to read.
+    pub offset: u64,
+    /// Lines read before it.
+    pub line: usize,
+    pub system: String,
+    pub station: String,
+    pub category: String,
+}
+
+/// Estimates of the distinct names read, compared in canonical form.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DistinctCounts {
+    systems: HyperLogLog,
+    stations: HyperLogLog,
+    commodities: HyperLogLog,
+}
+
+impl DistinctCounts {
+    pub fn systems(&self) -> u64 {
+        self.systems.estimate()
+    }
+
+    pub fn stations(&self) -> u64 {
+        self.stations.estimate()
+    }
+
+    pub fn commodities(&self) -> u64 {
+        self.commodities.estimate()
+    }
+
+    fn station(&mut self, system: &str, station: &str) {
+        let system = canonical_name(system);
+        self.stations
+            .insert(&format!("{}/{}", system, canonical_name(station)));
+        self.systems.insert(&system);
+    }
+}
+
+/// Reads PriceRecords from a .prices file, one item line at a time.
+pub struct PricesReader<R> {
+    reader: R,
+    buffer: String,
+    line: usize,
+    /// Bytes read so far, and the offset of the current line.
+    offset: u64,
+    line_offset: u64,
+    system: String,
+    station: String,
+    category: String,
+    options: ParseOptions,
+    /// Malformed lines seen so far.
+    errors: usize,
+    /// Set once max_errors has been exceeded.
+    stopped: bool,
+    /// Lowercased names of the items to expect, if given.
+    known_items: Option<HashSet<String>>,
+    /// Seconds since the unix epoch when the reader was made, if known.
+    now: Option<i64>,
+    warnings: Vec<ParseWarning>,
+    /// The stations to read, if not all of them.
+    stations: Option<BloomFilter>,
+    /// Set while under a station the filter rules out.
+    skipping: bool,
+    /// Item lines skipped so far.
+    skipped: usize,
+    distinct: Option<DistinctCounts>,
+    /// Records parsed but not yet added to the metrics.
+    unreported: u64,
+}
+
+/// Records a reader parses before adding them to the metrics.
+const REPORT_EVERY: u64 = 4096;
+
+impl<R> Drop for PricesReader<R> {
+    fn drop(&mut self) {
+        metrics::add(Counter::RecordsParsed, self.unreported);
+    }
+}
+
+impl<R: BufRead> PricesReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            line: 0,
+            offset: 0,
+            line_offset: 0,
+            system: String::new(),
+            station: String::new(),
+            category: String::new(),
+            options: ParseOptions::default(),
+            errors: 0,
+            stopped: false,
+            known_items: None,
+            now: clock::unix_time(),
+            warnings: Vec::new(),
+            stations: None,
+            skipping: false,
+            skipped: 0,
+            distinct: None,
+            unreported: 0,
+        }
+    }
+
+    pub fn options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Warns about items not in `items`, compared by canonical name.
+    pub fn known_items<I, S>(mut self, items: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let items = items.into_iter().map(|item| canonical_name(item.as_ref()));
+        self.known_items = Some(items.collect());
+        self
+    }
+
+    /// Reads only the stations in `stations`: item lines of any station the
+    /// filter rules out are skipped without being parsed.
+    pub fn only_stations(mut self, stations: BloomFilter) -> Self {
+        self.stations = Some(stations);
+        self.update_skipping();
+        self
+    }
+
+    fn update_skipping(&mut self) {
+        self.skipping = !self.station.is_empty()
+            && self
+                .stations
+                .as_ref()
+                .is_some_and(|stations| !stations.contains_station(&self.system, &self.station));
+    }
+
+    /// Estimates the distinct systems, stations and commodities read.
+    pub fn count_distinct(mut self) -> Self {
+        self.distinct = Some(DistinctCounts::default());
+        self
+    }
+
+    /// The distinct counts so far, if `count_distinct` was asked for.
+    pub fn distinct(&self) -> Option<&DistinctCounts> {
+        self.distinct.as_ref()
+    }
+
+    /// Item lines skipped so far for being under a station not in the
+    /// `only_stations` filter.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Carries on from a checkpoint; the reader must already be positioned
+    /// at its offset.
+    pub fn resume(mut self, checkpoint: &Checkpoint) -> Self {
+        self.offset = checkpoint.offset;
+        self.line = checkpoint.line;
+        self.system = checkpoint.system.clone();
+        self.station = checkpoint.station.clone();
+        self.category = checkpoint.category.clone();
+        self.update_skipping();
+        self
+    }
+
+    /// The state after the lines read so far.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            offset: self.offset,
+            line: self.line,
+            system: self.system.clone(),
+            station: self.station.clone(),
+            category: self.category.clone(),
+        }
+    }
+
+    /// Returns the warnings raised since the last call.
+    pub fn take_warnings(&mut self) -> Vec<ParseWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Number of lines consumed so far.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// An error about the current line as a whole, located at its first
+    /// non-blank char.
+    fn error(&self, message: impl Into<String>) -> PricesError {
+        self.error_at(self.buffer.trim_start(), message)
+    }
+
+    /// The span of `token`, which must be a slice of the current line.
+    fn span_at(&self, token: &str) -> Span {
+        let at = (token.as_ptr() as usize).saturating_sub(self.buffer.as_ptr() as usize);
+        Span::in_line(&self.buffer, self.line, self.line_offset, at)
+    }
+
+    fn error_at(&self, token: &str, message: impl Into<String>) -> PricesError {
+        PricesError::Parse {
+            span: self.span_at(token),
+            message: message.into(),
+        }
+    }
+
+    fn warning_at(&self, token: &str, kind: WarningKind, message: String) -> ParseWarning {
+        ParseWarning {
+            kind,
+            span: self.span_at(token),
+            message,
+        }
+    }
+
+    fn parse_item(
+        &self,
+        text: &str,
+        warnings: &mut Vec<ParseWarning>,
+    ) -> Result<PriceRecord, PricesError> {
+        if self.station.is_empty() {
+            return Err(self.error("item listed before any '@ SYSTEM/Station' line"));
+        }
+        let mut tokens: Vec<&str> = text.split_whitespace().collect();
+
+        let mut modified = None;
+        let mut stamp = "";
+        // Readings never contain a ':', so that marks a trailing timestamp.
+        if tokens.len() >= 2 && tokens[tokens.len() - 1].contains(':') {
+            let (date, time) = (tokens[tokens.len() - 2], tokens[tokens.len() - 1]);
+            modified = Some(parse_timestamp(date, time).ok_or_else(|| {
+                self.error_at(date, format!("invalid timestamp: {} {}", date, time))
+            })?);
+            stamp = date;
+            tokens.truncate(tokens.len() - 2);
+        }
+
+        // The name runs up to the first pair of prices.
+        let is_price = |token: &str| is_price(token, &self.options);
+        let prices = (1..tokens.len().saturating_sub(1))
+            .find(|&idx| is_price(tokens[idx]) && is_price(tokens[idx + 1]))
+            .ok_or_else(|| self.error(format!("expected item name and prices: {}", text)))?;
+        let price = |token: &str| {
+            parse_number(token, &self.options)
+                .ok()
+                .and_then(|price| i32::try_from(price).ok())
+                .ok_or_else(|| self.error_at(token, format!("price out of range: {}", token)))
+        };
+        let level = |&(start, token): &(&str, &str)| {
+            let reading = if self.options.lenient() {
+                parse_supply_level_lenient(token, self.options.decimal)
+            } else {
+                parse_supply_level(token)
+            };
+            reading.map_err(|e| self.error_at(start, format!("{}: {}", e, token)))
+        };
+        let joined;
+        let readings: Vec<(&str, &str)> = if self.options.lenient() && tokens.len() > prices + 4 {
+            joined = join_split_readings(&tokens[prices + 2..], &self.options);
+            joined
+                .iter()
+                .map(|(start, reading)| (*start, reading.as_str()))
+                .collect()
+        } else {
+            tokens[prices + 2..]
+                .iter()
+                .map(|&token| (token, token))
+                .collect()
+        };
+        let ((demand_units, demand_level), (supply_units, supply_level)) = match readings.as_slice()
+        {
+            [] => ((-1, -1), (-1, -1)),
+            [demand, supply] => (level(demand)?, level(supply)?),
+            [(start, _)] | [_, _, (start, _), ..] => {
+                return Err(self.error_at(start, format!("unexpected fields: {}", text)))
+            }
+        };
+
+        let record = PriceRecord {
+            system: self.system.clone(),
+            station: self.station.clone(),
+            category: self.category.clone(),
+            item: tokens[..prices].join(" "),
+            demand_price: price(tokens[prices])?,
+            demand_units,
+            demand_level,
+            supply_price: price(tokens[prices + 1])?,
+            supply_units,
+            supply_level,
+            modified,
+            line: self.line,
+        };
+
+        if let Some(known) = &self.known_items {
+            if !known.contains(&canonical_name(&record.item)) {
+                let message = format!("unknown item: {}", record.item);
+                warnings.push(self.warning_at(tokens[0], WarningKind::UnknownItem, message));
+            }
+        }
+        if let (Some(modified), Some(now)) = (record.modified, self.now) {
+            if modified > now + CLOCK_SKEW {
+                let message = format!("timestamp is {}s in the future", modified - now);
+                warnings.push(self.warning_at(stamp, WarningKind::FutureTimestamp, message));
+            }
+        }
+        if record.demand_price == 0 && record.supply_price == 0 {
+            let message = "item is neither bought nor sold".to_string();
+            warnings.push(self.warning_at(tokens[prices], WarningKind::ZeroPrice, message));
+        } else if record.demand_price == 0 && record.demand_units > 0 {
+            let message = format!("{} units in demand at a price of 0", record.demand_units);
+            warnings.push(self.warning_at(tokens[prices], WarningKind::ZeroPrice, message));
+        } else if record.supply_price == 0 && record.supply_units > 0 {
+            let message = format!("{} units in supply at a price of 0", record.supply_units);
+            warnings.push(self.warning_at(tokens[prices + 1], WarningKind::ZeroPrice, message));
+        }
+        Ok(record)
+    }
+}
+
+impl<R: BufRead> PricesReader<R> {
+    fn next_record(&mut self) -> Option<Result<PriceRecord, PricesError>> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(read) => {
+                    self.line += 1;
+                    self.line_offset = self.offset;
+                    self.offset += read as u64;
+                }
+                Err(e) => return Some(Err(e.into())),
+            }
+            let text = self.buffer.trim();
+            if text.is_empty() || text.starts_with(self.options.comment_char) {
+                continue;
+            }
+            if let Some(rest) = text.strip_prefix('@') {
+                let Some((system, station)) = rest.trim().split_once('/') else {
+                    return Some(Err(
+                        self.error(format!("expected @ SYSTEM/Station: {}", text))
+                    ));
+                };
+                self.system = system.trim().to_string();
+                self.station = station.trim().to_string();
+                self.category.clear();
+                self.update_skipping();
+                if let Some(distinct) = self.distinct.as_mut().filter(|_| !self.skipping) {
+                    distinct.station(&self.system, &self.station);
+                }
+                continue;
+            }
+            if let Some(rest) = text.strip_prefix('+') {
+                self.category = rest.trim().to_string();
+                continue;
+            }
+            if self.skipping {
+                self.skipped += 1;
+                continue;
+            }
+            let mut warnings = Vec::new();
+            let record = self.parse_item(text, &mut warnings);
+            self.warnings.append(&mut warnings);
+            if let (Some(distinct), Ok(record)) = (self.distinct.as_mut(), &record) {
+                distinct.commodities.insert(&canonical_name(&record.item));
+            }
+            return Some(record);
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PricesReader<R> {
+    type Item = Result<PriceRecord, PricesError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stopped {
+            return None;
+        }
+        let record = self.next_record()?;
+        match record {
+            Ok(_) => {
+                self.unreported += 1;
+                if self.unreported == REPORT_EVERY {
+                    metrics::add(Counter::RecordsParsed, REPORT_EVERY);
+                    self.unreported = 0;
+                }
+            }
+            Err(PricesError::Parse { .. }) => {
+                self.errors += 1;
+                self.stopped = self.options.too_many_errors(self.errors);
+            }
+            Err(_) => {}
+        }
+        Some(record)
+    }
+}
+
+/// Reads .prices data already in memory, such as a file a browser handed
+/// over, skipping any byte-order mark.
+pub fn read_prices<'a>(
+    data: &'a [u8],
+    parse_options: &ParseOptions,
+) -> io::Result<PricesReader<&'a [u8]>> {
+    let mut data = data;
+    let skipped = skip_bom(&mut data)?;
+    let mut reader = PricesReader::new(data).options(parse_options.clone());
+    reader.offset = skipped as u64;
+    Ok(reader)
+}
+
+/// Opens the .prices data of any source, gzip-compressed or not, for
+/// streaming.
+pub fn read_source<S: DataSource>(
+    source: &S,
+    parse_options: &ParseOptions,
+) -> io::Result<PricesReader<DecodedReader<S::Reader>>> {
+    let (reader, skipped) = source::open_decoded(source)?;
+    let mut reader = PricesReader::new(reader).options(parse_options.clone());
+    reader.offset = skipped as u64;
+    Ok(reader)
+}
+
+/// Opens a .prices file, gzip-compressed or not, for streaming.
+#[cfg(feature = "fs")]
+pub fn open_prices(
+    filename: impl AsRef<Path>,
+    options: &ReadOptions,
+    parse_options: &ParseOptions,
+) -> io::Result<PricesReader<InputReader>> {
+    let (reader, skipped) = open_decoded(filename, options)?;
+    let mut reader = PricesReader::new(reader).options(parse_options.clone());
+    // offsets count from the start of the file, byte-order mark included
+    reader.offset = skipped as u64;
+    Ok(reader)
+}
+
+/// What a .prices file holds, from a pass over it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PricesSummary {
+    pub records: usize,
+    /// Lines that failed to parse.
+    pub errors: usize,
+    pub warnings: usize,
+    pub distinct: DistinctCounts,
+}
+
+/// Streams .prices data and counts what's in it: records, failed lines,
+/// warnings and estimates of the distinct systems, stations and
+/// commodities. Only I/O errors fail it.
+#[tracing::instrument(skip_all, fields(source = source.name()))]
+pub fn summarize_prices<S: DataSource>(
+    source: &S,
+    parse_options: &ParseOptions,
+) -> io::Result<PricesSummary> {
+    let parse_options = ParseOptions {
+        max_errors: None,
+        ..parse_options.clone()
+    };
+    let mut reader = read_source(source, &parse_options)?.count_distinct();
+    let mut summary = PricesSummary::default();
+    // warnings are counted as they come rather than piling up in the reader
+    while let Some(record) = reader.next() {
+        match record {
+            Ok(_) => summary.records += 1,
+            Err(PricesError::Io(e)) => return Err(e),
+            Err(PricesError::Parse { .. }) => summary.errors += 1,
+        }
+        summary.warnings += reader.take_warnings().len();
+    }
+    summary.distinct = reader.distinct.take().unwrap_or_default();
+    info!(
+        records = summary.records,
+        errors = summary.errors,
+        systems = summary.distinct.systems(),
+        stations = summary.distinct.stations(),
+        commodities = summary.distinct.commodities(),
+        "summarized .prices file"
+    );
+    Ok(summary)
+}
+
+/// Opens a .prices file to carry on from a checkpoint. A checkpoint at the
+/// very start is the same as open_prices.
+#[cfg(feature = "fs")]
+pub fn resume_prices(
+    filename: impl AsRef<Path>,
+    options: &ReadOptions,
+    parse_options: &ParseOptions,
+    checkpoint: &Checkpoint,
+) -> io::Result<PricesReader<InputReader>> {
+    if checkpoint.offset == 0 {
+        return open_prices(filename, options, parse_options);
+    }
+    let reader = open_decoded_at(filename, options, checkpoint.offset)?;
+    Ok(PricesReader::new(reader)
+        .options(parse_options.clone())
+        .resume(checkpoint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{DecimalSeparator, Strictness};
+    use crate::span::Span;
+    #[cfg(feature = "fs")]
+    use std::io::Write;
+
+    const SAMPLE: &str = "\
+# TradeDangerous prices
+@ SOL/Abraham Lincoln
+   + Chemicals
+      Explosives          265      0        ?        -
+      Hydrogen Fuel       105    110    2079L   28430H  2024-05-01 12:00:00
+
+@ LAVE/Lave Station
+   + Metals
+      Gold              9500   9000       -     120M
+";
+
+    fn parse(text: &str) -> Vec<Result<PriceRecord, PricesError>> {
+        PricesReader::new(text.as_bytes()).collect()
+    }
+
+    fn lenient(text: &str, decimal: DecimalSeparator) -> PricesReader<&[u8]> {
+        PricesReader::new(text.as_bytes()).options(ParseOptions {
+            strictness: Strictness::Lenient,
+            decimal,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01", "00:00:00"), Some(0));
+        assert_eq!(parse_timestamp("2024-05-01", "12:00:00"), Some(1714564800));
+        assert_eq!(parse_timestamp("2000-02-29", "23:59:59"), Some(951868799));
+        assert_eq!(parse_timestamp("2024-13-01", "12:00:00"), None);
+        assert_eq!(parse_timestamp("2024-02-29", "00:00:00"), Some(1709164800));
+        assert_eq!(parse_timestamp("2024-12-31", "00:00:00"), Some(1735603200));
+        for date in [
+            "2024-02-30",
+            "2023-02-29",
+            "1900-02-29",
+            "2024-04-31",
+            "2024-05-00",
+        ] {
+            assert_eq!(parse_timestamp(date, "12:00:00"), None, "{}", date);
+        }
+        assert_eq!(parse_timestamp("2024-05", "12:00:00"), None);
+        assert_eq!(parse_timestamp("2024-05-01", "noon"), None);
+        assert_eq!(parse_timestamp("2024-05-01", "-1:00:00"), None);
+        assert_eq!(
+            parse_timestamp("9223372036854775807-01-01", "00:00:00"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_prices_reader() {
+        let records: Vec<PriceRecord> = parse(SAMPLE).into_iter().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 3);
+
+        let explosives = &records[0];
+        assert_eq!(explosives.system, "SOL");
+        assert_eq!(explosives.station, "Abraham Lincoln");
+        assert_eq!(explosives.category, "Chemicals");
+        assert_eq!(explosives.item, "Explosives");
+        assert_eq!((explosives.demand_price, explosives.supply_price), (265, 0));
+        assert_eq!((explosives.demand_units, explosives.demand_level), (-1, -1));
+        assert_eq!((explosives.supply_units, explosives.supply_level), (0, 0));
+        assert_eq!(explosives.modified, None);
+        assert_eq!(explosives.line, 4);
+
+        let fuel = &records[1];
+        assert_eq!(fuel.item, "Hydrogen Fuel");
+        assert_eq!((fuel.demand_units, fuel.demand_level), (2079, 1));
+        assert_eq!((fuel.supply_units, fuel.supply_level), (28430, 3));
+        assert_eq!(fuel.modified, Some(1714564800));
+
+        let gold = &records[2];
+        assert_eq!(
+            (gold.system.as_str(), gold.station.as_str()),
+            ("LAVE", "Lave Station")
+        );
+        assert_eq!(gold.category, "Metals");
+        assert_eq!((gold.supply_units, gold.supply_level), (120, 2));
+        assert_eq!(gold.line, 9);
+    }
+
+    #[test]
+    fn test_prices_without_readings() {
+        let records = parse("@ SOL/Abraham Lincoln\nGold 9500 9000\n");
+        let gold = records[0].as_ref().unwrap();
+        assert_eq!((gold.demand_price, gold.supply_price), (9500, 9000));
+        assert_eq!((gold.demand_units, gold.supply_units), (-1, -1));
+    }
+
+    #[test]
+    fn test_prices_lenient() {
+        let text = "@ SOL/A\nGold 9500 9000 300 M 120 h\nSilver 1 2 0 ? ?\nTea 1 2 5 L -\n";
+        assert!(parse(text).iter().all(Result::is_err));
+
+        let records: Vec<PriceRecord> = lenient(text, DecimalSeparator::Point)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!((records[0].demand_units, records[0].demand_level), (300, 2));
+        assert_eq!((records[0].supply_units, records[0].supply_level), (120, 3));
+        assert_eq!((records[1].demand_units, records[1].demand_level), (0, -1));
+        assert_eq!((records[1].supply_units, records[1].supply_level), (-1, -1));
+        assert_eq!((records[2].supply_units, records[2].supply_level), (0, 0));
+
+        let text = "@ SOL/A\nGold 9,500 9_000 1,234 M 120,000H\n";
+        assert!(parse(text)[0].is_err());
+        let gold = lenient(text, DecimalSeparator::Point)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!((gold.demand_price, gold.supply_price), (9500, 9000));
+        assert_eq!((gold.demand_units, gold.supply_units), (1234, 120000));
+
+        // with a decimal comma, '.' groups thousands instead
+        let text = "@ SOL/A\nGold 9.500 9.000 1.234 M -\n";
+        let gold = lenient(text, DecimalSeparator::Comma)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!((gold.demand_price, gold.demand_units), (9500, 1234));
+        assert!(lenient(text, DecimalSeparator::Point)
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_prices_errors() {
+        let message = |text: &str| match parse(text).remove(0) {
+            Err(PricesError::Parse { span, message }) => (span.line, message),
+            other => panic!("expected a parse error, got {:?}", other),
+        };
+        assert_eq!(message("Gold 1 2\n").0, 1);
+        assert!(message("@ SOL\n").1.contains("SYSTEM/Station"));
+        assert!(message("@ SOL/A\nGold\n").1.contains("expected item name"));
+        assert!(message("@ SOL/A\nGold 1 2 3L\n").1.contains("unexpected"));
+        assert!(message("@ SOL/A\nGold 1 2 3X -\n")
+            .1
+            .contains("invalid unit"));
+        assert!(message("@ SOL/A\nGold 1 2 - - 2024-99-01 00:00:00\n")
+            .1
+            .contains("timestamp"));
+
+        // parsing carries on after a bad line
+        let records = parse("@ SOL/A\nGold x 2\nSilver 1 2\n");
+        assert!(records[0].is_err());
+        assert_eq!(records[1].as_ref().unwrap().item, "Silver");
+    }
+
+    #[test]
+    fn test_prices_error_spans() {
+        let span = |text: &str, options: ParseOptions| match PricesReader::new(text.as_bytes())
+            .options(options)
+            .next()
+        {
+            Some(Err(PricesError::Parse { span, .. })) => span,
+            other => panic!("expected a parse error, got {:?}", other),
+        };
+        let strict = |text: &str| span(text, ParseOptions::default());
+
+        let located = strict("@ SOL/A\n  Gold 1 2 3X -\n");
+        assert_eq!(
+            located,
+            Span {
+                offset: 19,
+                line: 2,
+                column: 12,
+                snippet: "  Gold 1 2 3X -".into(),
+            }
+        );
+        // whole-line errors point at the first non-blank char
+        assert_eq!(strict("# x\n\n   @ SOL\n").column, 4);
+        assert_eq!(strict("# x\n\n   @ SOL\n").offset, 8);
+        assert_eq!(strict("@ SOL/A\nGold 1 2 3L\n").column, 10);
+        assert_eq!(strict("@ SOL/A\nGold 1 2 - - x\n").column, 14);
+        assert_eq!(strict("@ SOL/A\nGold 1 99999999999 - -\n").column, 8);
+        assert_eq!(
+            strict("@ SOL/A\nGold 1 2 - - 2024-99-01 00:00:00\n").column,
+            14
+        );
+        // columns are of the original text, not of rejoined readings
+        let lenient = ParseOptions {
+            strictness: Strictness::Lenient,
+            ..Default::default()
+        };
+        assert_eq!(span("@ SOL/A\nGold 1 2 3 M 4 H 5\n", lenient).column, 18);
+
+        // offsets include a skipped byte-order mark
+        let mut reader = PricesReader::new("x\n".as_bytes());
+        reader.offset = 3;
+        match reader.next() {
+            Some(Err(e @ PricesError::Parse { .. })) => {
+                assert!(e.to_string().starts_with("line 1, column 1: item listed"));
+                assert!(matches!(e, PricesError::Parse { span, .. } if span.offset == 3));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_prices_warnings() {
+        let text = "@ SOL/A\n\
+                    Gold 9500 9000 - -\n\
+                    Unobtainium 1 2 - -\n\
+                    Silver 1 2 - - 2999-01-01 00:00:00\n\
+                    Tea 0 0 - -\n\
+                    Wine 0 10 300M -\n\
+                    Beer 5 0 - 100H\n\
+                    Fish 5 0 - -\n";
+        let mut reader = PricesReader::new(text.as_bytes()).known_items([
+            "gold",
+            "Silver",
+            "Tea",
+            "Wine",
+            "Beer",
+            "ＦＩＳＨ",
+        ]);
+        let mut warned = Vec::new();
+        while let Some(record) = reader.next() {
+            let line = record.unwrap().line;
+            for warning in reader.take_warnings() {
+                assert_eq!(warning.span.line, line);
+                warned.push((line, warning.kind, warning.span.column));
+            }
+        }
+        assert_eq!(
+            warned,
+            [
+                (3, WarningKind::UnknownItem, 1),
+                (4, WarningKind::FutureTimestamp, 16),
+                (5, WarningKind::ZeroPrice, 5),
+                (6, WarningKind::ZeroPrice, 6),
+                (7, WarningKind::ZeroPrice, 8),
+            ]
+        );
+
+        // without known items, names aren't checked
+        let mut reader = PricesReader::new("@ SOL/A\nUnobtainium 1 2\n".as_bytes());
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_read_prices() {
+        let data = b"\xef\xbb\xbf@ SOL/A\n+ Metals\nGold 1 2\nSilver x 4\n";
+        let mut reader = read_prices(data, &ParseOptions::default()).unwrap();
+        assert_eq!(reader.checkpoint().offset, 3);
+        let gold = reader.next().unwrap().unwrap();
+        assert_eq!((gold.system.as_str(), gold.item.as_str()), ("SOL", "Gold"));
+        // spans count the byte-order mark, as they do for files
+        match reader.next() {
+            Some(Err(PricesError::Parse { span, .. })) => assert_eq!(span.offset, 29),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        assert!(read_prices(b"\xff\xfe@\0", &ParseOptions::default()).is_err());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_prices_resume() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"\xef\xbb\xbf@ SOL/A\n+ Metals\nGold 1 2\nSilver 3 4\n# end\n")
+            .unwrap();
+        let (options, parse_options) = (ReadOptions::default(), ParseOptions::default());
+        let mut reader = open_prices(file.path(), &options, &parse_options).unwrap();
+        assert_eq!(reader.checkpoint().offset, 3);
+        assert_eq!(reader.next().unwrap().unwrap().item, "Gold");
+        let checkpoint = reader.checkpoint();
+        assert_eq!(
+            checkpoint,
+            Checkpoint {
+                offset: 29,
+                line: 3,
+                system: "SOL".into(),
+                station: "A".into(),
+                category: "Metals".into(),
+            }
+        );
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+        let end = reader.checkpoint();
+
+        file.write_all(b"@ SOL/B\nTea 5 6 x\n").unwrap();
+        let resumed = |checkpoint: &Checkpoint| {
+            resume_prices(file.path(), &options, &parse_options, checkpoint)
+                .unwrap()
+                .map(|record| match record {
+                    Ok(record) => format!(
+                        "{} {} {} {}",
+                        record.line, record.station, record.category, record.item
+                    ),
+                    Err(e) => e.to_string(),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            resumed(&checkpoint),
+            [
+                "4 A Metals Silver",
+                "line 7, column 9: unexpected fields: Tea 5 6 x"
+            ]
+        );
+        // only the appended lines are read, and errors are located in the
+        // whole file
+        assert_eq!(
+            resumed(&end),
+            ["line 7, column 9: unexpected fields: Tea 5 6 x"]
+        );
+        match resume_prices(file.path(), &options, &parse_options, &end)
+            .unwrap()
+            .next()
+        {
+            Some(Err(PricesError::Parse { span, .. })) => assert_eq!(span.offset, 62),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        assert_eq!(resumed(&Checkpoint::default()).len(), 3);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_prices_gzip() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(b"\xef\xbb\xbf@ SOL/A\n+ Metals\nGold 1 2\nSilver 3 4\n")
+            .unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&compressed).unwrap();
+        let (options, parse_options) = (ReadOptions::default(), ParseOptions::default());
+        let mut reader = open_prices(file.path(), &options, &parse_options).unwrap();
+        assert_eq!(reader.checkpoint().offset, 3);
+        assert_eq!(reader.next().unwrap().unwrap().item, "Gold");
+        // offsets count decompressed bytes, so checkpoints resume
+        let checkpoint = reader.checkpoint();
+        let mut resumed =
+            resume_prices(file.path(), &options, &parse_options, &checkpoint).unwrap();
+        assert_eq!(resumed.next().unwrap().unwrap().item, "Silver");
+        assert!(resumed.next().is_none());
+
+        // a download cut short says so instead of failing to parse
+        let mut truncated = tempfile::NamedTempFile::new().unwrap();
+        truncated
+            .write_all(&compressed[..compressed.len() - 3])
+            .unwrap();
+        let errors: Vec<String> = open_prices(truncated.path(), &options, &parse_options)
+            .unwrap()
+            .filter_map(|record| record.err().map(|e| e.to_string()))
+            .collect();
+        assert_eq!(
+            errors,
+            [format!(
+                "file is truncated at byte {}",
+                compressed.len() - 3
+            )]
+        );
+    }
+
+    #[test]
+    fn test_prices_max_errors() {
+        let text = "@ SOL/A\nGold x 2\nSilver 1 2\nTea y 2\nWine 1 2\nBeer z 2\nFish 1 2\n";
+        let read = |max_errors| {
+            let mut reader = PricesReader::new(text.as_bytes()).options(ParseOptions {
+                max_errors,
+                ..Default::default()
+            });
+            let results: Vec<bool> = reader.by_ref().map(|r| r.is_ok()).collect();
+            (results, reader.errors)
+        };
+        assert_eq!(read(None).0, [false, true, false, true, false, true]);
+        assert_eq!(read(None).1, 3);
+        // the error over the limit is the last thing returned
+        assert_eq!(read(Some(0)), (vec![false], 1));
+        assert_eq!(read(Some(1)).0, [false, true, false]);
+    }
+
+    #[test]
+    fn test_prices_comment_char() {
+        let text = "; exported by hand\n@ SOL/A\n# Gold 1 2\nSilver 1 2\n";
+        assert!(parse(text)[0].is_err());
+        let options = ParseOptions {
+            comment_char: ';',
+            ..Default::default()
+        };
+        let records: Vec<_> = PricesReader::new(text.as_bytes())
+            .options(options)
+            .collect();
+        // '#' is no longer a comment, so "# Gold" is an item line
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].as_ref().unwrap().item, "# Gold");
+    }
+
+    #[test]
+    fn test_prices_only_stations() {
+        let text = "@ SOL/Abraham Lincoln\nGold 1 2\n@ SOL/Daedalus\nGold 3 4\nSilver x\n\
+                    @ LHS 3447/Bluford Orbital\nGold 5 6\n";
+        let mut stations = BloomFilter::new(10, 0.001);
+        stations.insert_station("sol", "abraham lincoln");
+        stations.insert_station("LHS 3447", "Bluford Orbital");
+        let mut reader = PricesReader::new(text.as_bytes()).only_stations(stations.clone());
+        let stations_read: Vec<String> = reader
+            .by_ref()
+            .map(|record| record.unwrap().station)
+            .collect();
+        // Daedalus' lines aren't parsed, so its bad line isn't an error
+        assert_eq!(stations_read, ["Abraham Lincoln", "Bluford Orbital"]);
+        assert_eq!(reader.skipped(), 2);
+
+        // a checkpoint under a station that's filtered out stays filtered
+        let checkpoint = Checkpoint {
+            offset: 0,
+            line: 4,
+            system: "SOL".into(),
+            station: "Daedalus".into(),
+            category: String::new(),
+        };
+        let records: Vec<_> = PricesReader::new(&b"Gold 3 4\n"[..])
+            .resume(&checkpoint)
+            .only_stations(stations)
+            .collect();
+        assert!(records.is_empty());
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_summarize_prices() {
+        use crate::source::FileSource;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(
+            b"@ SOL/Abraham Lincoln\nGold 1 2\nSilver 3 4\n\
+              @ sol/daedalus\nGOLD 1 2\nTea x\nWine 0 0\n\
+              @ LHS 3447/Bluford Orbital\nGold 5 6\n",
+        )
+        .unwrap();
+        let parse_options = ParseOptions {
+            max_errors: Some(0),
+            ..Default::default()
+        };
+        let source = FileSource::new(file.path(), &ReadOptions::default());
+        let summary = summarize_prices(&source, &parse_options).unwrap();
+        assert_eq!(
+            (summary.records, summary.errors, summary.warnings),
+            (5, 1, 1)
+        );
+        let distinct = &summary.distinct;
+        assert_eq!(
+            (
+                distinct.systems(),
+                distinct.stations(),
+                distinct.commodities()
+            ),
+            (2, 3, 3)
+        );
+        let missing = FileSource::new(
+            file.path().with_extension("missing"),
+            &ReadOptions::default(),
+        );
+        assert!(summarize_prices(&missing, &parse_options).is_err());
+
+        // only what's read is counted
+        let mut stations = BloomFilter::new(10, 0.001);
+        stations.insert_station("Sol", "Daedalus");
+        let mut reader = open_prices(file.path(), &ReadOptions::default(), &Default::default())
+            .unwrap()
+            .only_stations(stations)
+            .count_distinct();
+        assert_eq!(reader.by_ref().count(), 3);
+        let distinct = reader.distinct().unwrap();
+        assert_eq!((distinct.stations(), distinct.commodities()), (1, 2));
+    }
+}
@@ -0,0 +1,362 @@
+//! Queries over a MarketStore: which listings of which commodities, near
+//! where, how fresh, best first, so commands like TradeDangerous' `buy`,
+//! `sell` and `market` get their rows without looking at each one in
+//! Python.
+//!
+//! A Query is built up a condition at a time, as in
+//! `Query::new().side(Side::Buy).filter(gold).within(origin, 20.)
+//! .max_age(7.).order_by(Order::Profit).limit(25)`, and then run against a
+//! store, answering with the matching listings column by column alongside
+//! their distances from the query's origin.
+
+use std::cmp::Ordering;
+
+use crate::clock::unix_time;
+use crate::ids::{ItemId, StationId};
+use crate::market::{StationItem, StationItemColumns};
+use crate::store::MarketStore;
+use crate::topn::top_n;
+
+const SECONDS_PER_DAY: f64 = 24. * 60. * 60.;
+
+/// Which side of a market a query wants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    /// Stations you can buy from: those with a supply price.
+    Buy,
+    /// Stations you can sell to: those with a demand price.
+    Sell,
+}
+
+/// How a query's results are ordered. Ties are broken by station, then
+/// item, so the same query of the same store always answers the same way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Order {
+    /// Best for the trader first: the cheapest supply price when buying,
+    /// the highest demand price when selling. With no side, by station.
+    #[default]
+    Profit,
+    /// Nearest first; without an origin, by station.
+    Distance,
+    /// Most recently updated first.
+    Age,
+    /// Most units first: supply when buying, demand when selling, and
+    /// otherwise the larger of the two.
+    Units,
+    /// By station, then item.
+    Station,
+}
+
+/// Listings answering a query, best first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueryResult {
+    pub listings: StationItemColumns,
+    /// Each listing's distance in ly from the query's origin; empty if it
+    /// had none.
+    pub distances: Vec<f64>,
+}
+
+impl QueryResult {
+    pub fn len(&self) -> usize {
+        self.listings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listings.is_empty()
+    }
+}
+
+/// A query of a MarketStore's listings. With no conditions it matches every
+/// listing.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    items: Vec<ItemId>,
+    stations: Vec<StationId>,
+    side: Option<Side>,
+    /// Where distances are measured from, and the furthest a station may be.
+    origin: Option<([f64; 3], f64)>,
+    max_age: Option<f64>,
+    now: Option<i64>,
+    order: Order,
+    limit: Option<usize>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only listings of this commodity; called more than once, of any of
+    /// them.
+    pub fn filter(mut self, item_id: ItemId) -> Self {
+        self.items.push(item_id);
+        self
+    }
+
+    /// Only listings at this station; called more than once, at any of
+    /// them.
+    pub fn station(mut self, station_id: StationId) -> Self {
+        self.stations.push(station_id);
+        self
+    }
+
+    /// Only listings on one side of the market.
+    pub fn side(mut self, side: Side) -> Self {
+        self.side = Some(side);
+        self
+    }
+
+    /// Only stations within `ly` of `origin`, with their distances in the
+    /// results. Stations without a position are left out. An infinite `ly`
+    /// gives distances without limiting them.
+    ///
+    /// Panics if `ly` is negative or NaN.
+    pub fn within(mut self, origin: [f64; 3], ly: f64) -> Self {
+        assert!(ly >= 0., "radius must be 0 or more, not {}", ly);
+        self.origin = Some((origin, ly));
+        self
+    }
+
+    /// Only listings updated in the last `days` days.
+    pub fn max_age(mut self, days: f64) -> Self {
+        self.max_age = Some(days);
+        self
+    }
+
+    /// The unix time ages are measured from, by default the clock's.
+    pub fn now(mut self, now: i64) -> Self {
+        self.now = Some(now);
+        self
+    }
+
+    pub fn order_by(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// At most `n` results, the best `n` picked without sorting the rest.
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    fn on_side(&self, item: &StationItem) -> bool {
+        match self.side {
+            Some(Side::Buy) => item.supply_price > 0,
+            Some(Side::Sell) => item.demand_price > 0,
+            None => true,
+        }
+    }
+
+    /// The listings that could match, before the conditions are checked,
+    /// from whichever of the store's indexes narrows them down.
+    fn candidates(&self, store: &MarketStore) -> Vec<StationItem> {
+        let mut items = if !self.stations.is_empty() {
+            let mut stations = self.stations.clone();
+            stations.sort_unstable();
+            stations.dedup();
+            stations
+                .into_iter()
+                .flat_map(|station_id| store.station_items(station_id))
+                .filter(|item| self.items.is_empty() || self.items.contains(&item.item_id))
+                .collect()
+        } else if !self.items.is_empty() {
+            let mut items = self.items.clone();
+            items.sort_unstable();
+            items.dedup();
+            items
+                .into_iter()
+                .flat_map(|item_id| store.item_listings(item_id))
+                .collect()
+        } else if let Some((origin, ly)) = self.origin.filter(|(_, ly)| ly.is_finite()) {
+            store
+                .stations_within(origin, ly)
+                .into_iter()
+                .flat_map(|(station_id, _)| store.station_items(station_id))
+                .collect()
+        } else {
+            store.iter().collect::<Vec<_>>()
+        };
+        items.retain(|item| self.on_side(item));
+        items
+    }
+
+    /// How a listing ranks under the query's order; smaller is better.
+    fn rank(&self, item: &StationItem, distance: f64) -> (f64, StationId, ItemId) {
+        let key = match self.order {
+            Order::Profit => match self.side {
+                Some(Side::Buy) => item.supply_price as f64,
+                Some(Side::Sell) => -(item.demand_price as f64),
+                None => 0.,
+            },
+            Order::Distance => distance,
+            Order::Age => -(item.modified as f64),
+            Order::Units => {
+                -(match self.side {
+                    Some(Side::Buy) => item.supply_units,
+                    Some(Side::Sell) => item.demand_units,
+                    None => item.supply_units.max(item.demand_units),
+                } as f64)
+            }
+            Order::Station => 0.,
+        };
+        (key, item.station_id, item.item_id)
+    }
+
+    /// Runs the query against a store.
+    pub fn run(&self, store: &MarketStore) -> QueryResult {
+        let now = self.now.or_else(unix_time);
+        let oldest = self
+            .max_age
+            .zip(now)
+            .map(|(days, now)| now as f64 - days * SECONDS_PER_DAY);
+        let found = self
+            .candidates(store)
+            .into_iter()
+            .filter(|item| oldest.is_none_or(|oldest| item.modified as f64 >= oldest))
+            .filter_map(|item| match self.origin {
+                None => Some((item, f64::NAN)),
+                Some((origin, ly)) => {
+                    let pos = store.station_position(item.station_id)?;
+                    let distance = distance(origin, pos);
+                    (distance <= ly).then_some((item, distance))
+                }
+            });
+        let by_rank = |(a, a_distance): &(StationItem, f64),
+                       (b, b_distance): &(StationItem, f64)| {
+            compare(self.rank(a, *a_distance), self.rank(b, *b_distance))
+        };
+        let found = match self.limit {
+            Some(limit) => top_n(found, limit, by_rank),
+            None => {
+                let mut found: Vec<(StationItem, f64)> = found.collect();
+                found.sort_by(by_rank);
+                found
+            }
+        };
+
+        let listings = found.iter().map(|(item, _)| item).collect();
+        let distances = match self.origin {
+            Some(_) => found.iter().map(|(_, distance)| *distance).collect(),
+            None => Vec::new(),
+        };
+        QueryResult {
+            listings,
+            distances,
+        }
+    }
+}
+
+fn compare(a: (f64, StationId, ItemId), b: (f64, StationId, ItemId)) -> Ordering {
+    a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2))
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let (dx, dy, dz) = (a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+    (dx * dx + dy * dy + dz * dz).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(station_id: u32, item_id: u32, demand_price: i32, supply_price: i32) -> StationItem {
+        StationItem {
+            station_id: StationId(station_id),
+            item_id: ItemId(item_id),
+            demand_price,
+            supply_price,
+            supply_units: supply_price.into(),
+            modified: 1_700_000_000 - station_id as i64 * 86400,
+            ..Default::default()
+        }
+    }
+
+    fn sample_store() -> MarketStore {
+        let mut store = MarketStore::new();
+        store.add_station(StationId(1), "Sol", 0., 0., 0.);
+        store.add_station(StationId(2), "Sol", 0., 0., 0.);
+        store.add_station(StationId(3), "Alpha Centauri", 3., 4., 0.);
+        store.add_station(StationId(4), "Far Away", 3., 4., 300.);
+        store.insert(item(1, 100, 0, 500));
+        store.insert(item(2, 100, 700, 450));
+        store.insert(item(3, 100, 900, 0));
+        store.insert(item(4, 100, 1000, 400));
+        store.insert(item(1, 200, 50, 40));
+        store.insert(item(5, 100, 0, 100));
+        store
+    }
+
+    fn stations(result: &QueryResult) -> Vec<u32> {
+        result.listings.station_id.iter().map(|id| id.0).collect()
+    }
+
+    #[test]
+    fn test_query_orders_by_profit() {
+        let store = sample_store();
+        let buy = Query::new().side(Side::Buy).filter(ItemId(100));
+        assert_eq!(stations(&buy.clone().run(&store)), [5, 4, 2, 1]);
+        assert_eq!(stations(&buy.clone().limit(2).run(&store)), [5, 4]);
+        let sell = Query::new().side(Side::Sell).filter(ItemId(100));
+        let result = sell.run(&store);
+        assert_eq!(stations(&result), [4, 3, 2]);
+        assert_eq!(result.listings.demand_price, [1000, 900, 700]);
+        assert!(result.distances.is_empty());
+        assert_eq!(
+            stations(&Query::new().filter(ItemId(100)).run(&store)),
+            [1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn test_query_within_and_max_age() {
+        let store = sample_store();
+        let near = Query::new()
+            .side(Side::Buy)
+            .within([0., 0., 0.], 10.)
+            .order_by(Order::Distance);
+        let result = near.clone().run(&store);
+        // station 5 has no position
+        assert_eq!(stations(&result), [1, 1, 2]);
+        assert_eq!(result.distances, [0., 0., 0.]);
+        let result = near.clone().within([3., 4., 0.], 400.).run(&store);
+        assert_eq!(stations(&result), [1, 1, 2, 4]);
+        assert_eq!(result.distances, [5., 5., 5., 300.]);
+
+        // wider than the galaxy: the stations are checked, not the cells
+        let result = near.clone().within([3., 4., 0.], 200_000.).run(&store);
+        assert_eq!(stations(&result), [1, 1, 2, 4]);
+
+        let fresh = Query::new().now(1_700_000_000).max_age(2.5);
+        assert_eq!(stations(&fresh.clone().run(&store)), [1, 1, 2]);
+        let newest = fresh.order_by(Order::Age).max_age(10.);
+        assert_eq!(stations(&newest.run(&store)), [1, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "radius must be 0 or more")]
+    fn test_query_within_negative() {
+        Query::new().within([0., 0., 0.], -1.);
+    }
+
+    #[test]
+    fn test_query_station_market() {
+        let store = sample_store();
+        let market = Query::new().station(StationId(1)).order_by(Order::Station);
+        let result = market.clone().run(&store);
+        assert_eq!(result.listings.row(1), item(1, 200, 50, 40));
+        assert_eq!(
+            market
+                .clone()
+                .order_by(Order::Units)
+                .run(&store)
+                .listings
+                .item_id,
+            [ItemId(100), ItemId(200)]
+        );
+        let result = market.station(StationId(3)).filter(ItemId(100)).run(&store);
+        assert_eq!(stations(&result), [1, 3]);
+        assert!(Query::new().filter(ItemId(300)).run(&store).is_empty());
+        assert_eq!(Query::new().run(&store).len(), store.len());
+    }
+}
@@ -0,0 +1,233 @@
+//! Named galactic regions ("Inner Orion Spur", "Empyrean Straits", ...).
+//!
+//! Regions are polygons on the galactic plane (x, z), so a system's region
+//! doesn't depend on its height above or below the plane. Maps are read
+//! from a JSON document of the form
+//! `[{"name": "Inner Orion Spur", "polygon": [[x, z], ...]}, ...]`.
+//! Where polygons overlap, the one listed first wins.
+//!
+//! [`RegionMap::galaxy`] is built from the 42 regions embedded in
+//! `regions.json`. Its outlines are coarse: the core, then three rings of
+//! sectors around it, each named after the region lying mostly there, so
+//! near a border it may name the neighbouring region. Load exact outlines
+//! with [`RegionMap::from_json`] where that matters.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::fs;
+#[cfg(feature = "fs")]
+use std::io;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+#[cfg(feature = "fs")]
+use tracing::info;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Region {
+    pub name: String,
+    /// Vertices as (x, z) pairs; the polygon is closed implicitly.
+    pub polygon: Vec<[f64; 2]>,
+}
+
+impl Region {
+    /// Even-odd test of whether (x, z) lies inside the polygon.
+    pub fn contains(&self, x: f64, z: f64) -> bool {
+        let mut inside = false;
+        let mut prev = match self.polygon.last() {
+            Some(p) => *p,
+            None => return false,
+        };
+        for &cur in self.polygon.iter() {
+            let ([x1, z1], [x2, z2]) = (prev, cur);
+            if (z1 > z) != (z2 > z) && x < x1 + (z - z1) * (x2 - x1) / (z2 - z1) {
+                inside = !inside;
+            }
+            prev = cur;
+        }
+        inside
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RegionMap {
+    regions: Vec<Region>,
+    /// (min_x, min_z, max_x, max_z) of each region, to skip most polygons.
+    bounds: Vec<[f64; 4]>,
+}
+
+impl RegionMap {
+    pub fn new(regions: Vec<Region>) -> Self {
+        let bounds = regions
+            .iter()
+            .map(|region| {
+                region.polygon.iter().fold(
+                    [
+                        f64::INFINITY,
+                        f64::INFINITY,
+                        f64::NEG_INFINITY,
+                        f64::NEG_INFINITY,
+                    ],
+                    |b, &[x, z]| [b[0].min(x), b[1].min(z), b[2].max(x), b[3].max(z)],
+                )
+            })
+            .collect();
+        Self { regions, bounds }
+    }
+
+    /// The galaxy's named regions, from the embedded `regions.json`.
+    pub fn galaxy() -> &'static RegionMap {
+        static GALAXY: OnceLock<RegionMap> = OnceLock::new();
+        GALAXY.get_or_init(|| {
+            Self::from_json(include_str!("regions.json")).expect("embedded regions.json is valid")
+        })
+    }
+
+    /// Parses a JSON list of regions.
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Ok(Self::new(serde_json::from_str(json)?))
+    }
+
+    /// Loads a JSON list of regions from a file.
+    #[cfg(feature = "fs")]
+    #[tracing::instrument(skip_all, fields(filename = %filename.as_ref().display()))]
+    pub fn load(filename: impl AsRef<Path>) -> io::Result<Self> {
+        let map = Self::from_json(&fs::read_to_string(filename)?)?;
+        info!(regions = map.len(), "loaded region map");
+        Ok(map)
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Name of the region containing a position, if any.
+    pub fn region_for(&self, x: f64, y: f64, z: f64) -> Option<&str> {
+        self.index_for(x, y, z)
+            .map(|idx| self.regions[idx].name.as_str())
+    }
+
+    /// Where in `regions` the region containing a position is, if any.
+    pub fn index_for(&self, x: f64, _y: f64, z: f64) -> Option<usize> {
+        self.regions
+            .iter()
+            .zip(self.bounds.iter())
+            .position(|(region, b)| {
+                b[0] <= x && x <= b[2] && b[1] <= z && z <= b[3] && region.contains(x, z)
+            })
+    }
+
+    /// Groups ids by the region their position falls in. Positions outside
+    /// every region are left out.
+    pub fn group<T, I>(&self, positions: I) -> BTreeMap<String, Vec<T>>
+    where
+        T: Ord,
+        I: IntoIterator<Item = (T, [f64; 3])>,
+    {
+        let mut groups: BTreeMap<String, Vec<T>> = BTreeMap::new();
+        for (id, pos) in positions {
+            if let Some(name) = self.region_for(pos[0], pos[1], pos[2]) {
+                groups.entry(name.to_string()).or_default().push(id);
+            }
+        }
+        for ids in groups.values_mut() {
+            ids.sort_unstable();
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGIONS: &str = r#"[
+        {"name": "Square", "polygon": [[0, 0], [10, 0], [10, 10], [0, 10]]},
+        {"name": "Notch", "polygon": [[20, 0], [30, 0], [30, 10], [25, 5], [20, 10]]},
+        {"name": "Big", "polygon": [[-100, -100], [100, -100], [100, 100], [-100, 100]]}
+    ]"#;
+
+    #[test]
+    fn test_region_contains() {
+        let map = RegionMap::from_json(REGIONS).unwrap();
+        let notch = &map.regions()[1];
+        assert!(notch.contains(21., 8.));
+        assert!(notch.contains(25., 4.));
+        assert!(!notch.contains(25., 7.));
+        assert!(!notch.contains(31., 5.));
+    }
+
+    #[test]
+    fn test_region_for() {
+        let map = RegionMap::from_json(REGIONS).unwrap();
+        assert_eq!(map.len(), 3);
+        // the height above the plane doesn't matter
+        assert_eq!(map.region_for(5., 1000., 5.), Some("Square"));
+        // overlaps go to the first region listed
+        assert_eq!(map.region_for(25., 0., 7.), Some("Big"));
+        assert_eq!(map.region_for(500., 0., 0.), None);
+        assert_eq!(map.index_for(25., 0., 4.), Some(1));
+        assert!(RegionMap::from_json("[{\"name\": \"x\"}]").is_err());
+    }
+
+    #[test]
+    fn test_region_group() {
+        let map = RegionMap::from_json(REGIONS).unwrap();
+        let groups = map.group(vec![
+            (3, [5., 0., 5.]),
+            (1, [1., 0., 1.]),
+            (2, [50., 0., 50.]),
+            (4, [500., 0., 0.]),
+        ]);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups["Square"], vec![1, 3]);
+        assert_eq!(groups["Big"], vec![2]);
+    }
+
+    #[test]
+    fn test_region_galaxy() {
+        let map = RegionMap::galaxy();
+        assert_eq!(map.len(), 42);
+        // Sol, Sagittarius A* and Colonia
+        assert_eq!(map.region_for(0., 0., 0.), Some("Inner Orion Spur"));
+        assert_eq!(
+            map.region_for(25.21875, -20.90625, 25899.96875),
+            Some("Galactic Centre")
+        );
+        assert_eq!(
+            map.region_for(-9530.5, -910.28125, 19808.125),
+            Some("Inner Orion-Perseus Conflux")
+        );
+        assert_eq!(map.region_for(0., 0., 90000.), None);
+        // every region is reachable
+        for region in map.regions() {
+            let [x, z] = region
+                .polygon
+                .iter()
+                .fold([0., 0.], |c, p| [c[0] + p[0], c[1] + p[1]]);
+            let n = region.polygon.len() as f64;
+            assert_eq!(map.region_for(x / n, 0., z / n), Some(region.name.as_str()));
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_region_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("regions.json");
+        fs::write(&path, REGIONS).unwrap();
+        let map = RegionMap::load(&path).unwrap();
+        assert_eq!(map.len(), 3);
+        assert!(RegionMap::load(dir.path().join("missing")).is_err());
+    }
+}
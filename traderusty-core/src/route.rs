@@ -0,0 +1,241 @@
+//! Computed routes and their export format.
+//!
+//! Routes serialize to a JSON document listing each hop's system, position,
+//! distance from the previous hop and what to do there, for feeding external
+//! visualizers; `system_list` gives the plain list of system names that can
+//! be pasted into the in-game route plotter one at a time. `to_csv` gives
+//! the same hops a row each for spreadsheets.
+
+use serde::Serialize;
+
+use crate::export::{self, CsvWriter};
+use crate::grid::distance;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteKind {
+    /// Jumps between systems only.
+    Nav,
+    /// Station to station with trades along the way.
+    Trade,
+}
+
+/// Something to do at a hop.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Top up the tank from the star.
+    Refuel,
+    Buy {
+        item: String,
+        units: u32,
+        price: i32,
+    },
+    Sell {
+        item: String,
+        units: u32,
+        price: i32,
+    },
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Refuel => write!(f, "refuel"),
+            Action::Buy { item, units, price } => write!(f, "buy {} {} @ {}", units, item, price),
+            Action::Sell { item, units, price } => {
+                write!(f, "sell {} {} @ {}", units, item, price)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Hop {
+    pub system: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub station: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    /// Straight-line distance in ly from the previous hop; 0 for the first.
+    pub distance: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<Action>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Route {
+    pub kind: RouteKind,
+    pub hops: Vec<Hop>,
+}
+
+/// The summary and hops that make up the exported document.
+#[derive(Serialize)]
+struct RouteDocument<'a> {
+    kind: RouteKind,
+    jumps: usize,
+    total_distance: f64,
+    hops: &'a [Hop],
+}
+
+impl Route {
+    pub fn new(kind: RouteKind) -> Self {
+        Self {
+            kind,
+            hops: Vec::new(),
+        }
+    }
+
+    /// Appends a hop, working out its distance from the previous one.
+    pub fn push(&mut self, system: &str, station: Option<&str>, pos: [f64; 3]) -> &mut Hop {
+        let distance = self
+            .hops
+            .last()
+            .map(|prev| distance([prev.x, prev.y, prev.z], pos))
+            .unwrap_or(0.);
+        self.hops.push(Hop {
+            system: system.to_string(),
+            station: station.map(str::to_string),
+            x: pos[0],
+            y: pos[1],
+            z: pos[2],
+            distance,
+            actions: Vec::new(),
+        });
+        self.hops.last_mut().unwrap()
+    }
+
+    /// Number of hops after the starting point.
+    pub fn jumps(&self) -> usize {
+        self.hops.len().saturating_sub(1)
+    }
+
+    pub fn total_distance(&self) -> f64 {
+        self.hops.iter().map(|hop| hop.distance).sum()
+    }
+
+    /// Serializes the route to its JSON export document, pretty-printed with
+    /// the given indent or compact if None.
+    pub fn to_json(&self, indent: Option<usize>) -> serde_json::Result<String> {
+        let document = RouteDocument {
+            kind: self.kind,
+            jumps: self.jumps(),
+            total_distance: self.total_distance(),
+            hops: &self.hops,
+        };
+        export::to_json(&document, indent)
+    }
+
+    /// The hops as CSV with a header row, the actions at each hop joined
+    /// into one column.
+    pub fn to_csv(&self) -> String {
+        let mut csv = CsvWriter::new();
+        csv.write_row(["system", "station", "x", "y", "z", "distance", "actions"]);
+        for hop in &self.hops {
+            let actions: Vec<String> = hop.actions.iter().map(ToString::to_string).collect();
+            csv.write_row([
+                hop.system.clone(),
+                hop.station.clone().unwrap_or_default(),
+                hop.x.to_string(),
+                hop.y.to_string(),
+                hop.z.to_string(),
+                hop.distance.to_string(),
+                actions.join("; "),
+            ]);
+        }
+        csv.finish()
+    }
+
+    /// The systems visited, one per line, skipping repeats of the same
+    /// system (e.g. hopping between stations in one system).
+    pub fn system_list(&self) -> String {
+        let mut systems: Vec<&str> = self.hops.iter().map(|hop| hop.system.as_str()).collect();
+        systems.dedup();
+        systems.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_route() -> Route {
+        let mut route = Route::new(RouteKind::Trade);
+        route
+            .push("Sol", Some("Abraham Lincoln"), [0., 0., 0.])
+            .actions
+            .push(Action::Buy {
+                item: "Gold".to_string(),
+                units: 720,
+                price: 9000,
+            });
+        route.push("Alpha Centauri", None, [3., 0., 4.]);
+        route
+            .push("Lave", Some("Lave Station"), [3., 12., 4.])
+            .actions
+            .push(Action::Sell {
+                item: "Gold".to_string(),
+                units: 720,
+                price: 10500,
+            });
+        route
+    }
+
+    #[test]
+    fn test_route_distances() {
+        let route = sample_route();
+        assert_eq!(route.jumps(), 2);
+        assert_eq!(route.hops[0].distance, 0.);
+        assert_eq!(route.hops[1].distance, 5.);
+        assert_eq!(route.hops[2].distance, 12.);
+        assert_eq!(route.total_distance(), 17.);
+    }
+
+    #[test]
+    fn test_route_json() {
+        let json = sample_route().to_json(None).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["kind"], "trade");
+        assert_eq!(value["jumps"], 2);
+        assert_eq!(value["total_distance"], 17.0);
+        assert_eq!(value["hops"][0]["station"], "Abraham Lincoln");
+        assert_eq!(value["hops"][0]["actions"][0]["action"], "buy");
+        assert_eq!(value["hops"][0]["actions"][0]["units"], 720);
+        // optional fields are left out rather than null
+        assert!(value["hops"][1].get("station").is_none());
+        assert!(value["hops"][1].get("actions").is_none());
+        assert_eq!(value["hops"][2]["y"], 12.0);
+
+        let pretty = sample_route().to_json(Some(2)).unwrap();
+        assert!(pretty.contains("\n  \"kind\": \"trade\""));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&pretty).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_route_csv() {
+        let mut route = sample_route();
+        route.hops[2].actions.push(Action::Refuel);
+        let csv = route.to_csv();
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "system,station,x,y,z,distance,actions");
+        assert_eq!(lines[1], "Sol,Abraham Lincoln,0,0,0,0,buy 720 Gold @ 9000");
+        assert_eq!(lines[2], "Alpha Centauri,,3,0,4,5,");
+        assert_eq!(
+            lines[3],
+            "Lave,Lave Station,3,12,4,12,sell 720 Gold @ 10500; refuel"
+        );
+    }
+
+    #[test]
+    fn test_route_system_list() {
+        let mut route = sample_route();
+        route.push("Lave", Some("Castellan Station"), [3., 12., 4.]);
+        assert_eq!(route.system_list(), "Sol\nAlpha Centauri\nLave");
+        assert_eq!(Route::new(RouteKind::Nav).system_list(), "");
+    }
+}
@@ -0,0 +1,479 @@
+//! Shortest routes over a jump graph for a ship that has to watch its fuel.
+//!
+//! Each jump burns fuel according to a fuel model, and the tank can only be
+//! topped up by scooping at main-sequence stars ("KGB FOAM"). The search is
+//! Dijkstra over (system, fuel remaining) labels: a label is dropped if the
+//! same system has already been reached at no more cost with at least as
+//! much fuel, so fuel stays exact rather than being rounded into buckets.
+//!
+//! In neutron mode, jumps leaving a neutron star are supercharged: they
+//! reach four times as far for the fuel of a normal jump a quarter of the
+//! length. The graph must have been built with `neutron_boosts` for those
+//! longer edges to exist.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use tracing::debug;
+
+use crate::cancel::CancelToken;
+use crate::fsd::NEUTRON_BOOST;
+use crate::graph::JumpGraph;
+use crate::metrics::{self, Counter};
+
+/// The broad spectral class of a system's primary star.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StarClass {
+    O,
+    B,
+    A,
+    F,
+    G,
+    K,
+    M,
+    WhiteDwarf,
+    Neutron,
+    BlackHole,
+    /// Anything else: brown dwarfs, T Tauri, carbon stars, unknown...
+    Other,
+}
+
+impl StarClass {
+    /// Parses a class as given in system dumps, e.g. "K", "G (White-Yellow)
+    /// star", "Neutron Star", "DA" or "N".
+    pub fn parse(class: &str) -> Self {
+        let class = class.trim();
+        let lower = class.to_ascii_lowercase();
+        if lower.starts_with("neutron") || class == "N" {
+            return StarClass::Neutron;
+        }
+        if lower.starts_with("white dwarf") || (class.starts_with('D') && class.len() <= 3) {
+            return StarClass::WhiteDwarf;
+        }
+        if lower.starts_with("black hole") || lower.starts_with("supermassive") || class == "H" {
+            return StarClass::BlackHole;
+        }
+        let first_word = class.split([' ', '_']).next().unwrap_or("");
+        match first_word {
+            "O" => StarClass::O,
+            "B" => StarClass::B,
+            "A" => StarClass::A,
+            "F" => StarClass::F,
+            "G" => StarClass::G,
+            "K" => StarClass::K,
+            "M" => StarClass::M,
+            _ => StarClass::Other,
+        }
+    }
+
+    /// True if fuel can be scooped from the star.
+    pub fn is_scoopable(self) -> bool {
+        matches!(
+            self,
+            StarClass::O
+                | StarClass::B
+                | StarClass::A
+                | StarClass::F
+                | StarClass::G
+                | StarClass::K
+                | StarClass::M
+        )
+    }
+}
+
+/// How much fuel a ship burns per jump.
+pub trait FuelModel {
+    /// Tank capacity in tons.
+    fn tank(&self) -> f64;
+
+    /// Fuel in tons needed to jump a distance, or None if it's beyond the
+    /// drive's range no matter how much fuel is in the tank.
+    fn fuel_for(&self, distance: f64) -> Option<f64>;
+}
+
+/// Fuel use proportional to distance, capped per jump.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinearFuel {
+    pub tank: f64,
+    pub fuel_per_ly: f64,
+    pub max_fuel_per_jump: f64,
+}
+
+impl FuelModel for LinearFuel {
+    fn tank(&self) -> f64 {
+        self.tank
+    }
+
+    fn fuel_for(&self, distance: f64) -> Option<f64> {
+        let fuel = distance * self.fuel_per_ly;
+        (fuel <= self.max_fuel_per_jump).then_some(fuel)
+    }
+}
+
+/// What a route costs: each jump costs jump_cost plus fuel_cost per ton
+/// burned, and each stop to scoop costs refuel_cost.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteCosts {
+    pub jump_cost: f64,
+    pub fuel_cost: f64,
+    pub refuel_cost: f64,
+}
+
+impl Default for RouteCosts {
+    fn default() -> Self {
+        Self {
+            jump_cost: 1.0,
+            fuel_cost: 0.1,
+            refuel_cost: 1.0,
+        }
+    }
+}
+
+/// One system along a plotted route.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Waypoint {
+    pub node: u32,
+    /// Fuel in the tank on leaving the system.
+    pub fuel: f64,
+    /// Whether the tank was topped up by scooping here.
+    pub refuelled: bool,
+    /// Whether the jump leaving here was supercharged.
+    pub supercharged: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    pub waypoints: Vec<Waypoint>,
+    pub cost: f64,
+}
+
+impl Plan {
+    pub fn jumps(&self) -> usize {
+        self.waypoints.len().saturating_sub(1)
+    }
+
+    pub fn refuels(&self) -> usize {
+        self.waypoints.iter().filter(|w| w.refuelled).count()
+    }
+
+    pub fn supercharges(&self) -> usize {
+        self.waypoints.iter().filter(|w| w.supercharged).count()
+    }
+}
+
+/// Per-node range multipliers for `JumpGraph::build_boosted` that give
+/// neutron stars their supercharged range.
+pub fn neutron_boosts(stars: &[StarClass]) -> Vec<f64> {
+    stars
+        .iter()
+        .map(|star| match star {
+            StarClass::Neutron => NEUTRON_BOOST,
+            _ => 1.,
+        })
+        .collect()
+}
+
+struct Label {
+    node: u32,
+    fuel: f64,
+    cost: f64,
+    refuelled: bool,
+    prev: Option<usize>,
+    /// Set once a cheaper label with at least as much fuel reaches the node.
+    dominated: bool,
+}
+
+/// Min-heap entry ordering labels by cost.
+struct Queued(f64, usize);
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0).then(other.1.cmp(&self.1))
+    }
+}
+
+pub struct Router<'a> {
+    graph: &'a JumpGraph,
+    /// Star class of each node in the graph.
+    stars: &'a [StarClass],
+    pub costs: RouteCosts,
+    /// Supercharge jumps leaving neutron stars.
+    pub neutron: bool,
+    /// Checked as the search goes; once cancelled it gives up, returning
+    /// None.
+    pub cancel: Option<CancelToken>,
+}
+
+impl<'a> Router<'a> {
+    pub fn new(graph: &'a JumpGraph, stars: &'a [StarClass]) -> Self {
+        Self {
+            graph,
+            stars,
+            costs: RouteCosts::default(),
+            neutron: false,
+            cancel: None,
+        }
+    }
+
+    /// Range multiplier for jumps leaving a node.
+    fn boost(&self, node: u32) -> f64 {
+        match self.stars.get(node as usize) {
+            Some(StarClass::Neutron) if self.neutron => NEUTRON_BOOST,
+            _ => 1.,
+        }
+    }
+
+    fn scoopable(&self, node: u32) -> bool {
+        self.stars
+            .get(node as usize)
+            .is_some_and(|star| star.is_scoopable())
+    }
+
+    /// Finds the cheapest route from start to goal that never runs the tank
+    /// dry, starting with `fuel` tons aboard. Returns None if there is none.
+    #[tracing::instrument(skip(self, model))]
+    pub fn route<F: FuelModel>(&self, start: u32, goal: u32, model: &F, fuel: f64) -> Option<Plan> {
+        if start as usize >= self.graph.node_count() {
+            return None;
+        }
+        let tank = model.tank();
+        let mut labels = vec![Label {
+            node: start,
+            fuel: fuel.min(tank),
+            cost: 0.,
+            refuelled: false,
+            prev: None,
+            dominated: false,
+        }];
+        let mut at_node: Vec<Vec<usize>> = vec![Vec::new(); self.graph.node_count()];
+        at_node[start as usize].push(0);
+        let mut queue = BinaryHeap::from([Queued(0., 0)]);
+        let _timer = metrics::time_stage("route");
+        let mut expanded = 0;
+
+        while let Some(Queued(cost, idx)) = queue.pop() {
+            if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                metrics::add(Counter::RouteNodesExpanded, expanded);
+                debug!(labels = labels.len(), "route search cancelled");
+                return None;
+            }
+            if labels[idx].dominated {
+                continue;
+            }
+            let (node, fuel) = (labels[idx].node, labels[idx].fuel);
+            expanded += 1;
+            if node == goal {
+                metrics::add(Counter::RouteNodesExpanded, expanded);
+                let plan = self.plan(&labels, idx);
+                debug!(
+                    labels = labels.len(),
+                    jumps = plan.jumps(),
+                    refuels = plan.refuels(),
+                    supercharges = plan.supercharges(),
+                    cost,
+                    "found route"
+                );
+                return Some(plan);
+            }
+            let boost = self.boost(node);
+            let targets = self.graph.neighbours(node);
+            let distances = self.graph.distances(node);
+            for (&target, &distance) in targets.iter().zip(distances) {
+                let needed = match model.fuel_for(distance as f64 / boost) {
+                    Some(needed) if needed <= fuel => needed,
+                    _ => continue,
+                };
+                let cost = cost + self.costs.jump_cost + self.costs.fuel_cost * needed;
+                let mut arrivals = vec![(fuel - needed, cost, false)];
+                if self.scoopable(target) && fuel - needed < tank {
+                    arrivals.push((tank, cost + self.costs.refuel_cost, true));
+                }
+                for (fuel, cost, refuelled) in arrivals {
+                    let existing = &mut at_node[target as usize];
+                    if existing
+                        .iter()
+                        .any(|&other| labels[other].cost <= cost && labels[other].fuel >= fuel)
+                    {
+                        continue;
+                    }
+                    existing.retain(|&other| {
+                        let worse = labels[other].cost >= cost && labels[other].fuel <= fuel;
+                        labels[other].dominated |= worse;
+                        !worse
+                    });
+                    let new_idx = labels.len();
+                    existing.push(new_idx);
+                    labels.push(Label {
+                        node: target,
+                        fuel,
+                        cost,
+                        refuelled,
+                        prev: Some(idx),
+                        dominated: false,
+                    });
+                    queue.push(Queued(cost, new_idx));
+                }
+            }
+        }
+        metrics::add(Counter::RouteNodesExpanded, expanded);
+        debug!(labels = labels.len(), "no route");
+        None
+    }
+
+    fn plan(&self, labels: &[Label], last: usize) -> Plan {
+        let mut waypoints: Vec<Waypoint> = Vec::new();
+        let mut cursor = Some(last);
+        while let Some(idx) = cursor {
+            let label = &labels[idx];
+            waypoints.push(Waypoint {
+                node: label.node,
+                fuel: label.fuel,
+                refuelled: label.refuelled,
+                supercharged: !waypoints.is_empty() && self.boost(label.node) > 1.,
+            });
+            cursor = label.prev;
+        }
+        waypoints.reverse();
+        Plan {
+            waypoints,
+            cost: labels[last].cost,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ship(tank: f64) -> LinearFuel {
+        LinearFuel {
+            tank,
+            fuel_per_ly: 0.1,
+            max_fuel_per_jump: 2.,
+        }
+    }
+
+    fn nodes(plan: &Plan) -> Vec<u32> {
+        plan.waypoints.iter().map(|w| w.node).collect()
+    }
+
+    #[test]
+    fn test_star_class_parse() {
+        assert_eq!(StarClass::parse("K"), StarClass::K);
+        assert_eq!(StarClass::parse("G (White-Yellow) star"), StarClass::G);
+        assert_eq!(StarClass::parse("M_RedGiant"), StarClass::M);
+        assert_eq!(StarClass::parse("Neutron Star"), StarClass::Neutron);
+        assert_eq!(StarClass::parse("N"), StarClass::Neutron);
+        assert_eq!(StarClass::parse("DA"), StarClass::WhiteDwarf);
+        assert_eq!(
+            StarClass::parse("White Dwarf (DA) Star"),
+            StarClass::WhiteDwarf
+        );
+        assert_eq!(StarClass::parse("Black Hole"), StarClass::BlackHole);
+        assert_eq!(StarClass::parse("L"), StarClass::Other);
+        assert!(StarClass::parse("F").is_scoopable());
+        assert!(!StarClass::parse("Neutron Star").is_scoopable());
+        assert!(!StarClass::parse("T").is_scoopable());
+    }
+
+    #[test]
+    fn test_route_straight_line() {
+        let positions: Vec<[f64; 3]> = (0..5).map(|i| [i as f64 * 10., 0., 0.]).collect();
+        let graph = JumpGraph::build(&positions, 15.);
+        let stars = vec![StarClass::G; 5];
+        let router = Router::new(&graph, &stars);
+        let plan = router.route(0, 4, &ship(10.), 10.).unwrap();
+        assert_eq!(nodes(&plan), vec![0, 1, 2, 3, 4]);
+        assert_eq!(plan.jumps(), 4);
+        assert_eq!(plan.refuels(), 0);
+        assert!((plan.waypoints[4].fuel - 6.).abs() < 1e-9);
+        assert!((plan.cost - 4.4).abs() < 1e-9);
+
+        assert!(router.route(0, 4, &ship(10.), 0.5).is_none());
+        assert!(router.route(99, 4, &ship(10.), 10.).is_none());
+    }
+
+    #[test]
+    fn test_route_refuels_at_scoopable_stars() {
+        // 0 - 1 - 2 - 3 in a line, 10ly apart: 1t per jump, 2t tank.
+        let positions: Vec<[f64; 3]> = (0..4).map(|i| [i as f64 * 10., 0., 0.]).collect();
+        let graph = JumpGraph::build(&positions, 15.);
+        let stars = [
+            StarClass::K,
+            StarClass::Other,
+            StarClass::M,
+            StarClass::Other,
+        ];
+        let router = Router::new(&graph, &stars);
+        let plan = router.route(0, 3, &ship(2.), 2.).unwrap();
+        assert_eq!(nodes(&plan), vec![0, 1, 2, 3]);
+        assert_eq!(plan.refuels(), 1);
+        assert!(plan.waypoints[2].refuelled);
+
+        // without the M star the ship is stranded
+        let stars = [
+            StarClass::K,
+            StarClass::Other,
+            StarClass::Neutron,
+            StarClass::Other,
+        ];
+        let router = Router::new(&graph, &stars);
+        assert!(router.route(0, 3, &ship(2.), 2.).is_none());
+    }
+
+    #[test]
+    fn test_route_detours_for_fuel() {
+        // Direct: 0 -> 1 -> 2 through an unscoopable star. Detour: 0 -> 3 -> 2
+        // through a scoopable one, slightly further.
+        let positions = [[0., 0., 0.], [10., 0., 0.], [20., 0., 0.], [10., 3., 0.]];
+        let graph = JumpGraph::build(&positions, 12.);
+        let stars = [StarClass::G, StarClass::Other, StarClass::G, StarClass::F];
+        let router = Router::new(&graph, &stars);
+        let plan = router.route(0, 2, &ship(10.), 10.).unwrap();
+        assert_eq!(nodes(&plan), vec![0, 1, 2]);
+
+        let plan = router.route(0, 2, &ship(1.5), 1.5).unwrap();
+        assert_eq!(nodes(&plan), vec![0, 3, 2]);
+        assert!(plan.waypoints[1].refuelled);
+    }
+
+    #[test]
+    fn test_route_neutron_supercharge() {
+        // A neutron star one jump out, then a long gap only a supercharged
+        // jump can cross.
+        let positions = [[0., 0., 0.], [10., 0., 0.], [50., 0., 0.], [60., 0., 0.]];
+        let stars = [StarClass::G, StarClass::Neutron, StarClass::K, StarClass::M];
+        let graph = JumpGraph::build_boosted(&positions, 15., &neutron_boosts(&stars));
+        let mut router = Router::new(&graph, &stars);
+        assert!(router.route(0, 3, &ship(10.), 10.).is_none());
+
+        router.neutron = true;
+        let plan = router.route(0, 3, &ship(10.), 10.).unwrap();
+        assert_eq!(nodes(&plan), vec![0, 1, 3]);
+        assert_eq!(plan.supercharges(), 1);
+        assert!(plan.waypoints[1].supercharged);
+        assert!(!plan.waypoints[2].supercharged);
+        // 50ly supercharged costs the fuel of a 12.5ly jump
+        assert!((plan.waypoints[2].fuel - 7.75).abs() < 1e-9);
+
+        let token = CancelToken::default();
+        router.cancel = Some(token.clone());
+        assert!(router.route(0, 3, &ship(10.), 10.).is_some());
+        token.cancel();
+        assert!(router.route(0, 3, &ship(10.), 10.).is_none());
+    }
+}
@@ -0,0 +1,1308 @@
+use bytecount::count as byte_counter;
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+#[cfg(feature = "fs")]
+use std::io::{BufReader, Read};
+#[cfg(feature = "fs")]
+use std::path::Path;
+use tracing::debug;
+#[cfg(feature = "fs")]
+use tracing::info;
+
+#[cfg(feature = "fs")]
+use crate::gzip::{self, GzipReader};
+#[cfg(feature = "fs")]
+use crate::input::InputReader;
+use crate::options::{DecimalSeparator, ParseOptions};
+#[cfg(feature = "fs")]
+use crate::options::{ReadOptions, MIN_BUFFER_SIZE};
+use crate::spatial::Coordinate;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+const UTF16_LE_BOM: &[u8] = b"\xff\xfe";
+const UTF16_BE_BOM: &[u8] = b"\xfe\xff";
+
+/// Consumes a UTF-8 byte-order mark from the front of a reader, returning the
+/// number of bytes skipped. Editors like Notepad like to add these to .prices
+/// files, which breaks the parse of the first line. UTF-16 files are rejected
+/// outright since everything downstream expects UTF-8.
+pub fn skip_bom<R: BufRead>(reader: &mut R) -> io::Result<usize> {
+    let head = reader.fill_buf()?;
+    if head.starts_with(UTF8_BOM) {
+        debug!("skipping UTF-8 byte-order mark");
+        reader.consume(UTF8_BOM.len());
+        return Ok(UTF8_BOM.len());
+    }
+    if head.starts_with(UTF16_LE_BOM) || head.starts_with(UTF16_BE_BOM) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "file is UTF-16 encoded (byte-order mark found), expected UTF-8",
+        ));
+    }
+    Ok(0)
+}
+
+/// Opens a file for one of the readers according to the read options,
+/// returning it positioned after any byte-order mark along with the number
+/// of bytes that were skipped.
+#[cfg(feature = "fs")]
+pub fn open_reader(
+    filename: impl AsRef<Path>,
+    options: &ReadOptions,
+) -> io::Result<(InputReader, usize)> {
+    let capacity = options.buffer_size.max(MIN_BUFFER_SIZE);
+    let mut reader = InputReader::open(filename, options, capacity)?;
+    let skipped = skip_bom(&mut reader)?;
+    Ok((reader, skipped))
+}
+
+/// Like open_reader, but starting `offset` bytes into the file. Anywhere
+/// past the start is past any byte-order mark, so none is looked for.
+#[cfg(feature = "fs")]
+pub fn open_reader_at(
+    filename: impl AsRef<Path>,
+    options: &ReadOptions,
+    offset: u64,
+) -> io::Result<InputReader> {
+    if offset == 0 {
+        return open_reader(filename, options).map(|(reader, _)| reader);
+    }
+    let capacity = options.buffer_size.max(MIN_BUFFER_SIZE);
+    InputReader::open_at(filename, options, capacity, offset)
+}
+
+/// Like open_reader, but decompressing gzip files, told by their first
+/// bytes rather than their name. The byte-order mark, and the count of
+/// bytes skipped, are of the decompressed data.
+#[cfg(feature = "fs")]
+pub fn open_decoded(
+    filename: impl AsRef<Path>,
+    options: &ReadOptions,
+) -> io::Result<(InputReader, usize)> {
+    let capacity = options.buffer_size.max(MIN_BUFFER_SIZE);
+    let mut reader = InputReader::open(filename, options, capacity)?;
+    if gzip::is_gzip(reader.fill_buf()?) {
+        debug!("decompressing gzip file");
+        let decoder = GzipReader::new(reader);
+        reader = InputReader::Gzip(Box::new(BufReader::with_capacity(capacity, decoder)));
+    }
+    let skipped = skip_bom(&mut reader)?;
+    Ok((reader, skipped))
+}
+
+/// Like open_reader_at, but decompressing gzip files, where `offset` counts
+/// decompressed bytes. A compressed stream can't be seeked into, so it's
+/// decompressed up to there.
+#[cfg(feature = "fs")]
+pub fn open_decoded_at(
+    filename: impl AsRef<Path>,
+    options: &ReadOptions,
+    offset: u64,
+) -> io::Result<InputReader> {
+    if offset == 0 {
+        return open_decoded(filename, options).map(|(reader, _)| reader);
+    }
+    let capacity = options.buffer_size.max(MIN_BUFFER_SIZE);
+    let mut compressed = false;
+    let reader = InputReader::open_sniffed(filename, options, capacity, |head| {
+        compressed = gzip::is_gzip(head);
+        if compressed {
+            0
+        } else {
+            offset
+        }
+    })?;
+    if !compressed {
+        return Ok(reader);
+    }
+    debug!("decompressing gzip file");
+    let decoder = GzipReader::new(reader);
+    let mut reader = InputReader::Gzip(Box::new(BufReader::with_capacity(capacity, decoder)));
+    let skipped = skip_bom(&mut reader)?;
+    let skip = offset.saturating_sub(skipped as u64);
+    if io::copy(&mut reader.by_ref().take(skip), &mut io::sink())? < skip {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("offset {} is past the end of the file", offset),
+        ));
+    }
+    Ok(reader)
+}
+
+/// Counts the number of '\n's in a file as quickly as possible and then
+/// returns the count.
+#[cfg(feature = "fs")]
+pub fn count_file_lines(filename: impl AsRef<Path>) -> io::Result<usize> {
+    count_file_lines_with(filename, &ReadOptions::default())
+}
+
+/// count_file_lines, reading the file as `options` say.
+#[cfg(feature = "fs")]
+#[tracing::instrument(skip_all, fields(filename = %filename.as_ref().display()))]
+pub fn count_file_lines_with(
+    filename: impl AsRef<Path>,
+    options: &ReadOptions,
+) -> io::Result<usize> {
+    let (mut reader, _) = open_reader(filename, options)?;
+    let mut buffer = options.make_buffer();
+    let mut count = 0;
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        count += count_lines(&buffer[..bytes_read]);
+    }
+
+    debug!(count, "counted lines");
+    Ok(count)
+}
+
+/// Checks that a file is entirely valid UTF-8, returning the byte offset of
+/// the first invalid sequence, or None if the whole file is valid.
+#[cfg(feature = "fs")]
+pub fn validate_utf8(filename: impl AsRef<Path>) -> io::Result<Option<u64>> {
+    validate_utf8_with(filename, &ReadOptions::default())
+}
+
+/// validate_utf8, reading the file as `options` say.
+#[cfg(feature = "fs")]
+#[tracing::instrument(skip_all, fields(filename = %filename.as_ref().display()))]
+pub fn validate_utf8_with(
+    filename: impl AsRef<Path>,
+    options: &ReadOptions,
+) -> io::Result<Option<u64>> {
+    let (mut reader, skipped) = open_reader(filename, options)?;
+    let mut buffer = options.make_buffer();
+    // offset is the file position of buffer[0], carry is the number of bytes of
+    // a multi-byte sequence that straddled the end of the previous read.
+    let mut offset = skipped as u64;
+    let mut carry = 0;
+
+    loop {
+        let bytes_read = reader.read(&mut buffer[carry..])?;
+        if bytes_read == 0 {
+            // A sequence that's still incomplete at end-of-file is truncated.
+            if carry > 0 {
+                info!(offset, "truncated UTF-8 sequence at end of file");
+                return Ok(Some(offset));
+            }
+            return Ok(None);
+        }
+        let filled = carry + bytes_read;
+        match std::str::from_utf8(&buffer[..filled]) {
+            Ok(_) => {
+                offset += filled as u64;
+                carry = 0;
+            }
+            Err(e) => {
+                let valid = e.valid_up_to();
+                if e.error_len().is_some() {
+                    let offset = offset + valid as u64;
+                    info!(offset, "invalid UTF-8 sequence");
+                    return Ok(Some(offset));
+                }
+                // The buffer ends part-way through a sequence, shuffle the
+                // partial sequence to the front and let the next read finish it.
+                buffer.copy_within(valid..filled, 0);
+                carry = filled - valid;
+                offset += valid as u64;
+            }
+        }
+    }
+}
+
+/// Counts the '\n's in data already in memory.
+pub fn count_lines(data: &[u8]) -> usize {
+    byte_counter(data, b'\n')
+}
+
+/// Like validate_utf8, for data already in memory: the offset of the first
+/// invalid or truncated sequence, or None if it's all valid.
+pub fn first_invalid_utf8(data: &[u8]) -> Option<u64> {
+    std::str::from_utf8(data)
+        .err()
+        .map(|e| e.valid_up_to() as u64)
+}
+
+/// Attempts to parse a supply level reading into a number of units and a
+/// level. The expected format is one of:
+///     ?               => unknown (represented by -1, -1)
+///     -               => zero    (0, 0),
+///     <units><level>
+///         units := [0-9]+, up to i64::MAX
+///         level := { [Ll] => 1, [Mm] => 2, [Hh] => 3, '?' => -1 }
+///
+/// Any input, including arbitrary non-ASCII text, gives an error rather
+/// than a panic.
+pub fn parse_supply_level(reading: &str) -> Result<(i64, i32), &'static str> {
+    let mut chars = reading.chars();
+    if let (Some(first), Some(unit_char)) = (chars.next(), chars.next_back()) {
+        if !first.is_ascii_digit() {
+            return Err("malformed supply reading");
+        }
+        // At least two characters, we can hope for units and a level.
+        // Split it into the digits and the level, on a char boundary.
+        let digits = &reading[..reading.len() - unit_char.len_utf8()];
+        let number = digits
+            .parse::<i64>()
+            .map_err(|_| "invalid number in supply reading")?;
+        let unit = match unit_char.to_ascii_lowercase() {
+            '0'..='9' => return Err("missing level-suffix in supply reading"),
+            'l' => 1,
+            'm' => 2,
+            'h' => 3,
+            '?' => -1,
+            _ => return Err("invalid unit in supply reading"),
+        };
+
+        return Ok((number, unit));
+    }
+
+    // 1 or zero characters, just do a direct match.
+    match reading {
+        "?" => Ok((-1, -1)),
+        "-" => Ok((0, 0)),
+        "0" => Ok((0, 0)),
+        "" => Err("empty supply reading"),
+        _ => Err("invalid supply reading"),
+    }
+}
+
+/// Checks the digits of a whole number, with an optional leading '-', and
+/// returns them without thousands separators. Separators are only allowed
+/// with `lenient`, and only when every group after the first has three
+/// digits.
+fn ungroup_digits(
+    text: &str,
+    lenient: bool,
+    decimal: DecimalSeparator,
+) -> Result<Cow<'_, str>, &'static str> {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    if digits.is_empty() {
+        return Err("invalid number");
+    }
+    if digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(Cow::Borrowed(text));
+    }
+    let separator = match digits.chars().find(|c| !c.is_ascii_digit()) {
+        Some(c) if lenient && (c == '_' || c == decimal.thousands()) => c,
+        _ => return Err("invalid number"),
+    };
+    let mut groups = digits.split(separator);
+    let first = groups.next().unwrap_or_default();
+    let well_formed = (1..=3).contains(&first.len())
+        && groups.all(|group| group.len() == 3 && group.bytes().all(|b| b.is_ascii_digit()));
+    if !well_formed {
+        return Err("misplaced thousands separator");
+    }
+    Ok(Cow::Owned(text.replace(separator, "")))
+}
+
+/// Parses a whole number. Strictly that's an optional '-' and digits;
+/// leniently, thousands separators as community-edited files have them
+/// ("1,234,567" or "1_234_567", "1.234.567" with a decimal comma) are
+/// accepted too, as long as every group after the first has three digits.
+pub fn parse_number(text: &str, options: &ParseOptions) -> Result<i64, &'static str> {
+    ungroup_digits(text, options.lenient(), options.decimal)?
+        .parse::<i64>()
+        .map_err(|_| "number out of range")
+}
+
+/// Parses a number with a fractional part, such as a coordinate or a
+/// distance, written with the options' decimal separator. Leniently the
+/// whole part may have thousands separators. Text using the other
+/// convention is rejected rather than misread: "1,5" isn't 15 with a
+/// decimal point.
+pub fn parse_decimal(text: &str, options: &ParseOptions) -> Result<f64, &'static str> {
+    let (lenient, decimal) = (options.lenient(), options.decimal);
+    let (whole, fraction) = text.split_once(decimal.decimal()).unwrap_or((text, ""));
+    let has_fraction = whole.len() < text.len();
+    if has_fraction && (fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit())) {
+        return Err("invalid number");
+    }
+    // ".5" and "-.5" have no whole digits to check
+    let whole = match whole {
+        "" | "-" if has_fraction => Cow::Borrowed(whole),
+        _ => ungroup_digits(whole, lenient, decimal)?,
+    };
+    format!("{}.{}0", whole, fraction)
+        .parse::<f64>()
+        .map_err(|_| "invalid number")
+}
+
+/// Like parse_supply_level, but forgiving of hand edits: whitespace around
+/// the reading and between the units and the level (" 300M", "300 M") is
+/// ignored, and the units may have thousands separators ("1,234M").
+pub fn parse_supply_level_lenient(
+    reading: &str,
+    decimal: DecimalSeparator,
+) -> Result<(i64, i32), &'static str> {
+    let reading = reading.trim();
+    match reading.char_indices().next_back() {
+        Some((last, suffix)) if last > 0 && !suffix.is_ascii_digit() => {
+            let units = reading[..last].trim_end();
+            if units.contains(['_', decimal.thousands()]) {
+                let units = ungroup_digits(units, true, decimal)
+                    .map_err(|_| "invalid number in supply reading")?;
+                parse_supply_level(&format!("{}{}", units, suffix))
+            } else if units.len() == last {
+                parse_supply_level(reading)
+            } else {
+                parse_supply_level(&format!("{}{}", units, suffix))
+            }
+        }
+        _ => parse_supply_level(reading),
+    }
+}
+
+/// Calculates the stellar-grid key for a pair of coordinates.
+/*
+ATOW current populated values for x, y, and z are:
+│ min(pos_x)  │ max(pos_x) │ min(pos_y) │ max(pos_y) │ min(pos_z) │ max(pos_z)  │
+│ -42213.8125 │ 40503.8125 │ -3381.375  │ 5319.21875 │ -16899.75  │ 65630.15625 │
+
+dividing by 32 proved to produce a good balance between bucket count/bucket size,
+│  min x /32  │ max x /32  │ min y / 32 │ max y / 32 │ min z / 32 │ max z / 32  │
+│ -1319.18164 │ 1265.74414 │ -105.66796 │ 166.225585 │ -528.11718 │ 2050.942382 │
+
+-> x (-1320,+1266), y (-106, +167), z (-529, +2051)
+next power of two:
+->   (-2048,+2048),   (-128, +256),   (-1024, +4096)
+
+I think it's reasonable to imagine those limits being exceeded at least once, so
+we can assume that we need to store values of upto +/- 8192 for each coordinate,
+which calls for signed 16-bit compartments.
+ */
+fn stellar_grid_key_component(component: f64) -> i16 {
+    (component / 32.).floor() as i16
+}
+
+/// The key of the stellar grid cell containing x, y, z, given as f64 or as
+/// f32; an f32 coordinate gets the key its exact f64 value would.
+pub fn stellar_grid_key<T: Coordinate>(x: T, y: T, z: T) -> u64 {
+    pack_grid_key(
+        stellar_grid_key_component(x.into()),
+        stellar_grid_key_component(y.into()),
+        stellar_grid_key_component(z.into()),
+    )
+}
+
+/// The stellar grid keys of many positions at once; the same keys as
+/// stellar_grid_key, computed in batches the compiler can vectorize.
+pub fn stellar_grid_keys<T: Coordinate>(positions: &[[T; 3]]) -> Vec<u64> {
+    grid_keys(positions, 32.)
+}
+
+/// Returns the keys of every stellar grid cell overlapping the box between
+/// two corners, which is how a radius search finds its candidate cells.
+pub fn stellar_grid_keys_in_box(min: [f64; 3], max: [f64; 3]) -> Vec<u64> {
+    iter_stellar_grid_keys_in_box(min, max).collect()
+}
+
+/// Like stellar_grid_keys_in_box, but produces the keys as they're
+/// iterated rather than all up front.
+pub fn iter_stellar_grid_keys_in_box(min: [f64; 3], max: [f64; 3]) -> impl Iterator<Item = u64> {
+    GridCells::in_box(min, max, 32.).map(|(gx, gy, gz)| pack_grid_key(gx, gy, gz))
+}
+
+/// The keys of the stellar grid cells with any part within radius of a
+/// point, produced as they're iterated. A 500ly radius touches some 18,000
+/// of the 34,000 cells in its bounding box.
+pub fn iter_stellar_grid_keys_in_sphere(
+    center: [f64; 3],
+    radius: f64,
+) -> impl Iterator<Item = u64> {
+    GridCells::in_sphere(center, radius, 32.).map(|(gx, gy, gz)| pack_grid_key(gx, gy, gz))
+}
+
+/// Walks the (x, y, z) indexes of the cells of a given size overlapping a
+/// box, y slowest and z fastest, without collecting them. Built with
+/// `in_sphere`, it skips the cells that lie wholly outside the sphere.
+#[derive(Clone, Debug)]
+pub struct GridCells {
+    min: [i32; 3],
+    max: [i32; 3],
+    size: f64,
+    /// The next cell to consider, or None once they've all been.
+    next: Option<[i32; 3]>,
+    sphere: Option<([f64; 3], f64)>,
+}
+
+impl GridCells {
+    /// The cells overlapping the box between two corners.
+    pub fn in_box(min: [f64; 3], max: [f64; 3], size: f64) -> Self {
+        let index = |component: f64| (component / size).floor() as i16 as i32;
+        let (min, max) = (min.map(index), max.map(index));
+        let empty = (0..3).any(|axis| min[axis] > max[axis]);
+        Self {
+            min,
+            max,
+            size,
+            next: (!empty).then_some(min),
+            sphere: None,
+        }
+    }
+
+    /// The cells with any part within radius of a point.
+    pub fn in_sphere(center: [f64; 3], radius: f64, size: f64) -> Self {
+        let mut cells = Self::in_box(center.map(|c| c - radius), center.map(|c| c + radius), size);
+        cells.sphere = Some((center, radius));
+        cells
+    }
+
+    /// The cell after `cell` in iteration order.
+    fn step(&self, [gx, gy, gz]: [i32; 3]) -> Option<[i32; 3]> {
+        if gz < self.max[2] {
+            Some([gx, gy, gz + 1])
+        } else if gx < self.max[0] {
+            Some([gx + 1, gy, self.min[2]])
+        } else if gy < self.max[1] {
+            Some([self.min[0], gy + 1, self.min[2]])
+        } else {
+            None
+        }
+    }
+
+    /// Whether the cell's nearest point to the sphere's center is within
+    /// its radius; always true without a sphere.
+    fn touches_sphere(&self, cell: [i32; 3]) -> bool {
+        let Some((center, radius)) = self.sphere else {
+            return true;
+        };
+        let mut squared = 0.;
+        for axis in 0..3 {
+            let low = cell[axis] as f64 * self.size;
+            let gap = (low - center[axis])
+                .max(center[axis] - (low + self.size))
+                .max(0.);
+            squared += gap * gap;
+        }
+        squared <= radius * radius
+    }
+}
+
+impl Iterator for GridCells {
+    type Item = (i16, i16, i16);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let cell = self.next?;
+            self.next = self.step(cell);
+            if self.touches_sphere(cell) {
+                return Some((cell[0] as i16, cell[1] as i16, cell[2] as i16));
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let Some([gx, gy, gz]) = self.next else {
+            return (0, Some(0));
+        };
+        let span = |axis: usize| (self.max[axis] - self.min[axis] + 1) as usize;
+        let rows_left = (self.max[1] - gy) as usize * span(0) + (self.max[0] - gx) as usize;
+        let left = rows_left * span(2) + (self.max[2] - gz + 1) as usize;
+        match self.sphere {
+            Some(_) => (0, Some(left)),
+            None => (left, Some(left)),
+        }
+    }
+}
+
+/// Packs per-axis cell indexes into a single key, the layout shared by the
+/// stellar grid and other cell-based keys.
+pub fn pack_grid_key(gx: i16, gy: i16, gz: i16) -> u64 {
+    // I've chosen to make 'y' the most-significant word here because it currently
+    // has the least range since the galaxy is disk-like, and because it represents
+    // galactic "north/south".
+    // Promote gy into a u32 so that negatives fill all the most significant bits:
+    //  0xffffi16 -> i64 -> u64 = 0xffffffffffffffff
+    // where i16 -> u16 -> u64 = 0x000000000000ffff
+    let gy = gy as i64 as u64;
+    let gx = gx as u16 as u64;
+    let gz = gz as u16 as u64;
+
+    (gy << 32) | (gx << 16) | gz
+}
+
+/// Positions keyed per step of grid_keys: enough to fill the widest vector
+/// registers twice over.
+const KEY_LANES: usize = 8;
+
+/// `(component / size).floor() as i16`, saturating alike and NaN to 0, but
+/// without calling floor, which doesn't vectorize on baseline x86-64.
+#[inline(always)]
+fn cell_index(component: f64, size: f64) -> i16 {
+    let quotient = component / size;
+    let truncated = quotient as i32;
+    // truncation rounds negative quotients up; step those back down
+    let floored = truncated.saturating_sub(((truncated as f64) > quotient) as i32);
+    floored.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// The keys, packed as by pack_grid_key, of the cells of `cell_size` ly
+/// containing each position.
+///
+/// Positions are taken KEY_LANES at a time and split into a row per axis,
+/// so each step of divide, floor and pack runs over a fixed-size array of
+/// lanes that the compiler turns into vector instructions.
+pub fn grid_keys<T: Coordinate>(positions: &[[T; 3]], cell_size: f64) -> Vec<u64> {
+    let mut keys = Vec::with_capacity(positions.len());
+    let chunks = positions.chunks_exact(KEY_LANES);
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        let mut cells = [[0i16; KEY_LANES]; 3];
+        for (axis, row) in cells.iter_mut().enumerate() {
+            for (cell, pos) in row.iter_mut().zip(chunk) {
+                *cell = cell_index(pos[axis].into(), cell_size);
+            }
+        }
+        let mut packed = [0u64; KEY_LANES];
+        for (lane, key) in packed.iter_mut().enumerate() {
+            *key = pack_grid_key(cells[0][lane], cells[1][lane], cells[2][lane]);
+        }
+        keys.extend_from_slice(&packed);
+    }
+    keys.extend(rest.iter().map(|pos| {
+        let [gx, gy, gz] = pos.map(|component| cell_index(component.into(), cell_size));
+        pack_grid_key(gx, gy, gz)
+    }));
+    keys
+}
+
+/// Recovers the (x, y, z) cell indexes from a key made by pack_grid_key.
+pub fn unpack_grid_key(key: u64) -> (i16, i16, i16) {
+    (
+        (key >> 16) as u16 as i16,
+        (key >> 32) as u16 as i16,
+        key as u16 as i16,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Strictness;
+    use std::io::Read;
+    #[cfg(feature = "fs")]
+    use std::io::Write;
+    #[cfg(feature = "fs")]
+    use tempfile::NamedTempFile;
+    use DecimalSeparator::{Comma, Point};
+
+    fn options(lenient: bool, decimal: DecimalSeparator) -> ParseOptions {
+        ParseOptions {
+            strictness: if lenient {
+                Strictness::Lenient
+            } else {
+                Strictness::Strict
+            },
+            decimal,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_count_file_lines_no_newlines() {
+        // create a temp file with no content.
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            count_file_lines(tmpfile.path().to_str().unwrap()).unwrap(),
+            0
+        );
+
+        // write some content with no newlines
+        write!(tmpfile, "no newline").unwrap();
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            count_file_lines(tmpfile.path().to_str().unwrap()).unwrap(),
+            0
+        );
+
+        // now write every character except newline
+        for i in 0..256 {
+            if i != 10 {
+                write!(tmpfile, "{}", i as u8).unwrap();
+            }
+        }
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            count_file_lines(tmpfile.path().to_str().unwrap()).unwrap(),
+            0
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    #[allow(clippy::unused_io_amount)]
+    fn test_count_file_lines_just_newlines() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+
+        for i in 1..257 {
+            tmpfile.write("\n".as_bytes()).unwrap();
+            tmpfile.flush().unwrap();
+            assert_eq!(
+                count_file_lines(tmpfile.path().to_str().unwrap()).unwrap(),
+                i
+            );
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    #[allow(clippy::unused_io_amount, clippy::needless_range_loop)]
+    fn test_count_file_lines_mixed() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        let mut buf: [u8; 65536] = [0; 65536];
+        let mut lines: usize = 0;
+
+        // Fill the buffer
+        for i in 0..65536 {
+            let start = i / 256;
+            let count = i % 256;
+            let end = start + count + 1;
+            for c in i..end {
+                buf[i] = c as u8;
+                if c == 10 {
+                    lines += 1
+                }
+            }
+        }
+        tmpfile.write(&buf).unwrap();
+        tmpfile.flush().unwrap();
+
+        assert_ne!(lines, 0);
+        assert_eq!(
+            count_file_lines(tmpfile.path().to_str().unwrap()).unwrap(),
+            lines
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_validate_utf8_valid() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            validate_utf8(tmpfile.path().to_str().unwrap()).unwrap(),
+            None
+        );
+
+        write!(tmpfile, "Sol/Abraham Lincoln\nLave/Lavé Station ☃\n").unwrap();
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            validate_utf8(tmpfile.path().to_str().unwrap()).unwrap(),
+            None
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_validate_utf8_invalid() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        tmpfile.write_all(b"abc\xffdef").unwrap();
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            validate_utf8(tmpfile.path().to_str().unwrap()).unwrap(),
+            Some(3)
+        );
+
+        // a multi-byte sequence cut short by the end of the file
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        tmpfile.write_all(b"abcdef\xe2\x98").unwrap();
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            validate_utf8(tmpfile.path().to_str().unwrap()).unwrap(),
+            Some(6)
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_validate_utf8_across_reads() {
+        // place a 3-byte character so it straddles the read buffer boundary
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        let mut buf = vec![b'a'; crate::options::DEFAULT_BUFFER_SIZE - 1];
+        buf.extend_from_slice("☃".as_bytes());
+        buf.push(b'\n');
+        tmpfile.write_all(&buf).unwrap();
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            validate_utf8(tmpfile.path().to_str().unwrap()).unwrap(),
+            None
+        );
+
+        // and an invalid byte after the boundary is reported at its file offset
+        tmpfile.write_all(b"\x80").unwrap();
+        tmpfile.flush().unwrap();
+        assert_eq!(
+            validate_utf8(tmpfile.path().to_str().unwrap()).unwrap(),
+            Some(buf.len() as u64)
+        );
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_readers_small_buffers() {
+        // every buffer size should give the same answers, including ones that
+        // split multi-byte sequences at every possible position.
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        write!(tmpfile, "\u{feff}Lavé\n☃ Station\n𝄞\n").unwrap();
+        tmpfile.flush().unwrap();
+        let path = tmpfile.path().to_str().unwrap();
+        for buffer_size in 0..16 {
+            let options = ReadOptions {
+                buffer_size,
+                ..Default::default()
+            };
+            assert_eq!(count_file_lines_with(path, &options).unwrap(), 3);
+            assert_eq!(validate_utf8_with(path, &options).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_skip_bom() {
+        let mut reader = io::Cursor::new(b"\xef\xbb\xbf@ SOL/Abraham Lincoln".to_vec());
+        assert_eq!(skip_bom(&mut reader).unwrap(), 3);
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "@ SOL/Abraham Lincoln");
+
+        // no bom, nothing consumed
+        let mut reader = io::Cursor::new(b"@ SOL".to_vec());
+        assert_eq!(skip_bom(&mut reader).unwrap(), 0);
+        assert_eq!(reader.fill_buf().unwrap(), b"@ SOL");
+
+        // empty input is fine too
+        let mut reader = io::Cursor::new(Vec::new());
+        assert_eq!(skip_bom(&mut reader).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_in_memory_readers() {
+        assert_eq!(count_lines(b""), 0);
+        assert_eq!(count_lines(b"a\nb\nc"), 2);
+        assert_eq!(first_invalid_utf8("Lav\u{e9}\n".as_bytes()), None);
+        assert_eq!(first_invalid_utf8(b"abc\xffdef"), Some(3));
+        // a sequence cut off by the end
+        assert_eq!(first_invalid_utf8(b"ab\xc3"), Some(2));
+    }
+
+    #[test]
+    fn test_skip_bom_rejects_utf16() {
+        for bom in [UTF16_LE_BOM, UTF16_BE_BOM] {
+            let mut reader = io::Cursor::new(bom.to_vec());
+            let err = skip_bom(&mut reader).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_readers_skip_bom() {
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        tmpfile
+            .write_all(b"\xef\xbb\xbfline 1\nline 2\n\xff")
+            .unwrap();
+        tmpfile.flush().unwrap();
+        let path = tmpfile.path().to_str().unwrap();
+        assert_eq!(count_file_lines(path).unwrap(), 2);
+        // offsets are still reported relative to the start of the file
+        assert_eq!(validate_utf8(path).unwrap(), Some(17));
+
+        let mut tmpfile = NamedTempFile::new().unwrap();
+        tmpfile.write_all(b"\xff\xfel\x00\n\x00").unwrap();
+        tmpfile.flush().unwrap();
+        let path = tmpfile.path().to_str().unwrap();
+        assert!(count_file_lines(path).is_err());
+        assert!(validate_utf8(path).is_err());
+    }
+
+    #[test]
+    fn test_parse_supply_level_invalid() {
+        // form a string that starts with a digit and ends with a valid level suffix,
+        // but has a non-numeric character that will cause the digits.parse to fail
+        assert_eq!(
+            parse_supply_level("0:?"),
+            Err("invalid number in supply reading")
+        );
+        assert_eq!(
+            parse_supply_level("0123123.m"),
+            Err("invalid number in supply reading")
+        );
+        // pass a number too large for an i64
+        assert_eq!(
+            parse_supply_level("9999999999999999999m"),
+            Err("invalid number in supply reading")
+        );
+
+        // pass a value that doesn't end with a valid suffix.
+        assert_eq!(
+            parse_supply_level("00"),
+            Err("missing level-suffix in supply reading")
+        );
+
+        // pass a value that doesn't start with a digit
+        assert_eq!(parse_supply_level("?m"), Err("malformed supply reading"));
+
+        // try some invalid single chars.
+        assert_eq!(parse_supply_level("!"), Err("invalid supply reading"));
+        assert_eq!(parse_supply_level("a"), Err("invalid supply reading"));
+        assert_eq!(parse_supply_level("1"), Err("invalid supply reading"));
+
+        // lastly, empty string.
+        assert_eq!(parse_supply_level(""), Err("empty supply reading"));
+    }
+
+    #[test]
+    fn test_parse_supply_level_unknown() {
+        // "unknown" indicated by two -1s
+        assert_eq!(parse_supply_level("?"), Ok((-1, -1)));
+    }
+
+    #[test]
+    fn test_parse_supply_level_zero() {
+        // "zero"
+        assert_eq!(parse_supply_level("-"), Ok((0, 0)));
+        assert_eq!(parse_supply_level("0"), Ok((0, 0)));
+    }
+
+    #[test]
+    fn test_parse_supply_level_values() {
+        // 0 units, unknown level
+        assert_eq!(parse_supply_level("0?"), Ok((0, -1)));
+
+        // 10 units, low
+        assert_eq!(parse_supply_level("10l"), Ok((10, 1)));
+
+        // 1000 units, low caps
+        assert_eq!(parse_supply_level("1000L"), Ok((1000, 1)));
+
+        // 424242 units
+        assert_eq!(parse_supply_level("424242?"), Ok((424242, -1)));
+        assert_eq!(parse_supply_level("424242l"), Ok((424242, 1)));
+        assert_eq!(parse_supply_level("424242m"), Ok((424242, 2)));
+        assert_eq!(parse_supply_level("424242h"), Ok((424242, 3)));
+
+        // 2134567891 units
+        assert_eq!(parse_supply_level("2134567891L"), Ok((2134567891, 1)));
+        assert_eq!(parse_supply_level("2134567891M"), Ok((2134567891, 2)));
+        assert_eq!(parse_supply_level("2134567891H"), Ok((2134567891, 3)));
+
+        // beyond u32, as fleet carrier orders can be
+        assert_eq!(parse_supply_level("5000000000M"), Ok((5000000000, 2)));
+        assert_eq!(
+            parse_supply_level("9223372036854775807h"),
+            Ok((i64::MAX, 3))
+        );
+    }
+
+    #[test]
+    fn test_parse_supply_level_lenient() {
+        assert_eq!(parse_supply_level_lenient(" 300M", Point), Ok((300, 2)));
+        assert_eq!(parse_supply_level_lenient("300 M", Point), Ok((300, 2)));
+        assert_eq!(
+            parse_supply_level_lenient("\t300 \t h \n", Point),
+            Ok((300, 3))
+        );
+        assert_eq!(parse_supply_level_lenient(" ? ", Point), Ok((-1, -1)));
+        assert_eq!(parse_supply_level_lenient(" - ", Point), Ok((0, 0)));
+        assert_eq!(parse_supply_level_lenient("1,234 M", Point), Ok((1234, 2)));
+        assert_eq!(parse_supply_level_lenient("1_234L", Point), Ok((1234, 1)));
+        assert_eq!(
+            parse_supply_level_lenient("12,34L", Point),
+            Err("invalid number in supply reading")
+        );
+        assert_eq!(
+            parse_supply_level_lenient("3 00M", Point),
+            Err("invalid number in supply reading")
+        );
+        assert_eq!(
+            parse_supply_level_lenient("300 ", Point),
+            Err("missing level-suffix in supply reading")
+        );
+        assert_eq!(
+            parse_supply_level_lenient(" ", Point),
+            Err("empty supply reading")
+        );
+        assert_eq!(
+            parse_supply_level_lenient("300 x", Point),
+            Err("invalid unit in supply reading")
+        );
+
+        // strict mode still rejects them
+        assert!(parse_supply_level(" 300M").is_err());
+        assert!(parse_supply_level("300 M").is_err());
+    }
+
+    #[test]
+    fn test_parse_supply_level_non_ascii() {
+        assert_eq!(
+            parse_supply_level("300é"),
+            Err("invalid unit in supply reading")
+        );
+        assert_eq!(
+            parse_supply_level("3é5M"),
+            Err("invalid number in supply reading")
+        );
+        assert_eq!(parse_supply_level("é"), Err("invalid supply reading"));
+        assert_eq!(parse_supply_level("é300M"), Err("malformed supply reading"));
+    }
+
+    #[test]
+    fn test_parsers_never_panic() {
+        // every combination of a few awkward fragments, including multibyte
+        // chars at either end and around separators.
+        let fragments = [
+            "",
+            "0",
+            "9",
+            "-",
+            "+",
+            ",",
+            ".",
+            "_",
+            " ",
+            "?",
+            "M",
+            "é",
+            "€",
+            "\u{0}",
+            "\u{feff}",
+            "99999999999999999999",
+        ];
+        let all = [
+            options(false, Point),
+            options(true, Point),
+            options(true, Comma),
+        ];
+        for a in fragments {
+            for b in fragments {
+                for c in fragments {
+                    let text = format!("{}{}{}", a, b, c);
+                    let _ = parse_supply_level(&text);
+                    for options in &all {
+                        let _ = parse_supply_level_lenient(&text, options.decimal);
+                        let _ = parse_number(&text, options);
+                        let _ = parse_decimal(&text, options);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("1234567", &options(false, Point)), Ok(1234567));
+        assert_eq!(parse_number("-42", &options(false, Point)), Ok(-42));
+        assert_eq!(
+            parse_number("1,234,567", &options(true, Point)),
+            Ok(1234567)
+        );
+        assert_eq!(
+            parse_number("1_234_567", &options(true, Point)),
+            Ok(1234567)
+        );
+        assert_eq!(parse_number("-12,345", &options(true, Point)), Ok(-12345));
+        assert_eq!(parse_number("123", &options(true, Point)), Ok(123));
+
+        // strict mode rejects separators
+        assert_eq!(
+            parse_number("1,234,567", &options(false, Point)),
+            Err("invalid number")
+        );
+        assert_eq!(
+            parse_number("1_234", &options(false, Point)),
+            Err("invalid number")
+        );
+
+        for misplaced in ["1,23", "1234,567", ",123", "1,234,", "1,,234", "1,234_567"] {
+            assert!(
+                parse_number(misplaced, &options(true, Point)).is_err(),
+                "{}",
+                misplaced
+            );
+        }
+        assert_eq!(
+            parse_number("", &options(true, Point)),
+            Err("invalid number")
+        );
+        assert_eq!(
+            parse_number("-", &options(true, Point)),
+            Err("invalid number")
+        );
+        assert_eq!(
+            parse_number("1.5", &options(true, Point)),
+            Err("invalid number")
+        );
+        assert_eq!(
+            parse_number("99,999,999,999,999,999,999", &options(true, Point)),
+            Err("number out of range")
+        );
+    }
+
+    #[test]
+    fn test_decimal_separators() {
+        assert_eq!(
+            parse_number("1.234.567", &options(true, Comma)),
+            Ok(1234567)
+        );
+        assert_eq!(
+            parse_number("1.234.567", &options(true, Point)),
+            Err("invalid number")
+        );
+        assert_eq!(
+            parse_number("1,234", &options(true, Comma)),
+            Err("invalid number")
+        );
+        assert_eq!(parse_supply_level_lenient("1.234 M", Comma), Ok((1234, 2)));
+        assert_eq!(DecimalSeparator::from_char(','), Some(Comma));
+        assert_eq!(DecimalSeparator::from_char(';'), None);
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal("12.5", &options(false, Point)), Ok(12.5));
+        assert_eq!(parse_decimal("12,5", &options(false, Comma)), Ok(12.5));
+        assert_eq!(parse_decimal("-0.25", &options(false, Point)), Ok(-0.25));
+        assert_eq!(parse_decimal("-,25", &options(false, Comma)), Ok(-0.25));
+        assert_eq!(parse_decimal("42", &options(false, Point)), Ok(42.0));
+        assert_eq!(parse_decimal("1,234.5", &options(true, Point)), Ok(1234.5));
+        assert_eq!(parse_decimal("1.234,5", &options(true, Comma)), Ok(1234.5));
+        assert_eq!(
+            parse_decimal("123456789012345678901234.5", &options(false, Point)),
+            Ok(123456789012345678901234.5)
+        );
+
+        // the other convention is an error, not a different number
+        assert_eq!(
+            parse_decimal("12,5", &options(true, Point)),
+            Err("misplaced thousands separator")
+        );
+        assert_eq!(
+            parse_decimal("12.5", &options(true, Comma)),
+            Err("misplaced thousands separator")
+        );
+        assert_eq!(
+            parse_decimal("1.234,5", &options(false, Point)),
+            Err("invalid number")
+        );
+        assert_eq!(
+            parse_decimal("1,234.5", &options(false, Point)),
+            Err("invalid number")
+        );
+        for invalid in ["", ".", "-", "1.", "1.2.3", "1e5", "0x1", "1.-5", " 1"] {
+            assert!(
+                parse_decimal(invalid, &options(true, Point)).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_sellar_grid_key_component() {
+        // positive values should populate the space 0+,
+        // while negative values should start at -1.
+        assert_eq!(0i16, stellar_grid_key_component(0.));
+        assert_eq!(0i16, stellar_grid_key_component(-0.));
+        assert_eq!(0i16, stellar_grid_key_component(1.));
+        assert_eq!(0i16, stellar_grid_key_component(2.));
+        assert_eq!(0i16, stellar_grid_key_component(16.));
+        assert_eq!(0i16, stellar_grid_key_component(31.9999));
+        assert_eq!(1i16, stellar_grid_key_component(32.0));
+        assert_eq!(1i16, stellar_grid_key_component(63.9999999999));
+        assert_eq!(2i16, stellar_grid_key_component(64.));
+
+        assert_eq!(-1i16, stellar_grid_key_component(-0.00001));
+        assert_eq!(-1i16, stellar_grid_key_component(-1.));
+        assert_eq!(-1i16, stellar_grid_key_component(-2.));
+        assert_eq!(-1i16, stellar_grid_key_component(-16.));
+        assert_eq!(-1i16, stellar_grid_key_component(-31.9999));
+        assert_eq!(-1i16, stellar_grid_key_component(-32.0));
+        assert_eq!(-2i16, stellar_grid_key_component(-32.000000001));
+        assert_eq!(-2i16, stellar_grid_key_component(-63.9999999999));
+        assert_eq!(-2i16, stellar_grid_key_component(-64.));
+        assert_eq!(-3i16, stellar_grid_key_component(-64.0000001));
+    }
+
+    #[test]
+    fn test_unpack_grid_key() {
+        for (gx, gy, gz) in [
+            (0, 0, 0),
+            (-1, -1, -1),
+            (1, 2, 3),
+            (-1320, 167, 2051),
+            (i16::MIN, i16::MAX, -5),
+        ] {
+            assert_eq!(unpack_grid_key(pack_grid_key(gx, gy, gz)), (gx, gy, gz));
+        }
+        assert_eq!(
+            unpack_grid_key(stellar_grid_key(-33.0, -65.0, -97.0)),
+            (-2, -3, -4)
+        );
+    }
+
+    #[test]
+    fn test_stellar_grid_key_zero() {
+        let actual_key = stellar_grid_key(0., 0., 0.);
+        assert_eq!(0, actual_key);
+    }
+
+    #[test]
+    fn test_stellar_grd_key_minus_one() {
+        let actual_key = stellar_grid_key(-1., -1., -1.);
+        assert_eq!(-1i64 as u64, actual_key);
+    }
+
+    #[test]
+    fn test_stellar_grid_keys_in_box() {
+        let keys = stellar_grid_keys_in_box([-1., 0., 0.], [32., 31., 0.]);
+        assert_eq!(keys.len(), 3);
+        assert!(keys.contains(&stellar_grid_key(-1., 0., 0.)));
+        assert!(keys.contains(&stellar_grid_key(0., 0., 0.)));
+        assert!(keys.contains(&stellar_grid_key(32., 0., 0.)));
+        assert_eq!(stellar_grid_keys_in_box([0.; 3], [0.; 3]), vec![0]);
+        assert_eq!(
+            stellar_grid_keys_in_box([-64.; 3], [95.; 3]).len(),
+            5 * 5 * 5
+        );
+    }
+
+    #[test]
+    fn test_iter_stellar_grid_keys() {
+        let (min, max) = ([-64., -33., 5.], [95., 31., 200.]);
+        let mut keys = iter_stellar_grid_keys_in_box(min, max);
+        assert_eq!(keys.size_hint(), (5 * 3 * 7, Some(5 * 3 * 7)));
+        keys.next();
+        assert_eq!(keys.size_hint().0, 5 * 3 * 7 - 1);
+        assert_eq!(
+            keys.collect::<Vec<_>>(),
+            stellar_grid_keys_in_box(min, max)[1..]
+        );
+        assert_eq!(iter_stellar_grid_keys_in_box([40.; 3], [0.; 3]).count(), 0);
+
+        // the sphere keeps the cells the box does that come within radius
+        let center = [10., -20., 3000.];
+        let sphere: Vec<u64> = iter_stellar_grid_keys_in_sphere(center, 500.).collect();
+        let mut boxed =
+            stellar_grid_keys_in_box(center.map(|c| c - 500.), center.map(|c| c + 500.));
+        boxed.sort_unstable();
+        assert!(
+            sphere.len() < boxed.len() * 6 / 10,
+            "{} {}",
+            sphere.len(),
+            boxed.len()
+        );
+        assert!(sphere.iter().all(|key| boxed.binary_search(key).is_ok()));
+        assert!(sphere.contains(&stellar_grid_key(10., -20., 3499.)));
+        assert!(sphere.contains(&stellar_grid_key(300., 250., 3250.)));
+        assert!(!sphere.contains(&stellar_grid_key(400., 400., 3400.)));
+        assert_eq!(
+            iter_stellar_grid_keys_in_sphere([0.; 3], 0.).collect::<Vec<_>>(),
+            vec![0]
+        );
+    }
+
+    #[test]
+    fn test_stellar_grid_key_near_zero() {
+        // where -32 < n < 32, we should come out to zero also
+        assert_eq!(0, stellar_grid_key(1.0, 1.0, 1.0));
+        assert_eq!(0, stellar_grid_key(31.0, 31.0, 31.0));
+
+        assert_ne!(0, stellar_grid_key(0.,  0.,  32.));
+        assert_ne!(0, stellar_grid_key(0.,  32.,  0.));
+        assert_ne!(0, stellar_grid_key(0.,  32.,  32.));
+        assert_ne!(0, stellar_grid_key(32.,  0.,  0.));
+        assert_ne!(0, stellar_grid_key(32.,  0.,  32.));
+        assert_ne!(0, stellar_grid_key(32.,  32.,  0.));
+        assert_ne!(0, stellar_grid_key(32.,  32.,  32.));
+    }
+
+    #[test]
+    fn test_stellar_grid_key_near_minus_1() {
+        let neg1key = -1i64 as u64;
+
+        assert_eq!(neg1key, stellar_grid_key(-1.0, -1.0, -1.0));
+        assert_eq!(neg1key, stellar_grid_key(-32.0, -32.0, -32.0));
+
+        assert_ne!(neg1key, stellar_grid_key( -0.,   -0.,   -32.1));
+        assert_ne!(neg1key, stellar_grid_key( -0.,   -32.1, -0.));
+        assert_ne!(neg1key, stellar_grid_key( -0.,   -32.1, -321.));
+        assert_ne!(neg1key, stellar_grid_key(-32.1,  -0.,   -0.));
+        assert_ne!(neg1key, stellar_grid_key(-32.1,  -0.,   -321.));
+        assert_ne!(neg1key, stellar_grid_key(-32.1,  -32.1, -0.));
+        assert_ne!(neg1key, stellar_grid_key(-32.1,  -32.1, -32.1));
+    }
+
+    #[test]
+    #[allow(clippy::identity_op)]
+    fn test_stellar_grid_key_ordering_pos() {
+        // 1.0, 2.0, 3.0 should be -> 0x00 0x02 0x01 0x03
+        let result = stellar_grid_key(32.0, 64.0, 96.0);
+        assert_eq!(1, (result >> 16) & 0xff);
+        assert_eq!(2, (result >> 32) & 0xffff);
+        assert_eq!(3, (result >> 0 ) & 0xff);
+    }
+
+    #[test]
+    fn test_stellar_grid_key_ordering_neg() {
+        let result = stellar_grid_key(-33.0, -65.0, -97.0);
+        // this should be: (-3 << 32) | (-2 << 16) | (-4)
+        // aka: 0xfffffffefffffffd
+        let expectation = 0xfffffffdfffefffc;
+        assert_eq!(expectation, result);
+    }
+
+    #[test]
+    fn test_stellar_grid_key_f32() {
+        for (x, y, z) in [(0f32, -0.5, 31.99), (-32., 65000.5, -40213.5), (1e-30, -1e-30, 4096.)] {
+            assert_eq!(
+                stellar_grid_key(x, y, z),
+                stellar_grid_key(x as f64, y as f64, z as f64)
+            );
+        }
+    }
+
+    #[test]
+    fn test_grid_keys_match_scalar() {
+        let mut positions = vec![
+            [0., -0., 31.999999],
+            [-32., -32.000001, 32.],
+            [f64::NAN, f64::INFINITY, f64::NEG_INFINITY],
+            [1e12, -1e12, 1048576.],
+            [-40213.5, 1200.25, 65000.],
+        ];
+        positions.extend((0..100).map(|i| {
+            let f = i as f64;
+            [f * 41.3 - 2000., f * -7.9, (f * 913.7) % 70000. - 5000.]
+        }));
+        // every remainder length after the full batches
+        for len in 0..positions.len() {
+            let keys = stellar_grid_keys(&positions[..len]);
+            let expected: Vec<u64> = positions[..len]
+                .iter()
+                .map(|&[x, y, z]| stellar_grid_key(x, y, z))
+                .collect();
+            assert_eq!(keys, expected);
+        }
+        for size in [4., 10., 256.] {
+            let keys = grid_keys(&positions, size);
+            for (key, pos) in keys.iter().zip(&positions) {
+                let [gx, gy, gz] = pos.map(|component| (component / size).floor() as i16);
+                assert_eq!(*key, pack_grid_key(gx, gy, gz));
+            }
+        }
+        let narrow: Vec<[f32; 3]> = positions
+            .iter()
+            .map(|pos| pos.map(|component| component as f32))
+            .collect();
+        let keys = stellar_grid_keys(&narrow);
+        for (key, &[x, y, z]) in keys.iter().zip(&narrow) {
+            assert_eq!(*key, stellar_grid_key(x, y, z));
+        }
+    }
+}
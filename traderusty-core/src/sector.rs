@@ -0,0 +1,314 @@
+//! Galaxy sectors and the procedural "boxel" part of system names.
+//!
+//! The galaxy is carved into 1280ly cube sectors, and each sector into cubes
+//! ("boxels") of 10ly * 2^n where n is the mass code 'a' to 'h'. Procedurally
+//! named systems are labelled `<sector> <boxel> <mass code><n1>-<n2>`, e.g.
+//! "Wregoe XQ-L c21-0", where the letters and n1 encode which boxel of the
+//! sector the system is in and n2 numbers the systems within the boxel.
+//!
+//! Everything but the sector name and n2 follows from the coordinates, which
+//! is what this module computes. Sector names themselves come from the name
+//! data, since the phoneme tables the game uses to build them aren't embedded.
+
+use crate::rusty::pack_grid_key;
+
+/// Edge length of a sector in ly.
+pub const SECTOR_SIZE: f64 = 1280.0;
+
+/// Coordinates of the corner of sector (0, 0, 0).
+pub const GALAXY_BASE: [f64; 3] = [-49985.0, -40985.0, -24105.0];
+
+/// Edge length of the smallest ('a') boxel in ly.
+const BOXEL_BASE_SIZE: f64 = 10.0;
+
+/// Largest mass code: an 'h' boxel is a whole sector.
+const MAX_MASS_CODE: u8 = 7;
+
+/// Boxels are numbered as x + y*128 + z*128*128 regardless of mass code.
+const BOXEL_STRIDE: u64 = 128;
+
+/// Index of a sector along each axis.
+pub type SectorIndex = [i32; 3];
+
+/// The coordinate-derived components of a procedural system name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProceduralSuffix {
+    /// 0 ('a') to 7 ('h').
+    pub mass_code: u8,
+    /// Boxel number within the sector.
+    pub boxel: u64,
+    /// Number of the system within the boxel.
+    pub n2: u32,
+}
+
+/// Returns the sector containing a position.
+pub fn sector_index(x: f64, y: f64, z: f64) -> SectorIndex {
+    let axis = |v: f64, base: f64| ((v - base) / SECTOR_SIZE).floor() as i32;
+    [
+        axis(x, GALAXY_BASE[0]),
+        axis(y, GALAXY_BASE[1]),
+        axis(z, GALAXY_BASE[2]),
+    ]
+}
+
+/// Returns the corner (minimum x, y, z) of a sector.
+pub fn sector_origin(sector: SectorIndex) -> [f64; 3] {
+    let axis = |i: usize| GALAXY_BASE[i] + sector[i] as f64 * SECTOR_SIZE;
+    [axis(0), axis(1), axis(2)]
+}
+
+/// A sector, identified by a key packed the same way as stellar grid keys.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sector {
+    pub id: u64,
+    pub index: SectorIndex,
+    pub origin: [f64; 3],
+}
+
+/// Returns the sector containing a position.
+pub fn sector_for(x: f64, y: f64, z: f64) -> Sector {
+    sector_from_index(sector_index(x, y, z))
+}
+
+/// Returns the sector with the given indexes.
+pub fn sector_from_index(index: SectorIndex) -> Sector {
+    Sector {
+        id: pack_grid_key(index[0] as i16, index[1] as i16, index[2] as i16),
+        index,
+        origin: sector_origin(index),
+    }
+}
+
+/// Returns the sector with the given id.
+pub fn sector_from_id(id: u64) -> Sector {
+    let (sx, sy, sz) = crate::rusty::unpack_grid_key(id);
+    sector_from_index([sx as i32, sy as i32, sz as i32])
+}
+
+/// Edge length in ly of the boxels for a mass code.
+pub fn boxel_size(mass_code: u8) -> f64 {
+    BOXEL_BASE_SIZE * (1u32 << mass_code) as f64
+}
+
+/// Works out the boxel number of a position for a given mass code.
+pub fn boxel_number(x: f64, y: f64, z: f64, mass_code: u8) -> u64 {
+    let origin = sector_origin(sector_index(x, y, z));
+    let size = boxel_size(mass_code);
+    let per_side = BOXEL_STRIDE >> mass_code;
+    let axis = |v: f64, o: f64| (((v - o) / size).floor() as u64).min(per_side - 1);
+    let (bx, by, bz) = (axis(x, origin[0]), axis(y, origin[1]), axis(z, origin[2]));
+    bx + by * BOXEL_STRIDE + bz * BOXEL_STRIDE * BOXEL_STRIDE
+}
+
+impl ProceduralSuffix {
+    /// Derives the suffix for a system at the given position.
+    pub fn for_position(x: f64, y: f64, z: f64, mass_code: u8, n2: u32) -> Self {
+        let mass_code = mass_code.min(MAX_MASS_CODE);
+        Self {
+            mass_code,
+            boxel: boxel_number(x, y, z, mass_code),
+            n2,
+        }
+    }
+
+    /// Parses a suffix like "XQ-L c21-0" or "AB-C d5".
+    pub fn parse(text: &str) -> Option<Self> {
+        let (letters, numbers) = text.trim().split_once(' ')?;
+        let letters = letters.as_bytes();
+        if letters.len() != 4 || letters[2] != b'-' {
+            return None;
+        }
+        let letter = |c: u8| c.is_ascii_uppercase().then(|| (c - b'A') as u64);
+        let (l1, l2, l3) = (
+            letter(letters[0])?,
+            letter(letters[1])?,
+            letter(letters[3])?,
+        );
+
+        let mass_code = numbers.as_bytes().first()?.wrapping_sub(b'a');
+        if mass_code > MAX_MASS_CODE {
+            return None;
+        }
+        let (n1, n2) = match numbers[1..].split_once('-') {
+            Some((n1, n2)) => (n1.parse::<u64>().ok()?, n2.parse::<u32>().ok()?),
+            None => (0, numbers[1..].parse::<u32>().ok()?),
+        };
+
+        // A huge n1 is a malformed name, not an overflow.
+        let boxel = n1
+            .checked_mul(26 * 26 * 26)?
+            .checked_add(l1 + l2 * 26 + l3 * 26 * 26)?;
+        Some(Self {
+            mass_code,
+            boxel,
+            n2,
+        })
+    }
+
+    /// Position of the boxel's corner relative to its sector's corner.
+    pub fn relative_origin(&self) -> [f64; 3] {
+        let size = boxel_size(self.mass_code);
+        let axis =
+            |shift: u32| ((self.boxel / BOXEL_STRIDE.pow(shift)) % BOXEL_STRIDE) as f64 * size;
+        [axis(0), axis(1), axis(2)]
+    }
+
+    /// Corner of the boxel in galactic coordinates, given its sector.
+    pub fn origin(&self, sector: SectorIndex) -> [f64; 3] {
+        let base = sector_origin(sector);
+        let rel = self.relative_origin();
+        [base[0] + rel[0], base[1] + rel[1], base[2] + rel[2]]
+    }
+}
+
+impl std::fmt::Display for ProceduralSuffix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let letter = |n: u64| (b'A' + (n % 26) as u8) as char;
+        let n1 = self.boxel / (26 * 26 * 26);
+        write!(
+            f,
+            "{}{}-{} {}",
+            letter(self.boxel),
+            letter(self.boxel / 26),
+            letter(self.boxel / (26 * 26)),
+            (b'a' + self.mass_code) as char,
+        )?;
+        if n1 > 0 {
+            write!(f, "{}-", n1)?;
+        }
+        write!(f, "{}", self.n2)
+    }
+}
+
+/// Builds the full procedural name of a system from its sector's name.
+pub fn procedural_name(
+    sector_name: &str,
+    x: f64,
+    y: f64,
+    z: f64,
+    mass_code: u8,
+    n2: u32,
+) -> String {
+    format!(
+        "{} {}",
+        sector_name,
+        ProceduralSuffix::for_position(x, y, z, mass_code, n2)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sector_index() {
+        // Sol sits a little way into its sector
+        assert_eq!(sector_index(0., 0., 0.), [39, 32, 18]);
+        let origin = sector_origin([39, 32, 18]);
+        assert_eq!(origin, [-65., -25., -1065.]);
+        assert_eq!(sector_index(origin[0], origin[1], origin[2]), [39, 32, 18]);
+        assert_eq!(
+            sector_index(origin[0] - 0.1, origin[1], origin[2]),
+            [38, 32, 18]
+        );
+    }
+
+    #[test]
+    fn test_sector_for() {
+        let sol = sector_for(0., 0., 0.);
+        assert_eq!(sol.index, [39, 32, 18]);
+        assert_eq!(sol.origin, [-65., -25., -1065.]);
+        assert_eq!(sol.id, pack_grid_key(39, 32, 18));
+        assert_eq!(sector_from_id(sol.id), sol);
+
+        // everything in the same 1280ly cube shares an id
+        assert_eq!(sector_for(1214.9, 1254.9, 214.9).id, sol.id);
+        assert_ne!(sector_for(1215., 0., 0.).id, sol.id);
+
+        // sectors below the galaxy base still round-trip
+        let below = sector_for(-60000., -50000., -30000.);
+        assert_eq!(below.index, [-8, -8, -5]);
+        assert_eq!(sector_from_id(below.id), below);
+    }
+
+    #[test]
+    fn test_boxel_size() {
+        assert_eq!(boxel_size(0), 10.);
+        assert_eq!(boxel_size(2), 40.);
+        assert_eq!(boxel_size(7), 1280.);
+    }
+
+    #[test]
+    fn test_suffix_format() {
+        let suffix = ProceduralSuffix {
+            mass_code: 2,
+            boxel: 23 + 16 * 26 + 11 * 676 + 21 * 17576,
+            n2: 0,
+        };
+        assert_eq!(suffix.to_string(), "XQ-L c21-0");
+
+        let suffix = ProceduralSuffix {
+            mass_code: 3,
+            boxel: 0,
+            n2: 5,
+        };
+        assert_eq!(suffix.to_string(), "AA-A d5");
+    }
+
+    #[test]
+    fn test_suffix_parse() {
+        let suffix = ProceduralSuffix::parse("XQ-L c21-0").unwrap();
+        assert_eq!(suffix.mass_code, 2);
+        assert_eq!(suffix.n2, 0);
+        assert_eq!(suffix.to_string(), "XQ-L c21-0");
+        assert_eq!(ProceduralSuffix::parse("AA-A h5").unwrap().mass_code, 7);
+
+        assert!(ProceduralSuffix::parse("XQ-L").is_none());
+        assert!(ProceduralSuffix::parse("XQL c21-0").is_none());
+        assert!(ProceduralSuffix::parse("Xq-L c21-0").is_none());
+        assert!(ProceduralSuffix::parse("XQ-L z21-0").is_none());
+        assert!(ProceduralSuffix::parse("XQ-L c21-").is_none());
+        assert!(ProceduralSuffix::parse("XQ-L c").is_none());
+        assert!(ProceduralSuffix::parse("XQ-L c18446744073709551615-0").is_none());
+        assert!(ProceduralSuffix::parse("XQ-É c21-0").is_none());
+    }
+
+    #[test]
+    fn test_suffix_round_trip() {
+        // deriving the suffix for a point and then finding the boxel it names
+        // must give a boxel containing the point.
+        for mass_code in 0..=MAX_MASS_CODE {
+            for (x, y, z) in [
+                (0., 0., 0.),
+                (-1234.5, 56.25, 8000.),
+                (25000., -300., 40000.),
+            ] {
+                let suffix = ProceduralSuffix::for_position(x, y, z, mass_code, 7);
+                let parsed = ProceduralSuffix::parse(&suffix.to_string()).unwrap();
+                assert_eq!(parsed, suffix);
+
+                let origin = parsed.origin(sector_index(x, y, z));
+                let size = boxel_size(mass_code);
+                for (v, o) in [(x, origin[0]), (y, origin[1]), (z, origin[2])] {
+                    assert!(o <= v && v < o + size, "{} not in [{}, {})", v, o, o + size);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_procedural_name() {
+        let origin = sector_origin([10, 20, 30]);
+        let name = procedural_name(
+            "Wregoe",
+            origin[0] + 5.,
+            origin[1] + 5.,
+            origin[2] + 5.,
+            0,
+            3,
+        );
+        assert_eq!(name, "Wregoe AA-A a3");
+        let name = procedural_name("Wregoe", origin[0] + 15., origin[1], origin[2], 0, 3);
+        assert_eq!(name, "Wregoe BA-A a3");
+    }
+}
@@ -0,0 +1,345 @@
+//! Where the importers' input comes from. A DataSource opens a fresh reader
+//! over its bytes each time it's asked, and says how big it is and what to
+//! call it in messages, so the same import runs on a file, bytes already in
+//! memory (tests, a browser's upload) or a download.
+//!
+//! Sources hand over their bytes as stored; `open_decoded` decompresses
+//! gzip, told by the first bytes as with files, and skips a byte-order mark.
+
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "fs")]
+use std::time::UNIX_EPOCH;
+
+use tracing::debug;
+
+use crate::gzip::{self, GzipReader};
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+use crate::http::RetryPolicy;
+#[cfg(feature = "fs")]
+use crate::input::{InputReader, STDIN_PATH};
+use crate::options::DEFAULT_BUFFER_SIZE;
+#[cfg(feature = "fs")]
+use crate::options::{ReadOptions, MIN_BUFFER_SIZE};
+use crate::rusty::skip_bom;
+
+/// Times an HttpSource retries a failed request by default, and the wait
+/// before the first retry.
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+pub const DEFAULT_RETRIES: usize = 3;
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+pub const DEFAULT_BACKOFF: std::time::Duration = std::time::Duration::from_secs(1);
+
+pub trait DataSource {
+    type Reader: BufRead;
+
+    /// A reader from the start of the data.
+    fn open(&self) -> io::Result<Self::Reader>;
+
+    /// The size in bytes, if it can be known before reading.
+    fn size(&self) -> Option<u64>;
+
+    /// What to call the source in messages: a path or a URL.
+    fn name(&self) -> &str;
+
+    /// Seconds since the unix epoch the data was last changed, if known;
+    /// undated .prices lines are dated by it.
+    fn modified(&self) -> Option<i64> {
+        None
+    }
+}
+
+/// Bytes in memory. Cloning one shares the bytes.
+#[derive(Clone, Debug)]
+pub struct MemorySource {
+    name: String,
+    data: Arc<[u8]>,
+    modified: Option<i64>,
+}
+
+impl MemorySource {
+    pub fn new(name: &str, data: impl Into<Arc<[u8]>>) -> Self {
+        Self {
+            name: name.to_string(),
+            data: data.into(),
+            modified: None,
+        }
+    }
+
+    /// Dates the data, as a file's modification time would.
+    pub fn modified_at(mut self, timestamp: i64) -> Self {
+        self.modified = Some(timestamp);
+        self
+    }
+}
+
+impl DataSource for MemorySource {
+    type Reader = Cursor<Arc<[u8]>>;
+
+    fn open(&self) -> io::Result<Self::Reader> {
+        Ok(Cursor::new(self.data.clone()))
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.data.len() as u64)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn modified(&self) -> Option<i64> {
+        self.modified
+    }
+}
+
+/// Seconds since the unix epoch a file was last modified, if the platform
+/// can say.
+#[cfg(feature = "fs")]
+pub fn file_modified(path: &Path) -> Option<i64> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_secs() as i64)
+}
+
+/// A file, or standard input for "-", read according to ReadOptions.
+#[cfg(feature = "fs")]
+#[derive(Clone, Debug)]
+pub struct FileSource {
+    path: PathBuf,
+    name: String,
+    options: ReadOptions,
+    modified: Option<i64>,
+}
+
+#[cfg(feature = "fs")]
+impl FileSource {
+    pub fn new(path: impl AsRef<Path>, options: &ReadOptions) -> Self {
+        let path = path.as_ref();
+        Self {
+            path: path.to_path_buf(),
+            name: path.display().to_string(),
+            options: options.clone(),
+            modified: None,
+        }
+    }
+
+    /// Dates the data, in place of the file's modification time: for a
+    /// copy of a file that should be dated as the original.
+    pub fn modified_at(mut self, timestamp: Option<i64>) -> Self {
+        self.modified = timestamp;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn options(&self) -> &ReadOptions {
+        &self.options
+    }
+}
+
+#[cfg(feature = "fs")]
+impl DataSource for FileSource {
+    type Reader = InputReader;
+
+    fn open(&self) -> io::Result<Self::Reader> {
+        let capacity = self.options.buffer_size.max(MIN_BUFFER_SIZE);
+        InputReader::open(&self.path, &self.options, capacity)
+    }
+
+    fn size(&self) -> Option<u64> {
+        if self.path == Path::new(STDIN_PATH) {
+            return None;
+        }
+        std::fs::metadata(&self.path).ok().map(|meta| meta.len())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn modified(&self) -> Option<i64> {
+        self.modified.or_else(|| file_modified(&self.path))
+    }
+}
+
+/// A URL, fetched with a GET each time it's opened.
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+pub struct HttpSource {
+    agent: ureq::Agent,
+    url: String,
+    retry: RetryPolicy,
+}
+
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+impl HttpSource {
+    /// A source giving up on a connection that's silent for longer than
+    /// `timeout`.
+    pub fn new(url: &str, timeout: std::time::Duration) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(timeout)
+                .timeout_read(timeout)
+                .build(),
+            url: url.to_string(),
+            retry: RetryPolicy::new(DEFAULT_RETRIES, DEFAULT_BACKOFF),
+        }
+    }
+
+    /// Retries failed requests according to `retry`. Only opening is
+    /// retried; a connection lost partway through fails the read.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+}
+
+#[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+impl DataSource for HttpSource {
+    type Reader = BufReader<Box<dyn Read + Send + Sync>>;
+
+    fn open(&self) -> io::Result<Self::Reader> {
+        let response = self
+            .retry
+            .run(&self.url, || Ok(self.agent.get(&self.url).call()?))
+            .map_err(|e| io::Error::other(format!("{}: {}", self.url, e)))?;
+        Ok(BufReader::new(response.into_reader()))
+    }
+
+    /// The Content-Length of a HEAD request, if the server gives one.
+    fn size(&self) -> Option<u64> {
+        let response = self.agent.head(&self.url).call().ok()?;
+        response.header("Content-Length")?.parse().ok()
+    }
+
+    fn name(&self) -> &str {
+        &self.url
+    }
+}
+
+/// A source's reader, decompressed if it was gzip.
+pub enum DecodedReader<R: BufRead> {
+    Plain(R),
+    Gzip(Box<BufReader<GzipReader<R>>>),
+}
+
+impl<R: BufRead> Read for DecodedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            DecodedReader::Plain(reader) => reader.read(buf),
+            DecodedReader::Gzip(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl<R: BufRead> BufRead for DecodedReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            DecodedReader::Plain(reader) => reader.fill_buf(),
+            DecodedReader::Gzip(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match self {
+            DecodedReader::Plain(reader) => reader.consume(amount),
+            DecodedReader::Gzip(reader) => reader.consume(amount),
+        }
+    }
+}
+
+/// Opens a source, decompressing it if it's gzip and skipping a byte-order
+/// mark, and returns the reader with the count of bytes skipped.
+pub fn open_decoded<S: DataSource>(source: &S) -> io::Result<(DecodedReader<S::Reader>, usize)> {
+    let mut reader = source.open()?;
+    let mut reader = if gzip::is_gzip(reader.fill_buf()?) {
+        debug!(source = source.name(), "decompressing gzip data");
+        let decoder = GzipReader::new(reader);
+        DecodedReader::Gzip(Box::new(BufReader::with_capacity(
+            DEFAULT_BUFFER_SIZE,
+            decoder,
+        )))
+    } else {
+        DecodedReader::Plain(reader)
+    };
+    let skipped = skip_bom(&mut reader)?;
+    Ok((reader, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn read_all<S: DataSource>(source: &S) -> String {
+        let (mut reader, _) = open_decoded(source).unwrap();
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_memory_source() {
+        let source = MemorySource::new("sample.prices", &b"\xef\xbb\xbf@ SOL/Galileo\n"[..]);
+        assert_eq!((source.name(), source.size()), ("sample.prices", Some(17)));
+        assert_eq!(source.modified(), None);
+        let (_, skipped) = open_decoded(&source).unwrap();
+        assert_eq!(skipped, 3);
+        // every open starts again from the top
+        assert_eq!(read_all(&source), "@ SOL/Galileo\n");
+        assert_eq!(read_all(&source), "@ SOL/Galileo\n");
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(b"@ LAVE/Lave Station\n").unwrap();
+        let gzipped = MemorySource::new("gz", encoder.finish().unwrap()).modified_at(7);
+        assert_eq!(read_all(&gzipped), "@ LAVE/Lave Station\n");
+        assert_eq!(gzipped.modified(), Some(7));
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_file_source() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"@ SOL/Galileo\n").unwrap();
+        let source = FileSource::new(file.path(), &ReadOptions::default());
+        assert_eq!(source.size(), Some(14));
+        assert!(source.modified().is_some());
+        assert_eq!(read_all(&source), "@ SOL/Galileo\n");
+        assert_eq!(
+            FileSource::new(STDIN_PATH, &ReadOptions::default()).size(),
+            None
+        );
+        assert!(FileSource::new("/no/such/file", &ReadOptions::default())
+            .open()
+            .is_err());
+    }
+
+    #[cfg(any(feature = "download", feature = "edsm", feature = "spansh"))]
+    #[test]
+    fn test_http_source() {
+        use crate::http::testing;
+        use std::time::Duration;
+
+        let body = "@ SOL/Galileo\n";
+        let (url, server) = testing::serve(vec![
+            testing::response("200 OK", "", body),
+            testing::response("200 OK", "", body),
+            testing::response("404 Not Found", "", ""),
+        ]);
+        let source = HttpSource::new(&format!("{}/listings.prices", url), Duration::from_secs(5))
+            .retry_policy(RetryPolicy::new(0, Duration::ZERO));
+        assert!(source.name().ends_with("/listings.prices"));
+        assert_eq!(source.size(), Some(body.len() as u64));
+        assert_eq!(read_all(&source), body);
+        assert!(source.open().is_err());
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+//! Where in the input a parse error happened: the byte offset, line and
+//! column of the failure and the text around it, so an error message can
+//! point at the exact spot in a file.
+
+use std::fmt;
+
+/// Longest snippet quoted from the failing line, in chars.
+const SNIPPET_WIDTH: usize = 80;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    /// Bytes from the start of the input to the failure.
+    pub offset: u64,
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, counted in chars.
+    pub column: usize,
+    /// The failing line, or up to SNIPPET_WIDTH chars of it around the
+    /// column when it's long.
+    pub snippet: String,
+}
+
+/// The largest char boundary of text at or before byte `at`.
+fn char_boundary(text: &str, at: usize) -> usize {
+    let mut at = at.min(text.len());
+    while !text.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+impl Span {
+    /// Span of byte `at` of one line of input, given the line's number and
+    /// the offset it starts at.
+    pub fn in_line(text: &str, line: usize, line_offset: u64, at: usize) -> Self {
+        let text = text.trim_end_matches(['\r', '\n']);
+        let at = char_boundary(text, at);
+        let column = text[..at].chars().count() + 1;
+        let skip = column.saturating_sub(SNIPPET_WIDTH / 2 + 1);
+        Self {
+            offset: line_offset + at as u64,
+            line,
+            column,
+            snippet: text.chars().skip(skip).take(SNIPPET_WIDTH).collect(),
+        }
+    }
+
+    /// Span of byte `offset` of a whole document.
+    pub fn in_text(text: &str, offset: usize) -> Self {
+        let offset = char_boundary(text, offset);
+        let start = text[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        let end = text[offset..]
+            .find('\n')
+            .map_or(text.len(), |idx| offset + idx);
+        let line = text.as_bytes()[..start]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count()
+            + 1;
+        Self::in_line(&text[start..end], line, start as u64, offset - start)
+    }
+
+    /// Span of a document's 1-based line and column, where the column
+    /// counts bytes as serde_json's does.
+    pub fn at_line_column(text: &str, line: usize, column: usize) -> Self {
+        let start = match line {
+            0 | 1 => 0,
+            _ => text
+                .match_indices('\n')
+                .nth(line - 2)
+                .map_or(text.len(), |(idx, _)| idx + 1),
+        };
+        Self::in_text(text, start + column.saturating_sub(1))
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_line() {
+        let span = Span::in_line("Gold 1 2 3X -\n", 4, 100, 9);
+        assert_eq!(
+            span,
+            Span {
+                offset: 109,
+                line: 4,
+                column: 10,
+                snippet: "Gold 1 2 3X -".into(),
+            }
+        );
+        // columns count chars, and offsets inside a char or past the end
+        // are pulled back
+        let span = Span::in_line("Café 1 é", 1, 0, 9);
+        assert_eq!((span.offset, span.column), (8, 8));
+        assert_eq!(Span::in_line("Café", 1, 0, 99).column, 5);
+
+        let long = format!("{}X{}", "a".repeat(100), "b".repeat(100));
+        let span = Span::in_line(&long, 1, 0, 100);
+        assert_eq!(span.snippet.chars().count(), SNIPPET_WIDTH);
+        assert_eq!(span.snippet.find('X'), Some(SNIPPET_WIDTH / 2));
+    }
+
+    #[test]
+    fn test_in_text() {
+        let text = "first\nsecond line\r\nthird";
+        let span = Span::in_text(text, 13);
+        assert_eq!((span.line, span.column, span.offset), (2, 8, 13));
+        assert_eq!(span.snippet, "second line");
+        assert_eq!(Span::in_text(text, 0).line, 1);
+        assert_eq!(Span::in_text(text, 999).snippet, "third");
+        assert_eq!(Span::in_text("", 0).column, 1);
+    }
+
+    #[test]
+    fn test_at_line_column() {
+        let text = "[\n  {\"name\": 1}\n]";
+        let span = Span::at_line_column(text, 2, 12);
+        assert_eq!((span.line, span.column, span.offset), (2, 12, 13));
+        assert_eq!(span.to_string(), "line 2, column 12");
+        assert_eq!(Span::at_line_column(text, 99, 1).line, 3);
+    }
+}
@@ -0,0 +1,365 @@
+//! A client for Spansh's search API, so a partial refresh can fetch just the
+//! systems and markets it needs instead of the whole galaxy dump. Built with
+//! the "spansh" feature.
+//!
+//! Searches are POSTed as JSON filters and answered a page at a time; the
+//! client walks the pages until it has every result or the limit asked for.
+//! Requests that fail in a way that may not happen again (connection
+//! trouble, 429, 5xx) are retried according to the client's RetryPolicy.
+
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::http::{HttpError, RetryPolicy};
+use crate::ids::{ItemId, StationId, SystemId};
+use crate::journal::MarketFile;
+use crate::market::StationItem;
+use crate::prices::parse_timestamp;
+use crate::system::System;
+
+pub const SPANSH_URL: &str = "https://spansh.co.uk";
+
+/// Results asked for per page.
+const PAGE_SIZE: usize = 100;
+
+/// Times a failed request is retried by default, and the wait before the
+/// first retry.
+pub const DEFAULT_RETRIES: usize = 3;
+pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(Deserialize)]
+struct Page<T> {
+    count: usize,
+    results: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct SpanshSystem {
+    name: String,
+    id64: Option<SystemId>,
+    x: f64,
+    y: f64,
+    z: f64,
+}
+
+#[derive(Deserialize)]
+struct SpanshListing {
+    commodity: String,
+    buy_price: i32,
+    sell_price: i32,
+    supply: i64,
+    demand: i64,
+}
+
+#[derive(Deserialize)]
+struct SpanshStation {
+    name: String,
+    system_name: String,
+    market_id: StationId,
+    market_updated_at: Option<String>,
+    #[serde(default)]
+    market: Vec<SpanshListing>,
+}
+
+/// Parses Spansh's "2024-05-01 12:00:00+00" into seconds since the epoch.
+fn parse_spansh_timestamp(text: &str) -> Option<i64> {
+    let text = text.strip_suffix("+00").unwrap_or(text);
+    let (date, time) = text.split_once([' ', 'T'])?;
+    parse_timestamp(date, time)
+}
+
+impl SpanshStation {
+    /// Spansh has no brackets, so levels of what's offered are unknown.
+    fn into_market(self) -> MarketFile {
+        let timestamp = self
+            .market_updated_at
+            .as_deref()
+            .and_then(parse_spansh_timestamp)
+            .unwrap_or(0);
+        let listings = self.market.into_iter().map(|listing| {
+            let supplied = listing.supply > 0 && listing.buy_price > 0;
+            let demanded = listing.sell_price > 0;
+            let item = StationItem {
+                station_id: self.market_id,
+                item_id: ItemId(0),
+                demand_price: listing.sell_price,
+                demand_units: listing.demand,
+                demand_level: if demanded { -1 } else { 0 },
+                supply_price: if supplied { listing.buy_price } else { 0 },
+                supply_units: listing.supply,
+                supply_level: if supplied { -1 } else { 0 },
+                modified: timestamp,
+            };
+            (listing.commodity, item)
+        });
+        MarketFile::new(
+            self.market_id,
+            self.name,
+            self.system_name,
+            timestamp,
+            listings,
+        )
+    }
+}
+
+pub struct SpanshClient {
+    agent: ureq::Agent,
+    base_url: String,
+    retry: RetryPolicy,
+}
+
+impl SpanshClient {
+    /// A client for the Spansh at `base_url`, giving up on requests that
+    /// take longer than `timeout`.
+    pub fn new(base_url: &str, timeout: Duration) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            retry: RetryPolicy::new(DEFAULT_RETRIES, DEFAULT_BACKOFF),
+        }
+    }
+
+    /// Retries failed requests up to `retries` times, waiting `backoff`
+    /// before the first retry and twice as long before each one after.
+    pub fn retries(mut self, retries: usize, backoff: Duration) -> Self {
+        self.retry.retries = retries;
+        self.retry.backoff = backoff;
+        self
+    }
+
+    /// Retries failed requests according to `retry`.
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// POSTs one page of a search, retrying transient failures.
+    fn post(&self, url: &str, body: &Value) -> Result<String, HttpError> {
+        self.retry.run(url, || {
+            let response = self
+                .agent
+                .post(url)
+                .set("Content-Type", "application/json")
+                .send_string(&body.to_string())?;
+            Ok(response.into_string()?)
+        })
+    }
+
+    /// Runs a search against `/api/{kind}/search`, collecting results page
+    /// by page, up to `limit` of them.
+    fn search<T: DeserializeOwned>(
+        &self,
+        kind: &str,
+        mut query: Value,
+        limit: usize,
+    ) -> Result<Vec<T>, HttpError> {
+        let url = format!("{}/api/{}/search", self.base_url, kind);
+        let mut results = Vec::new();
+        if limit == 0 {
+            return Ok(results);
+        }
+        for page in 0.. {
+            query["size"] = json!(PAGE_SIZE.min(limit - results.len()));
+            query["page"] = json!(page);
+            let body = self.post(&url, &query)?;
+            let page: Page<T> = serde_json::from_str(&body)?;
+            let empty = page.results.is_empty();
+            results.extend(page.results);
+            debug!(
+                kind,
+                results = results.len(),
+                count = page.count,
+                "Spansh search page"
+            );
+            if empty || results.len() >= page.count.min(limit) {
+                break;
+            }
+        }
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    fn systems_matching(&self, query: Value, limit: usize) -> Result<Vec<System>, HttpError> {
+        let systems: Vec<SpanshSystem> = self.search("systems", query, limit)?;
+        Ok(systems
+            .into_iter()
+            .map(|system| System {
+                name: system.name,
+                id64: system.id64,
+                x: system.x,
+                y: system.y,
+                z: system.z,
+            })
+            .collect())
+    }
+
+    /// Looks up systems by name. Ones Spansh doesn't know are left out.
+    pub fn systems(&self, names: &[&str]) -> Result<Vec<System>, HttpError> {
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = json!({
+            "filters": {"name": {"value": names}},
+            "sort": [{"name": {"direction": "asc"}}],
+        });
+        self.systems_matching(query, names.len())
+    }
+
+    /// Up to `limit` systems within `radius` ly of a point, nearest first.
+    pub fn systems_near(
+        &self,
+        center: [f64; 3],
+        radius: f64,
+        limit: usize,
+    ) -> Result<Vec<System>, HttpError> {
+        let query = json!({
+            "filters": {"distance": {"min": "0", "max": radius.to_string()}},
+            "sort": [{"distance": {"direction": "asc"}}],
+            "reference_coords": {"x": center[0], "y": center[1], "z": center[2]},
+        });
+        self.systems_matching(query, limit)
+    }
+
+    /// The markets of the stations in the named systems.
+    pub fn markets(&self, systems: &[&str], limit: usize) -> Result<Vec<MarketFile>, HttpError> {
+        if systems.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = json!({
+            "filters": {
+                "system_name": {"value": systems},
+                "has_market": {"value": true},
+            },
+            "sort": [{"market_id": {"direction": "asc"}}],
+        });
+        let stations: Vec<SpanshStation> = self.search("stations", query, limit)?;
+        Ok(stations
+            .into_iter()
+            .map(SpanshStation::into_market)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commodities::canonical_commodity;
+    use crate::http::testing::{response, serve};
+
+    fn client(url: &str) -> SpanshClient {
+        SpanshClient::new(url, Duration::from_secs(5)).retries(2, Duration::ZERO)
+    }
+
+    fn system(name: &str, x: f64) -> Value {
+        json!({"name": name, "id64": 1, "x": x, "y": 0.0, "z": 0.0, "distance": x})
+    }
+
+    fn page(count: usize, results: Vec<Value>) -> String {
+        response(
+            "200 OK",
+            "",
+            &json!({"count": count, "results": results}).to_string(),
+        )
+    }
+
+    #[test]
+    fn test_parse_spansh_timestamp() {
+        assert_eq!(
+            parse_spansh_timestamp("2024-05-01 12:00:00+00"),
+            Some(1714564800)
+        );
+        assert_eq!(
+            parse_spansh_timestamp("2024-05-01T12:00:00"),
+            Some(1714564800)
+        );
+        assert_eq!(parse_spansh_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn test_systems_near_pages() {
+        let (url, server) = serve(vec![
+            page(3, vec![system("A", 1.), system("B", 2.)]),
+            page(3, vec![system("C", 3.)]),
+        ]);
+        let systems = client(&url).systems_near([0., 0., 0.], 10., 50).unwrap();
+        let names: Vec<&str> = systems.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["A", "B", "C"]);
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].0, "POST /api/systems/search HTTP/1.1");
+        let first: Value = serde_json::from_str(&requests[0].1).unwrap();
+        assert_eq!(
+            (first["page"].clone(), first["size"].clone()),
+            (json!(0), json!(50))
+        );
+        assert_eq!(
+            first["reference_coords"],
+            json!({"x": 0.0, "y": 0.0, "z": 0.0})
+        );
+        let second: Value = serde_json::from_str(&requests[1].1).unwrap();
+        assert_eq!(second["page"], json!(1));
+    }
+
+    #[test]
+    fn test_search_limit_and_retry() {
+        let (url, server) = serve(vec![
+            response("502 Bad Gateway", "", ""),
+            page(500, vec![system("A", 1.), system("B", 2.)]),
+            response("400 Bad Request", "", ""),
+        ]);
+        let client = client(&url);
+        // the first page covers the limit, so no second is asked for
+        let systems = client.systems_near([0., 0., 0.], 10., 2).unwrap();
+        assert_eq!(systems.len(), 2);
+        let err = client.systems(&["Sol"]).unwrap_err();
+        assert!(!err.is_transient());
+        assert_eq!(server.join().unwrap().len(), 3);
+        assert!(client.systems(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_markets() {
+        let station = json!({
+            "name": "Abraham Lincoln", "system_name": "Sol", "market_id": 128016640,
+            "market_updated_at": "2024-05-01 12:00:00+00",
+            "market": [
+                {"commodity": "Gold", "category": "Metals", "buy_price": 9100,
+                 "sell_price": 8900, "supply": 120, "demand": 0},
+                {"commodity": "Platinum", "category": "Metals", "buy_price": 0,
+                 "sell_price": 31000, "supply": 0, "demand": 500},
+                {"commodity": "Newthing", "category": "Metals", "buy_price": 1,
+                 "sell_price": 1, "supply": 1, "demand": 1}
+            ]
+        });
+        let (url, server) = serve(vec![page(1, vec![station])]);
+        let markets = client(&url).markets(&["Sol"], 100).unwrap();
+        server.join().unwrap();
+
+        let market = &markets[0];
+        assert_eq!(
+            (market.station.as_str(), market.system.as_str()),
+            ("Abraham Lincoln", "Sol")
+        );
+        assert_eq!(market.unknown_items, ["Newthing"]);
+        let snapshot = &market.snapshot;
+        assert_eq!(
+            (snapshot.station_id, snapshot.timestamp),
+            (StationId(128016640), 1714564800)
+        );
+        let gold = snapshot
+            .get(canonical_commodity("Gold").unwrap().id)
+            .unwrap();
+        assert_eq!(
+            (gold.supply_price, gold.supply_level, gold.demand_level),
+            (9100, -1, -1)
+        );
+        let platinum = snapshot
+            .get(canonical_commodity("Platinum").unwrap().id)
+            .unwrap();
+        assert_eq!((platinum.supply_price, platinum.supply_level), (0, 0));
+    }
+}
@@ -0,0 +1,48 @@
+//! What the spatial indexes have in common: given points by position,
+//! which are within some distance of a position. GridIndex suits evenly
+//! spread points; Octree copes with the dense bubble around Sol as well as
+//! the sparse rim.
+//!
+//! Both index positions of either f64 or f32 components. Spansh's dump has
+//! its coordinates as f32, and keeping them so halves the memory of the
+//! position arrays; f32 widens to f64 exactly, so keys and distances come
+//! out the same as if they'd been stored as f64.
+
+use crate::grid::distance;
+
+/// A type a position's components can be: f64 or f32.
+pub trait Coordinate: Copy + Into<f64> {}
+
+impl Coordinate for f32 {}
+impl Coordinate for f64 {}
+
+/// Widens a position to f64, exactly.
+pub fn widen<T: Coordinate>(pos: [T; 3]) -> [f64; 3] {
+    pos.map(Into::into)
+}
+
+pub trait SpatialIndex {
+    /// Number of points indexed.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn position(&self, idx: u32) -> [f64; 3];
+
+    /// Indexes of the points that may be within range of a position: all of
+    /// those that are, and some that aren't.
+    fn candidates(&self, pos: [f64; 3], range: f64) -> Vec<u32>;
+
+    /// Indexes and distances of the points within range of a position.
+    fn within(&self, pos: [f64; 3], range: f64) -> Vec<(u32, f64)> {
+        self.candidates(pos, range)
+            .into_iter()
+            .filter_map(|idx| {
+                let distance = distance(pos, self.position(idx));
+                (distance <= range).then_some((idx, distance))
+            })
+            .collect()
+    }
+}
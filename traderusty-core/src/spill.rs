@@ -0,0 +1,216 @@
+//! Spilling a merge's working set to temporary files once it outgrows the
+//! memory budget, so merging the whole galaxy's listings doesn't need the
+//! whole galaxy in memory.
+//!
+//! Each spill writes a run: the winning entries so far, sorted by key with
+//! one entry per key, in a compact binary form to an anonymous temporary
+//! file that's gone as soon as it's closed. Merging streams the runs back
+//! together, holding one entry per run, and keeps the newest entry for each
+//! key; on a tie the later run wins, as later files do in the merge itself.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::mem;
+
+use crate::prices::PriceRecord;
+
+/// Canonical system, station and item names.
+pub type Key = (String, String, String);
+
+/// Rough bytes an entry of a merge's working set takes in memory: the
+/// record, its key, and the map node holding them.
+pub fn entry_size(key: &Key, record: &PriceRecord) -> usize {
+    const NODE_OVERHEAD: usize = 64;
+    mem::size_of::<(Key, i64, PriceRecord)>()
+        + NODE_OVERHEAD
+        + key.0.len()
+        + key.1.len()
+        + key.2.len()
+        + record.heap_size()
+}
+
+/// Sorted runs spilled so far.
+#[derive(Default)]
+pub struct Runs {
+    files: Vec<File>,
+}
+
+impl Runs {
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Writes entries, which must be sorted by key, out as a new run.
+    pub fn spill(
+        &mut self,
+        entries: impl IntoIterator<Item = (Key, (i64, PriceRecord))>,
+    ) -> io::Result<()> {
+        let mut out = BufWriter::new(tempfile::tempfile()?);
+        for (key, (time, record)) in entries {
+            write_entry(&mut out, &key, time, &record)?;
+        }
+        let mut file = out.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+        self.files.push(file);
+        Ok(())
+    }
+
+    /// Streams the runs back in key order, handing the winning record for
+    /// each key to `emit`.
+    pub fn merge(self, mut emit: impl FnMut(PriceRecord)) -> io::Result<()> {
+        let mut readers: Vec<_> = self.files.into_iter().map(BufReader::new).collect();
+        let mut heads: Vec<Option<(i64, PriceRecord)>> = vec![None; readers.len()];
+        let mut heap = BinaryHeap::new();
+        let mut advance = |run: usize,
+                           heads: &mut Vec<Option<(i64, PriceRecord)>>,
+                           heap: &mut BinaryHeap<Reverse<(Key, usize)>>|
+         -> io::Result<()> {
+            if let Some((key, time, record)) = read_entry(&mut readers[run])? {
+                heads[run] = Some((time, record));
+                heap.push(Reverse((key, run)));
+            }
+            Ok(())
+        };
+        for run in 0..heads.len() {
+            advance(run, &mut heads, &mut heap)?;
+        }
+        // Equal keys pop in run order, so `>=` lets the later run win a tie.
+        while let Some(Reverse((key, run))) = heap.pop() {
+            let mut best = heads[run].take().expect("popped run has a head");
+            advance(run, &mut heads, &mut heap)?;
+            while matches!(heap.peek(), Some(Reverse((next, _))) if *next == key) {
+                let Reverse((_, run)) = heap.pop().unwrap();
+                let entry = heads[run].take().expect("popped run has a head");
+                if entry.0 >= best.0 {
+                    best = entry;
+                }
+                advance(run, &mut heads, &mut heap)?;
+            }
+            emit(best.1);
+        }
+        Ok(())
+    }
+}
+
+fn write_str(out: &mut impl Write, value: &str) -> io::Result<()> {
+    out.write_all(&(value.len() as u32).to_le_bytes())?;
+    out.write_all(value.as_bytes())
+}
+
+fn write_entry(out: &mut impl Write, key: &Key, time: i64, record: &PriceRecord) -> io::Result<()> {
+    for value in [&key.0, &key.1, &key.2] {
+        write_str(out, value)?;
+    }
+    out.write_all(&time.to_le_bytes())?;
+    for value in [
+        &record.system,
+        &record.station,
+        &record.category,
+        &record.item,
+    ] {
+        write_str(out, value)?;
+    }
+    out.write_all(&record.demand_price.to_le_bytes())?;
+    out.write_all(&record.demand_units.to_le_bytes())?;
+    out.write_all(&record.demand_level.to_le_bytes())?;
+    out.write_all(&record.supply_price.to_le_bytes())?;
+    out.write_all(&record.supply_units.to_le_bytes())?;
+    out.write_all(&record.supply_level.to_le_bytes())?;
+    match record.modified {
+        Some(modified) => {
+            out.write_all(&[1])?;
+            out.write_all(&modified.to_le_bytes())?;
+        }
+        None => out.write_all(&[0])?,
+    }
+    out.write_all(&(record.line as u64).to_le_bytes())
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_str(input: &mut impl Read) -> io::Result<String> {
+    let len = u32::from_le_bytes(read_array(input)?) as usize;
+    let mut bytes = vec![0; len];
+    input.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The next entry of a run, or None at its end.
+fn read_entry(input: &mut impl BufRead) -> io::Result<Option<(Key, i64, PriceRecord)>> {
+    if input.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let key = (read_str(input)?, read_str(input)?, read_str(input)?);
+    let time = i64::from_le_bytes(read_array(input)?);
+    let record = PriceRecord {
+        system: read_str(input)?,
+        station: read_str(input)?,
+        category: read_str(input)?,
+        item: read_str(input)?,
+        demand_price: i32::from_le_bytes(read_array(input)?),
+        demand_units: i64::from_le_bytes(read_array(input)?),
+        demand_level: i32::from_le_bytes(read_array(input)?),
+        supply_price: i32::from_le_bytes(read_array(input)?),
+        supply_units: i64::from_le_bytes(read_array(input)?),
+        supply_level: i32::from_le_bytes(read_array(input)?),
+        modified: match read_array::<1>(input)?[0] {
+            0 => None,
+            _ => Some(i64::from_le_bytes(read_array(input)?)),
+        },
+        line: u64::from_le_bytes(read_array(input)?) as usize,
+    };
+    Ok(Some((key, time, record)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(item: &str, time: i64, price: i32) -> (Key, (i64, PriceRecord)) {
+        let key = ("SOL".to_string(), "A".to_string(), item.to_uppercase());
+        let record = PriceRecord {
+            system: "Sol".to_string(),
+            station: "A".to_string(),
+            item: item.to_string(),
+            demand_price: price,
+            modified: (time > 0).then_some(time),
+            line: 7,
+            ..Default::default()
+        };
+        (key, (time, record))
+    }
+
+    #[test]
+    fn test_merge_runs() {
+        let mut runs = Runs::default();
+        assert!(runs.is_empty());
+        runs.spill([entry("Gold", 10, 1), entry("Tea", 5, 2)])
+            .unwrap();
+        runs.spill([entry("Gold", 10, 3), entry("Silver", 1, 4)])
+            .unwrap();
+        runs.spill([entry("Tea", 0, 5)]).unwrap();
+        assert_eq!(runs.len(), 3);
+
+        let mut merged = Vec::new();
+        runs.merge(|record| merged.push(record)).unwrap();
+        let prices: Vec<(&str, i32)> = merged
+            .iter()
+            .map(|r| (r.item.as_str(), r.demand_price))
+            .collect();
+        // the later run wins Gold's tie; Tea's older listing loses
+        assert_eq!(prices, [("Gold", 3), ("Silver", 4), ("Tea", 2)]);
+        assert_eq!(merged[0], entry("Gold", 10, 3).1 .1);
+        assert_eq!(merged[0].line, 7);
+        assert_eq!(merged[1].modified, Some(1));
+    }
+}
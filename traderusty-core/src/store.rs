@@ -0,0 +1,672 @@
+//! In-memory store of station market listings with secondary indexes, so that
+//! "who sells X", "who buys X" and "what's near here" don't need a scan.
+//!
+//! Listings are kept packed, 32 bytes where a StationItem takes 48, since a
+//! full galaxy has tens of millions of them: the ids are only in the key,
+//! the levels are 2-bit codes and the units u32s. The few listings that
+//! don't fit are kept whole beside the packed ones. Lookups unpack, so they
+//! return StationItems rather than references.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::commodities::{item_category, Category};
+use crate::ids::{ItemId, StationId};
+use crate::intern::Interner;
+use crate::market::{level_code, level_from_code, MarketSnapshot, StationItem};
+use crate::rusty::{iter_stellar_grid_keys_in_sphere, stellar_grid_key, stellar_grid_keys};
+use crate::sector::sector_for;
+
+/// Marks a Listing whose StationItem is in the store's `spilled` map.
+const SPILLED: u8 = 1 << 4;
+
+/// Units as a u32: u32::MAX for -1, unknown.
+fn pack_units(units: i64) -> Option<u32> {
+    match units {
+        -1 => Some(u32::MAX),
+        _ => u32::try_from(units).ok().filter(|&units| units != u32::MAX),
+    }
+}
+
+fn unpack_units(units: u32) -> i64 {
+    if units == u32::MAX {
+        -1
+    } else {
+        units.into()
+    }
+}
+
+/// A listing as the store keeps it, without its ids. `levels` holds the
+/// demand level's code in bits 0-1, the supply level's in bits 2-3, and
+/// SPILLED.
+#[derive(Clone, Copy, Debug, Default)]
+struct Listing {
+    demand_price: i32,
+    supply_price: i32,
+    demand_units: u32,
+    supply_units: u32,
+    modified: i64,
+    levels: u8,
+}
+
+impl Listing {
+    const SPILLED: Self = Self {
+        demand_price: 0,
+        supply_price: 0,
+        demand_units: 0,
+        supply_units: 0,
+        modified: 0,
+        levels: SPILLED,
+    };
+
+    /// Packs a listing, None if it doesn't fit.
+    fn pack(item: &StationItem) -> Option<Self> {
+        let demand_level = level_code(item.demand_level, item.demand_units)?;
+        let supply_level = level_code(item.supply_level, item.supply_units)?;
+        Some(Self {
+            demand_price: item.demand_price,
+            supply_price: item.supply_price,
+            demand_units: pack_units(item.demand_units)?,
+            supply_units: pack_units(item.supply_units)?,
+            modified: item.modified,
+            levels: demand_level | supply_level << 2,
+        })
+    }
+
+    fn unpack(&self, station_id: StationId, item_id: ItemId) -> StationItem {
+        let (demand_units, supply_units) = (
+            unpack_units(self.demand_units),
+            unpack_units(self.supply_units),
+        );
+        StationItem {
+            station_id,
+            item_id,
+            demand_price: self.demand_price,
+            demand_units,
+            demand_level: level_from_code(self.levels, demand_units),
+            supply_price: self.supply_price,
+            supply_units,
+            supply_level: level_from_code(self.levels >> 2, supply_units),
+            modified: self.modified,
+        }
+    }
+
+    fn is_spilled(&self) -> bool {
+        self.levels & SPILLED != 0
+    }
+}
+
+#[derive(Default)]
+pub struct MarketStore {
+    /// Every listing, keyed by (station_id, item_id).
+    records: HashMap<(StationId, ItemId), Listing>,
+    /// The listings that don't pack, whole.
+    spilled: HashMap<(StationId, ItemId), StationItem>,
+    /// station_id -> items listed there.
+    by_station: HashMap<StationId, BTreeSet<ItemId>>,
+    /// item_id -> stations listing it.
+    by_item: HashMap<ItemId, BTreeSet<StationId>>,
+    /// station_id -> coordinates of the station's system.
+    positions: HashMap<StationId, [f64; 3]>,
+    /// System names, interned since many stations share a system.
+    systems: Interner,
+    /// station_id -> interned system name.
+    station_systems: HashMap<StationId, u32>,
+    /// interned system name -> stations in that system.
+    system_stations: HashMap<u32, BTreeSet<StationId>>,
+    /// stellar grid key -> stations in that cell.
+    by_cell: HashMap<u64, BTreeSet<StationId>>,
+    /// Categories of items set with `set_category`, for ids from a database
+    /// whose Item table numbers items its own way.
+    categories: HashMap<ItemId, Category>,
+    /// Bumped by every change, so derived results can tell they're stale.
+    generation: u64,
+}
+
+impl MarketStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of listings held.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// A counter that changes whenever the store does.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Records (or moves) a station's system and location, which is what
+    /// places it in the grid-cell index.
+    pub fn add_station(&mut self, station_id: StationId, system: &str, x: f64, y: f64, z: f64) {
+        self.place(station_id, system, [x, y, z], stellar_grid_key(x, y, z));
+    }
+
+    /// add_station for many stations at once, working out their grid cells
+    /// in one batch.
+    pub fn add_stations<S: AsRef<str>>(&mut self, stations: &[(StationId, S, [f64; 3])]) {
+        let positions: Vec<[f64; 3]> = stations.iter().map(|(_, _, pos)| *pos).collect();
+        let keys = stellar_grid_keys(&positions);
+        for ((station_id, system, pos), key) in stations.iter().zip(keys) {
+            self.place(*station_id, system.as_ref(), *pos, key);
+        }
+    }
+
+    fn place(&mut self, station_id: StationId, system: &str, pos: [f64; 3], key: u64) {
+        self.generation += 1;
+        let system_id = self.systems.intern(system);
+        if let Some(old) = self.station_systems.insert(station_id, system_id) {
+            if let Some(stations) = self.system_stations.get_mut(&old) {
+                stations.remove(&station_id);
+            }
+        }
+        self.system_stations
+            .entry(system_id)
+            .or_default()
+            .insert(station_id);
+        if let Some(old) = self.positions.insert(station_id, pos) {
+            let old_key = stellar_grid_key(old[0], old[1], old[2]);
+            if let Some(cell) = self.by_cell.get_mut(&old_key) {
+                cell.remove(&station_id);
+                if cell.is_empty() {
+                    self.by_cell.remove(&old_key);
+                }
+            }
+        }
+        self.by_cell.entry(key).or_default().insert(station_id);
+    }
+
+    /// Returns the coordinates registered for a station.
+    pub fn station_position(&self, station_id: StationId) -> Option<[f64; 3]> {
+        self.positions.get(&station_id).copied()
+    }
+
+    /// Every registered station with its coordinates, in no particular order.
+    pub fn station_positions(&self) -> impl Iterator<Item = (StationId, [f64; 3])> + '_ {
+        self.positions
+            .iter()
+            .map(|(station_id, pos)| (*station_id, *pos))
+    }
+
+    /// The stations within `ly` of a point with their distances, nearest
+    /// first, found through the stellar grid cells the sphere touches, or
+    /// by checking every station once the sphere has more cells than there
+    /// are stations.
+    pub fn stations_within(&self, origin: [f64; 3], ly: f64) -> Vec<(StationId, f64)> {
+        let within = |&station_id: &StationId| {
+            let pos = self.positions[&station_id];
+            let distance = (0..3)
+                .map(|n| (pos[n] - origin[n]).powi(2))
+                .sum::<f64>()
+                .sqrt();
+            (distance <= ly).then_some((station_id, distance))
+        };
+        // the cells of the sphere's bounding box, as a float since a wide
+        // enough sphere's would overflow
+        let cells: f64 = origin
+            .iter()
+            .map(|&c| ((c + ly) / 32.).floor() - ((c - ly) / 32.).floor() + 1.)
+            .product();
+        let mut stations: Vec<(StationId, f64)> = if cells < self.positions.len() as f64 {
+            iter_stellar_grid_keys_in_sphere(origin, ly)
+                .filter_map(|key| self.by_cell.get(&key))
+                .flatten()
+                .filter_map(within)
+                .collect()
+        } else {
+            self.positions.keys().filter_map(within).collect()
+        };
+        stations.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        stations
+    }
+
+    /// The stations located in a given sector, by sector id.
+    pub fn stations_in_sector(&self, sector_id: u64) -> Vec<StationId> {
+        let mut stations: Vec<StationId> = self
+            .station_positions()
+            .filter(|(_, pos)| sector_for(pos[0], pos[1], pos[2]).id == sector_id)
+            .map(|(station_id, _)| station_id)
+            .collect();
+        stations.sort_unstable();
+        stations
+    }
+
+    /// Returns the name of the system a station is in.
+    pub fn station_system(&self, station_id: StationId) -> Option<&str> {
+        let system_id = self.station_systems.get(&station_id)?;
+        self.systems.resolve(*system_id)
+    }
+
+    /// Returns the ids of the stations in a system.
+    pub fn stations_in_system(&self, system: &str) -> Vec<StationId> {
+        self.systems
+            .get(system)
+            .and_then(|system_id| self.system_stations.get(&system_id))
+            .map(|stations| stations.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Adds a listing, replacing any existing listing for the same station
+    /// and item.
+    pub fn insert(&mut self, item: StationItem) {
+        let (station_id, item_id) = (item.station_id, item.item_id);
+        self.generation += 1;
+        self.by_station
+            .entry(station_id)
+            .or_default()
+            .insert(item_id);
+        self.by_item.entry(item_id).or_default().insert(station_id);
+        let key = (station_id, item_id);
+        match Listing::pack(&item) {
+            Some(listing) => {
+                self.records.insert(key, listing);
+                self.spilled.remove(&key);
+            }
+            None => {
+                self.records.insert(key, Listing::SPILLED);
+                self.spilled.insert(key, item);
+            }
+        }
+    }
+
+    /// Replaces a station's entire market with the contents of a snapshot.
+    pub fn insert_snapshot(&mut self, snapshot: &MarketSnapshot) {
+        self.remove_market(snapshot.station_id);
+        for item in snapshot.items() {
+            self.insert(item.clone());
+        }
+    }
+
+    /// Removes a single listing, returning it if it was present.
+    pub fn remove(&mut self, station_id: StationId, item_id: ItemId) -> Option<StationItem> {
+        let key = (station_id, item_id);
+        let listing = self.records.remove(&key)?;
+        let removed = if listing.is_spilled() {
+            self.spilled.remove(&key).unwrap()
+        } else {
+            listing.unpack(station_id, item_id)
+        };
+        self.generation += 1;
+        if let Some(items) = self.by_station.get_mut(&station_id) {
+            items.remove(&item_id);
+            if items.is_empty() {
+                self.by_station.remove(&station_id);
+            }
+        }
+        if let Some(stations) = self.by_item.get_mut(&item_id) {
+            stations.remove(&station_id);
+            if stations.is_empty() {
+                self.by_item.remove(&item_id);
+            }
+        }
+        Some(removed)
+    }
+
+    /// Removes all of a station's listings, returning how many there were.
+    pub fn remove_market(&mut self, station_id: StationId) -> usize {
+        let items = self
+            .by_station
+            .get(&station_id)
+            .cloned()
+            .unwrap_or_default();
+        for item_id in items.iter() {
+            self.remove(station_id, *item_id);
+        }
+        items.len()
+    }
+
+    fn unpack(&self, station_id: StationId, item_id: ItemId, listing: &Listing) -> StationItem {
+        if listing.is_spilled() {
+            self.spilled[&(station_id, item_id)].clone()
+        } else {
+            listing.unpack(station_id, item_id)
+        }
+    }
+
+    /// Returns a single listing.
+    pub fn get(&self, station_id: StationId, item_id: ItemId) -> Option<StationItem> {
+        let listing = self.records.get(&(station_id, item_id))?;
+        Some(self.unpack(station_id, item_id, listing))
+    }
+
+    /// Every listing, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = StationItem> + '_ {
+        self.records
+            .iter()
+            .map(|(&(station_id, item_id), listing)| self.unpack(station_id, item_id, listing))
+    }
+
+    /// Returns all the listings of a station, ordered by item.
+    pub fn station_items(&self, station_id: StationId) -> Vec<StationItem> {
+        self.by_station
+            .get(&station_id)
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item_id| self.get(station_id, *item_id))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns all the listings of an item, ordered by station.
+    pub fn item_listings(&self, item_id: ItemId) -> Vec<StationItem> {
+        self.by_item
+            .get(&item_id)
+            .map(|stations| {
+                stations
+                    .iter()
+                    .filter_map(|station_id| self.get(*station_id, item_id))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Files an item id under a category, for item ids that aren't the
+    /// commodity table's.
+    pub fn set_category(&mut self, item_id: ItemId, category: Category) {
+        self.categories.insert(item_id, category);
+    }
+
+    /// The category of an item id: as set with `set_category`, or else the
+    /// commodity table's.
+    pub fn category(&self, item_id: ItemId) -> Option<Category> {
+        self.categories
+            .get(&item_id)
+            .copied()
+            .or_else(|| item_category(item_id))
+    }
+
+    /// Returns the listings of a station in one commodity category, ordered
+    /// by item. Items with no category are left out.
+    pub fn station_items_in(&self, station_id: StationId, category: Category) -> Vec<StationItem> {
+        self.station_items(station_id)
+            .into_iter()
+            .filter(|item| self.category(item.item_id) == Some(category))
+            .collect()
+    }
+
+    /// Returns all the listings of the items in a commodity category,
+    /// ordered by item then station.
+    pub fn category_listings(&self, category: Category) -> Vec<StationItem> {
+        let mut item_ids: Vec<ItemId> = self
+            .by_item
+            .keys()
+            .copied()
+            .filter(|&item_id| self.category(item_id) == Some(category))
+            .collect();
+        item_ids.sort_unstable();
+        item_ids
+            .into_iter()
+            .flat_map(|item_id| self.item_listings(item_id))
+            .collect()
+    }
+
+    /// Listings of stations you can buy an item from, cheapest first.
+    pub fn sellers_of(&self, item_id: ItemId) -> Vec<StationItem> {
+        let mut sellers: Vec<StationItem> = self
+            .item_listings(item_id)
+            .into_iter()
+            .filter(|item| item.supply_price > 0)
+            .collect();
+        sellers.sort_by_key(|item| (item.supply_price, item.station_id));
+        sellers
+    }
+
+    /// Listings of stations you can sell an item to, best paying first.
+    pub fn buyers_of(&self, item_id: ItemId) -> Vec<StationItem> {
+        let mut buyers: Vec<StationItem> = self
+            .item_listings(item_id)
+            .into_iter()
+            .filter(|item| item.demand_price > 0)
+            .collect();
+        buyers.sort_by_key(|item| (-(item.demand_price as i64), item.station_id));
+        buyers
+    }
+
+    /// The stations located in a given stellar grid cell.
+    pub fn stations_in(&self, grid_key: u64) -> Vec<StationId> {
+        self.by_cell
+            .get(&grid_key)
+            .map(|stations| stations.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(station_id: u32, item_id: u32, demand_price: i32, supply_price: i32) -> StationItem {
+        StationItem {
+            station_id: StationId(station_id),
+            item_id: ItemId(item_id),
+            demand_price,
+            supply_price,
+            ..Default::default()
+        }
+    }
+
+    fn sample_store() -> MarketStore {
+        let mut store = MarketStore::new();
+        store.add_station(StationId(1), "Sol", 0., 0., 0.);
+        store.add_station(StationId(2), "Sol", 10., 10., 10.);
+        store.add_station(StationId(3), "Alpha Centauri", 100., 0., 0.);
+        store.insert(item(1, 100, 0, 500));
+        store.insert(item(2, 100, 700, 450));
+        store.insert(item(3, 100, 900, 0));
+        store.insert(item(1, 200, 50, 40));
+        store
+    }
+
+    #[test]
+    fn test_store_insert_and_replace() {
+        let mut store = sample_store();
+        assert_eq!(store.len(), 4);
+        store.insert(item(1, 100, 0, 480));
+        assert_eq!(store.len(), 4);
+        assert_eq!(store.iter().count(), 4);
+        assert_eq!(
+            store.get(StationId(1), ItemId(100)).unwrap().supply_price,
+            480
+        );
+    }
+
+    #[test]
+    fn test_store_sellers_and_buyers() {
+        let store = sample_store();
+        let sellers: Vec<StationId> = store
+            .sellers_of(ItemId(100))
+            .iter()
+            .map(|i| i.station_id)
+            .collect();
+        assert_eq!(sellers, vec![StationId(2), StationId(1)]);
+        let buyers: Vec<StationId> = store
+            .buyers_of(ItemId(100))
+            .iter()
+            .map(|i| i.station_id)
+            .collect();
+        assert_eq!(buyers, vec![StationId(3), StationId(2)]);
+        assert!(store.sellers_of(ItemId(999)).is_empty());
+    }
+
+    #[test]
+    fn test_store_stations_in() {
+        let mut store = sample_store();
+        let origin = stellar_grid_key(0., 0., 0.);
+        assert_eq!(store.stations_in(origin), vec![StationId(1), StationId(2)]);
+        assert_eq!(
+            store.stations_in(stellar_grid_key(100., 0., 0.)),
+            vec![StationId(3)]
+        );
+        let distance = 300f64.sqrt();
+        assert_eq!(
+            store.stations_within([0., 0., 0.], 20.),
+            vec![(StationId(1), 0.), (StationId(2), distance)]
+        );
+        assert_eq!(
+            store.stations_within([0., 0., 0.], distance - 0.001),
+            vec![(StationId(1), 0.)]
+        );
+        assert_eq!(store.stations_within([100., 0., 0.], 90.).len(), 1);
+
+        // moving a station moves it between cells
+        store.add_station(StationId(2), "Alpha Centauri", 100., 1., 1.);
+        assert_eq!(store.stations_in(origin), vec![StationId(1)]);
+        assert_eq!(
+            store.stations_in(stellar_grid_key(100., 0., 0.)),
+            vec![StationId(2), StationId(3)]
+        );
+
+        // a batch places stations the same way
+        store.add_stations(&[
+            (StationId(2), "Sol", [10., 10., 10.]),
+            (StationId(4), "Lave", [100., 2., 2.]),
+        ]);
+        assert_eq!(store.stations_in(origin), vec![StationId(1), StationId(2)]);
+        assert_eq!(
+            store.stations_in(stellar_grid_key(100., 0., 0.)),
+            vec![StationId(3), StationId(4)]
+        );
+        assert_eq!(store.station_system(StationId(4)), Some("Lave"));
+    }
+
+    #[test]
+    fn test_store_station_systems() {
+        let store = sample_store();
+        assert_eq!(store.station_system(StationId(1)), Some("Sol"));
+        assert_eq!(store.station_system(StationId(3)), Some("Alpha Centauri"));
+        assert_eq!(store.station_system(StationId(4)), None);
+        assert_eq!(
+            store.stations_in_system("Sol"),
+            vec![StationId(1), StationId(2)]
+        );
+        assert!(store.stations_in_system("Lave").is_empty());
+        assert_eq!(store.station_position(StationId(3)), Some([100., 0., 0.]));
+        assert_eq!(
+            store.stations_in_sector(sector_for(0., 0., 0.).id),
+            vec![StationId(1), StationId(2), StationId(3)]
+        );
+        assert!(store
+            .stations_in_sector(sector_for(5000., 0., 0.).id)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_store_snapshot_replaces_market() {
+        let mut store = sample_store();
+        let snapshot = MarketSnapshot::new(StationId(1), 0, vec![item(0, 300, 10, 9)]);
+        store.insert_snapshot(&snapshot);
+        let items: Vec<u32> = store
+            .station_items(StationId(1))
+            .iter()
+            .map(|i| i.item_id.0)
+            .collect();
+        assert_eq!(items, vec![300]);
+        assert!(store.get(StationId(1), ItemId(100)).is_none());
+        assert_eq!(store.item_listings(ItemId(200)).len(), 0);
+        assert_eq!(store.len(), 3);
+    }
+
+    #[test]
+    fn test_store_generation() {
+        let mut store = sample_store();
+        let generation = store.generation();
+        store.get(StationId(1), ItemId(100));
+        store.sellers_of(ItemId(100));
+        assert_eq!(store.generation(), generation);
+        store.insert(item(1, 100, 0, 480));
+        assert!(store.generation() > generation);
+        let generation = store.generation();
+        store.remove(StationId(9), ItemId(9));
+        assert_eq!(store.generation(), generation);
+        store.remove_market(StationId(1));
+        assert!(store.generation() > generation);
+    }
+
+    #[test]
+    fn test_store_categories() {
+        let id = |name| crate::commodities::canonical_commodity(name).unwrap().id.0;
+        let (gold, silver, tea) = (id("Gold"), id("Silver"), id("Tea"));
+        let mut store = sample_store();
+        store.insert(item(1, silver, 0, 4000));
+        store.insert(item(2, gold, 9000, 0));
+        store.insert(item(1, gold, 0, 8000));
+        store.insert(item(1, tea, 0, 1000));
+
+        let listings: Vec<(u32, u32)> = store
+            .station_items_in(StationId(1), Category::Metals)
+            .iter()
+            .map(|i| (i.station_id.0, i.item_id.0))
+            .collect();
+        assert_eq!(listings, vec![(1, gold), (1, silver)]);
+        let listings: Vec<(u32, u32)> = store
+            .category_listings(Category::Metals)
+            .iter()
+            .map(|i| (i.station_id.0, i.item_id.0))
+            .collect();
+        assert_eq!(listings, vec![(1, gold), (2, gold), (1, silver)]);
+        assert_eq!(store.category_listings(Category::Foods).len(), 1);
+        assert!(store.category_listings(Category::Weapons).is_empty());
+    }
+
+    #[test]
+    fn test_store_packs_listings() {
+        assert_eq!(std::mem::size_of::<Listing>(), 32);
+        let mut store = MarketStore::new();
+        let packed = StationItem {
+            demand_units: -1,
+            demand_level: -1,
+            supply_units: 4_000_000_000,
+            supply_level: 3,
+            modified: 1714564800,
+            ..item(1, 100, 900, 800)
+        };
+        // "0?", and more units than a u32 holds
+        let odd_level = StationItem {
+            demand_level: -1,
+            ..item(1, 200, 10, 0)
+        };
+        let huge = StationItem {
+            supply_units: 1 << 40,
+            supply_level: 2,
+            ..item(2, 100, 0, 5)
+        };
+        for listing in [&packed, &odd_level, &huge] {
+            store.insert(listing.clone());
+            assert_eq!(
+                store.get(listing.station_id, listing.item_id).as_ref(),
+                Some(listing)
+            );
+        }
+        assert_eq!(store.spilled.len(), 2);
+        assert_eq!(
+            store.sellers_of(ItemId(100)),
+            [huge.clone(), packed.clone()]
+        );
+        let mut all: Vec<StationItem> = store.iter().collect();
+        all.sort_by_key(|item| (item.station_id, item.item_id));
+        assert_eq!(all, [packed.clone(), odd_level.clone(), huge.clone()]);
+
+        // replacing a spilled listing with one that packs drops the spilled copy
+        store.insert(item(1, 200, 10, 0));
+        assert_eq!(store.spilled.len(), 1);
+        assert_eq!(store.remove(StationId(2), ItemId(100)), Some(huge));
+        assert!(store.spilled.is_empty());
+    }
+
+    #[test]
+    fn test_store_remove() {
+        let mut store = sample_store();
+        assert!(store.remove(StationId(1), ItemId(100)).is_some());
+        assert!(store.remove(StationId(1), ItemId(100)).is_none());
+        assert_eq!(store.remove_market(StationId(1)), 1);
+        assert!(store.station_items(StationId(1)).is_empty());
+        assert_eq!(store.len(), 2);
+    }
+}
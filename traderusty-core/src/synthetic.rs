@@ -0,0 +1,241 @@
+//! Stable IDs for systems and stations the game gave none: player-named
+//! carriers seen only by name, stations added by hand, systems from sources
+//! without a SystemAddress.
+//!
+//! Synthetic IDs come from ranges real ones don't reach: station IDs count
+//! up from STATION_BASE, above every MarketID the game hands out (carriers'
+//! are around 3.7 billion), and system IDs have the top bit set, which a
+//! SystemAddress never does. Names are keyed in canonical form, so the same
+//! carrier spelled differently gets the same ID. The allocations are kept
+//! in the database's SyntheticSystem and SyntheticStation tables, so an ID
+//! survives from one import to the next.
+
+use std::collections::HashMap;
+
+#[cfg(feature = "fs")]
+use rusqlite::{types::Value, Connection};
+#[cfg(feature = "fs")]
+use tracing::info;
+
+#[cfg(feature = "fs")]
+use crate::db::{in_transaction, Batcher, DbError, Row, DEFAULT_BATCH_SIZE};
+use crate::ids::{StationId, SystemId};
+use crate::names::canonical_name;
+
+/// The first synthetic station ID.
+pub const STATION_BASE: u32 = 0xF000_0000;
+
+/// The first synthetic system ID.
+pub const SYSTEM_BASE: u64 = 1 << 63;
+
+pub fn is_synthetic_station(id: StationId) -> bool {
+    id.0 >= STATION_BASE
+}
+
+pub fn is_synthetic_system(id: SystemId) -> bool {
+    id.0 >= SYSTEM_BASE
+}
+
+/// A SyntheticSystem row.
+#[cfg(feature = "fs")]
+struct SystemRow<'a>(&'a str, SystemId);
+
+#[cfg(feature = "fs")]
+impl Row for SystemRow<'_> {
+    const TABLE: &'static str = "SyntheticSystem";
+    const COLUMNS: &'static [&'static str] = &["name", "id"];
+    const ON_CONFLICT: &'static str = "IGNORE";
+
+    fn bind(&self, params: &mut Vec<Value>) {
+        // the i64 with the same bits, as SystemId's ToSql writes it
+        params.extend([
+            Value::Text(self.0.to_string()),
+            Value::Integer(self.1 .0 as i64),
+        ]);
+    }
+}
+
+/// A SyntheticStation row.
+#[cfg(feature = "fs")]
+struct StationRow<'a>(&'a str, &'a str, StationId);
+
+#[cfg(feature = "fs")]
+impl Row for StationRow<'_> {
+    const TABLE: &'static str = "SyntheticStation";
+    const COLUMNS: &'static [&'static str] = &["system", "station", "id"];
+    const ON_CONFLICT: &'static str = "IGNORE";
+
+    fn bind(&self, params: &mut Vec<Value>) {
+        params.extend([
+            Value::Text(self.0.to_string()),
+            Value::Text(self.1.to_string()),
+            Value::Integer(self.2 .0 as i64),
+        ]);
+    }
+}
+
+/// Hands out synthetic IDs, the same one every time for the same name.
+#[derive(Clone, Debug)]
+pub struct SyntheticIds {
+    systems: HashMap<String, SystemId>,
+    stations: HashMap<(String, String), StationId>,
+    next_system: u64,
+    next_station: u32,
+}
+
+impl Default for SyntheticIds {
+    fn default() -> Self {
+        Self {
+            systems: HashMap::new(),
+            stations: HashMap::new(),
+            next_system: SYSTEM_BASE,
+            next_station: STATION_BASE,
+        }
+    }
+}
+
+impl SyntheticIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Picks up from allocations made earlier; new IDs follow the highest.
+    #[cfg(feature = "fs")]
+    fn from_maps(
+        systems: HashMap<String, SystemId>,
+        stations: HashMap<(String, String), StationId>,
+    ) -> Self {
+        let next_system = systems.values().map(|id| id.0 + 1).max();
+        let next_station = stations.values().map(|id| id.0.saturating_add(1)).max();
+        Self {
+            next_system: next_system.unwrap_or(SYSTEM_BASE).max(SYSTEM_BASE),
+            next_station: next_station.unwrap_or(STATION_BASE).max(STATION_BASE),
+            systems,
+            stations,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.systems.len() + self.stations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty() && self.stations.is_empty()
+    }
+
+    /// The ID of a system, allocating the next one if it has none yet.
+    pub fn system(&mut self, name: &str) -> SystemId {
+        *self.systems.entry(canonical_name(name)).or_insert_with(|| {
+            self.next_system += 1;
+            SystemId(self.next_system - 1)
+        })
+    }
+
+    /// The ID of a station, allocating the next one if it has none yet.
+    ///
+    /// # Panics
+    ///
+    /// If all 268 million synthetic station IDs are taken.
+    pub fn station(&mut self, system: &str, station: &str) -> StationId {
+        let key = (canonical_name(system), canonical_name(station));
+        *self.stations.entry(key).or_insert_with(|| {
+            let id = self.next_station;
+            self.next_station = id.checked_add(1).expect("synthetic station IDs exhausted");
+            StationId(id)
+        })
+    }
+
+    /// The ID already allocated to a system, if any.
+    pub fn get_system(&self, name: &str) -> Option<SystemId> {
+        self.systems.get(&canonical_name(name)).copied()
+    }
+
+    /// The ID already allocated to a station, if any.
+    pub fn get_station(&self, system: &str, station: &str) -> Option<StationId> {
+        let key = (canonical_name(system), canonical_name(station));
+        self.stations.get(&key).copied()
+    }
+
+    /// Reads the allocations saved in a database migrated to at least
+    /// version 3.
+    #[cfg(feature = "fs")]
+    pub fn load(conn: &Connection) -> Result<Self, DbError> {
+        let mut stmt = conn.prepare("SELECT name, id FROM SyntheticSystem")?;
+        let systems = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        let mut stmt = conn.prepare("SELECT system, station, id FROM SyntheticStation")?;
+        let stations = stmt
+            .query_map([], |row| Ok(((row.get(0)?, row.get(1)?), row.get(2)?)))?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        let ids = Self::from_maps(systems, stations);
+        info!(ids = ids.len(), "loaded synthetic ids");
+        Ok(ids)
+    }
+
+    /// Writes every allocation to the database in one transaction; ones
+    /// already there are left alone. Returns how many were new.
+    #[cfg(feature = "fs")]
+    pub fn save(&self, conn: &mut Connection) -> Result<usize, DbError> {
+        let added = in_transaction(conn, |tx| {
+            let mut systems = Batcher::new(tx, DEFAULT_BATCH_SIZE);
+            for (name, id) in &self.systems {
+                systems.push(&SystemRow(name, *id))?;
+            }
+            let mut stations = Batcher::new(tx, DEFAULT_BATCH_SIZE);
+            for ((system, station), id) in &self.stations {
+                stations.push(&StationRow(system, station, *id))?;
+            }
+            Ok(systems.finish()? + stations.finish()?)
+        })?;
+        info!(added, "saved synthetic ids");
+        Ok(added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_ids_are_stable() {
+        let mut ids = SyntheticIds::new();
+        let carrier = ids.station("Sol", "Dora's Explorer");
+        let other = ids.station("Sol", "Abraham Lincoln");
+        assert_ne!(carrier, other);
+        assert!(is_synthetic_station(carrier) && is_synthetic_station(other));
+        assert!(!is_synthetic_station(StationId(3_700_000_000)));
+        assert_eq!(ids.station(" sol ", "DORA'S  EXPLORER"), carrier);
+        assert_eq!(ids.get_station("SOL", "dora's explorer"), Some(carrier));
+        assert_eq!(ids.get_station("Sol", "Galileo"), None);
+
+        let system = ids.system("Hand Added");
+        assert!(is_synthetic_system(system));
+        assert!(!is_synthetic_system(SystemId(10477373803)));
+        assert_eq!(ids.system("hand added"), system);
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn test_synthetic_ids_survive_reload() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::migrate::migrate(&mut conn).unwrap();
+        let mut ids = SyntheticIds::new();
+        let carrier = ids.station("Sol", "Dora's Explorer");
+        let system = ids.system("Hand Added");
+        assert_eq!(ids.save(&mut conn).unwrap(), 2);
+        assert_eq!(ids.save(&mut conn).unwrap(), 0);
+
+        let mut reloaded = SyntheticIds::load(&conn).unwrap();
+        assert_eq!(
+            reloaded.get_station("Sol", "Dora's Explorer"),
+            Some(carrier)
+        );
+        assert_eq!(reloaded.get_system("Hand Added"), Some(system));
+        // new allocations carry on after the saved ones
+        let next = reloaded.station("Sol", "Abraham Lincoln");
+        assert_eq!(next, StationId(carrier.0 + 1));
+        assert_eq!(reloaded.save(&mut conn).unwrap(), 1);
+    }
+}
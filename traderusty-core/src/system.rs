@@ -0,0 +1,26 @@
+//! Star systems as the online databases describe them: a name, the game's
+//! 64-bit system address where known, and galactic coordinates in ly.
+
+use crate::grid::distance;
+use crate::ids::SystemId;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct System {
+    pub name: String,
+    /// The game's SystemAddress.
+    pub id64: Option<SystemId>,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl System {
+    pub fn position(&self) -> [f64; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    /// Straight-line distance to another system in ly.
+    pub fn distance_to(&self, other: &System) -> f64 {
+        distance(self.position(), other.position())
+    }
+}
@@ -0,0 +1,366 @@
+//! Reading TradeDangerous' System.csv and Station.csv, the tables of system
+//! coordinates and station distances that its data directory ships and
+//! contributors edit by hand or in spreadsheets.
+//!
+//! The files are TD's CSV dialect: a header row naming the columns
+//! ("unq:name", "pos_x", "name@System.system_id", "ls_from_star"), then one
+//! row per system or station, with text fields optionally quoted in ' or "
+//! and the quote doubled inside them ('Barnard''s Star'). Columns are found
+//! by name, so extra or reordered columns don't matter.
+//!
+//! Coordinates and ls-from-star are read with `parse_decimal`, following
+//! ParseOptions' `decimal`: with ',' "12,5" is 12.5, and with '.' it's an
+//! error rather than 125 or 12. A spreadsheet writing decimal commas
+//! separates fields with ';', so a header with a ';' in it makes that the
+//! separator; otherwise it's ','. A decimal-comma file separated by ','
+//! has to quote its numbers.
+//!
+//! Strictly the first malformed row is an error. Leniently malformed rows
+//! are skipped and counted, until there are more than `max_errors`.
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::options::ParseOptions;
+use crate::rusty::parse_decimal;
+use crate::span::Span;
+use crate::system::System;
+
+/// A Station.csv row: the station, the system it's in, and its distance
+/// from the arrival star in ls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StationRow {
+    pub system: String,
+    pub name: String,
+    pub ls_from_star: f64,
+    /// 1-based line number the row came from.
+    pub line: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.message)
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+/// Reads System.csv text into Systems, returning them with the number of
+/// rows skipped as malformed.
+pub fn read_systems(text: &str, options: &ParseOptions) -> Result<(Vec<System>, usize), CsvError> {
+    read_rows(text, options, &["name", "pos_x", "pos_y", "pos_z"], |row| {
+        Ok(System {
+            name: row.text(0),
+            id64: None,
+            x: row.decimal(1)?,
+            y: row.decimal(2)?,
+            z: row.decimal(3)?,
+        })
+    })
+}
+
+/// Reads Station.csv text, returning the rows with the number skipped as
+/// malformed.
+pub fn read_stations(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<StationRow>, usize), CsvError> {
+    read_rows(
+        text,
+        options,
+        &["name@System.system_id", "name", "ls_from_star"],
+        |row| {
+            Ok(StationRow {
+                system: row.text(0),
+                name: row.text(1),
+                ls_from_star: row.decimal(2)?,
+                line: row.line,
+            })
+        },
+    )
+}
+
+/// One data row, with the fields of the wanted columns in the order they
+/// were asked for.
+struct Row<'a> {
+    line: usize,
+    line_offset: u64,
+    text: &'a str,
+    /// Each field's byte offset in the line and its unquoted text.
+    fields: Vec<(usize, Cow<'a, str>)>,
+    options: &'a ParseOptions,
+}
+
+impl Row<'_> {
+    fn error_at(&self, at: usize, message: String) -> CsvError {
+        CsvError {
+            span: Span::in_line(self.text, self.line, self.line_offset, at),
+            message,
+        }
+    }
+
+    fn text(&self, idx: usize) -> String {
+        self.fields[idx].1.trim().to_string()
+    }
+
+    fn decimal(&self, idx: usize) -> Result<f64, CsvError> {
+        let (at, field) = &self.fields[idx];
+        parse_decimal(field.trim(), self.options)
+            .map_err(|e| self.error_at(*at, format!("{}: {}", e, field)))
+    }
+}
+
+fn read_rows<T>(
+    text: &str,
+    options: &ParseOptions,
+    columns: &[&str],
+    mut parse: impl FnMut(&Row<'_>) -> Result<T, CsvError>,
+) -> Result<(Vec<T>, usize), CsvError> {
+    let mut lines = text
+        .split_inclusive('\n')
+        .enumerate()
+        .scan(0u64, |offset, (idx, line)| {
+            let line_offset = *offset;
+            *offset += line.len() as u64;
+            Some((idx + 1, line_offset, line.trim_end_matches(['\r', '\n'])))
+        })
+        .filter(|(_, _, line)| !line.trim().is_empty() && !line.starts_with(options.comment_char));
+
+    let Some((header_line, header_offset, header)) = lines.next() else {
+        return Ok((Vec::new(), 0));
+    };
+    let separator = if header.contains(';') { ';' } else { ',' };
+    let names: Vec<String> = split_fields(header, separator)
+        .map_err(|at| CsvError {
+            span: Span::in_line(header, header_line, header_offset, at),
+            message: "unterminated quote".to_string(),
+        })?
+        .into_iter()
+        .map(|(_, name)| name.trim().trim_start_matches("unq:").to_string())
+        .collect();
+    let mut wanted = Vec::with_capacity(columns.len());
+    for column in columns {
+        let idx = names
+            .iter()
+            .position(|name| name == column)
+            .ok_or_else(|| CsvError {
+                span: Span::in_line(header, header_line, header_offset, 0),
+                message: format!("no {} column", column),
+            })?;
+        wanted.push(idx);
+    }
+
+    let (mut rows, mut errors) = (Vec::new(), 0);
+    for (line, line_offset, text) in lines {
+        let result = split_fields(text, separator)
+            .map_err(|at| (at, "unterminated quote".to_string()))
+            .and_then(|mut fields| {
+                if fields.len() != names.len() {
+                    let message =
+                        format!("expected {} fields, found {}", names.len(), fields.len());
+                    return Err((0, message));
+                }
+                let fields = wanted
+                    .iter()
+                    .map(|&idx| std::mem::take(&mut fields[idx]))
+                    .collect();
+                Ok(fields)
+            })
+            .map_err(|(at, message)| CsvError {
+                span: Span::in_line(text, line, line_offset, at),
+                message,
+            })
+            .and_then(|fields| {
+                parse(&Row {
+                    line,
+                    line_offset,
+                    text,
+                    fields,
+                    options,
+                })
+            });
+        match result {
+            Ok(row) => rows.push(row),
+            Err(e) => {
+                errors += 1;
+                if !options.lenient() || options.too_many_errors(errors) {
+                    return Err(e);
+                }
+            }
+        }
+    }
+    Ok((rows, errors))
+}
+
+/// Splits a line into its fields, each with its byte offset, taking off
+/// the quotes around a quoted one. A quote left open is an error at the
+/// offset it opened.
+fn split_fields(line: &str, separator: char) -> Result<Vec<(usize, Cow<'_, str>)>, usize> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    loop {
+        let rest = &line[start..];
+        let padding = rest.len() - rest.trim_start().len();
+        let quote = rest[padding..]
+            .chars()
+            .next()
+            .filter(|c| matches!(c, '\'' | '"'));
+        let (field, end) = match quote {
+            Some(quote) => {
+                let open = start + padding;
+                let mut value = String::new();
+                let mut chars = line[open + 1..].char_indices();
+                let close = loop {
+                    match chars.next() {
+                        Some((idx, c)) if c == quote => {
+                            if line[open + 1 + idx + 1..].starts_with(quote) {
+                                value.push(quote);
+                                chars.next();
+                            } else {
+                                break open + 1 + idx + 1;
+                            }
+                        }
+                        Some((_, c)) => value.push(c),
+                        None => return Err(open),
+                    }
+                };
+                let end = line[close..]
+                    .find(separator)
+                    .map_or(line.len(), |idx| close + idx);
+                (Cow::Owned(value), end)
+            }
+            None => {
+                let end = rest.find(separator).map_or(line.len(), |idx| start + idx);
+                (Cow::Borrowed(&line[start..end]), end)
+            }
+        };
+        fields.push((start + padding, field));
+        if end == line.len() {
+            return Ok(fields);
+        }
+        start = end + separator.len_utf8();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{DecimalSeparator, Strictness};
+
+    fn comma() -> ParseOptions {
+        ParseOptions {
+            decimal: DecimalSeparator::Comma,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_read_systems() {
+        let text = "unq:name,pos_x,pos_y,pos_z,added@Added.added_id,modified\n\
+                    'Sol',0,0,0,1,'2024-05-01 12:00:00'\n\
+                    # a comment\n\
+                    'Barnard''s Star',-3.03125,1.375,4.9375,1,'2024-05-01 12:00:00'\n";
+        let (systems, skipped) = read_systems(text, &ParseOptions::default()).unwrap();
+        assert_eq!(skipped, 0);
+        assert_eq!(systems.len(), 2);
+        assert_eq!(systems[1].name, "Barnard's Star");
+        assert_eq!(systems[1].position(), [-3.03125, 1.375, 4.9375]);
+    }
+
+    #[test]
+    fn test_read_systems_decimal_comma() {
+        // as a spreadsheet saves it with a decimal comma
+        let text = "unq:name;pos_x;pos_y;pos_z\r\nLave;75,75;48,75;70,75\r\n";
+        let (systems, _) = read_systems(text, &comma()).unwrap();
+        assert_eq!(systems[0].position(), [75.75, 48.75, 70.75]);
+        // quoted, the usual separator works too
+        let text = "unq:name,pos_x,pos_y,pos_z\nLave,'75,75','48,75','70,75'\n";
+        let (systems, _) = read_systems(text, &comma()).unwrap();
+        assert_eq!(systems[0].position(), [75.75, 48.75, 70.75]);
+
+        // the other convention is an error, not a different number
+        let err = read_systems(text, &ParseOptions::default()).unwrap_err();
+        assert_eq!((err.span.line, err.span.column), (2, 6));
+        let text = "unq:name,pos_x,pos_y,pos_z\nLave,75.75,48.75,70.75\n";
+        assert!(read_systems(text, &comma()).is_err());
+    }
+
+    #[test]
+    fn test_read_stations() {
+        let text = "unq:name@System.system_id,unq:name,ls_from_star,blackmarket\n\
+                    'Sol','Abraham Lincoln',496,'N'\n\
+                    'Lave','Lave Station',\"299,5\",'Y'\n";
+        let err = read_stations(text, &ParseOptions::default()).unwrap_err();
+        assert_eq!(err.span.line, 3);
+        assert!(err.message.contains("299,5"), "{}", err);
+
+        let (stations, _) = read_stations(text, &comma()).unwrap();
+        assert_eq!(
+            stations[1],
+            StationRow {
+                system: "Lave".to_string(),
+                name: "Lave Station".to_string(),
+                ls_from_star: 299.5,
+                line: 3,
+            }
+        );
+        assert_eq!(stations[0].ls_from_star, 496.0);
+    }
+
+    #[test]
+    fn test_read_stations_lenient() {
+        let text = "unq:name@System.system_id,unq:name,ls_from_star\n\
+                    'Sol','Abraham Lincoln',496\n\
+                    'Sol','Galileo',lots\n\
+                    'Sol','Daedalus\n\
+                    'Sol','Li Qing Jao',\n\
+                    'Sol','Mars High','1,234.5'\n";
+        let strict = read_stations(text, &ParseOptions::default()).unwrap_err();
+        assert_eq!(strict.span.line, 3);
+        let lenient = ParseOptions {
+            strictness: Strictness::Lenient,
+            ..Default::default()
+        };
+        let (stations, skipped) = read_stations(text, &lenient).unwrap();
+        assert_eq!(skipped, 3);
+        let names: Vec<_> = stations
+            .iter()
+            .map(|s| (s.name.as_str(), s.ls_from_star))
+            .collect();
+        assert_eq!(names, [("Abraham Lincoln", 496.0), ("Mars High", 1234.5)]);
+
+        let limited = ParseOptions {
+            max_errors: Some(2),
+            ..lenient
+        };
+        let err = read_stations(text, &limited).unwrap_err();
+        assert_eq!(err.span.line, 5);
+    }
+
+    #[test]
+    fn test_missing_column() {
+        let err = read_stations("unq:name,ls_from_star\n", &ParseOptions::default()).unwrap_err();
+        assert_eq!(err.message, "no name@System.system_id column");
+        assert_eq!(
+            read_systems("", &ParseOptions::default()),
+            Ok((Vec::new(), 0))
+        );
+    }
+
+    #[test]
+    fn test_split_fields() {
+        let fields = split_fields(r#"a, 'b,c' ,"d""e",,'f''g'"#, ',').unwrap();
+        let texts: Vec<_> = fields.iter().map(|(at, f)| (*at, f.as_ref())).collect();
+        assert_eq!(
+            texts,
+            [(0, "a"), (3, "b,c"), (10, "d\"e"), (17, ""), (18, "f'g")]
+        );
+        assert_eq!(split_fields("a,'bc", ','), Err(2));
+    }
+}
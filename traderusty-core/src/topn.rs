@@ -0,0 +1,166 @@
+//! The best few of many candidates, without sorting them all: "the 25 best
+//! sellers of Gold near here" keeps 25 listings, not every one near here.
+//!
+//! TopN holds at most `limit` items in a binary heap with the worst of them
+//! at the root, so each candidate is checked against that one and only
+//! replaces it if better: O(n log k) for n candidates and k kept, and O(k)
+//! memory. "Better" is a comparison function, smaller first, the same one
+//! a sort would take. Given a total order, the result is what sorting all
+//! the candidates and keeping the first `limit` would give.
+
+use std::cmp::Ordering;
+
+#[derive(Clone, Debug)]
+pub struct TopN<T, F> {
+    limit: usize,
+    /// A max-heap under `cmp`: every item is no better than its children.
+    heap: Vec<T>,
+    cmp: F,
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> TopN<T, F> {
+    /// Keeps the `limit` smallest items under `cmp`.
+    pub fn new(limit: usize, cmp: F) -> Self {
+        Self {
+            limit,
+            heap: Vec::with_capacity(limit.min(1024)),
+            cmp,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// The worst item kept: once `limit` are, what a candidate has to beat.
+    pub fn worst(&self) -> Option<&T> {
+        self.heap.first()
+    }
+
+    /// Whether `item` would be kept if pushed now.
+    pub fn accepts(&self, item: &T) -> bool {
+        self.heap.len() < self.limit
+            || self
+                .worst()
+                .is_some_and(|worst| (self.cmp)(item, worst).is_lt())
+    }
+
+    /// Offers an item, keeping it if it's among the best so far.
+    pub fn push(&mut self, item: T) {
+        if self.heap.len() < self.limit {
+            self.heap.push(item);
+            self.sift_up(self.heap.len() - 1);
+        } else if self.accepts(&item) {
+            self.heap[0] = item;
+            self.sift_down(0);
+        }
+    }
+
+    /// Combines the items of two selectors with the same limit and order,
+    /// as when each thread of a parallel scan keeps its own.
+    pub fn merge(mut self, other: Self) -> Self {
+        self.extend(other.heap);
+        self
+    }
+
+    /// The items kept, best first.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        let (mut items, cmp) = (self.heap, self.cmp);
+        items.sort_by(&cmp);
+        items
+    }
+
+    fn is_worse(&self, a: usize, b: usize) -> bool {
+        (self.cmp)(&self.heap[a], &self.heap[b]).is_gt()
+    }
+
+    fn sift_up(&mut self, mut idx: usize) {
+        while idx > 0 {
+            let parent = (idx - 1) / 2;
+            if !self.is_worse(idx, parent) {
+                break;
+            }
+            self.heap.swap(idx, parent);
+            idx = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut idx: usize) {
+        loop {
+            let mut worst = idx;
+            for child in [2 * idx + 1, 2 * idx + 2] {
+                if child < self.heap.len() && self.is_worse(child, worst) {
+                    worst = child;
+                }
+            }
+            if worst == idx {
+                break;
+            }
+            self.heap.swap(idx, worst);
+            idx = worst;
+        }
+    }
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> Extend<T> for TopN<T, F> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.push(item);
+        }
+    }
+}
+
+/// The `limit` smallest of `items` under `cmp`, smallest first.
+pub fn top_n<T>(
+    items: impl IntoIterator<Item = T>,
+    limit: usize,
+    cmp: impl Fn(&T, &T) -> Ordering,
+) -> Vec<T> {
+    let mut top = TopN::new(limit, cmp);
+    top.extend(items);
+    top.into_sorted_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_n_matches_sort() {
+        // a scrambled 0..1000 with repeats, ordered by value then position
+        let values: Vec<(u32, usize)> = (0..1000)
+            .map(|n: u32| (n.wrapping_mul(2_654_435_761) % 500, n as usize))
+            .collect();
+        let mut sorted = values.clone();
+        sorted.sort();
+        for limit in [0, 1, 2, 25, 499, 1000, 5000] {
+            let top = top_n(values.iter().copied(), limit, |a, b| a.cmp(b));
+            assert_eq!(top, sorted[..limit.min(1000)], "limit {}", limit);
+        }
+        let descending = top_n(0..100, 3, |a, b| b.cmp(a));
+        assert_eq!(descending, [99, 98, 97]);
+    }
+
+    #[test]
+    fn test_top_n_merge() {
+        let by_value = |a: &i32, b: &i32| a.cmp(b);
+        let mut evens = TopN::new(3, by_value);
+        evens.extend((0..20).rev().filter(|n| n % 2 == 0));
+        assert_eq!(evens.len(), 3);
+        assert_eq!(evens.worst(), Some(&4));
+        assert!(evens.accepts(&3));
+        assert!(!evens.accepts(&5));
+        let mut odds = TopN::new(3, by_value);
+        odds.extend([7, 1, 5, 3]);
+        assert_eq!(evens.merge(odds).into_sorted_vec(), [0, 1, 2]);
+        assert!(!TopN::new(0, by_value).accepts(&0));
+    }
+}
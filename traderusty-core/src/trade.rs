@@ -0,0 +1,666 @@
+//! What to carry between stations, and loops of stations worth running.
+//!
+//! Prices come from a MarketStore: a station sells an item if its supply
+//! price is set and buys it if its demand price is set. A positive supply
+//! unit count limits how much can be bought; zero or less means unknown and
+//! isn't treated as a limit.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use rayon::prelude::*;
+use tracing::{debug, info};
+
+use crate::grid::GridIndex;
+use crate::ids::{ItemId, StationId};
+use crate::metrics::{self, Counter};
+use crate::store::MarketStore;
+use crate::topn::TopN;
+
+/// What a trader can carry and afford on a leg.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TradeLimits {
+    /// Cargo hold size in tons (one unit is one ton).
+    pub capacity: u32,
+    pub credits: i64,
+}
+
+/// Buying units of one item at one station to sell at another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trade {
+    pub item_id: ItemId,
+    pub units: u32,
+    /// Price paid per unit at the source.
+    pub buy_price: i32,
+    /// Price received per unit at the destination.
+    pub sell_price: i32,
+}
+
+impl Trade {
+    pub fn unit_profit(&self) -> i64 {
+        self.sell_price as i64 - self.buy_price as i64
+    }
+
+    pub fn profit(&self) -> i64 {
+        self.units as i64 * self.unit_profit()
+    }
+}
+
+/// The cargo carried on one leg.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Load {
+    pub trades: Vec<Trade>,
+}
+
+impl Load {
+    pub fn profit(&self) -> i64 {
+        self.trades.iter().map(Trade::profit).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trades.is_empty()
+    }
+}
+
+/// Items that can be bought at `from` and sold at `to` for a profit, as
+/// trades sized to the supply available, most profitable per unit first.
+fn candidates(store: &MarketStore, from: StationId, to: StationId) -> Vec<Trade> {
+    let mut trades: Vec<Trade> = store
+        .station_items(from)
+        .into_iter()
+        .filter(|item| item.supply_price > 0)
+        .filter_map(|item| {
+            let buyer = store.get(to, item.item_id)?;
+            (buyer.demand_price > item.supply_price).then_some(Trade {
+                item_id: item.item_id,
+                units: if item.supply_units > 0 {
+                    item.supply_units.min(u32::MAX as i64) as u32
+                } else {
+                    u32::MAX
+                },
+                buy_price: item.supply_price,
+                sell_price: buyer.demand_price,
+            })
+        })
+        .collect();
+    trades.sort_by_key(|t| (-t.unit_profit(), t.item_id));
+    trades
+}
+
+/// Buys from the candidates in order, as many units of each as are
+/// available, fit in the remaining hold and can be afforded.
+fn fill(candidates: &[Trade], limits: &TradeLimits) -> Load {
+    let (mut space, mut credits) = (limits.capacity, limits.credits);
+    let mut trades = Vec::new();
+    for candidate in candidates {
+        if space == 0 {
+            break;
+        }
+        let affordable = (credits / candidate.buy_price as i64).clamp(0, u32::MAX as i64) as u32;
+        let units = candidate.units.min(space).min(affordable);
+        if units == 0 {
+            continue;
+        }
+        space -= units;
+        credits -= units as i64 * candidate.buy_price as i64;
+        trades.push(Trade {
+            units,
+            ..candidate.clone()
+        });
+    }
+    Load { trades }
+}
+
+/// Most nodes the allocation search visits before settling for the best
+/// load found so far.
+const ALLOCATION_BUDGET: usize = 20_000;
+
+/// Branch and bound over how many units of each candidate to buy, most
+/// profitable per unit first. A branch is abandoned when even a fractional
+/// fill of the remaining items, limited by hold space alone or by credits
+/// alone, can't beat the best load found.
+struct Allocator<'a> {
+    /// Candidates by descending profit per unit.
+    by_unit: &'a [Trade],
+    /// Indexes into by_unit by descending profit per credit.
+    by_credit: Vec<usize>,
+    units: Vec<u32>,
+    best: (i64, Vec<u32>),
+    visited: usize,
+}
+
+impl<'a> Allocator<'a> {
+    fn new(by_unit: &'a [Trade]) -> Self {
+        let mut by_credit: Vec<usize> = (0..by_unit.len()).collect();
+        let ratio = |i: &usize| by_unit[*i].unit_profit() as f64 / by_unit[*i].buy_price as f64;
+        by_credit.sort_by(|a, b| ratio(b).total_cmp(&ratio(a)));
+        Self {
+            by_unit,
+            by_credit,
+            units: vec![0; by_unit.len()],
+            best: (0, vec![0; by_unit.len()]),
+            visited: 0,
+        }
+    }
+
+    /// Upper bound on the profit from candidates `from` onwards.
+    fn bound(&self, from: usize, space: u32, credits: i64) -> f64 {
+        let mut by_space = 0.;
+        let mut left = space as f64;
+        for trade in &self.by_unit[from..] {
+            let units = (trade.units as f64).min(left);
+            by_space += units * trade.unit_profit() as f64;
+            left -= units;
+            if left <= 0. {
+                break;
+            }
+        }
+        let mut by_credits = 0.;
+        let mut left = credits as f64;
+        for trade in self
+            .by_credit
+            .iter()
+            .filter(|i| **i >= from)
+            .map(|i| &self.by_unit[*i])
+        {
+            let units = (trade.units as f64).min(left / trade.buy_price as f64);
+            by_credits += units * trade.unit_profit() as f64;
+            left -= units * trade.buy_price as f64;
+            if left <= 0. {
+                break;
+            }
+        }
+        by_space.min(by_credits)
+    }
+
+    fn search(&mut self, idx: usize, space: u32, credits: i64, profit: i64) {
+        self.visited += 1;
+        if profit > self.best.0 {
+            self.best = (profit, self.units.clone());
+        }
+        if idx == self.by_unit.len() || space == 0 || self.visited > ALLOCATION_BUDGET {
+            return;
+        }
+        if profit as f64 + self.bound(idx, space, credits) <= self.best.0 as f64 {
+            return;
+        }
+        let trade = &self.by_unit[idx];
+        let affordable = (credits / trade.buy_price as i64).clamp(0, u32::MAX as i64) as u32;
+        let most = trade.units.min(space).min(affordable);
+        for units in (0..=most).rev() {
+            self.units[idx] = units;
+            self.search(
+                idx + 1,
+                space - units,
+                credits - units as i64 * trade.buy_price as i64,
+                profit + units as i64 * trade.unit_profit(),
+            );
+            if self.visited > ALLOCATION_BUDGET {
+                break;
+            }
+        }
+        self.units[idx] = 0;
+    }
+}
+
+/// The most profitable load for the leg from `from` to `to`, mixing items
+/// when the best one can't fill the hold or would use up the credits.
+///
+/// Filling the hold with the highest profit per unit first is optimal while
+/// credits aren't the limit; when they are, a search over the mix of items
+/// improves on it (within a fixed budget, so very large markets get a good
+/// rather than a guaranteed best load).
+pub fn best_load(
+    store: &MarketStore,
+    from: StationId,
+    to: StationId,
+    limits: &TradeLimits,
+) -> Load {
+    let candidates = candidates(store, from, to);
+    let greedy = fill(&candidates, limits);
+    let mut allocator = Allocator::new(&candidates);
+    allocator.best = (greedy.profit(), Vec::new());
+    allocator.search(0, limits.capacity, limits.credits, 0);
+    let (profit, units) = allocator.best;
+    if units.is_empty() || profit <= greedy.profit() {
+        return greedy;
+    }
+    let trades = candidates
+        .iter()
+        .zip(units)
+        .filter(|(_, units)| *units > 0)
+        .map(|(candidate, units)| Trade {
+            units,
+            ..candidate.clone()
+        })
+        .collect();
+    Load { trades }
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    /// The store generation the entries were computed against.
+    generation: u64,
+    loads: HashMap<(StationId, StationId, TradeLimits), Load>,
+}
+
+/// Memoized best loads keyed by (from, to, constraints), so re-running
+/// a search with one parameter tweaked only evaluates the legs it hasn't
+/// seen. Entries are dropped as soon as the store they came from changes.
+#[derive(Default)]
+pub struct LoadCache {
+    entries: RwLock<CacheEntries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl LoadCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// best_load, served from the cache when possible.
+    pub fn best_load(
+        &self,
+        store: &MarketStore,
+        from: StationId,
+        to: StationId,
+        limits: &TradeLimits,
+    ) -> Load {
+        let key = (from, to, *limits);
+        {
+            let entries = self.entries.read().unwrap();
+            if entries.generation == store.generation() {
+                if let Some(load) = entries.loads.get(&key) {
+                    self.hits.fetch_add(1, Ordering::Relaxed);
+                    metrics::add(Counter::CacheHits, 1);
+                    return load.clone();
+                }
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        metrics::add(Counter::CacheMisses, 1);
+        let load = best_load(store, from, to, limits);
+        let mut entries = self.entries.write().unwrap();
+        if entries.generation != store.generation() {
+            debug!(
+                dropped = entries.loads.len(),
+                "market store changed, invalidating load cache"
+            );
+            entries.loads.clear();
+            entries.generation = store.generation();
+        }
+        entries.loads.insert(key, load.clone());
+        load
+    }
+
+    /// (hits, misses, entries held).
+    pub fn stats(&self) -> (u64, u64, usize) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+            self.entries.read().unwrap().loads.len(),
+        )
+    }
+
+    pub fn clear(&self) {
+        self.entries.write().unwrap().loads.clear();
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A closed circuit of stations, each leg carrying its best load.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TradeLoop {
+    /// Stations in visiting order; the last leg returns to the first.
+    pub stations: Vec<StationId>,
+    /// loads[i] is carried from stations[i] to the next station.
+    pub loads: Vec<Load>,
+    pub profit: i64,
+}
+
+impl TradeLoop {
+    pub fn profit_per_leg(&self) -> f64 {
+        self.profit as f64 / self.loads.len() as f64
+    }
+}
+
+/// Parameters of a loop search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoopSearch {
+    /// Furthest apart in ly two consecutive stations may be.
+    pub max_distance: f64,
+    /// Most legs in a loop; 2 is a simple A -> B -> A run.
+    pub max_legs: usize,
+    pub limits: TradeLimits,
+    /// How many loops to return.
+    pub limit: usize,
+}
+
+/// Finds the most profitable loops, best profit per leg first, where every
+/// leg makes a profit. Each loop is reported once, starting from its lowest
+/// station id. Leg loads are looked up through the cache.
+#[tracing::instrument(skip(store, cache))]
+pub fn find_loops(store: &MarketStore, search: &LoopSearch, cache: &LoadCache) -> Vec<TradeLoop> {
+    let _timer = metrics::time_stage("find_loops");
+    let mut stations: Vec<(StationId, [f64; 3])> = store.station_positions().collect();
+    stations.sort_unstable_by_key(|(station_id, _)| *station_id);
+    let positions: Vec<[f64; 3]> = stations.iter().map(|(_, pos)| *pos).collect();
+    let grid = GridIndex::new(&positions);
+
+    // The profitable legs out of each station, as (node, load).
+    let legs: Vec<Vec<(u32, Load)>> = (0..stations.len())
+        .into_par_iter()
+        .map(|node| {
+            let from = stations[node].0;
+            let mut out: Vec<(u32, Load)> = grid
+                .within(positions[node], search.max_distance)
+                .into_iter()
+                .filter(|(target, _)| *target as usize != node)
+                .filter_map(|(target, _)| {
+                    let to = stations[target as usize].0;
+                    let load = cache.best_load(store, from, to, &search.limits);
+                    (!load.is_empty()).then_some((target, load))
+                })
+                .collect();
+            out.sort_unstable_by_key(|(target, _)| *target);
+            out
+        })
+        .collect();
+    let edge_count: usize = legs.iter().map(Vec::len).sum();
+    info!(
+        stations = stations.len(),
+        legs = edge_count,
+        "found profitable legs"
+    );
+
+    // each thread keeps its best `limit` loops, so the loops found are
+    // never all held at once
+    let by_profit = |a: &TradeLoop, b: &TradeLoop| {
+        b.profit_per_leg()
+            .total_cmp(&a.profit_per_leg())
+            .then_with(|| a.stations.cmp(&b.stations))
+    };
+    (0..stations.len() as u32)
+        .into_par_iter()
+        .flat_map_iter(|start| {
+            let mut found = Vec::new();
+            let mut path = vec![start];
+            let mut loads: Vec<&Load> = Vec::new();
+            extend_loops(&legs, search.max_legs, &mut path, &mut loads, &mut found);
+            found
+        })
+        .map(|(path, loads)| TradeLoop {
+            stations: path.iter().map(|node| stations[*node as usize].0).collect(),
+            profit: loads.iter().map(Load::profit).sum(),
+            loads,
+        })
+        .fold(
+            || TopN::new(search.limit, by_profit),
+            |mut top, found| {
+                top.push(found);
+                top
+            },
+        )
+        .reduce(|| TopN::new(search.limit, by_profit), TopN::merge)
+        .into_sorted_vec()
+}
+
+type Found = Vec<(Vec<u32>, Vec<Load>)>;
+
+/// Depth-first search for simple cycles back to path[0] that only pass
+/// through nodes numbered above it, so each cycle is found from one start.
+fn extend_loops<'a>(
+    legs: &'a [Vec<(u32, Load)>],
+    max_legs: usize,
+    path: &mut Vec<u32>,
+    loads: &mut Vec<&'a Load>,
+    found: &mut Found,
+) {
+    let (start, at) = (path[0], *path.last().unwrap());
+    for (next, load) in legs[at as usize].iter() {
+        if *next == start && path.len() >= 2 {
+            let mut cycle: Vec<Load> = loads.iter().map(|l| (*l).clone()).collect();
+            cycle.push(load.clone());
+            found.push((path.clone(), cycle));
+        } else if *next > start && path.len() < max_legs && !path.contains(next) {
+            path.push(*next);
+            loads.push(load);
+            extend_loops(legs, max_legs, path, loads, found);
+            loads.pop();
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::StationItem;
+
+    fn listing(
+        station_id: u32,
+        item_id: u32,
+        demand_price: i32,
+        supply_price: i32,
+        supply_units: i64,
+    ) -> StationItem {
+        StationItem {
+            station_id: StationId(station_id),
+            item_id: ItemId(item_id),
+            demand_price,
+            supply_price,
+            supply_units,
+            ..Default::default()
+        }
+    }
+
+    fn limits() -> TradeLimits {
+        TradeLimits {
+            capacity: 100,
+            credits: 1_000_000,
+        }
+    }
+
+    /// Three stations in a triangle; 1 and 2 trade both ways, 3 buys from 2
+    /// and sells to 1.
+    fn sample_store() -> MarketStore {
+        let mut store = MarketStore::new();
+        store.add_station(StationId(1), "A", 0., 0., 0.);
+        store.add_station(StationId(2), "B", 10., 0., 0.);
+        store.add_station(StationId(3), "C", 5., 8., 0.);
+        store.add_station(StationId(4), "Far", 500., 0., 0.);
+        // item 10: cheap at 1, dear at 2
+        store.insert(listing(1, 10, 0, 100, 1000));
+        store.insert(listing(2, 10, 300, 0, 0));
+        // item 20: cheap at 2, dear at 1
+        store.insert(listing(2, 20, 0, 50, 1000));
+        store.insert(listing(1, 20, 120, 0, 0));
+        // item 30: cheap at 2, very dear at 3; item 40: cheap at 3, dear at 1
+        store.insert(listing(3, 30, 500, 0, 0));
+        store.insert(listing(2, 30, 0, 100, 1000));
+        store.insert(listing(3, 40, 0, 10, 1000));
+        store.insert(listing(1, 40, 400, 0, 0));
+        // the far station pays a fortune but is out of range
+        store.insert(listing(4, 10, 5000, 0, 0));
+        store
+    }
+
+    #[test]
+    fn test_best_load() {
+        let store = sample_store();
+        let load = best_load(&store, StationId(1), StationId(2), &limits());
+        assert_eq!(load.trades.len(), 1);
+        assert_eq!(load.trades[0].item_id, ItemId(10));
+        assert_eq!(load.trades[0].units, 100);
+        assert_eq!(load.profit(), 100 * 200);
+
+        // credits limit how much can be bought
+        let poor = TradeLimits {
+            capacity: 100,
+            credits: 2_550,
+        };
+        assert_eq!(
+            best_load(&store, StationId(1), StationId(2), &poor).trades[0].units,
+            25
+        );
+        assert!(best_load(&store, StationId(1), StationId(1), &limits()).is_empty());
+        assert!(best_load(&store, StationId(3), StationId(2), &limits()).is_empty());
+    }
+
+    #[test]
+    fn test_fill_without_credits() {
+        // negative credits buy nothing rather than wrapping around
+        let store = sample_store();
+        let broke = TradeLimits {
+            capacity: 100,
+            credits: -1_000,
+        };
+        let candidates = candidates(&store, StationId(1), StationId(2));
+        assert!(fill(&candidates, &broke).is_empty());
+        assert!(best_load(&store, StationId(1), StationId(2), &broke).is_empty());
+    }
+
+    #[test]
+    fn test_best_load_respects_supply() {
+        let mut store = sample_store();
+        store.insert(listing(1, 10, 0, 100, 10));
+        store.insert(listing(1, 50, 0, 100, 1000));
+        store.insert(listing(2, 50, 250, 0, 0));
+        // only 10 units of item 10 at +200, so the rest of the hold takes
+        // item 50 at +150
+        let load = best_load(&store, StationId(1), StationId(2), &limits());
+        let items: Vec<(u32, u32)> = load.trades.iter().map(|t| (t.item_id.0, t.units)).collect();
+        assert_eq!(items, vec![(10, 10), (50, 90)]);
+        assert_eq!(load.profit(), 15_500);
+
+        // supply beyond u32 (a fleet carrier) is plenty, not wrapped around
+        store.insert(listing(1, 10, 0, 100, 1 << 32));
+        assert_eq!(
+            best_load(&store, StationId(1), StationId(2), &limits()).trades[0].units,
+            100
+        );
+    }
+
+    #[test]
+    fn test_best_load_mixes_within_credits() {
+        let mut store = MarketStore::new();
+        store.add_station(StationId(1), "A", 0., 0., 0.);
+        store.add_station(StationId(2), "B", 10., 0., 0.);
+        // item 1: +1000 per unit but costs 10000; item 2: +500 for 1000
+        store.insert(listing(1, 1, 0, 10_000, 0));
+        store.insert(listing(2, 1, 11_000, 0, 0));
+        store.insert(listing(1, 2, 0, 1_000, 0));
+        store.insert(listing(2, 2, 1_500, 0, 0));
+        store.insert(listing(1, 3, 0, 10, 5));
+        store.insert(listing(2, 3, 20, 0, 0));
+
+        // plenty of credits: fill the hold with the best item
+        let rich = TradeLimits {
+            capacity: 10,
+            credits: 1_000_000,
+        };
+        assert_eq!(
+            best_load(&store, StationId(1), StationId(2), &rich).profit(),
+            10_000
+        );
+
+        // 50000 credits buys 5 of item 1 (+5000) or 10 of item 2 (+5000);
+        // 4 of item 1 and 6 of item 2 make +7000
+        let tight = TradeLimits {
+            capacity: 10,
+            credits: 50_000,
+        };
+        let load = best_load(&store, StationId(1), StationId(2), &tight);
+        let items: Vec<(u32, u32)> = load.trades.iter().map(|t| (t.item_id.0, t.units)).collect();
+        assert_eq!(items, vec![(1, 4), (2, 6)]);
+        assert_eq!(load.profit(), 7_000);
+        assert!(
+            load.trades
+                .iter()
+                .map(|t| t.units as i64 * t.buy_price as i64)
+                .sum::<i64>()
+                <= 50_000
+        );
+        assert!(load.trades.iter().map(|t| t.units).sum::<u32>() <= 10);
+    }
+
+    #[test]
+    fn test_find_loops() {
+        let store = sample_store();
+        let search = LoopSearch {
+            max_distance: 15.,
+            max_legs: 3,
+            limits: limits(),
+            limit: 10,
+        };
+        let cache = LoadCache::new();
+        let loops = find_loops(&store, &search, &cache);
+        let found: Vec<(Vec<u32>, i64)> = loops
+            .iter()
+            .map(|l| (l.stations.iter().map(|s| s.0).collect(), l.profit))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                // 2 -> 3 +40000, 3 -> 1 +39000, 1 -> 2 +20000
+                (vec![1, 2, 3], 99_000),
+                // 1 -> 2 +20000, 2 -> 1 +7000
+                (vec![1, 2], 27_000),
+            ]
+        );
+        assert_eq!(loops[0].loads[0].trades[0].item_id, ItemId(10));
+        assert_eq!(loops[0].profit_per_leg(), 33_000.);
+
+        let pairs_only = LoopSearch {
+            max_legs: 2,
+            ..search.clone()
+        };
+        assert_eq!(find_loops(&store, &pairs_only, &cache).len(), 1);
+        let short = LoopSearch {
+            max_distance: 5.,
+            ..search
+        };
+        assert!(find_loops(&store, &short, &cache).is_empty());
+    }
+
+    #[test]
+    fn test_load_cache() {
+        let mut store = sample_store();
+        let cache = LoadCache::new();
+        let load = cache.best_load(&store, StationId(1), StationId(2), &limits());
+        assert_eq!(
+            cache.best_load(&store, StationId(1), StationId(2), &limits()),
+            load
+        );
+        assert_eq!(cache.stats(), (1, 1, 1));
+
+        // different constraints are a different entry
+        let small = TradeLimits {
+            capacity: 10,
+            ..limits()
+        };
+        assert_eq!(
+            cache
+                .best_load(&store, StationId(1), StationId(2), &small)
+                .profit(),
+            2_000
+        );
+        assert_eq!(cache.stats(), (1, 2, 2));
+
+        // changing the store drops everything
+        store.insert(listing(2, 10, 400, 0, 0));
+        assert_eq!(
+            cache
+                .best_load(&store, StationId(1), StationId(2), &limits())
+                .profit(),
+            30_000
+        );
+        assert_eq!(cache.stats(), (1, 3, 1));
+
+        cache.clear();
+        assert_eq!(cache.stats(), (0, 0, 0));
+    }
+}
@@ -0,0 +1,231 @@
+//! Conversions between the ways positions come to us: galactic coordinates
+//! in ly with Sol at the origin, as EDSM, Spansh and the journal give them;
+//! sector-local coordinates, a sector and the offset from its corner; and
+//! the boxel a system's id64 places it in.
+//!
+//! An id64 packs, from the least significant bit: the mass code (3 bits),
+//! then for z, y and x in turn the boxel's index within its sector (7 -
+//! mass code bits) and the sector's index (7 bits, 6 for y), and above
+//! those n2, the system's number within the boxel. It pins a system down to
+//! its boxel, not to a point, so `id64_position` is the boxel's center and
+//! can be out by half a boxel (5ly to 640ly with the mass code).
+
+use crate::sector::{boxel_size, sector_index, sector_origin, ProceduralSuffix, SectorIndex};
+
+/// Bits of sector index along x, y and z.
+const SECTOR_BITS: [u32; 3] = [7, 6, 7];
+
+/// A position as a sector and the offset in ly from the sector's corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SectorLocal {
+    pub sector: SectorIndex,
+    pub offset: [f64; 3],
+}
+
+pub fn to_sector_local(pos: [f64; 3]) -> SectorLocal {
+    let sector = sector_index(pos[0], pos[1], pos[2]);
+    let origin = sector_origin(sector);
+    SectorLocal {
+        sector,
+        offset: [pos[0] - origin[0], pos[1] - origin[1], pos[2] - origin[2]],
+    }
+}
+
+pub fn from_sector_local(local: &SectorLocal) -> [f64; 3] {
+    let origin = sector_origin(local.sector);
+    [
+        origin[0] + local.offset[0],
+        origin[1] + local.offset[1],
+        origin[2] + local.offset[2],
+    ]
+}
+
+/// Where an id64 says a system is: a boxel of a sector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Id64Location {
+    pub sector: SectorIndex,
+    /// 0 ('a') to 7 ('h').
+    pub mass_code: u8,
+    /// The boxel's index within the sector along x, y and z.
+    pub boxel: [u32; 3],
+    /// The system's number within the boxel.
+    pub n2: u64,
+}
+
+impl Id64Location {
+    /// Decodes an id64. Any 64-bit value decodes to some location; whether
+    /// a system is there is another matter.
+    pub fn from_id64(id64: u64) -> Self {
+        let mut bits = id64;
+        let mut take = |width: u32| {
+            let value = bits & ((1 << width) - 1);
+            bits >>= width;
+            value
+        };
+        let mass_code = take(3) as u8;
+        let boxel_bits = 7 - mass_code as u32;
+        let (mut sector, mut boxel) = ([0; 3], [0; 3]);
+        for axis in [2, 1, 0] {
+            boxel[axis] = take(boxel_bits) as u32;
+            sector[axis] = take(SECTOR_BITS[axis]) as i32;
+        }
+        Self {
+            sector,
+            mass_code,
+            boxel,
+            n2: bits,
+        }
+    }
+
+    /// The location of the boxel containing a position, or None if the
+    /// position is outside the sectors an id64 can name or the mass code is
+    /// past 'h'.
+    pub fn for_position(pos: [f64; 3], mass_code: u8, n2: u64) -> Option<Self> {
+        if mass_code > 7 {
+            return None;
+        }
+        let local = to_sector_local(pos);
+        let size = boxel_size(mass_code);
+        let location = Self {
+            sector: local.sector,
+            mass_code,
+            boxel: local.offset.map(|offset| (offset / size).floor() as u32),
+            n2,
+        };
+        location.to_id64().map(|_| location)
+    }
+
+    /// Encodes the location, or None if a field doesn't fit its bits. n2 is
+    /// not checked: it has whatever bits are left.
+    pub fn to_id64(&self) -> Option<u64> {
+        if self.mass_code > 7 {
+            return None;
+        }
+        let boxel_bits = 7 - self.mass_code as u32;
+        let mut id64 = 0;
+        let mut shift = 3;
+        for axis in [2, 1, 0] {
+            let (boxel, sector) = (self.boxel[axis] as u64, self.sector[axis]);
+            if boxel >> boxel_bits != 0 || sector < 0 || (sector as u64) >> SECTOR_BITS[axis] != 0 {
+                return None;
+            }
+            id64 |= boxel << shift;
+            shift += boxel_bits;
+            id64 |= (sector as u64) << shift;
+            shift += SECTOR_BITS[axis];
+        }
+        Some(id64 | self.mass_code as u64 | self.n2.checked_shl(shift).unwrap_or(0))
+    }
+
+    pub fn boxel_size(&self) -> f64 {
+        boxel_size(self.mass_code)
+    }
+
+    /// The boxel's corner with the lowest coordinates.
+    pub fn boxel_origin(&self) -> [f64; 3] {
+        let origin = sector_origin(self.sector);
+        let size = self.boxel_size();
+        [0, 1, 2].map(|axis| origin[axis] + self.boxel[axis] as f64 * size)
+    }
+
+    pub fn boxel_center(&self) -> [f64; 3] {
+        let half = self.boxel_size() / 2.;
+        self.boxel_origin().map(|corner| corner + half)
+    }
+
+    /// Whether a position is inside the boxel.
+    pub fn contains(&self, pos: [f64; 3]) -> bool {
+        let (origin, size) = (self.boxel_origin(), self.boxel_size());
+        (0..3).all(|axis| (origin[axis]..origin[axis] + size).contains(&pos[axis]))
+    }
+
+    /// The procedural name suffix, e.g. "XQ-L c21-0", the location would
+    /// have; None if n2 doesn't fit the name's number.
+    pub fn suffix(&self) -> Option<ProceduralSuffix> {
+        let [bx, by, bz] = self.boxel.map(u64::from);
+        Some(ProceduralSuffix {
+            mass_code: self.mass_code,
+            boxel: bx + by * 128 + bz * 128 * 128,
+            n2: self.n2.try_into().ok()?,
+        })
+    }
+}
+
+/// The center of the boxel an id64 names: a system's position to within
+/// half a boxel.
+pub fn id64_position(id64: u64) -> [f64; 3] {
+    Id64Location::from_id64(id64).boxel_center()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL: u64 = 10477373803;
+    const SAGITTARIUS_A: u64 = 20578934;
+
+    #[test]
+    fn test_sector_local() {
+        let local = to_sector_local([0.; 3]);
+        assert_eq!(local.sector, [39, 32, 18]);
+        assert_eq!(local.offset, [65., 25., 1065.]);
+        for pos in [
+            [0.; 3],
+            [-42213.8, -3381.4, 65630.2],
+            [25.21875, -20.90625, 25899.96875],
+        ] {
+            let back = from_sector_local(&to_sector_local(pos));
+            assert!((0..3).all(|axis| (back[axis] - pos[axis]).abs() < 1e-9));
+        }
+    }
+
+    #[test]
+    fn test_from_id64() {
+        let sol = Id64Location::from_id64(SOL);
+        assert_eq!(sol.sector, [39, 32, 18]);
+        assert_eq!((sol.mass_code, sol.boxel, sol.n2), (3, [0, 0, 13], 0));
+        assert!(sol.contains([0.; 3]));
+        assert_eq!(sol.boxel_center(), [-25., 15., 15.]);
+
+        let sgr_a = Id64Location::from_id64(SAGITTARIUS_A);
+        assert_eq!((sgr_a.sector, sgr_a.mass_code), ([39, 32, 39], 6));
+        assert!(sgr_a.contains([25.21875, -20.90625, 25899.96875]));
+        let center = id64_position(SAGITTARIUS_A);
+        assert!((center[2] - 25899.97).abs() < 320.);
+    }
+
+    #[test]
+    fn test_to_id64() {
+        for id64 in [SOL, SAGITTARIUS_A, 1 << 60 | 5, u64::MAX >> 9] {
+            assert_eq!(Id64Location::from_id64(id64).to_id64(), Some(id64));
+        }
+        let sol = Id64Location::for_position([0.; 3], 3, 0).unwrap();
+        assert_eq!(sol.to_id64(), Some(SOL));
+        assert_eq!(Id64Location::for_position([0.; 3], 8, 0), None);
+        // beyond the sectors an id64 can name
+        assert_eq!(Id64Location::for_position([0., 50000., 0.], 0, 0), None);
+        assert_eq!(Id64Location::for_position([-60000., 0., 0.], 0, 0), None);
+        let mut bad = Id64Location::from_id64(SOL);
+        bad.boxel[0] = 16;
+        assert_eq!(bad.to_id64(), None);
+    }
+
+    #[test]
+    fn test_suffix() {
+        // a position's boxel agrees with the sector module's reckoning
+        let pos = [-1234.5, 67.25, 8901.75];
+        for mass_code in 0..8 {
+            let location = Id64Location::for_position(pos, mass_code, 21).unwrap();
+            assert!(location.contains(pos));
+            assert_eq!(
+                location.suffix(),
+                Some(ProceduralSuffix::for_position(
+                    pos[0], pos[1], pos[2], mass_code, 21
+                ))
+            );
+        }
+        let mut huge = Id64Location::from_id64(SOL);
+        huge.n2 = u64::MAX;
+        assert_eq!(huge.suffix(), None);
+    }
+}
@@ -0,0 +1,136 @@
+//! An on-disk cache of API responses, so repeated runs don't ask EDSM the
+//! same thing again and work offline while what they were told is still
+//! fresh.
+//!
+//! Each response is a file in the cache directory named by the SHA-256 of
+//! its key (the request's URL, query included). The file's first line is
+//! the unix time it was fetched and the rest is the body as received. An
+//! entry older than the TTL is a miss; `purge` deletes those.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::clock::unix_time;
+use crate::verify::sha256_hex;
+
+/// How long responses stay fresh by default: a day.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const EXTENSION: &str = "response";
+
+#[derive(Clone, Debug)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    /// A cache in `dir`, created if it doesn't exist, keeping responses
+    /// for `ttl`.
+    pub fn new(dir: impl AsRef<Path>, ttl: Duration) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            ttl,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let name = sha256_hex(key.as_bytes());
+        self.dir.join(name).with_extension(EXTENSION)
+    }
+
+    fn is_fresh(&self, fetched: i64, now: i64) -> bool {
+        now.saturating_sub(fetched) < self.ttl.as_secs() as i64
+    }
+
+    /// The response stored for `key`, if there is one younger than the
+    /// TTL. An unreadable entry is a miss.
+    pub fn get(&self, key: &str) -> Option<String> {
+        let now = unix_time()?;
+        let text = fs::read_to_string(self.path(key)).ok()?;
+        let (fetched, body) = text.split_once('\n')?;
+        if !self.is_fresh(fetched.parse().ok()?, now) {
+            debug!(key, "cached response expired");
+            return None;
+        }
+        debug!(key, "using cached response");
+        Some(body.to_string())
+    }
+
+    /// Stores the response for `key`, replacing any older one. The file is
+    /// written whole and then renamed into place, so a reader never sees
+    /// half of it.
+    pub fn put(&self, key: &str, body: &str) -> io::Result<()> {
+        let now = unix_time().unwrap_or(0);
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        writeln!(file, "{}", now)?;
+        file.write_all(body.as_bytes())?;
+        file.persist(self.path(key)).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// Deletes the entries older than the TTL, and any that can't be read,
+    /// and returns how many went.
+    pub fn purge(&self) -> io::Result<usize> {
+        let now = unix_time().unwrap_or(i64::MAX);
+        let mut purged = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let fresh = fs::read_to_string(&path)
+                .ok()
+                .and_then(|text| text.split_once('\n')?.0.parse().ok())
+                .is_some_and(|fetched| self.is_fresh(fetched, now));
+            if !fresh {
+                fs::remove_file(&path)?;
+                purged += 1;
+            }
+        }
+        debug!(purged, "purged response cache");
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ResponseCache::new(dir.path().join("edsm"), DEFAULT_TTL).unwrap();
+        let key = "https://www.edsm.net/api-v1/system?systemName=Sol";
+        assert_eq!(cache.get(key), None);
+        cache.put(key, "{\"name\": \"Sol\"}\n").unwrap();
+        assert_eq!(cache.get(key).as_deref(), Some("{\"name\": \"Sol\"}\n"));
+        assert_eq!(cache.get(&key.to_lowercase()), None);
+        cache.put(key, "[]").unwrap();
+        assert_eq!(cache.get(key).as_deref(), Some("[]"));
+        assert_eq!(cache.purge().unwrap(), 0);
+
+        // the same files through a cache that keeps nothing
+        let expired = ResponseCache::new(cache.dir(), Duration::ZERO).unwrap();
+        assert_eq!(expired.get(key), None);
+        fs::write(cache.dir().join("junk.response"), "not a time").unwrap();
+        fs::write(cache.dir().join("README"), "left alone").unwrap();
+        assert_eq!(expired.purge().unwrap(), 2);
+        assert_eq!(cache.get(key), None);
+        assert!(cache.dir().join("README").exists());
+    }
+}
@@ -0,0 +1,152 @@
+//! Checking downloaded dump files against published SHA-256 hashes before
+//! an import starts on them.
+//!
+//! Files are hashed in parallel, each read front to back with the given
+//! ReadOptions, so a set of multi-gigabyte dumps takes about as long as the
+//! largest of them. Hashes are lowercase hex; expected hashes are compared
+//! ignoring case and surrounding whitespace, as they're often pasted from a
+//! web page or a `.sha256` file.
+
+use std::fmt::Write;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::input::InputReader;
+use crate::metrics;
+use crate::options::{ReadOptions, MIN_BUFFER_SIZE};
+
+/// A file whose contents didn't match the hash expected of it.
+#[derive(Debug)]
+pub struct Mismatch {
+    pub path: PathBuf,
+    pub expected: String,
+    /// The file's actual hash, or why it couldn't be hashed.
+    pub actual: io::Result<String>,
+}
+
+/// The SHA-256 of a file's bytes, in lowercase hex. A byte-order mark is
+/// part of the file as far as this is concerned.
+pub fn sha256_file(path: impl AsRef<Path>, options: &ReadOptions) -> io::Result<String> {
+    let path = path.as_ref();
+    let capacity = options.buffer_size.max(MIN_BUFFER_SIZE);
+    let mut reader = InputReader::open(path, options, capacity)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+    let mut buffer = options.make_buffer();
+    let mut hasher = Sha256::new();
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+/// The SHA-256 of some bytes, in lowercase hex. The caches name their files
+/// by it.
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    to_hex(&Sha256::digest(bytes))
+}
+
+/// Hashes files in parallel, returning their hashes in order.
+pub fn hash_files<P: AsRef<Path> + Sync>(
+    paths: &[P],
+    options: &ReadOptions,
+) -> io::Result<Vec<String>> {
+    paths
+        .par_iter()
+        .map(|path| sha256_file(path, options))
+        .collect()
+}
+
+/// Hashes files in parallel and returns the ones that don't match their
+/// expected hash, or couldn't be read, in the order given.
+#[tracing::instrument(skip_all, fields(files = files.len()))]
+pub fn verify_files<P: AsRef<Path> + Sync>(
+    files: &[(P, &str)],
+    options: &ReadOptions,
+) -> Vec<Mismatch> {
+    let _timer = metrics::time_stage("verify_files");
+    let mismatches: Vec<Mismatch> = files
+        .par_iter()
+        .filter_map(|(path, expected)| {
+            let expected = expected.trim().to_ascii_lowercase();
+            let actual = sha256_file(path, options);
+            if matches!(&actual, Ok(hash) if *hash == expected) {
+                return None;
+            }
+            Some(Mismatch {
+                path: path.as_ref().to_path_buf(),
+                expected,
+                actual,
+            })
+        })
+        .collect();
+    for mismatch in &mismatches {
+        warn!(path = %mismatch.path.display(), "file failed verification");
+    }
+    info!(
+        files = files.len(),
+        mismatches = mismatches.len(),
+        "verified files"
+    );
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const HELLO: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_verify_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (good, bad) = (
+            dir.path().join("good.prices"),
+            dir.path().join("bad.prices"),
+        );
+        fs::write(&good, "hello").unwrap();
+        fs::write(&bad, "\u{feff}hello").unwrap();
+        let missing = dir.path().join("missing.prices");
+        let options = ReadOptions::default();
+        assert_eq!(sha256_file(&good, &options).unwrap(), HELLO);
+
+        let upper = format!(" {}\n", HELLO.to_uppercase());
+        let files = [
+            (good.clone(), upper.as_str()),
+            (bad.clone(), HELLO),
+            (missing.clone(), HELLO),
+        ];
+        let mismatches = verify_files(&files, &options);
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(
+            (
+                mismatches[0].path.as_path(),
+                mismatches[0].expected.as_str()
+            ),
+            (bad.as_path(), HELLO)
+        );
+        assert_ne!(mismatches[0].actual.as_ref().unwrap(), HELLO);
+        let error = mismatches[1].actual.as_ref().unwrap_err();
+        assert!(error.to_string().contains("missing.prices"));
+
+        let hashes = hash_files(&[&good, &good], &options).unwrap();
+        assert_eq!(hashes, [HELLO, HELLO]);
+        assert!(hash_files(&[&good, &missing], &options).is_err());
+    }
+}